@@ -0,0 +1,71 @@
+/*
+ * libstdbuf - set the buffering of the C standard streams, for stdbuf
+ *
+ * stdbuf puts this library in LD_PRELOAD and the modes in _STDBUF_I, _STDBUF_O and
+ * _STDBUF_E before exec'ing the command, the constructor below then runs before the
+ * command's main and calls setvbuf(3) for each stream given: `0` unbuffered, `L` line
+ * buffered, a size fully buffered. Like GNU's library only programs writing through C
+ * stdio are affected, and the environment is the same so either front-end works.
+ */
+
+use libc::{c_char, FILE};
+use std::ffi::CStr;
+use std::ptr;
+
+extern "C" {
+    static mut stdin: *mut FILE;
+    static mut stdout: *mut FILE;
+    static mut stderr: *mut FILE;
+}
+
+fn apply_mode(stream: *mut FILE, name: &str, mode: &CStr) {
+    let (buffering, size) = match mode.to_bytes() {
+        [b'0', ..] => (libc::_IONBF, 0),
+        [b'L', ..] => (libc::_IOLBF, 0),
+        size => match std::str::from_utf8(size).ok().and_then(|s| s.parse().ok()) {
+            Some(size) if size > 0 => (libc::_IOFBF, size),
+            _ => {
+                let mode = mode.to_string_lossy();
+                eprintln!("invalid buffering mode {mode} for {name}");
+                return;
+            }
+        },
+    };
+    // glibc ignores the size without a buffer, which fclose frees like its own
+    let buffer = match size {
+        0 => ptr::null_mut(),
+        size => match unsafe { libc::malloc(size) } {
+            buffer if buffer.is_null() => {
+                eprintln!("failed to allocate a {size} byte stdio buffer");
+                return;
+            }
+            buffer => buffer.cast::<c_char>(),
+        },
+    };
+    if unsafe { libc::setvbuf(stream, buffer, buffering, size) } != 0 {
+        let mode = mode.to_string_lossy();
+        eprintln!("could not set buffering of {name} to mode {mode}");
+        unsafe { libc::free(buffer.cast()) };
+    }
+}
+
+extern "C" fn stdbuf() {
+    let streams = unsafe {
+        [
+            (&b"_STDBUF_E\0"[..], stderr, "stderr"),
+            (&b"_STDBUF_I\0"[..], stdin, "stdin"),
+            (&b"_STDBUF_O\0"[..], stdout, "stdout"),
+        ]
+    };
+    for (variable, stream, name) in streams {
+        let mode = unsafe { libc::getenv(variable.as_ptr().cast()) };
+        if !mode.is_null() {
+            apply_mode(stream, name, unsafe { CStr::from_ptr(mode) });
+        }
+    }
+}
+
+// Called by the dynamic loader once the library is loaded, before the command's main
+#[used]
+#[link_section = ".init_array"]
+static CONSTRUCTOR: extern "C" fn() = stdbuf;
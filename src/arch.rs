@@ -0,0 +1,36 @@
+/*
+ * arch - print machine hardware name
+ *
+ * The same as `uname -m`, through uname's own helpers.
+ */
+
+use crate::uname::{system_name, write_line};
+use crate::{parse_args, quote, reset_sigpipe};
+use clap::Parser;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "arch", version, long_about = None)]
+#[command(about = "Print machine architecture")]
+#[command(override_usage = "arch [OPTION]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("arch: extra operand {extra}\nTry 'arch --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let Some(uts) = system_name("arch") else {
+        return ExitCode::FAILURE;
+    };
+    write_line("arch", uts.machine().as_bytes())
+}
@@ -0,0 +1,13 @@
+/*
+ * b2sum - compute and check BLAKE2b message digests
+ *
+ * All of it is the shared *sum front-end in checksum.rs.
+ */
+
+use crate::checksum::{self, Algorithm};
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    checksum::sum_main("b2sum", Algorithm::Blake2b, args)
+}
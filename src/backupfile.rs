@@ -0,0 +1,136 @@
+/*
+ * Backup file names for --backup, after gnulib's backupfile
+ *
+ * The control comes from --backup=CONTROL or the VERSION_CONTROL environment
+ * variable, and chooses between simple backups (`file~`, the suffix taken from -S
+ * or SIMPLE_BACKUP_SUFFIX), numbered ones (`file.~N~`) or, the default, numbered
+ * backups only for files that already have some.
+ */
+
+use crate::quote;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackupType {
+    None,
+    Simple,
+    Existing,
+    Numbered,
+}
+
+// The accepted controls, synonyms next to each other like argmatch lists them
+const CONTROLS: [(&str, BackupType); 8] = [
+    ("none", BackupType::None),
+    ("off", BackupType::None),
+    ("simple", BackupType::Simple),
+    ("never", BackupType::Simple),
+    ("existing", BackupType::Existing),
+    ("nil", BackupType::Existing),
+    ("numbered", BackupType::Numbered),
+    ("t", BackupType::Numbered),
+];
+
+// argmatch's error, `context` being what the value was given for
+fn invalid(problem: &str, value: &str, context: &str) -> String {
+    let mut message = format!(
+        "{problem} argument {} for {}\nValid arguments are:",
+        quote(value.as_bytes()),
+        quote(context.as_bytes())
+    );
+    let mut previous = None;
+    for (name, kind) in CONTROLS {
+        match previous == Some(kind) {
+            true => message.push_str(&format!(", '{name}'")),
+            false => message.push_str(&format!("\n  - '{name}'")),
+        }
+        previous = Some(kind);
+    }
+    message
+}
+
+// Exact names or unambiguous abbreviations of them, like argmatch
+fn match_control(value: &str, context: &str) -> Result<BackupType, String> {
+    if let Some(&(_, kind)) = CONTROLS.iter().find(|(name, _)| *name == value) {
+        return Ok(kind);
+    }
+    let mut found = None;
+    for (name, kind) in CONTROLS {
+        if !name.starts_with(value) {
+            continue;
+        }
+        match found {
+            Some(other) if other != kind => return Err(invalid("ambiguous", value, context)),
+            _ => found = Some(kind),
+        }
+    }
+    found.ok_or_else(|| invalid("invalid", value, context))
+}
+
+/// The backup type for `--backup=CONTROL`, VERSION_CONTROL without one. The error
+/// is argmatch's message listing the valid controls
+pub fn backup_type(control: Option<&str>) -> Result<BackupType, String> {
+    match control.filter(|control| !control.is_empty()) {
+        Some(control) => match_control(control, "backup type"),
+        None => match env::var("VERSION_CONTROL") {
+            Ok(control) if !control.is_empty() => match_control(&control, "$VERSION_CONTROL"),
+            _ => Ok(BackupType::Existing),
+        },
+    }
+}
+
+/// The suffix of simple backups: `suffix`, else SIMPLE_BACKUP_SUFFIX, unless it
+/// contains a '/'
+pub fn simple_suffix(suffix: Option<&[u8]>) -> Vec<u8> {
+    let from_env = env::var_os("SIMPLE_BACKUP_SUFFIX");
+    let suffix = suffix.or(from_env.as_ref().map(|s| s.as_bytes()));
+    match suffix {
+        Some(suffix) if !suffix.is_empty() && !suffix.contains(&b'/') => suffix.to_vec(),
+        _ => b"~".to_vec(),
+    }
+}
+
+// The highest N of the `base.~N~` files in `dir`, 0 if there are none
+fn highest_backup(dir: &[u8], base: &[u8]) -> io::Result<u64> {
+    let dir = if dir.is_empty() { &b"."[..] } else { dir };
+    let mut highest = 0;
+    for entry in fs::read_dir(OsStr::from_bytes(dir))? {
+        let name = entry?.file_name();
+        let Some(rest) = name.as_bytes().strip_prefix(base) else {
+            continue;
+        };
+        let Some(number) = rest.strip_prefix(b".~").and_then(|r| r.strip_suffix(b"~")) else {
+            continue;
+        };
+        if number.first().is_some_and(|c| (b'1'..=b'9').contains(c))
+            && number.iter().all(u8::is_ascii_digit)
+        {
+            let number = std::str::from_utf8(number)
+                .ok()
+                .and_then(|n| n.parse().ok());
+            highest = highest.max(number.unwrap_or(0));
+        }
+    }
+    Ok(highest)
+}
+
+/// The name to back `file` up as, None when `kind` is BackupType::None
+pub fn backup_name(file: &[u8], kind: BackupType, suffix: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let simple = [file, suffix].concat();
+    let split = file.iter().rposition(|&c| c == b'/').map_or(0, |i| i + 1);
+    let (dir, base) = file.split_at(split);
+    let highest = match kind {
+        BackupType::None => return Ok(None),
+        BackupType::Simple => return Ok(Some(simple)),
+        BackupType::Existing | BackupType::Numbered => highest_backup(dir, base)?,
+    };
+    if kind == BackupType::Existing && highest == 0 {
+        return Ok(Some(simple));
+    }
+    Ok(Some(
+        [file, format!(".~{}~", highest + 1).as_bytes()].concat(),
+    ))
+}
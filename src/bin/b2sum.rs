@@ -0,0 +1,5 @@
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    ratiscat::b2sum::main(std::env::args_os())
+}
@@ -0,0 +1,5 @@
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    ratiscat::chgrp::main(std::env::args_os())
+}
@@ -34,20 +34,36 @@
  * --> color automatically by detected line type (ie. `error`, `warn`, `info`, etc)
  * --> support `cut` like behavior on each line directly to keep ie. timestamps, color, etc
  * --> strict mode, do not gracefully ignore missing files like cat (ie. cat /does/not/exist /etc/hosts)
- * --> escape characters mode (escape_default?)
+ * --> escape characters mode (escape_default?), see -v / --show-nonprinting
  */
 
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use nix::fcntl;
+use nix::errno::Errno;
 use nix::fcntl::PosixFadviseAdvice::POSIX_FADV_SEQUENTIAL;
+use nix::poll::{poll, PollFd, PollFlags};
+use ratiscat::checksum::{self, CheckLine, Digest};
+use ratiscat::strerror;
+use ratiscat::width::line_width;
+use regex::bytes::Regex;
+use std::cell::Cell;
 use std::cmp::min;
-use std::fs::{File, Metadata};
-use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
-use std::os::fd::AsFd;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata, OpenOptions};
+use std::ffi::OsStr;
+use std::fmt;
+use std::hash::Hasher;
+use std::num::NonZeroU64;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsFd, OwnedFd};
 use std::os::linux::fs::MetadataExt;
-use std::os::unix::fs::FileTypeExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
 use std::os::unix::io::AsRawFd;
-use std::process::ExitCode;
+use std::process::{Child, Command, ExitCode, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)] // Read from `Cargo.toml`
@@ -59,30 +75,1921 @@ struct Cli {
     /// Do not gracefully allow errors
     #[clap(long, short, action)]
     strict: bool,
+    /// Number all output lines like `cat -n`, counting on across files
+    #[clap(long, short, action)]
+    number: bool,
+    /// Number nonempty output lines like `cat -b`, overrides -n
+    #[clap(long, short = 'b', action)]
+    number_nonblank: bool,
+    /// Number the lines from N rather than 1, ie. the line --seek-to-line starts at
+    /// (implies -n without -b)
+    #[clap(long, value_name = "N", value_parser = line_number)]
+    line_numbers_from: Option<u64>,
+    /// What -n/-b write before each line: its LINE number, or the BYTEs or CHARacters
+    /// output before it, counting on across files (implies -n without -b)
+    #[clap(long, value_name = "WHAT", value_enum, alias = "prefix-line-count")]
+    gutter: Option<Gutter>,
     /// Unbuffered character writes (implies --no-iocopy)
     #[clap(long, short, action)]
     unbuffered: bool,
+    /// Report the number and sizes of read chunks per file to stderr (implies --no-iocopy)
+    #[clap(long, action)]
+    chunk_size_report: bool,
+    /// Report the display width of the widest line per file to stderr, like `wc -L`:
+    /// wide characters take 2 columns, combining marks none and tabs go to the next
+    /// multiple of 8
+    #[clap(long, action)]
+    max_line_width: bool,
+    /// Replace invalid UTF-8 sequences with U+FFFD (implies --no-iocopy)
+    #[clap(long, action)]
+    utf8_lossy: bool,
+    /// Warn when a file ends in the middle of a multibyte UTF-8 sequence, as truncated
+    /// files do, an error with --strict (implies --no-iocopy)
+    #[clap(long, action)]
+    detect_truncated_utf8: bool,
+    /// Convert every line ending, LF, CRLF or a bare CR, to STYLE (implies --no-iocopy)
+    #[clap(long, value_name = "STYLE", value_enum)]
+    normalize_newlines_to: Option<Newline>,
+    /// Show control characters by name, ie. <NUL>, <TAB>, <ESC> (implies --no-iocopy)
+    #[clap(long, action)]
+    show_control_names: bool,
+    /// Prefix output lines with the lines of PATH in turn, starting over from the first
+    /// once they run out
+    #[clap(long, value_name = "PATH")]
+    line_prefix_from_file: Option<String>,
+    /// Use each --line-prefix-from-file line once, output lines past the last one going
+    /// unprefixed
+    #[clap(long, action, requires = "line_prefix_from_file")]
+    prefix_one_to_one: bool,
+    /// Drop a #! first line from every file but the first, or from ALL of them, to join
+    /// scripts into one
+    #[clap(
+        long,
+        value_name = "WHICH",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "rest"
+    )]
+    strip_shebang: Option<StripShebang>,
+    /// Use ^ and M- notation, except for LFD and TAB, like `cat -v`
+    #[clap(long, short = 'v', action)]
+    show_nonprinting: bool,
+    /// Display TAB characters as ^I like `cat -T`
+    #[clap(long, short = 'T', action)]
+    show_tabs: bool,
+    /// Display $ at the end of each line like `cat -E`, a CRLF ending as ^M$
+    #[clap(long, short = 'E', action)]
+    show_ends: bool,
+    /// Mark the spaces and tabs that would go unseen, those ending lines or ALL of them,
+    /// as · and →
+    #[clap(
+        long,
+        value_name = "WHERE",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "trailing"
+    )]
+    show_invisible: Option<Invisible>,
+    /// The two characters --show-invisible marks a space and a tab with
+    #[clap(
+        long,
+        value_name = "CHARS",
+        value_parser = invisible_marks,
+        requires = "show_invisible"
+    )]
+    invisible_marks: Option<[String; 2]>,
+    /// Substitute $VAR and ${VAR} in each line from the environment, $$ being a literal $
+    #[clap(long, action)]
+    expand_env: bool,
+    /// What an unset variable expands to: EMPTY, the reference kept as is, or an ERROR
+    #[clap(
+        long,
+        value_name = "POLICY",
+        value_enum,
+        default_value = "empty",
+        requires = "expand_env"
+    )]
+    env_default: EnvDefault,
+    /// Sum the numeric values of field N, printed to stderr at the end
+    #[clap(long, value_name = "N", value_parser = field_number)]
+    field_sum: Option<usize>,
+    /// Average the numeric values of field N, printed to stderr at the end
+    #[clap(long, value_name = "N", value_parser = field_number)]
+    field_avg: Option<usize>,
+    /// Minimum numeric value of field N, printed to stderr at the end
+    #[clap(long, value_name = "N", value_parser = field_number)]
+    field_min: Option<usize>,
+    /// Maximum numeric value of field N, printed to stderr at the end
+    #[clap(long, value_name = "N", value_parser = field_number)]
+    field_max: Option<usize>,
+    /// Field delimiter for --field-*, runs of whitespace by default
+    #[clap(long, short, value_name = "DELIM")]
+    delimiter: Option<String>,
+    /// Only output lines matching the regular expression PATTERN
+    #[clap(long, value_name = "PATTERN")]
+    grep: Option<Regex>,
+    /// Print the number of lines matching --grep per file (and a total) instead of the lines
+    #[clap(long, action, requires = "grep")]
+    count_matches: bool,
+    /// Stop reading a file after N lines matching --grep
+    #[clap(long, value_name = "N", requires = "grep")]
+    max_matches: Option<u64>,
+    /// Exit 0 if any line matched --grep, 1 if none did and 2 on errors, like grep
+    #[clap(long, action, requires = "grep")]
+    invert_exit_code: bool,
+    /// Exit 1 when no bytes at all were written to stdout, from the files or counts
+    #[clap(long, action)]
+    fail_if_empty: bool,
+    /// Prefix the lines matching --grep with their file name, the default when there are
+    /// several paths
+    #[clap(long, short = 'H', action, requires = "grep", overrides_with = "no_filename")]
+    with_filename: bool,
+    /// Never prefix the lines matching --grep with their file name
+    #[clap(long, action, requires = "grep", overrides_with = "with_filename")]
+    no_filename: bool,
+    /// Write STRING between the output of consecutive files, files writing nothing are
+    /// skipped. C escapes like \n, \t or \xHH are supported (implies --no-iocopy)
+    #[clap(long, value_name = "STRING", value_parser = c_escapes, allow_hyphen_values = true)]
+    output_delimiter_between_files: Option<Bytes>,
+    /// Read PATH instead of stdin when no paths are given and stdin is a terminal
+    #[clap(long, value_name = "PATH")]
+    stdin_fallback: Option<String>,
+    /// Print every option as KEY=VALUE, with the ones other options imply and the
+    /// defaults, and exit without reading anything
+    #[clap(long, action)]
+    dump_config: bool,
+    /// Coalesce small reads into full buffer writes, writing out a partial buffer once
+    /// the input has been idle for MS milliseconds (100 by default, implies --no-iocopy)
+    #[clap(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100"
+    )]
+    write_combine: Option<u16>,
+    /// Always use the line by line (formatted) path, even without any formatting option
+    #[clap(long, action)]
+    force_formatted: bool,
+    /// When stdout is a regular file, fdatasync it every BYTES bytes written so a crash
+    /// loses at most that much of a long copy (implies --no-iocopy)
+    #[clap(long, value_name = "BYTES")]
+    fsync_interval: Option<NonZeroU64>,
+    /// Stop everything with exit status 3 once the output would exceed BYTES bytes in
+    /// total, the first BYTES being written, against runaway or endless inputs (implies
+    /// --no-iocopy)
+    #[clap(long, value_name = "BYTES")]
+    abort_after_bytes: Option<u64>,
+    /// Verify each file against its digest listed in FILE (`sha256sum` style lines),
+    /// reporting OK or FAILED to stderr. Without paths the listed files are read
+    /// (implies --no-iocopy)
+    #[clap(long, value_name = "FILE")]
+    checksum_verify: Option<String>,
+    /// Only output the first occurrence of each line, wherever (and in whichever file)
+    /// the repeats are. A 64-bit hash of every distinct line is kept, about 16 bytes each
+    #[clap(long, action)]
+    dedupe_global: bool,
+    /// Allow at most N consecutive blank lines, longer runs being cut down to N (0 drops
+    /// every blank line). Runs carry on from one file into the next, like `cat -s`
+    #[clap(long, value_name = "N")]
+    max_consecutive_blank: Option<u64>,
+    /// Suppress repeated empty output lines like `cat -s`, ie. --max-consecutive-blank=1
+    /// (-s being --strict)
+    #[clap(long, action, conflicts_with = "max_consecutive_blank")]
+    squeeze_blank: bool,
+    /// Compare lines ignoring ASCII case for --dedupe-global
+    #[clap(long, action, requires = "dedupe_global")]
+    ignore_case_dedupe: bool,
+    /// What to do with a FIFO that has no writer yet: block until one opens it, fail or
+    /// skip it silently
+    #[clap(long, value_name = "MODE", value_enum, default_value_t = FifoBlock::Wait)]
+    on_fifo_block: FifoBlock,
+    /// Take an advisory flock(2) on each regular file named before reading it, shared or
+    /// exclusive, so writers using locks too (ie. log rotation) aren't read mid-change.
+    /// The lock is released once the file is read
+    #[clap(long, value_name = "MODE", value_enum)]
+    lock_input: Option<LockMode>,
+    /// Fail on a file whose lock is held rather than waiting for it
+    #[clap(long, action, requires = "lock_input")]
+    lock_nonblocking: bool,
+    /// Collapse runs of CHAR anywhere in the output to a single one, like `tr -s`. May be
+    /// repeated for a set of characters, C escapes like \t or \xHH are supported
+    /// (implies --no-iocopy)
+    #[clap(long, value_name = "CHAR", value_parser = squeeze_char)]
+    squeeze_repeats: Vec<u8>,
+    /// Only check line endings: list the lines ending in CRLF as FILE:LINE on stdout and
+    /// exit 1 if there are any
+    #[clap(long, action, conflicts_with = "check_lf")]
+    check_crlf: bool,
+    /// Only check line endings: list the lines ending in a bare LF (in files expected to
+    /// use CRLF) as FILE:LINE on stdout and exit 1 if there are any
+    #[clap(long, action)]
+    check_lf: bool,
+    /// Output the NDJSON input as a single JSON array, each line being parsed and written
+    /// back compactly. Blank lines are skipped, invalid ones too with a warning (an error
+    /// with --strict)
+    #[cfg(feature = "json")]
+    #[clap(long, action)]
+    stream_json_array: bool,
+    /// Output only the value at PATH of each NDJSON line, ie. .msg, .http.status or
+    /// .items[0].id, strings unquoted and anything else as compact JSON
+    #[cfg(feature = "json")]
+    #[clap(
+        long,
+        value_name = "PATH",
+        value_parser = json_path,
+        conflicts_with = "stream_json_array"
+    )]
+    json_field: Option<JsonPath>,
+    /// What a line without --json-field's PATH gives: it's SKIPped, an EMPTY line or an
+    /// ERROR
+    #[cfg(feature = "json")]
+    #[clap(
+        long,
+        value_name = "POLICY",
+        value_enum,
+        default_value = "skip",
+        requires = "json_field"
+    )]
+    json_missing: JsonMissing,
+    /// When stdout is a terminal and the output is taller than it, page it through CMD
+    /// ($PAGER, else less). Shorter output goes to the terminal as usual
+    #[clap(
+        long,
+        value_name = "CMD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    pager: Option<String>,
+    /// Read the clipboard where stdin would be read, with wl-paste under Wayland and
+    /// xclip otherwise
+    #[clap(long, action)]
+    from_clipboard: bool,
+    /// Write the output to the clipboard instead of stdout, with wl-copy under Wayland and
+    /// xclip otherwise
+    #[clap(long, action, conflicts_with = "pager")]
+    to_clipboard: bool,
+    /// Prefix each line the commands rat runs (--pager, the clipboard tools) write to
+    /// stderr with the command, so their messages can be told apart
+    #[clap(long, action)]
+    capture_stderr_of_children: bool,
+    /// Start the output of each file at line N, the lines before it being skipped
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = line_number,
+        conflicts_with = "checksum_verify"
+    )]
+    seek_to_line: Option<u64>,
+    /// Only index the lines of the file given, writing the offset of every 4096th line
+    /// to PATH for --use-line-index
+    #[clap(long, value_name = "PATH", conflicts_with = "use_line_index")]
+    build_line_index: Option<String>,
+    /// Seek through the index in PATH to get to --seek-to-line rather than reading every
+    /// line before it. An index older than the file is ignored with a warning (an error
+    /// with --strict)
+    #[clap(long, value_name = "PATH", requires = "seek_to_line")]
+    use_line_index: Option<String>,
+    /// When writing to stdout fails, ie. the terminal went away, append the rest of the
+    /// output to PATH instead of losing it (implies --no-iocopy)
+    #[clap(long, value_name = "PATH")]
+    output_fd_fallback: Option<String>,
+    /// Show how much of the input was read in the terminal's title while stderr is one,
+    /// the title being restored at the end (implies --no-iocopy)
+    #[clap(long, action)]
+    progress_to_title: bool,
+    /// Report to stderr how each file was copied and why (the syscall io::copy goes for
+    /// given the file types, or rat's own loops), and the bytes per way at the end
+    #[clap(long, action)]
+    path_telemetry: bool,
+    /// Pick the way of copying per file: io::copy where the kernel has a syscall for
+    /// the two ends (copy_file_range, sendfile or splice), rat's own chunks otherwise
+    #[clap(long, action, conflicts_with = "no_iocopy")]
+    auto_pipe_detect: bool,
     /// Optional file paths to read, stdin by default
     paths: Option<Vec<String>>,
 }
 
-// using i32 here since `fcntl::F_GETPIPE_SZ` calls returns the same
-const IO_BUFSIZE: i32 = 1 << 17; // or 2^17 or 131072 (bytes) or 32 pages (4K each usually)
-const NEWLINE_CH: u8 = 10; // 0x0A
-const STDIN_FD: i32 = 0;
-const STDOUT_FD: i32 = 1;
+impl Cli {
+    // Turn on the options others imply
+    fn resolve(&mut self) {
+        if self.unbuffered
+            || self.chunk_size_report
+            || self.utf8_lossy
+            || self.normalize_newlines_to.is_some()
+            || self.detect_truncated_utf8
+            || self.show_control_names
+            || self.output_delimiter_between_files.is_some()
+            || self.write_combine.is_some()
+            || self.checksum_verify.is_some()
+            || !self.squeeze_repeats.is_empty()
+            || self.fsync_interval.is_some()
+            || self.abort_after_bytes.is_some()
+            || self.output_fd_fallback.is_some()
+            || self.progress_to_title
+        {
+            self.no_iocopy = true;
+        }
+        if (self.line_numbers_from.is_some() || self.gutter.is_some()) && !self.number_nonblank {
+            self.number = true;
+        }
+    }
+
+    // --dump-config: every option as KEY=VALUE in the order of --help, one line per value
+    // of those given several times and an empty value for those not given. The options
+    // turned on by others are shown on
+    fn dump(&self, matches: &ArgMatches, output: &mut impl Write) -> io::Result<()> {
+        let implied = [("no_iocopy", self.no_iocopy), ("number", self.number)];
+        for arg in Cli::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            let key = arg.get_long().unwrap_or(id);
+            if let Some((_, on)) = implied.iter().find(|(name, _)| *name == id) {
+                writeln!(output, "{key}={on}")?;
+                continue;
+            }
+            match matches.get_raw(id) {
+                Some(values) if values.len() > 0 => {
+                    for value in values {
+                        writeln!(output, "{key}={}", value.to_string_lossy())?;
+                    }
+                }
+                _ => writeln!(output, "{key}=")?,
+            }
+        }
+        Ok(())
+    }
+
+    // Any of these need the output rewritten (or inspected) line by line
+    fn is_formatted(&self) -> bool {
+        self.force_formatted
+            || self.number
+            || self.number_nonblank
+            || self.expand_env
+            || self.grep.is_some()
+            || self.dedupe_global
+            || self.max_consecutive_blank.is_some()
+            || self.squeeze_blank
+            || self.check_crlf
+            || self.check_lf
+            || self.max_line_width
+            || self.show_invisible.is_some()
+            || self.show_ends
+            || self.show_tabs
+            || self.show_nonprinting
+            || self.strip_shebang.is_some()
+            || self.line_prefix_from_file.is_some()
+            || self.stream_json_array()
+            || self.json_field()
+            || self.field_sum.is_some()
+            || self.field_avg.is_some()
+            || self.field_min.is_some()
+            || self.field_max.is_some()
+    }
+
+    fn stream_json_array(&self) -> bool {
+        #[cfg(feature = "json")]
+        return self.stream_json_array;
+        #[cfg(not(feature = "json"))]
+        false
+    }
+
+    fn json_field(&self) -> bool {
+        #[cfg(feature = "json")]
+        return self.json_field.is_some();
+        #[cfg(not(feature = "json"))]
+        false
+    }
+}
+
+// One step down a --json-field path: an object's member or an array's element
+#[cfg(feature = "json")]
+#[derive(Clone, Debug)]
+enum JsonStep {
+    Key(String),
+    Index(usize),
+}
+
+#[cfg(feature = "json")]
+#[derive(Clone, Debug)]
+struct JsonPath(Vec<JsonStep>);
+
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum JsonMissing {
+    Skip,
+    Empty,
+    Error,
+}
+
+// `.a.b[0]` as its steps, `.` alone being the whole value
+#[cfg(feature = "json")]
+fn json_path(arg: &str) -> Result<JsonPath, String> {
+    let mut steps = Vec::new();
+    let mut rest = arg;
+    if !rest.starts_with(['.', '[']) {
+        return Err(String::from("paths start with '.', ie. .msg"));
+    }
+    while !rest.is_empty() {
+        if let Some(index) = rest.strip_prefix('[') {
+            let (index, tail) = index
+                .split_once(']')
+                .ok_or_else(|| String::from("missing ']'"))?;
+            let index = index
+                .parse()
+                .map_err(|_| format!("invalid array index '{index}'"))?;
+            steps.push(JsonStep::Index(index));
+            rest = tail;
+        } else if let Some(key) = rest.strip_prefix('.') {
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            if end > 0 {
+                steps.push(JsonStep::Key(String::from(&key[..end])));
+            } else if key.starts_with('.') {
+                return Err(String::from("empty key"));
+            }
+            rest = &key[end..];
+        } else {
+            return Err(format!("expected '.' or '[' before '{rest}'"));
+        }
+    }
+    Ok(JsonPath(steps))
+}
+
+fn field_number(arg: &str) -> Result<usize, String> {
+    match arg.parse::<usize>() {
+        Ok(0) => Err(String::from("fields are numbered from 1")),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn line_number(arg: &str) -> Result<u64, String> {
+    match arg.parse::<u64>() {
+        Ok(0) => Err(String::from("lines are numbered from 1")),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Alias so the clap derive takes a `Vec<u8>` as a single value, not a list of `u8`
+type Bytes = Vec<u8>;
+
+// Decode the C escapes \\ \a \b \e \f \n \r \t \v \0 and \xHH
+fn c_escapes(arg: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(arg.len());
+    let mut chars = arg.bytes();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some(b'\\') => b'\\',
+            Some(b'a') => 0x07,
+            Some(b'b') => 0x08,
+            Some(b'e') => 0x1b,
+            Some(b'f') => 0x0c,
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'v') => 0x0b,
+            Some(b'0') => 0,
+            Some(b'x') => {
+                let hex = [chars.next(), chars.next()];
+                let hex: Option<Vec<u8>> = hex.into_iter().collect();
+                std::str::from_utf8(&hex.unwrap_or_default())
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| String::from("\\x needs two hex digits"))?
+            }
+            Some(c) => return Err(format!("unknown escape \\{}", c as char)),
+            None => return Err(String::from("trailing backslash")),
+        };
+        bytes.push(c);
+    }
+    Ok(bytes)
+}
+
+// A single byte for --squeeze-repeats
+fn squeeze_char(arg: &str) -> Result<u8, String> {
+    match c_escapes(arg)?[..] {
+        [c] => Ok(c),
+        _ => Err(String::from("a single byte is expected")),
+    }
+}
+
+// The marks of --invisible-marks
+fn invisible_marks(arg: &str) -> Result<[String; 2], String> {
+    match arg.chars().collect::<Vec<char>>()[..] {
+        [space, tab] => Ok([space.to_string(), tab.to_string()]),
+        _ => Err(String::from("two characters are expected, for a space and a tab")),
+    }
+}
+
+// using i32 here since `fcntl::F_GETPIPE_SZ` calls returns the same
+const IO_BUFSIZE: i32 = 1 << 17; // or 2^17 or 131072 (bytes) or 32 pages (4K each usually)
+const NEWLINE_CH: u8 = 10; // 0x0A
+const STDOUT_FD: i32 = 1;
+// Exit status of --abort-after-bytes, set apart from the usual failures
+const EXIT_ABORTED: u8 = 3;
+// Lines between the offsets of --build-line-index
+const LINE_INDEX_INTERVAL: u64 = 4096;
+// Time between the title updates of --progress-to-title
+const TITLE_INTERVAL: Duration = Duration::from_millis(200);
+
+// TODO: use IsTerminal or something
+extern "C" fn isatty(fd: i32) -> bool {
+    unsafe { libc::isatty(fd) != 0 }
+}
+
+fn is_same_file(imeta: &Metadata, ometa: &Metadata) -> bool {
+    imeta.is_file()
+        && ometa.is_file() // I AM THE OMETA
+        && imeta.st_dev() == ometa.st_dev()
+        && imeta.st_ino() == ometa.st_ino()
+        && imeta.st_size() != 0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Newline {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl Newline {
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            Newline::Lf => b"\n",
+            Newline::Crlf => b"\r\n",
+            Newline::Cr => b"\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Invisible {
+    Trailing,
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum StripShebang {
+    Rest,
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum EnvDefault {
+    Empty,
+    Keep,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FifoBlock {
+    Wait,
+    Error,
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Gutter {
+    #[default]
+    Line,
+    Byte,
+    Char,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+// flock(2) on a regular file for --lock-input, anything else is read unlocked.
+// Ok(false) when --lock-nonblocking found it held
+fn lock_input(input: &File, mode: LockMode, nonblocking: bool) -> io::Result<bool> {
+    if !input.metadata()?.is_file() {
+        return Ok(true);
+    }
+    let arg = match (mode, nonblocking) {
+        (LockMode::Shared, false) => fcntl::FlockArg::LockShared,
+        (LockMode::Shared, true) => fcntl::FlockArg::LockSharedNonblock,
+        (LockMode::Exclusive, false) => fcntl::FlockArg::LockExclusive,
+        (LockMode::Exclusive, true) => fcntl::FlockArg::LockExclusiveNonblock,
+    };
+    match fcntl::flock(input.as_raw_fd(), arg) {
+        Ok(()) => Ok(true),
+        Err(Errno::EWOULDBLOCK) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Whether a FIFO opened with O_NONBLOCK has a writer. tee(2) peeks without consuming
+// anything: it only returns 0 once there is no writer and nothing left to read
+fn fifo_has_writer(input: &File) -> io::Result<bool> {
+    let (scratch_in, scratch_out) = nix::unistd::pipe()?;
+    let flags = fcntl::SpliceFFlags::SPLICE_F_NONBLOCK;
+    let result = fcntl::tee(input.as_raw_fd(), scratch_out, 1, flags);
+    let _ = nix::unistd::close(scratch_in);
+    let _ = nix::unistd::close(scratch_out);
+    match result {
+        Ok(0) => Ok(false),
+        Ok(_) | Err(Errno::EAGAIN) => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug)]
+struct FieldStats {
+    aggregate: Aggregate,
+    field: usize,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+/// State of the formatted (line by line) path, kept across lines and input files
+#[derive(Debug, Default)]
+struct Formatter {
+    delimiter: Option<Vec<u8>>,
+    strict: bool,
+    fields: Vec<FieldStats>,
+    grep: Option<Regex>,
+    count_matches: bool,
+    max_matches: Option<u64>,
+    // Written before each line of the current file with --with-filename, ie. `FILE:`
+    prefix: Option<Vec<u8>>,
+    // --line-prefix-from-file's lines, and how many output lines have been through them
+    line_prefixes: Vec<Vec<u8>>,
+    prefixed: usize,
+    prefix_one_to_one: bool,
+    // Last line number of -n/-b, and whether the next byte output starts a line
+    number: Option<u64>,
+    line_start: bool,
+    // -b: blank lines go unnumbered
+    number_nonblank: bool,
+    // --gutter, and the bytes or characters numbered lines have had so far
+    gutter: Gutter,
+    counted: u64,
+    // Lines matching `grep` in the current file
+    matches: u64,
+    // Written before the first byte of a file's output if earlier files wrote anything
+    between_files: Option<Vec<u8>>,
+    // Bytes written for the current file and for all the previous ones (custom loop only)
+    file_bytes: u64,
+    output_bytes: u64,
+    // Hashes of the lines output so far with --dedupe-global
+    seen: Option<HashSet<u64>>,
+    ignore_case: bool,
+    // --max-consecutive-blank, and the blank lines output in a row so far
+    max_blank: Option<u64>,
+    blank_run: u64,
+    // Bytes of --squeeze-repeats, and the last byte written so runs continue across
+    // reads and files
+    squeeze: Vec<u8>,
+    last_byte: Option<u8>,
+    // --fsync-interval when the output is a regular file, and the bytes written since the
+    // last sync
+    fsync_interval: Option<u64>,
+    unsynced: u64,
+    // Most bytes written over all files before aborting with --abort-after-bytes
+    abort_after: Option<u64>,
+    check_crlf: bool,
+    check_lf: bool,
+    // Lines read from the current file, and those with the line ending checked for
+    lines: u64,
+    // Whether a #! first line of the current file is dropped, with --strip-shebang
+    strip_shebang: bool,
+    bad_endings: Vec<u64>,
+    // Widest line of the current file with --max-line-width
+    max_width: Option<usize>,
+    // JSON values written so far with --stream-json-array, None without it
+    json_values: Option<u64>,
+    #[cfg(feature = "json")]
+    json_field: Option<(JsonPath, JsonMissing)>,
+    pager: Option<Pager>,
+    // --use-line-index, read once for all files
+    line_index: Option<LineIndex>,
+    // --output-fd-fallback, taken once the output went over to it
+    fallback: Option<String>,
+    title: Option<TitleProgress>,
+    // --path-telemetry: bytes and files by way of copying, in the order first taken
+    paths: Option<Vec<(&'static str, u64, u64)>>,
+}
+
+impl Formatter {
+    fn new(args: &Cli) -> Formatter {
+        let aggregates = [
+            (Aggregate::Sum, args.field_sum),
+            (Aggregate::Avg, args.field_avg),
+            (Aggregate::Min, args.field_min),
+            (Aggregate::Max, args.field_max),
+        ];
+        let fields = aggregates
+            .into_iter()
+            .filter_map(|(aggregate, field)| {
+                Some(FieldStats {
+                    aggregate,
+                    field: field?,
+                    count: 0,
+                    sum: 0.0,
+                    min: f64::INFINITY,
+                    max: f64::NEG_INFINITY,
+                })
+            })
+            .collect();
+        Formatter {
+            delimiter: args.delimiter.clone().map(String::into_bytes),
+            strict: args.strict,
+            fields,
+            grep: args.grep.clone(),
+            count_matches: args.count_matches,
+            max_matches: args.max_matches,
+            prefix: None,
+            line_prefixes: Vec::new(),
+            prefixed: 0,
+            prefix_one_to_one: args.prefix_one_to_one,
+            number: (args.number || args.number_nonblank)
+                .then(|| args.line_numbers_from.map_or(0, |from| from - 1)),
+            line_start: true,
+            number_nonblank: args.number_nonblank,
+            gutter: args.gutter.unwrap_or_default(),
+            counted: 0,
+            matches: 0,
+            between_files: args.output_delimiter_between_files.clone(),
+            file_bytes: 0,
+            output_bytes: 0,
+            seen: args.dedupe_global.then(HashSet::new),
+            ignore_case: args.ignore_case_dedupe,
+            max_blank: args.max_consecutive_blank.or(args.squeeze_blank.then_some(1)),
+            blank_run: 0,
+            squeeze: args.squeeze_repeats.clone(),
+            last_byte: None,
+            fsync_interval: None,
+            unsynced: 0,
+            abort_after: args.abort_after_bytes,
+            check_crlf: args.check_crlf,
+            check_lf: args.check_lf,
+            lines: 0,
+            strip_shebang: false,
+            bad_endings: Vec::new(),
+            max_width: args.max_line_width.then_some(0),
+            json_values: args.stream_json_array().then_some(0),
+            #[cfg(feature = "json")]
+            json_field: args
+                .json_field
+                .clone()
+                .map(|path| (path, args.json_missing)),
+            pager: None,
+            line_index: None,
+            fallback: args.output_fd_fallback.clone(),
+            title: None,
+            paths: args.path_telemetry.then(Vec::new),
+        }
+    }
+
+    // Nth (1-based) field of the line, either split by the delimiter or by runs of whitespace
+    fn field<'a>(&self, line: &'a [u8], n: usize) -> Option<&'a [u8]> {
+        match self.delimiter.as_deref() {
+            Some(delim) if !delim.is_empty() => {
+                let mut rest = line;
+                for _ in 1..n {
+                    let at = rest.windows(delim.len()).position(|w| w == delim)?;
+                    rest = &rest[at + delim.len()..];
+                }
+                let end = rest.windows(delim.len()).position(|w| w == delim);
+                Some(&rest[..end.unwrap_or(rest.len())])
+            }
+            _ => line
+                .split(|c| c.is_ascii_whitespace())
+                .filter(|f| !f.is_empty())
+                .nth(n - 1),
+        }
+    }
+
+    // Inspect a line, returns whether it should be output
+    fn line(&mut self, line: &[u8]) -> io::Result<bool> {
+        self.lines += 1;
+        // Check only, nothing is output. A last line without a newline has no ending
+        if self.check_crlf || self.check_lf {
+            let crlf = line.ends_with(b"\r\n");
+            if (self.check_crlf && crlf) || (self.check_lf && !crlf && line.ends_with(b"\n")) {
+                self.bad_endings.push(self.lines);
+            }
+            return Ok(false);
+        }
+        if self.strip_shebang && self.lines == 1 && line.starts_with(b"#!") {
+            return Ok(false);
+        }
+        let full_line = line;
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(max_width) = self.max_width.as_mut() {
+            *max_width = (*max_width).max(line_width(line));
+        }
+        if let Some(grep) = &self.grep {
+            if !grep.is_match(line) {
+                return Ok(false);
+            }
+            self.matches += 1;
+        }
+        for i in 0..self.fields.len() {
+            let n = self.fields[i].field;
+            let Some(field) = self.field(line, n) else {
+                continue;
+            };
+            let value = std::str::from_utf8(field)
+                .ok()
+                .and_then(|f| f.trim().parse::<f64>().ok());
+            let stats = &mut self.fields[i];
+            match value {
+                Some(value) => {
+                    stats.count += 1;
+                    stats.sum += value;
+                    stats.min = stats.min.min(value);
+                    stats.max = stats.max.max(value);
+                }
+                // Gracefully skip anything that isn't a number
+                None if !self.strict => {}
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "field {n} is not a number: {}",
+                            String::from_utf8_lossy(field)
+                        ),
+                    ))
+                }
+            }
+        }
+        let ignore_case = self.ignore_case;
+        if let Some(seen) = &mut self.seen {
+            if !seen.insert(line_hash(line, ignore_case)) {
+                return Ok(false);
+            }
+        }
+        if let Some(max_blank) = self.max_blank {
+            // Like `cat -s`, a line with only a CR isn't blank, nor is the newline
+            // ending the last line of the previous file
+            match self.line_start && full_line == b"\n" {
+                true => self.blank_run += 1,
+                false => self.blank_run = 0,
+            }
+            if self.blank_run > max_blank {
+                return Ok(false);
+            }
+        }
+        Ok(!self.count_matches)
+    }
+
+    // Rewrite an NDJSON line as the next element of the --stream-json-array array, `[`
+    // opening it before the first one. Returns false when the line was dropped
+    #[cfg(feature = "json")]
+    fn json_element(&mut self, file: &str, buffer: &mut Vec<u8>) -> io::Result<bool> {
+        let Some(count) = self.json_values.as_mut() else {
+            return Ok(true);
+        };
+        if buffer.iter().all(u8::is_ascii_whitespace) {
+            buffer.clear();
+            return Ok(false);
+        }
+        let value = match serde_json::from_slice::<serde_json::Value>(buffer) {
+            Ok(value) => value,
+            Err(e) => {
+                let message = format!("line {}: invalid JSON ({e})", self.lines);
+                if self.strict {
+                    return Err(io::Error::new(ErrorKind::InvalidData, message));
+                }
+                eprintln!("rat: {file}: {message}, skipped");
+                buffer.clear();
+                return Ok(false);
+            }
+        };
+        buffer.clear();
+        buffer.push(if *count == 0 { b'[' } else { b',' });
+        serde_json::to_writer(&mut *buffer, &value)?;
+        *count += 1;
+        Ok(true)
+    }
+
+    // Replace an NDJSON line with the value at its --json-field path and a newline.
+    // Returns false when the line was dropped
+    #[cfg(feature = "json")]
+    fn json_field(&mut self, file: &str, buffer: &mut Vec<u8>) -> io::Result<bool> {
+        let Some((path, missing)) = &self.json_field else {
+            return Ok(true);
+        };
+        if buffer.iter().all(u8::is_ascii_whitespace) {
+            buffer.clear();
+            return Ok(false);
+        }
+        let value = match serde_json::from_slice::<serde_json::Value>(buffer) {
+            Ok(value) => value,
+            Err(e) => {
+                let message = format!("line {}: invalid JSON ({e})", self.lines);
+                if self.strict {
+                    return Err(io::Error::new(ErrorKind::InvalidData, message));
+                }
+                eprintln!("rat: {file}: {message}, skipped");
+                buffer.clear();
+                return Ok(false);
+            }
+        };
+        let found = path.0.iter().try_fold(&value, |value, step| match step {
+            JsonStep::Key(key) => value.get(key),
+            JsonStep::Index(index) => value.get(index),
+        });
+        buffer.clear();
+        match (found, missing) {
+            (Some(serde_json::Value::String(string)), _) => {
+                buffer.extend_from_slice(string.as_bytes())
+            }
+            (Some(value), _) => serde_json::to_writer(&mut *buffer, value)?,
+            (None, JsonMissing::Skip) => return Ok(false),
+            (None, JsonMissing::Empty) => {}
+            (None, JsonMissing::Error) => {
+                let message = format!("line {}: no value at the --json-field path", self.lines);
+                return Err(io::Error::new(ErrorKind::InvalidData, message));
+            }
+        }
+        buffer.push(NEWLINE_CH);
+        Ok(true)
+    }
+
+    // Close the --stream-json-array array, `[]` when there were no values at all
+    fn json_end(&self, output: &mut impl Write) -> io::Result<()> {
+        match self.json_values {
+            Some(0) => output.write_all(b"[]\n"),
+            Some(_) => output.write_all(b"]\n"),
+            None => Ok(()),
+        }
+    }
+
+    // Prefix every line starting in `buffer` with its -n number (or --gutter's count), a
+    // line carried on from the previous buffer (or file) having got one already. With -b
+    // a lone newline is left unnumbered, and uncounted
+    fn number(&mut self, buffer: &mut Vec<u8>) {
+        let Some(number) = self.number.as_mut() else {
+            // Still needed to tell blank lines apart
+            if let Some(&last) = buffer.last() {
+                self.line_start = last == NEWLINE_CH;
+            }
+            return;
+        };
+        let input = std::mem::take(buffer);
+        for line in input.split_inclusive(|&c| c == NEWLINE_CH) {
+            if self.line_start && !(self.number_nonblank && line == [NEWLINE_CH]) {
+                *number += 1;
+                let shown = match self.gutter {
+                    Gutter::Line => *number,
+                    Gutter::Byte | Gutter::Char => self.counted,
+                };
+                buffer.extend_from_slice(format!("{shown:>6}\t").as_bytes());
+            }
+            self.counted += match self.gutter {
+                Gutter::Line => 0,
+                Gutter::Byte => line.len() as u64,
+                // UTF-8 continuation bytes aside, so a character split across reads counts once
+                Gutter::Char => line.iter().filter(|&&c| c & 0xc0 != 0x80).count() as u64,
+            };
+            buffer.extend_from_slice(line);
+            self.line_start = line.ends_with(&[NEWLINE_CH]);
+        }
+    }
+
+    // Prefix the line in `buffer` with the next --line-prefix-from-file line, if any
+    fn line_prefix(&mut self, buffer: &mut Vec<u8>) {
+        if self.line_prefixes.is_empty() {
+            return;
+        }
+        let n = self.prefixed;
+        self.prefixed += 1;
+        let prefix = match self.prefix_one_to_one {
+            true => self.line_prefixes.get(n),
+            false => self.line_prefixes.get(n % self.line_prefixes.len()),
+        };
+        if let Some(prefix) = prefix {
+            buffer.splice(..0, prefix.iter().copied());
+        }
+    }
+
+    // Drop the repeats of the --squeeze-repeats bytes
+    fn squeeze(&mut self, buffer: &mut Vec<u8>) {
+        let (squeeze, last) = (&self.squeeze, &mut self.last_byte);
+        buffer.retain(|&c| {
+            let repeat = *last == Some(c) && squeeze.contains(&c);
+            *last = Some(c);
+            !repeat
+        });
+    }
+
+    // Run a write to the output, which is retried once on the --output-fd-fallback file
+    // if it fails. The write must leave `output` as it was when it fails, as BufWriter's
+    // `write_all` and `flush` do with what they couldn't write
+    fn or_fallback(
+        &mut self,
+        output: &mut BufWriter<&File>,
+        mut write: impl FnMut(&mut BufWriter<&File>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let e = match write(output) {
+            // The user quitting the pager isn't a failure to save output from
+            Err(e) if !self.pager.as_ref().is_some_and(Pager::is_running) => e,
+            result => return result,
+        };
+        let Some(path) = self.fallback.take() else {
+            return Err(e);
+        };
+        let fallback = OpenOptions::new().create(true).append(true).open(&path);
+        let fallback =
+            fallback.map_err(|e| io::Error::new(e.kind(), format!("{path}: {}", strerror(&e))))?;
+        nix::unistd::dup2(fallback.as_raw_fd(), output.get_ref().as_raw_fd())?;
+        // stderr likely went with the terminal, not being able to say so is fine
+        let _ = writeln!(
+            io::stderr(),
+            "rat: standard output: {}, writing to {path} instead",
+            strerror(&e)
+        );
+        write(output)
+    }
+
+    // Whether --max-matches was reached, ie. the rest of the file can be skipped
+    fn is_done(&self) -> bool {
+        self.max_matches.is_some_and(|max| self.matches >= max)
+    }
+
+    // Count a file copied by `path` for --path-telemetry, saying why it went that way
+    fn record_path(&mut self, file: &str, path: &'static str, reason: &str, bytes: u64) {
+        let Some(paths) = self.paths.as_mut() else {
+            return;
+        };
+        eprintln!("rat: {file}: {path} ({reason}), {bytes} bytes");
+        match paths.iter_mut().find(|(name, _, _)| *name == path) {
+            Some((_, total, files)) => {
+                *total += bytes;
+                *files += 1;
+            }
+            None => paths.push((path, bytes, 1)),
+        }
+    }
+
+    fn report_paths(&self) {
+        for (path, bytes, files) in self.paths.iter().flatten() {
+            let plural = if *files == 1 { "" } else { "s" };
+            eprintln!("rat: {path}: {bytes} bytes from {files} file{plural}");
+        }
+    }
+
+    fn report(&self) {
+        for stats in &self.fields {
+            let (name, value) = match stats.aggregate {
+                Aggregate::Sum => ("sum", stats.sum),
+                Aggregate::Avg => ("avg", stats.sum / stats.count as f64),
+                Aggregate::Min => ("min", stats.min),
+                Aggregate::Max => ("max", stats.max),
+            };
+            if stats.count == 0 && !matches!(stats.aggregate, Aggregate::Sum) {
+                eprintln!("rat: field {} {name}: n/a", stats.field);
+            } else {
+                eprintln!("rat: field {} {name}: {value}", stats.field);
+            }
+        }
+    }
+}
+
+/// --progress-to-title: the share of the input read so far, or just the bytes when the
+/// size of some input isn't known, in the title of the terminal
+#[derive(Debug)]
+struct TitleProgress {
+    // Size of all the input files
+    size: Option<u64>,
+    // Bytes of the files read before the current one
+    before: u64,
+    updated: Option<Instant>,
+}
+
+impl TitleProgress {
+    // The terminal's title is pushed on its title stack, and popped back on drop
+    fn new(size: Option<u64>) -> TitleProgress {
+        let _ = write!(io::stderr(), "\x1b[22;0t");
+        TitleProgress {
+            size,
+            before: 0,
+            updated: None,
+        }
+    }
+
+    // At most every TITLE_INTERVAL, `read` being the bytes of the current file
+    fn update(&mut self, read: u64) {
+        let now = Instant::now();
+        if self.updated.is_some_and(|updated| now - updated < TITLE_INTERVAL) {
+            return;
+        }
+        self.updated = Some(now);
+        let read = self.before + read;
+        let title = match self.size {
+            Some(size) => {
+                let percent = (read * 100).checked_div(size).unwrap_or(100).min(100);
+                format!("rat: {percent}% ({} of {})", human_size(read), human_size(size))
+            }
+            None => format!("rat: {} read", human_size(read)),
+        };
+        let _ = write!(io::stderr(), "\x1b]0;{title}\x07");
+    }
+
+    fn file_done(&mut self, read: u64) {
+        self.before += read;
+        self.update(0);
+    }
+}
+
+impl Drop for TitleProgress {
+    fn drop(&mut self) {
+        let _ = write!(io::stderr(), "\x1b[23;0t");
+    }
+}
+
+// Bytes in binary units with a decimal, ie. "1.5 MiB"
+fn human_size(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in units {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    match unit {
+        "B" => format!("{bytes} B"),
+        unit => format!("{size:.1} {unit}"),
+    }
+}
+
+/// --abort-after-bytes tripped, which ends the whole run rather than the current file
+#[derive(Debug)]
+struct Aborted(u64);
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "output exceeded {} bytes, aborting", self.0)
+    }
+}
+
+impl std::error::Error for Aborted {}
+
+fn is_aborted(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Aborted>())
+}
+
+/// Stdout for what rat writes besides the files (--count-matches, --check-crlf...), the
+/// bytes counted for --fail-if-empty
+struct Counted<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Key of --dedupe-global, collisions would drop a distinct line which 64 bits make unlikely
+fn line_hash(line: &[u8], ignore_case: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match ignore_case {
+        true => hasher.write(&line.to_ascii_lowercase()),
+        false => hasher.write(line),
+    }
+    hasher.finish()
+}
+
+/// Offsets of every `interval`th line of a file for --seek-to-line, and the size and
+/// mtime the file had when they were taken. Stored as text: a header line then an
+/// offset per line
+#[derive(Debug)]
+struct LineIndex {
+    size: u64,
+    mtime: (i64, i64),
+    interval: u64,
+    // Offset of line `1 + interval * (i + 1)`
+    offsets: Vec<u64>,
+}
+
+impl LineIndex {
+    const MAGIC: &'static str = "rat-line-index 1";
+
+    fn build(input: &mut impl BufRead, meta: &Metadata) -> io::Result<LineIndex> {
+        let mut index = LineIndex {
+            size: meta.st_size(),
+            mtime: (meta.st_mtime(), meta.st_mtime_nsec()),
+            interval: LINE_INDEX_INTERVAL,
+            offsets: Vec::new(),
+        };
+        let (mut offset, mut lines) = (0u64, 0u64);
+        loop {
+            let chunk = input.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            for (i, _) in chunk.iter().enumerate().filter(|(_, &c)| c == NEWLINE_CH) {
+                lines += 1;
+                if lines % index.interval == 0 {
+                    index.offsets.push(offset + i as u64 + 1);
+                }
+            }
+            let n = chunk.len();
+            offset += n as u64;
+            input.consume(n);
+        }
+        Ok(index)
+    }
+
+    fn write(&self, output: &mut impl Write) -> io::Result<()> {
+        let (sec, nsec) = self.mtime;
+        let header = format!("{} {} {sec}.{nsec:09} {}", Self::MAGIC, self.size, self.interval);
+        writeln!(output, "{header}")?;
+        for offset in &self.offsets {
+            writeln!(output, "{offset}")?;
+        }
+        output.flush()
+    }
+
+    fn read(input: impl BufRead) -> io::Result<LineIndex> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "not a line index");
+        let mut lines = input.lines();
+        let header = lines.next().ok_or_else(invalid)??;
+        let fields = header.strip_prefix(Self::MAGIC).ok_or_else(invalid)?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [size, mtime, interval] = fields[..] else {
+            return Err(invalid());
+        };
+        let (sec, nsec) = mtime.split_once('.').ok_or_else(invalid)?;
+        let number = |n: &str| n.parse::<u64>().map_err(|_| invalid());
+        let mut index = LineIndex {
+            size: number(size)?,
+            mtime: (sec.parse().map_err(|_| invalid())?, number(nsec)? as i64),
+            interval: number(interval)?,
+            offsets: Vec::new(),
+        };
+        if index.interval == 0 {
+            return Err(invalid());
+        }
+        for line in lines {
+            index.offsets.push(number(&line?)?);
+        }
+        Ok(index)
+    }
+
+    // Whether the file is still the one indexed, as far as its size and mtime tell
+    fn is_current(&self, meta: &Metadata) -> bool {
+        meta.is_file()
+            && meta.st_size() == self.size
+            && (meta.st_mtime(), meta.st_mtime_nsec()) == self.mtime
+    }
+
+    // Offset and number of the closest indexed line at or before line `n`
+    fn lookup(&self, n: u64) -> (u64, u64) {
+        match (((n - 1) / self.interval) as usize).min(self.offsets.len()) {
+            0 => (0, 1),
+            i => (self.offsets[i - 1], 1 + i as u64 * self.interval),
+        }
+    }
+}
+
+// --build-line-index: index the one file given, returns whether that went well
+fn build_line_index(paths: &[String], index_path: &str) -> bool {
+    let [file] = paths else {
+        eprintln!("rat: --build-line-index takes a single file");
+        return false;
+    };
+    let index = File::open(file).and_then(|input| {
+        let meta = input.metadata()?;
+        if !meta.is_file() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "not a regular file"));
+        }
+        let mut input = BufReader::with_capacity(IO_BUFSIZE as usize, input);
+        LineIndex::build(&mut input, &meta)
+    });
+    let index = match index {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("rat: {file}: {}", strerror(&e));
+            return false;
+        }
+    };
+    let written =
+        File::create(index_path).and_then(|output| index.write(&mut BufWriter::new(output)));
+    if let Err(e) = written {
+        eprintln!("rat: {index_path}: {}", strerror(&e));
+        return false;
+    }
+    true
+}
+
+// Position `input` at the start of line `n` for --seek-to-line, jumping through the index
+// when it's current. Returns the number of lines before it, fewer when the file is shorter
+fn seek_to_line(
+    input: &mut BufReader<File>,
+    n: u64,
+    index: Option<&LineIndex>,
+    file: &str,
+    strict: bool,
+) -> io::Result<u64> {
+    let mut line = 1;
+    if let Some(index) = index {
+        if index.is_current(&input.get_ref().metadata()?) {
+            let offset;
+            (offset, line) = index.lookup(n);
+            input.seek(SeekFrom::Start(offset))?;
+        } else if strict {
+            let message = "line index is out of date";
+            return Err(io::Error::new(ErrorKind::InvalidData, message));
+        } else {
+            eprintln!("rat: {file}: warning: line index is out of date, reading through");
+        }
+    }
+    while line < n {
+        let chunk = input.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        match chunk.iter().position(|&c| c == NEWLINE_CH) {
+            Some(i) => {
+                input.consume(i + 1);
+                line += 1;
+            }
+            None => {
+                let n = chunk.len();
+                input.consume(n);
+            }
+        }
+    }
+    Ok(line - 1)
+}
+
+/// --pager: output is held back while it fits the terminal, the pager is started (and the
+/// output fd pointed at its stdin) once it doesn't
+#[derive(Debug)]
+struct Pager {
+    command: String,
+    rows: usize,
+    columns: usize,
+    // Terminal rows the held output takes, long lines wrapping
+    used: usize,
+    held: Vec<u8>,
+    child: Option<Child>,
+    // --capture-stderr-of-children, and the thread tagging the pager's stderr
+    capture_stderr: bool,
+    stderr: Option<JoinHandle<()>>,
+    // Set once the held output went to the terminal, nothing is held from then on
+    released: bool,
+}
+
+impl Pager {
+    fn new(command: &str, terminal: &File, capture_stderr: bool) -> Pager {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        unsafe { libc::ioctl(terminal.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+        let command = match command {
+            "" => std::env::var("PAGER").unwrap_or_else(|_| String::from("less")),
+            command => command.to_string(),
+        };
+        Pager {
+            command,
+            rows: if size.ws_row > 0 { size.ws_row.into() } else { 24 },
+            columns: if size.ws_col > 0 { size.ws_col.into() } else { 80 },
+            used: 0,
+            held: Vec::new(),
+            child: None,
+            capture_stderr,
+            stderr: None,
+            released: false,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    // Take the lines in `buffer` while they fit the terminal, returns true when they are
+    // to be written instead. Once they overflow the pager starts, the held lines being
+    // moved to `buffer` to go out first
+    fn hold(&mut self, buffer: &mut Vec<u8>, output: &File) -> io::Result<bool> {
+        if self.child.is_some() || self.released {
+            return Ok(true);
+        }
+        for line in buffer.split_inclusive(|&c| c == NEWLINE_CH) {
+            self.used += ((line.len() + self.columns - 1) / self.columns).max(1);
+        }
+        self.held.append(buffer);
+        // Keep the last row for the shell prompt
+        if self.used < self.rows {
+            return Ok(false);
+        }
+        let mut pager = Command::new("sh");
+        pager.arg("-c").arg(&self.command).stdin(Stdio::piped());
+        // Like git, less quits on short output and passes colors through unless told
+        if std::env::var_os("LESS").is_none() {
+            pager.env("LESS", "FRX");
+        }
+        if self.capture_stderr {
+            pager.stderr(Stdio::piped());
+        }
+        let mut child = pager.spawn()?;
+        self.stderr = tag_stderr(&mut child, &self.command);
+        // The pager has the terminal as its stdout, ours now writes to its stdin
+        let stdin = child.stdin.take().unwrap();
+        nix::unistd::dup2(stdin.as_raw_fd(), output.as_raw_fd())?;
+        self.child = Some(child);
+        std::mem::swap(buffer, &mut self.held);
+        Ok(true)
+    }
+
+    // Write whatever is still held to the terminal, ie. the whole output fit in it
+    fn release(&mut self, mut output: &File) -> io::Result<()> {
+        self.released = true;
+        output.write_all(&std::mem::take(&mut self.held))
+    }
+
+    // Close the pager's stdin, by pointing the output fd back at the terminal, and wait
+    // for the user to quit it
+    fn wait(self, output: &File) -> io::Result<()> {
+        if let Some(mut child) = self.child {
+            nix::unistd::dup2(STDOUT_FD, output.as_raw_fd())?;
+            child.wait()?;
+        }
+        if let Some(stderr) = self.stderr {
+            let _ = stderr.join();
+        }
+        Ok(())
+    }
+}
+
+// With --capture-stderr-of-children the child's stderr is a pipe: a thread copies it to
+// rat's stderr, each line prefixed by `rat: NAME: `, until the child closes it
+fn tag_stderr(child: &mut Child, name: &str) -> Option<JoinHandle<()>> {
+    let stderr = child.stderr.take()?;
+    let prefix = format!("rat: {name}: ").into_bytes();
+    let tagger = std::thread::spawn(move || {
+        for line in BufReader::new(stderr).split(NEWLINE_CH) {
+            let Ok(line) = line else {
+                break;
+            };
+            let _ = io::stderr().write_all(&[&prefix[..], &line, b"\n"].concat());
+        }
+    });
+    Some(tagger)
+}
+
+/// --from-clipboard and --to-clipboard, the clipboard tool runs for the whole of rat's
+/// run with a pipe standing in for stdin or stdout
+#[derive(Debug)]
+struct Clipboard {
+    name: &'static str,
+    child: Child,
+    copy: bool,
+    stderr: Option<JoinHandle<()>>,
+}
+
+impl Clipboard {
+    // Start the tool copying to the clipboard what's written to the returned pipe, or
+    // pasting from the clipboard into it
+    fn spawn(copy: bool, capture_stderr: bool) -> io::Result<(Clipboard, File)> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let (name, args): (_, &[&str]) = match (wayland, copy) {
+            (true, true) => ("wl-copy", &[]),
+            (true, false) => ("wl-paste", &["--no-newline"]),
+            (false, true) => ("xclip", &["-selection", "clipboard", "-in"]),
+            (false, false) => ("xclip", &["-selection", "clipboard", "-out"]),
+        };
+        let mut command = Command::new(name);
+        command.args(args);
+        match copy {
+            true => command.stdin(Stdio::piped()),
+            false => command.stdout(Stdio::piped()),
+        };
+        if capture_stderr {
+            command.stderr(Stdio::piped());
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("{name}: {}", strerror(&e))))?;
+        let pipe: OwnedFd = match copy {
+            true => child.stdin.take().unwrap().into(),
+            false => child.stdout.take().unwrap().into(),
+        };
+        let stderr = tag_stderr(&mut child, name);
+        let clipboard = Clipboard {
+            name,
+            child,
+            copy,
+            stderr,
+        };
+        Ok((clipboard, File::from(pipe)))
+    }
 
-// TODO: use IsTerminal or something
-extern "C" fn isatty(fd: i32) -> bool {
-    unsafe { libc::isatty(fd) != 0 }
+    // Wait for the tool, after closing the pipe to wl-copy/xclip by pointing the output fd
+    // back at stdout
+    fn wait(mut self, output: &File) -> io::Result<()> {
+        if self.copy {
+            nix::unistd::dup2(STDOUT_FD, output.as_raw_fd())?;
+        }
+        let status = self.child.wait()?;
+        if let Some(stderr) = self.stderr {
+            let _ = stderr.join();
+        }
+        if !status.success() {
+            let message = format!("{} failed ({status})", self.name);
+            return Err(io::Error::new(ErrorKind::Other, message));
+        }
+        Ok(())
+    }
 }
 
-fn is_same_file(imeta: &Metadata, ometa: &Metadata) -> bool {
-    imeta.is_file()
-        && ometa.is_file() // I AM THE OMETA
-        && imeta.st_dev() == ometa.st_dev()
-        && imeta.st_ino() == ometa.st_ino()
-        && imeta.st_size() != 0
+/// Streaming `String::from_utf8_lossy`, an incomplete sequence at the end of a read
+/// is carried over to the next one
+#[derive(Debug, Default)]
+struct Utf8Lossy {
+    carry: Vec<u8>,
+}
+
+impl Utf8Lossy {
+    const REPLACEMENT: &'static [u8] = "\u{FFFD}".as_bytes();
+
+    // Rewrite `buffer` in place, at `eof` whatever is left over is replaced too
+    fn convert(&mut self, buffer: &mut Vec<u8>, eof: bool) {
+        let mut input = std::mem::take(&mut self.carry);
+        input.append(buffer);
+        let mut rest = &input[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(_) => {
+                    buffer.extend_from_slice(rest);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    buffer.extend_from_slice(valid);
+                    match e.error_len() {
+                        Some(len) => {
+                            buffer.extend_from_slice(Self::REPLACEMENT);
+                            rest = &invalid[len..];
+                        }
+                        // Truncated sequence, it may complete on the next read
+                        None if !eof => {
+                            self.carry.extend_from_slice(invalid);
+                            break;
+                        }
+                        None => {
+                            buffer.extend_from_slice(Self::REPLACEMENT);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Streaming --normalize-newlines-to, a CR ending a read is held until the next one
+/// tells whether it's a CRLF
+#[derive(Debug)]
+struct Newlines {
+    ending: &'static [u8],
+    carry_cr: bool,
+}
+
+impl Newlines {
+    fn new(style: Newline) -> Newlines {
+        Newlines {
+            ending: style.bytes(),
+            carry_cr: false,
+        }
+    }
+
+    // Rewrite `buffer` in place, at `eof` a held CR is a line ending of its own
+    fn convert(&mut self, buffer: &mut Vec<u8>, eof: bool) {
+        let input = std::mem::take(buffer);
+        for c in input {
+            if self.carry_cr {
+                self.carry_cr = false;
+                buffer.extend_from_slice(self.ending);
+                if c == NEWLINE_CH {
+                    continue;
+                }
+            }
+            match c {
+                b'\r' => self.carry_cr = true,
+                NEWLINE_CH => buffer.extend_from_slice(self.ending),
+                c => buffer.push(c),
+            }
+        }
+        if eof && self.carry_cr {
+            self.carry_cr = false;
+            buffer.extend_from_slice(self.ending);
+        }
+    }
+}
+
+// Hand the lines of `buffer` to `write` one by one with `split`, all at once otherwise
+fn write_lines(
+    buffer: &mut Vec<u8>,
+    split: bool,
+    write: &mut impl FnMut(&mut Vec<u8>) -> io::Result<()>,
+) -> io::Result<()> {
+    let lines = buffer.iter().filter(|&&c| c == NEWLINE_CH).count();
+    if !split || lines < 2 {
+        return write(buffer);
+    }
+    let input = std::mem::take(buffer);
+    for line in input.split_inclusive(|&c| c == NEWLINE_CH) {
+        buffer.extend_from_slice(line);
+        write(buffer)?;
+        buffer.clear();
+    }
+    Ok(())
+}
+
+// Replace the LF endings of `buffer` with `ending`
+fn lf_to(buffer: &mut Vec<u8>, ending: &[u8]) {
+    if !buffer.contains(&NEWLINE_CH) {
+        return;
+    }
+    let input = std::mem::take(buffer);
+    for c in input {
+        match c {
+            NEWLINE_CH => buffer.extend_from_slice(ending),
+            c => buffer.push(c),
+        }
+    }
+}
+
+/// The last bytes read, enough to tell whether the input ends in the middle of a
+/// multibyte UTF-8 sequence
+#[derive(Debug, Default)]
+struct Utf8Tail {
+    tail: Vec<u8>,
+}
+
+impl Utf8Tail {
+    // Sequences are 4 bytes at most, a truncated one has 3 of them
+    const MAX: usize = 3;
+
+    fn update(&mut self, bytes: &[u8]) {
+        let keep = Self::MAX.saturating_sub(bytes.len()).min(self.tail.len());
+        self.tail.drain(..self.tail.len() - keep);
+        self.tail
+            .extend_from_slice(&bytes[bytes.len().saturating_sub(Self::MAX)..]);
+    }
+
+    // Length of the incomplete sequence ending the input, 0 if it ends cleanly. Stray
+    // continuation bytes are invalid rather than truncated
+    fn incomplete(&self) -> usize {
+        let Some(lead) = self.tail.iter().rposition(|&c| c & 0xc0 != 0x80) else {
+            return 0;
+        };
+        match std::str::from_utf8(&self.tail[lead..]) {
+            Err(e) if e.valid_up_to() == 0 && e.error_len().is_none() => self.tail.len() - lead,
+            _ => 0,
+        }
+    }
+}
+
+// Names of the control characters 0x00-0x1F, DEL is handled separately
+const CONTROL_NAMES: [&str; 32] = [
+    "NUL", "SOH", "STX", "ETX", "EOT", "ENQ", "ACK", "BEL", "BS", "TAB", "LF", "VT", "FF", "CR",
+    "SO", "SI", "DLE", "DC1", "DC2", "DC3", "DC4", "NAK", "SYN", "ETB", "CAN", "EM", "SUB", "ESC",
+    "FS", "GS", "RS", "US",
+];
+
+// Rewrite control characters in `buffer` as `<NAME>`, newlines still end lines
+fn show_control_names(buffer: &mut Vec<u8>) {
+    if !buffer.iter().any(|&c| (c < 0x20 && c != b'\n') || c == 0x7f) {
+        return;
+    }
+    let input = std::mem::take(buffer);
+    for c in input {
+        let name = match c {
+            b'\n' => None,
+            0..=0x1f => Some(CONTROL_NAMES[c as usize]),
+            0x7f => Some("DEL"),
+            _ => None,
+        };
+        match name {
+            Some(name) => {
+                buffer.push(b'<');
+                buffer.extend_from_slice(name.as_bytes());
+                buffer.push(b'>');
+            }
+            None => buffer.push(c),
+        }
+    }
+}
+
+// GNU cat's -v for one byte: control characters as ^X and DEL as ^?, with M- before
+// the same for bytes with the high bit set. Only a plain tab or newline is left as is
+fn escape_nonprinting(byte: u8, out: &mut Vec<u8>) {
+    if byte >= 0x80 {
+        out.extend_from_slice(b"M-");
+    }
+    match byte & 0x7f {
+        _ if byte == b'\t' || byte == NEWLINE_CH => out.push(byte),
+        0x7f => out.extend_from_slice(b"^?"),
+        c @ 0..=0x1f => out.extend_from_slice(&[b'^', c + 0x40]),
+        c => out.push(c),
+    }
+}
+
+fn show_nonprinting(buffer: &mut Vec<u8>) {
+    if !buffer
+        .iter()
+        .any(|&c| (c < 0x20 && c != b'\t' && c != NEWLINE_CH) || c >= 0x7f)
+    {
+        return;
+    }
+    let input = std::mem::take(buffer);
+    for c in input {
+        escape_nonprinting(c, buffer);
+    }
+}
+
+// Every tab of `buffer` as ^I, bytes being bytes whatever the encoding
+fn show_tabs(buffer: &mut Vec<u8>) {
+    if !buffer.contains(&b'\t') {
+        return;
+    }
+    let input = std::mem::take(buffer);
+    for c in input {
+        match c {
+            b'\t' => buffer.extend_from_slice(b"^I"),
+            c => buffer.push(c),
+        }
+    }
+}
+
+// A $ before every newline of `buffer`, GNU's ^M$ when it ends a CRLF. The lines come
+// whole, so a CR is never left at the end of the previous buffer
+fn show_ends(buffer: &mut Vec<u8>) {
+    if !buffer.contains(&NEWLINE_CH) {
+        return;
+    }
+    let input = std::mem::take(buffer);
+    for line in input.split_inclusive(|&c| c == NEWLINE_CH) {
+        let Some(line) = line.strip_suffix(&[NEWLINE_CH]) else {
+            buffer.extend_from_slice(line);
+            continue;
+        };
+        match line.strip_suffix(b"\r") {
+            Some(line) => {
+                buffer.extend_from_slice(line);
+                buffer.extend_from_slice(b"^M$\n");
+            }
+            None => {
+                buffer.extend_from_slice(line);
+                buffer.extend_from_slice(b"$\n");
+            }
+        }
+    }
+}
+
+// Replace the spaces and tabs of the line in `buffer` with `marks`, only the trailing
+// ones (before the LF or CRLF ending) unless `all`
+fn show_invisible(buffer: &mut Vec<u8>, all: bool, [space, tab]: &[String; 2]) {
+    let line = buffer.strip_suffix(b"\n").unwrap_or(buffer);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let end = line.len();
+    let start = match all {
+        true => 0,
+        false => end - line.iter().rev().take_while(|&&c| c == b' ' || c == b'\t').count(),
+    };
+    if !buffer[start..end].iter().any(|&c| c == b' ' || c == b'\t') {
+        return;
+    }
+    let tail = buffer.split_off(start);
+    for &c in &tail[..end - start] {
+        match c {
+            b' ' => buffer.extend_from_slice(space.as_bytes()),
+            b'\t' => buffer.extend_from_slice(tab.as_bytes()),
+            c => buffer.push(c),
+        }
+    }
+    buffer.extend_from_slice(&tail[end - start..]);
+}
+
+// Replace the $NAME and ${NAME} references of a line with the variables' values, $$ with
+// a $. Any other $ is left alone, as is a ${ without its closing brace
+fn expand_env(buffer: &mut Vec<u8>, unset: EnvDefault) -> io::Result<()> {
+    if !buffer.contains(&b'$') {
+        return Ok(());
+    }
+    let is_name = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_';
+    let line = std::mem::take(buffer);
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let (name, len) = match rest {
+            [b'$', b'$', ..] => (None, 2),
+            [b'$', b'{', tail @ ..] => match tail.iter().position(|&c| c == b'}') {
+                Some(end) if end > 0 && tail[..end].iter().all(is_name) => {
+                    (Some(&tail[..end]), end + 3)
+                }
+                _ => (None, 1),
+            },
+            [b'$', c, ..] if c.is_ascii_alphabetic() || *c == b'_' => {
+                let end = rest[1..].iter().take_while(|c| is_name(c)).count();
+                (Some(&rest[1..=end]), end + 1)
+            }
+            _ => (None, 1),
+        };
+        let Some(name) = name else {
+            buffer.push(rest[0]);
+            i += len;
+            continue;
+        };
+        match std::env::var_os(OsStr::from_bytes(name)) {
+            Some(value) => buffer.extend_from_slice(value.as_bytes()),
+            None => match unset {
+                EnvDefault::Empty => {}
+                EnvDefault::Keep => buffer.extend_from_slice(&rest[..len]),
+                EnvDefault::Error => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("unset variable: {}", String::from_utf8_lossy(name)),
+                    ))
+                }
+            },
+        }
+        i += len;
+    }
+    Ok(())
+}
+
+// Fill `buffer` with up to `limit` bytes, stopping early only at EOF or when some bytes
+// were read and no more arrived within `timeout_ms`
+fn read_combined<R: Read + AsFd>(
+    input: &mut BufReader<R>,
+    buffer: &mut Vec<u8>,
+    limit: usize,
+    timeout_ms: u16,
+) -> io::Result<usize> {
+    let start = buffer.len();
+    while buffer.len() - start < limit {
+        if buffer.len() > start && input.buffer().is_empty() {
+            let fd = input.get_ref().as_fd().as_raw_fd();
+            let mut fds = [PollFd::new(fd, PollFlags::POLLIN)];
+            match poll(&mut fds, timeout_ms.into()) {
+                Ok(0) => break,
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let chunk = match input.fill_buf() {
+            Ok(chunk) => chunk,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if chunk.is_empty() {
+            break;
+        }
+        let n = chunk.len().min(limit - (buffer.len() - start));
+        buffer.extend_from_slice(&chunk[..n]);
+        input.consume(n);
+    }
+    Ok(buffer.len() - start)
+}
+
+/// Buffers allocated once and reused for every input file, `rat *.txt` over thousands of
+/// small files otherwise spends its time allocating and freeing them.
+/// cat uses a single shared buffer to read into and write from, that doesn't seem
+/// possible in rust using safe interfaces (??) so there are 3: 1 in, 1 out, 1 to move data
+/// between. io::copy does some black magic using unstable BorrowedBuf
+/// https://doc.rust-lang.org/src/std/io/copy.rs.html#137-163
+struct BufferPool<'a> {
+    // Reads the file being rat'd, a dup of stdin while there's none
+    input: BufReader<File>,
+    output: BufWriter<&'a File>,
+    // Lines or chunks on their way from input to output, only ever grows
+    buffer: Vec<u8>,
 }
 
 /*
@@ -96,73 +2003,371 @@ fn is_same_file(imeta: &Metadata, ometa: &Metadata) -> bool {
  * https://github.com/rust-lang/rust/issues/58326
  * https://github.com/rust-lang/libs-team/issues/148
  */
-fn simple_rat<R: Read, W: Write>(
+fn simple_rat(
     args: &Cli,
-    input: &mut BufReader<R>,
-    output: &mut BufWriter<W>,
+    file: &str,
+    pool: &mut BufferPool,
+    ibufsize: u64,
+    fmt: &mut Formatter,
     is_tty: bool,
+    mut digest: Option<&mut Digest>,
 ) -> io::Result<u64> {
     // Fully buffered output by default
     let mut _bufch: u8 = 0;
     let unbuffered = args.unbuffered;
+    let formatted = args.is_formatted();
+    let control_names = args.show_control_names;
+    let default_marks = [String::from("·"), String::from("→")];
+    let invisible_marks = args.invisible_marks.as_ref().unwrap_or(&default_marks);
+    let squeeze = !args.squeeze_repeats.is_empty();
+    let combine = args.write_combine.is_some();
+
+    let BufferPool {
+        input,
+        output,
+        buffer,
+    } = pool;
+    if let Some(line) = args.seek_to_line {
+        fmt.lines = seek_to_line(input, line, fmt.line_index.as_ref(), file, args.strict)?;
+    }
+    let mut chunks_why = "--no-iocopy or an option implying it";
+    if !is_tty && !formatted && !args.no_iocopy {
+        // copy_cat equivalent, plus some `splice(2)` goodness for inter-pipe
+        // In rust 1.73 this bug will be fixed: https://github.com/rust-lang/rust/pull/114373
+        let path = match fmt.paths.is_some() || args.auto_pipe_detect {
+            true => kernel_copy(&input.get_ref().metadata()?, &output.get_ref().metadata()?),
+            false => ("", ""),
+        };
+        if args.auto_pipe_detect && path.0 == "read/write" {
+            chunks_why = "--auto-pipe-detect, no end a file or pipe";
+        } else {
+            let copied = io::copy(input, output)?;
+            output.flush()?;
+            fmt.file_bytes += copied;
+            fmt.record_path(file, path.0, path.1, copied);
+            return Ok(copied);
+        }
+    }
 
-    let ibufsize: u64 = input.capacity().try_into().unwrap();
     let mut read = |buffer: &mut Vec<u8>, bufch: u8| -> io::Result<usize> {
-        let mut input = input.take(ibufsize);
         // ie. read up until newline when interactive
         // TODO: unbuffered reads?
         if bufch > 0 {
+            // Not capped by `ibufsize`: a `take` limit would split lines longer than the
+            // buffer in two, and line oriented transforms need to see them whole.
+            // A final line without the trailing newline is returned as is at EOF.
             return input.read_until(bufch, buffer);
         };
-        input.read_to_end(buffer)
+        if let Some(timeout_ms) = args.write_combine {
+            return read_combined(input, buffer, ibufsize as usize, timeout_ms);
+        }
+        // A fresh `Take` every call, so its limit is never exhausted up front:
+        // 0 bytes read can only mean the underlying reader hit EOF
+        input.take(ibufsize).read_to_end(buffer)
     };
 
+    let done = Cell::new(fmt.is_done());
+    // Bytes read from the file so far, for --progress-to-title
+    let progress = Cell::new(0);
     let mut write = |buffer: &mut Vec<u8>| -> io::Result<()> {
+        if let Some(title) = &mut fmt.title {
+            title.update(progress.get());
+        }
         // TODO: how to prepend output ie. timestamps etc:
         // Insert generic functions here for arbitrary formatting?
         //let _prefix = "[TEST] ".as_bytes();
         //output.write(_prefix)?;
+        if formatted {
+            let keep = fmt.line(buffer)?;
+            done.set(fmt.is_done());
+            if !keep {
+                buffer.clear();
+                return Ok(());
+            }
+            if args.expand_env {
+                expand_env(buffer, args.env_default)?;
+            }
+            #[cfg(feature = "json")]
+            if !fmt.json_field(file, buffer)? {
+                return Ok(());
+            }
+            #[cfg(feature = "json")]
+            if !fmt.json_element(file, buffer)? {
+                return Ok(());
+            }
+        }
+        if squeeze {
+            fmt.squeeze(buffer);
+            if buffer.is_empty() {
+                return Ok(());
+            }
+        }
+        if args.show_nonprinting {
+            show_nonprinting(buffer);
+        }
+        if let Some(invisible) = args.show_invisible {
+            show_invisible(buffer, invisible == Invisible::All, invisible_marks);
+        }
+        if args.show_tabs {
+            show_tabs(buffer);
+        }
+        if control_names {
+            show_control_names(buffer);
+        }
+        fmt.line_prefix(buffer);
+        fmt.number(buffer);
+        // The file name goes first, as grep puts it before its -n numbers
+        if let Some(prefix) = &fmt.prefix {
+            buffer.splice(..0, prefix.iter().copied());
+        }
+        // After -b told the blank lines apart
+        if args.show_ends {
+            show_ends(buffer);
+        }
+        // Line endings came in as LF for the line transforms, they go out as asked
+        if let Some(style) = args.normalize_newlines_to.filter(|&style| style != Newline::Lf) {
+            lf_to(buffer, style.bytes());
+        }
+        if fmt.file_bytes == 0 && fmt.output_bytes > 0 {
+            if let Some(between) = &fmt.between_files {
+                buffer.splice(..0, between.iter().copied());
+            }
+        }
+        // Write up to the limit, then fail
+        let aborted = fmt.abort_after.and_then(|limit| {
+            let left = limit - (fmt.output_bytes + fmt.file_bytes);
+            (buffer.len() as u64 > left).then(|| {
+                buffer.truncate(left as usize);
+                Aborted(limit)
+            })
+        });
+        let written = buffer.len() as u64;
+        fmt.file_bytes += written;
+        if let Some(pager) = &mut fmt.pager {
+            if !pager.hold(buffer, output.get_ref())? {
+                // What's held goes out when cli() handles the abort
+                return match aborted {
+                    Some(aborted) => Err(io::Error::new(ErrorKind::Other, aborted)),
+                    None => Ok(()),
+                };
+            }
+        }
         if unbuffered {
             for c in buffer.drain(..) {
-                output.write(&[c])?;
-                output.flush()?;
+                fmt.or_fallback(output, |output| output.write_all(&[c]))?;
+                fmt.or_fallback(output, |output| output.flush())?;
+            }
+        }
+        fmt.or_fallback(output, |output| output.write_all(buffer))?;
+        buffer.clear();
+        if let Some(interval) = fmt.fsync_interval {
+            fmt.unsynced += written;
+            if fmt.unsynced >= interval {
+                fmt.or_fallback(output, |output| output.flush())?;
+                output.get_ref().sync_data()?;
+                fmt.unsynced = 0;
             }
         }
-        output.write_all(buffer.drain(..).as_ref())?;
-        output.flush() // Noop unless we're line buffering?
+        if let Some(aborted) = aborted {
+            fmt.or_fallback(output, |output| output.flush())?;
+            return Err(io::Error::new(ErrorKind::Other, aborted));
+        }
+        if is_tty || combine {
+            // Line buffering when interactive, --write-combine already buffered the reads
+            return fmt.or_fallback(output, |output| output.flush());
+        }
+        Ok(())
     };
 
-    if is_tty {
+    if is_tty || formatted {
         // or format
         _bufch = NEWLINE_CH;
-    } else if !args.no_iocopy {
-        // copy_cat equivalent, plus some `splice(2)` goodness for inter-pipe
-        // In rust 1.73 this bug will be fixed: https://github.com/rust-lang/rust/pull/114373
-        return io::copy(input, output);
     }
 
     // Fallback to custom IO loop for formatting/etc
-    let mut buffer = Vec::with_capacity(ibufsize as usize);
+    let mut lossy = args.utf8_lossy.then(Utf8Lossy::default);
+    // Every ending read as LF, and the lines written one at a time when the transforms
+    // need them whole, as bare CRs can turn a read into several
+    let mut newlines = args.normalize_newlines_to.map(|_| Newlines::new(Newline::Lf));
+    let split = newlines.is_some() && _bufch == NEWLINE_CH;
+    let mut utf8_tail = args.detect_truncated_utf8.then(Utf8Tail::default);
+    buffer.clear();
+    let (mut chunks, mut total, mut smallest, mut largest) = (0u64, 0u64, usize::MAX, 0);
     loop {
-        match read(&mut buffer, _bufch) {
+        // --max-matches reached, no need to read any further unless the digest needs it
+        if done.get() && digest.is_none() {
+            break;
+        }
+        match read(buffer, _bufch) {
             // EOF
-            Ok(0) => break,
+            Ok(0) => {
+                if !done.get() {
+                    if let Some(lossy) = lossy.as_mut() {
+                        lossy.convert(buffer, true);
+                    }
+                    if let Some(newlines) = newlines.as_mut() {
+                        newlines.convert(buffer, true);
+                    }
+                    if !buffer.is_empty() {
+                        write_lines(buffer, split, &mut write)?;
+                    }
+                }
+                break;
+            }
             // Data in the buffer
-            Ok(..) => write(&mut buffer)?,
-            // Raise errors
-            Err(e) => return Err(e),
+            Ok(n) => {
+                chunks += 1;
+                total += n as u64;
+                progress.set(total);
+                smallest = min(smallest, n);
+                largest = largest.max(n);
+                if let Some(digest) = digest.as_mut() {
+                    digest.update(&buffer[buffer.len() - n..]);
+                }
+                if let Some(utf8_tail) = utf8_tail.as_mut() {
+                    utf8_tail.update(&buffer[buffer.len() - n..]);
+                }
+                if done.get() {
+                    buffer.clear();
+                    continue;
+                }
+                if let Some(lossy) = lossy.as_mut() {
+                    lossy.convert(buffer, false);
+                }
+                if let Some(newlines) = newlines.as_mut() {
+                    newlines.convert(buffer, false);
+                }
+                if !buffer.is_empty() {
+                    write_lines(buffer, split, &mut write)?
+                }
+            }
+            // Raise errors, but only after writing out whatever was read before it
+            // (`read_until`/`read_to_end` leave those bytes in the buffer)
+            Err(e) => {
+                if let Some(lossy) = lossy.as_mut() {
+                    lossy.convert(buffer, true);
+                }
+                if let Some(newlines) = newlines.as_mut() {
+                    newlines.convert(buffer, true);
+                }
+                if !buffer.is_empty() && !done.get() {
+                    write_lines(buffer, split, &mut write)?;
+                }
+                fmt.or_fallback(output, |output| output.flush())?;
+                return Err(e);
+            }
+        }
+    }
+    fmt.or_fallback(output, |output| output.flush())?;
+    if let Some(title) = &mut fmt.title {
+        title.file_done(total);
+    }
+    match (is_tty, formatted) {
+        (_, true) => fmt.record_path(file, "lines", "a formatting option", total),
+        (true, false) => fmt.record_path(file, "lines", "stdout is a terminal", total),
+        _ => fmt.record_path(file, "chunks", chunks_why, total),
+    }
+    let incomplete = utf8_tail.map_or(0, |utf8_tail| utf8_tail.incomplete());
+    if incomplete > 0 {
+        let offset = total - incomplete as u64;
+        let message = format!("truncated UTF-8 sequence at byte {offset}");
+        if args.strict {
+            return Err(io::Error::new(ErrorKind::InvalidData, message));
+        }
+        eprintln!("rat: {file}: warning: {message}");
+    }
+    if args.chunk_size_report {
+        if chunks == 0 {
+            smallest = 0;
         }
+        eprintln!(
+            "rat: {file}: {chunks} chunks, {total} bytes (smallest {smallest}, largest {largest}, limit {ibufsize})"
+        );
     }
-    // We don't care about the result here, just needed for `return io::copy` above
+    // Only the `return io::copy` above reports the byte count
     Ok(0)
 }
 
-fn cli(ok: &mut bool, mut args: Cli) -> io::Result<()> {
+// The syscall io::copy tries first between these, the way std's kernel_copy picks it,
+// and why
+fn kernel_copy(input: &Metadata, output: &Metadata) -> (&'static str, &'static str) {
+    let (input, output) = (input.file_type(), output.file_type());
+    if input.is_file() && output.is_file() {
+        ("copy_file_range", "regular files on both ends")
+    } else if input.is_file() || input.is_block_device() {
+        ("sendfile", "input is a regular file or block device")
+    } else if input.is_fifo() || output.is_fifo() {
+        ("splice", "a pipe on either end")
+    } else {
+        ("read/write", "no end a file or pipe")
+    }
+}
+
+// Expected digests of --checksum-verify by file name, later lines win over earlier ones
+fn read_manifest(manifest: &str) -> io::Result<Vec<CheckLine>> {
+    let mut input: Box<dyn BufRead> = match manifest {
+        "-" => Box::new(io::stdin().lock()),
+        _ => Box::new(BufReader::new(File::open(manifest)?)),
+    };
+    let listed = checksum::read_list(&mut input)?;
+    if listed.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "no properly formatted checksum lines found",
+        ));
+    }
+    Ok(listed)
+}
+
+// Report the --checksum-verify result of a file, returns whether it matched
+fn verify(
+    file: &str,
+    manifest: &str,
+    expected: Option<&CheckLine>,
+    digest: Option<Digest>,
+) -> bool {
+    let Some(expected) = expected else {
+        eprintln!("rat: {file}: not listed in {manifest}");
+        return false;
+    };
+    // No digest when the file couldn't be opened or read through
+    let result = match digest.map(Digest::finish) {
+        Some(digest) if digest == expected.digest => "OK",
+        Some(_) => "FAILED",
+        None => "FAILED open or read",
+    };
+    eprintln!("rat: {file}: {result}");
+    result == "OK"
+}
+
+// Returns the number of lines matching --grep over all files
+fn cli(ok: &mut bool, args: Cli) -> io::Result<u64> {
     // lock these standard file descriptors, they are subsequently F_DUPFD_CLOEXEC
     // hence fd=0 -> fd=3 and fd=1 -> fd=4 (this is not a problem)
     // stdio might be re-used throughout the runtime, let's just reference it
-    let ref stdin = File::from(io::stdin().lock().as_fd().try_clone_to_owned()?);
-    let ref stdout = File::from(io::stdout().lock().as_fd().try_clone_to_owned()?);
+    let stdin = &File::from(io::stdin().lock().as_fd().try_clone_to_owned()?);
+    let stdout = &File::from(io::stdout().lock().as_fd().try_clone_to_owned()?);
+    // The clipboard takes the place of stdin and stdout through pipes
+    let mut clipboards = Vec::new();
+    let mut spawn_clipboard = |copy: bool, fd: &File| -> io::Result<bool> {
+        match Clipboard::spawn(copy, args.capture_stderr_of_children) {
+            Ok((clipboard, pipe)) => {
+                nix::unistd::dup2(pipe.as_raw_fd(), fd.as_raw_fd())?;
+                clipboards.push(clipboard);
+                Ok(true)
+            }
+            Err(e) => {
+                eprintln!("rat: clipboard: {e}");
+                Ok(false)
+            }
+        }
+    };
+    if (args.from_clipboard && !spawn_clipboard(false, stdin)?)
+        || (args.to_clipboard && !spawn_clipboard(true, stdout)?)
+    {
+        *ok &= false;
+        return Ok(0);
+    }
 
     let mut obufsize = IO_BUFSIZE;
     let _stdout_meta = stdout.metadata()?;
@@ -171,19 +2376,106 @@ fn cli(ok: &mut bool, mut args: Cli) -> io::Result<()> {
         //fcntl::fcntl(stdout.as_raw_fd(), fcntl::F_SETPIPE_SZ(IO_BUFSIZE))?;
         obufsize = fcntl::fcntl(stdout.as_raw_fd(), fcntl::F_GETPIPE_SZ)?;
     }
+    // Input buffer sizes never exceed obufsize, reads are limited to each file's own
+    let mut pool = BufferPool {
+        input: BufReader::with_capacity(obufsize as usize, stdin.try_clone()?),
+        output: BufWriter::with_capacity(obufsize as usize, stdout),
+        buffer: Vec::new(),
+    };
 
-    if args.unbuffered {
-        args.no_iocopy = true;
+    let mut fmt = Formatter::new(&args);
+    if let Some(command) = args.pager.as_deref().filter(|_| isatty(STDOUT_FD)) {
+        let capture_stderr = args.capture_stderr_of_children;
+        fmt.pager = Some(Pager::new(command, stdout, capture_stderr));
     }
+    // Pipes and terminals have nothing to sync
+    fmt.fsync_interval = args
+        .fsync_interval
+        .filter(|_| _stdout_meta.is_file())
+        .map(NonZeroU64::get);
+
+    let listed = match &args.checksum_verify {
+        Some(manifest) => match read_manifest(manifest) {
+            Ok(listed) => listed,
+            Err(e) => {
+                *ok &= false;
+                eprintln!("rat: {manifest}: {}", strerror(&e));
+                return Ok(0);
+            }
+        },
+        None => Vec::new(),
+    };
+    let expected: HashMap<&[u8], &CheckLine> =
+        listed.iter().map(|l| (l.name.as_slice(), l)).collect();
 
     // Is there a way to use the clap derive for default here?
-    let paths = args
-        .paths
-        .clone()
-        .unwrap_or_else(|| vec![String::from("-")]);
+    let paths = args.paths.clone().unwrap_or_else(|| {
+        // Like `sha256sum -c`, read the files the manifest lists
+        if args.checksum_verify.is_some() {
+            let names = listed.iter().map(|l| String::from_utf8_lossy(&l.name));
+            return names.map(String::from).collect();
+        }
+        // Rather than waiting on someone to type when a script forgot to pipe input
+        match &args.stdin_fallback {
+            Some(fallback) if isatty(stdin.as_raw_fd()) => vec![fallback.clone()],
+            _ => vec![String::from("-")],
+        }
+    });
+    if let Some(index_path) = &args.build_line_index {
+        *ok &= build_line_index(&paths, index_path);
+        return Ok(0);
+    }
+    if let Some(index_path) = &args.use_line_index {
+        match File::open(index_path).and_then(|index| LineIndex::read(BufReader::new(index))) {
+            Ok(index) => fmt.line_index = Some(index),
+            Err(e) => {
+                *ok &= false;
+                eprintln!("rat: {index_path}: {}", strerror(&e));
+                return Ok(0);
+            }
+        }
+    }
+    if let Some(prefixes) = &args.line_prefix_from_file {
+        match std::fs::read(prefixes) {
+            Ok(lines) => {
+                let lines = lines.strip_suffix(b"\n").unwrap_or(&lines);
+                fmt.line_prefixes = lines
+                    .split(|&c| c == NEWLINE_CH)
+                    .map(|line| line.strip_suffix(b"\r").unwrap_or(line).to_vec())
+                    .collect();
+            }
+            Err(e) => {
+                *ok &= false;
+                eprintln!("rat: {prefixes}: {}", strerror(&e));
+                return Ok(0);
+            }
+        }
+    }
+    if args.progress_to_title && isatty(libc::STDERR_FILENO) {
+        // Known when every input is a regular file
+        let sizes = paths.iter().map(|path| {
+            let meta = match path.as_str() {
+                "-" => stdin.metadata(),
+                path => std::fs::metadata(path),
+            };
+            meta.ok().filter(Metadata::is_file).map(|meta| meta.len())
+        });
+        fmt.title = Some(TitleProgress::new(sizes.sum()));
+    }
+    let many_paths = paths.len() > 1;
+    // Like grep, name the files matches come from when there are several
+    let with_filename = args.with_filename || (many_paths && !args.no_filename);
+    let mut counts = Counted {
+        inner: stdout,
+        bytes: 0,
+    };
+    let mut total_matches = 0;
+    let manifest = args.checksum_verify.as_deref();
 
-    for file in paths {
-        let mut is_tty = isatty(STDOUT_FD); // false here allows io::copy to sendfile to interactive stdout (!?)
+    for (n, file) in paths.into_iter().enumerate() {
+        let listed_file = expected.get(file.as_bytes()).copied();
+        // A pager still has the terminal, the clipboard doesn't
+        let mut is_tty = isatty(STDOUT_FD) && !args.to_clipboard; // false here allows io::copy to sendfile to interactive stdout (!?)
         let mut is_stdin = false;
         let mut ibufsize = IO_BUFSIZE;
         let mut filename = file.as_str();
@@ -197,29 +2489,64 @@ fn cli(ok: &mut bool, mut args: Cli) -> io::Result<()> {
 
         // We need handle to be consistent Ok(&File) to match stdin - how else could we do this DRYly?
         // maybe passing `dyn` type or boxing or some other generic-ism?
-        let handle: io::Result<_>;
-        let _fhandle: File;
-        if is_stdin {
-            is_tty |= isatty(STDIN_FD);
-            handle = Ok(stdin);
+        let mut _fhandle: Option<File> = None;
+        let handle: io::Result<_> = if is_stdin {
+            is_tty |= isatty(stdin.as_raw_fd());
+            Ok(stdin)
         } else {
-            let _result = File::open(filename);
+            // Opening a FIFO for reading blocks until there's a writer, unless non-blocking
+            let mut options = OpenOptions::new();
+            options.read(true);
+            if args.on_fifo_block != FifoBlock::Wait {
+                options.custom_flags(libc::O_NONBLOCK);
+            }
+            let _result = options.open(filename);
             if let Err(e) = _result {
                 *ok &= false;
-                //eprintln!("{:#?}", e);
-                match e.kind() {
-                    // Also
-                    // rat: $#t: No such file or directory
-                    // cat: '$#t': No such file or directory
-                    ErrorKind::NotFound => eprintln!("rat: {file}: No such file or directory"),
-                    ErrorKind::PermissionDenied => eprintln!("rat: {file}: Permission denied"),
-                    _ => todo!(),
-                };
+                // Also
+                // rat: $#t: No such file or directory
+                // cat: '$#t': No such file or directory
+                eprintln!("rat: {file}: {}", strerror(&e));
+                if let Some(manifest) = manifest {
+                    verify(&file, manifest, listed_file, None);
+                }
                 continue;
             }
-            _fhandle = _result.unwrap();
-            handle = Ok(&_fhandle)
-        }
+            let opened = _fhandle.insert(_result.unwrap());
+            if args.on_fifo_block != FifoBlock::Wait {
+                let is_fifo = opened.metadata()?.file_type().is_fifo();
+                if is_fifo && !fifo_has_writer(opened)? {
+                    if args.on_fifo_block == FifoBlock::Error {
+                        *ok &= false;
+                        eprintln!("rat: {file}: FIFO has no writer");
+                        if let Some(manifest) = manifest {
+                            verify(&file, manifest, listed_file, None);
+                        }
+                    }
+                    continue;
+                }
+                // Reads block as usual from here
+                let flags = fcntl::fcntl(opened.as_raw_fd(), fcntl::F_GETFL)?;
+                let flags = fcntl::OFlag::from_bits_truncate(flags) - fcntl::OFlag::O_NONBLOCK;
+                fcntl::fcntl(opened.as_raw_fd(), fcntl::F_SETFL(flags))?;
+            }
+            // Closing the file after reading it releases the lock
+            if let Some(mode) = args.lock_input {
+                let locked = lock_input(opened, mode, args.lock_nonblocking);
+                if !matches!(locked, Ok(true)) {
+                    *ok &= false;
+                    match locked {
+                        Err(e) => eprintln!("rat: {file}: cannot lock: {}", strerror(&e)),
+                        _ => eprintln!("rat: {file}: locked by another process"),
+                    }
+                    if let Some(manifest) = manifest {
+                        verify(&file, manifest, listed_file, None);
+                    }
+                    continue;
+                }
+            }
+            Ok(&*opened)
+        };
 
         match handle {
             Ok(input) => {
@@ -229,6 +2556,9 @@ fn cli(ok: &mut bool, mut args: Cli) -> io::Result<()> {
                     if is_same_file(&_input_meta, &_stdout_meta) {
                         *ok &= false;
                         eprintln!("rat: {file}: input file is output file");
+                        if let Some(manifest) = manifest {
+                            verify(&file, manifest, listed_file, None);
+                        }
                         continue;
                     }
                     if _input_meta.file_type().is_fifo() {
@@ -237,38 +2567,154 @@ fn cli(ok: &mut bool, mut args: Cli) -> io::Result<()> {
                 }
                 // Decoupling the buffer sizes causes massive performance hit with pipes
                 ibufsize = min(ibufsize, obufsize);
-                simple_rat(
+                fmt.matches = 0;
+                // As grep names them in its prefixes and counts
+                let name = if is_stdin { "(standard input)" } else { &file };
+                fmt.prefix =
+                    (args.grep.is_some() && with_filename).then(|| format!("{name}:").into_bytes());
+                fmt.lines = 0;
+                fmt.strip_shebang = match args.strip_shebang {
+                    Some(StripShebang::All) => true,
+                    Some(StripShebang::Rest) => n > 0,
+                    None => false,
+                };
+                fmt.max_width = fmt.max_width.map(|_| 0);
+                fmt.bad_endings.clear();
+                fmt.output_bytes += fmt.file_bytes;
+                fmt.file_bytes = 0;
+                let mut digest = listed_file.map(|l| Digest::new(l.algorithm, l.bits));
+                // Lend the opened file to the pooled reader, whatever the previous file left
+                // unread in its buffer being discarded like a fresh BufReader would
+                let idle = _fhandle
+                    .take()
+                    .map(|file| std::mem::replace(pool.input.get_mut(), file));
+                let unread = pool.input.buffer().len();
+                pool.input.consume(unread);
+                let result = simple_rat(
                     &args,
-                    // cat uses a single shared buffer to read into and write from
-                    // that doesn't seem possible in rust using safe interfaces (??)
-                    // So, ultimately we have 3 buffers: 1 in, 1 out, 1 to move data between
-                    // io::copy does some black magic using unstable BorrowedBuf
-                    // https://doc.rust-lang.org/src/std/io/copy.rs.html#137-163
-                    BufReader::with_capacity(ibufsize as usize, input).by_ref(),
-                    BufWriter::with_capacity(obufsize as usize, stdout).by_ref(),
+                    &file,
+                    &mut pool,
+                    ibufsize as u64,
+                    &mut fmt,
                     is_tty,
-                )
-                .unwrap_or_else(|e| {
-                    // TODO: this catches trying to read directories/etc
-                    // slightly different than cat like this:
+                    digest.as_mut(),
+                );
+                // The file is closed here rather than when the next one replaces it
+                if let Some(idle) = idle {
+                    *pool.input.get_mut() = idle;
+                }
+                // Whatever was written before an error goes out before it's reported
+                let _ = pool.output.flush();
+                if result.is_err() {
+                    digest = None;
+                }
+                // The user quit the pager, there's no one left to write to
+                let pager_quit = fmt.pager.as_ref().is_some_and(Pager::is_running)
+                    && matches!(&result, Err(e) if e.kind() == ErrorKind::BrokenPipe);
+                if pager_quit {
+                    return fmt.pager.take().unwrap().wait(stdout).map(|_| total_matches);
+                }
+                // --abort-after-bytes ends the run, output held for the pager still goes out
+                if matches!(&result, Err(e) if is_aborted(e)) {
+                    let e = result.unwrap_err();
+                    eprintln!("rat: {file}: {e}");
+                    if let Some(mut pager) = fmt.pager.take() {
+                        if !pager.is_running() {
+                            pager.release(stdout)?;
+                        }
+                        pager.wait(stdout)?;
+                    }
+                    return Err(e);
+                }
+                result.unwrap_or_else(|e| {
+                    // ie. trying to read directories, like cat:
                     // cat: t: Is a directory
-                    // rat: t: Is a directory (os error 21)
-                    eprintln!("rat: {file}: {}", e);
+                    *ok &= false;
+                    eprintln!("rat: {file}: {}", strerror(&e));
                     42u64 // Why not?
                 });
+                if let Some(manifest) = manifest {
+                    *ok &= verify(&file, manifest, listed_file, digest);
+                }
+                let ending = if args.check_crlf { "CRLF" } else { "LF" };
+                for line in &fmt.bad_endings {
+                    writeln!(counts, "{file}:{line}: {ending} line ending")?;
+                }
+                *ok &= fmt.bad_endings.is_empty();
+                if let Some(max_width) = fmt.max_width {
+                    eprintln!("rat: {file}: max line width {max_width}");
+                }
+                total_matches += fmt.matches;
+                if args.count_matches {
+                    // Like `grep -c`, files are named as with matching lines
+                    match with_filename {
+                        true => writeln!(counts, "{name}:{}", fmt.matches)?,
+                        false => writeln!(counts, "{}", fmt.matches)?,
+                    }
+                }
             }
             Err(_) => { /* We preempt this above */ }
         }
     }
-    Ok(())
+    if let Some(pager) = fmt.pager.as_mut().filter(|pager| !pager.is_running()) {
+        pager.release(stdout)?;
+    }
+    if args.count_matches && many_paths {
+        writeln!(counts, "total:{total_matches}")?;
+    }
+    fmt.json_end(&mut counts)?;
+    if args.fail_if_empty && fmt.output_bytes + fmt.file_bytes + counts.bytes == 0 {
+        *ok &= false;
+        eprintln!("rat: no output");
+    }
+    fmt.report();
+    fmt.report_paths();
+    if let Some(pager) = fmt.pager.take() {
+        pager.wait(stdout)?;
+    }
+    for clipboard in clipboards {
+        if let Err(e) = clipboard.wait(stdout) {
+            *ok &= false;
+            eprintln!("rat: clipboard: {e}");
+        }
+    }
+    Ok(total_matches)
 }
 
 fn main() -> ExitCode {
     // `ok` tracks if any file errors occurred in the loop
     let mut ok = true;
-    cli(&mut ok, Cli::parse()).unwrap_or_else(|e| {
+    let matches = Cli::command().get_matches();
+    let mut args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.resolve();
+    if args.dump_config {
+        // Before the clipboard or pager get to stdout, or any input is read
+        return match args.dump(&matches, &mut io::stdout().lock()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("rat: {}", strerror(&e));
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let invert_exit_code = args.invert_exit_code;
+    let matches = match cli(&mut ok, args) {
+        Err(e) if is_aborted(&e) => return ExitCode::from(EXIT_ABORTED),
+        matches => matches,
+    };
+    let matches = matches.unwrap_or_else(|e| {
         eprintln!("{:#?}", e);
+        ok &= !invert_exit_code;
+        0
     });
+    if invert_exit_code {
+        // Errors take precedence over matches, as with grep
+        return match (ok, matches) {
+            (false, _) => ExitCode::from(2),
+            (true, 0) => ExitCode::FAILURE,
+            (true, _) => ExitCode::SUCCESS,
+        };
+    }
     match ok {
         true => ExitCode::SUCCESS,
         false => ExitCode::FAILURE,
@@ -0,0 +1,593 @@
+/*
+ * POSIX basic regular expressions the way GNU's regex has them for expr (its
+ * RE_SYNTAX_POSIX_BASIC, empty ranges and intervals at the start allowed): \( \)
+ * groups with \1 to \9 back references, \{m,n\} intervals, \+ \? and \| , the GNU
+ * escapes \w \W \s \S \b \B \< \> \` \' and bracket expressions with [:class:],
+ * [.c.] and [=c=]. ^ and $ are only anchors where POSIX has them (at the start or end
+ * of the pattern, a group or an alternative) and a leading * is literal. Bytes are
+ * matched as in the C locale.
+ *
+ * Matching backtracks through every way the pattern can match at the start of the
+ * string to find the longest, like GNU's re_match; the groups are those of the first
+ * way found (in the usual greedy order) to match that much. Intervals are unrolled
+ * like glibc does, within a limit. Compile errors are GNU's messages.
+ */
+
+/// A compiled pattern
+#[derive(Debug)]
+pub struct Bre {
+    program: Vec<Inst>,
+    groups: usize,
+    // Iteration start registers
+    registers: usize,
+}
+
+/// The longest match at the start of a string: its length, and the span of each group
+/// (None for groups that took no part in it)
+#[derive(Debug)]
+pub struct Match {
+    pub len: usize,
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+type ByteSet = Box<[bool; 256]>;
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Fail,
+    Byte(u8),
+    Set(ByteSet),
+    Assert(Assertion),
+    Group(usize, Box<Node>),
+    Backref(usize),
+    Repeat(Box<Node>, u32, Option<u32>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+    WordStart,
+    WordEnd,
+}
+
+// RE_DUP_MAX
+const DUP_MAX: u32 = 0x7fff;
+const UNMATCHED_BRACKET: &str = "Unmatched [, [^, [:, [., or [=";
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn set_of(matches: impl Fn(u8) -> bool) -> ByteSet {
+    let mut set = Box::new([false; 256]);
+    for c in 0..=255 {
+        set[c as usize] = matches(c);
+    }
+    set
+}
+
+fn class(name: &[u8]) -> Option<fn(u8) -> bool> {
+    Some(match name {
+        b"alpha" => |c: u8| c.is_ascii_alphabetic(),
+        b"upper" => |c: u8| c.is_ascii_uppercase(),
+        b"lower" => |c: u8| c.is_ascii_lowercase(),
+        b"digit" => |c: u8| c.is_ascii_digit(),
+        b"xdigit" => |c: u8| c.is_ascii_hexdigit(),
+        b"alnum" => |c: u8| c.is_ascii_alphanumeric(),
+        b"space" => |c: u8| c.is_ascii_whitespace() || c == 0x0b,
+        b"blank" => |c: u8| c == b' ' || c == b'\t',
+        b"punct" => |c: u8| c.is_ascii_punctuation(),
+        b"print" => |c: u8| (b' '..=b'~').contains(&c),
+        b"graph" => |c: u8| c.is_ascii_graphic(),
+        b"cntrl" => |c: u8| c.is_ascii_control(),
+        _ => return None,
+    })
+}
+
+// An element of a bracket expression
+enum Element {
+    Byte(u8),
+    Class(fn(u8) -> bool),
+}
+
+struct Parser<'a> {
+    pattern: &'a [u8],
+    at: usize,
+    groups: usize,
+    // Groups closed so far, the only ones back references can refer to
+    closed: Vec<bool>,
+}
+
+impl Parser<'_> {
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.pattern.get(self.at + offset).copied()
+    }
+
+    fn at_escape(&self, c: u8) -> bool {
+        self.peek(0) == Some(b'\\') && self.peek(1) == Some(c)
+    }
+
+    fn alternation(&mut self, depth: usize) -> Result<Node, &'static str> {
+        // Back references can't refer to groups of another alternative
+        let before = self.closed.clone();
+        let mut branches = vec![self.branch(depth)?];
+        let mut closed = self.closed.clone();
+        while self.at_escape(b'|') {
+            self.at += 2;
+            let groups = self.closed.len();
+            self.closed = before.clone();
+            self.closed.resize(groups, false);
+            branches.push(self.branch(depth)?);
+            closed.resize(self.closed.len(), false);
+            for (closed, &now) in closed.iter_mut().zip(&self.closed) {
+                *closed |= now;
+            }
+        }
+        self.closed = closed;
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Node::Alternate(branches),
+        })
+    }
+
+    fn branch(&mut self, depth: usize) -> Result<Node, &'static str> {
+        let mut items = Vec::new();
+        // Before anything but a ^ anchor, where repetitions are literal
+        let mut start = true;
+        loop {
+            if self.peek(0).is_none() || self.at_escape(b'|') {
+                break;
+            }
+            if self.at_escape(b')') {
+                match depth {
+                    0 => return Err("Unmatched ) or \\)"),
+                    _ => break,
+                }
+            }
+            let c = self.peek(0).unwrap();
+            self.at += 1;
+            let atom = match c {
+                b'^' if items.is_empty() => {
+                    items.push(Node::Assert(Assertion::Start));
+                    continue;
+                }
+                b'$' if self.peek(0).is_none() || self.at_escape(b')') || self.at_escape(b'|') => {
+                    Node::Assert(Assertion::End)
+                }
+                b'*' if start => Node::Byte(b'*'),
+                // RE_DOT_NOT_NULL
+                b'.' => Node::Set(set_of(|c| c != 0)),
+                b'[' => self.bracket()?,
+                b'\\' => self.escape(start, depth)?,
+                c => Node::Byte(c),
+            };
+            start = false;
+            items.push(self.repeats(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn escape(&mut self, start: bool, depth: usize) -> Result<Node, &'static str> {
+        let Some(c) = self.peek(0) else {
+            return Err("Trailing backslash");
+        };
+        self.at += 1;
+        Ok(match c {
+            b'(' => {
+                self.groups += 1;
+                let group = self.groups;
+                self.closed.push(false);
+                let inner = self.alternation(depth + 1)?;
+                if !self.at_escape(b')') {
+                    return Err("Unmatched ( or \\(");
+                }
+                self.at += 2;
+                self.closed[group - 1] = true;
+                Node::Group(group, Box::new(inner))
+            }
+            b'1'..=b'9' => {
+                let group = (c - b'0') as usize;
+                if !self.closed.get(group - 1).copied().unwrap_or(false) {
+                    return Err("Invalid back reference");
+                }
+                Node::Backref(group)
+            }
+            b'w' => Node::Set(set_of(is_word)),
+            b'W' => Node::Set(set_of(|c| !is_word(c))),
+            b's' => Node::Set(set_of(|c| c.is_ascii_whitespace() || c == 0x0b)),
+            b'S' => Node::Set(set_of(|c| !(c.is_ascii_whitespace() || c == 0x0b))),
+            b'b' => Node::Assert(Assertion::WordBoundary),
+            b'B' => Node::Assert(Assertion::NotWordBoundary),
+            b'<' => Node::Assert(Assertion::WordStart),
+            b'>' => Node::Assert(Assertion::WordEnd),
+            b'`' => Node::Assert(Assertion::Start),
+            b'\'' => Node::Assert(Assertion::End),
+            // Repetitions with nothing to repeat are literal
+            b'+' | b'?' | b'{' if start => Node::Byte(c),
+            c => Node::Byte(c),
+        })
+    }
+
+    // Any *, \+, \? and \{m,n\} after an atom
+    fn repeats(&mut self, mut atom: Node) -> Result<Node, &'static str> {
+        loop {
+            let (min, max) = if self.peek(0) == Some(b'*') {
+                self.at += 1;
+                (0, None)
+            } else if self.at_escape(b'+') {
+                self.at += 2;
+                (1, None)
+            } else if self.at_escape(b'?') {
+                self.at += 2;
+                (0, Some(1))
+            } else if self.at_escape(b'{') {
+                self.at += 2;
+                self.interval()?
+            } else {
+                return Ok(atom);
+            };
+            atom = match atom {
+                // glibc's repeated assertions never match, not even zero times
+                Node::Assert(_) | Node::Fail => Node::Fail,
+                atom => Node::Repeat(Box::new(atom), min, max),
+            };
+        }
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let digits = self.pattern[self.at..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let number = self.pattern[self.at..self.at + digits]
+            .iter()
+            .fold(0u32, |n, &c| (n * 10 + (c - b'0') as u32).min(DUP_MAX + 1));
+        self.at += digits;
+        (digits > 0).then_some(number)
+    }
+
+    fn interval(&mut self) -> Result<(u32, Option<u32>), &'static str> {
+        let min = self.number();
+        let max = match self.peek(0) {
+            Some(b',') => {
+                self.at += 1;
+                self.number()
+            }
+            _ => Some(min.unwrap_or(0)),
+        };
+        let min = min.unwrap_or(0);
+        match (self.peek(0), self.peek(1)) {
+            (None, _) | (Some(b'\\'), None) => return Err("Unmatched \\{"),
+            (Some(b'\\'), Some(b'}')) => self.at += 2,
+            _ => return Err("Invalid content of \\{\\}"),
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err("Invalid content of \\{\\}");
+        }
+        if DUP_MAX < max.unwrap_or(min) {
+            return Err("Regular expression too big");
+        }
+        Ok((min, max))
+    }
+
+    // After the [
+    fn bracket(&mut self) -> Result<Node, &'static str> {
+        let negate = self.peek(0) == Some(b'^');
+        self.at += negate as usize;
+        if self.peek(0).is_none() {
+            return Err("Invalid regular expression");
+        }
+        let mut set = Box::new([false; 256]);
+        let mut first = true;
+        loop {
+            let Some(c) = self.peek(0) else {
+                return Err(UNMATCHED_BRACKET);
+            };
+            if c == b']' && !first {
+                self.at += 1;
+                break;
+            }
+            first = false;
+            let start = self.element()?;
+            let is_range = self.peek(0) == Some(b'-') && !matches!(self.peek(1), Some(b']') | None);
+            match (start, is_range) {
+                (Element::Class(_), true) => return Err("Invalid range end"),
+                (Element::Class(class), false) => {
+                    for c in 0..=255u8 {
+                        set[c as usize] |= class(c);
+                    }
+                }
+                (Element::Byte(c), false) => set[c as usize] = true,
+                (Element::Byte(low), true) => {
+                    self.at += 1;
+                    let Element::Byte(high) = self.element()? else {
+                        return Err("Invalid range end");
+                    };
+                    // Empty when reversed
+                    for c in low..=high {
+                        set[c as usize] = true;
+                    }
+                }
+            }
+        }
+        if negate {
+            for c in set.iter_mut() {
+                *c = !*c;
+            }
+        }
+        Ok(Node::Set(set))
+    }
+
+    fn element(&mut self) -> Result<Element, &'static str> {
+        let c = self.peek(0).ok_or(UNMATCHED_BRACKET)?;
+        let kind = self.peek(1).filter(|kind| b":.=".contains(kind));
+        let Some(kind) = kind.filter(|_| c == b'[') else {
+            self.at += 1;
+            return Ok(Element::Byte(c));
+        };
+        let start = self.at + 2;
+        let end = self.pattern[start..]
+            .windows(2)
+            .position(|w| w == [kind, b']'])
+            .ok_or(UNMATCHED_BRACKET)?;
+        let name = &self.pattern[start..start + end];
+        self.at = start + end + 2;
+        match (kind, name) {
+            (b':', name) => class(name)
+                .map(Element::Class)
+                .ok_or("Invalid character class name"),
+            (_, [c]) => Ok(Element::Byte(*c)),
+            _ => Err("Invalid collation character"),
+        }
+    }
+}
+
+// Programs longer than this are refused rather than unrolling nested intervals until
+// memory runs out, which is what glibc does
+const PROGRAM_MAX: usize = 1 << 20;
+
+// The parsed pattern flattened for a backtracking machine with its own stack, so long
+// strings don't take a level of recursion per byte
+#[derive(Debug)]
+enum Inst {
+    Byte(u8),
+    Set(ByteSet),
+    Assert(Assertion),
+    Fail,
+    // Record the position in a capture slot, 2 * group for the start and one more for
+    // the end
+    Save(usize),
+    Backref(usize),
+    // Carry on at the first, backtracking to the second
+    Split(usize, usize),
+    Jump(usize),
+    // Record where an iteration started in a register, and fail the iteration if it
+    // ends there, so that empty iterations don't loop
+    Mark(usize),
+    Progress(usize),
+    Match,
+}
+
+#[derive(Default)]
+struct Compiler {
+    program: Vec<Inst>,
+    registers: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, &'static str> {
+        if self.program.len() == PROGRAM_MAX {
+            return Err("Regular expression too big");
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), &'static str> {
+        match node {
+            Node::Empty => {}
+            Node::Fail => {
+                self.emit(Inst::Fail)?;
+            }
+            Node::Byte(c) => {
+                self.emit(Inst::Byte(*c))?;
+            }
+            Node::Set(set) => {
+                self.emit(Inst::Set(set.clone()))?;
+            }
+            Node::Assert(assertion) => {
+                self.emit(Inst::Assert(*assertion))?;
+            }
+            Node::Group(group, inner) => {
+                self.emit(Inst::Save(2 * group))?;
+                self.compile(inner)?;
+                self.emit(Inst::Save(2 * group + 1))?;
+            }
+            Node::Backref(group) => {
+                self.emit(Inst::Backref(*group))?;
+            }
+            Node::Concat(items) => items.iter().try_for_each(|item| self.compile(item))?,
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    let split = (i + 1 < branches.len())
+                        .then(|| self.emit(Inst::Split(0, 0)))
+                        .transpose()?;
+                    self.compile(branch)?;
+                    if let Some(split) = split {
+                        jumps.push(self.emit(Inst::Jump(0))?);
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                }
+                for jump in jumps {
+                    self.program[jump] = Inst::Jump(self.program.len());
+                }
+            }
+            Node::Repeat(inner, min, max) => {
+                for _ in 0..*min {
+                    self.compile(inner)?;
+                }
+                let register = self.registers;
+                self.registers += 1;
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.iteration(inner, register)?;
+                        self.emit(Inst::Jump(split))?;
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    }
+                    // Each optional iteration skipping to the end
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.iteration(inner, register)?;
+                        }
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, self.program.len());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn iteration(&mut self, inner: &Node, register: usize) -> Result<(), &'static str> {
+        self.emit(Inst::Mark(register))?;
+        self.compile(inner)?;
+        self.emit(Inst::Progress(register))?;
+        Ok(())
+    }
+}
+
+// What's left to try, and what to undo on the way back to it
+enum Frame {
+    Try(usize, usize),
+    Slot(usize, Option<usize>),
+    Register(usize, usize),
+}
+
+fn assert(string: &[u8], assertion: Assertion, at: usize) -> bool {
+    let before = at > 0 && is_word(string[at - 1]);
+    let after = string.get(at).is_some_and(|&c| is_word(c));
+    match assertion {
+        Assertion::Start => at == 0,
+        Assertion::End => at == string.len(),
+        Assertion::WordBoundary => before != after,
+        Assertion::NotWordBoundary => before == after,
+        Assertion::WordStart => !before && after,
+        Assertion::WordEnd => before && !after,
+    }
+}
+
+impl Bre {
+    pub fn new(pattern: &[u8]) -> Result<Bre, &'static str> {
+        let mut parser = Parser {
+            pattern,
+            at: 0,
+            groups: 0,
+            closed: Vec::new(),
+        };
+        let node = parser.alternation(0)?;
+        let mut compiler = Compiler::default();
+        compiler.compile(&node)?;
+        compiler.emit(Inst::Match)?;
+        Ok(Bre {
+            program: compiler.program,
+            groups: parser.groups,
+            registers: compiler.registers,
+        })
+    }
+
+    /// The number of \( \) groups
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// The longest match anchored at the start of `string`
+    pub fn match_start(&self, string: &[u8]) -> Option<Match> {
+        let mut slots: Vec<Option<usize>> = vec![None; 2 * (self.groups + 1)];
+        let mut registers = vec![usize::MAX; self.registers];
+        let mut longest: Option<Match> = None;
+        let mut stack = vec![Frame::Try(0, 0)];
+        while let Some(frame) = stack.pop() {
+            let (mut pc, mut at) = match frame {
+                Frame::Try(pc, at) => (pc, at),
+                Frame::Slot(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+                Frame::Register(register, value) => {
+                    registers[register] = value;
+                    continue;
+                }
+            };
+            loop {
+                match &self.program[pc] {
+                    Inst::Byte(c) if string.get(at) == Some(c) => at += 1,
+                    Inst::Set(set) if string.get(at).is_some_and(|&c| set[c as usize]) => at += 1,
+                    Inst::Assert(assertion) if assert(string, *assertion, at) => {}
+                    Inst::Save(slot) => {
+                        stack.push(Frame::Slot(*slot, slots[*slot]));
+                        slots[*slot] = Some(at);
+                    }
+                    Inst::Backref(group) => match (slots[2 * group], slots[2 * group + 1]) {
+                        (Some(start), Some(end))
+                            if start <= end && string[at..].starts_with(&string[start..end]) =>
+                        {
+                            at += end - start
+                        }
+                        _ => break,
+                    },
+                    Inst::Split(next, alternative) => {
+                        stack.push(Frame::Try(*alternative, at));
+                        pc = *next;
+                        continue;
+                    }
+                    Inst::Jump(next) => {
+                        pc = *next;
+                        continue;
+                    }
+                    Inst::Mark(register) => {
+                        stack.push(Frame::Register(*register, registers[*register]));
+                        registers[*register] = at;
+                    }
+                    Inst::Progress(register) if registers[*register] != at => {}
+                    Inst::Match => {
+                        if longest.as_ref().map_or(true, |longest| longest.len < at) {
+                            let groups = (1..=self.groups).map(|group| {
+                                match (slots[2 * group], slots[2 * group + 1]) {
+                                    (Some(start), Some(end)) if start <= end => Some((start, end)),
+                                    _ => None,
+                                }
+                            });
+                            longest = Some(Match {
+                                len: at,
+                                groups: groups.collect(),
+                            });
+                        }
+                        // Nothing can be longer than all of it
+                        if at == string.len() {
+                            return longest;
+                        }
+                        break;
+                    }
+                    _ => break,
+                }
+                pc += 1;
+            }
+        }
+        longest
+    }
+}
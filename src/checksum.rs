@@ -0,0 +1,1146 @@
+/*
+ * Checksums and message digests shared by cksum and the *sum utilities
+ *
+ * Output and `--check` parsing follow GNU: tagged (BSD style) lines look like
+ * `MD5 (file) = hex`, untagged ones `hex  file` (`hex *file` in binary mode), and
+ * file names with a backslash, newline or carriage return are escaped with the
+ * line prefixed by a backslash.
+ *
+ * md5sum and the other *sum utilities are `sum_main` with a fixed algorithm.
+ *
+ * The legacy sysv/bsd/crc checksums aren't digests: they print a number, the size
+ * and the file name in their own formats and can't be checked.
+ */
+
+use crate::{get_matches, reset_sigpipe, shell_quote, stdout_file, strerror};
+use blake2::digest::{DynDigest, Update, VariableOutput};
+use blake2::Blake2bVar;
+use clap::{Arg, ArgAction, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::process::ExitCode;
+
+const IO_BUFSIZE: usize = 1 << 17;
+const BLAKE2B_MAX_BITS: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    Bsd,
+    Sysv,
+    Crc,
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Blake2b,
+    Sm3,
+}
+
+impl Algorithm {
+    const ALL: [Algorithm; 11] = [
+        Algorithm::Bsd,
+        Algorithm::Sysv,
+        Algorithm::Crc,
+        Algorithm::Md5,
+        Algorithm::Sha1,
+        Algorithm::Sha224,
+        Algorithm::Sha256,
+        Algorithm::Sha384,
+        Algorithm::Sha512,
+        Algorithm::Blake2b,
+        Algorithm::Sm3,
+    ];
+
+    /// Name used in tagged output lines (and error messages)
+    pub fn tag(self) -> &'static str {
+        match self {
+            Algorithm::Bsd => "BSD",
+            Algorithm::Sysv => "SYSV",
+            Algorithm::Crc => "CRC",
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha224 => "SHA224",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha384 => "SHA384",
+            Algorithm::Sha512 => "SHA512",
+            Algorithm::Blake2b => "BLAKE2b",
+            Algorithm::Sm3 => "SM3",
+        }
+    }
+
+    /// Digest size in bits, the maximum one for blake2b
+    pub fn bits(self) -> usize {
+        match self {
+            Algorithm::Bsd | Algorithm::Sysv => 16,
+            Algorithm::Crc => 32,
+            Algorithm::Md5 => 128,
+            Algorithm::Sha1 => 160,
+            Algorithm::Sha224 => 224,
+            Algorithm::Sha256 | Algorithm::Sm3 => 256,
+            Algorithm::Sha384 => 384,
+            Algorithm::Sha512 | Algorithm::Blake2b => 512,
+        }
+    }
+
+    /// The sysv/bsd/crc checksums, which have their own output format
+    pub fn is_legacy(self) -> bool {
+        matches!(self, Algorithm::Bsd | Algorithm::Sysv | Algorithm::Crc)
+    }
+
+    fn hasher(self, bits: usize) -> Hasher {
+        match self {
+            Algorithm::Bsd => Hasher::Bsd(0),
+            Algorithm::Sysv => Hasher::Sysv(0),
+            Algorithm::Crc => Hasher::Crc(0),
+            Algorithm::Md5 => Hasher::Digest(Box::<md5::Md5>::default()),
+            Algorithm::Sha1 => Hasher::Digest(Box::<sha1::Sha1>::default()),
+            Algorithm::Sha224 => Hasher::Digest(Box::<sha2::Sha224>::default()),
+            Algorithm::Sha256 => Hasher::Digest(Box::<sha2::Sha256>::default()),
+            Algorithm::Sha384 => Hasher::Digest(Box::<sha2::Sha384>::default()),
+            Algorithm::Sha512 => Hasher::Digest(Box::<sha2::Sha512>::default()),
+            Algorithm::Sm3 => Hasher::Digest(Box::<sm3::Sm3>::default()),
+            Algorithm::Blake2b => {
+                Hasher::Blake2b(Blake2bVar::new(bits / 8).expect("valid blake2b length"))
+            }
+        }
+    }
+}
+
+/*
+ * The POSIX cksum CRC: polynomial 0x04C11DB7 processed most significant bit first
+ * with the length appended, unlike zlib's reflected crc32. Tables for slicing by 8
+ * bytes at a time, `CRC_TABLE[0]` being the usual byte at a time one.
+ */
+const CRC_TABLE: [[u32; 256]; 8] = crc_tables();
+
+const fn crc_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut t = 1;
+    while t < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let prev = tables[t - 1][i];
+            tables[t][i] = (prev << 8) ^ tables[0][(prev >> 24) as usize];
+            i += 1;
+        }
+        t += 1;
+    }
+    tables
+}
+
+fn crc_update(mut crc: u32, data: &[u8]) -> u32 {
+    let t = &CRC_TABLE;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let one = crc ^ u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let two = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = t[7][(one >> 24) as usize]
+            ^ t[6][(one >> 16 & 0xff) as usize]
+            ^ t[5][(one >> 8 & 0xff) as usize]
+            ^ t[4][(one & 0xff) as usize]
+            ^ t[3][(two >> 24) as usize]
+            ^ t[2][(two >> 16 & 0xff) as usize]
+            ^ t[1][(two >> 8 & 0xff) as usize]
+            ^ t[0][(two & 0xff) as usize];
+    }
+    for &b in chunks.remainder() {
+        crc = (crc << 8) ^ t[0][((crc >> 24) as u8 ^ b) as usize];
+    }
+    crc
+}
+
+enum Hasher {
+    Bsd(u16),
+    Sysv(u32),
+    Crc(u32),
+    Digest(Box<dyn DynDigest>),
+    Blake2b(Blake2bVar),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            // Rotate right then add each byte
+            Hasher::Bsd(sum) => {
+                for &b in data {
+                    *sum = sum.rotate_right(1).wrapping_add(b as u16);
+                }
+            }
+            Hasher::Sysv(sum) => {
+                *sum = data.iter().fold(*sum, |sum, &b| sum.wrapping_add(b as u32));
+            }
+            Hasher::Crc(crc) => *crc = crc_update(*crc, data),
+            Hasher::Digest(digest) => digest.update(data),
+            Hasher::Blake2b(digest) => digest.update(data),
+        }
+    }
+
+    fn finish(self, size: u64) -> Sum {
+        match self {
+            Hasher::Bsd(sum) => Sum::Bsd(sum),
+            Hasher::Sysv(sum) => {
+                let r = (sum & 0xffff) + (sum >> 16);
+                Sum::Sysv((r & 0xffff) + (r >> 16))
+            }
+            Hasher::Crc(mut crc) => {
+                let mut length = size;
+                while length != 0 {
+                    crc = (crc << 8) ^ CRC_TABLE[0][((crc >> 24) as u8 ^ length as u8) as usize];
+                    length >>= 8;
+                }
+                Sum::Crc(!crc)
+            }
+            Hasher::Digest(digest) => Sum::Digest(digest.finalize().into_vec()),
+            Hasher::Blake2b(digest) => {
+                let mut out = vec![0; digest.output_size()];
+                digest
+                    .finalize_variable(&mut out)
+                    .expect("output sized by the hasher");
+                Sum::Digest(out)
+            }
+        }
+    }
+}
+
+enum Sum {
+    Bsd(u16),
+    Sysv(u32),
+    Crc(u32),
+    Digest(Vec<u8>),
+}
+
+/// Incremental digest of data as it streams by, ie. while `rat` copies a file
+pub struct Digest {
+    hasher: Hasher,
+    size: u64,
+}
+
+impl Digest {
+    pub fn new(algorithm: Algorithm, bits: usize) -> Digest {
+        Digest {
+            hasher: algorithm.hasher(bits),
+            size: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.size += data.len() as u64;
+    }
+
+    /// The digest bytes, empty for the legacy checksums
+    pub fn finish(self) -> Vec<u8> {
+        match self.hasher.finish(self.size) {
+            Sum::Digest(digest) => digest,
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Config {
+    /// Program name for messages
+    pub prog: &'static str,
+    /// None with `cksum --check`, the algorithm then comes from each line's tag
+    pub algorithm: Option<Algorithm>,
+    /// Digest length for blake2b
+    pub bits: usize,
+    pub tag: bool,
+    /// Mark untagged lines with '*' rather than ' ', there's no difference on POSIX
+    pub binary: bool,
+    pub zero: bool,
+    pub check: bool,
+    pub ignore_missing: bool,
+    pub quiet: bool,
+    pub status: bool,
+    pub strict: bool,
+    pub warn: bool,
+}
+
+impl Config {
+    /// Reject the verification modifiers when not verifying, and vice versa
+    pub fn validate(&self) -> Result<(), String> {
+        if self.check {
+            if self.zero {
+                return Err(String::from(
+                    "the --zero option is not supported when verifying checksums",
+                ));
+            }
+            return Ok(());
+        }
+        let modifiers = [
+            (self.ignore_missing, "--ignore-missing"),
+            (self.status, "--status"),
+            (self.warn, "--warn"),
+            (self.quiet, "--quiet"),
+            (self.strict, "--strict"),
+        ];
+        match modifiers.iter().find(|(set, _)| *set) {
+            Some((_, name)) => Err(format!(
+                "the {name} option is meaningful only when verifying checksums"
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse a `--length` for blake2b, 0 meaning the full 512 bits
+pub fn parse_length(length: &str) -> Result<usize, String> {
+    let bits = length
+        .parse::<usize>()
+        .map_err(|_| format!("invalid length: '{length}'"))?;
+    if bits % 8 != 0 {
+        return Err(format!(
+            "invalid length: '{length}'\nlength is not a multiple of 8"
+        ));
+    }
+    if bits > BLAKE2B_MAX_BITS {
+        return Err(format!(
+            "invalid length: '{length}'\nmaximum digest length for 'BLAKE2b' is {BLAKE2B_MAX_BITS} bits"
+        ));
+    }
+    Ok(if bits == 0 { BLAKE2B_MAX_BITS } else { bits })
+}
+
+fn open(name: &[u8]) -> io::Result<Box<dyn Read>> {
+    if name == b"-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    let path = std::ffi::OsStr::from_bytes(name);
+    Ok(Box::new(File::open(path)?))
+}
+
+fn digest_file(name: &[u8], algorithm: Algorithm, bits: usize) -> io::Result<(Sum, u64)> {
+    let mut input = open(name)?;
+    let mut hasher = algorithm.hasher(bits);
+    let mut buf = vec![0; IO_BUFSIZE];
+    let mut size = 0u64;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((hasher.finish(size), size))
+}
+
+fn needs_escape(name: &[u8]) -> bool {
+    name.iter().any(|c| matches!(c, b'\\' | b'\n' | b'\r'))
+}
+
+fn write_name(out: &mut dyn Write, name: &[u8], escape: bool) -> io::Result<()> {
+    if !escape {
+        return out.write_all(name);
+    }
+    for &c in name {
+        match c {
+            b'\\' => out.write_all(b"\\\\")?,
+            b'\n' => out.write_all(b"\\n")?,
+            b'\r' => out.write_all(b"\\r")?,
+            _ => out.write_all(&[c])?,
+        }
+    }
+    Ok(())
+}
+
+fn unescape_name(name: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(name.len());
+    let mut chars = name.iter();
+    while let Some(&c) = chars.next() {
+        match c {
+            b'\\' => out.push(match chars.next()? {
+                b'\\' => b'\\',
+                b'n' => b'\n',
+                b'r' => b'\r',
+                _ => return None,
+            }),
+            _ => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn tag_name(algorithm: Algorithm, bits: usize) -> String {
+    match algorithm {
+        Algorithm::Blake2b if bits != BLAKE2B_MAX_BITS => format!("BLAKE2b-{bits}"),
+        _ => algorithm.tag().to_string(),
+    }
+}
+
+fn write_sum(
+    out: &mut dyn Write,
+    config: &Config,
+    algorithm: Algorithm,
+    sum: Sum,
+    size: u64,
+    name: Option<&[u8]>,
+) -> io::Result<()> {
+    let delim = if config.zero { b'\0' } else { b'\n' };
+    let digest = match sum {
+        Sum::Bsd(sum) => {
+            // Sizes in 1K blocks
+            write!(out, "{sum:05} {:>5}", (size + 1023) / 1024)?;
+            return write_legacy_name(out, name, delim);
+        }
+        Sum::Sysv(sum) => {
+            // Sizes in 512 byte blocks
+            write!(out, "{sum} {}", (size + 511) / 512)?;
+            return write_legacy_name(out, name, delim);
+        }
+        Sum::Crc(crc) => {
+            write!(out, "{crc} {size}")?;
+            return write_legacy_name(out, name, delim);
+        }
+        Sum::Digest(digest) => digest,
+    };
+    let name = name.unwrap_or(b"-");
+    let escape = !config.zero && needs_escape(name);
+    if escape {
+        out.write_all(b"\\")?;
+    }
+    if config.tag {
+        write!(out, "{} (", tag_name(algorithm, config.bits))?;
+        write_name(out, name, escape)?;
+        out.write_all(b") = ")?;
+    }
+    for byte in &digest {
+        write!(out, "{byte:02x}")?;
+    }
+    if !config.tag {
+        out.write_all(if config.binary { b" *" } else { b"  " })?;
+        write_name(out, name, escape)?;
+    }
+    out.write_all(&[delim])
+}
+
+// The legacy checksums only name files given as arguments, and never escape them
+fn write_legacy_name(out: &mut dyn Write, name: Option<&[u8]>, delim: u8) -> io::Result<()> {
+    if let Some(name) = name {
+        out.write_all(b" ")?;
+        out.write_all(name)?;
+    }
+    out.write_all(&[delim])
+}
+
+/// A parsed `--check` line
+pub struct CheckLine {
+    pub algorithm: Algorithm,
+    pub bits: usize,
+    pub digest: Vec<u8>,
+    pub name: Vec<u8>,
+}
+
+/// State kept across the lines of one checksum file
+struct Checker<'a> {
+    config: &'a Config,
+    // Algorithm of the last tagged line, named in the warnings about improper lines
+    algorithm: Algorithm,
+    // Whether lines are `hex name` (BSD reversed) rather than `hex  name`, once known
+    bsd_reversed: Option<bool>,
+    // Take the algorithm of untagged lines from their digest length, without a configured one
+    guess: bool,
+}
+
+fn is_white(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+fn parse_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let digit = |c: u8| (c as char).to_digit(16).unwrap() as u8;
+    Some(
+        hex.chunks(2)
+            .map(|pair| digit(pair[0]) << 4 | digit(pair[1]))
+            .collect(),
+    )
+}
+
+impl Checker<'_> {
+    // `TAG (name) = hex` (the `TAG` already consumed)
+    fn split_tagged(
+        &self,
+        rest: &[u8],
+        algorithm: Algorithm,
+        bits: usize,
+        escaped: bool,
+    ) -> Option<CheckLine> {
+        // File names aren't escaped by BSD tools, so search backwards for the last ')'
+        let close = rest.iter().rposition(|&c| c == b')')?;
+        let name = match escaped {
+            true => unescape_name(&rest[..close])?,
+            false => rest[..close].to_vec(),
+        };
+        let mut i = close + 1;
+        while i < rest.len() && is_white(rest[i]) {
+            i += 1;
+        }
+        if rest.get(i) != Some(&b'=') {
+            return None;
+        }
+        i += 1;
+        while i < rest.len() && is_white(rest[i]) {
+            i += 1;
+        }
+        let digest = parse_hex(&rest[i..]).filter(|d| d.len() * 8 == bits)?;
+        Some(CheckLine {
+            algorithm,
+            bits,
+            digest,
+            name,
+        })
+    }
+
+    fn split(&mut self, line: &[u8]) -> Option<CheckLine> {
+        let mut i = line.iter().take_while(|&&c| is_white(c)).count();
+        let escaped = line.get(i) == Some(&b'\\');
+        if escaped {
+            i += 1;
+        }
+
+        // Tagged, BSD style
+        let tagged = Algorithm::ALL.into_iter().find(|algorithm| {
+            let tag = algorithm.tag().as_bytes();
+            line[i..].starts_with(tag)
+                && match line.get(i + tag.len()) {
+                    Some(b' ' | b'(') => true,
+                    Some(b'-') => *algorithm == Algorithm::Blake2b,
+                    _ => false,
+                }
+        });
+        let tagged = match (tagged, self.config.algorithm) {
+            (Some(tagged), Some(algorithm)) if tagged != algorithm => None,
+            (tagged, _) => tagged,
+        };
+        if let Some(algorithm) = tagged {
+            // Only the newer digests can be checked, not the legacy sums
+            if algorithm.is_legacy() {
+                return None;
+            }
+            self.algorithm = algorithm;
+            i += algorithm.tag().len();
+            let mut bits = algorithm.bits();
+            if line[i] == b'-' {
+                let digits = line[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                let length = std::str::from_utf8(&line[i + 1..i + 1 + digits]).ok()?;
+                bits = length
+                    .parse()
+                    .ok()
+                    .filter(|&b| b > 0 && b <= bits && b % 8 == 0)?;
+                i += 1 + digits;
+            }
+            if line.get(i) == Some(&b' ') {
+                i += 1;
+            }
+            if line.get(i) != Some(&b'(') {
+                return None;
+            }
+            return self.split_tagged(&line[i + 1..], algorithm, bits, escaped);
+        }
+
+        // Untagged lines need the algorithm from the command line
+        let hex_len = line[i..].iter().take_while(|c| !is_white(**c)).count();
+        let algorithm = match self.config.algorithm {
+            Some(algorithm) => algorithm,
+            None if self.guess => untagged_algorithm(hex_len)?,
+            None => return None,
+        };
+        let bits = match algorithm {
+            // The length of a blake2b digest is whatever the line says
+            Algorithm::Blake2b => {
+                let hex_digits = line[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .count();
+                if hex_digits < 2 || hex_digits % 2 != 0 || hex_digits * 4 > BLAKE2B_MAX_BITS {
+                    return None;
+                }
+                hex_digits * 4
+            }
+            _ => algorithm.bits(),
+        };
+        // A digest, a blank and at least one character of a name
+        if line.len() - i < bits / 4 + 2 {
+            return None;
+        }
+        let digest = parse_hex(&line[i..i + hex_len]).filter(|d| d.len() * 8 == bits)?;
+        i += hex_len;
+        if i == line.len() {
+            return None;
+        }
+        i += 1;
+
+        // `hex name` with a single blank is the BSD reversed format, don't allow mixing it
+        // with the usual one, so a file name with a leading space can't change meaning
+        let reversed = line.len() - i == 1 || (line[i] != b' ' && line[i] != b'*');
+        if reversed {
+            if self.bsd_reversed == Some(false) {
+                return None;
+            }
+            self.bsd_reversed = Some(true);
+        } else if self.bsd_reversed != Some(true) {
+            self.bsd_reversed = Some(false);
+            i += 1;
+        }
+        let name = match escaped {
+            true => unescape_name(&line[i..])?,
+            false => line[i..].to_vec(),
+        };
+        Some(CheckLine {
+            algorithm,
+            bits,
+            digest,
+            name,
+        })
+    }
+}
+
+// The *sum utility writing untagged digests of `hex_len` digits, blake2b never wins over sha512
+fn untagged_algorithm(hex_len: usize) -> Option<Algorithm> {
+    [
+        Algorithm::Md5,
+        Algorithm::Sha1,
+        Algorithm::Sha224,
+        Algorithm::Sha256,
+        Algorithm::Sha384,
+        Algorithm::Sha512,
+    ]
+    .into_iter()
+    .find(|algorithm| algorithm.bits() / 4 == hex_len)
+}
+
+/// Parse the properly formatted lines of a checksum list, in either the tagged or the
+/// untagged format of any *sum utility, the others are skipped
+pub fn read_list(input: &mut dyn BufRead) -> io::Result<Vec<CheckLine>> {
+    let config = Config::default();
+    let mut checker = Checker {
+        config: &config,
+        algorithm: Algorithm::Md5,
+        bsd_reversed: None,
+        guess: true,
+    };
+    let mut entries = Vec::new();
+    let mut line = Vec::new();
+    while input.read_until(b'\n', &mut line)? != 0 {
+        let mut text = line.strip_suffix(b"\n").unwrap_or(&line);
+        text = text.strip_suffix(b"\r").unwrap_or(text);
+        if !text.is_empty() && text[0] != b'#' {
+            entries.extend(checker.split(text));
+        }
+        line.clear();
+    }
+    Ok(entries)
+}
+
+/// Verify the checksums listed in `file`, `algorithm` being the last one detected
+fn check_file(
+    config: &Config,
+    file: &[u8],
+    algorithm: &mut Algorithm,
+    out: &mut dyn Write,
+) -> io::Result<bool> {
+    let prog = config.prog;
+    let display = match file {
+        b"-" => String::from("'standard input'"),
+        _ => shell_quote(file, false),
+    };
+    let is_stdin = file == b"-";
+    let mut input = match open(file) {
+        Ok(input) => BufReader::with_capacity(IO_BUFSIZE, input),
+        Err(e) => {
+            eprintln!("{prog}: {display}: {}", strerror(&e));
+            return Ok(false);
+        }
+    };
+    let mut checker = Checker {
+        config,
+        algorithm: *algorithm,
+        bsd_reversed: None,
+        guess: false,
+    };
+    let (mut misformatted, mut unreadable, mut mismatched) = (0u64, 0u64, 0u64);
+    let (mut properly_formatted, mut matched) = (false, false);
+
+    let mut line = Vec::new();
+    let mut number = 0u64;
+    loop {
+        line.clear();
+        match input.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                out.flush()?;
+                eprintln!("{prog}: {display}: read error: {}", strerror(&e));
+                return Ok(false);
+            }
+        }
+        number += 1;
+        if line[0] == b'#' {
+            continue;
+        }
+        let mut text = line.strip_suffix(b"\n").unwrap_or(&line);
+        text = text.strip_suffix(b"\r").unwrap_or(text);
+        if text.is_empty() {
+            continue;
+        }
+
+        let parsed = checker
+            .split(text)
+            .filter(|c| !(is_stdin && c.name == b"-"));
+        let Some(entry) = parsed else {
+            misformatted += 1;
+            if config.warn {
+                out.flush()?;
+                eprintln!(
+                    "{prog}: {display}: {number}: improperly formatted {} checksum line",
+                    checker.algorithm.tag()
+                );
+            }
+            continue;
+        };
+
+        properly_formatted = true;
+        // Unlike when printing checksums GNU only escapes names with a newline here
+        let escape = !config.status && entry.name.contains(&b'\n');
+        let report = |out: &mut dyn Write, result: &str| -> io::Result<()> {
+            if escape {
+                out.write_all(b"\\")?;
+            }
+            write_name(out, &entry.name, escape)?;
+            writeln!(out, ": {result}")
+        };
+        match digest_file(&entry.name, entry.algorithm, entry.bits) {
+            Err(e) if config.ignore_missing && e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                unreadable += 1;
+                out.flush()?;
+                eprintln!(
+                    "{prog}: {}: {}",
+                    shell_quote(&entry.name, false),
+                    strerror(&e)
+                );
+                if !config.status {
+                    report(out, "FAILED open or read")?;
+                }
+            }
+            Ok((Sum::Digest(digest), _)) if digest == entry.digest => {
+                matched = true;
+                if !config.status && !config.quiet {
+                    report(out, "OK")?;
+                }
+            }
+            Ok(_) => {
+                mismatched += 1;
+                if !config.status {
+                    report(out, "FAILED")?;
+                }
+            }
+        }
+    }
+    out.flush()?;
+    *algorithm = checker.algorithm;
+
+    if !properly_formatted {
+        eprintln!("{prog}: {display}: no properly formatted checksum lines found");
+        return Ok(false);
+    }
+    let plural = |n: u64| if n == 1 { "" } else { "s" };
+    if !config.status {
+        if misformatted != 0 {
+            let verb = if misformatted == 1 {
+                "line is"
+            } else {
+                "lines are"
+            };
+            eprintln!("{prog}: WARNING: {misformatted} {verb} improperly formatted");
+        }
+        if unreadable != 0 {
+            eprintln!(
+                "{prog}: WARNING: {unreadable} listed file{} could not be read",
+                plural(unreadable)
+            );
+        }
+        if mismatched != 0 {
+            eprintln!(
+                "{prog}: WARNING: {mismatched} computed checksum{} did NOT match",
+                plural(mismatched)
+            );
+        }
+        if config.ignore_missing && !matched {
+            eprintln!("{prog}: {display}: no file was verified");
+        }
+    }
+    Ok(mismatched == 0
+        && unreadable == 0
+        && (!config.strict || misformatted == 0)
+        && (!config.ignore_missing || matched))
+}
+
+/// Print (or with `config.check` verify) the checksums of `files`, stdin when empty
+pub fn run(config: &Config, files: &[Vec<u8>], out: &mut dyn Write) -> ExitCode {
+    let prog = config.prog;
+    let named = !files.is_empty();
+    let stdin = [b"-".to_vec()];
+    let files = if named { files } else { &stdin[..] };
+
+    let mut ok = true;
+    let mut detected = config.algorithm.unwrap_or(Algorithm::Crc);
+    for file in files {
+        let result = if config.check {
+            check_file(config, file, &mut detected, out)
+        } else {
+            let algorithm = config.algorithm.unwrap_or(Algorithm::Crc);
+            match digest_file(file, algorithm, config.bits) {
+                Ok((sum, size)) => write_sum(
+                    out,
+                    config,
+                    algorithm,
+                    sum,
+                    size,
+                    named.then_some(&file[..]),
+                )
+                .map(|_| true),
+                Err(e) => {
+                    let _ = out.flush();
+                    eprintln!("{prog}: {}: {}", shell_quote(file, false), strerror(&e));
+                    Ok(false)
+                }
+            }
+        };
+        match result {
+            Ok(file_ok) => ok &= file_ok,
+            Err(e) => {
+                eprintln!("{prog}: write error: {}", strerror(&e));
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Err(e) = out.flush() {
+        eprintln!("{prog}: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+/// Command line of the *sum utilities, named and described for the algorithm at runtime
+#[derive(Debug, Parser)]
+#[command(version, long_about = None)]
+#[command(next_line_help = true)]
+struct SumCli {
+    /// Read in binary mode
+    #[clap(long, short, action, overrides_with = "text")]
+    binary: bool,
+    /// Read checksums from the FILEs and check them
+    #[clap(long, short, action)]
+    check: bool,
+    /// Create a BSD style checksum (implies binary mode)
+    #[clap(long, action)]
+    tag: bool,
+    /// Read in text mode (default)
+    #[clap(long, short, action, overrides_with = "binary")]
+    text: bool,
+    /// End each output line with NUL, not newline, and disable file name escaping
+    #[clap(long, short, action)]
+    zero: bool,
+    /// Don't fail or report status for missing files
+    #[clap(long, action)]
+    ignore_missing: bool,
+    /// Don't print OK for each successfully verified file
+    #[clap(long, action, overrides_with_all = ["status", "warn"])]
+    quiet: bool,
+    /// Don't output anything, the exit status shows success
+    #[clap(long, action, overrides_with_all = ["quiet", "warn"])]
+    status: bool,
+    /// Exit non zero for improperly formatted checksum lines
+    #[clap(long, action)]
+    strict: bool,
+    /// Warn about improperly formatted checksum lines
+    #[clap(long, short, action, overrides_with_all = ["quiet", "status"])]
+    warn: bool,
+    /// Files to checksum, stdin by default
+    #[clap(action = ArgAction::Append)]
+    files: Vec<OsString>,
+}
+
+/// `main` of md5sum and the other single algorithm *sum utilities, b2sum adds `-l`
+pub fn sum_main(
+    prog: &'static str,
+    algorithm: Algorithm,
+    args: impl IntoIterator<Item = OsString>,
+) -> ExitCode {
+    reset_sigpipe();
+    let about = format!(
+        "Print or check {} ({}-bit) checksums",
+        algorithm.tag(),
+        algorithm.bits()
+    );
+    let mut command = SumCli::command().name(prog).about(about);
+    // Only blake2b has a variable length, as in b2sum
+    if algorithm == Algorithm::Blake2b {
+        command = command.arg(
+            Arg::new("length")
+                .long("length")
+                .short('l')
+                .value_name("BITS")
+                .allow_hyphen_values(true)
+                .help("Digest length in bits, a multiple of 8 no larger than 512"),
+        );
+    }
+    let matches = get_matches(command, args, 1);
+    let mut args = SumCli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let usage_error = |message: &str| {
+        eprintln!("{prog}: {message}\nTry '{prog} --help' for more information.");
+        ExitCode::FAILURE
+    };
+
+    let mut bits = algorithm.bits();
+    if let Some(length) = matches.try_get_one::<String>("length").ok().flatten() {
+        bits = match parse_length(length) {
+            Ok(bits) => bits,
+            Err(e) => {
+                e.lines().for_each(|line| eprintln!("{prog}: {line}"));
+                return ExitCode::FAILURE;
+            }
+        };
+    }
+
+    // There's no difference between the modes on POSIX, but only binary can be tagged:
+    // --tag selects binary mode and a later --text is an error
+    if args.tag && args.text {
+        if matches.index_of("text") > matches.index_of("tag") {
+            return usage_error("--tag does not support --text mode");
+        }
+        (args.binary, args.text) = (true, false);
+    }
+    let config = Config {
+        prog,
+        algorithm: Some(algorithm),
+        bits,
+        tag: args.tag,
+        binary: args.binary,
+        zero: args.zero,
+        check: args.check,
+        ignore_missing: args.ignore_missing,
+        quiet: args.quiet,
+        status: args.status,
+        strict: args.strict,
+        warn: args.warn,
+    };
+    if let Err(e) = config.validate() {
+        return usage_error(&e);
+    }
+    if args.check && args.tag {
+        return usage_error("the --tag option is meaningless when verifying checksums");
+    }
+    if args.check && (args.binary || args.text) {
+        return usage_error(
+            "the --binary and --text options are meaningless when verifying checksums",
+        );
+    }
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("{prog}: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files: Vec<Vec<u8>> = args.files.into_iter().map(OsStringExt::into_vec).collect();
+    run(&config, &files, &mut out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD5: &str = "d41d8cd98f00b204e9800998ecf8427e";
+    const SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    // The algorithm, digest length, hex digest and name of a line, None when improper
+    type Entry = Option<(Algorithm, usize, String, String)>;
+
+    // Parse `lines` as one checksum file
+    fn split(algorithm: Option<Algorithm>, lines: &[&str]) -> Vec<Entry> {
+        let config = Config {
+            algorithm,
+            ..Config::default()
+        };
+        let mut checker = Checker {
+            config: &config,
+            algorithm: algorithm.unwrap_or(Algorithm::Crc),
+            bsd_reversed: None,
+            guess: false,
+        };
+        let hex = |digest: &[u8]| digest.iter().map(|c| format!("{c:02x}")).collect();
+        lines
+            .iter()
+            .map(|line| {
+                let entry = checker.split(line.as_bytes())?;
+                let name = String::from_utf8(entry.name).unwrap();
+                Some((entry.algorithm, entry.bits, hex(&entry.digest), name))
+            })
+            .collect()
+    }
+
+    fn entry(algorithm: Algorithm, bits: usize, digest: &str, name: &str) -> Entry {
+        Some((algorithm, bits, digest.to_string(), name.to_string()))
+    }
+
+    #[test]
+    fn tagged_lines() {
+        let lines = [
+            format!("MD5 (a b) = {MD5}"),
+            format!("SHA256 (x) = {SHA256}"),
+            format!("BLAKE2b-128 (c) = {MD5}"),
+            format!("MD5(tight)={MD5}"),
+            // The last ')' ends the name
+            format!("MD5 (a) (b)) = {MD5}"),
+            format!("\\MD5 (new\\nline) = {MD5}"),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(
+            split(None, &lines),
+            [
+                entry(Algorithm::Md5, 128, MD5, "a b"),
+                entry(Algorithm::Sha256, 256, SHA256, "x"),
+                entry(Algorithm::Blake2b, 128, MD5, "c"),
+                entry(Algorithm::Md5, 128, MD5, "tight"),
+                entry(Algorithm::Md5, 128, MD5, "a) (b)"),
+                entry(Algorithm::Md5, 128, MD5, "new\nline"),
+            ]
+        );
+    }
+
+    #[test]
+    fn improper_tagged_lines() {
+        let lines = [
+            // Digest of the wrong length for the tag
+            format!("SHA256 (x) = {MD5}"),
+            format!("MD5 (x) = {MD5}0"),
+            format!("MD5 (x) = {}", MD5.replace('d', "g")),
+            format!("MD5 (x) {MD5}"),
+            format!("MD5 x = {MD5}"),
+            // Only the newer digests can be checked
+            String::from("CRC (x) = 4294967295"),
+            format!("BLAKE2b-12 (x) = {MD5}"),
+            format!("BLAKE2b-1024 (x) = {MD5}"),
+            format!("\\MD5 (bad\\escape) = {MD5}"),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(split(None, &lines), vec![None; lines.len()]);
+    }
+
+    #[test]
+    fn untagged_lines() {
+        let lines = [
+            format!("{MD5}  text"),
+            format!("{MD5} *binary"),
+            format!("{MD5}   leading blank"),
+            format!("  {MD5}  indented"),
+            format!("{}  upper", MD5.to_uppercase()),
+            format!("\\{MD5}  back\\\\slash\\nnew"),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(
+            split(Some(Algorithm::Md5), &lines),
+            [
+                entry(Algorithm::Md5, 128, MD5, "text"),
+                entry(Algorithm::Md5, 128, MD5, "binary"),
+                entry(Algorithm::Md5, 128, MD5, " leading blank"),
+                entry(Algorithm::Md5, 128, MD5, "indented"),
+                entry(Algorithm::Md5, 128, MD5, "upper"),
+                entry(Algorithm::Md5, 128, MD5, "back\\slash\nnew"),
+            ]
+        );
+    }
+
+    #[test]
+    fn improper_untagged_lines() {
+        let lines = [
+            String::from("bogus"),
+            String::from(MD5),
+            format!("{MD5} "),
+            format!("{MD5}ff  long"),
+            format!("{}  short", &MD5[2..]),
+            format!("{SHA256}  other"),
+            format!("\\{MD5}  bad\\escape"),
+        ];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(split(Some(Algorithm::Md5), &lines), vec![None; lines.len()]);
+        // Untagged lines need an algorithm, cksum only has the tags
+        assert_eq!(split(None, &[&format!("{MD5}  x")]), [None]);
+    }
+
+    #[test]
+    fn bsd_reversed_lines() {
+        // The first line tells the format, `hex  name` then has a name with a blank
+        let lines = [format!("{MD5} one"), format!("{MD5}  two")];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(
+            split(Some(Algorithm::Md5), &lines),
+            [
+                entry(Algorithm::Md5, 128, MD5, "one"),
+                entry(Algorithm::Md5, 128, MD5, " two"),
+            ]
+        );
+        // While after the usual format a reversed line is improper
+        let lines = [format!("{MD5}  one"), format!("{MD5} two")];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(
+            split(Some(Algorithm::Md5), &lines),
+            [entry(Algorithm::Md5, 128, MD5, "one"), None]
+        );
+        // Any other blank than a space is reversed too
+        assert_eq!(
+            split(Some(Algorithm::Md5), &[&format!("{MD5}\ttab")]),
+            [entry(Algorithm::Md5, 128, MD5, "tab")]
+        );
+        // A single blank, the name being the blank
+        assert_eq!(
+            split(Some(Algorithm::Md5), &[&format!("{MD5}  ")]),
+            [entry(Algorithm::Md5, 128, MD5, " ")]
+        );
+    }
+
+    #[test]
+    fn tagged_lines_of_another_algorithm() {
+        // md5sum only takes MD5 lines, cksum -a md5 too
+        let lines = [format!("SHA256 (x) = {SHA256}"), format!("MD5 (x) = {MD5}")];
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        assert_eq!(
+            split(Some(Algorithm::Md5), &lines),
+            [None, entry(Algorithm::Md5, 128, MD5, "x")]
+        );
+    }
+}
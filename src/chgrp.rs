@@ -0,0 +1,120 @@
+/*
+ * chgrp - change group ownership
+ *
+ * A front-end over the core shared with chown (`chown_core.rs`). The group is
+ * looked up by name first and taken as a number otherwise, -c and -v show it
+ * as given.
+ */
+
+use crate::chown_core::{self, Options, Owner};
+use crate::quote;
+use crate::userspec::{parse_id, Accounts, System};
+use clap::Parser;
+use nix::sys::stat::FileStat;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "chgrp", version, long_about = None)]
+#[command(about = "Change the group of each FILE to GROUP, or to that of RFILE with --reference")]
+#[command(override_usage = "chgrp [OPTION]... GROUP FILE...
+       chgrp [OPTION]... --reference=RFILE FILE...")]
+#[command(next_line_help = true, disable_help_flag = true)]
+struct Cli {
+    #[command(flatten)]
+    options: Options,
+    /// The group (unless given with --reference), then the files
+    #[clap(value_name = "GROUP|FILE")]
+    operands: Vec<OsString>,
+}
+
+// What files are changed to for GROUP, None when it's neither a group nor a number
+fn group_owner(group: &[u8], accounts: &dyn Accounts) -> Option<Owner> {
+    // An empty group changes nothing
+    let gid = match accounts.group(group) {
+        _ if group.is_empty() => None,
+        Some(gid) => Some(gid),
+        None => Some(parse_id(group)?),
+    };
+    Some(Owner {
+        gid,
+        group_name: gid.map(|_| String::from_utf8_lossy(group).into_owned()),
+        ..Owner::default()
+    })
+}
+
+// What files are changed to for --reference, `st` being the reference file's
+fn reference_owner(st: &FileStat, accounts: &dyn Accounts) -> Owner {
+    Owner {
+        gid: Some(st.st_gid),
+        group_name: Some(accounts.gid_to_name(st.st_gid)),
+        ..Owner::default()
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args = Cli::parse_from(args);
+    if let Err(status) = chown_core::check("chgrp", &args.options, &args.operands) {
+        return status;
+    }
+
+    let mut operands = args.operands.into_iter();
+    let owner = match &args.options.reference {
+        Some(reference) => match chown_core::reference_stat("chgrp", reference) {
+            Ok(st) => reference_owner(&st, &System),
+            Err(status) => return status,
+        },
+        None => {
+            let group = operands.next().unwrap_or_default();
+            match group_owner(group.as_bytes(), &System) {
+                Some(owner) => owner,
+                None => {
+                    eprintln!("chgrp: invalid group: {}", quote(group.as_bytes()));
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    };
+
+    let files = operands.collect();
+    chown_core::chown_files("chgrp", &args.options, owner, None, None, files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::userspec::mock;
+
+    // The gid and the name -c and -v show for `group`
+    fn group(group: &str) -> Option<(Option<u32>, Option<String>)> {
+        let owner = group_owner(group.as_bytes(), &mock::Accounts)?;
+        assert_eq!((owner.uid, owner.user_name), (None, None));
+        Some((owner.gid, owner.group_name))
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(group("staff"), Some((Some(50), Some("staff".into()))));
+        // A name that is a number is the group's, other numbers are ids
+        assert_eq!(group("42"), Some((Some(8), Some("42".into()))));
+        assert_eq!(group("77"), Some((Some(77), Some("77".into()))));
+        assert_eq!(group("+42"), Some((Some(42), Some("+42".into()))));
+        assert_eq!(group(""), Some((None, None)));
+        assert_eq!(group("nogroup"), None);
+        assert_eq!(group("4294967295"), None);
+    }
+
+    #[test]
+    fn reference() {
+        let mut st: FileStat = unsafe { std::mem::zeroed() };
+        (st.st_uid, st.st_gid) = (1000, 100);
+        let owner = reference_owner(&st, &mock::Accounts);
+        assert_eq!((owner.uid, owner.gid), (None, Some(100)));
+        assert_eq!(owner.group_name.as_deref(), Some("users"));
+
+        st.st_gid = 8765;
+        let owner = reference_owner(&st, &mock::Accounts);
+        assert_eq!(owner.group_name.as_deref(), Some("8765"));
+    }
+}
@@ -0,0 +1,473 @@
+/*
+ * chmod - change file mode bits
+ *
+ * GNU's getopt takes modes like `-w` or `-x,g+s` as options, here they are picked
+ * out of the arguments before clap parses the rest, and like GNU they also turn on
+ * the warning about the umask leaving bits set that the mode would have cleared.
+ *
+ * -R walks directories like fts does: every entry is stat'ed and changed relative
+ * to its parent's fd. Symbolic links are never changed themselves, they are only
+ * followed on the command line (-H, the default), everywhere (-L) or nowhere (-P).
+ */
+
+use crate::modechange::{permissions, ModeChange, CHMOD_MODE_BITS};
+use crate::{errno_desc, parse_args, quote, shell_quote, stdout_file, strerror};
+use clap::Parser;
+use nix::dir::Dir;
+use nix::errno::Errno;
+use nix::fcntl::{AtFlags, OFlag};
+use nix::sys::stat::{fchmodat, fstatat, umask, FchmodatFlags, FileStat, Mode, SFlag};
+use std::ffi::{CStr, CString, OsString};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::MetadataExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "chmod", version, long_about = None)]
+#[command(about = "Change the mode of each FILE to MODE, or to that of RFILE with --reference")]
+#[command(override_usage = "chmod [OPTION]... MODE[,MODE]... FILE...
+       chmod [OPTION]... OCTAL-MODE FILE...
+       chmod [OPTION]... --reference=RFILE FILE...")]
+#[command(after_help = "Each MODE is of the form '[ugoa]*([-+=]([rwxXst]*|[ugo]))+|[-+=][0-7]+'.")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Like verbose but report only when a change is made
+    #[clap(long, short, action, overrides_with = "verbose")]
+    changes: bool,
+    /// Suppress most error messages
+    #[clap(long, short = 'f', visible_alias = "quiet", action)]
+    silent: bool,
+    /// Output a diagnostic for every file processed
+    #[clap(long, short, action, overrides_with = "changes")]
+    verbose: bool,
+    /// Do not treat '/' specially (the default)
+    #[clap(long, action, overrides_with = "preserve_root")]
+    no_preserve_root: bool,
+    /// Fail to operate recursively on '/'
+    #[clap(long, action, overrides_with = "no_preserve_root")]
+    preserve_root: bool,
+    /// Use RFILE's mode instead of MODE values
+    #[clap(long, value_name = "RFILE")]
+    reference: Option<OsString>,
+    /// Change files and directories recursively
+    #[clap(long, short = 'R', action)]
+    recursive: bool,
+    /// With -R, traverse symbolic links to directories given as arguments (the default)
+    #[clap(short = 'H', action, overrides_with_all = ["follow_all", "follow_none"])]
+    follow_args: bool,
+    /// With -R, traverse every symbolic link to a directory
+    #[clap(short = 'L', action, overrides_with_all = ["follow_args", "follow_none"])]
+    follow_all: bool,
+    /// With -R, do not traverse any symbolic links
+    #[clap(short = 'P', action, overrides_with_all = ["follow_args", "follow_all"])]
+    follow_none: bool,
+    /// The mode (unless given as an option or with --reference), then the files
+    #[clap(value_name = "MODE|FILE")]
+    operands: Vec<OsString>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Follow {
+    Args,
+    All,
+    None,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Verbosity {
+    Off,
+    Changes,
+    High,
+}
+
+// How changing a file went, ordered like GNU's: NotApplied and up are successes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    NoStat,
+    Failed,
+    NotApplied,
+    NoChangeRequested,
+    Succeeded,
+}
+
+struct Chmod {
+    change: ModeChange,
+    umask: u32,
+    recursive: bool,
+    follow: Follow,
+    verbosity: Verbosity,
+    silent: bool,
+    diagnose_surprises: bool,
+    // Device and inode of '/' with --preserve-root
+    root: Option<(u64, u64)>,
+    out: BufWriter<File>,
+}
+
+fn is_type(st: &FileStat, kind: SFlag) -> bool {
+    st.st_mode & SFlag::S_IFMT.bits() == kind.bits()
+}
+
+// Like the `%04lo` of GNU's messages
+fn octal_mode(mode: u32) -> String {
+    format!("{:04o}", mode & CHMOD_MODE_BITS)
+}
+
+impl Chmod {
+    fn error(&mut self, message: &str) {
+        let _ = self.out.flush();
+        eprintln!("chmod: {message}");
+    }
+
+    fn describe(&mut self, path: &[u8], status: Status, old: u32, new: u32) {
+        let name = shell_quote(path, true);
+        let (old_mode, old_perms) = (octal_mode(old), permissions(old));
+        let (new_mode, new_perms) = (octal_mode(new), permissions(new));
+        let change = format!("from {old_mode} ({old_perms}) to {new_mode} ({new_perms})");
+        let _ = match status {
+            Status::NoStat => writeln!(self.out, "{name} could not be accessed"),
+            Status::Failed => writeln!(self.out, "failed to change mode of {name} {change}"),
+            Status::NotApplied => writeln!(
+                self.out,
+                "neither symbolic link {name} nor referent has been changed"
+            ),
+            Status::NoChangeRequested => {
+                writeln!(
+                    self.out,
+                    "mode of {name} retained as {new_mode} ({new_perms})"
+                )
+            }
+            Status::Succeeded => writeln!(self.out, "mode of {name} changed {change}"),
+        };
+    }
+
+    // Whether the mode really changed, the special bits may have been silently dropped
+    fn mode_changed(&mut self, dirfd: RawFd, name: &CStr, path: &[u8], old: u32, new: u32) -> bool {
+        let mut new = new;
+        if new & 0o7000 != 0 {
+            match fstatat(dirfd, name, AtFlags::empty()) {
+                Ok(st) => new = st.st_mode,
+                Err(e) => {
+                    if !self.silent {
+                        let name = shell_quote(path, true);
+                        self.error(&format!(
+                            "getting new attributes of {name}: {}",
+                            errno_desc(e)
+                        ));
+                    }
+                    return false;
+                }
+            }
+        }
+        (old ^ new) & CHMOD_MODE_BITS != 0
+    }
+
+    // Change the mode of `name` in `dirfd` and with -R what's below it, `path` is its
+    // name as shown in messages. Returns whether that all went fine
+    fn process(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        path: &[u8],
+        level: usize,
+        ancestors: &mut Vec<(u64, u64)>,
+    ) -> bool {
+        let follow = match self.follow {
+            _ if !self.recursive => true,
+            Follow::Args => level == 0,
+            Follow::All => true,
+            Follow::None => false,
+        };
+        let flags = match follow {
+            true => AtFlags::empty(),
+            false => AtFlags::AT_SYMLINK_NOFOLLOW,
+        };
+        let st = match fstatat(dirfd, name, flags) {
+            Ok(st) => st,
+            Err(e) => {
+                if !self.silent {
+                    let dangling = follow
+                        && e == Errno::ENOENT
+                        && fstatat(dirfd, name, AtFlags::AT_SYMLINK_NOFOLLOW)
+                            .is_ok_and(|st| is_type(&st, SFlag::S_IFLNK));
+                    let name = shell_quote(path, true);
+                    match dangling {
+                        true => self.error(&format!("cannot operate on dangling symlink {name}")),
+                        false => self.error(&format!("cannot access {name}: {}", errno_desc(e))),
+                    }
+                }
+                if self.verbosity == Verbosity::High {
+                    self.describe(path, Status::NoStat, 0, 0);
+                }
+                return false;
+            }
+        };
+
+        let id = (st.st_dev, st.st_ino);
+        let is_dir = is_type(&st, SFlag::S_IFDIR);
+        // A directory containing itself, only expected when following symlinks
+        let cycle = is_dir && ancestors.contains(&id);
+        if cycle && self.follow != Follow::All {
+            self.error(&format!(
+                "WARNING: Circular directory structure.\n\
+                 This almost certainly means that you have a corrupted file system.\n\
+                 NOTIFY YOUR SYSTEM MANAGER.\n\
+                 The following directory is part of the cycle:\n  {}\n",
+                shell_quote(path, false)
+            ));
+            return false;
+        }
+        if self.root == Some(id) {
+            let name = shell_quote(path, true);
+            match path {
+                b"/" => self.error(&format!("it is dangerous to operate recursively on {name}")),
+                _ => self.error(&format!(
+                    "it is dangerous to operate recursively on {name} (same as '/')"
+                )),
+            }
+            self.error("use --no-preserve-root to override this failsafe");
+            return false;
+        }
+
+        let old = st.st_mode;
+        let mut new = old;
+        let mut status = Status::NotApplied;
+        if !is_type(&st, SFlag::S_IFLNK) {
+            new = self.change.adjust(old, is_dir, self.umask).0;
+            let mode = Mode::from_bits_truncate(new);
+            status = match fchmodat(Some(dirfd), name, mode, FchmodatFlags::FollowSymlink) {
+                Ok(()) => Status::Succeeded,
+                Err(e) => {
+                    if !self.silent {
+                        let name = shell_quote(path, true);
+                        self.error(&format!(
+                            "changing permissions of {name}: {}",
+                            errno_desc(e)
+                        ));
+                    }
+                    Status::Failed
+                }
+            };
+        }
+
+        if self.verbosity != Verbosity::Off {
+            if status == Status::Succeeded && !self.mode_changed(dirfd, name, path, old, new) {
+                status = Status::NoChangeRequested;
+            }
+            if status == Status::Succeeded || self.verbosity == Verbosity::High {
+                self.describe(path, status, old, new);
+            }
+        }
+
+        if status >= Status::NoChangeRequested && self.diagnose_surprises {
+            // What the mode gives without the umask, ie. `chmod -w` keeping some `w` bits
+            let naive = self.change.adjust(old, is_dir, 0).0;
+            if new & !naive != 0 {
+                self.error(&format!(
+                    "{}: new permissions are {}, not {}",
+                    shell_quote(path, false),
+                    permissions(new),
+                    permissions(naive)
+                ));
+                status = Status::Failed;
+            }
+        }
+
+        let mut ok = status >= Status::NotApplied;
+        if self.recursive && is_dir && !cycle {
+            ancestors.push(id);
+            ok &= self.descend(dirfd, name, path, level, follow, ancestors);
+            ancestors.pop();
+        }
+        ok
+    }
+
+    fn descend(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        path: &[u8],
+        level: usize,
+        follow: bool,
+        ancestors: &mut Vec<(u64, u64)>,
+    ) -> bool {
+        let mut flags = OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+        if !follow {
+            flags |= OFlag::O_NOFOLLOW;
+        }
+        let entries = Dir::openat(dirfd, name, flags, Mode::empty()).and_then(|mut dir| {
+            let names = dir
+                .iter()
+                .filter(|entry| {
+                    let name = entry.as_ref().map(|e| e.file_name().to_bytes());
+                    !matches!(name, Ok(b"." | b".."))
+                })
+                .map(|entry| entry.map(|e| e.file_name().to_owned()))
+                .collect::<Result<Vec<CString>, Errno>>()?;
+            Ok((dir, names))
+        });
+        let (dir, names) = match entries {
+            Ok(entries) => entries,
+            Err(e) => {
+                if !self.silent {
+                    let name = shell_quote(path, true);
+                    self.error(&format!("cannot read directory {name}: {}", errno_desc(e)));
+                }
+                if self.verbosity == Verbosity::High {
+                    self.describe(path, Status::NoStat, 0, 0);
+                }
+                return false;
+            }
+        };
+
+        // Like fts, don't double a trailing slash
+        let mut prefix = path.to_vec();
+        if !prefix.ends_with(b"/") {
+            prefix.push(b'/');
+        }
+        let mut ok = true;
+        for child in names {
+            let child_path = [&prefix[..], child.to_bytes()].concat();
+            ok &= self.process(dir.as_raw_fd(), &child, &child_path, level + 1, ancestors);
+        }
+        ok
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("chmod: {message}\nTry 'chmod --help' for more information.");
+    ExitCode::FAILURE
+}
+
+// An option getopt would take as part of a mode, like `-w`, `-x,g+s` or `-0`
+fn is_mode_option(arg: &[u8]) -> bool {
+    arg.len() > 1 && arg[0] == b'-' && b"rwxXstugoa,+=01234567".contains(&arg[1])
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    // Modes given as options are joined with commas, like GNU does
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    let mut option_mode: Option<Vec<u8>> = None;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].as_bytes();
+        if arg == b"--" {
+            break;
+        }
+        if arg == b"--reference" {
+            i += 2;
+            continue;
+        }
+        if !is_mode_option(arg) {
+            i += 1;
+            continue;
+        }
+        let arg = args.remove(i).into_vec();
+        option_mode = Some(match option_mode {
+            Some(mode) => [&mode[..], b",", &arg[..]].concat(),
+            None => arg,
+        });
+    }
+    let args: Cli = parse_args(args, 1);
+
+    let diagnose_surprises = option_mode.is_some();
+    let mut operands = args.operands.into_iter().map(OsStringExt::into_vec);
+    let (mode, mode_operand) = match (&args.reference, option_mode) {
+        (Some(_), Some(_)) => return usage_error("cannot combine mode and --reference options"),
+        (Some(_), None) => (None, false),
+        (None, Some(mode)) => (Some(mode), false),
+        (None, None) => (operands.next(), true),
+    };
+    let files: Vec<Vec<u8>> = operands.collect();
+    if files.is_empty() {
+        return match (mode, mode_operand) {
+            (Some(mode), true) => usage_error(&format!("missing operand after {}", quote(&mode))),
+            _ => usage_error("missing operand"),
+        };
+    }
+
+    let (change, umask_value) = match (&args.reference, mode) {
+        (Some(reference), _) => match std::fs::metadata(reference) {
+            Ok(meta) => (ModeChange::from_mode(meta.mode()), 0),
+            Err(e) => {
+                let name = shell_quote(reference.as_bytes(), true);
+                eprintln!(
+                    "chmod: failed to get attributes of {name}: {}",
+                    strerror(&e)
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, mode) => {
+            let mode = mode.unwrap_or_default();
+            let Some(change) = ModeChange::parse(&mode) else {
+                return usage_error(&format!("invalid mode: {}", quote(&mode)));
+            };
+            let mask = umask(Mode::empty());
+            umask(mask);
+            (change, mask.bits())
+        }
+    };
+
+    let root = match args.recursive && args.preserve_root {
+        true => match fstatat(libc::AT_FDCWD, "/", AtFlags::empty()) {
+            Ok(st) => Some((st.st_dev, st.st_ino)),
+            Err(e) => {
+                eprintln!("chmod: failed to get attributes of '/': {}", errno_desc(e));
+                return ExitCode::FAILURE;
+            }
+        },
+        false => None,
+    };
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("chmod: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chmod = Chmod {
+        change,
+        umask: umask_value,
+        recursive: args.recursive,
+        follow: match (args.follow_all, args.follow_none) {
+            (true, _) => Follow::All,
+            (_, true) => Follow::None,
+            _ => Follow::Args,
+        },
+        verbosity: match (args.changes, args.verbose) {
+            (true, _) => Verbosity::Changes,
+            (_, true) => Verbosity::High,
+            _ => Verbosity::Off,
+        },
+        silent: args.silent,
+        diagnose_surprises,
+        root,
+        out: BufWriter::new(stdout),
+    };
+
+    let mut ok = true;
+    for file in files {
+        // fts trims repeated trailing slashes down to one
+        let mut len = file.len();
+        if len > 2 && file[len - 1] == b'/' {
+            while len > 1 && file[len - 2] == b'/' {
+                len -= 1;
+            }
+        }
+        let path = &file[..len];
+        let name = CString::new(path).expect("arguments have no NUL bytes");
+        ok &= chmod.process(libc::AT_FDCWD, &name, path, 0, &mut Vec::new());
+    }
+    if let Err(e) = chmod.out.flush() {
+        eprintln!("chmod: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
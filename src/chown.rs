@@ -0,0 +1,206 @@
+/*
+ * chown - change file owner and group
+ *
+ * The spec is parsed like gnulib's parse_user_spec (in `userspec.rs`) and the files
+ * are changed by the core shared with chgrp (`chown_core.rs`). As in GNU, -c and
+ * -v show the names from the spec, the ids given as numbers are shown as numbers.
+ */
+
+use crate::chown_core::{self, Options, Owner};
+use crate::quote;
+use crate::userspec::{parse_user_spec_with, Accounts, System, UserSpec};
+use clap::Parser;
+use nix::sys::stat::FileStat;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "chown", version, long_about = None)]
+#[command(about = "Change the owner and/or group of each FILE to OWNER and/or GROUP")]
+#[command(override_usage = "chown [OPTION]... [OWNER][:[GROUP]] FILE...
+       chown [OPTION]... --reference=RFILE FILE...")]
+#[command(
+    after_help = "Owner is unchanged if missing. Group is unchanged if missing, but changed
+to login group if implied by a ':' following a symbolic OWNER.
+OWNER and GROUP may be numeric as well as symbolic."
+)]
+#[command(next_line_help = true, disable_help_flag = true)]
+struct Cli {
+    #[command(flatten)]
+    options: Options,
+    /// Change the owner and/or group of each file only if its current owner and/or
+    /// group match those specified here. Either may be omitted
+    #[clap(long, value_name = "CURRENT_OWNER:CURRENT_GROUP")]
+    from: Option<OsString>,
+    /// The owner and group (unless given with --reference), then the files
+    #[clap(value_name = "OWNER|FILE")]
+    operands: Vec<OsString>,
+}
+
+// Parse a spec, GNU only warns about the obsolete '.' separator
+fn parse_spec(spec: &[u8], accounts: &dyn Accounts) -> Result<UserSpec, ExitCode> {
+    match parse_user_spec_with(spec, accounts) {
+        Ok((parsed, dot)) => {
+            if dot {
+                eprintln!("chown: warning: '.' should be ':': {}", quote(spec));
+            }
+            Ok(parsed)
+        }
+        Err(error) => {
+            eprintln!("chown: {error}: {}", quote(spec));
+            Err(ExitCode::FAILURE)
+        }
+    }
+}
+
+// What files are changed to for `spec`
+fn spec_owner(spec: UserSpec) -> Owner {
+    // A group without a user is reported as ":GROUP" ownership
+    let user_name = match (spec.user_name, &spec.group_name) {
+        (None, Some(_)) => Some(String::new()),
+        (user_name, _) => user_name,
+    };
+    Owner {
+        uid: spec.uid,
+        gid: spec.gid,
+        user_name,
+        group_name: spec.group_name,
+    }
+}
+
+// What files are changed to for --reference, `st` being the reference file's
+fn reference_owner(st: &FileStat, accounts: &dyn Accounts) -> Owner {
+    Owner {
+        uid: Some(st.st_uid),
+        gid: Some(st.st_gid),
+        user_name: Some(accounts.uid_to_name(st.st_uid)),
+        group_name: Some(accounts.gid_to_name(st.st_gid)),
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args = Cli::parse_from(args);
+
+    let required = match &args.from {
+        Some(from) => match parse_spec(from.as_bytes(), &System) {
+            Ok(from) => from,
+            Err(status) => return status,
+        },
+        None => UserSpec::default(),
+    };
+    if let Err(status) = chown_core::check("chown", &args.options, &args.operands) {
+        return status;
+    }
+
+    let mut operands = args.operands.into_iter();
+    let owner = match &args.options.reference {
+        Some(reference) => match chown_core::reference_stat("chown", reference) {
+            Ok(st) => reference_owner(&st, &System),
+            Err(status) => return status,
+        },
+        None => {
+            let spec = operands.next().unwrap_or_default();
+            match parse_spec(spec.as_bytes(), &System) {
+                Ok(spec) => spec_owner(spec),
+                Err(status) => return status,
+            }
+        }
+    };
+
+    let files = operands.collect();
+    chown_core::chown_files(
+        "chown",
+        &args.options,
+        owner,
+        required.uid,
+        required.gid,
+        files,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::userspec::mock;
+
+    // The ids and the names -c and -v show for `spec`
+    fn owner(spec: &str) -> (Option<u32>, Option<u32>, Option<String>, Option<String>) {
+        let spec = parse_spec(spec.as_bytes(), &mock::Accounts).expect("valid spec");
+        let owner = spec_owner(spec);
+        (owner.uid, owner.gid, owner.user_name, owner.group_name)
+    }
+
+    fn names(user: Option<&str>, group: Option<&str>) -> (Option<String>, Option<String>) {
+        (user.map(String::from), group.map(String::from))
+    }
+
+    #[test]
+    fn specs() {
+        let (uid, gid, user, group) = owner("alice:staff");
+        assert_eq!((uid, gid), (Some(1000), Some(50)));
+        assert_eq!((user, group), names(Some("alice"), Some("staff")));
+
+        // The group alone is shown as ":GROUP"
+        let (uid, gid, user, group) = owner(":staff");
+        assert_eq!((uid, gid), (None, Some(50)));
+        assert_eq!((user, group), names(Some(""), Some("staff")));
+
+        // "USER:" is the user's login group
+        let (uid, gid, user, group) = owner("alice:");
+        assert_eq!((uid, gid), (Some(1000), Some(100)));
+        assert_eq!((user, group), names(Some("alice"), Some("users")));
+
+        let (uid, gid, user, group) = owner("alice");
+        assert_eq!((uid, gid), (Some(1000), None));
+        assert_eq!((user, group), names(Some("alice"), None));
+    }
+
+    #[test]
+    fn numeric_specs() {
+        // Ids given as numbers have no names, -c and -v show the numbers
+        let (uid, gid, user, group) = owner("123:456");
+        assert_eq!((uid, gid), (Some(123), Some(456)));
+        assert_eq!((user, group), names(None, None));
+
+        // A name that is a number is the account's, unless '+' makes it an id
+        let (uid, gid, _, _) = owner("42:42");
+        assert_eq!((uid, gid), (Some(7), Some(8)));
+        let (uid, gid, _, _) = owner("+42:+42");
+        assert_eq!((uid, gid), (Some(42), Some(42)));
+
+        assert!(parse_spec(b"nobody-here", &mock::Accounts).is_err());
+        assert!(parse_spec(b"alice:nogroup", &mock::Accounts).is_err());
+    }
+
+    #[test]
+    fn from_specs() {
+        // --from is parsed like the spec, either part may be left out
+        let from = parse_spec(b"alice:users", &mock::Accounts).unwrap();
+        assert_eq!((from.uid, from.gid), (Some(1000), Some(100)));
+        let from = parse_spec(b":0", &mock::Accounts).unwrap();
+        assert_eq!((from.uid, from.gid), (None, Some(0)));
+        let from = parse_spec(b"root", &mock::Accounts).unwrap();
+        assert_eq!((from.uid, from.gid), (Some(0), None));
+    }
+
+    #[test]
+    fn reference() {
+        let mut st: FileStat = unsafe { std::mem::zeroed() };
+        (st.st_uid, st.st_gid) = (1000, 50);
+        let owner = reference_owner(&st, &mock::Accounts);
+        assert_eq!((owner.uid, owner.gid), (Some(1000), Some(50)));
+        assert_eq!(
+            (owner.user_name, owner.group_name),
+            names(Some("alice"), Some("staff"))
+        );
+
+        // Ids without a name are shown as numbers
+        (st.st_uid, st.st_gid) = (4321, 8765);
+        let owner = reference_owner(&st, &mock::Accounts);
+        assert_eq!(
+            (owner.user_name, owner.group_name),
+            names(Some("4321"), Some("8765"))
+        );
+    }
+}
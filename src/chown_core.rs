@@ -0,0 +1,1155 @@
+/*
+ * The part of chown and chgrp that changes files, after GNU's chown-core
+ *
+ * Without -R symbolic links given as arguments are dereferenced unless -h is
+ * given. -R walks directories like chmod does, but by default nothing is followed
+ * (-P) and the links met are changed themselves. With -H or -L links are followed
+ * as in chmod and their referents are changed instead, unless -h is given.
+ * Directories are changed after what's below them, like fts' postorder visit.
+ * The system calls go through `Files` and names through `Accounts`, so what gets
+ * changed and reported can be tested without the privileges to change owners.
+ */
+
+use crate::userspec::{Accounts, System};
+use crate::{errno_desc, shell_quote, stdout_file, strerror};
+use clap::ArgAction;
+use nix::dir::Dir;
+use nix::errno::Errno;
+use nix::fcntl::{openat, AtFlags, OFlag};
+use nix::sys::stat::{fstat, fstatat, FileStat, Mode, SFlag};
+use nix::unistd::{close, fchown, fchownat, FchownatFlags, Gid, Uid};
+use std::ffi::{CStr, CString, OsString};
+use std::io::{BufWriter, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+/// The options chown and chgrp have in common
+#[derive(Debug, clap::Args)]
+pub struct Options {
+    /// Like verbose but report only when a change is made
+    #[clap(long, short, action, overrides_with = "verbose")]
+    pub changes: bool,
+    /// Suppress most error messages
+    #[clap(long, short = 'f', visible_alias = "quiet", action)]
+    pub silent: bool,
+    /// Output a diagnostic for every file processed
+    #[clap(long, short, action, overrides_with = "changes")]
+    pub verbose: bool,
+    /// Affect the referent of each symbolic link (the default), rather than the link itself
+    #[clap(long, action, overrides_with = "no_dereference")]
+    pub dereference: bool,
+    /// Affect symbolic links instead of any referenced file
+    #[clap(long, short = 'h', action, overrides_with = "dereference")]
+    pub no_dereference: bool,
+    /// Do not treat '/' specially (the default)
+    #[clap(long, action, overrides_with = "preserve_root")]
+    pub no_preserve_root: bool,
+    /// Fail to operate recursively on '/'
+    #[clap(long, action, overrides_with = "no_preserve_root")]
+    pub preserve_root: bool,
+    /// Use RFILE's owner and group rather than specifying values
+    #[clap(long, value_name = "RFILE")]
+    pub reference: Option<OsString>,
+    /// Operate on files and directories recursively
+    #[clap(long, short = 'R', action)]
+    pub recursive: bool,
+    /// With -R, traverse symbolic links to directories given as arguments
+    #[clap(short = 'H', action, overrides_with_all = ["follow_all", "follow_none"])]
+    pub follow_args: bool,
+    /// With -R, traverse every symbolic link to a directory
+    #[clap(short = 'L', action, overrides_with_all = ["follow_args", "follow_none"])]
+    pub follow_all: bool,
+    /// With -R, do not traverse any symbolic links (the default)
+    #[clap(short = 'P', action, overrides_with_all = ["follow_args", "follow_all"])]
+    pub follow_none: bool,
+    /// Print help
+    #[clap(long, action = ArgAction::Help)]
+    pub help: Option<bool>,
+}
+
+/// What files are changed to, with the names -c and -v show for the ids
+#[derive(Clone, Debug, Default)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+}
+
+/// The system calls changing owners, names being relative to the directory `dirfd`
+pub trait Files {
+    /// An open directory, its fd being the `dirfd` of its entries
+    type Dir: AsRawFd;
+
+    /// stat(2), or lstat(2) unless `follow`
+    fn stat(&mut self, dirfd: RawFd, name: &CStr, follow: bool) -> nix::Result<FileStat>;
+
+    /// chown(2), or lchown(2) unless `follow`. None leaves the id as it is
+    fn chown(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        follow: bool,
+    ) -> nix::Result<()>;
+
+    /// Change the file found as `st` through an fd, only if it's still that file and
+    /// owned as `required` says so a file replaced meanwhile is left alone. Some(false)
+    /// when it was left alone, None when it can't be opened to check
+    fn chown_checked(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        st: &FileStat,
+        owner: (Option<u32>, Option<u32>),
+        required: (Option<u32>, Option<u32>),
+    ) -> nix::Result<Option<bool>>;
+
+    /// Open the directory `name` (not through a link unless `follow`) and list it,
+    /// without `.` and `..`
+    fn read_dir(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        follow: bool,
+    ) -> nix::Result<(Self::Dir, Vec<CString>)>;
+}
+
+/// The system's files
+pub struct SystemFiles;
+
+impl Files for SystemFiles {
+    type Dir = Dir;
+
+    fn stat(&mut self, dirfd: RawFd, name: &CStr, follow: bool) -> nix::Result<FileStat> {
+        let flags = match follow {
+            true => AtFlags::empty(),
+            false => AtFlags::AT_SYMLINK_NOFOLLOW,
+        };
+        fstatat(dirfd, name, flags)
+    }
+
+    fn chown(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        follow: bool,
+    ) -> nix::Result<()> {
+        let flags = match follow {
+            true => FchownatFlags::FollowSymlink,
+            false => FchownatFlags::NoFollowSymlink,
+        };
+        let (uid, gid) = (uid.map(Uid::from_raw), gid.map(Gid::from_raw));
+        fchownat(Some(dirfd), name, uid, gid, flags)
+    }
+
+    fn chown_checked(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        st: &FileStat,
+        owner: (Option<u32>, Option<u32>),
+        required: (Option<u32>, Option<u32>),
+    ) -> nix::Result<Option<bool>> {
+        let mut flags = OFlag::O_NONBLOCK | OFlag::O_NOCTTY | OFlag::O_CLOEXEC;
+        let regular = is_type(st, SFlag::S_IFREG);
+        match is_type(st, SFlag::S_IFDIR) {
+            true => flags |= OFlag::O_DIRECTORY,
+            false if !regular => return Ok(None),
+            false => {}
+        }
+        let fd = match openat(dirfd, name, flags | OFlag::O_RDONLY, Mode::empty()) {
+            Err(Errno::EACCES) if regular => {
+                openat(dirfd, name, flags | OFlag::O_WRONLY, Mode::empty())
+            }
+            fd => fd,
+        };
+        let fd = match fd {
+            Ok(fd) => fd,
+            Err(Errno::EACCES) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let changed = fstat(fd).and_then(|now| {
+            if (now.st_dev, now.st_ino) != (st.st_dev, st.st_ino)
+                || !matches(required.0, now.st_uid)
+                || !matches(required.1, now.st_gid)
+            {
+                return Ok(false);
+            }
+            let (uid, gid) = (owner.0.map(Uid::from_raw), owner.1.map(Gid::from_raw));
+            fchown(fd, uid, gid).map(|()| true)
+        });
+        let closed = close(fd);
+        let changed = changed?;
+        closed.map(|()| Some(changed))
+    }
+
+    fn read_dir(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        follow: bool,
+    ) -> nix::Result<(Dir, Vec<CString>)> {
+        let mut flags = OFlag::O_RDONLY | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC;
+        if !follow {
+            flags |= OFlag::O_NOFOLLOW;
+        }
+        let mut dir = Dir::openat(dirfd, name, flags, Mode::empty())?;
+        let names = dir
+            .iter()
+            .filter(|entry| {
+                let name = entry.as_ref().map(|e| e.file_name().to_bytes());
+                !matches!(name, Ok(b"." | b".."))
+            })
+            .map(|entry| entry.map(|e| e.file_name().to_owned()))
+            .collect::<Result<Vec<CString>, Errno>>()?;
+        Ok((dir, names))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Follow {
+    Args,
+    All,
+    None,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Verbosity {
+    Off,
+    Changes,
+    High,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Succeeded,
+    Failed,
+    NotApplied,
+    NoChangeRequested,
+}
+
+struct Chown<'a, F: Files, W: Write> {
+    prog: &'a str,
+    files: F,
+    accounts: &'a dyn Accounts,
+    owner: Owner,
+    required_uid: Option<u32>,
+    required_gid: Option<u32>,
+    recursive: bool,
+    follow: Follow,
+    affect_referent: bool,
+    verbosity: Verbosity,
+    silent: bool,
+    // Device and inode of '/' with --preserve-root
+    root: Option<(u64, u64)>,
+    out: W,
+}
+
+fn is_type(st: &FileStat, kind: SFlag) -> bool {
+    st.st_mode & SFlag::S_IFMT.bits() == kind.bits()
+}
+
+// `user:group`, or the one that's there
+fn user_group(user: Option<&str>, group: Option<&str>) -> Option<String> {
+    match (user, group) {
+        (Some(user), Some(group)) => Some(format!("{user}:{group}")),
+        (Some(name), None) | (None, Some(name)) => Some(name.to_string()),
+        (None, None) => None,
+    }
+}
+
+fn matches(required: Option<u32>, id: u32) -> bool {
+    required.map_or(true, |required| required == id)
+}
+
+impl<F: Files, W: Write> Chown<'_, F, W> {
+    fn error(&mut self, message: &str) {
+        let _ = self.out.flush();
+        eprintln!("{}: {message}", self.prog);
+    }
+
+    fn describe(&mut self, path: &[u8], status: Status, old: Option<&FileStat>) {
+        let name = shell_quote(path, true);
+        if status == Status::NotApplied {
+            let _ = writeln!(
+                self.out,
+                "neither symbolic link {name} nor referent has been changed"
+            );
+            return;
+        }
+        let user = (self.owner.user_name.clone()).or(self.owner.uid.map(|uid| uid.to_string()));
+        let group = (self.owner.group_name.clone()).or(self.owner.gid.map(|gid| gid.to_string()));
+        let old_user = old
+            .filter(|_| user.is_some())
+            .map(|st| self.accounts.uid_to_name(st.st_uid));
+        let old_group = old
+            .filter(|_| group.is_some())
+            .map(|st| self.accounts.gid_to_name(st.st_gid));
+        let spec = user_group(user.as_deref(), group.as_deref()).unwrap_or_default();
+        let old_spec = user_group(old_user.as_deref(), old_group.as_deref());
+        let what = match (&user, &group) {
+            (Some(_), _) => "ownership",
+            (None, Some(_)) => "group",
+            (None, None) => "",
+        };
+        let _ = match (status, old_spec) {
+            (Status::Succeeded, _) if what.is_empty() => {
+                writeln!(self.out, "no change to ownership of {name}")
+            }
+            (Status::Succeeded, old_spec) => {
+                let old_spec = old_spec.unwrap_or_default();
+                writeln!(
+                    self.out,
+                    "changed {what} of {name} from {old_spec} to {spec}"
+                )
+            }
+            (Status::Failed, _) if what.is_empty() => {
+                writeln!(self.out, "failed to change ownership of {name}")
+            }
+            (Status::Failed, Some(old_spec)) => writeln!(
+                self.out,
+                "failed to change {what} of {name} from {old_spec} to {spec}"
+            ),
+            (Status::Failed, None) => {
+                writeln!(self.out, "failed to change {what} of {name} to {spec}")
+            }
+            (_, _) if what.is_empty() => writeln!(self.out, "ownership of {name} retained"),
+            (_, old_spec) => {
+                let old_spec = old_spec.unwrap_or_default();
+                writeln!(self.out, "{what} of {name} retained as {old_spec}")
+            }
+        };
+    }
+
+    fn root_warning(&mut self, path: &[u8]) {
+        let name = shell_quote(path, true);
+        match path {
+            b"/" => self.error(&format!("it is dangerous to operate recursively on {name}")),
+            _ => self.error(&format!(
+                "it is dangerous to operate recursively on {name} (same as '/')"
+            )),
+        }
+        self.error("use --no-preserve-root to override this failsafe");
+    }
+
+    // Change `name` in `dirfd` through an fd when --from is given, so that a file
+    // replaced since it was checked isn't changed. Ok(None) asks for a plain chown
+    fn restricted_chown(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        st: &FileStat,
+    ) -> nix::Result<Option<bool>> {
+        if self.required_uid.is_none() && self.required_gid.is_none() {
+            return Ok(None);
+        }
+        let owner = (self.owner.uid, self.owner.gid);
+        let required = (self.required_uid, self.required_gid);
+        self.files.chown_checked(dirfd, name, st, owner, required)
+    }
+
+    // Change the owner of `name` in `dirfd`, `st` being what the walk found there
+    fn change(&mut self, dirfd: RawFd, name: &CStr, path: &[u8], st: &FileStat) -> bool {
+        let mut ok = true;
+        let mut file_stats = *st;
+        if self.affect_referent && is_type(st, SFlag::S_IFLNK) {
+            match self.files.stat(dirfd, name, true) {
+                Ok(referent) => file_stats = referent,
+                Err(e) => {
+                    if !self.silent {
+                        let name = shell_quote(path, true);
+                        self.error(&format!("cannot dereference {name}: {}", errno_desc(e)));
+                    }
+                    ok = false;
+                }
+            }
+        }
+        let mut do_chown = ok
+            && matches(self.required_uid, file_stats.st_uid)
+            && matches(self.required_gid, file_stats.st_gid);
+
+        let (uid, gid) = (self.owner.uid, self.owner.gid);
+        let mut symlink_changed = true;
+        if do_chown {
+            let result = match self.affect_referent {
+                false => {
+                    match self.files.chown(dirfd, name, uid, gid, false) {
+                        // Not every system can change the owner of a link
+                        Err(Errno::EOPNOTSUPP) => {
+                            symlink_changed = false;
+                            Ok(())
+                        }
+                        result => result,
+                    }
+                }
+                true => match self.restricted_chown(dirfd, name, &file_stats) {
+                    Ok(None) => self.files.chown(dirfd, name, uid, gid, true),
+                    Ok(Some(true)) => Ok(()),
+                    Ok(Some(false)) => {
+                        do_chown = false;
+                        ok = false;
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                },
+            };
+            if let Err(e) = result {
+                ok = false;
+                if !self.silent {
+                    let name = shell_quote(path, true);
+                    let what = if uid.is_some() { "ownership" } else { "group" };
+                    self.error(&format!("changing {what} of {name}: {}", errno_desc(e)));
+                }
+            }
+        }
+
+        if self.verbosity != Verbosity::Off {
+            let changed = do_chown
+                && ok
+                && symlink_changed
+                && !(matches(self.owner.uid, file_stats.st_uid)
+                    && matches(self.owner.gid, file_stats.st_gid));
+            if changed || self.verbosity == Verbosity::High {
+                let status = match (ok, symlink_changed, changed) {
+                    (false, _, _) => Status::Failed,
+                    (_, false, _) => Status::NotApplied,
+                    (_, _, false) => Status::NoChangeRequested,
+                    _ => Status::Succeeded,
+                };
+                self.describe(path, status, Some(&file_stats));
+            }
+        }
+        ok
+    }
+
+    // Like `change` for what couldn't be looked at
+    fn failed(&mut self, path: &[u8]) -> bool {
+        if self.verbosity == Verbosity::High {
+            self.describe(path, Status::Failed, None);
+        }
+        false
+    }
+
+    // Change `name` in `dirfd` and with -R what's below it, `path` is its name as
+    // shown in messages. Returns whether that all went fine
+    fn process(
+        &mut self,
+        dirfd: RawFd,
+        name: &CStr,
+        path: &[u8],
+        level: usize,
+        ancestors: &mut Vec<(u64, u64)>,
+    ) -> bool {
+        let follow = match self.follow {
+            _ if !self.recursive => false,
+            Follow::Args => level == 0,
+            Follow::All => true,
+            Follow::None => false,
+        };
+        let st = match self.files.stat(dirfd, name, follow) {
+            // A dangling link is still changed, or at least tried
+            Err(Errno::ENOENT) if follow => match self.files.stat(dirfd, name, false) {
+                Ok(st) => return self.change(dirfd, name, path, &st),
+                Err(e) => Err(e),
+            },
+            st => st,
+        };
+        let st = match st {
+            Ok(st) => st,
+            Err(e) => {
+                if !self.silent {
+                    let name = shell_quote(path, true);
+                    self.error(&format!("cannot access {name}: {}", errno_desc(e)));
+                }
+                return self.failed(path);
+            }
+        };
+
+        let id = (st.st_dev, st.st_ino);
+        let is_dir = is_type(&st, SFlag::S_IFDIR);
+        if !self.recursive || !is_dir {
+            return self.change(dirfd, name, path, &st);
+        }
+        // A directory containing itself, only expected when following symlinks
+        if ancestors.contains(&id) {
+            if self.follow != Follow::All {
+                self.error(&format!(
+                    "WARNING: Circular directory structure.\n\
+                     This almost certainly means that you have a corrupted file system.\n\
+                     NOTIFY YOUR SYSTEM MANAGER.\n\
+                     The following directory is part of the cycle:\n  {}\n",
+                    shell_quote(path, false)
+                ));
+                return false;
+            }
+            return self.change(dirfd, name, path, &st);
+        }
+        if self.root == Some(id) {
+            self.root_warning(path);
+            return false;
+        }
+
+        let entries = self.files.read_dir(dirfd, name, follow);
+        let (dir, names) = match entries {
+            Ok(entries) => entries,
+            Err(e) => {
+                if !self.silent {
+                    let name = shell_quote(path, true);
+                    self.error(&format!("cannot read directory {name}: {}", errno_desc(e)));
+                }
+                return self.failed(path);
+            }
+        };
+
+        // Like fts, don't double a trailing slash
+        let mut prefix = path.to_vec();
+        if !prefix.ends_with(b"/") {
+            prefix.push(b'/');
+        }
+        let mut ok = true;
+        ancestors.push(id);
+        for child in names {
+            let child_path = [&prefix[..], child.to_bytes()].concat();
+            ok &= self.process(dir.as_raw_fd(), &child, &child_path, level + 1, ancestors);
+        }
+        ancestors.pop();
+        self.change(dirfd, name, path, &st) && ok
+    }
+}
+
+/// Print a usage error of `prog` and fail
+pub fn usage_error(prog: &str, message: &str) -> ExitCode {
+    eprintln!("{prog}: {message}\nTry '{prog} --help' for more information.");
+    ExitCode::FAILURE
+}
+
+/// Check the options and operands (FILEs after the owner or group unless --reference
+/// is given) before anything is parsed, the error status is set when they're wrong
+pub fn check(prog: &str, options: &Options, operands: &[OsString]) -> Result<(), ExitCode> {
+    if options.recursive && !options.follow_args && !options.follow_all && options.dereference {
+        eprintln!("{prog}: -R --dereference requires either -H or -L");
+        return Err(ExitCode::FAILURE);
+    }
+    let needed = if options.reference.is_some() { 1 } else { 2 };
+    if operands.len() < needed {
+        return Err(match operands.last() {
+            Some(last) => usage_error(
+                prog,
+                &format!("missing operand after {}", crate::quote(last.as_bytes())),
+            ),
+            None => usage_error(prog, "missing operand"),
+        });
+    }
+    Ok(())
+}
+
+/// The owner and group of `--reference`'s file, the error status once reported
+pub fn reference_stat(prog: &str, reference: &OsString) -> Result<FileStat, ExitCode> {
+    nix::sys::stat::stat(reference.as_os_str()).map_err(|e| {
+        let name = shell_quote(reference.as_bytes(), true);
+        eprintln!(
+            "{prog}: failed to get attributes of {name}: {}",
+            errno_desc(e)
+        );
+        ExitCode::FAILURE
+    })
+}
+
+/// Change `files` to `owner` as the options say, only those owned by `required_uid`
+/// and in `required_gid` when given. `prog` is the name errors are prefixed with
+pub fn chown_files(
+    prog: &str,
+    options: &Options,
+    owner: Owner,
+    required_uid: Option<u32>,
+    required_gid: Option<u32>,
+    files: Vec<OsString>,
+) -> ExitCode {
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("{prog}: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let required = (required_uid, required_gid);
+    let out = BufWriter::new(stdout);
+    let Some(mut chown) = Chown::new(prog, options, owner, required, SystemFiles, &System, out)
+    else {
+        return ExitCode::FAILURE;
+    };
+    let mut ok = chown.run(files);
+    if let Err(e) = chown.out.flush() {
+        eprintln!("{prog}: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+impl<'a, F: Files, W: Write> Chown<'a, F, W> {
+    // Set up the walk the options ask for, None once it failed to look at '/' for
+    // --preserve-root
+    fn new(
+        prog: &'a str,
+        options: &Options,
+        owner: Owner,
+        required: (Option<u32>, Option<u32>),
+        mut files: F,
+        accounts: &'a dyn Accounts,
+        out: W,
+    ) -> Option<Self> {
+        let follow = match (options.follow_args, options.follow_all) {
+            (true, _) => Follow::Args,
+            (_, true) => Follow::All,
+            _ => Follow::None,
+        };
+        // Links met on a -R walk without -H or -L are never dereferenced
+        let affect_referent = match options.recursive && follow == Follow::None {
+            true => false,
+            false => !options.no_dereference,
+        };
+
+        let root = match options.recursive && options.preserve_root {
+            true => match files.stat(libc::AT_FDCWD, &CString::new("/").unwrap(), true) {
+                Ok(st) => Some((st.st_dev, st.st_ino)),
+                Err(e) => {
+                    eprintln!("{prog}: failed to get attributes of '/': {}", errno_desc(e));
+                    return None;
+                }
+            },
+            false => None,
+        };
+
+        Some(Chown {
+            prog,
+            files,
+            accounts,
+            owner,
+            required_uid: required.0,
+            required_gid: required.1,
+            recursive: options.recursive,
+            follow,
+            affect_referent,
+            verbosity: match (options.changes, options.verbose) {
+                (true, _) => Verbosity::Changes,
+                (_, true) => Verbosity::High,
+                _ => Verbosity::Off,
+            },
+            silent: options.silent,
+            root,
+            out,
+        })
+    }
+
+    // Change each of `files`, returns whether that all went fine
+    fn run(&mut self, files: Vec<OsString>) -> bool {
+        let mut ok = true;
+        for file in files {
+            // fts trims repeated trailing slashes down to one
+            let file = file.as_bytes();
+            let mut len = file.len();
+            if len > 2 && file[len - 1] == b'/' {
+                while len > 1 && file[len - 2] == b'/' {
+                    len -= 1;
+                }
+            }
+            let path = &file[..len];
+            let name = CString::new(path).expect("arguments have no NUL bytes");
+            ok &= self.process(libc::AT_FDCWD, &name, path, 0, &mut Vec::new());
+        }
+        ok
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use nix::errno::Errno;
+    use nix::sys::stat::{FileStat, SFlag};
+    use std::collections::BTreeMap;
+    use std::ffi::{CStr, CString};
+    use std::os::fd::{AsRawFd, RawFd};
+
+    #[derive(Clone, Copy)]
+    pub enum Kind {
+        File,
+        Dir,
+        Link(&'static str),
+    }
+
+    pub struct Node {
+        pub kind: Kind,
+        pub uid: u32,
+        pub gid: u32,
+        ino: u64,
+    }
+
+    /// Files in memory keyed by their path, the directories read so far being their
+    /// fds from 100 up. The calls changing owners are logged
+    #[derive(Default)]
+    pub struct Files {
+        pub nodes: BTreeMap<String, Node>,
+        dirs: Vec<String>,
+        pub log: Vec<String>,
+    }
+
+    pub struct Dir(RawFd);
+
+    impl AsRawFd for Dir {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0
+        }
+    }
+
+    impl Files {
+        /// Files made of (path, kind, uid, gid)
+        pub fn new(nodes: &[(&str, Kind, u32, u32)]) -> Self {
+            let mut files = Files::default();
+            for (ino, &(path, kind, uid, gid)) in nodes.iter().enumerate() {
+                let ino = ino as u64 + 1;
+                let node = Node {
+                    kind,
+                    uid,
+                    gid,
+                    ino,
+                };
+                files.nodes.insert(path.to_string(), node);
+            }
+            files
+        }
+
+        /// The owner of `path` as "uid:gid"
+        pub fn owner(&self, path: &str) -> String {
+            let node = &self.nodes[path];
+            format!("{}:{}", node.uid, node.gid)
+        }
+
+        fn path(&self, dirfd: RawFd, name: &CStr) -> String {
+            let name = name.to_str().unwrap();
+            match dirfd {
+                libc::AT_FDCWD => name.to_string(),
+                dirfd => format!("{}/{name}", self.dirs[dirfd as usize - 100]),
+            }
+        }
+
+        // The path of what `dirfd` and `name` are, through a link when `follow`
+        fn resolve(&self, dirfd: RawFd, name: &CStr, follow: bool) -> nix::Result<String> {
+            let path = self.path(dirfd, name);
+            match self.nodes.get(&path).ok_or(Errno::ENOENT)?.kind {
+                Kind::Link(target) if follow => match self.nodes.contains_key(target) {
+                    true => Ok(target.to_string()),
+                    false => Err(Errno::ENOENT),
+                },
+                _ => Ok(path),
+            }
+        }
+
+        fn set(&mut self, path: &str, uid: Option<u32>, gid: Option<u32>) {
+            let node = self.nodes.get_mut(path).unwrap();
+            node.uid = uid.unwrap_or(node.uid);
+            node.gid = gid.unwrap_or(node.gid);
+        }
+    }
+
+    // So that tests can look at the files once changed
+    impl<F: super::Files> super::Files for &mut F {
+        type Dir = F::Dir;
+
+        fn stat(&mut self, dirfd: RawFd, name: &CStr, follow: bool) -> nix::Result<FileStat> {
+            (**self).stat(dirfd, name, follow)
+        }
+
+        fn chown(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            uid: Option<u32>,
+            gid: Option<u32>,
+            follow: bool,
+        ) -> nix::Result<()> {
+            (**self).chown(dirfd, name, uid, gid, follow)
+        }
+
+        fn chown_checked(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            st: &FileStat,
+            owner: (Option<u32>, Option<u32>),
+            required: (Option<u32>, Option<u32>),
+        ) -> nix::Result<Option<bool>> {
+            (**self).chown_checked(dirfd, name, st, owner, required)
+        }
+
+        fn read_dir(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            follow: bool,
+        ) -> nix::Result<(F::Dir, Vec<CString>)> {
+            (**self).read_dir(dirfd, name, follow)
+        }
+    }
+
+    impl super::Files for Files {
+        type Dir = Dir;
+
+        fn stat(&mut self, dirfd: RawFd, name: &CStr, follow: bool) -> nix::Result<FileStat> {
+            let node = &self.nodes[&self.resolve(dirfd, name, follow)?];
+            let mut st: FileStat = unsafe { std::mem::zeroed() };
+            st.st_mode = match node.kind {
+                Kind::File => SFlag::S_IFREG,
+                Kind::Dir => SFlag::S_IFDIR,
+                Kind::Link(_) => SFlag::S_IFLNK,
+            }
+            .bits();
+            (st.st_dev, st.st_ino) = (1, node.ino);
+            (st.st_uid, st.st_gid) = (node.uid, node.gid);
+            Ok(st)
+        }
+
+        fn chown(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            uid: Option<u32>,
+            gid: Option<u32>,
+            follow: bool,
+        ) -> nix::Result<()> {
+            let path = self.resolve(dirfd, name, follow)?;
+            let call = if follow { "chown" } else { "lchown" };
+            self.log.push(format!("{call} {}", self.path(dirfd, name)));
+            self.set(&path, uid, gid);
+            Ok(())
+        }
+
+        fn chown_checked(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            st: &FileStat,
+            owner: (Option<u32>, Option<u32>),
+            required: (Option<u32>, Option<u32>),
+        ) -> nix::Result<Option<bool>> {
+            let path = self.resolve(dirfd, name, true)?;
+            let node = &self.nodes[&path];
+            if node.ino != st.st_ino
+                || !super::matches(required.0, node.uid)
+                || !super::matches(required.1, node.gid)
+            {
+                return Ok(Some(false));
+            }
+            self.log.push(format!("fchown {}", self.path(dirfd, name)));
+            self.set(&path, owner.0, owner.1);
+            Ok(Some(true))
+        }
+
+        fn read_dir(
+            &mut self,
+            dirfd: RawFd,
+            name: &CStr,
+            follow: bool,
+        ) -> nix::Result<(Dir, Vec<CString>)> {
+            let path = self.resolve(dirfd, name, follow)?;
+            match self.nodes[&path].kind {
+                Kind::Dir => {}
+                Kind::Link(_) => return Err(Errno::ELOOP),
+                Kind::File => return Err(Errno::ENOTDIR),
+            }
+            let prefix = format!("{path}/");
+            let names = (self.nodes.keys())
+                .filter_map(|key| key.strip_prefix(&prefix))
+                .filter(|child| !child.contains('/'))
+                .map(|child| CString::new(child).unwrap())
+                .collect();
+            self.dirs.push(path);
+            Ok((Dir(self.dirs.len() as RawFd + 99), names))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::Kind::{Dir, File, Link};
+    use super::*;
+    use crate::userspec::mock::Accounts;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    #[command(disable_help_flag = true)]
+    struct Cli {
+        #[command(flatten)]
+        options: Options,
+    }
+
+    // A directory with a file, a subdirectory and a link to another directory, a link
+    // to the directory and a file owned by alice
+    fn tree() -> mock::Files {
+        mock::Files::new(&[
+            ("d", Dir, 0, 0),
+            ("d/f", File, 0, 0),
+            ("d/sub", Dir, 0, 0),
+            ("d/sub/g", File, 0, 0),
+            ("d/link", Link("e"), 0, 0),
+            ("e", Dir, 0, 0),
+            ("e/h", File, 0, 0),
+            ("top", Link("d"), 0, 0),
+            ("owned", File, 1000, 100),
+            ("dangling", Link("nowhere"), 0, 0),
+        ])
+    }
+
+    // Change `paths` in `files` to `owner`, only those owned as `required` says.
+    // Returns whether it all went fine and what -c or -v printed
+    fn chown(
+        files: &mut mock::Files,
+        args: &[&str],
+        owner: Owner,
+        required: (Option<u32>, Option<u32>),
+        paths: &[&str],
+    ) -> (bool, String) {
+        let options = Cli::parse_from([&["chown"], args].concat()).options;
+        let mut out = Vec::new();
+        let mut chown = Chown::new(
+            "chown", &options, owner, required, files, &Accounts, &mut out,
+        )
+        .expect("'/' to be found");
+        let ok = chown.run(paths.iter().map(OsString::from).collect());
+        (ok, String::from_utf8(out).unwrap())
+    }
+
+    fn alice() -> Owner {
+        Owner {
+            uid: Some(1000),
+            gid: Some(100),
+            user_name: Some("alice".to_string()),
+            group_name: Some("users".to_string()),
+        }
+    }
+
+    #[test]
+    fn links() {
+        // The referent by default, the link itself with -h
+        let mut files = tree();
+        assert!(chown(&mut files, &[], alice(), (None, None), &["top"]).0);
+        assert_eq!(files.log, ["chown top"]);
+        assert_eq!(
+            (files.owner("d"), files.owner("top")),
+            ("1000:100".into(), "0:0".into())
+        );
+
+        let mut files = tree();
+        assert!(chown(&mut files, &["-h"], alice(), (None, None), &["top"]).0);
+        assert_eq!(files.log, ["lchown top"]);
+        assert_eq!(
+            (files.owner("d"), files.owner("top")),
+            ("0:0".into(), "1000:100".into())
+        );
+
+        // A dangling link is tried, and fails
+        let mut files = tree();
+        assert!(!chown(&mut files, &[], alice(), (None, None), &["dangling"]).0);
+    }
+
+    #[test]
+    fn recursive() {
+        // -P: links are changed themselves and not followed, children come first
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R"], alice(), (None, None), &["d"]).0);
+        let log = [
+            "lchown d/f",
+            "lchown d/link",
+            "lchown d/sub/g",
+            "lchown d/sub",
+            "lchown d",
+        ];
+        assert_eq!(files.log, log);
+        assert_eq!(files.owner("e"), "0:0");
+
+        // A link given is not followed either
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-P"], alice(), (None, None), &["top"]).0);
+        assert_eq!(files.log, ["lchown top"]);
+    }
+
+    #[test]
+    fn recursive_following() {
+        // -H follows the links given, not those met. What's below a link is logged
+        // under the referent's path
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-H"], alice(), (None, None), &["top"]).0);
+        let log = [
+            "chown d/f",
+            "chown d/link",
+            "chown d/sub/g",
+            "chown d/sub",
+            "chown top",
+        ];
+        assert_eq!(files.log, log);
+        assert_eq!(
+            (files.owner("e"), files.owner("e/h")),
+            ("1000:100".into(), "0:0".into())
+        );
+
+        // -L follows them all
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-L"], alice(), (None, None), &["top"]).0);
+        assert!(files.log.contains(&"chown e/h".to_string()));
+        assert_eq!(files.owner("e/h"), "1000:100");
+
+        // -h still changes the links met with -H and -L
+        let mut files = tree();
+        assert!(
+            chown(
+                &mut files,
+                &["-R", "-L", "-h"],
+                alice(),
+                (None, None),
+                &["d"]
+            )
+            .0
+        );
+        assert!(files.log.contains(&"lchown d/link".to_string()));
+        assert_eq!(files.owner("e"), "0:0");
+    }
+
+    #[test]
+    fn from() {
+        // Only what is owned as --from says is changed
+        let root = Owner {
+            uid: Some(0),
+            ..Owner::default()
+        };
+        let mut files = tree();
+        assert!(
+            chown(
+                &mut files,
+                &[],
+                root.clone(),
+                (Some(1000), None),
+                &["owned", "d/f"]
+            )
+            .0
+        );
+        assert_eq!(files.log, ["fchown owned"]);
+        assert_eq!(files.owner("owned"), "0:100");
+
+        let mut files = tree();
+        let required = (Some(1000), Some(50));
+        assert!(chown(&mut files, &[], root.clone(), required, &["owned"]).0);
+        assert!(files.log.is_empty());
+
+        // The walk goes on below what doesn't match
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R"], alice(), (Some(0), Some(0)), &["d"]).0);
+        assert_eq!(files.log.len(), 5);
+        assert!(files.log.iter().all(|call| call.starts_with("lchown")));
+    }
+
+    #[test]
+    fn group_only() {
+        // What chgrp changes files to: only the group, on a walk like chown's
+        let staff = || Owner {
+            gid: Some(50),
+            group_name: Some("staff".into()),
+            ..Owner::default()
+        };
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-P"], staff(), (None, None), &["d"]).0);
+        assert!(files.log.iter().all(|call| call.starts_with("lchown")));
+        let owners = ["d", "d/f", "d/link", "d/sub", "d/sub/g"].map(|path| files.owner(path));
+        assert_eq!(owners, ["0:50"; 5]);
+        assert_eq!(files.owner("e"), "0:0");
+
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-H"], staff(), (None, None), &["top"]).0);
+        assert_eq!(
+            (files.owner("d"), files.owner("top")),
+            ("0:50".into(), "0:0".into())
+        );
+        assert_eq!(
+            (files.owner("e"), files.owner("e/h")),
+            ("0:50".into(), "0:0".into())
+        );
+
+        let mut files = tree();
+        assert!(chown(&mut files, &["-R", "-L"], staff(), (None, None), &["top"]).0);
+        assert_eq!(files.owner("e/h"), "0:50");
+
+        // Only the files in the required group, as with chown --from=:GROUP
+        let mut files = tree();
+        let paths = ["owned", "d/f"];
+        assert!(chown(&mut files, &[], staff(), (None, Some(100)), &paths).0);
+        assert_eq!(
+            (files.owner("owned"), files.owner("d/f")),
+            ("1000:50".into(), "0:0".into())
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let mut files = tree();
+        let paths = ["d/f", "owned"];
+        let (ok, out) = chown(&mut files, &["-v"], alice(), (None, None), &paths);
+        assert!(ok);
+        assert_eq!(
+            out,
+            "changed ownership of 'd/f' from root:root to alice:users\n\
+             ownership of 'owned' retained as alice:users\n"
+        );
+
+        // -c only reports changes
+        let mut files = tree();
+        let (_, out) = chown(&mut files, &["-c"], alice(), (None, None), &paths);
+        assert_eq!(
+            out,
+            "changed ownership of 'd/f' from root:root to alice:users\n"
+        );
+
+        // With only a group it's the group that's reported, as chgrp does
+        let staff = Owner {
+            gid: Some(50),
+            group_name: Some("staff".into()),
+            ..Owner::default()
+        };
+        let mut files = tree();
+        let (_, out) = chown(&mut files, &["-v"], staff, (None, None), &["owned"]);
+        assert_eq!(out, "changed group of 'owned' from users to staff\n");
+
+        // Ids without names are shown as numbers, failures too
+        let ids = Owner {
+            uid: Some(5),
+            gid: Some(6),
+            ..Owner::default()
+        };
+        let mut files = tree();
+        let (ok, out) = chown(
+            &mut files,
+            &["-v"],
+            ids,
+            (None, None),
+            &["owned", "missing"],
+        );
+        assert!(!ok);
+        assert_eq!(
+            out,
+            "changed ownership of 'owned' from alice:users to 5:6\n\
+             failed to change ownership of 'missing' to 5:6\n"
+        );
+    }
+
+    #[test]
+    fn preserve_root() {
+        let mut files = mock::Files::new(&[("/", Dir, 0, 0)]);
+        let args = ["-R", "--preserve-root"];
+        assert!(!chown(&mut files, &args, alice(), (None, None), &["/"]).0);
+        assert!(files.log.is_empty());
+    }
+}
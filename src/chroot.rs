@@ -0,0 +1,455 @@
+/*
+ * chroot - run command or interactive shell with special root directory
+ *
+ * GNU's sequence: users and groups are looked up before chroot(2), while the system's
+ * databases (and NSS plugins) are at hand, then again inside the new root where ids
+ * may differ, the first results standing in when the second lookup fails. chdir("/")
+ * follows the chroot unless --skip-chdir, then the supplementary groups, the group
+ * and the user are set in that order, the user last since it drops the privilege to
+ * set the others. A NEWROOT resolving to "/" isn't chrooted to, and is the only one
+ * --skip-chdir is allowed with.
+ * Statuses are GNU's: 125 for chroot's own errors, 126 when the command can't be run
+ * and 127 when it isn't found.
+ * The system calls go through `Calls` and lookups through `Accounts`, for tests to
+ * see what's called in which order.
+ */
+
+use crate::userspec::{parse_id, parse_user_spec_with, Accounts, System};
+use crate::{errno_desc, exec_command, exec_status, parse_args, quote, shell_quote};
+use clap::Parser;
+use nix::unistd::{self, Gid, Uid};
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "chroot", version, long_about = None)]
+#[command(about = "Run COMMAND with root directory set to NEWROOT")]
+#[command(override_usage = "chroot [OPTION] NEWROOT [COMMAND [ARG]...]")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Specify supplementary groups as g1,g2,..,gN
+    #[clap(long, value_name = "G_LIST")]
+    groups: Option<OsString>,
+    /// Specify user and group (ID or name) to use
+    #[clap(long, value_name = "USER:GROUP")]
+    userspec: Option<OsString>,
+    /// Do not change working directory to '/'
+    #[clap(long, action)]
+    skip_chdir: bool,
+    /// NEWROOT, then the command to run and its arguments
+    #[clap(hide = true, trailing_var_arg = true)]
+    operands: Vec<OsString>,
+}
+
+const EXIT_CANCELED: u8 = 125;
+
+/// The calls changing the root and the credentials of the process
+trait Calls {
+    /// Whether `dir` is the current root, as far as canonicalizing it goes
+    fn is_root(&self, dir: &OsStr) -> bool;
+    fn chroot(&mut self, dir: &OsStr) -> nix::Result<()>;
+    fn chdir_root(&mut self) -> nix::Result<()>;
+    fn setgroups(&mut self, gids: &[u32]) -> nix::Result<()>;
+    fn setgid(&mut self, gid: u32) -> nix::Result<()>;
+    fn setuid(&mut self, uid: u32) -> nix::Result<()>;
+}
+
+struct SystemCalls;
+
+impl Calls for SystemCalls {
+    fn is_root(&self, dir: &OsStr) -> bool {
+        std::fs::canonicalize(dir).is_ok_and(|dir| dir.as_os_str() == "/")
+    }
+
+    fn chroot(&mut self, dir: &OsStr) -> nix::Result<()> {
+        unistd::chroot(dir)
+    }
+
+    fn chdir_root(&mut self) -> nix::Result<()> {
+        unistd::chdir("/")
+    }
+
+    fn setgroups(&mut self, gids: &[u32]) -> nix::Result<()> {
+        let gids: Vec<Gid> = gids.iter().map(|&gid| Gid::from_raw(gid)).collect();
+        unistd::setgroups(&gids)
+    }
+
+    fn setgid(&mut self, gid: u32) -> nix::Result<()> {
+        unistd::setgid(Gid::from_raw(gid))
+    }
+
+    fn setuid(&mut self, uid: u32) -> nix::Result<()> {
+        unistd::setuid(Uid::from_raw(uid))
+    }
+}
+
+/// Who the command runs as, what's unset being left as it is
+#[derive(Debug, Default)]
+struct Credentials {
+    uid: Option<u32>,
+    gid: Option<u32>,
+    // For the user's supplementary groups
+    user_name: Option<String>,
+    // Supplementary groups, empty until some were found
+    groups: Vec<u32>,
+}
+
+impl Credentials {
+    // The login group of the user when there's no group, and the user's name for
+    // getgrouplist when --groups doesn't say. False when the user has no entry
+    fn user_entry(&mut self, groups: Option<&[u8]>, accounts: &dyn Accounts) -> bool {
+        let Some(uid) = self.uid.filter(|_| groups.is_none() || self.gid.is_none()) else {
+            return true;
+        };
+        match accounts.user_by_id(uid) {
+            Some((name, gid)) => {
+                self.gid = self.gid.or(Some(gid));
+                self.user_name = Some(name);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// The ids of --groups, names looked up first unless the entry is a number with a '+'.
+// Every bad entry is reported with `show_errors`, otherwise the first one ends it
+fn parse_groups(list: &[u8], show_errors: bool, accounts: &dyn Accounts) -> Option<Vec<u32>> {
+    let mut gids = Vec::new();
+    let mut ok = true;
+    for entry in list.split(|&c| c == b',').filter(|entry| !entry.is_empty()) {
+        let gid = match parse_id(entry) {
+            Some(gid) => {
+                let blanks = entry.iter().take_while(|c| c.is_ascii_whitespace()).count();
+                let entry = &entry[blanks..];
+                match entry.first() {
+                    // A group can be named like a number
+                    Some(b'+') => Some(gid),
+                    _ => Some(accounts.group(entry).unwrap_or(gid)),
+                }
+            }
+            None => accounts.group(entry),
+        };
+        match gid {
+            Some(gid) => gids.push(gid),
+            None if show_errors => {
+                eprintln!("chroot: invalid group {}", quote(entry));
+                ok = false;
+            }
+            None => return None,
+        }
+    }
+    if ok && gids.is_empty() && show_errors {
+        eprintln!("chroot: invalid group list {}", quote(list));
+    }
+    (ok && !gids.is_empty()).then_some(gids)
+}
+
+fn die(message: &str) -> ExitCode {
+    eprintln!("chroot: {message}");
+    ExitCode::from(EXIT_CANCELED)
+}
+
+// Change the root to `newroot` and set the credentials as `args` say, the exit status
+// once that failed
+fn enter(
+    args: &Cli,
+    newroot: &OsStr,
+    calls: &mut dyn Calls,
+    accounts: &dyn Accounts,
+) -> Result<(), ExitCode> {
+    // `USER:` is the same as `USER`, the login group is the default anyway
+    let userspec = args.userspec.as_ref().map(|spec| {
+        let spec = spec.as_bytes();
+        spec.strip_suffix(b":").unwrap_or(spec)
+    });
+    let groups = args.groups.as_ref().map(|groups| groups.as_bytes());
+
+    let mut credentials = Credentials::default();
+    if !calls.is_root(newroot) {
+        if args.skip_chdir {
+            eprintln!(
+                "chroot: option --skip-chdir only permitted if NEWROOT is old '/'\n\
+                Try 'chroot --help' for more information."
+            );
+            return Err(ExitCode::from(EXIT_CANCELED));
+        }
+        // Failures only count inside the new root
+        if let Some(Ok((spec, _))) = userspec.map(|spec| parse_user_spec_with(spec, accounts)) {
+            credentials.uid = spec.uid;
+            credentials.gid = spec.gid;
+        }
+        credentials.user_entry(groups, accounts);
+        match (groups, &credentials.user_name) {
+            (Some(list), _) if !list.is_empty() => {
+                credentials.groups = parse_groups(list, false, accounts).unwrap_or_default();
+            }
+            (None, Some(name)) => {
+                let gids = accounts.group_list(name, credentials.gid);
+                credentials.groups = gids.unwrap_or_default();
+            }
+            _ => {}
+        }
+
+        if let Err(e) = calls.chroot(newroot) {
+            let newroot = shell_quote(newroot.as_bytes(), true);
+            return Err(die(&format!(
+                "cannot change root directory to {newroot}: {}",
+                errno_desc(e)
+            )));
+        }
+    }
+    if !args.skip_chdir {
+        if let Err(e) = calls.chdir_root() {
+            return Err(die(&format!(
+                "cannot chdir to root directory: {}",
+                errno_desc(e)
+            )));
+        }
+    }
+
+    // The same lookups where it matters
+    if let Some(spec) = userspec {
+        match parse_user_spec_with(spec, accounts) {
+            Ok((spec, dot)) => {
+                if dot {
+                    eprintln!("chroot: warning: '.' should be ':'");
+                }
+                credentials.uid = spec.uid.or(credentials.uid);
+                credentials.gid = spec.gid.or(credentials.gid);
+            }
+            Err(message) => return Err(die(message)),
+        }
+    }
+    if !credentials.user_entry(groups, accounts) && credentials.gid.is_none() {
+        let uid = credentials.uid.unwrap();
+        return Err(die(&format!("no group specified for unknown uid: {uid}")));
+    }
+    match (groups, &credentials.user_name) {
+        (Some(list), _) if !list.is_empty() => {
+            match parse_groups(list, credentials.groups.is_empty(), accounts) {
+                Some(gids) => credentials.groups = gids,
+                // What was found outside will do
+                None if !credentials.groups.is_empty() => {}
+                None => return Err(ExitCode::from(EXIT_CANCELED)),
+            }
+        }
+        (None, Some(name)) => match accounts.group_list(name, credentials.gid) {
+            Ok(gids) if !gids.is_empty() => credentials.groups = gids,
+            _ if !credentials.groups.is_empty() => {}
+            Ok(_) => return Err(die("failed to get supplemental groups")),
+            Err(e) => {
+                let message = format!("failed to get supplemental groups: {}", errno_desc(e));
+                return Err(die(&message));
+            }
+        },
+        _ => {}
+    }
+
+    if credentials.uid.is_some() || groups.is_some() {
+        if let Err(e) = calls.setgroups(&credentials.groups) {
+            let message = format!("failed to set supplemental groups: {}", errno_desc(e));
+            return Err(die(&message));
+        }
+    }
+    if let Some(gid) = credentials.gid {
+        if let Err(e) = calls.setgid(gid) {
+            return Err(die(&format!("failed to set group-ID: {}", errno_desc(e))));
+        }
+    }
+    if let Some(uid) = credentials.uid {
+        if let Err(e) = calls.setuid(uid) {
+            return Err(die(&format!("failed to set user-ID: {}", errno_desc(e))));
+        }
+    }
+    Ok(())
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, EXIT_CANCELED);
+    let Some((newroot, command)) = args.operands.split_first() else {
+        eprintln!("chroot: missing operand\nTry 'chroot --help' for more information.");
+        return ExitCode::from(EXIT_CANCELED);
+    };
+    if let Err(status) = enter(&args, newroot, &mut SystemCalls, &System) {
+        return status;
+    }
+
+    // An interactive shell by default
+    let command = match command {
+        [] => {
+            let shell = std::env::var_os("SHELL").unwrap_or_else(|| OsString::from("/bin/sh"));
+            vec![shell, OsString::from("-i")]
+        }
+        command => command.to_vec(),
+    };
+    let e = exec_command(&command);
+    eprintln!(
+        "chroot: failed to run command {}: {}",
+        quote(command[0].as_bytes()),
+        errno_desc(e)
+    );
+    exec_status(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::userspec::mock::Accounts;
+
+    // Logs the calls, failing `fail` with EPERM. "/" is the only root
+    #[derive(Default)]
+    struct MockCalls {
+        log: Vec<String>,
+        fail: Option<&'static str>,
+    }
+
+    impl MockCalls {
+        fn call(&mut self, name: &'static str, arg: String) -> nix::Result<()> {
+            self.log.push(format!("{name} {arg}"));
+            match self.fail == Some(name) {
+                true => Err(nix::Error::EPERM),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl Calls for MockCalls {
+        fn is_root(&self, dir: &OsStr) -> bool {
+            dir == "/"
+        }
+
+        fn chroot(&mut self, dir: &OsStr) -> nix::Result<()> {
+            self.call("chroot", dir.to_string_lossy().into_owned())
+        }
+
+        fn chdir_root(&mut self) -> nix::Result<()> {
+            self.call("chdir", "/".into())
+        }
+
+        fn setgroups(&mut self, gids: &[u32]) -> nix::Result<()> {
+            let gids: Vec<String> = gids.iter().map(u32::to_string).collect();
+            self.call("setgroups", gids.join(","))
+        }
+
+        fn setgid(&mut self, gid: u32) -> nix::Result<()> {
+            self.call("setgid", gid.to_string())
+        }
+
+        fn setuid(&mut self, uid: u32) -> nix::Result<()> {
+            self.call("setuid", uid.to_string())
+        }
+    }
+
+    // The calls made for `args` with `fail` failing, and the exit status when it failed
+    fn chroot(args: &[&str], fail: Option<&'static str>) -> (Vec<String>, Option<ExitCode>) {
+        let args = Cli::try_parse_from([&["chroot"], args].concat()).unwrap();
+        let mut calls = MockCalls {
+            fail,
+            ..MockCalls::default()
+        };
+        let status = enter(&args, &args.operands[0], &mut calls, &Accounts).err();
+        (calls.log, status)
+    }
+
+    fn ok(log: &[&str]) -> (Vec<String>, Option<ExitCode>) {
+        (log.iter().map(|call| call.to_string()).collect(), None)
+    }
+
+    fn canceled(log: &[&str]) -> (Vec<String>, Option<ExitCode>) {
+        let (log, _) = ok(log);
+        (log, Some(ExitCode::from(EXIT_CANCELED)))
+    }
+
+    #[test]
+    fn root_only() {
+        assert_eq!(chroot(&["/new"], None), ok(&["chroot /new", "chdir /"]));
+        // "/" isn't chrooted to, and only there --skip-chdir is allowed
+        assert_eq!(chroot(&["/"], None), ok(&["chdir /"]));
+        assert_eq!(chroot(&["--skip-chdir", "/"], None), ok(&[]));
+        assert_eq!(chroot(&["--skip-chdir", "/new"], None), canceled(&[]));
+        assert_eq!(
+            chroot(&["/new"], Some("chroot")),
+            canceled(&["chroot /new"])
+        );
+    }
+
+    #[test]
+    fn userspec() {
+        // The user's login and supplementary groups, the user set last
+        let calls = [
+            "chroot /new",
+            "chdir /",
+            "setgroups 100,50",
+            "setgid 100",
+            "setuid 1000",
+        ];
+        assert_eq!(chroot(&["--userspec=alice", "/new"], None), ok(&calls));
+        assert_eq!(chroot(&["--userspec=alice:", "/new"], None), ok(&calls));
+        let calls = [
+            "chroot /new",
+            "chdir /",
+            "setgroups 50",
+            "setgid 50",
+            "setuid 1000",
+        ];
+        assert_eq!(
+            chroot(&["--userspec=alice:staff", "/new"], None),
+            ok(&calls)
+        );
+
+        // Ids without entries have no supplementary groups, and need a group
+        let calls = [
+            "chroot /new",
+            "chdir /",
+            "setgroups ",
+            "setgid 55",
+            "setuid 1234",
+        ];
+        assert_eq!(chroot(&["--userspec=1234:55", "/new"], None), ok(&calls));
+        let calls = ["chroot /new", "chdir /"];
+        assert_eq!(chroot(&["--userspec=1234", "/new"], None), canceled(&calls));
+        assert_eq!(
+            chroot(&["--userspec=nobody", "/new"], None),
+            canceled(&calls)
+        );
+
+        // Nothing is set once a call failed
+        let calls = ["chroot /new", "chdir /", "setgroups 100,50", "setgid 100"];
+        let userspec = ["--userspec=alice", "/new"];
+        assert_eq!(chroot(&userspec, Some("setgid")), canceled(&calls));
+    }
+
+    #[test]
+    fn groups() {
+        // Names first unless a number has a '+', empty entries skipped
+        let args = ["--userspec=alice", "--groups=staff,,+42,42", "/new"];
+        let calls = [
+            "chroot /new",
+            "chdir /",
+            "setgroups 50,42,8",
+            "setgid 100",
+            "setuid 1000",
+        ];
+        assert_eq!(chroot(&args, None), ok(&calls));
+        // Without a user only the groups are set
+        let args = ["--groups=root", "/new"];
+        assert_eq!(
+            chroot(&args, None),
+            ok(&["chroot /new", "chdir /", "setgroups 0"])
+        );
+
+        let args = ["--groups=staff,nogroup", "/new"];
+        assert_eq!(chroot(&args, None), canceled(&["chroot /new", "chdir /"]));
+    }
+
+    #[test]
+    fn skip_chdir() {
+        let args = ["--skip-chdir", "--userspec=0:0", "/"];
+        let calls = ["setgroups 0", "setgid 0", "setuid 0"];
+        assert_eq!(chroot(&args, None), ok(&calls));
+        let args = ["--skip-chdir", "--userspec=0:0", "/new"];
+        assert_eq!(chroot(&args, None), canceled(&[]));
+    }
+}
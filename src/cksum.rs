@@ -0,0 +1,122 @@
+/*
+ * cksum - compute and verify file checksums
+ *
+ * Defaults to the POSIX CRC, `-a` selects any of the digests shared with the *sum
+ * utilities (see checksum.rs). Digests are printed tagged unless --untagged, and
+ * `--check` without `-a` detects the algorithm from each line's tag.
+ */
+
+use crate::checksum::{self, Algorithm, Config};
+use crate::{parse_args, reset_sigpipe, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use std::ffi::OsString;
+use std::io::BufWriter;
+use std::os::unix::ffi::OsStringExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "cksum", version, long_about = None)]
+#[command(about = "Print or verify checksums, by default the 32 bit CRC")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Select the digest type to use
+    #[clap(long, short, value_enum, value_name = "TYPE")]
+    algorithm: Option<Algorithm>,
+    /// Read checksums from the FILEs and check them
+    #[clap(long, short, action)]
+    check: bool,
+    /// Digest length in bits, a multiple of 8 no larger than 512 (blake2b only)
+    #[clap(long, short, value_name = "BITS", allow_hyphen_values = true)]
+    length: Option<String>,
+    /// Create a BSD style checksum (the default)
+    #[clap(long, action, overrides_with = "untagged")]
+    tag: bool,
+    /// Create a reversed style checksum, without digest type
+    #[clap(long, action, overrides_with = "tag")]
+    untagged: bool,
+    /// End each output line with NUL, not newline, and disable file name escaping
+    #[clap(long, short, action)]
+    zero: bool,
+    /// Don't fail or report status for missing files
+    #[clap(long, action)]
+    ignore_missing: bool,
+    /// Don't print OK for each successfully verified file
+    #[clap(long, action, overrides_with_all = ["status", "warn"])]
+    quiet: bool,
+    /// Don't output anything, the exit status shows success
+    #[clap(long, action, overrides_with_all = ["quiet", "warn"])]
+    status: bool,
+    /// Exit non zero for improperly formatted checksum lines
+    #[clap(long, action)]
+    strict: bool,
+    /// Warn about improperly formatted checksum lines
+    #[clap(long, short, action, overrides_with_all = ["quiet", "status"])]
+    warn: bool,
+    /// Files to checksum, stdin by default
+    #[clap(action = ArgAction::Append)]
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("cksum: {message}\nTry 'cksum --help' for more information.");
+    ExitCode::FAILURE
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    let algorithm = args.algorithm;
+
+    let mut bits = algorithm.unwrap_or(Algorithm::Crc).bits();
+    if let Some(length) = &args.length {
+        bits = match checksum::parse_length(length) {
+            Ok(bits) => bits,
+            Err(e) => {
+                e.lines().for_each(|line| eprintln!("cksum: {line}"));
+                return ExitCode::FAILURE;
+            }
+        };
+        if algorithm != Some(Algorithm::Blake2b) {
+            eprintln!("cksum: --length is only supported with --algorithm=blake2b");
+            return ExitCode::FAILURE;
+        }
+    }
+    if args.check && algorithm.is_some_and(Algorithm::is_legacy) {
+        eprintln!("cksum: --check is not supported with --algorithm={{bsd,sysv,crc}}");
+        return ExitCode::FAILURE;
+    }
+
+    let config = Config {
+        prog: "cksum",
+        algorithm: match args.check {
+            true => algorithm,
+            false => Some(algorithm.unwrap_or(Algorithm::Crc)),
+        },
+        bits,
+        tag: !args.untagged,
+        binary: false,
+        zero: args.zero,
+        check: args.check,
+        ignore_missing: args.ignore_missing,
+        quiet: args.quiet,
+        status: args.status,
+        strict: args.strict,
+        warn: args.warn,
+    };
+    if let Err(e) = config.validate() {
+        return usage_error(&e);
+    }
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("cksum: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files: Vec<Vec<u8>> = args.files.into_iter().map(OsStringExt::into_vec).collect();
+    checksum::run(&config, &files, &mut out)
+}
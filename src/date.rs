@@ -0,0 +1,418 @@
+/*
+ * date - print or set the system date and time
+ *
+ * The output goes through strftime.rs, with GNU's extensions to the conversions, and
+ * dates given with -d, -f or -s are parsed by parse_datetime.rs, both in the zone
+ * TZ says; -u is TZ=UTC0 like POSIX has it. An operand not starting with '+' is the
+ * POSIX MMDDhhmm[[CC]YY][.ss] date to set, which is done with clock_settime(2), the
+ * date being printed even when that's refused.
+ */
+
+use crate::parse_datetime::parse_datetime;
+use crate::strftime::{strftime, Time};
+use crate::{errno_desc, parse_args, quote, reset_sigpipe, shell_quote, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use nix::sys::time::TimeSpec;
+use nix::time::{clock_gettime, clock_settime, ClockId};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "date", version, long_about = None)]
+#[command(
+    about = "Display the current time in the given FORMAT, or set the system date. \
+FORMAT takes strftime(3)'s conversions with GNU's extensions: %N nanoseconds, %:z \
++hh:mm numeric time zone (%::z +hh:mm:ss, %:::z as precise as necessary), %q quarter of \
+the year, %P lowercase am or pm and %s seconds since the Epoch. After '%' may come the \
+flags '-' (do not pad the field), '_' (pad with spaces), '0' (pad with zeros), '+' (pad \
+with zeros and put '+' before future years with more than 4 digits), '^' (use upper \
+case) and '#' (use the opposite case), then a field width"
+)]
+#[command(override_usage = "date [OPTION]... [+FORMAT]
+       date [-u|--utc|--universal] [MMDDhhmm[[CC]YY][.ss]]")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Display time described by STRING, not 'now'
+    #[clap(long, short, value_name = "STRING", allow_hyphen_values = true)]
+    #[clap(action = ArgAction::Set, overrides_with = "date")]
+    date: Option<OsString>,
+    /// Like --date; once for each line of DATEFILE
+    #[clap(long, short, value_name = "DATEFILE")]
+    file: Option<OsString>,
+    /// Output date/time in ISO 8601 format. FMT='date' for date only (the default),
+    /// 'hours', 'minutes', 'seconds', or 'ns' for date and time to the indicated
+    /// precision. Example: 2006-08-14T02:34:56-06:00
+    #[clap(
+        long = "iso-8601",
+        short = 'I',
+        value_name = "FMT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "date"
+    )]
+    iso_8601: Option<String>,
+    /// Output date and time in RFC 5322 format. Example: Mon, 14 Aug 2006 02:34:56 -0600
+    #[clap(long = "rfc-email", short = 'R', aliases = ["rfc-822", "rfc-2822"], action)]
+    rfc_email: bool,
+    /// Output date/time in RFC 3339 format. FMT='date', 'seconds', or 'ns' for date and
+    /// time to the indicated precision. Example: 2006-08-14 02:34:56-06:00
+    #[clap(long = "rfc-3339", value_name = "FMT")]
+    rfc_3339: Option<String>,
+    /// Display the last modification time of FILE
+    #[clap(long, short, value_name = "FILE")]
+    reference: Option<OsString>,
+    /// Set time described by STRING
+    #[clap(long, short, value_name = "STRING", allow_hyphen_values = true)]
+    set: Option<OsString>,
+    /// Print or set Coordinated Universal Time (UTC)
+    #[clap(long, short, visible_alias = "universal", action)]
+    utc: bool,
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+const DEFAULT_FORMAT: &[u8] = b"%a %b %e %H:%M:%S %Z %Y";
+const RFC_EMAIL_FORMAT: &[u8] = b"%a, %d %b %Y %H:%M:%S %z";
+
+// The precisions of -I in argmatch's order, with their formats
+const ISO_8601_FORMATS: [(&str, &[u8]); 5] = [
+    ("hours", b"%Y-%m-%dT%H%:z"),
+    ("minutes", b"%Y-%m-%dT%H:%M%:z"),
+    ("date", b"%Y-%m-%d"),
+    ("seconds", b"%Y-%m-%dT%H:%M:%S%:z"),
+    ("ns", b"%Y-%m-%dT%H:%M:%S,%N%:z"),
+];
+const RFC_3339_FORMATS: [(&str, &[u8]); 3] = [
+    ("date", b"%Y-%m-%d"),
+    ("seconds", b"%Y-%m-%d %H:%M:%S%:z"),
+    ("ns", b"%Y-%m-%d %H:%M:%S.%N%:z"),
+];
+
+// Exact names or unambiguous abbreviations of them like argmatch, else its error
+fn match_format(
+    value: &str,
+    option: &str,
+    formats: &[(&str, &'static [u8])],
+) -> Result<&'static [u8], String> {
+    if let Some(&(_, format)) = formats.iter().find(|(name, _)| *name == value) {
+        return Ok(format);
+    }
+    let mut matches = formats.iter().filter(|(name, _)| name.starts_with(value));
+    let problem = match (matches.next(), matches.next()) {
+        (Some(&(_, format)), None) => return Ok(format),
+        (Some(_), Some(_)) => "ambiguous",
+        _ => "invalid",
+    };
+    let mut message = format!(
+        "{problem} argument {} for {}\nValid arguments are:",
+        quote(value.as_bytes()),
+        quote(option.as_bytes())
+    );
+    for (name, _) in formats {
+        message.push_str(&format!("\n  - '{name}'"));
+    }
+    Err(message)
+}
+
+// A POSIX MMDDhhmm[[CC]YY][.ss] date like gnulib's posixtime, seconds of 60 being
+// taken as the next minute's first
+fn posix_time(s: &[u8], now: i64) -> Option<i64> {
+    let (digits, seconds) = match s.iter().position(|&c| c == b'.') {
+        Some(dot) => (&s[..dot], Some(&s[dot + 1..])),
+        None => (s, None),
+    };
+    if !(8..=12).contains(&digits.len()) || digits.len() % 2 != 0 {
+        return None;
+    }
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let pairs: Vec<i32> = digits
+        .chunks(2)
+        .map(|pair| ((pair[0] - b'0') * 10 + pair[1] - b'0') as i32)
+        .collect();
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    (tm.tm_mon, tm.tm_mday, tm.tm_hour, tm.tm_min) = (pairs[0] - 1, pairs[1], pairs[2], pairs[3]);
+    tm.tm_year = match pairs[4..] {
+        [year] if year <= 68 => year + 100,
+        [year] => year,
+        [century, year] => century * 100 + year - 1900,
+        _ => {
+            let mut today: libc::tm = unsafe { std::mem::zeroed() };
+            if unsafe { libc::localtime_r(&now, &mut today) }.is_null() {
+                return None;
+            }
+            today.tm_year
+        }
+    };
+    tm.tm_sec = match seconds {
+        None => 0,
+        Some(&[tens, units]) if tens.is_ascii_digit() && units.is_ascii_digit() => {
+            ((tens - b'0') * 10 + units - b'0') as i32
+        }
+        Some(_) => return None,
+    };
+    let wanted = tm;
+    tm.tm_isdst = -1;
+    tm.tm_wday = -1;
+    let t = unsafe { libc::mktime(&mut tm) };
+    if tm.tm_wday == -1 {
+        return None;
+    }
+    let fields = |tm: &libc::tm| {
+        (
+            tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec,
+        )
+    };
+    if fields(&wanted) == fields(&tm) {
+        return Some(t);
+    }
+    // Even in zones without leap seconds
+    if wanted.tm_sec != 60 {
+        return None;
+    }
+    let mut s = s.to_vec();
+    let length = s.len();
+    s[length - 2..].copy_from_slice(b"59");
+    posix_time(&s, now).map(|t| t + 1)
+}
+
+// Write the time as `format` says, false when it's out of the range of broken down
+// times
+fn show_date(out: &mut impl Write, format: &[u8], (sec, nsec): (i64, u32)) -> io::Result<bool> {
+    let Some(time) = Time::local(sec, nsec) else {
+        eprintln!(
+            "date: time {} is out of range",
+            quote(sec.to_string().as_bytes())
+        );
+        return Ok(false);
+    };
+    let mut line = strftime(format, &time);
+    line.push(b'\n');
+    out.write_all(&line)?;
+    Ok(true)
+}
+
+// -f: the date on each line of `file`, invalid ones being reported and skipped
+fn batch_convert(
+    out: &mut impl Write,
+    file: &[u8],
+    format: &[u8],
+    now: (i64, u32),
+) -> io::Result<bool> {
+    let input: Box<dyn BufRead> = match file {
+        b"-" => Box::new(io::stdin().lock()),
+        _ => match File::open(std::ffi::OsStr::from_bytes(file)) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("date: {}: {}", shell_quote(file, false), strerror(&e));
+                return Ok(false);
+            }
+        },
+    };
+    let mut ok = true;
+    for line in input.split(b'\n') {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("date: {}: {}", shell_quote(file, false), strerror(&e));
+                return Ok(false);
+            }
+        };
+        match parse_datetime(&line, now) {
+            Some(when) => ok &= show_date(out, format, when)?,
+            None => {
+                out.flush()?;
+                eprintln!("date: invalid date {}", quote(&line));
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+// Rewrite `-IFMT` into `--iso-8601=FMT`, the value being attached like getopt's optional
+// arguments have it
+fn attached_iso_8601(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut options = true;
+    args.into_iter()
+        .map(|arg| {
+            let bytes = arg.as_bytes();
+            options &= bytes != b"--";
+            match bytes.strip_prefix(b"-I") {
+                Some(format) if options && !format.is_empty() => {
+                    let mut option = OsString::from("--iso-8601=");
+                    option.push(std::ffi::OsStr::from_bytes(format));
+                    option
+                }
+                _ => arg,
+            }
+        })
+        .collect()
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(attached_iso_8601(args), 1);
+    let try_help = "Try 'date --help' for more information.";
+    let mut formats = Vec::new();
+    let specs = [
+        (&args.iso_8601, "--iso-8601", &ISO_8601_FORMATS[..]),
+        (&args.rfc_3339, "--rfc-3339", &RFC_3339_FORMATS[..]),
+    ];
+    for (spec, option, choices) in specs {
+        let Some(spec) = spec else {
+            continue;
+        };
+        match match_format(spec, option, choices) {
+            Ok(format) => formats.push(format),
+            Err(message) => {
+                eprintln!("date: {message}\n{try_help}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if args.rfc_email {
+        formats.push(RFC_EMAIL_FORMAT);
+    }
+    if formats.len() > 1 {
+        eprintln!("date: multiple output formats specified");
+        return ExitCode::FAILURE;
+    }
+    if args.utc {
+        std::env::set_var("TZ", "UTC0");
+    }
+
+    let given = [&args.date, &args.file, &args.reference];
+    let specified_date = given.iter().any(|date| date.is_some());
+    if given.iter().filter(|date| date.is_some()).count() > 1 {
+        eprintln!(
+            "date: the options to specify dates for printing are mutually exclusive\n{try_help}"
+        );
+        return ExitCode::FAILURE;
+    }
+    if args.set.is_some() && specified_date {
+        eprintln!(
+            "date: the options to print and set the time may not be used together\n{try_help}"
+        );
+        return ExitCode::FAILURE;
+    }
+    if let Some(extra) = args.operands.get(1) {
+        eprintln!(
+            "date: extra operand {}\n{try_help}",
+            quote(extra.as_bytes())
+        );
+        return ExitCode::FAILURE;
+    }
+    let mut format = formats.pop().map(<[u8]>::to_vec);
+    let mut set_operand = None;
+    if let Some(operand) = args.operands.first().map(|operand| operand.as_bytes()) {
+        if let Some(operand_format) = operand.strip_prefix(b"+") {
+            if format.is_some() {
+                eprintln!("date: multiple output formats specified");
+                return ExitCode::FAILURE;
+            }
+            format = Some(operand_format.to_vec());
+        } else if args.set.is_some() || specified_date {
+            eprintln!(
+                "date: the argument {} lacks a leading '+';\n\
+                when using an option to specify date(s), any non-option\n\
+                argument must be a format string beginning with '+'\n{try_help}",
+                quote(operand)
+            );
+            return ExitCode::FAILURE;
+        } else {
+            set_operand = Some(operand);
+        }
+    }
+    let mut format = format.unwrap_or_else(|| DEFAULT_FORMAT.to_vec());
+    // %-N is as many digits as the clock has, all 9 of the nanoseconds here
+    let mut i = 0;
+    while i + 1 < format.len() {
+        if format[i] == b'%' && format[i + 1..].starts_with(b"-N") {
+            format[i + 1] = b'9';
+        }
+        i += match format[i] == b'%' && format[i + 1] == b'%' {
+            true => 2,
+            false => 1,
+        };
+    }
+
+    let now = match clock_gettime(ClockId::CLOCK_REALTIME) {
+        Ok(now) => (now.tv_sec(), now.tv_nsec() as u32),
+        Err(e) => {
+            eprintln!("date: cannot get the time: {}", errno_desc(e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = match stdout_file() {
+        Ok(out) => BufWriter::new(out),
+        Err(e) => {
+            eprintln!("date: write error: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let shown = match (&args.file, set_operand, &args.reference) {
+        (Some(file), _, _) => batch_convert(&mut out, file.as_bytes(), &format, now),
+        (None, Some(operand), _) => {
+            let Some(when) = posix_time(operand, now.0) else {
+                eprintln!("date: invalid date {}", quote(operand));
+                return ExitCode::FAILURE;
+            };
+            let set = set_time((when, 0));
+            show_date(&mut out, &format, (when, 0)).map(|shown| shown && set)
+        }
+        (None, None, Some(reference)) => match fs::metadata(reference) {
+            Ok(metadata) => {
+                let when = (metadata.mtime(), metadata.mtime_nsec() as u32);
+                show_date(&mut out, &format, when)
+            }
+            Err(e) => {
+                eprintln!(
+                    "date: {}: {}",
+                    shell_quote(reference.as_bytes(), false),
+                    strerror(&e)
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, None, None) => {
+            let date = args.set.as_ref().or(args.date.as_ref());
+            let when = match date {
+                Some(date) => match parse_datetime(date.as_bytes(), now) {
+                    Some(when) => when,
+                    None => {
+                        eprintln!("date: invalid date {}", quote(date.as_bytes()));
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => now,
+            };
+            let set = args.set.is_none() || set_time(when);
+            show_date(&mut out, &format, when).map(|shown| shown && set)
+        }
+    };
+    match shown.and_then(|shown| out.flush().map(|_| shown)) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("date: write error: {}", strerror(&e));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Set the system clock, false when that's not allowed or fails
+fn set_time((sec, nsec): (i64, u32)) -> bool {
+    match clock_settime(ClockId::CLOCK_REALTIME, TimeSpec::new(sec, nsec as i64)) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("date: cannot set date: {}", errno_desc(e));
+            false
+        }
+    }
+}
@@ -0,0 +1,183 @@
+/*
+ * expand - convert tabs to spaces
+ *
+ * Columns are counted in bytes like GNU does, a backspace moves back one column
+ * (and reconsiders the previous tab stop) and lines continue across input files.
+ * A tab past the last explicit tab stop becomes a single space.
+ */
+
+use crate::tabstops::TabStops;
+use crate::{parse_args, reset_sigpipe, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "expand", version, long_about = None)]
+#[command(about = "Convert tabs in each FILE to spaces, writing to standard output")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Do not convert tabs after non blanks
+    #[clap(long, short, action)]
+    initial: bool,
+    /// Have tabs N characters apart, or use comma separated list of explicit tab
+    /// positions. The last one may be prefixed with '/' (tabs after it every
+    /// multiple of N) or '+' (tabs after it every N columns)
+    #[clap(long, short, value_name = "LIST", action = ArgAction::Append)]
+    tabs: Vec<String>,
+    /// Files to expand, stdin by default
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+
+/// Tab expansion state, fed one byte at a time
+pub struct Expander<'a> {
+    stops: &'a TabStops,
+    initial: bool,
+    column: u64,
+    tab_index: usize,
+    // Still converting, ie. not past the leading blanks with `initial`
+    convert: bool,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(stops: &'a TabStops, initial: bool) -> Expander<'a> {
+        Expander {
+            stops,
+            initial,
+            column: 0,
+            tab_index: 0,
+            convert: true,
+        }
+    }
+
+    pub fn push(&mut self, c: u8, out: &mut dyn Write) -> io::Result<()> {
+        if self.convert {
+            match c {
+                b'\t' => {
+                    let next = self
+                        .stops
+                        .next_tab_column(self.column, &mut self.tab_index)
+                        .unwrap_or(self.column + 1);
+                    let spaces = [b' '; 64];
+                    let mut pad = next - self.column;
+                    while pad > 0 {
+                        let n = pad.min(spaces.len() as u64);
+                        out.write_all(&spaces[..n as usize])?;
+                        pad -= n;
+                    }
+                    self.column = next;
+                    return Ok(());
+                }
+                b'\x08' => {
+                    // Go back one column, and reconsider the previous tab stop
+                    self.column = self.column.saturating_sub(1);
+                    self.tab_index = self.tab_index.saturating_sub(1);
+                }
+                _ => self.column += 1,
+            }
+            self.convert = !self.initial || c == b' ' || c == b'\t';
+        }
+        if c == b'\n' {
+            self.column = 0;
+            self.tab_index = 0;
+            self.convert = true;
+        }
+        out.write_all(&[c])
+    }
+}
+
+// Rewrite the obsolete `-N[,N...]` form into `-t N[,N...]`
+fn obsolete_tabs(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    args.into_iter()
+        .map(|arg| {
+            let bytes = arg.as_bytes();
+            if bytes.len() > 1 && bytes[0] == b'-' && bytes[1].is_ascii_digit() {
+                let mut tabs = OsString::from("-t");
+                tabs.push(std::ffi::OsStr::from_bytes(&bytes[1..]));
+                tabs
+            } else {
+                arg
+            }
+        })
+        .collect()
+}
+
+fn expand_file(
+    expander: &mut Expander,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        for &c in buf {
+            expander.push(c, out)?;
+        }
+        let n = buf.len();
+        input.consume(n);
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(obsolete_tabs(args), 1);
+    let mut stops = TabStops::default();
+    let parsed = args
+        .tabs
+        .iter()
+        .try_for_each(|list| stops.parse(list))
+        .and_then(|_| stops.finalize());
+    if let Err(e) = parsed {
+        eprintln!("expand: {e}");
+        return ExitCode::FAILURE;
+    }
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("expand: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files = match args.files.is_empty() {
+        true => vec![OsString::from("-")],
+        false => args.files,
+    };
+
+    let mut ok = true;
+    // Lines (and their columns) carry on from one file to the next
+    let mut expander = Expander::new(&stops, args.initial);
+    for file in files {
+        let mut input: Box<dyn BufRead> = if file == "-" {
+            Box::new(BufReader::with_capacity(IO_BUFSIZE, io::stdin().lock()))
+        } else {
+            match File::open(&file) {
+                Ok(f) => Box::new(BufReader::with_capacity(IO_BUFSIZE, f)),
+                Err(e) => {
+                    eprintln!("expand: {}: {}", file.to_string_lossy(), strerror(&e));
+                    ok = false;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = expand_file(&mut expander, input.as_mut(), &mut out) {
+            eprintln!("expand: {}: {}", file.to_string_lossy(), strerror(&e));
+            ok = false;
+        }
+    }
+    if let Err(e) = out.flush() {
+        eprintln!("expand: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
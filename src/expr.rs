@@ -0,0 +1,690 @@
+/*
+ * expr - evaluate expressions
+ *
+ * GNU's grammar and messages: every argument is one token, so `expr 1+1` is the
+ * string "1+1" while `expr 1 + 1` is 2, and an operator, keyword or parenthesis can
+ * still be a string where an operand is expected. Integers are arbitrary precision
+ * decimals (optionally '-' then digits, nothing else) and keep the text they came as
+ * until arithmetic needs them, so `expr 010` prints 010. The right side of | and & is
+ * only evaluated, errors and all, when it decides the result. Strings are bytes as in
+ * the C locale, and : uses bre.rs since the regex crate has neither back references
+ * nor POSIX's longest match. Exits 0 for a value neither null nor 0, 1 for one that
+ * is, 2 for an invalid expression and 3 for errors.
+ */
+
+use crate::bre::Bre;
+use crate::{quote, reset_sigpipe, stdout_file, strerror};
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStringExt;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: expr EXPRESSION
+  or:  expr OPTION
+
+      --help        display this help and exit
+      --version     output version information and exit
+
+Print the value of EXPRESSION to standard output.  A blank line below
+separates increasing precedence groups.  EXPRESSION may be:
+
+  ARG1 | ARG2       ARG1 if it is neither null nor 0, otherwise ARG2
+
+  ARG1 & ARG2       ARG1 if neither argument is null or 0, otherwise 0
+
+  ARG1 < ARG2       ARG1 is less than ARG2
+  ARG1 <= ARG2      ARG1 is less than or equal to ARG2
+  ARG1 = ARG2       ARG1 is equal to ARG2
+  ARG1 != ARG2      ARG1 is unequal to ARG2
+  ARG1 >= ARG2      ARG1 is greater than or equal to ARG2
+  ARG1 > ARG2       ARG1 is greater than ARG2
+
+  ARG1 + ARG2       arithmetic sum of ARG1 and ARG2
+  ARG1 - ARG2       arithmetic difference of ARG1 and ARG2
+
+  ARG1 * ARG2       arithmetic product of ARG1 and ARG2
+  ARG1 / ARG2       arithmetic quotient of ARG1 divided by ARG2
+  ARG1 % ARG2       arithmetic remainder of ARG1 divided by ARG2
+
+  STRING : REGEXP   anchored pattern match of REGEXP in STRING
+
+  match STRING REGEXP        same as STRING : REGEXP
+  substr STRING POS LENGTH   substring of STRING, POS counted from 1
+  index STRING CHARS         index in STRING where any CHARS is found, or 0
+  length STRING              length of STRING
+  + TOKEN                    interpret TOKEN as a string, even if it is a
+                               keyword like 'match' or an operator like '/'
+
+  ( EXPRESSION )             value of EXPRESSION
+
+Beware that many operators need to be escaped or quoted for shells.
+Comparisons are arithmetic if both ARGs are numbers, else lexicographical.
+Pattern matches return the string matched between \\( and \\) or null; if
+\\( and \\) are not used, they return the number of characters matched or 0.
+
+Exit status is 0 if EXPRESSION is neither null nor 0, 1 if EXPRESSION is null
+or 0, 2 if EXPRESSION is syntactically invalid, and 3 if an error occurred.
+";
+
+const EXIT_INVALID: u8 = 2;
+const EXIT_FAILURE: u8 = 3;
+
+// Limbs of nine decimal digits
+const BASE: u64 = 1_000_000_000;
+
+/// A signed integer of any size: little endian limbs with no high zero limbs, zero
+/// being no limbs and never negative
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Integer {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+fn trim(mut limbs: Vec<u32>) -> Vec<u32> {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+    limbs
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let limb = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push((limb % BASE) as u32);
+        carry = limb / BASE;
+    }
+    sum.push(carry as u32);
+    trim(sum)
+}
+
+// a - b where a >= b
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let subtrahend = *b.get(i).unwrap_or(&0) as i64 + borrow;
+        let mut limb = limb as i64 - subtrahend;
+        borrow = (limb < 0) as i64;
+        if limb < 0 {
+            limb += BASE as i64;
+        }
+        difference.push(limb as u32);
+    }
+    trim(difference)
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u64; a.len() + b.len() + 1];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let limb = product[i + j] + x as u64 * y as u64 + carry;
+            product[i + j] = limb % BASE;
+            carry = limb / BASE;
+        }
+        product[i + b.len()] += carry;
+    }
+    trim(product.into_iter().map(|limb| limb as u32).collect())
+}
+
+fn mul_small(a: &[u32], m: u32) -> Vec<u32> {
+    mul_magnitudes(a, &[m])
+}
+
+// Long division a limb at a time, each quotient limb found by bisection
+fn divrem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for i in (0..a.len()).rev() {
+        remainder.insert(0, a[i]);
+        remainder = trim(remainder);
+        let (mut low, mut high) = (0, BASE as u32 - 1);
+        while low < high {
+            let middle = low + (high - low + 1) / 2;
+            match compare_magnitudes(&mul_small(b, middle), &remainder) {
+                Ordering::Greater => high = middle - 1,
+                _ => low = middle,
+            }
+        }
+        remainder = sub_magnitudes(&remainder, &mul_small(b, low));
+        quotient[i] = low;
+    }
+    (trim(quotient), remainder)
+}
+
+impl Integer {
+    fn new(negative: bool, limbs: Vec<u32>) -> Integer {
+        let limbs = trim(limbs);
+        Integer {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    /// An optional '-' and at least one digit, GNU's looks_like_integer
+    fn parse(s: &[u8]) -> Option<Integer> {
+        let (negative, digits) = match s {
+            [b'-', digits @ ..] => (true, digits),
+            digits => (false, digits),
+        };
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let limbs = digits
+            .rchunks(9)
+            .map(|chunk| chunk.iter().fold(0, |n, &c| n * 10 + (c - b'0') as u32))
+            .collect();
+        Some(Integer::new(negative, limbs))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn add(&self, other: &Integer) -> Integer {
+        if self.negative == other.negative {
+            return Integer::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match compare_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                Integer::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs))
+            }
+            _ => Integer::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    fn sub(&self, other: &Integer) -> Integer {
+        self.add(&Integer::new(!other.negative, other.limbs.clone()))
+    }
+
+    fn mul(&self, other: &Integer) -> Integer {
+        let limbs = mul_magnitudes(&self.limbs, &other.limbs);
+        Integer::new(self.negative != other.negative, limbs)
+    }
+
+    /// Quotient truncated toward zero and the remainder with the dividend's sign, like C
+    fn div_rem(&self, other: &Integer) -> (Integer, Integer) {
+        let (quotient, remainder) = divrem_magnitudes(&self.limbs, &other.limbs);
+        (
+            Integer::new(self.negative != other.negative, quotient),
+            Integer::new(self.negative, remainder),
+        )
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            return None;
+        }
+        (self.limbs.iter().rev()).try_fold(0u64, |n, &limb| {
+            n.checked_mul(BASE)?.checked_add(limb as u64)
+        })
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Integer) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Integer) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => compare_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => compare_magnitudes(&other.limbs, &self.limbs),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl std::fmt::Display for Integer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut limbs = self.limbs.iter().rev();
+        let sign = if self.negative { "-" } else { "" };
+        write!(f, "{sign}{}", limbs.next().unwrap_or(&0))?;
+        limbs.try_for_each(|limb| write!(f, "{limb:09}"))
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Integer(Integer),
+    String(Vec<u8>),
+}
+
+impl Value {
+    fn from_bool(value: bool) -> Value {
+        Value::Integer(Integer::new(false, vec![value as u32]))
+    }
+
+    fn from_usize(n: usize) -> Value {
+        Value::Integer(Integer::parse(n.to_string().as_bytes()).unwrap())
+    }
+
+    // GNU's null(): 0, the empty string, or '-' followed by nothing but zeros
+    fn is_null(&self) -> bool {
+        match self {
+            Value::Integer(n) => n.is_zero(),
+            Value::String(s) => match s.as_slice() {
+                [] => true,
+                [b'-', zeros @ ..] => !zeros.is_empty() && zeros.iter().all(|&c| c == b'0'),
+                zeros => zeros.iter().all(|&c| c == b'0'),
+            },
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Value::Integer(n) => n.to_string().into_bytes(),
+            Value::String(s) => s,
+        }
+    }
+
+    fn to_integer(&self) -> Option<Integer> {
+        match self {
+            Value::Integer(n) => Some(n.clone()),
+            Value::String(s) => Integer::parse(s),
+        }
+    }
+}
+
+type Result<T, E = String> = std::result::Result<T, E>;
+
+fn syntax_error(message: String) -> String {
+    format!("syntax error: {message}")
+}
+
+struct Expr {
+    args: Vec<Vec<u8>>,
+    pos: usize,
+}
+
+impl Expr {
+    fn no_more(&self) -> bool {
+        self.pos == self.args.len()
+    }
+
+    // Take the next argument if it's `token`
+    fn next_is(&mut self, token: &[u8]) -> bool {
+        let is = self.args.get(self.pos).is_some_and(|arg| arg == token);
+        self.pos += is as usize;
+        is
+    }
+
+    fn require_more(&self) -> Result<()> {
+        match self.no_more() {
+            true => Err(syntax_error(format!(
+                "missing argument after {}",
+                quote(&self.args[self.pos - 1])
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    // Parenthesized expressions and plain strings
+    fn primary(&mut self, evaluate: bool) -> Result<Value> {
+        self.require_more()?;
+        if self.next_is(b"(") {
+            let value = self.or(evaluate)?;
+            if self.no_more() {
+                return Err(syntax_error(format!(
+                    "expecting ')' after {}",
+                    quote(&self.args[self.pos - 1])
+                )));
+            }
+            if !self.next_is(b")") {
+                return Err(syntax_error(format!(
+                    "expecting ')' instead of {}",
+                    quote(&self.args[self.pos])
+                )));
+            }
+            return Ok(value);
+        }
+        if self.next_is(b")") {
+            return Err(syntax_error(String::from("unexpected ')'")));
+        }
+        self.pos += 1;
+        Ok(Value::String(self.args[self.pos - 1].clone()))
+    }
+
+    // + TOKEN and the keywords
+    fn unary(&mut self, evaluate: bool) -> Result<Value> {
+        if self.next_is(b"+") {
+            self.require_more()?;
+            self.pos += 1;
+            Ok(Value::String(self.args[self.pos - 1].clone()))
+        } else if self.next_is(b"length") {
+            let string = self.unary(evaluate)?.into_bytes();
+            Ok(Value::from_usize(string.len()))
+        } else if self.next_is(b"match") {
+            let string = self.unary(evaluate)?;
+            let pattern = self.unary(evaluate)?;
+            match evaluate {
+                true => matches(string, pattern),
+                false => Ok(string),
+            }
+        } else if self.next_is(b"index") {
+            let string = self.unary(evaluate)?.into_bytes();
+            let chars = self.unary(evaluate)?.into_bytes();
+            let index = string.iter().position(|c| chars.contains(c));
+            Ok(Value::from_usize(index.map_or(0, |index| index + 1)))
+        } else if self.next_is(b"substr") {
+            let string = self.unary(evaluate)?.into_bytes();
+            let pos = self.unary(evaluate)?;
+            let len = self.unary(evaluate)?;
+            Ok(Value::String(substr(&string, &pos, &len).to_vec()))
+        } else {
+            self.primary(evaluate)
+        }
+    }
+
+    fn colon(&mut self, evaluate: bool) -> Result<Value> {
+        let mut l = self.unary(evaluate)?;
+        while self.next_is(b":") {
+            let r = self.unary(evaluate)?;
+            if evaluate {
+                l = matches(l, r)?;
+            }
+        }
+        Ok(l)
+    }
+
+    fn arithmetic(
+        &mut self,
+        operators: &[&[u8]],
+        operand: fn(&mut Expr, bool) -> Result<Value>,
+        evaluate: bool,
+    ) -> Result<Value> {
+        let mut l = operand(self, evaluate)?;
+        while let Some(&operator) = operators.iter().find(|&&op| self.next_is(op)) {
+            let r = operand(self, evaluate)?;
+            if evaluate {
+                l = Value::Integer(arithmetic(operator, &l, &r)?);
+            }
+        }
+        Ok(l)
+    }
+
+    fn product(&mut self, evaluate: bool) -> Result<Value> {
+        self.arithmetic(&[b"*", b"/", b"%"], Expr::colon, evaluate)
+    }
+
+    fn sum(&mut self, evaluate: bool) -> Result<Value> {
+        self.arithmetic(&[b"+", b"-"], Expr::product, evaluate)
+    }
+
+    fn comparison(&mut self, evaluate: bool) -> Result<Value> {
+        const OPERATORS: [&[u8]; 7] = [b"<", b"<=", b"=", b"==", b"!=", b">=", b">"];
+        let mut l = self.sum(evaluate)?;
+        while let Some(&operator) = OPERATORS.iter().find(|&&op| self.next_is(op)) {
+            let r = self.sum(evaluate)?;
+            if evaluate {
+                let ordering = match (l.to_integer(), r.to_integer()) {
+                    (Some(l), Some(r)) => l.cmp(&r),
+                    _ => l.into_bytes().cmp(&r.into_bytes()),
+                };
+                l = Value::from_bool(match operator {
+                    b"<" => ordering.is_lt(),
+                    b"<=" => ordering.is_le(),
+                    b"=" | b"==" => ordering.is_eq(),
+                    b"!=" => ordering.is_ne(),
+                    b">=" => ordering.is_ge(),
+                    _ => ordering.is_gt(),
+                });
+            }
+        }
+        Ok(l)
+    }
+
+    fn and(&mut self, evaluate: bool) -> Result<Value> {
+        let mut l = self.comparison(evaluate)?;
+        while self.next_is(b"&") {
+            let r = self.comparison(evaluate && !l.is_null())?;
+            if evaluate && (l.is_null() || r.is_null()) {
+                l = Value::from_bool(false);
+            }
+        }
+        Ok(l)
+    }
+
+    fn or(&mut self, evaluate: bool) -> Result<Value> {
+        let mut l = self.and(evaluate)?;
+        while self.next_is(b"|") {
+            let r = self.and(evaluate && l.is_null())?;
+            if evaluate && l.is_null() {
+                l = match r.is_null() {
+                    true => Value::from_bool(false),
+                    false => r,
+                };
+            }
+        }
+        Ok(l)
+    }
+}
+
+fn arithmetic(operator: &[u8], l: &Value, r: &Value) -> Result<Integer> {
+    let (Some(l), Some(r)) = (l.to_integer(), r.to_integer()) else {
+        return Err(String::from("non-integer argument"));
+    };
+    if matches!(operator, b"/" | b"%") && r.is_zero() {
+        return Err(String::from("division by zero"));
+    }
+    Ok(match operator {
+        b"+" => l.add(&r),
+        b"-" => l.sub(&r),
+        b"*" => l.mul(&r),
+        b"/" => l.div_rem(&r).0,
+        _ => l.div_rem(&r).1,
+    })
+}
+
+// GNU's getsize: anything negative or too big is as good as infinite
+fn size(value: &Value) -> Option<u64> {
+    Some(value.to_integer()?.to_u64().unwrap_or(u64::MAX))
+}
+
+fn substr<'a>(string: &'a [u8], pos: &Value, len: &Value) -> &'a [u8] {
+    let (Some(pos), Some(len)) = (size(pos), size(len)) else {
+        return b"";
+    };
+    let llen = string.len() as u64;
+    if llen < pos || pos == 0 || len == 0 || len == u64::MAX {
+        return b"";
+    }
+    let start = pos as usize - 1;
+    let len = len.min(llen - pos + 1) as usize;
+    &string[start..start + len]
+}
+
+// STRING : REGEXP, anchored at the start
+fn matches(string: Value, pattern: Value) -> Result<Value> {
+    let (string, pattern) = (string.into_bytes(), pattern.into_bytes());
+    let bre = Bre::new(&pattern).map_err(String::from)?;
+    let found = bre.match_start(&string);
+    Ok(match (bre.groups(), found) {
+        (0, found) => Value::from_usize(found.map_or(0, |found| found.len)),
+        (_, Some(found)) => match found.groups[0] {
+            Some((start, end)) => Value::String(string[start..end].to_vec()),
+            None => Value::String(Vec::new()),
+        },
+        (_, None) => Value::String(Vec::new()),
+    })
+}
+
+/// The value of the expression making up all of `args` but the program name
+fn evaluate(args: Vec<Vec<u8>>) -> Result<Value> {
+    let mut expr = Expr { args, pos: 1 };
+    let value = expr.or(true)?;
+    match expr.no_more() {
+        true => Ok(value),
+        false => Err(syntax_error(format!(
+            "unexpected argument {}",
+            quote(&expr.args[expr.pos])
+        ))),
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let mut args: Vec<Vec<u8>> = args.into_iter().map(OsStringExt::into_vec).collect();
+    if args.len() == 2 {
+        match args[1].as_slice() {
+            b"--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            b"--version" => {
+                println!("expr {}", env!("CARGO_PKG_VERSION"));
+                return ExitCode::SUCCESS;
+            }
+            _ => {}
+        }
+    }
+    // Like getopt, the only option is the end of them
+    if args.get(1).is_some_and(|arg| arg == b"--") {
+        args.remove(1);
+    }
+    if args.len() < 2 {
+        eprintln!("expr: missing operand\nTry 'expr --help' for more information.");
+        return ExitCode::from(EXIT_INVALID);
+    }
+
+    let value = match evaluate(args) {
+        Ok(value) => value,
+        Err(message) => {
+            eprintln!("expr: {message}");
+            return ExitCode::from(EXIT_INVALID);
+        }
+    };
+
+    let null = value.is_null();
+    let mut line = value.into_bytes();
+    line.push(b'\n');
+    if let Err(e) = stdout_file().and_then(|mut out| out.write_all(&line)) {
+        eprintln!("expr: write error: {}", strerror(&e));
+        return ExitCode::from(EXIT_FAILURE);
+    }
+    match null {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What expr prints for `args` (the value or the error) and its exit status
+    fn expr(args: &[&str]) -> (String, u8) {
+        let mut args: Vec<Vec<u8>> = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+        args.insert(0, b"expr".to_vec());
+        match evaluate(args) {
+            Ok(value) => {
+                let status = value.is_null() as u8;
+                (String::from_utf8(value.into_bytes()).unwrap(), status)
+            }
+            Err(message) => (message, EXIT_INVALID),
+        }
+    }
+
+    fn check(cases: &[(&[&str], &str, u8)]) {
+        for (args, output, status) in cases {
+            assert_eq!(expr(args), (output.to_string(), *status), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        check(&[
+            (&["1", "+", "2", "*", "3"], "7", 0),
+            (&["(", "1", "+", "2", ")", "*", "3"], "9", 0),
+            (&["10", "-", "4", "-", "3"], "3", 0),
+            (&["7", "/", "2", "%", "3"], "0", 1),
+            (&["2", "*", "-3"], "-6", 0),
+            (&["1", "<", "2", "+", "0"], "1", 0),
+            (&["2", "=", "2", "|", "0"], "1", 0),
+            (&["0", "|", ""], "0", 1),
+            (&["3", "|", "x"], "3", 0),
+            (&["0", "&", "1"], "0", 1),
+            (&["a", "&", "0"], "0", 1),
+            // The right side of | is never evaluated when the left decides
+            (&["1", "|", "1", "/", "0"], "1", 0),
+            (&["0", "&", "1", "/", "0"], "0", 1),
+        ]);
+    }
+
+    #[test]
+    fn comparisons_and_integers() {
+        check(&[
+            (&["10", "<", "9"], "0", 1),
+            (&["10", "<", "9a"], "1", 0),
+            (&["1", "!=", "1"], "0", 1),
+            (&["b", ">=", "a"], "1", 0),
+            (&["010"], "010", 0),
+            (&["-5", "+", "3"], "-2", 0),
+            (
+                &["99999999999999999999", "+", "1"],
+                "100000000000000000000",
+                0,
+            ),
+            (&["0"], "0", 1),
+            (&[""], "", 1),
+        ]);
+    }
+
+    #[test]
+    fn matching() {
+        check(&[
+            (&["abcde", ":", "ab\\(c*\\)"], "c", 0),
+            (&["abcde", ":", "a.c"], "3", 0),
+            // Anchored at the start
+            (&["abc", ":", "b"], "0", 1),
+            (&["abc", ":", "x\\(y\\)"], "", 1),
+            (&["match", "abcd", "a\\(b\\)"], "b", 0),
+            (&["abab", ":", "\\(ab\\)\\1"], "ab", 0),
+            (&["a", ":", "\\("], "Unmatched ( or \\(", 2),
+        ]);
+    }
+
+    #[test]
+    fn string_functions() {
+        check(&[
+            (&["index", "abcdef", "dc"], "3", 0),
+            (&["index", "abc", "z"], "0", 1),
+            (&["substr", "hello", "2", "3"], "ell", 0),
+            (&["substr", "hello", "0", "2"], "", 1),
+            (&["substr", "hello", "4", "99"], "lo", 0),
+            (&["length", "hello"], "5", 0),
+            // + quotes a keyword as a string
+            (&["+", "length"], "length", 0),
+        ]);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        check(&[
+            (
+                &["length"],
+                "syntax error: missing argument after 'length'",
+                2,
+            ),
+            (&["1", "+"], "syntax error: missing argument after '+'", 2),
+            (&["(", "1"], "syntax error: expecting ')' after '1'", 2),
+            (&["1", "2"], "syntax error: unexpected argument '2'", 2),
+            (&["1", "+", "a"], "non-integer argument", 2),
+            (&["1", "/", "0"], "division by zero", 2),
+            (&["5", "%", "0"], "division by zero", 2),
+        ]);
+    }
+}
@@ -0,0 +1,645 @@
+/*
+ * x87 80-bit long doubles for printf's floating point conversions
+ *
+ * GNU printf parses arguments with strtold(3) and formats them with glibc, so values
+ * carry a 64-bit mantissa. Parsing rounds exactly (half to even, with denormals) and
+ * %e/%f/%g expand the binary value to all of its decimal digits before rounding, the
+ * same digits glibc prints.
+ */
+
+use std::cmp::Ordering;
+
+// Exponent range of `mantissa * 2^exponent`, denormals have the smallest exponent
+const MIN_EXP: i64 = -16445;
+const MAX_EXP: i64 = 16320;
+
+// Significant decimal digits kept when parsing, any further nonzero digits only
+// matter as a sticky digit for rounding
+const MAX_DIGITS: usize = 20000;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Class {
+    Finite { mantissa: u64, exponent: i32 },
+    Infinite,
+    Nan,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Float80 {
+    pub negative: bool,
+    pub class: Class,
+}
+
+impl From<u8> for Float80 {
+    fn from(n: u8) -> Float80 {
+        let shift = (n as u64).leading_zeros() % 64;
+        Float80 {
+            negative: false,
+            class: Class::Finite {
+                mantissa: (n as u64) << shift,
+                exponent: -(shift as i32),
+            },
+        }
+    }
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
+
+fn starts_with_ignore_case(s: &[u8], prefix: &[u8]) -> bool {
+    s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Length of the `[eE][+-]digits` (or `[pP]...`) exponent at the start of `s`, if any
+fn parse_exponent(s: &[u8], marker: u8) -> (i64, usize) {
+    if !s.first().is_some_and(|c| c.to_ascii_lowercase() == marker) {
+        return (0, 0);
+    }
+    let sign = matches!(s.get(1), Some(b'+' | b'-')) as usize;
+    let digits = s[1 + sign..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digits == 0 {
+        return (0, 0);
+    }
+    let value = s[1 + sign..1 + sign + digits]
+        .iter()
+        .fold(0i64, |v, d| (v * 10 + (d - b'0') as i64).min(1 << 40));
+    match s[1] {
+        b'-' => (-value, 1 + sign + digits),
+        _ => (value, 1 + sign + digits),
+    }
+}
+
+impl Float80 {
+    fn zero(negative: bool) -> Float80 {
+        Float80 {
+            negative,
+            class: Class::Finite {
+                mantissa: 0,
+                exponent: 0,
+            },
+        }
+    }
+
+    /// Parse the longest number at the start of `s` like strtold(3) in the C locale.
+    /// Returns the value, the length parsed (0 when there's no number at all) and
+    /// whether the value was out of range (overflowed, or underflowed inexactly).
+    pub fn parse(s: &[u8]) -> (Float80, usize, bool) {
+        let mut i = s.iter().take_while(|c| is_space(**c)).count();
+        let negative = s.get(i) == Some(&b'-');
+        if matches!(s.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        let rest = &s[i..];
+
+        if starts_with_ignore_case(rest, b"inf") {
+            let len = match starts_with_ignore_case(rest, b"infinity") {
+                true => 8,
+                false => 3,
+            };
+            let value = Float80 {
+                negative,
+                class: Class::Infinite,
+            };
+            return (value, i + len, false);
+        }
+        if starts_with_ignore_case(rest, b"nan") {
+            let mut len = 3;
+            if rest.get(3) == Some(&b'(') {
+                let chars = rest[4..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+                    .count();
+                if rest.get(4 + chars) == Some(&b')') {
+                    len += chars + 2;
+                }
+            }
+            let value = Float80 {
+                negative,
+                class: Class::Nan,
+            };
+            return (value, i + len, false);
+        }
+
+        let hex = starts_with_ignore_case(rest, b"0x")
+            && match rest.get(2) {
+                Some(b'.') => rest.get(3).is_some_and(u8::is_ascii_hexdigit),
+                Some(c) => c.is_ascii_hexdigit(),
+                None => false,
+            };
+        let (radix, marker, start) = match hex {
+            true => (16, b'p', 2),
+            false => (10, b'e', 0),
+        };
+
+        // Mantissa digits, with the exponent adjusted for those after the point
+        let mut digits = Vec::new();
+        let mut j = start;
+        let mut point = None;
+        loop {
+            match rest.get(j) {
+                Some(b'.') if point.is_none() => point = Some(digits.len()),
+                Some(c) if (*c as char).is_digit(radix) => digits.push(*c),
+                _ => break,
+            }
+            j += 1;
+        }
+        if digits.is_empty() {
+            return (Float80::zero(false), 0, false);
+        }
+        let (exponent, len) = parse_exponent(&rest[j..], marker);
+        let fraction = (digits.len() - point.unwrap_or(digits.len())) as i64;
+        let end = i + j + len;
+
+        let (value, range) = match hex {
+            true => from_hex(negative, &digits, exponent - 4 * fraction),
+            false => from_decimal(negative, digits, exponent - fraction),
+        };
+        (value, end, range)
+    }
+
+    /// Format the magnitude for a `%a %A %e %E %f %F %g %G` conversion, the caller
+    /// adds the sign and padding
+    pub fn format(&self, conversion: u8, precision: Option<usize>, alt: bool) -> Vec<u8> {
+        let upper = conversion.is_ascii_uppercase();
+        let (mantissa, exponent) = match self.class {
+            Class::Finite { mantissa, exponent } => (mantissa, exponent),
+            Class::Infinite if upper => return b"INF".to_vec(),
+            Class::Infinite => return b"inf".to_vec(),
+            Class::Nan if upper => return b"NAN".to_vec(),
+            Class::Nan => return b"nan".to_vec(),
+        };
+        if conversion.eq_ignore_ascii_case(&b'a') {
+            return format_hex(mantissa, exponent, precision, alt, upper);
+        }
+
+        let (digits, point) = decimal(mantissa, exponent);
+        match conversion.to_ascii_lowercase() {
+            b'e' => format_exponent(digits, point, precision.unwrap_or(6), alt, upper),
+            b'f' => format_fixed(digits, point, precision.unwrap_or(6), alt),
+            _ => {
+                let precision = match precision {
+                    None => 6,
+                    Some(0) => 1,
+                    Some(precision) => precision,
+                };
+                let x = match digits.is_empty() {
+                    true => 0,
+                    false => round_digits(digits.clone(), point, precision as i64).1 - 1,
+                };
+                let mut out = match precision as i64 > x && x >= -4 {
+                    true => format_fixed(digits, point, (precision as i64 - 1 - x) as usize, alt),
+                    false => format_exponent(digits, point, precision - 1, alt, upper),
+                };
+                if !alt {
+                    strip_fraction_zeros(&mut out);
+                }
+                out
+            }
+        }
+    }
+}
+
+fn from_hex(negative: bool, digits: &[u8], exponent: i64) -> (Float80, bool) {
+    let mut n = Big::default();
+    for &c in digits {
+        n.mul_add(16, (c as char).to_digit(16).unwrap());
+    }
+    let bits = n.bit_len() as i64;
+    let drop = (bits - 66).max(0);
+    let (q, sticky) = n.shr_u128(drop as u64);
+    round(negative, q, sticky, exponent.saturating_add(drop))
+}
+
+fn from_decimal(negative: bool, mut digits: Vec<u8>, mut exponent: i64) -> (Float80, bool) {
+    let leading = digits.iter().take_while(|c| **c == b'0').count();
+    digits.drain(..leading);
+    while digits.last() == Some(&b'0') {
+        digits.pop();
+        exponent += 1;
+    }
+    if digits.is_empty() {
+        return (Float80::zero(negative), false);
+    }
+    if digits.len() > MAX_DIGITS {
+        exponent += (digits.len() - MAX_DIGITS - 1) as i64;
+        digits.truncate(MAX_DIGITS);
+        digits.push(b'1');
+    }
+
+    // The value lies in [10^(magnitude-1), 10^magnitude), the largest long double is
+    // about 1.19e4932 and half the smallest denormal about 1.8e-4951
+    let magnitude = digits.len() as i64 + exponent;
+    if magnitude > 4934 {
+        let value = Float80 {
+            negative,
+            class: Class::Infinite,
+        };
+        return (value, true);
+    }
+    if magnitude < -4952 {
+        return (Float80::zero(negative), true);
+    }
+
+    // digits * 10^exponent as n / d * 2^binary with a quotient of 66 or 67 bits
+    let mut n = Big::from_digits(&digits);
+    let mut d = Big::from_u128(1);
+    match exponent >= 0 {
+        true => n.mul_pow5(exponent as u64),
+        false => d.mul_pow5(exponent.unsigned_abs()),
+    }
+    let mut binary = exponent;
+    let shift = 66 - (n.bit_len() as i64 - d.bit_len() as i64);
+    match shift > 0 {
+        true => n.shl(shift as u64),
+        false => d.shl(shift.unsigned_abs()),
+    }
+    binary -= shift;
+    let (q, sticky) = n.div(&d);
+    round(negative, q, sticky, binary)
+}
+
+/// Round `(q + sticky) * 2^exponent` to a long double, `sticky` standing for nonzero
+/// bits below those of q. Also returns whether the result is out of range.
+fn round(negative: bool, q: u128, sticky: bool, exponent: i64) -> (Float80, bool) {
+    if q == 0 {
+        return (Float80::zero(negative), sticky);
+    }
+    let bits = 128 - q.leading_zeros() as i64;
+    let mut exp = exponent.saturating_add(bits - 64).max(MIN_EXP);
+    let drop = exp.saturating_sub(exponent);
+    let (mut mantissa, half, rest) = match drop {
+        ..=0 => (q << -drop, false, sticky),
+        1..=127 => (
+            q >> drop,
+            q >> (drop - 1) & 1 == 1,
+            q & ((1 << (drop - 1)) - 1) != 0 || sticky,
+        ),
+        128 => (0, q >> 127 == 1, q & ((1 << 127) - 1) != 0 || sticky),
+        _ => (0, false, true),
+    };
+    if half && (rest || mantissa & 1 == 1) {
+        mantissa += 1;
+        if mantissa == 1 << 64 {
+            mantissa = 1 << 63;
+            exp += 1;
+        }
+    }
+    if exp > MAX_EXP {
+        let value = Float80 {
+            negative,
+            class: Class::Infinite,
+        };
+        return (value, true);
+    }
+    let value = Float80 {
+        negative,
+        class: Class::Finite {
+            mantissa: mantissa as u64,
+            exponent: exp as i32,
+        },
+    };
+    (value, (half || rest) && mantissa < 1 << 63)
+}
+
+/// The exact decimal digits of `mantissa * 2^exponent` without trailing zeros, and
+/// the position of the decimal point relative to the first digit
+fn decimal(mantissa: u64, exponent: i32) -> (Vec<u8>, i64) {
+    if mantissa == 0 {
+        return (Vec::new(), 0);
+    }
+    let mut n = Big::from_u128(mantissa as u128);
+    let (mut digits, point) = match exponent >= 0 {
+        true => {
+            n.shl(exponent as u64);
+            let digits = n.into_decimal();
+            let point = digits.len() as i64;
+            (digits, point)
+        }
+        false => {
+            n.mul_pow5(exponent.unsigned_abs() as u64);
+            let digits = n.into_decimal();
+            let point = digits.len() as i64 + exponent as i64;
+            (digits, point)
+        }
+    };
+    while digits.last() == Some(&b'0') {
+        digits.pop();
+    }
+    (digits, point)
+}
+
+/// Round to the first `keep` digits, half to even, `keep` may be past either end
+fn round_digits(mut digits: Vec<u8>, mut point: i64, keep: i64) -> (Vec<u8>, i64) {
+    if keep >= digits.len() as i64 {
+        return (digits, point);
+    }
+    if keep < 0 {
+        return (Vec::new(), point);
+    }
+    let keep = keep as usize;
+    let next = digits[keep];
+    let odd = keep > 0 && (digits[keep - 1] - b'0') % 2 == 1;
+    let round_up = next > b'5' || (next == b'5' && (digits.len() > keep + 1 || odd));
+    digits.truncate(keep);
+    if round_up {
+        while let Some(last) = digits.pop() {
+            if last != b'9' {
+                digits.push(last + 1);
+                return (digits, point);
+            }
+        }
+        digits.push(b'1');
+        point += 1;
+    }
+    (digits, point)
+}
+
+fn digit_at(digits: &[u8], i: i64) -> u8 {
+    match usize::try_from(i) {
+        Ok(i) if i < digits.len() => digits[i],
+        _ => b'0',
+    }
+}
+
+fn format_fixed(digits: Vec<u8>, point: i64, precision: usize, alt: bool) -> Vec<u8> {
+    let (digits, point) = round_digits(digits, point, point + precision as i64);
+    let mut out = match point > 0 {
+        true => (0..point).map(|i| digit_at(&digits, i)).collect(),
+        false => vec![b'0'],
+    };
+    if precision > 0 || alt {
+        out.push(b'.');
+    }
+    out.extend((point..point + precision as i64).map(|i| digit_at(&digits, i)));
+    out
+}
+
+fn format_exponent(
+    digits: Vec<u8>,
+    point: i64,
+    precision: usize,
+    alt: bool,
+    upper: bool,
+) -> Vec<u8> {
+    let (digits, exponent) = match digits.is_empty() {
+        true => (digits, 0),
+        false => {
+            let (digits, point) = round_digits(digits, point, precision as i64 + 1);
+            (digits, point - 1)
+        }
+    };
+    let mut out = vec![digit_at(&digits, 0)];
+    if precision > 0 || alt {
+        out.push(b'.');
+    }
+    out.extend((1..=precision as i64).map(|i| digit_at(&digits, i)));
+    out.push(if upper { b'E' } else { b'e' });
+    out.push(if exponent < 0 { b'-' } else { b'+' });
+    out.extend(format!("{:02}", exponent.unsigned_abs()).bytes());
+    out
+}
+
+/// %g's removal of trailing zeros, and of the point when nothing follows it
+fn strip_fraction_zeros(out: &mut Vec<u8>) {
+    let end = out
+        .iter()
+        .position(|c| c.eq_ignore_ascii_case(&b'e'))
+        .unwrap_or(out.len());
+    if !out[..end].contains(&b'.') {
+        return;
+    }
+    let zeros = out[..end].iter().rev().take_while(|c| **c == b'0').count();
+    let point = (out[end - zeros - 1] == b'.') as usize;
+    out.drain(end - zeros - point..end);
+}
+
+/// glibc's %a for long doubles: the leading hex digit holds the top 4 bits of the
+/// mantissa, so 1 is printed as 0x8p-3
+fn format_hex(
+    mantissa: u64,
+    exponent: i32,
+    precision: Option<usize>,
+    alt: bool,
+    upper: bool,
+) -> Vec<u8> {
+    let (mut leading, mut exponent) = match mantissa {
+        0 => (0, 0),
+        _ => ((mantissa >> 60) as u8, exponent as i64 + 60),
+    };
+    let mut fraction: Vec<u8> = (0..15)
+        .rev()
+        .map(|i| (mantissa >> (4 * i) & 0xf) as u8)
+        .collect();
+    match precision {
+        None => {
+            while fraction.last() == Some(&0) {
+                fraction.pop();
+            }
+        }
+        Some(precision) if precision < fraction.len() => {
+            let next = fraction[precision];
+            let more = fraction[precision + 1..].iter().any(|d| *d != 0);
+            let last = match precision {
+                0 => leading,
+                _ => fraction[precision - 1],
+            };
+            fraction.truncate(precision);
+            if next > 8 || (next == 8 && (more || last & 1 == 1)) {
+                let carry = fraction.iter().rposition(|d| *d != 0xf);
+                fraction[carry.map_or(0, |i| i + 1)..].fill(0);
+                match carry {
+                    Some(i) => fraction[i] += 1,
+                    None if leading == 0xf => {
+                        leading = 1;
+                        exponent += 4;
+                    }
+                    None => leading += 1,
+                }
+            }
+        }
+        Some(precision) => fraction.resize(precision, 0),
+    }
+
+    let hex = match upper {
+        true => b"0123456789ABCDEF",
+        false => b"0123456789abcdef",
+    };
+    let mut out = match upper {
+        true => b"0X".to_vec(),
+        false => b"0x".to_vec(),
+    };
+    out.push(hex[leading as usize]);
+    if !fraction.is_empty() || alt {
+        out.push(b'.');
+    }
+    out.extend(fraction.iter().map(|d| hex[*d as usize]));
+    out.push(if upper { b'P' } else { b'p' });
+    out.extend(format!("{exponent:+}").bytes());
+    out
+}
+
+/// Just enough of an arbitrary precision unsigned integer for exact conversions,
+/// little endian limbs without high zero limbs
+#[derive(Clone, Default)]
+struct Big(Vec<u32>);
+
+impl Big {
+    fn from_u128(mut n: u128) -> Big {
+        let mut limbs = Vec::new();
+        while n != 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        Big(limbs)
+    }
+
+    fn from_digits(digits: &[u8]) -> Big {
+        let mut n = Big::default();
+        for chunk in digits.chunks(9) {
+            let value = chunk.iter().fold(0, |v, d| v * 10 + (d - b'0') as u32);
+            n.mul_add(10u32.pow(chunk.len() as u32), value);
+        }
+        n
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn bit_len(&self) -> u64 {
+        match self.0.last() {
+            Some(top) => self.0.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    /// `self = self * m + a`, m must not be 0
+    fn mul_add(&mut self, m: u32, a: u32) {
+        let mut carry = a as u64;
+        for limb in &mut self.0 {
+            let t = *limb as u64 * m as u64 + carry;
+            *limb = t as u32;
+            carry = t >> 32;
+        }
+        if carry != 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    fn mul_pow5(&mut self, mut k: u64) {
+        while k >= 13 {
+            self.mul_add(1220703125, 0);
+            k -= 13;
+        }
+        self.mul_add(5u32.pow(k as u32), 0);
+    }
+
+    fn shl(&mut self, bits: u64) {
+        if self.is_zero() {
+            return;
+        }
+        let shift = (bits % 32) as u32;
+        if shift != 0 {
+            let mut carry = 0;
+            for limb in &mut self.0 {
+                let t = (*limb as u64) << shift | carry;
+                *limb = t as u32;
+                carry = t >> 32;
+            }
+            if carry != 0 {
+                self.0.push(carry as u32);
+            }
+        }
+        let limbs = (bits / 32) as usize;
+        self.0.splice(0..0, std::iter::repeat(0).take(limbs));
+    }
+
+    /// `self >> bits` for a result which fits 128 bits, and whether any bit shifted
+    /// out was set
+    fn shr_u128(&self, bits: u64) -> (u128, bool) {
+        let limbs = (bits / 32) as usize;
+        let shift = bits % 32;
+        let q = self.0[limbs.min(self.0.len())..]
+            .iter()
+            .rev()
+            .fold(0u128, |q, limb| q << 32 | *limb as u128);
+        let sticky = self.0[..limbs.min(self.0.len())]
+            .iter()
+            .any(|limb| *limb != 0)
+            || self
+                .0
+                .get(limbs)
+                .is_some_and(|limb| limb & ((1 << shift) - 1) != 0);
+        (q >> shift, sticky)
+    }
+
+    fn cmp(&self, other: &Big) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+
+    /// `self -= other`, other must not be larger
+    fn sub(&mut self, other: &Big) {
+        let mut borrow = 0;
+        for (i, limb) in self.0.iter_mut().enumerate() {
+            let t = *limb as i64 - borrow - *other.0.get(i).unwrap_or(&0) as i64;
+            *limb = t as u32;
+            borrow = (t < 0) as i64;
+        }
+        self.trim();
+    }
+
+    /// `self / d` for a quotient which fits 128 bits, and whether there's a remainder
+    fn div(mut self, d: &Big) -> (u128, bool) {
+        let mut q = 0;
+        if self.bit_len() >= d.bit_len() {
+            for shift in (0..=self.bit_len() - d.bit_len()).rev() {
+                let mut t = d.clone();
+                t.shl(shift);
+                if self.cmp(&t) != Ordering::Less {
+                    self.sub(&t);
+                    q |= 1 << shift;
+                }
+            }
+        }
+        (q, !self.is_zero())
+    }
+
+    fn div_small(&mut self, d: u32) -> u32 {
+        let mut rem = 0;
+        for limb in self.0.iter_mut().rev() {
+            let t = rem << 32 | *limb as u64;
+            *limb = (t / d as u64) as u32;
+            rem = t % d as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+
+    fn into_decimal(mut self) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        while !self.is_zero() {
+            chunks.push(self.div_small(1_000_000_000));
+        }
+        let mut digits = match chunks.pop() {
+            Some(top) => top.to_string().into_bytes(),
+            None => return Vec::new(),
+        };
+        for chunk in chunks.iter().rev() {
+            digits.extend(format!("{chunk:09}").bytes());
+        }
+        digits
+    }
+}
@@ -0,0 +1,808 @@
+/*
+ * fmt - simple optimal text formatter
+ *
+ * Follows GNU fmt closely: a paragraph is broken into words, then every suffix of
+ * it is given its cheapest set of line breaks (a line's cost grows with the square
+ * of its distance from the goal width, with bonuses for breaking at sentence ends
+ * and penalties for widows and orphans). A sentence ends at a word ending in
+ * [.?!] (optionally followed by closing quotes/brackets) which is followed by the
+ * end of the line or at least two spaces.
+ *
+ * With -p only lines starting with PREFIX (after optional leading blanks) are
+ * reformatted, the prefix is kept on every output line and everything else is
+ * copied through unchanged.
+ */
+
+use crate::{reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "fmt", version, long_about = None)]
+#[command(about = "Reformat each paragraph in the FILE(s), writing to standard output")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Preserve indentation of first two lines
+    #[clap(long, short, action)]
+    crown_margin: bool,
+    /// Reformat only lines beginning with STRING, reattaching the prefix to reformatted lines
+    #[clap(long, short, value_name = "STRING")]
+    prefix: Option<String>,
+    /// Split long lines, but do not refill
+    #[clap(long, short, action)]
+    split_only: bool,
+    /// Indentation of first line different from second
+    #[clap(long, short, action)]
+    tagged_paragraph: bool,
+    /// One space between words, two after sentences
+    #[clap(long, short, action)]
+    uniform_spacing: bool,
+    /// Maximum line width (default of 75 columns)
+    #[clap(long, short, value_name = "WIDTH")]
+    width: Option<String>,
+    /// Goal width (default of 93% of width)
+    #[clap(long, short, value_name = "WIDTH")]
+    goal: Option<String>,
+    /// Files to format, stdin by default
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+const EOF: i32 = -1;
+const TAB_WIDTH: i64 = 8;
+
+// Default maximum line width, lines aim to be LEEWAY % shorter than that
+const WIDTH: i64 = 75;
+const LEEWAY: i64 = 7;
+// Secondary indent of tagged paragraphs with no multi-line paragraph before them
+const DEF_INDENT: i64 = 3;
+
+// Paragraphs longer than this are flushed in pieces at a cheap break point
+const MAX_WORDS: usize = 1000;
+const MAX_CHARS: usize = 5000;
+
+/*
+ * Costs and bonuses are the equivalent departure from the goal line length,
+ * multiplied by 10 and squared (so a cost of EQUIV(50) is as bad as a line 5
+ * columns off). These are GNU's numbers, output should match it exactly.
+ */
+type Cost = i64;
+
+const fn equiv(n: i64) -> Cost {
+    n * n
+}
+
+fn short_cost(n: i64) -> Cost {
+    equiv(n * 10)
+}
+
+fn ragged_cost(n: i64) -> Cost {
+    short_cost(n) / 2
+}
+
+fn widow_cost(n: i64) -> Cost {
+    equiv(200) / (n + 2)
+}
+
+fn orphan_cost(n: i64) -> Cost {
+    equiv(150) / (n + 2)
+}
+
+const LINE_COST: Cost = equiv(70);
+const SENTENCE_BONUS: Cost = equiv(50);
+const NOBREAK_COST: Cost = equiv(600);
+const PAREN_BONUS: Cost = equiv(40);
+const PUNCT_BONUS: Cost = equiv(40);
+const LINE_CREDIT: Cost = equiv(3);
+
+fn is_open(c: u8) -> bool {
+    b"(['`\"".contains(&c)
+}
+
+fn is_close(c: u8) -> bool {
+    b")]'\"".contains(&c)
+}
+
+fn is_period(c: u8) -> bool {
+    b".?!".contains(&c)
+}
+
+// isspace(3) in the C locale
+fn is_space(c: i32) -> bool {
+    matches!(c, 0x20 | 0x09..=0x0d)
+}
+
+struct Options {
+    crown: bool,
+    tagged: bool,
+    split: bool,
+    uniform: bool,
+    // The prefix without its surrounding spaces, and the lengths around it
+    prefix: Vec<u8>,
+    prefix_lead_space: i64,
+    prefix_full_length: i64,
+    max_width: i64,
+    goal_width: i64,
+}
+
+impl Options {
+    fn set_prefix(&mut self, prefix: &str) {
+        let trimmed = prefix.trim_start_matches(' ');
+        self.prefix_lead_space = (prefix.len() - trimmed.len()) as i64;
+        self.prefix_full_length = trimmed.len() as i64;
+        self.prefix = trimmed.trim_end_matches(' ').as_bytes().to_vec();
+    }
+}
+
+#[derive(Clone, Default)]
+struct Word {
+    // Offset of the text in the paragraph buffer
+    start: usize,
+    length: i64,
+    // Size of the following space
+    space: i64,
+    // Starts with an open paren, ends in [.?!])*, ends in punctuation, ends a sentence
+    paren: bool,
+    period: bool,
+    punct: bool,
+    last: bool,
+    // Filled in by `fmt_paragraph`: the best line starting here and its cost
+    line_length: i64,
+    best_cost: Cost,
+    next_break: usize,
+}
+
+/// Formatting state of a single input file
+struct Formatter<'a> {
+    opts: &'a Options,
+    input: &'a mut dyn BufRead,
+    out: &'a mut dyn Write,
+    // Input column of the current character and output column
+    in_column: i64,
+    out_column: i64,
+    // Text and words of the paragraph so far
+    text: Vec<u8>,
+    words: Vec<Word>,
+    // Whether input used tabs in leading white space, reintroduced on output
+    tabs: bool,
+    prefix_indent: i64,
+    first_indent: i64,
+    other_indent: i64,
+    next_char: i32,
+    next_prefix_indent: i64,
+    last_line_length: i64,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(opts: &'a Options, input: &'a mut dyn BufRead, out: &'a mut dyn Write) -> Self {
+        Formatter {
+            opts,
+            input,
+            out,
+            in_column: 0,
+            out_column: 0,
+            text: Vec::new(),
+            words: Vec::new(),
+            tabs: false,
+            prefix_indent: 0,
+            first_indent: 0,
+            other_indent: 0,
+            next_char: EOF,
+            next_prefix_indent: 0,
+            last_line_length: 0,
+        }
+    }
+
+    fn getc(&mut self) -> io::Result<i32> {
+        let buf = self.input.fill_buf()?;
+        let Some(&c) = buf.first() else {
+            return Ok(EOF);
+        };
+        self.input.consume(1);
+        Ok(c as i32)
+    }
+
+    fn putc(&mut self, c: u8) -> io::Result<()> {
+        self.out.write_all(&[c])
+    }
+
+    fn run(&mut self) -> io::Result<()> {
+        self.next_char = self.get_prefix()?;
+        while self.get_paragraph()? {
+            if self.words.is_empty() {
+                continue;
+            }
+            self.fmt_paragraph();
+            self.put_paragraph(self.words.len())?;
+        }
+        Ok(())
+    }
+
+    fn set_other_indent(&mut self, same_paragraph: bool) {
+        let opts = self.opts;
+        if opts.split {
+            self.other_indent = self.first_indent;
+        } else if opts.crown {
+            self.other_indent = match same_paragraph {
+                true => self.in_column,
+                false => self.first_indent,
+            };
+        } else if opts.tagged {
+            if same_paragraph && self.in_column != self.first_indent {
+                self.other_indent = self.in_column;
+            } else if self.other_indent == self.first_indent {
+                // A one line paragraph reuses the last secondary indent, unless it's the same
+                self.other_indent = if self.first_indent == 0 { DEF_INDENT } else { 0 };
+            }
+        } else {
+            self.other_indent = self.first_indent;
+        }
+    }
+
+    /// Read a paragraph, copying through any blank or unprefixed lines before it.
+    /// Returns false at EOF before the start of a paragraph.
+    fn get_paragraph(&mut self) -> io::Result<bool> {
+        let opts = self.opts;
+        self.last_line_length = 0;
+        let mut c = self.next_char;
+
+        while c == b'\n' as i32
+            || c == EOF
+            || self.next_prefix_indent < opts.prefix_lead_space
+            || self.in_column < self.next_prefix_indent + opts.prefix_full_length
+        {
+            c = self.copy_rest(c)?;
+            if c == EOF {
+                self.next_char = EOF;
+                return Ok(false);
+            }
+            self.putc(b'\n')?;
+            c = self.get_prefix()?;
+        }
+
+        self.prefix_indent = self.next_prefix_indent;
+        self.first_indent = self.in_column;
+        self.text.clear();
+        self.words.clear();
+        c = self.get_line(c)?;
+        self.set_other_indent(self.same_para(c));
+
+        if opts.split {
+            // A paragraph is a single line
+        } else if opts.crown || opts.tagged {
+            // The first line's indent is free (but must differ from the rest when tagged)
+            if self.same_para(c) && (opts.crown || self.in_column != self.first_indent) {
+                loop {
+                    c = self.get_line(c)?;
+                    if !(self.same_para(c) && self.in_column == self.other_indent) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            while self.same_para(c) && self.in_column == self.other_indent {
+                c = self.get_line(c)?;
+            }
+        }
+
+        // Nothing is left when a long paragraph was flushed right at its end
+        if let Some(last) = self.words.last_mut() {
+            last.period = true;
+            last.last = true;
+        }
+        self.next_char = c;
+        Ok(true)
+    }
+
+    /// Copy a line that didn't match the prefix (C being the mismatching character),
+    /// or was blank after it (C being newline or EOF). Returns the line's terminator.
+    fn copy_rest(&mut self, mut c: i32) -> io::Result<i32> {
+        let opts = self.opts;
+        self.out_column = 0;
+        if self.in_column > self.next_prefix_indent || (c != b'\n' as i32 && c != EOF) {
+            self.put_space(self.next_prefix_indent)?;
+            let mut prefix = opts.prefix.iter();
+            while self.out_column != self.in_column {
+                let Some(&p) = prefix.next() else {
+                    break;
+                };
+                self.putc(p)?;
+                self.out_column += 1;
+            }
+            if c != EOF && c != b'\n' as i32 {
+                self.put_space(self.in_column - self.out_column)?;
+            }
+            if c == EOF && self.in_column >= self.next_prefix_indent + opts.prefix.len() as i64 {
+                self.putc(b'\n')?;
+            }
+        }
+        while c != b'\n' as i32 && c != EOF {
+            self.putc(c as u8)?;
+            c = self.getc()?;
+        }
+        Ok(c)
+    }
+
+    /// Whether a line starting with C (after the prefix) continues the current paragraph
+    fn same_para(&self, c: i32) -> bool {
+        self.next_prefix_indent == self.prefix_indent
+            && self.in_column >= self.next_prefix_indent + self.opts.prefix_full_length
+            && c != b'\n' as i32
+            && c != EOF
+    }
+
+    /// Split the rest of a line, starting with C, into words.
+    /// Returns the first non-blank character of the next line (after its prefix).
+    fn get_line(&mut self, mut c: i32) -> io::Result<i32> {
+        loop {
+            // Scan a word
+            let mut start = self.text.len();
+            loop {
+                if self.text.len() == MAX_CHARS {
+                    self.set_other_indent(true);
+                    start -= self.flush_paragraph(start)?;
+                }
+                self.text.push(c as u8);
+                c = self.getc()?;
+                if c == EOF || is_space(c) {
+                    break;
+                }
+            }
+            let length = (self.text.len() - start) as i64;
+            self.in_column += length;
+            let mut word = self.word(start, length);
+
+            // Scan the space after it
+            let column = self.in_column;
+            c = self.get_space(c)?;
+            word.space = self.in_column - column;
+            word.last = c == EOF || (word.period && (c == b'\n' as i32 || word.space > 1));
+            if c == b'\n' as i32 || c == EOF || self.opts.uniform {
+                word.space = if word.last { 2 } else { 1 };
+            }
+            if self.words.len() == MAX_WORDS - 2 {
+                self.set_other_indent(true);
+                word.start -= self.flush_paragraph(start)?;
+            }
+            self.words.push(word);
+            if c == b'\n' as i32 || c == EOF {
+                return self.get_prefix();
+            }
+        }
+    }
+
+    fn word(&self, start: usize, length: i64) -> Word {
+        let text = &self.text[start..];
+        let mut finish = text.len() - 1;
+        let paren = is_open(text[0]);
+        let punct = text[finish].is_ascii_punctuation();
+        while finish > 0 && is_close(text[finish]) {
+            finish -= 1;
+        }
+        Word {
+            start,
+            length,
+            paren,
+            period: is_period(text[finish]),
+            punct,
+            ..Word::default()
+        }
+    }
+
+    /// Read the prefix of a line, returning the first character not matching it or
+    /// the first non-blank one after it
+    fn get_prefix(&mut self) -> io::Result<i32> {
+        let opts = self.opts;
+        self.in_column = 0;
+        let c = self.getc()?;
+        let mut c = self.get_space(c)?;
+        if opts.prefix.is_empty() {
+            self.next_prefix_indent = opts.prefix_lead_space.min(self.in_column);
+        } else {
+            self.next_prefix_indent = self.in_column;
+            for &p in &opts.prefix {
+                if c != p as i32 {
+                    return Ok(c);
+                }
+                self.in_column += 1;
+                c = self.getc()?;
+            }
+            c = self.get_space(c)?;
+        }
+        Ok(c)
+    }
+
+    /// Skip blanks starting with C, keeping track of the column
+    fn get_space(&mut self, mut c: i32) -> io::Result<i32> {
+        loop {
+            if c == b' ' as i32 {
+                self.in_column += 1;
+            } else if c == b'\t' as i32 {
+                self.tabs = true;
+                self.in_column = (self.in_column / TAB_WIDTH + 1) * TAB_WIDTH;
+            } else {
+                return Ok(c);
+            }
+            c = self.getc()?;
+        }
+    }
+
+    /// Output the words so far up to a cheap break near the end, and keep the rest
+    /// (along with the word at PENDING still being read) as the start of the
+    /// paragraph. Returns by how much the text was shifted.
+    fn flush_paragraph(&mut self, pending: usize) -> io::Result<usize> {
+        if self.words.is_empty() {
+            // It's all one (unfinished) word, just write it out
+            self.out.write_all(&self.text)?;
+            self.text.clear();
+            return Ok(0);
+        }
+
+        self.fmt_paragraph();
+
+        let limit = self.words.len();
+        let mut split_point = limit;
+        let mut best_break = Cost::MAX;
+        let mut w = self.words[0].next_break;
+        while w != limit {
+            let next = self.words[w].next_break;
+            let next_cost = if next == limit { 0 } else { self.words[next].best_cost };
+            if self.words[w].best_cost - next_cost < best_break {
+                split_point = w;
+                best_break = self.words[w].best_cost - next_cost;
+            }
+            if best_break <= Cost::MAX - LINE_CREDIT {
+                best_break += LINE_CREDIT;
+            }
+            w = next;
+        }
+        self.put_paragraph(split_point)?;
+
+        let shift = match self.words.get(split_point) {
+            Some(word) => word.start,
+            None => pending,
+        };
+        self.text.drain(..shift);
+        self.words.drain(..split_point);
+        for word in &mut self.words {
+            word.start -= shift;
+        }
+        Ok(shift)
+    }
+
+    /// Find the cheapest breaks for every suffix of the paragraph, from the end
+    fn fmt_paragraph(&mut self) {
+        let opts = self.opts;
+        let limit = self.words.len();
+        // The sentinel past the last word can't ever fit on a line
+        self.words.push(Word {
+            length: opts.max_width,
+            ..Word::default()
+        });
+
+        for start in (0..limit).rev() {
+            let mut best = Cost::MAX;
+            let mut len = if start == 0 { self.first_indent } else { self.other_indent };
+
+            // At least one word, however long, on the line
+            let mut w = start;
+            len += self.words[w].length;
+            loop {
+                w += 1;
+
+                // Consider breaking before w
+                let mut wcost = self.line_cost(w, len) + self.words[w].best_cost;
+                if start == 0 && self.last_line_length > 0 {
+                    wcost += ragged_cost(len - self.last_line_length);
+                }
+                if wcost < best {
+                    best = wcost;
+                    self.words[start].next_break = w;
+                    self.words[start].line_length = len;
+                }
+
+                if w == limit {
+                    break;
+                }
+                len += self.words[w - 1].space + self.words[w].length;
+                if len >= opts.max_width {
+                    break;
+                }
+            }
+            self.words[start].best_cost = best + self.base_cost(start);
+        }
+
+        self.words.pop();
+    }
+
+    /// The cost of breaking before word W which doesn't depend on the line length
+    fn base_cost(&self, w: usize) -> Cost {
+        let words = &self.words;
+        let mut cost = LINE_COST;
+
+        if w > 0 {
+            let prev = &words[w - 1];
+            if prev.period {
+                cost += if prev.last { -SENTENCE_BONUS } else { NOBREAK_COST };
+            } else if prev.punct {
+                cost -= PUNCT_BONUS;
+            } else if w > 1 && words[w - 2].last {
+                cost += widow_cost(prev.length);
+            }
+        }
+
+        if words[w].paren {
+            cost -= PAREN_BONUS;
+        } else if words[w].last {
+            cost += orphan_cost(words[w].length);
+        }
+        cost
+    }
+
+    /// The cost of breaking before word NEXT which depends on LEN, the length of the
+    /// line ending there
+    fn line_cost(&self, next: usize, len: i64) -> Cost {
+        let limit = self.words.len() - 1;
+        if next == limit {
+            return 0;
+        }
+        let mut cost = short_cost(self.opts.goal_width - len);
+        if self.words[next].next_break != limit {
+            cost += ragged_cost(len - self.words[next].line_length);
+        }
+        cost
+    }
+
+    /// Output the paragraph up to (not including) word FINISH, which must be a break
+    fn put_paragraph(&mut self, finish: usize) -> io::Result<()> {
+        self.put_line(0, self.first_indent)?;
+        let mut w = self.words[0].next_break;
+        while w != finish {
+            self.put_line(w, self.other_indent)?;
+            w = self.words[w].next_break;
+        }
+        Ok(())
+    }
+
+    fn put_line(&mut self, first: usize, indent: i64) -> io::Result<()> {
+        let opts = self.opts;
+        self.out_column = 0;
+        self.put_space(self.prefix_indent)?;
+        self.out.write_all(&opts.prefix)?;
+        self.out_column += opts.prefix.len() as i64;
+        self.put_space(indent - self.out_column)?;
+
+        let end = self.words[first].next_break - 1;
+        for w in first..=end {
+            let Word { start, length, .. } = self.words[w];
+            self.out.write_all(&self.text[start..start + length as usize])?;
+            self.out_column += length;
+            if w != end {
+                self.put_space(self.words[w].space)?;
+            }
+        }
+        self.last_line_length = self.out_column;
+        self.putc(b'\n')
+    }
+
+    /// Output SPACE blanks, as tabs too if the input used them
+    fn put_space(&mut self, space: i64) -> io::Result<()> {
+        let target = self.out_column + space;
+        if self.tabs {
+            let tab_target = target / TAB_WIDTH * TAB_WIDTH;
+            if self.out_column + 1 < tab_target {
+                while self.out_column < tab_target {
+                    self.putc(b'\t')?;
+                    self.out_column = (self.out_column / TAB_WIDTH + 1) * TAB_WIDTH;
+                }
+            }
+        }
+        while self.out_column < target {
+            self.putc(b' ')?;
+            self.out_column += 1;
+        }
+        Ok(())
+    }
+}
+
+fn parse_width(width: &str, max: i64) -> Result<i64, String> {
+    match width.parse::<i64>() {
+        Ok(n) if (0..=max).contains(&n) => Ok(n),
+        Ok(_) => Err(format!("invalid width: '{width}': Numerical result out of range")),
+        Err(_) => Err(format!("invalid width: '{width}'")),
+    }
+}
+
+// The obsolete `-WIDTH` form is only accepted as the very first argument
+fn obsolete_width(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    if let Some(first) = args.get(1).and_then(|arg| arg.to_str()) {
+        if first.len() > 1 && first.starts_with('-') && first.as_bytes()[1].is_ascii_digit() {
+            args[1] = OsString::from(format!("-w{}", &first[1..]));
+        }
+    }
+    args
+}
+
+fn options(args: &Cli) -> Result<Options, String> {
+    let mut opts = Options {
+        crown: args.crown_margin,
+        tagged: args.tagged_paragraph,
+        split: args.split_only,
+        uniform: args.uniform_spacing,
+        prefix: Vec::new(),
+        prefix_lead_space: 0,
+        prefix_full_length: 0,
+        max_width: WIDTH,
+        goal_width: 0,
+    };
+    if let Some(prefix) = &args.prefix {
+        opts.set_prefix(prefix);
+    }
+    if let Some(width) = &args.width {
+        // Very wide lines make for ugly output, cap them like GNU does
+        opts.max_width = parse_width(width, MAX_CHARS as i64 / 2)?;
+    }
+    match &args.goal {
+        Some(goal) => {
+            opts.goal_width = parse_width(goal, opts.max_width)?;
+            if args.width.is_none() {
+                opts.max_width = opts.goal_width + 10;
+            }
+        }
+        None => opts.goal_width = opts.max_width * (2 * (100 - LEEWAY) + 1) / 200,
+    }
+    Ok(opts)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args = Cli::parse_from(obsolete_width(args));
+    let opts = match options(&args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("fmt: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("fmt: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files = match args.files.is_empty() {
+        true => vec![OsString::from("-")],
+        false => args.files,
+    };
+
+    let mut ok = true;
+    for file in files {
+        let mut input: Box<dyn BufRead> = if file == "-" {
+            Box::new(BufReader::with_capacity(IO_BUFSIZE, io::stdin().lock()))
+        } else {
+            match File::open(&file) {
+                Ok(f) => Box::new(BufReader::with_capacity(IO_BUFSIZE, f)),
+                Err(e) => {
+                    let file = file.to_string_lossy();
+                    eprintln!("fmt: cannot open '{file}' for reading: {}", strerror(&e));
+                    ok = false;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = Formatter::new(&opts, input.as_mut(), &mut out).run() {
+            eprintln!("fmt: {}: {}", file.to_string_lossy(), strerror(&e));
+            ok = false;
+        }
+    }
+    if let Err(e) = out.flush() {
+        eprintln!("fmt: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(args: &[&str], input: &str) -> String {
+        let args = ["fmt"].iter().chain(args).map(OsString::from);
+        let opts = options(&Cli::parse_from(obsolete_width(args))).unwrap();
+        let mut out = Vec::new();
+        Formatter::new(&opts, &mut input.as_bytes(), &mut out)
+            .run()
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prefix() {
+        let comments = "# This is a comment block that\n# should be reflowed to fit.\n#\n\
+                        # A second paragraph here.\ncode(); # not a prefix line\n\
+                        \x20 # indented prefix line joined\n";
+        let reflowed = "# This is a comment block\n# that should be reflowed\n# to fit.\n#\n\
+                        # A second paragraph here.\ncode(); # not a prefix line\n\
+                        \x20 # indented prefix line\n  # joined\n";
+        assert_eq!(fmt(&["-p", "# ", "-w", "30"], comments), reflowed);
+        // Blanks ending the prefix don't have to be given
+        assert_eq!(fmt(&["-p", "#", "-w", "30"], comments), reflowed);
+        assert_eq!(
+            fmt(
+                &["-p", "#", "-w", "20"],
+                "#tight text that goes past the width\n"
+            ),
+            "#tight text that\n#goes past the\n#width\n"
+        );
+        // Indentation before the prefix is kept, lines without it pass through
+        assert_eq!(
+            fmt(
+                &["--prefix=//", "-w", "30"],
+                "    // indented comment that is long enough to wrap around\n    \
+                 // the width\n    let x = 1;\n"
+            ),
+            "    // indented comment that\n    // is long enough to wrap\n    \
+             // around the width\n    let x = 1;\n"
+        );
+    }
+
+    #[test]
+    fn sentences() {
+        // Two spaces end a sentence, and the break is taken there
+        assert_eq!(
+            fmt(
+                &["-w", "20"],
+                "One two.  Three four five six seven eight nine ten eleven.\n"
+            ),
+            "One two.  Three\nfour five six\nseven eight nine\nten eleven.\n"
+        );
+        assert_eq!(fmt(&["-u"], "a   b.  c\n"), "a b.  c\n");
+        assert_eq!(fmt(&["-u"], "a   b. c\n"), "a b. c\n");
+    }
+
+    #[test]
+    fn split_only() {
+        assert_eq!(
+            fmt(&["-s", "-w", "10"], "aaa bbb ccc ddd eee fff\nggg\n"),
+            "aaa bbb\nccc ddd\neee fff\nggg\n"
+        );
+        assert_eq!(fmt(&["-5"], "a b c d e f g h\n"), "a b\nc d\ne f\ng h\n");
+    }
+
+    #[test]
+    fn indents() {
+        assert_eq!(
+            fmt(
+                &["-t", "-w", "20"],
+                "  first line of a tagged\nparagraph with more words to wrap around here\n"
+            ),
+            "  first line of a\ntagged paragraph\nwith more words to\nwrap around here\n"
+        );
+        assert_eq!(
+            fmt(
+                &["-c", "-w", "25"],
+                "  first line of a crown\n    paragraph with more words to wrap around here\n"
+            ),
+            "  first line of a crown\n    paragraph with\n    more words to wrap\n    around here\n"
+        );
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(parse_width("x", 2500), Err("invalid width: 'x'".into()));
+        assert_eq!(
+            parse_width("99999", 2500),
+            Err("invalid width: '99999': Numerical result out of range".into())
+        );
+        assert_eq!(parse_width("30", 2500), Ok(30));
+    }
+}
@@ -0,0 +1,328 @@
+/*
+ * fold - wrap each input line to fit in specified width
+ *
+ * Columns follow POSIX (and GNU's `adjust_column`): backspace goes back one column,
+ * carriage return resets to column 0 and tab advances to the next multiple of 8.
+ *
+ * Multibyte characters are still counted one column per byte, like GNU does, but
+ * unlike GNU a line is never broken in the middle of a UTF-8 sequence unless -b
+ * asks for plain byte counting.
+ */
+
+use crate::{errno_desc, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::errno::Errno;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "fold", version, long_about = None)]
+#[command(about = "Wrap input lines in each FILE, writing to standard output")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Count bytes rather than columns
+    #[clap(long, short, action)]
+    bytes: bool,
+    /// Break at spaces
+    #[clap(long, short, action)]
+    spaces: bool,
+    /// Use WIDTH columns instead of 80
+    #[clap(long, short, value_name = "WIDTH", default_value = "80")]
+    width: String,
+    /// Files to fold, stdin by default
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+const TAB_WIDTH: usize = 8;
+
+/// Line wrapping state, fed one byte at a time
+pub struct Folder {
+    width: usize,
+    count_bytes: bool,
+    break_spaces: bool,
+    column: usize,
+    line: Vec<u8>,
+}
+
+impl Folder {
+    pub fn new(width: usize, count_bytes: bool, break_spaces: bool) -> Folder {
+        Folder {
+            width,
+            count_bytes,
+            break_spaces,
+            column: 0,
+            line: Vec::new(),
+        }
+    }
+
+    fn adjust_column(&self, column: usize, c: u8) -> usize {
+        if self.count_bytes {
+            return column + 1;
+        }
+        match c {
+            b'\x08' => column.saturating_sub(1),
+            b'\r' => 0,
+            b'\t' => column + TAB_WIDTH - column % TAB_WIDTH,
+            _ => column + 1,
+        }
+    }
+
+    /// Emit `line[..end]` plus a newline and carry the rest over to the next line
+    fn break_at(&mut self, end: usize, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&self.line[..end])?;
+        out.write_all(b"\n")?;
+        self.line.drain(..end);
+        self.column = self
+            .line
+            .iter()
+            .fold(0, |column, &c| self.adjust_column(column, c));
+        Ok(())
+    }
+
+    pub fn push(&mut self, c: u8, out: &mut dyn Write) -> io::Result<()> {
+        if c == b'\n' {
+            self.line.push(c);
+            out.write_all(&self.line)?;
+            self.line.clear();
+            self.column = 0;
+            return Ok(());
+        }
+        loop {
+            let column = self.adjust_column(self.column, c);
+            if column <= self.width || self.line.is_empty() {
+                self.column = column;
+                self.line.push(c);
+                return Ok(());
+            }
+            // This character would make the line too long, start a new line with it
+            if self.break_spaces {
+                if let Some(blank) = self.line.iter().rposition(|&b| b == b' ' || b == b'\t') {
+                    self.break_at(blank + 1, out)?;
+                    continue;
+                }
+            }
+            if !self.count_bytes && (0x80..0xc0).contains(&c) {
+                // Move the whole partial UTF-8 sequence along to the next line
+                let lead = self.line.iter().rposition(|&b| !(0x80..0xc0).contains(&b));
+                match lead {
+                    Some(lead) if lead > 0 && self.line[lead] >= 0xc0 => {
+                        self.break_at(lead, out)?;
+                        continue;
+                    }
+                    // The sequence alone is wider than the line, let it overflow
+                    Some(lead) if self.line[lead] >= 0xc0 => {
+                        self.column = column;
+                        self.line.push(c);
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            let end = self.line.len();
+            self.break_at(end, out)?;
+        }
+    }
+
+    /// Write out whatever is left of the last (unterminated) line
+    pub fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&self.line)?;
+        self.line.clear();
+        self.column = 0;
+        Ok(())
+    }
+}
+
+// Rewrite the obsolete `-WIDTH` form into `-w WIDTH`
+fn obsolete_width(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    args.into_iter()
+        .map(|arg| {
+            let bytes = arg.as_bytes();
+            if bytes.len() > 1 && bytes[0] == b'-' && bytes[1..].iter().all(u8::is_ascii_digit) {
+                let mut width = OsString::from("-w");
+                width.push(std::ffi::OsStr::from_bytes(&bytes[1..]));
+                width
+            } else {
+                arg
+            }
+        })
+        .collect()
+}
+
+// -w's WIDTH, from 1 up to what leaves room for a tab past it, as GNU words the others
+fn parse_width(arg: &str) -> Result<usize, String> {
+    let invalid = format!("invalid number of columns: '{arg}'");
+    let digits = arg.strip_prefix('+').unwrap_or(arg);
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(invalid);
+    }
+    match digits.parse::<usize>() {
+        Ok(0) => Err(format!("{invalid}: {}", errno_desc(Errno::ERANGE))),
+        Ok(width) if width < usize::MAX - TAB_WIDTH => Ok(width),
+        _ => Err(format!("{invalid}: {}", errno_desc(Errno::EOVERFLOW))),
+    }
+}
+
+fn fold_file(folder: &mut Folder, input: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        for &c in buf {
+            folder.push(c, out)?;
+        }
+        let n = buf.len();
+        input.consume(n);
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args = Cli::parse_from(obsolete_width(args));
+    let width = match parse_width(&args.width) {
+        Ok(width) => width,
+        Err(e) => {
+            eprintln!("fold: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("fold: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files = match args.files.is_empty() {
+        true => vec![OsString::from("-")],
+        false => args.files,
+    };
+
+    let mut ok = true;
+    for file in files {
+        // Lines never continue across files
+        let mut folder = Folder::new(width, args.bytes, args.spaces);
+        let mut input: Box<dyn BufRead> = if file == "-" {
+            Box::new(BufReader::with_capacity(IO_BUFSIZE, io::stdin().lock()))
+        } else {
+            match File::open(&file) {
+                Ok(f) => Box::new(BufReader::with_capacity(IO_BUFSIZE, f)),
+                Err(e) => {
+                    eprintln!("fold: {}: {}", file.to_string_lossy(), strerror(&e));
+                    ok = false;
+                    continue;
+                }
+            }
+        };
+        let result = fold_file(&mut folder, input.as_mut(), &mut out);
+        if let Err(e) = result.and_then(|_| folder.finish(&mut out)) {
+            eprintln!("fold: {}: {}", file.to_string_lossy(), strerror(&e));
+            ok = false;
+        }
+    }
+    if let Err(e) = out.flush() {
+        eprintln!("fold: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(input: &[u8], width: usize, count_bytes: bool, break_spaces: bool) -> Vec<u8> {
+        let mut folder = Folder::new(width, count_bytes, break_spaces);
+        let mut out = Vec::new();
+        for &c in input {
+            folder.push(c, &mut out).unwrap();
+        }
+        folder.finish(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(fold(b"abcdefghij\n", 4, false, false), b"abcd\nefgh\nij\n");
+        // A backspace takes a column back
+        assert_eq!(fold(b"ab\x08cdef\n", 3, false, false), b"ab\x08cd\nef\n");
+        // A carriage return goes back to the first
+        assert_eq!(fold(b"abc\rdefgh\n", 4, false, false), b"abc\rdefg\nh\n");
+        // A tab goes on to the next multiple of 8
+        assert_eq!(
+            fold(b"a\tbcdefghijk\n", 10, false, false),
+            b"a\tbc\ndefghijk\n"
+        );
+        assert_eq!(fold(b"a\tb\n", 8, false, false), b"a\t\nb\n");
+        // Unterminated
+        assert_eq!(fold(b"abcde", 2, false, false), b"ab\ncd\ne");
+    }
+
+    #[test]
+    fn bytes() {
+        // Every byte one column, tabs, backspaces and carriage returns too
+        assert_eq!(fold(b"a\tb\n", 2, true, false), b"a\t\nb\n");
+        assert_eq!(fold(b"ab\x08cd\n", 3, true, false), b"ab\x08\ncd\n");
+        assert_eq!(fold(b"ab\rcd\n", 3, true, false), b"ab\r\ncd\n");
+        // UTF-8 sequences broken anywhere
+        assert_eq!(fold("é".as_bytes(), 1, true, false), b"\xc3\n\xa9");
+    }
+
+    #[test]
+    fn spaces() {
+        assert_eq!(fold(b"aaa bbb ccc\n", 5, false, true), b"aaa \nbbb \nccc\n");
+        assert_eq!(
+            fold(b"a b\tcdefghi\n", 10, false, true),
+            b"a b\t\ncdefghi\n"
+        );
+        // No blank to break at
+        assert_eq!(fold(b"abcdefg h\n", 4, false, true), b"abcd\nefg \nh\n");
+    }
+
+    #[test]
+    fn multibyte() {
+        // One column per byte, but sequences kept whole where GNU splits them
+        assert_eq!(
+            fold("aéb".as_bytes(), 2, false, false),
+            "a\né\nb".as_bytes()
+        );
+        assert_eq!(fold("日".as_bytes(), 1, false, false), "日".as_bytes());
+    }
+
+    #[test]
+    fn widths() {
+        assert_eq!(parse_width("1"), Ok(1));
+        assert_eq!(parse_width("+80"), Ok(80));
+        let invalid = |arg: &str, suffix: &str| {
+            let message = format!("invalid number of columns: '{arg}'{suffix}");
+            assert_eq!(parse_width(arg), Err(message));
+        };
+        invalid("0", ": Numerical result out of range");
+        invalid(
+            "18446744073709551615",
+            ": Value too large for defined data type",
+        );
+        invalid(
+            "99999999999999999999999",
+            ": Value too large for defined data type",
+        );
+        invalid("abc", "");
+        invalid("-3", "");
+        invalid("", "");
+    }
+
+    #[test]
+    fn obsolete_widths() {
+        let args = ["fold", "-5", "-s", "-", "--", "-x"].map(OsString::from);
+        let expected = ["fold", "-w5", "-s", "-", "--", "-x"].map(OsString::from);
+        assert_eq!(obsolete_width(args), expected);
+    }
+}
@@ -0,0 +1,75 @@
+/*
+ * Group lists of id and groups, after gnulib's group-list
+ *
+ * The real group comes first, then the effective one if it differs and the other
+ * groups of the user (from the group database) or of the process (getgroups).
+ */
+
+use crate::userspec::{group_list, group_name};
+use crate::{errno_desc, quote};
+use nix::unistd::{Uid, User};
+
+/// Append the name of `gid` with `use_name` if it has one, the number otherwise.
+/// Returns false when a name was asked for and there was none, `prog` reporting it
+pub fn print_group(prog: &str, out: &mut Vec<u8>, gid: u32, use_name: bool) -> bool {
+    let name = if use_name { group_name(gid) } else { None };
+    let missing = use_name && name.is_none();
+    if missing {
+        eprintln!("{prog}: cannot find name for group ID {gid}");
+    }
+    out.extend(name.unwrap_or_else(|| gid.to_string()).as_bytes());
+    !missing
+}
+
+/// Report that the groups of `user`, or of the process without one, couldn't be listed
+pub fn groups_error(prog: &str, user: Option<&str>, e: nix::Error) {
+    match user {
+        Some(user) => eprintln!(
+            "{prog}: failed to get groups for user {}: {}",
+            quote(user.as_bytes()),
+            errno_desc(e)
+        ),
+        None => eprintln!(
+            "{prog}: failed to get groups for the current process: {}",
+            errno_desc(e)
+        ),
+    }
+}
+
+/// Append the groups of `user` (whose ids are `ruid` and `rgid`) or of the process
+/// (with `rgid` and `egid`), separated by `delimiter`. Returns false on any error
+pub fn print_group_list(
+    prog: &str,
+    out: &mut Vec<u8>,
+    user: Option<&str>,
+    (ruid, rgid, egid): (u32, u32, u32),
+    use_names: bool,
+    delimiter: u8,
+) -> bool {
+    // The primary group of the user, which getgrouplist includes
+    let primary = match user {
+        Some(_) => User::from_uid(Uid::from_raw(ruid)).ok().flatten(),
+        None => None,
+    };
+    let mut ok = user.is_none() || primary.is_some();
+    ok &= print_group(prog, out, rgid, use_names);
+    if egid != rgid {
+        out.push(delimiter);
+        ok &= print_group(prog, out, egid, use_names);
+    }
+    let gid = primary.map_or(egid, |user| user.gid.as_raw());
+    let groups = match group_list(user, Some(gid)) {
+        Ok(groups) => groups,
+        Err(e) => {
+            groups_error(prog, user, e);
+            return false;
+        }
+    };
+    for gid in groups {
+        if gid != rgid && gid != egid {
+            out.push(delimiter);
+            ok &= print_group(prog, out, gid, use_names);
+        }
+    }
+    ok
+}
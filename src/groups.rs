@@ -0,0 +1,73 @@
+/*
+ * groups - print the groups a user is in
+ *
+ * Without USER these are the groups of the process, as `id -Gn` shows them, so
+ * credentials changed since login are what's reported. A USER's come from the group
+ * database and are prefixed with `USER : `. Groups without a name print as numbers.
+ */
+
+use crate::grouplist::print_group_list;
+use crate::userspec::lookup_user;
+use crate::{parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::unistd::{getegid, getgid, getuid};
+use std::ffi::OsString;
+use std::io::{BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "groups", version, long_about = None)]
+#[command(
+    about = "Print group memberships for each USERNAME or, if no USERNAME is \
+specified, for the current process (which may differ if the groups database has changed)"
+)]
+#[command(override_usage = "groups [OPTION]... [USERNAME]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// The users to list the groups of, the current process by default
+    #[clap(value_name = "USERNAME")]
+    users: Vec<OsString>,
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+
+    let mut out = Vec::new();
+    let mut ok = true;
+    if args.users.is_empty() {
+        let ids = (getuid().as_raw(), getgid().as_raw(), getegid().as_raw());
+        ok &= print_group_list("groups", &mut out, None, ids, true, b' ');
+        out.push(b'\n');
+    }
+    for user in &args.users {
+        let user = user.as_bytes();
+        // Names only, unlike id a number isn't taken as a uid
+        let Some(found) = lookup_user(user) else {
+            eprintln!("groups: {}: no such user", quote(user));
+            ok = false;
+            continue;
+        };
+        out.extend(user);
+        out.extend(b" : ");
+        let gid = found.gid.as_raw();
+        let ids = (found.uid.as_raw(), gid, gid);
+        ok &= print_group_list("groups", &mut out, Some(&found.name), ids, true, b' ');
+        out.push(b'\n');
+    }
+
+    let written = stdout_file().and_then(|stdout| {
+        let mut stdout = BufWriter::new(stdout);
+        stdout.write_all(&out)?;
+        stdout.flush()
+    });
+    if let Err(e) = written {
+        eprintln!("groups: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
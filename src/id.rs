@@ -0,0 +1,209 @@
+/*
+ * id - print real and effective user and group IDs
+ *
+ * Without USER the ids are the process' own, with one they are the passwd entry's
+ * and the groups come from the group database (getgrouplist). Names and group lists
+ * go through `userspec.rs` like chown's. There's no SELinux support, -Z is an error
+ * like GNU on a kernel without it.
+ */
+
+use crate::grouplist::{groups_error, print_group, print_group_list};
+use crate::userspec::{group_list, group_name, parse_user_spec, user_name};
+use crate::{parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::unistd::{getegid, geteuid, getgid, getuid, Uid, User};
+use std::ffi::OsString;
+use std::io::{BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "id", version, long_about = None)]
+#[command(about = "Print user and group information for each USER, or the current process")]
+#[command(override_usage = "id [OPTION]... [USER]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Ignore, for compatibility with other versions
+    #[clap(short = 'a', action)]
+    _all: bool,
+    /// Print only the security context of the process (needs an SELinux kernel)
+    #[clap(long, short = 'Z', action)]
+    context: bool,
+    /// Print only the effective group ID
+    #[clap(long, short, action)]
+    group: bool,
+    /// Print all group IDs
+    #[clap(long, short = 'G', action)]
+    groups: bool,
+    /// Print a name instead of a number, for -u, -g, -G
+    #[clap(long, short, action)]
+    name: bool,
+    /// Print the real ID instead of the effective ID, with -u, -g, -G
+    #[clap(long, short, action)]
+    real: bool,
+    /// Print only the effective user ID
+    #[clap(long, short, action)]
+    user: bool,
+    /// Delimit entries with NUL characters, not whitespace; not permitted in default
+    /// format
+    #[clap(long, short, action)]
+    zero: bool,
+    /// The users to describe, the current process by default
+    #[clap(value_name = "USER")]
+    users: Vec<OsString>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Ids {
+    ruid: u32,
+    euid: u32,
+    rgid: u32,
+    egid: u32,
+}
+
+// The user name of `uid` with -n, the number otherwise. A missing name is reported
+// and the number is printed instead
+fn put_user(out: &mut Vec<u8>, uid: u32, use_name: bool) -> bool {
+    let name = if use_name { user_name(uid) } else { None };
+    let missing = use_name && name.is_none();
+    if missing {
+        eprintln!("id: cannot find name for user ID {uid}");
+    }
+    out.extend(name.unwrap_or_else(|| uid.to_string()).as_bytes());
+    !missing
+}
+
+// `FIELDN(NAME)`, without the parenthesis when there's no name
+fn put_field(out: &mut Vec<u8>, field: &str, id: u32, name: Option<String>) {
+    out.extend(format!("{field}{id}").as_bytes());
+    if let Some(name) = name {
+        out.extend(format!("({name})").as_bytes());
+    }
+}
+
+// The default format, ie. `uid=0(root) gid=0(root) groups=0(root)`
+fn put_full(out: &mut Vec<u8>, ids: Ids, user: Option<&str>) -> bool {
+    put_field(out, "uid=", ids.ruid, user_name(ids.ruid));
+    put_field(out, " gid=", ids.rgid, group_name(ids.rgid));
+    if ids.euid != ids.ruid {
+        put_field(out, " euid=", ids.euid, user_name(ids.euid));
+    }
+    if ids.egid != ids.rgid {
+        put_field(out, " egid=", ids.egid, group_name(ids.egid));
+    }
+    let primary = match user {
+        Some(_) => User::from_uid(Uid::from_raw(ids.euid))
+            .ok()
+            .flatten()
+            .map(|user| user.gid.as_raw()),
+        None => Some(ids.egid),
+    };
+    let groups = match group_list(user, primary) {
+        Ok(groups) => groups,
+        Err(e) => {
+            groups_error("id", user, e);
+            return false;
+        }
+    };
+    for (i, &gid) in groups.iter().enumerate() {
+        let field = if i == 0 { " groups=" } else { "," };
+        put_field(out, field, gid, group_name(gid));
+    }
+    true
+}
+
+// The line (or NUL terminated record) of the process or of one user
+fn put_ids(out: &mut Vec<u8>, args: &Cli, ids: Ids, user: Option<&str>) -> bool {
+    let ok = if args.user {
+        put_user(out, if args.real { ids.ruid } else { ids.euid }, args.name)
+    } else if args.group {
+        let gid = if args.real { ids.rgid } else { ids.egid };
+        print_group("id", out, gid, args.name)
+    } else if args.groups {
+        let delimiter = if args.zero { b'\0' } else { b' ' };
+        let ids = (ids.ruid, ids.rgid, ids.egid);
+        print_group_list("id", out, user, ids, args.name, delimiter)
+    } else {
+        put_full(out, ids, user)
+    };
+    // Records of several users end with two NULs so they can be told apart
+    if args.zero && args.groups && args.users.len() > 1 {
+        out.extend(b"\0\0");
+    } else {
+        out.push(if args.zero { b'\0' } else { b'\n' });
+    }
+    ok
+}
+
+fn die(message: &str) -> ExitCode {
+    eprintln!("id: {message}");
+    ExitCode::FAILURE
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    if args.context {
+        return die("--context (-Z) works only on an SELinux-enabled kernel");
+    }
+    let only = [args.user, args.group, args.groups];
+    if only.iter().filter(|&&only| only).count() > 1 {
+        return die("cannot print \"only\" of more than one choice");
+    }
+    let default_format = !only.contains(&true);
+    if default_format && (args.real || args.name) {
+        return die("cannot print only names or real IDs in default format");
+    }
+    if default_format && args.zero {
+        return die("option --zero not permitted in default format");
+    }
+
+    let mut out = Vec::new();
+    let mut ok = true;
+    if args.users.is_empty() {
+        let ids = Ids {
+            ruid: getuid().as_raw(),
+            euid: geteuid().as_raw(),
+            rgid: getgid().as_raw(),
+            egid: getegid().as_raw(),
+        };
+        ok &= put_ids(&mut out, &args, ids, None);
+    }
+    for spec in &args.users {
+        let spec = spec.as_bytes();
+        // A name, or a number with a passwd entry
+        let found = match parse_user_spec(spec) {
+            _ if spec.is_empty() => None,
+            Ok((parsed, _)) => parsed
+                .uid
+                .and_then(|uid| User::from_uid(Uid::from_raw(uid)).ok().flatten()),
+            Err(_) => None,
+        };
+        let Some(found) = found else {
+            eprintln!("id: {}: no such user", quote(spec));
+            ok = false;
+            continue;
+        };
+        let ids = Ids {
+            ruid: found.uid.as_raw(),
+            euid: found.uid.as_raw(),
+            rgid: found.gid.as_raw(),
+            egid: found.gid.as_raw(),
+        };
+        ok &= put_ids(&mut out, &args, ids, Some(&found.name));
+    }
+
+    let written = stdout_file().and_then(|stdout| {
+        let mut stdout = BufWriter::new(stdout);
+        stdout.write_all(&out)?;
+        stdout.flush()
+    });
+    if let Err(e) = written {
+        eprintln!("id: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,782 @@
+/*
+ * install - copy files and set attributes
+ *
+ * Like GNU the umask is cleared: files are created with mode 0600, chowned with
+ * -o/-g and then get -m's mode (0755 by default). An existing destination is
+ * removed first, or renamed with -b, so a running program is never written over.
+ * -C leaves the destination alone when its size, mode, owner and contents already
+ * match.
+ *
+ * -d and -D create directories like gnulib's mkdir-p: missing leading directories
+ * get 0755, those named with -d get -m's mode and when they already exist only the
+ * bits the mode mentions are changed, so `-m g+s` keeps the rest.
+ */
+
+use crate::backupfile::{backup_name, backup_type, simple_suffix, BackupType};
+use crate::modechange::{ModeChange, CHMOD_MODE_BITS};
+use crate::userspec::{lookup_group, lookup_user};
+use crate::{
+    errno_desc, file_name_concat, last_component, parse_args, parse_u32, quote, shell_quote,
+    stdout_file, strerror,
+};
+use clap::Parser;
+use nix::errno::Errno;
+use nix::sys::stat::{
+    fchmodat, futimens, lstat, stat, umask, utimensat, FchmodatFlags, FileStat, Mode, SFlag,
+    UtimensatFlags,
+};
+use nix::sys::time::TimeSpec;
+use nix::unistd::{fchownat, getgid, getuid, mkdir, unlink, FchownatFlags, Gid, Uid};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::{Command, ExitCode};
+
+#[derive(Debug, Parser)]
+#[command(name = "install", version, long_about = None)]
+#[command(
+    about = "Copy SOURCE to DEST or multiple SOURCE(s) to the existing DIRECTORY, \
+                   while setting permission modes and owner/group"
+)]
+#[command(override_usage = "install [OPTION]... [-T] SOURCE DEST
+       install [OPTION]... SOURCE... DIRECTORY
+       install [OPTION]... -t DIRECTORY SOURCE...
+       install [OPTION]... -d DIRECTORY...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Make a backup of each existing destination file
+    #[clap(long, value_name = "CONTROL", num_args = 0..=1, require_equals = true)]
+    backup: Option<Option<String>>,
+    /// Like --backup but does not accept an argument
+    #[clap(short = 'b', action)]
+    backup_existing: bool,
+    /// (ignored)
+    #[clap(short = 'c', action)]
+    copy: bool,
+    /// Compare content of source and destination files, and if no change to content,
+    /// ownership, and permissions, do not modify the destination at all
+    #[clap(long, short = 'C', action)]
+    compare: bool,
+    /// Treat all arguments as directory names; create all components of the
+    /// specified directories
+    #[clap(long, short, action)]
+    directory: bool,
+    /// Create all leading components of DEST except the last, or all components of
+    /// --target-directory, then copy SOURCE to DEST
+    #[clap(short = 'D', action)]
+    create_leading: bool,
+    /// Set group ownership, instead of process' current group
+    #[clap(long, short, value_name = "GROUP")]
+    group: Option<OsString>,
+    /// Set permission mode (as in chmod), instead of rwxr-xr-x
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    mode: Option<OsString>,
+    /// Set ownership (super-user only)
+    #[clap(long, short, value_name = "OWNER")]
+    owner: Option<OsString>,
+    /// Apply access/modification times of SOURCE files to corresponding destination
+    /// files
+    #[clap(long, short, action)]
+    preserve_timestamps: bool,
+    /// Strip symbol tables
+    #[clap(long, short, action)]
+    strip: bool,
+    /// Program used to strip binaries
+    #[clap(long, value_name = "PROGRAM")]
+    strip_program: Option<OsString>,
+    /// Override the usual backup suffix
+    #[clap(long, short = 'S', value_name = "SUFFIX", allow_hyphen_values = true)]
+    suffix: Option<OsString>,
+    /// Copy all SOURCE arguments into DIRECTORY
+    #[clap(long, short, value_name = "DIRECTORY")]
+    target_directory: Option<OsString>,
+    /// Treat DEST as a normal file
+    #[clap(long, short = 'T', action)]
+    no_target_directory: bool,
+    /// Print the name of each created file or directory
+    #[clap(long, short, action)]
+    verbose: bool,
+    /// The sources and destination, or the directories with -d
+    #[clap(value_name = "SOURCE|DEST")]
+    operands: Vec<OsString>,
+}
+
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+
+struct Install {
+    mode: u32,
+    dir_mode: u32,
+    // The bits -m mentions, the others are kept on existing directories
+    dir_mode_bits: u32,
+    owner: Option<u32>,
+    group: Option<u32>,
+    backup: BackupType,
+    suffix: Vec<u8>,
+    compare: bool,
+    preserve_timestamps: bool,
+    // The strip program with -s
+    strip: Option<OsString>,
+    verbose: bool,
+    out: BufWriter<File>,
+}
+
+fn is_type(st: &FileStat, kind: SFlag) -> bool {
+    st.st_mode & SFlag::S_IFMT.bits() == kind.bits()
+}
+
+// Set-user-ID, set-group-ID or sticky bits, which -C doesn't compare
+fn extra_mode(mode: u32) -> bool {
+    mode & CHMOD_MODE_BITS & !0o777 != 0
+}
+
+fn path(name: &[u8]) -> &OsStr {
+    OsStr::from_bytes(name)
+}
+
+// Whether two files have the same contents, reading both until they differ
+fn same_content(a: &mut File, b: &mut File) -> io::Result<bool> {
+    // Fill `buffer` unless the end of file is reached first
+    fn fill(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buffer.len() {
+            match file.read(&mut buffer[n..]) {
+                Ok(0) => break,
+                Ok(read) => n += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(n)
+    }
+    let (mut buffer_a, mut buffer_b) = (vec![0; 1 << 16], vec![0; 1 << 16]);
+    loop {
+        let n = fill(a, &mut buffer_a)?;
+        let m = fill(b, &mut buffer_b)?;
+        if buffer_a[..n] != buffer_b[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn timestamps(st: &FileStat) -> (TimeSpec, TimeSpec) {
+    (
+        TimeSpec::new(st.st_atime, st.st_atime_nsec),
+        TimeSpec::new(st.st_mtime, st.st_mtime_nsec),
+    )
+}
+
+impl Install {
+    fn error(&mut self, message: &str) {
+        let _ = self.out.flush();
+        eprintln!("install: {message}");
+    }
+
+    fn announce_mkdir(&mut self, dir: &[u8]) {
+        if self.verbose {
+            let dir = shell_quote(dir, true);
+            let _ = writeln!(self.out, "install: creating directory {dir}");
+        }
+    }
+
+    // Create the missing leading directories of `name` like gnulib's mkancesdirs,
+    // the error has the leading part of `name` that couldn't be made a directory
+    fn make_ancestors(&mut self, name: &[u8]) -> Result<(), (Vec<u8>, Errno)> {
+        // The end of the last component seen, and the start of the next one
+        let (mut sep, mut component) = (None, 0);
+        for i in 0..name.len() {
+            let next = name.get(i + 1);
+            if next == Some(&b'/') {
+                if name[i] != b'/' {
+                    sep = Some(i + 1);
+                }
+                continue;
+            }
+            let Some(sep) = sep.filter(|_| name[i] == b'/' && next.is_some()) else {
+                continue;
+            };
+            let dir = &name[..sep];
+            let made = match &name[component..sep] {
+                b"." => None,
+                // Only checked
+                b".." => Some(Ok(())),
+                _ => {
+                    let made = mkdir(dir, Mode::from_bits_truncate(0o755));
+                    if made.is_ok() {
+                        self.announce_mkdir(dir);
+                    }
+                    Some(made)
+                }
+            };
+            if let Some(made) = made {
+                let found = match stat(dir) {
+                    Ok(st) if is_type(&st, SFlag::S_IFDIR) => Ok(()),
+                    Ok(_) => Err(Errno::ENOTDIR),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = found {
+                    return Err(match made {
+                        Err(made) if e == Errno::ENOENT => (dir.to_vec(), made),
+                        _ => (dir.to_vec(), e),
+                    });
+                }
+            }
+            component = i + 1;
+        }
+        Ok(())
+    }
+
+    // Set the owner and the bits -m mentions of the directory `dir`, like gnulib's
+    // dirchownmod. `created` is whether it was just made
+    fn dirchownmod(&self, dir: &[u8], created: bool) -> nix::Result<()> {
+        let st = stat(dir)?;
+        if !is_type(&st, SFlag::S_IFDIR) {
+            return Err(Errno::ENOTDIR);
+        }
+        let mut indeterminate = 0;
+        if self.owner.is_some_and(|uid| uid != st.st_uid)
+            || self.group.is_some_and(|gid| gid != st.st_gid)
+        {
+            let flag = match created {
+                true => FchownatFlags::NoFollowSymlink,
+                false => FchownatFlags::FollowSymlink,
+            };
+            let (uid, gid) = (self.owner.map(Uid::from_raw), self.group.map(Gid::from_raw));
+            fchownat(None, dir, uid, gid, flag)?;
+            // chown may clear these on executable directories
+            if st.st_mode & 0o111 != 0 {
+                indeterminate = st.st_mode & (S_ISUID | S_ISGID);
+            }
+        }
+        if ((st.st_mode ^ self.dir_mode) | indeterminate) & self.dir_mode_bits != 0 {
+            let mode = self.dir_mode | (st.st_mode & CHMOD_MODE_BITS & !self.dir_mode_bits);
+            let mode = Mode::from_bits_truncate(mode);
+            fchmodat(None, dir, mode, FchmodatFlags::FollowSymlink)?;
+        }
+        Ok(())
+    }
+
+    // -d: create `dir` with its leading directories
+    fn make_dir(&mut self, dir: &[u8]) -> bool {
+        if let Err((ancestor, e)) = self.make_ancestors(dir) {
+            let ancestor = quote(&ancestor);
+            self.error(&format!(
+                "cannot create directory {ancestor}: {}",
+                errno_desc(e)
+            ));
+            return false;
+        }
+
+        // Until the owner and special bits are right, nobody else gets to write in it
+        let keep_owner = self.owner.is_none() && self.group.is_none();
+        let keep_special =
+            ((self.dir_mode_bits & (S_ISUID | S_ISGID)) | (self.dir_mode & S_ISVTX)) == 0;
+        let mut mkdir_mode = self.dir_mode;
+        if !keep_owner {
+            mkdir_mode &= !0o077;
+        } else if !keep_special {
+            mkdir_mode &= !0o022;
+        }
+        let made = mkdir(dir, Mode::from_bits_truncate(mkdir_mode));
+        if made.is_ok() {
+            self.announce_mkdir(dir);
+            // Nothing is left to change when mkdir gave the mode asked for
+            let umask_ok = self.dir_mode & self.dir_mode_bits & 0o777 == 0;
+            if keep_owner && keep_special && umask_ok {
+                return true;
+            }
+        }
+
+        let Err(e) = self.dirchownmod(dir, made.is_ok()) else {
+            return true;
+        };
+        let message = match made {
+            Err(made) if made == Errno::ENOENT || e == Errno::ENOTDIR => {
+                format!(
+                    "cannot create directory {}: {}",
+                    quote(dir),
+                    errno_desc(made)
+                )
+            }
+            _ if keep_owner => {
+                format!(
+                    "cannot change permissions of {}: {}",
+                    quote(dir),
+                    errno_desc(e)
+                )
+            }
+            _ => format!(
+                "cannot change owner and permissions of {}: {}",
+                quote(dir),
+                errno_desc(e)
+            ),
+        };
+        self.error(&message);
+        false
+    }
+
+    // -C: whether `to` differs from `from` or from the attributes it would get
+    fn need_copy(&self, from: &[u8], to: &[u8]) -> bool {
+        if extra_mode(self.mode) {
+            return true;
+        }
+        let (Ok(src), Ok(dst)) = (lstat(from), lstat(to)) else {
+            return true;
+        };
+        if !is_type(&src, SFlag::S_IFREG)
+            || !is_type(&dst, SFlag::S_IFREG)
+            || extra_mode(src.st_mode)
+            || extra_mode(dst.st_mode)
+            || src.st_size != dst.st_size
+            || dst.st_mode & CHMOD_MODE_BITS != self.mode
+            || dst.st_uid != self.owner.unwrap_or(getuid().as_raw())
+            || dst.st_gid != self.group.unwrap_or(getgid().as_raw())
+        {
+            return true;
+        }
+        match (File::open(path(from)), File::open(path(to))) {
+            (Ok(mut a), Ok(mut b)) => !same_content(&mut a, &mut b).unwrap_or(false),
+            _ => true,
+        }
+    }
+
+    // Copy `from` to a new `to`, moving any existing `to` out of the way first
+    fn copy(&mut self, from: &[u8], to: &[u8]) -> bool {
+        let (from_name, to_name) = (shell_quote(from, true), shell_quote(to, true));
+        let src = match stat(from) {
+            Ok(src) => src,
+            Err(e) => {
+                self.error(&format!("cannot stat {from_name}: {}", errno_desc(e)));
+                return false;
+            }
+        };
+        if is_type(&src, SFlag::S_IFDIR) {
+            self.error(&format!("omitting directory {from_name}"));
+            return false;
+        }
+
+        let mut backup = None;
+        match lstat(to) {
+            Ok(dst) => {
+                if (dst.st_dev, dst.st_ino) == (src.st_dev, src.st_ino) {
+                    self.error(&format!("{from_name} and {to_name} are the same file"));
+                    return false;
+                }
+                if is_type(&dst, SFlag::S_IFDIR) {
+                    self.error(&format!(
+                        "cannot overwrite directory {to_name} with non-directory"
+                    ));
+                    return false;
+                }
+                let moved = backup_name(to, self.backup, &self.suffix).and_then(|name| {
+                    if let Some(name) = &name {
+                        fs::rename(path(to), path(name))?;
+                    }
+                    Ok(name)
+                });
+                match moved {
+                    Ok(Some(name)) => backup = Some(name),
+                    Ok(None) => match unlink(to) {
+                        Ok(()) if self.verbose => {
+                            let _ = writeln!(self.out, "removed {to_name}");
+                        }
+                        Ok(()) | Err(Errno::ENOENT) => {}
+                        Err(e) => {
+                            self.error(&format!("cannot remove {to_name}: {}", errno_desc(e)));
+                            return false;
+                        }
+                    },
+                    Err(e) => {
+                        self.error(&format!("cannot backup {to_name}: {}", strerror(&e)));
+                        return false;
+                    }
+                }
+            }
+            Err(Errno::ENOENT) => {}
+            Err(e) => {
+                self.error(&format!("cannot stat {to_name}: {}", errno_desc(e)));
+                return false;
+            }
+        }
+        if self.verbose {
+            let _ = match &backup {
+                Some(backup) => writeln!(
+                    self.out,
+                    "{from_name} -> {to_name} (backup: {})",
+                    shell_quote(backup, true)
+                ),
+                None => writeln!(self.out, "{from_name} -> {to_name}"),
+            };
+        }
+
+        let mut input = match File::open(path(from)) {
+            Ok(input) => input,
+            Err(e) => {
+                self.error(&format!(
+                    "cannot open {from_name} for reading: {}",
+                    strerror(&e)
+                ));
+                return false;
+            }
+        };
+        let output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path(to));
+        let mut output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                // Like GNU, `dir/` that doesn't exist is not a directory
+                let message = match e.raw_os_error() == Some(libc::EISDIR) && to.ends_with(b"/") {
+                    true => errno_desc(Errno::ENOTDIR),
+                    false => strerror(&e),
+                };
+                self.error(&format!("cannot create regular file {to_name}: {message}"));
+                return false;
+            }
+        };
+        let mut buffer = vec![0; 1 << 17];
+        loop {
+            let n = match input.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.error(&format!("error reading {from_name}: {}", strerror(&e)));
+                    return false;
+                }
+            };
+            if let Err(e) = output.write_all(&buffer[..n]) {
+                self.error(&format!("error writing {to_name}: {}", strerror(&e)));
+                return false;
+            }
+        }
+        if self.preserve_timestamps {
+            let (atime, mtime) = timestamps(&src);
+            if let Err(e) = futimens(output.as_raw_fd(), &atime, &mtime) {
+                self.error(&format!(
+                    "preserving times for {to_name}: {}",
+                    errno_desc(e)
+                ));
+            }
+        }
+        true
+    }
+
+    fn strip(&mut self, program: &OsStr, to: &[u8]) -> bool {
+        // So that strip doesn't take the file for an option
+        let name = match to.first() {
+            Some(b'-') => [b"./", to].concat(),
+            _ => to.to_vec(),
+        };
+        let _ = self.out.flush();
+        match Command::new(program).arg(path(&name)).status() {
+            Ok(status) if status.success() => return true,
+            Ok(_) => {}
+            Err(e) => {
+                let program = shell_quote(program.as_bytes(), true);
+                self.error(&format!("cannot run {program}: {}", strerror(&e)));
+            }
+        }
+        self.error("strip process terminated abnormally");
+        false
+    }
+
+    // Set the owner (first, as chown can clear setuid bits) and then the mode
+    fn change_attributes(&mut self, to: &[u8]) -> bool {
+        if self.owner.is_some() || self.group.is_some() {
+            let (uid, gid) = (self.owner.map(Uid::from_raw), self.group.map(Gid::from_raw));
+            if let Err(e) = fchownat(None, to, uid, gid, FchownatFlags::NoFollowSymlink) {
+                let name = shell_quote(to, true);
+                self.error(&format!(
+                    "cannot change ownership of {name}: {}",
+                    errno_desc(e)
+                ));
+                return false;
+            }
+        }
+        let mode = Mode::from_bits_truncate(self.mode);
+        if let Err(e) = fchmodat(None, to, mode, FchmodatFlags::FollowSymlink) {
+            let name = shell_quote(to, true);
+            self.error(&format!(
+                "cannot change permissions of {name}: {}",
+                errno_desc(e)
+            ));
+            return false;
+        }
+        true
+    }
+
+    fn install_file(&mut self, from: &[u8], to: &[u8]) -> bool {
+        let src = match self.preserve_timestamps {
+            true => match stat(from) {
+                Ok(src) => Some(src),
+                Err(e) => {
+                    let name = shell_quote(from, true);
+                    self.error(&format!("cannot stat {name}: {}", errno_desc(e)));
+                    return false;
+                }
+            },
+            false => None,
+        };
+        if (!self.compare || self.need_copy(from, to)) && !self.copy(from, to) {
+            return false;
+        }
+        if let Some(program) = self.strip.clone() {
+            if !self.strip(&program, to) {
+                // Don't leave an unstripped file behind
+                if let Err(e) = unlink(to) {
+                    let name = shell_quote(to, true);
+                    self.error(&format!("cannot unlink {name}: {}", errno_desc(e)));
+                }
+                return false;
+            }
+            if let Some(src) = src {
+                let (atime, mtime) = timestamps(&src);
+                let flag = UtimensatFlags::FollowSymlink;
+                if let Err(e) = utimensat(None, to, &atime, &mtime, flag) {
+                    let name = shell_quote(to, true);
+                    self.error(&format!(
+                        "cannot set timestamps for {name}: {}",
+                        errno_desc(e)
+                    ));
+                    return false;
+                }
+            }
+        }
+        self.change_attributes(to)
+    }
+
+    // -D: the leading directories of `to`, then `from` installed as `to`
+    fn install_file_in_parents(&mut self, from: &[u8], to: &[u8]) -> bool {
+        if let Err((ancestor, e)) = self.make_ancestors(to) {
+            let ancestor = shell_quote(&ancestor, true);
+            self.error(&format!(
+                "cannot create directory {ancestor}: {}",
+                errno_desc(e)
+            ));
+            return false;
+        }
+        self.install_file(from, to)
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("install: {message}\nTry 'install --help' for more information.");
+    ExitCode::FAILURE
+}
+
+fn die(message: &str) -> ExitCode {
+    eprintln!("install: {message}");
+    ExitCode::FAILURE
+}
+
+// Whether `name` can be used as the target directory, with why not
+fn directory(name: &[u8]) -> nix::Result<()> {
+    match stat(name) {
+        Ok(st) if is_type(&st, SFlag::S_IFDIR) => Ok(()),
+        Ok(_) => Err(Errno::ENOTDIR),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+
+    if args.directory && args.strip {
+        return die("the strip option may not be used when installing a directory");
+    }
+    if args.directory && args.target_directory.is_some() {
+        return die("target directory not allowed when installing a directory");
+    }
+    let make_backups = args.backup.is_some() || args.backup_existing || args.suffix.is_some();
+    let backup = match make_backups {
+        true => match backup_type(args.backup.clone().flatten().as_deref()) {
+            Ok(backup) => backup,
+            Err(message) => return usage_error(&message),
+        },
+        false => BackupType::None,
+    };
+    let suffix = simple_suffix(args.suffix.as_ref().map(|s| s.as_bytes()));
+
+    let mut files: Vec<Vec<u8>> = args
+        .operands
+        .into_iter()
+        .map(OsStringExt::into_vec)
+        .collect();
+    let mut target = args.target_directory.map(OsStringExt::into_vec);
+    let needed = if args.directory || target.is_some() {
+        1
+    } else {
+        2
+    };
+    if files.len() < needed {
+        return match files.first() {
+            None => usage_error("missing file operand"),
+            Some(file) => usage_error(&format!(
+                "missing destination file operand after {}",
+                shell_quote(file, true)
+            )),
+        };
+    }
+    let mut target_exists = true;
+    if args.no_target_directory {
+        if target.is_some() {
+            return die("cannot combine --target-directory (-t) and --no-target-directory (-T)");
+        }
+        if files.len() > 2 {
+            return usage_error(&format!("extra operand {}", shell_quote(&files[2], true)));
+        }
+    } else if let Some(target) = &target {
+        match directory(target) {
+            Ok(()) => {}
+            // Made by -D
+            Err(Errno::ENOENT) if args.create_leading => target_exists = false,
+            Err(e) => {
+                let target = shell_quote(target, true);
+                return die(&format!("failed to access {target}: {}", errno_desc(e)));
+            }
+        }
+    } else if !args.directory {
+        let last = files.last().expect("two operands at least");
+        match directory(last) {
+            Ok(()) => target = files.pop(),
+            Err(e) if files.len() > 2 => {
+                return die(&format!(
+                    "target {}: {}",
+                    shell_quote(last, true),
+                    errno_desc(e)
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+
+    let (mode, dir_mode, dir_mode_bits) = match &args.mode {
+        Some(mode) => {
+            let Some(change) = ModeChange::parse(mode.as_bytes()) else {
+                return die(&format!("invalid mode {}", quote(mode.as_bytes())));
+            };
+            let (dir_mode, dir_mode_bits) = change.adjust(0, true, 0);
+            (change.adjust(0, false, 0).0, dir_mode, dir_mode_bits)
+        }
+        None => (0o755, 0o755, CHMOD_MODE_BITS),
+    };
+    if args.strip_program.is_some() && !args.strip {
+        eprintln!(
+            "install: WARNING: ignoring --strip-program option as -s option was not specified"
+        );
+    }
+    if args.compare && args.preserve_timestamps {
+        return usage_error(
+            "options --compare (-C) and --preserve-timestamps are mutually exclusive",
+        );
+    }
+    if args.compare && args.strip {
+        return usage_error("options --compare (-C) and --strip are mutually exclusive");
+    }
+    if args.compare && extra_mode(mode) {
+        eprintln!(
+            "install: the --compare (-C) option is ignored when you specify a mode with \
+             non-permission bits"
+        );
+    }
+
+    let owner = match &args.owner {
+        Some(owner) => {
+            let owner = owner.as_bytes();
+            match lookup_user(owner).map(|user| user.uid.as_raw()) {
+                Some(uid) => Some(uid),
+                None => match parse_u32(owner) {
+                    // The all ones id means "unchanged" like it does for chown
+                    Some(uid) => Some(uid).filter(|&uid| uid != u32::MAX),
+                    None => return die(&format!("invalid user {}", shell_quote(owner, true))),
+                },
+            }
+        }
+        None => None,
+    };
+    let group = match &args.group {
+        Some(group) => {
+            let group = group.as_bytes();
+            match lookup_group(group).map(|group| group.gid.as_raw()) {
+                Some(gid) => Some(gid),
+                None => match parse_u32(group) {
+                    Some(gid) => Some(gid).filter(|&gid| gid != u32::MAX),
+                    None => return die(&format!("invalid group {}", shell_quote(group, true))),
+                },
+            }
+        }
+        None => None,
+    };
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => return die(&strerror(&e)),
+    };
+    // Modes are exactly those asked for
+    umask(Mode::empty());
+    let mut install = Install {
+        mode,
+        dir_mode,
+        dir_mode_bits,
+        owner,
+        group,
+        backup,
+        suffix,
+        compare: args.compare,
+        preserve_timestamps: args.preserve_timestamps,
+        strip: match args.strip {
+            true => Some(
+                args.strip_program
+                    .unwrap_or_else(|| OsString::from("strip")),
+            ),
+            false => None,
+        },
+        verbose: args.verbose,
+        out: BufWriter::new(stdout),
+    };
+
+    let mut ok = true;
+    if args.directory {
+        for dir in &files {
+            ok &= install.make_dir(dir);
+        }
+    } else if let Some(target) = target {
+        for (i, file) in files.iter().enumerate() {
+            let to = file_name_concat(&target, last_component(file));
+            // -D makes a missing target directory for the first file
+            if !target_exists {
+                if i > 0 {
+                    ok = false;
+                    continue;
+                }
+                ok &= install.install_file_in_parents(file, &to);
+                target_exists = directory(&target).is_ok();
+                continue;
+            }
+            ok &= install.install_file(file, &to);
+        }
+    } else if args.create_leading {
+        ok &= install.install_file_in_parents(&files[0], &files[1]);
+    } else {
+        ok &= install.install_file(&files[0], &files[1]);
+    }
+
+    if let Err(e) = install.out.flush() {
+        eprintln!("install: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,396 @@
+/*
+ * Shared pieces of the `ratiscat` utilities
+ *
+ * `rat` itself lives in `src/bin/rat.rs`, every other utility is a module here
+ * exposing `main(args) -> ExitCode` with a thin wrapper under `src/bin/`.
+ */
+
+use std::ffi::{CString, OsString};
+use std::fs::File;
+use std::io;
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+pub mod arch;
+pub mod b2sum;
+pub mod backupfile;
+pub mod bre;
+pub mod checksum;
+pub mod chgrp;
+pub mod chmod;
+pub mod chown;
+pub mod chown_core;
+pub mod chroot;
+pub mod cksum;
+pub mod date;
+pub mod expand;
+pub mod expr;
+pub mod fmt;
+pub mod float80;
+pub mod fold;
+pub mod grouplist;
+pub mod groups;
+pub mod id;
+pub mod install;
+pub mod link;
+pub mod logname;
+pub mod md5sum;
+pub mod mkfifo;
+pub mod mknod;
+pub mod mktemp;
+pub mod modechange;
+pub mod nice;
+pub mod nohup;
+pub mod parse_datetime;
+pub mod pathchk;
+pub mod printenv;
+pub mod printf;
+pub mod random;
+pub mod sha1sum;
+pub mod sha224sum;
+pub mod sha256sum;
+pub mod sha384sum;
+pub mod sha512sum;
+pub mod shred;
+pub mod shuf;
+pub mod sort;
+pub mod stdbuf;
+pub mod strftime;
+pub mod sum;
+pub mod tabstops;
+pub mod test;
+pub mod timeout;
+pub mod tsort;
+pub mod tty;
+pub mod uname;
+pub mod unexpand;
+pub mod unlink;
+pub mod uptime;
+pub mod userspec;
+pub mod whoami;
+pub mod width;
+
+/// Describe an `io::Error` like strerror(3), ie. without rust's " (os error N)" suffix
+pub fn strerror(e: &io::Error) -> String {
+    match e.raw_os_error() {
+        Some(errno) => errno_desc(nix::errno::Errno::from_i32(errno)),
+        None => e.to_string(),
+    }
+}
+
+/// The message of strerror(3) for `errno`, the C library's rather than nix's
+/// `Errno::desc` which words some differently ("Cross-device link")
+pub fn errno_desc(errno: nix::errno::Errno) -> String {
+    let mut message = [0 as libc::c_char; 256];
+    // The XSI strerror_r, always filling `message`
+    unsafe { libc::strerror_r(errno as i32, message.as_mut_ptr(), message.len()) };
+    let message = unsafe { std::ffi::CStr::from_ptr(message.as_ptr()) };
+    message.to_string_lossy().into_owned()
+}
+
+/// Append the C escape of a non printable byte, `\n` style when there's one, `\ooo` otherwise
+pub fn c_escape(out: &mut Vec<u8>, c: u8) {
+    let letter = match c {
+        b'\x07' => b'a',
+        b'\x08' => b'b',
+        b'\x0c' => b'f',
+        b'\n' => b'n',
+        b'\r' => b'r',
+        b'\t' => b't',
+        b'\x0b' => b'v',
+        _ => return out.extend([b'\\', b'0' + (c >> 6), b'0' + (c >> 3 & 7), b'0' + (c & 7)]),
+    };
+    out.extend([b'\\', letter]);
+}
+
+/// Quote an argument for diagnostics like GNU's quote() in the C locale
+pub fn quote(s: &[u8]) -> String {
+    let mut out = vec![b'\''];
+    for &c in s {
+        match c {
+            b'\\' | b'\'' => out.extend([b'\\', c]),
+            b' '..=b'~' => out.push(c),
+            _ => c_escape(&mut out, c),
+        }
+    }
+    out.push(b'\'');
+    // Only ASCII is left
+    String::from_utf8(out).unwrap()
+}
+
+/// Quote for the shell like GNU's shell-escape style (printf %q, quotef), or with `always`
+/// shell-escape-always (quoteaf): '...' unless the shell needs none, "..." for strings with
+/// single quotes, and bash's $'...' for non printable bytes (C locale)
+pub fn shell_quote(s: &[u8], always: bool) -> String {
+    if s.is_empty() {
+        return String::from("''");
+    }
+    let mut needs_quotes = false;
+    let mut double_quotes = true;
+    let mut single_quote = false;
+    for (i, &c) in s.iter().enumerate() {
+        // Whether the byte needs quoting, and if it can be kept as is in "..."
+        let (special, compat) = match c {
+            b'{' | b'}' if s.len() == 1 => (true, true),
+            b'#' | b'~' if i == 0 => (true, true),
+            b'{' | b'}' | b'#' | b'~' => (false, false),
+            b' ' => (true, true),
+            b'\'' => {
+                single_quote = true;
+                (true, true)
+            }
+            b'!' | b'"' | b'$' | b'&' | b'(' | b')' | b'*' | b';' | b'<' | b'=' | b'>' | b'?'
+            | b'[' | b'\\' | b'^' | b'`' | b'|' => (true, false),
+            b'!'..=b'~' => (false, true),
+            _ => (true, false),
+        };
+        needs_quotes |= special;
+        double_quotes &= compat;
+    }
+    // Only printable ASCII is left unescaped
+    let ascii = |s: Vec<u8>| String::from_utf8(s).unwrap();
+    if !needs_quotes {
+        return ascii(match always {
+            true => [b"'", s, b"'"].concat(),
+            false => s.to_vec(),
+        });
+    }
+    if single_quote && double_quotes {
+        return ascii([b"\"", s, b"\""].concat());
+    }
+
+    let mut out = vec![b'\''];
+    // Inside a $'...' segment. gnulib rescans strings with single quotes without
+    // resetting this, so they start off as the first pass ended, quirks included
+    let last = s[s.len() - 1];
+    let mut escaping = single_quote && !(b' '..=b'~').contains(&last);
+    for &c in s {
+        match c {
+            b'\'' => {
+                out.extend(b"'\\''");
+                escaping = false;
+            }
+            b' '..=b'~' => {
+                if escaping {
+                    out.extend(b"''");
+                    escaping = false;
+                }
+                out.push(c);
+            }
+            _ => {
+                if !escaping {
+                    out.extend(b"'$'");
+                    escaping = true;
+                }
+                c_escape(&mut out, c);
+            }
+        }
+    }
+    out.push(b'\'');
+    ascii(out)
+}
+
+/// A number like xstrtoumax takes it with base 0: decimal, octal with a leading 0 or
+/// hexadecimal with 0x, the whole string and within 32 bits
+pub fn parse_u32(s: &[u8]) -> Option<u32> {
+    let start = s
+        .iter()
+        .take_while(|c| b" \t\n\x0b\x0c\r".contains(c))
+        .count();
+    let s = &s[start..];
+    let s = s.strip_prefix(b"+").unwrap_or(s);
+    let hex = s.strip_prefix(b"0x").or(s.strip_prefix(b"0X"));
+    let (digits, radix) = match hex {
+        Some(hex) if hex.first().is_some_and(u8::is_ascii_hexdigit) => (hex, 16),
+        _ if s.len() > 1 && s[0] == b'0' => (&s[1..], 8),
+        _ => (s, 10),
+    };
+    if digits.is_empty() || !digits.iter().all(|&c| (c as char).is_digit(radix)) {
+        return None;
+    }
+    u32::from_str_radix(std::str::from_utf8(digits).ok()?, radix).ok()
+}
+
+/// What follows the last slash of `name`, trailing slashes included, like gnulib's
+/// last_component
+pub fn last_component(name: &[u8]) -> &[u8] {
+    let mut base = name.iter().take_while(|&&c| c == b'/').count();
+    let mut slash = false;
+    for (i, &c) in name.iter().enumerate().skip(base) {
+        if c == b'/' {
+            slash = true;
+        } else if slash {
+            base = i;
+            slash = false;
+        }
+    }
+    &name[base..]
+}
+
+/// `dir` without its trailing slashes, a '/' and `base`, like gnulib's file_name_concat
+pub fn file_name_concat(dir: &[u8], base: &[u8]) -> Vec<u8> {
+    let mut len = dir.len();
+    while len > 1 && dir[len - 1] == b'/' {
+        len -= 1;
+    }
+    let mut name = dir[..len].to_vec();
+    if !name.is_empty() && !name.ends_with(b"/") {
+        name.push(b'/');
+    }
+    name.extend_from_slice(base);
+    name
+}
+
+/// Owned handle on stdout which skips the `LineWriter` wrapping of `io::Stdout`
+/// (see `simple_rat` in rat.rs)
+pub fn stdout_file() -> io::Result<File> {
+    Ok(File::from(io::stdout().lock().as_fd().try_clone_to_owned()?))
+}
+
+/// Rust ignores SIGPIPE by default, restore it so `util | head` exits quietly like coreutils
+pub fn reset_sigpipe() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+/// Parse the arguments like `Parser::parse_from`, but report usage errors like GNU
+/// (getopt's wording and a Try '... --help' line) and exit with `failure`, ie. 125 for
+/// the utilities running a command so it's told apart from its own
+pub fn parse_args<P: clap::Parser>(args: impl IntoIterator<Item = OsString>, failure: u8) -> P {
+    let matches = get_matches(P::command(), args, failure);
+    P::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+}
+
+/// `parse_args` for a `command` built at run time
+pub fn get_matches(
+    command: clap::Command,
+    args: impl IntoIterator<Item = OsString>,
+    failure: u8,
+) -> clap::ArgMatches {
+    let args: Vec<OsString> = args.into_iter().collect();
+    let prog = command.get_name().to_string();
+    command.try_get_matches_from(&args).unwrap_or_else(|e| {
+        if !e.use_stderr() {
+            let _ = e.print();
+            std::process::exit(0);
+        }
+        eprintln!("{prog}: {}", usage_error(&e, &args[1.min(args.len())..]));
+        eprintln!("Try '{prog} --help' for more information.");
+        std::process::exit(failure.into())
+    })
+}
+
+// The message of a clap error as getopt words it, `args` telling how the option was given
+fn usage_error(e: &clap::Error, args: &[OsString]) -> String {
+    use clap::error::{ContextKind, ContextValue, ErrorKind};
+    let invalid = match e.get(ContextKind::InvalidArg) {
+        Some(ContextValue::String(arg)) => arg.as_str(),
+        _ => "",
+    };
+    let missing = matches!(
+        e.get(ContextKind::InvalidValue),
+        Some(ContextValue::String(value)) if value.is_empty()
+    );
+    let last = args.last().map(|arg| arg.to_string_lossy()).unwrap_or_default();
+    match e.kind() {
+        ErrorKind::UnknownArgument if invalid.starts_with("--") => {
+            // With its =VALUE if any, which clap leaves out
+            let given = args.iter().map(|arg| arg.to_string_lossy()).find(|arg| {
+                arg.strip_prefix(invalid)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+            });
+            format!("unrecognized option '{}'", given.as_deref().unwrap_or(invalid))
+        }
+        ErrorKind::UnknownArgument if invalid.starts_with('-') => {
+            format!("invalid option -- '{}'", &invalid[1..])
+        }
+        ErrorKind::UnknownArgument => format!("extra operand {}", quote(invalid.as_bytes())),
+        // A missing value can only be that of the last argument
+        ErrorKind::InvalidValue if missing && last.starts_with("--") => {
+            format!("option '{last}' requires an argument")
+        }
+        ErrorKind::InvalidValue if missing && last.starts_with('-') && last.len() > 1 => {
+            let option = last.chars().last().unwrap_or('-');
+            format!("option requires an argument -- '{option}'")
+        }
+        // Otherwise clap's first line, ie. its "invalid value" or "unexpected argument"
+        _ => {
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default();
+            first.strip_prefix("error: ").unwrap_or(first).to_string()
+        }
+    }
+}
+
+/// Run `command` in place of the current process with execvp, only returning (the
+/// error) when that failed
+pub fn exec_command(command: &[OsString]) -> nix::Error {
+    // Arguments come from argv, they can't contain NUL bytes
+    let args: Vec<CString> = command
+        .iter()
+        .map(|arg| CString::new(arg.as_bytes()).unwrap())
+        .collect();
+    nix::unistd::execvp(&args[0], &args).unwrap_err()
+}
+
+/// GNU's status for a command that couldn't be run: 127 when it wasn't found, 126 when
+/// it couldn't be executed
+pub fn exec_status(e: nix::Error) -> ExitCode {
+    match e {
+        nix::errno::Errno::ENOENT => ExitCode::from(127),
+        _ => ExitCode::from(126),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+
+    #[test]
+    fn error_messages() {
+        // glibc's wording, where nix has its own
+        assert_eq!(errno_desc(Errno::EXDEV), "Invalid cross-device link");
+        assert_eq!(errno_desc(Errno::ELOOP), "Too many levels of symbolic links");
+        assert_eq!(errno_desc(Errno::EIO), "Input/output error");
+        let e = io::Error::from_raw_os_error(libc::ENOTTY);
+        assert_eq!(strerror(&e), "Inappropriate ioctl for device");
+        let e = io::Error::new(io::ErrorKind::Other, "no errno");
+        assert_eq!(strerror(&e), "no errno");
+    }
+
+    #[derive(Debug, clap::Parser)]
+    #[command(name = "prog")]
+    struct Cli {
+        #[clap(long, short, action)]
+        all: bool,
+        #[clap(long, short)]
+        tabs: Option<u32>,
+    }
+
+    // What parse_args would print before its Try line
+    fn usage(args: &[&str]) -> String {
+        let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+        let e = <Cli as clap::Parser>::try_parse_from(&args).unwrap_err();
+        usage_error(&e, &args[1..])
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(usage(&["prog", "-x"]), "invalid option -- 'x'");
+        assert_eq!(usage(&["prog", "-ax"]), "invalid option -- 'x'");
+        assert_eq!(usage(&["prog", "--foo"]), "unrecognized option '--foo'");
+        assert_eq!(usage(&["prog", "--foo=1"]), "unrecognized option '--foo=1'");
+        assert_eq!(usage(&["prog", "-t"]), "option requires an argument -- 't'");
+        assert_eq!(usage(&["prog", "-at"]), "option requires an argument -- 't'");
+        assert_eq!(usage(&["prog", "--tabs"]), "option '--tabs' requires an argument");
+        assert_eq!(usage(&["prog", "operand"]), "extra operand 'operand'");
+        // Left to clap
+        assert_eq!(
+            usage(&["prog", "--tabs=x"]),
+            "invalid value 'x' for '--tabs <TABS>': invalid digit found in string"
+        );
+    }
+}
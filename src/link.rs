@@ -0,0 +1,57 @@
+/*
+ * link - call the link function to create a link to a file
+ *
+ * Just link(2), as POSIX has it: exactly two operands, no directory target, no -f and
+ * nothing done about symlinks (FILE1 being one, the new name is a hard link to the
+ * symlink itself). Errors are GNU's messages with the syscall's reason.
+ */
+
+use crate::{errno_desc, quote, shell_quote};
+use clap::Parser;
+use nix::unistd::{linkat, LinkatFlags};
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "link", version, long_about = None)]
+#[command(about = "Call the link function to create a link named FILE2 to an existing FILE1")]
+#[command(override_usage = "link FILE1 FILE2\n       link OPTION")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args = Cli::parse_from(args);
+    let try_help = "Try 'link --help' for more information.";
+    let (target, name) = match &args.operands[..] {
+        [] => {
+            eprintln!("link: missing operand\n{try_help}");
+            return ExitCode::FAILURE;
+        }
+        [target] => {
+            let target = quote(target.as_bytes());
+            eprintln!("link: missing operand after {target}\n{try_help}");
+            return ExitCode::FAILURE;
+        }
+        [target, name] => (target.as_bytes(), name.as_bytes()),
+        [_, _, extra, ..] => {
+            let extra = quote(extra.as_bytes());
+            eprintln!("link: extra operand {extra}\n{try_help}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // link(2) doesn't follow a symlink FILE1, which linkat only does when asked
+    if let Err(e) = linkat(None, target, None, name, LinkatFlags::NoSymlinkFollow) {
+        eprintln!(
+            "link: cannot create link {} to {}: {}",
+            shell_quote(name, true),
+            shell_quote(target, true),
+            errno_desc(e)
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,63 @@
+/*
+ * logname - print the user's login name
+ *
+ * The name is glibc's getlogin_r: the audit login uid of the process if it has one,
+ * else the utmp entry of the controlling terminal. Containers and CI jobs usually
+ * have neither, which is the "no login name" error rather than an empty line.
+ */
+
+use crate::{parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use std::ffi::{CStr, OsString};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "logname", version, long_about = None)]
+#[command(about = "Print the user's login name")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+// LOGIN_NAME_MAX is 256 on Linux
+const LOGIN_NAME_MAX: usize = 256;
+
+// Not in the libc crate for glibc targets
+extern "C" {
+    fn getlogin_r(name: *mut libc::c_char, size: libc::size_t) -> libc::c_int;
+}
+
+/// The login name of the session, None when there's none
+fn login_name() -> Option<Vec<u8>> {
+    let mut name = [0u8; LOGIN_NAME_MAX + 1];
+    let failed = unsafe { getlogin_r(name.as_mut_ptr().cast(), name.len()) };
+    if failed != 0 {
+        return None;
+    }
+    let name = CStr::from_bytes_until_nul(&name).ok()?.to_bytes();
+    (!name.is_empty()).then(|| name.to_vec())
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("logname: extra operand {extra}\nTry 'logname --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let Some(mut name) = login_name() else {
+        eprintln!("logname: no login name");
+        return ExitCode::FAILURE;
+    };
+    name.push(b'\n');
+    let written = stdout_file().and_then(|mut out| out.write_all(&name));
+    if let Err(e) = written {
+        eprintln!("logname: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,95 @@
+/*
+ * mkfifo - make FIFOs (named pipes)
+ *
+ * FIFOs are created 0666 less the umask. A -m mode is computed like chmod's
+ * (`modechange.rs`) from 0666 and set again after creation, so the umask doesn't
+ * filter it. Every operand is tried, failures only change the exit status.
+ */
+
+use crate::modechange::ModeChange;
+use crate::{errno_desc, parse_args, shell_quote};
+use clap::Parser;
+use nix::sys::stat::{fchmodat, umask, FchmodatFlags, Mode};
+use nix::unistd::mkfifo;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "mkfifo", version, long_about = None)]
+#[command(about = "Create named pipes (FIFOs) with the given NAMEs")]
+#[command(override_usage = "mkfifo [OPTION]... NAME...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Set file permission bits to MODE, not a=rw - umask
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    mode: Option<OsString>,
+    /// Set the SELinux security context to default type (ignored, there's no SELinux
+    /// support)
+    #[clap(short = 'Z', action)]
+    selinux: bool,
+    /// Like -Z, or set the SELinux or SMACK security context to CTX (ignored with a
+    /// warning)
+    #[clap(long, value_name = "CTX", num_args = 0..=1, require_equals = true)]
+    context: Option<Option<OsString>>,
+    /// The FIFOs to create
+    #[clap(value_name = "NAME")]
+    names: Vec<OsString>,
+}
+
+const MODE_RW_UGO: u32 = 0o666;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+    if args.names.is_empty() {
+        eprintln!("mkfifo: missing operand\nTry 'mkfifo --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    if args.context.as_ref().is_some_and(Option::is_some) {
+        eprintln!(
+            "mkfifo: warning: ignoring --context; it requires an SELinux/SMACK-enabled kernel"
+        );
+    }
+
+    let mode = match &args.mode {
+        Some(mode) => {
+            let Some(change) = ModeChange::parse(mode.as_bytes()) else {
+                eprintln!("mkfifo: invalid mode");
+                return ExitCode::FAILURE;
+            };
+            let mask = umask(Mode::empty());
+            umask(mask);
+            let mode = change.adjust(MODE_RW_UGO, false, mask.bits()).0;
+            if mode & !0o777 != 0 {
+                eprintln!("mkfifo: mode must specify only file permission bits");
+                return ExitCode::FAILURE;
+            }
+            Some(Mode::from_bits_truncate(mode))
+        }
+        None => None,
+    };
+
+    let mut ok = true;
+    for name in &args.names {
+        let name = name.as_bytes();
+        let created = mkfifo(name, mode.unwrap_or(Mode::from_bits_truncate(MODE_RW_UGO)));
+        if let Err(e) = created {
+            let name = shell_quote(name, true);
+            eprintln!("mkfifo: cannot create fifo {name}: {}", errno_desc(e));
+            ok = false;
+        } else if let Some(mode) = mode {
+            if let Err(e) = fchmodat(None, name, mode, FchmodatFlags::FollowSymlink) {
+                let name = shell_quote(name, true);
+                eprintln!(
+                    "mkfifo: cannot set permissions of {name}: {}",
+                    errno_desc(e)
+                );
+                ok = false;
+            }
+        }
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,273 @@
+/*
+ * mknod - make block or character special files, or FIFOs
+ *
+ * The operands are checked before anything is created: their count depends on the
+ * type (only its first letter counts, so `character` works), and device numbers
+ * are decimal, octal or hexadecimal like GNU's. makedev() combines them, the kernel
+ * refusing those beyond its 12 bit major and 20 bit minor numbers. -m is handled
+ * like mkfifo's, computed with `modechange.rs` and set again after creation.
+ */
+
+use crate::modechange::ModeChange;
+use crate::{errno_desc, parse_u32, quote, shell_quote};
+use clap::Parser;
+use nix::sys::stat::{fchmodat, makedev, mknod, umask, FchmodatFlags, Mode, SFlag};
+use nix::unistd::mkfifo;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "mknod", version, long_about = None)]
+#[command(about = "Create the special file NAME of the given TYPE")]
+#[command(override_usage = "mknod [OPTION]... NAME TYPE [MAJOR MINOR]")]
+#[command(
+    after_help = "Both MAJOR and MINOR must be specified when TYPE is b, c, or u, and they
+must be omitted when TYPE is p. MAJOR and MINOR begin with 0x or 0X for
+hexadecimal, 0 for octal, otherwise decimal. TYPE may be:
+
+  b      create a block (buffered) special file
+  c, u   create a character (unbuffered) special file
+  p      create a FIFO"
+)]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Set file permission bits to MODE, not a=rw - umask
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    mode: Option<OsString>,
+    /// Set the SELinux security context to default type (ignored, there's no SELinux
+    /// support)
+    #[clap(short = 'Z', action)]
+    selinux: bool,
+    /// Like -Z, or set the SELinux or SMACK security context to CTX (ignored with a
+    /// warning)
+    #[clap(long, value_name = "CTX", num_args = 0..=1, require_equals = true)]
+    context: Option<Option<OsString>>,
+    /// NAME TYPE [MAJOR MINOR]
+    #[clap(value_name = "OPERAND")]
+    operands: Vec<OsString>,
+}
+
+const MODE_RW_UGO: u32 = 0o666;
+
+// What to create
+#[derive(Debug)]
+enum Node {
+    Fifo,
+    Device(SFlag, libc::dev_t),
+}
+
+// What to create and with which permissions, as -m and the operands say
+#[derive(Debug)]
+struct Spec {
+    node: Node,
+    mode: Option<Mode>,
+}
+
+// Usage errors are followed by the Try line
+fn usage(message: &str) -> String {
+    format!("{message}\nTry 'mknod --help' for more information.")
+}
+
+// The node NAME TYPE [MAJOR MINOR] asks for
+fn parse_node(operands: &[OsString]) -> Result<Node, String> {
+    let operand = |i: usize| operands[i].as_bytes();
+    // Two operands for FIFOs, four otherwise
+    let expected = match operands.get(1) {
+        _ if operands.is_empty() => 2,
+        Some(kind) if kind.as_bytes().first() == Some(&b'p') => 2,
+        _ => 4,
+    };
+    if operands.len() < expected {
+        let mut message = match operands.last() {
+            Some(last) => format!("missing operand after {}", quote(last.as_bytes())),
+            None => String::from("missing operand"),
+        };
+        if expected == 4 && operands.len() == 2 {
+            message.push_str("\nSpecial files require major and minor device numbers.");
+        }
+        return Err(usage(&message));
+    }
+    if operands.len() > expected {
+        let mut message = format!("extra operand {}", quote(operand(expected)));
+        if expected == 2 && operands.len() == 4 {
+            message.push_str("\nFifos do not have major and minor device numbers.");
+        }
+        return Err(usage(&message));
+    }
+
+    let kind = match operand(1).first() {
+        Some(b'b') => SFlag::S_IFBLK,
+        Some(b'c' | b'u') => SFlag::S_IFCHR,
+        Some(b'p') => return Ok(Node::Fifo),
+        _ => {
+            let kind = quote(operand(1));
+            return Err(usage(&format!("invalid device type {kind}")));
+        }
+    };
+    let major = parse_u32(operand(2))
+        .ok_or_else(|| format!("invalid major device number {}", quote(operand(2))))?;
+    let minor = parse_u32(operand(3))
+        .ok_or_else(|| format!("invalid minor device number {}", quote(operand(3))))?;
+    Ok(Node::Device(kind, makedev(major.into(), minor.into())))
+}
+
+// What -m MODE and the operands ask for, `mask` being the umask. The error is the
+// message to prefix with "mknod: "
+fn parse(mode: Option<&[u8]>, operands: &[OsString], mask: u32) -> Result<Spec, String> {
+    let mode = match mode {
+        Some(mode) => {
+            let change = ModeChange::parse(mode).ok_or("invalid mode")?;
+            let mode = change.adjust(MODE_RW_UGO, false, mask).0;
+            if mode & !0o777 != 0 {
+                return Err("mode must specify only file permission bits".into());
+            }
+            Some(Mode::from_bits_truncate(mode))
+        }
+        None => None,
+    };
+    let node = parse_node(operands)?;
+    Ok(Spec { node, mode })
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args = Cli::parse_from(args);
+    if args.context.as_ref().is_some_and(Option::is_some) {
+        eprintln!(
+            "mknod: warning: ignoring --context; it requires an SELinux/SMACK-enabled kernel"
+        );
+    }
+
+    let mask = umask(Mode::empty());
+    umask(mask);
+    let mode = args.mode.as_ref().map(|mode| mode.as_bytes());
+    let Spec { node, mode } = match parse(mode, &args.operands, mask.bits()) {
+        Ok(spec) => spec,
+        Err(message) => {
+            eprintln!("mknod: {message}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let name = args.operands[0].as_bytes();
+    let perm = mode.unwrap_or(Mode::from_bits_truncate(MODE_RW_UGO));
+    let created = match node {
+        Node::Fifo => mkfifo(name, perm),
+        Node::Device(kind, device) => mknod(name, kind, perm, device),
+    };
+    if let Err(e) = created {
+        eprintln!("mknod: {}: {}", shell_quote(name, false), errno_desc(e));
+        return ExitCode::FAILURE;
+    }
+    if let Some(mode) = mode {
+        if let Err(e) = fchmodat(None, name, mode, FchmodatFlags::FollowSymlink) {
+            let name = shell_quote(name, true);
+            eprintln!("mknod: cannot set permissions of {name}: {}", errno_desc(e));
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(operands: &[&str]) -> Result<Node, String> {
+        let operands: Vec<OsString> = operands.iter().map(OsString::from).collect();
+        parse(None, &operands, 0o022).map(|spec| spec.node)
+    }
+
+    fn mode(mode: &str, mask: u32) -> Result<Option<u32>, String> {
+        let operands = [OsString::from("name"), OsString::from("p")];
+        let spec = parse(Some(mode.as_bytes()), &operands, mask)?;
+        Ok(spec.mode.map(|mode| mode.bits()))
+    }
+
+    fn device(kind: SFlag, major: u64, minor: u64) -> String {
+        format!("{:?}", Node::Device(kind, makedev(major, minor)))
+    }
+
+    #[test]
+    fn types() {
+        assert!(matches!(node(&["f", "p"]), Ok(Node::Fifo)));
+        assert!(matches!(node(&["f", "pipe"]), Ok(Node::Fifo)));
+        let block = device(SFlag::S_IFBLK, 8, 1);
+        assert_eq!(format!("{:?}", node(&["f", "b", "8", "1"]).unwrap()), block);
+        // Only the first letter counts, u is c
+        let character = device(SFlag::S_IFCHR, 1, 3);
+        for kind in ["c", "u", "character"] {
+            let parsed = node(&["f", kind, "1", "3"]).unwrap();
+            assert_eq!(format!("{parsed:?}"), character);
+        }
+        let try_help = "\nTry 'mknod --help' for more information.";
+        let invalid = format!("invalid device type 'x'{try_help}");
+        assert_eq!(node(&["f", "x", "1", "3"]).unwrap_err(), invalid);
+        assert_eq!(
+            node(&["f", "", "1", "3"]).unwrap_err(),
+            format!("invalid device type ''{try_help}")
+        );
+    }
+
+    #[test]
+    fn device_numbers() {
+        let try_help = "\nTry 'mknod --help' for more information.";
+        // Required for b and c, not allowed for p
+        let required =
+            "missing operand after 'b'\nSpecial files require major and minor device numbers.";
+        assert_eq!(
+            node(&["f", "b"]).unwrap_err(),
+            format!("{required}{try_help}")
+        );
+        assert_eq!(
+            node(&["f", "c", "1"]).unwrap_err(),
+            format!("missing operand after '1'{try_help}")
+        );
+        let forbidden = "extra operand '1'\nFifos do not have major and minor device numbers.";
+        assert_eq!(
+            node(&["f", "p", "1", "2"]).unwrap_err(),
+            format!("{forbidden}{try_help}")
+        );
+        assert_eq!(
+            node(&["f", "p", "1"]).unwrap_err(),
+            format!("extra operand '1'{try_help}")
+        );
+        assert_eq!(
+            node(&["f"]).unwrap_err(),
+            format!("missing operand after 'f'{try_help}")
+        );
+        assert_eq!(node(&[]).unwrap_err(), format!("missing operand{try_help}"));
+
+        // Decimal, octal and hexadecimal
+        let parsed = node(&["f", "c", "010", "0x1f"]).unwrap();
+        assert_eq!(format!("{parsed:?}"), device(SFlag::S_IFCHR, 8, 31));
+        // Bad numbers aren't usage errors
+        assert_eq!(
+            node(&["f", "c", "x", "1"]).unwrap_err(),
+            "invalid major device number 'x'"
+        );
+        assert_eq!(
+            node(&["f", "c", "1", "-1"]).unwrap_err(),
+            "invalid minor device number '-1'"
+        );
+        assert_eq!(
+            node(&["f", "c", "1", "4294967296"]).unwrap_err(),
+            "invalid minor device number '4294967296'"
+        );
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(mode("600", 0o022), Ok(Some(0o600)));
+        // Relative to a=rw, the umask only limits what's added without a who
+        assert_eq!(mode("+x", 0o022), Ok(Some(0o777)));
+        assert_eq!(mode("+x", 0o077), Ok(Some(0o766)));
+        assert_eq!(mode("g-w,o=", 0o022), Ok(Some(0o640)));
+        assert_eq!(mode("z", 0o022), Err("invalid mode".into()));
+        let bits = "mode must specify only file permission bits";
+        assert_eq!(mode("1777", 0o022), Err(bits.into()));
+        assert_eq!(mode("u+s", 0o022), Err(bits.into()));
+        // Without -m the default is left to mknod(2) and the umask
+        let operands = [OsString::from("f"), OsString::from("p")];
+        assert_eq!(parse(None, &operands, 0o022).unwrap().mode, None);
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * mktemp - create a temporary file or directory
+ *
+ * The X's ending the template (or preceding --suffix) are replaced with random
+ * letters and digits and the name is created with O_EXCL, or mkdir for -d, so an
+ * existing file is never reused: on EEXIST fresh characters are tried, up to 62^3
+ * times like gnulib's gen_tempname. -u only checks that nothing has the name.
+ */
+
+use crate::random::RandomSource;
+use crate::{file_name_concat, last_component, parse_args, quote, stdout_file, strerror};
+use clap::Parser;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::ops::Range;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::Path;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "mktemp", version, long_about = None)]
+#[command(about = "Create a temporary file or directory, safely, and print its name")]
+#[command(override_usage = "mktemp [OPTION]... [TEMPLATE]")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Create a directory, not a file
+    #[clap(long, short, action)]
+    directory: bool,
+    /// Do not create anything; merely print a name (unsafe)
+    #[clap(long, short = 'u', action)]
+    dry_run: bool,
+    /// Suppress diagnostics about file/dir-creation failure
+    #[clap(long, short, action)]
+    quiet: bool,
+    /// Append SUFF to TEMPLATE; SUFF must not contain a slash
+    #[clap(long, value_name = "SUFF", allow_hyphen_values = true)]
+    suffix: Option<OsString>,
+    /// Same as --tmpdir=DIR
+    #[clap(short = 'p', value_name = "DIR", overrides_with = "tmpdir")]
+    dir: Option<OsString>,
+    /// Interpret TEMPLATE relative to DIR; if DIR is not specified, use $TMPDIR if set,
+    /// else /tmp. With this option, TEMPLATE must not be an absolute name
+    #[clap(
+        long,
+        value_name = "DIR",
+        num_args = 0..=1,
+        require_equals = true,
+        overrides_with = "dir"
+    )]
+    tmpdir: Option<Option<OsString>>,
+    /// Interpret TEMPLATE as a single file name component, relative to a directory:
+    /// $TMPDIR, if set; else the directory specified via -p; else /tmp [deprecated]
+    #[clap(short = 't', action)]
+    single_component: bool,
+    /// At least 3 consecutive X's in the last component, tmp.XXXXXXXXXX in the
+    /// temporary directory by default
+    #[clap(value_name = "TEMPLATE")]
+    templates: Vec<OsString>,
+}
+
+const LETTERS: &[u8; 62] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+// gnulib's ATTEMPTS_MIN, every combination of three characters
+const ATTEMPTS: u32 = 62 * 62 * 62;
+
+fn die(message: &str) -> ExitCode {
+    eprintln!("mktemp: {message}");
+    ExitCode::FAILURE
+}
+
+// Replace `name[xs]` with random characters until a name that doesn't exist yet is
+// created (or, with `dry_run`, found)
+fn create(name: &mut [u8], xs: Range<usize>, directory: bool, dry_run: bool) -> io::Result<()> {
+    let mut rng = RandomSource::open(None)?;
+    for _ in 0..ATTEMPTS {
+        for c in &mut name[xs.clone()] {
+            *c = LETTERS[rng.below(LETTERS.len() as u64)? as usize];
+        }
+        let path = Path::new(OsStr::from_bytes(name));
+        let created = match (dry_run, directory) {
+            (true, _) => match fs::symlink_metadata(path) {
+                Ok(_) => Err(io::Error::from_raw_os_error(libc::EEXIST)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            },
+            (false, true) => DirBuilder::new().mode(0o700).create(path),
+            (false, false) => {
+                let mut options = OpenOptions::new();
+                options.read(true).write(true).create_new(true).mode(0o600);
+                options.open(path).map(drop)
+            }
+        };
+        match created {
+            Err(e) if e.raw_os_error() == Some(libc::EEXIST) => {}
+            created => return created,
+        }
+    }
+    Err(io::Error::from_raw_os_error(libc::EEXIST))
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+    if args.templates.len() > 1 {
+        eprintln!("mktemp: too many templates\nTry 'mktemp --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let in_dir = args.dir.is_some() || args.tmpdir.is_some() || args.single_component;
+    let (mut template, in_dir) = match args.templates.first() {
+        Some(template) => (template.as_bytes().to_vec(), in_dir),
+        None => (b"tmp.XXXXXXXXXX".to_vec(), true),
+    };
+
+    // The suffix is whatever follows the last X unless given
+    let suffix_len = match &args.suffix {
+        Some(suffix) => {
+            if template.last() != Some(&b'X') {
+                let template = quote(&template);
+                return die(&format!("with --suffix, template {template} must end in X"));
+            }
+            template.extend_from_slice(suffix.as_bytes());
+            suffix.len()
+        }
+        None => match template.iter().rposition(|&c| c == b'X') {
+            Some(x) => template.len() - x - 1,
+            None => 0,
+        },
+    };
+    let end = template.len() - suffix_len;
+    let suffix = &template[end..];
+    if last_component(suffix).len() != suffix.len() {
+        let suffix = quote(suffix);
+        return die(&format!(
+            "invalid suffix {suffix}, contains directory separator"
+        ));
+    }
+    let x_count = template[..end]
+        .iter()
+        .rev()
+        .take_while(|&&c| c == b'X')
+        .count();
+    if x_count < 3 {
+        return die(&format!("too few X's in template {}", quote(&template)));
+    }
+
+    if in_dir {
+        let from_env = env::var_os("TMPDIR").filter(|dir| !dir.is_empty());
+        let from_args = args
+            .dir
+            .or(args.tmpdir.flatten())
+            .filter(|dir| !dir.is_empty());
+        // -t prefers TMPDIR over -p, --tmpdir the other way around
+        let dir = if args.single_component {
+            if last_component(&template).len() != template.len() {
+                let template = quote(&template);
+                return die(&format!(
+                    "invalid template, {template}, contains directory separator"
+                ));
+            }
+            from_env.or(from_args)
+        } else {
+            if template.first() == Some(&b'/') {
+                let template = quote(&template);
+                return die(&format!(
+                    "invalid template, {template}; with --tmpdir, it may not be absolute"
+                ));
+            }
+            from_args.or(from_env)
+        };
+        let dir = dir.unwrap_or_else(|| OsString::from("/tmp"));
+        template = file_name_concat(dir.as_bytes(), &template);
+    }
+
+    let end = template.len() - suffix_len;
+    let mut name = template.clone();
+    if let Err(e) = create(&mut name, end - x_count..end, args.directory, args.dry_run) {
+        if !args.quiet {
+            let kind = if args.directory { "directory" } else { "file" };
+            let template = quote(&template);
+            eprintln!(
+                "mktemp: failed to create {kind} via template {template}: {}",
+                strerror(&e)
+            );
+        }
+        return ExitCode::FAILURE;
+    }
+
+    name.push(b'\n');
+    let written = stdout_file().and_then(|mut stdout| stdout.write_all(&name));
+    if let Err(e) = written {
+        // Nobody would know what to clean up
+        let path = Path::new(OsStr::from_bytes(&name[..name.len() - 1]));
+        let _ = match (args.dry_run, args.directory) {
+            (true, _) => Ok(()),
+            (false, true) => fs::remove_dir(path),
+            (false, false) => fs::remove_file(path),
+        };
+        if !args.quiet {
+            eprintln!("mktemp: write error: {}", strerror(&e));
+        }
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
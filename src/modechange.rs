@@ -0,0 +1,251 @@
+/*
+ * Symbolic and octal file modes, shared by chmod and the utilities taking `-m MODE`
+ *
+ * This follows gnulib's modechange: a mode is a list of changes applied in order,
+ * each with the bits it affects (`ugoa`, none meaning "all minus the umask"), an
+ * operator and either permission letters, an octal number or another class to
+ * copy (`g=u`). Directories keep their setuid/setgid bits unless the change
+ * mentions them explicitly, which is why `chmod 755 dir` leaves `g+s` alone while
+ * `chmod 00755 dir` clears it.
+ */
+
+/// The permission bits chmod can change, including setuid, setgid and sticky
+pub const CHMOD_MODE_BITS: u32 = 0o7777;
+
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+const S_IRWXU: u32 = 0o700;
+const S_IRWXG: u32 = 0o070;
+const S_IRWXO: u32 = 0o007;
+const ALL_READ: u32 = 0o444;
+const ALL_WRITE: u32 = 0o222;
+const ALL_EXEC: u32 = 0o111;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Flag {
+    Ordinary,
+    // `X`: execute only if some execute bit is already set, or for directories
+    ExecIfAnyExec,
+    // `u`, `g` or `o` as the value, copying that class' current bits
+    CopyExisting,
+}
+
+#[derive(Clone, Debug)]
+struct Change {
+    op: u8,
+    flag: Flag,
+    affected: u32,
+    value: u32,
+    // Bits given explicitly, which are changed on directories even for setuid/setgid
+    mentioned: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModeChange {
+    changes: Vec<Change>,
+}
+
+// Parse octal digits up to the first non digit, None if the value exceeds 07777
+fn octal(s: &[u8]) -> Option<(u32, usize)> {
+    let digits = s.iter().take_while(|c| (b'0'..=b'7').contains(c)).count();
+    let mut value = 0u32;
+    for &c in &s[..digits] {
+        value = value * 8 + (c - b'0') as u32;
+        if value > CHMOD_MODE_BITS {
+            return None;
+        }
+    }
+    Some((value, digits))
+}
+
+impl ModeChange {
+    /// Set all the permission bits to those of `mode`, like `chmod --reference`
+    pub fn from_mode(mode: u32) -> ModeChange {
+        ModeChange::equals(mode & CHMOD_MODE_BITS, CHMOD_MODE_BITS)
+    }
+
+    fn equals(value: u32, mentioned: u32) -> ModeChange {
+        let changes = vec![Change {
+            op: b'=',
+            flag: Flag::Ordinary,
+            affected: CHMOD_MODE_BITS,
+            value,
+            mentioned,
+        }];
+        ModeChange { changes }
+    }
+
+    /// Parse an octal mode or comma separated symbolic clauses of the form
+    /// `[ugoa]*([-+=]([rwxXst]*|[ugo]))+|[-+=][0-7]+`, None if invalid
+    pub fn parse(mode: &[u8]) -> Option<ModeChange> {
+        if mode.first().is_some_and(|c| (b'0'..=b'7').contains(c)) {
+            let (value, digits) = octal(mode)?;
+            if digits != mode.len() {
+                return None;
+            }
+            // Fewer than 5 digits don't clear the setuid/setgid bits of directories
+            let mentioned = match digits < 5 {
+                true => (value & (S_ISUID | S_ISGID)) | S_ISVTX | 0o777,
+                false => CHMOD_MODE_BITS,
+            };
+            return Some(ModeChange::equals(value, mentioned));
+        }
+
+        let mut changes = Vec::new();
+        let mut i = 0;
+        loop {
+            let mut affected = 0;
+            loop {
+                match mode.get(i)? {
+                    b'u' => affected |= S_ISUID | S_IRWXU,
+                    b'g' => affected |= S_ISGID | S_IRWXG,
+                    b'o' => affected |= S_ISVTX | S_IRWXO,
+                    b'a' => affected |= CHMOD_MODE_BITS,
+                    b'=' | b'+' | b'-' => break,
+                    _ => return None,
+                }
+                i += 1;
+            }
+
+            while let Some(&op @ (b'=' | b'+' | b'-')) = mode.get(i) {
+                i += 1;
+                let mut mentioned = 0;
+                let mut flag = Flag::CopyExisting;
+                let value = match mode.get(i) {
+                    Some(b'0'..=b'7') => {
+                        let (value, digits) = octal(&mode[i..])?;
+                        i += digits;
+                        if affected != 0 || !matches!(mode.get(i), None | Some(b',')) {
+                            return None;
+                        }
+                        affected = CHMOD_MODE_BITS;
+                        mentioned = CHMOD_MODE_BITS;
+                        flag = Flag::Ordinary;
+                        value
+                    }
+                    Some(&class @ (b'u' | b'g' | b'o')) => {
+                        i += 1;
+                        match class {
+                            b'u' => S_IRWXU,
+                            b'g' => S_IRWXG,
+                            _ => S_IRWXO,
+                        }
+                    }
+                    _ => {
+                        flag = Flag::Ordinary;
+                        let mut value = 0;
+                        while let Some(c) = mode.get(i) {
+                            match c {
+                                b'r' => value |= ALL_READ,
+                                b'w' => value |= ALL_WRITE,
+                                b'x' => value |= ALL_EXEC,
+                                b'X' => flag = Flag::ExecIfAnyExec,
+                                // Only where `u`, `g` or `o` select them
+                                b's' => value |= S_ISUID | S_ISGID,
+                                b't' => value |= S_ISVTX,
+                                _ => break,
+                            }
+                            i += 1;
+                        }
+                        value
+                    }
+                };
+                let mentioned = match (mentioned, affected) {
+                    (0, 0) => value,
+                    (0, _) => affected & value,
+                    (mentioned, _) => mentioned,
+                };
+                changes.push(Change {
+                    op,
+                    flag,
+                    affected,
+                    value,
+                    mentioned,
+                });
+            }
+
+            match mode.get(i) {
+                None => return Some(ModeChange { changes }),
+                Some(b',') => i += 1,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Apply the changes to `old` (of a directory with `dir`), changes without `ugoa`
+    /// leave out the `umask` bits. Returns the new mode and the bits that were changed
+    pub fn adjust(&self, old: u32, dir: bool, umask: u32) -> (u32, u32) {
+        let mut mode = old & CHMOD_MODE_BITS;
+        let mut changed = 0;
+        for change in &self.changes {
+            let affected = change.affected;
+            let omit = match dir {
+                true => (S_ISUID | S_ISGID) & !change.mentioned,
+                false => 0,
+            };
+            let mut value = change.value;
+            match change.flag {
+                Flag::Ordinary => {}
+                Flag::CopyExisting => {
+                    value &= mode;
+                    // Copy the class' bits to the other two
+                    for bits in [ALL_READ, ALL_WRITE, ALL_EXEC] {
+                        if value & bits != 0 {
+                            value |= bits;
+                        }
+                    }
+                }
+                Flag::ExecIfAnyExec => {
+                    if mode & ALL_EXEC != 0 || dir {
+                        value |= ALL_EXEC;
+                    }
+                }
+            }
+            value &= if affected != 0 { affected } else { !umask };
+            value &= !omit;
+            match change.op {
+                b'=' => {
+                    // Without `ugoa` everything is reset, not only the classes given
+                    let preserved = if affected != 0 { !affected } else { 0 } | omit;
+                    changed |= CHMOD_MODE_BITS & !preserved;
+                    mode = (mode & preserved) | value;
+                }
+                b'+' => {
+                    changed |= value;
+                    mode |= value;
+                }
+                _ => {
+                    changed |= value;
+                    mode &= !value;
+                }
+            }
+        }
+        (mode, changed)
+    }
+}
+
+/// The `rwxr-xr-x` part of `ls -l`, with `s`/`S` and `t`/`T` for the special bits
+pub fn permissions(mode: u32) -> String {
+    let class = |read: u32, write: u32, exec: u32, special: u32, letter: char| {
+        let exec = match (mode & exec != 0, mode & special != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+        [
+            if mode & read != 0 { 'r' } else { '-' },
+            if mode & write != 0 { 'w' } else { '-' },
+            exec,
+        ]
+    };
+    [
+        class(0o400, 0o200, 0o100, S_ISUID, 's'),
+        class(0o040, 0o020, 0o010, S_ISGID, 's'),
+        class(0o004, 0o002, 0o001, S_ISVTX, 't'),
+    ]
+    .concat()
+    .into_iter()
+    .collect()
+}
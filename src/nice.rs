@@ -0,0 +1,163 @@
+/*
+ * nice - run a program with modified scheduling priority
+ *
+ * Without a command the current niceness is printed. The historical `-ADJUST` form
+ * (`-5`, `--5` for -5) is taken wherever options are, and options end at the command
+ * so its own aren't eaten. Failing to lower the niceness without privilege is only
+ * a warning, the command still runs. Statuses are GNU's: 125 for nice's own errors,
+ * 126 when the command can't be run and 127 when it isn't found.
+ */
+
+use crate::{errno_desc, exec_command, exec_status, parse_args, quote};
+use clap::Parser;
+use nix::errno::Errno;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "nice", version, long_about = None)]
+#[command(
+    about = "Run COMMAND with an adjusted niceness, which affects process scheduling. \
+With no COMMAND, print the current niceness"
+)]
+#[command(override_usage = "nice [OPTION] [COMMAND [ARG]...]")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Add integer N to the niceness (default 10)
+    #[clap(
+        long,
+        short = 'n',
+        value_name = "N",
+        allow_hyphen_values = true,
+        overrides_with = "adjustment"
+    )]
+    adjustment: Option<OsString>,
+    /// The command to run and its arguments
+    #[clap(trailing_var_arg = true)]
+    command: Vec<OsString>,
+}
+
+const EXIT_CANCELED: u8 = 125;
+// Niceness ranges from -NZERO to NZERO - 1
+const NZERO: i64 = 20;
+
+// Rewrite the `-ADJUST` options as `--adjustment=ADJUST`, which clap can take
+fn historical_adjustments(args: Vec<OsString>) -> Vec<OsString> {
+    let mut rewritten = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    rewritten.extend(args.next());
+    while let Some(arg) = args.next() {
+        let bytes = arg.as_bytes();
+        let sign = matches!(bytes.get(1), Some(b'-' | b'+')) as usize;
+        if bytes.first() == Some(&b'-') && bytes.get(1 + sign).is_some_and(u8::is_ascii_digit) {
+            let mut adjustment = OsString::from("--adjustment=");
+            adjustment.push(std::ffi::OsStr::from_bytes(&bytes[1..]));
+            rewritten.push(adjustment);
+            continue;
+        }
+        // The option takes the next argument as its value
+        let takes_value = bytes == b"-n" || bytes == b"--adjustment";
+        let is_option = bytes.len() > 1 && bytes[0] == b'-' && bytes != b"--";
+        rewritten.push(arg);
+        if takes_value {
+            rewritten.extend(args.next());
+        } else if !is_option {
+            break;
+        }
+    }
+    rewritten.extend(args);
+    rewritten
+}
+
+// An adjustment like xstrtol takes it, out of range values are clamped to what can
+// make a difference
+fn parse_adjustment(s: &[u8]) -> Option<i64> {
+    let start = s
+        .iter()
+        .take_while(|c| b" \t\n\x0b\x0c\r".contains(c))
+        .count();
+    let s = &s[start..];
+    let (negative, digits) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let mut value: i64 = 0;
+    for &c in digits {
+        value = value.saturating_mul(10).saturating_add((c - b'0') as i64);
+    }
+    let value = if negative { -value } else { value };
+    Some(value.clamp(1 - 2 * NZERO, 2 * NZERO - 1))
+}
+
+fn niceness() -> nix::Result<i32> {
+    Errno::clear();
+    let niceness = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+    match niceness == -1 && Errno::last() != Errno::UnknownErrno {
+        true => Err(Errno::last()),
+        false => Ok(niceness),
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args = historical_adjustments(args.into_iter().collect());
+    let args: Cli = parse_args(args, EXIT_CANCELED);
+
+    let adjustment = match &args.adjustment {
+        Some(given) => match parse_adjustment(given.as_bytes()) {
+            Some(adjustment) => Some(adjustment),
+            None => {
+                eprintln!("nice: invalid adjustment {}", quote(given.as_bytes()));
+                return ExitCode::from(EXIT_CANCELED);
+            }
+        },
+        None => None,
+    };
+    if args.command.is_empty() {
+        if adjustment.is_some() {
+            eprintln!(
+                "nice: a command must be given with an adjustment\n\
+                Try 'nice --help' for more information."
+            );
+            return ExitCode::from(EXIT_CANCELED);
+        }
+        return match niceness() {
+            Ok(niceness) => {
+                println!("{niceness}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("nice: cannot get niceness: {}", errno_desc(e));
+                ExitCode::from(EXIT_CANCELED)
+            }
+        };
+    }
+
+    let niceness = match niceness() {
+        Ok(niceness) => niceness,
+        Err(e) => {
+            eprintln!("nice: cannot get niceness: {}", errno_desc(e));
+            return ExitCode::from(EXIT_CANCELED);
+        }
+    };
+    let niceness = niceness + adjustment.unwrap_or(10) as i32;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, niceness) } != 0 {
+        let e = Errno::last();
+        eprintln!("nice: cannot set niceness: {}", errno_desc(e));
+        // Only a lack of privilege lets the command run anyway
+        if e != Errno::EPERM && e != Errno::EACCES {
+            return ExitCode::from(EXIT_CANCELED);
+        }
+    }
+    let e = exec_command(&args.command);
+    eprintln!(
+        "nice: {}: {}",
+        quote(args.command[0].as_bytes()),
+        errno_desc(e)
+    );
+    exec_status(e)
+}
@@ -0,0 +1,143 @@
+/*
+ * nohup - run a command immune to hangups
+ *
+ * SIGHUP is ignored and the command exec'd. Terminals are swapped out first like
+ * GNU: stdin for /dev/null (opened for writing, so reads fail), stdout for
+ * ./nohup.out, else $HOME/nohup.out (appended to, created 0600), and stderr for
+ * wherever stdout goes. nohup's own failures exit 125, 127 with POSIXLY_CORRECT.
+ */
+
+use crate::{errno_desc, exec_command, exec_status, file_name_concat, parse_args, shell_quote};
+use clap::Parser;
+use nix::fcntl::{fcntl, open, FcntlArg, OFlag};
+use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::sys::stat::{umask, Mode};
+use nix::unistd::{close, dup2, isatty};
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "nohup", version, long_about = None)]
+#[command(about = "Run COMMAND, ignoring hangup signals")]
+#[command(override_usage = "nohup COMMAND [ARG]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// The command to run and its arguments
+    #[clap(trailing_var_arg = true)]
+    command: Vec<OsString>,
+}
+
+const STDIN_FD: RawFd = 0;
+const STDOUT_FD: RawFd = 1;
+const STDERR_FD: RawFd = 2;
+const EXIT_CANCELED: u8 = 125;
+const POSIX_NOHUP_FAILURE: u8 = 127;
+
+// Open `name` onto `fd` if given, like gnulib's fd_reopen, else on a new fd
+fn open_onto(name: &[u8], flags: OFlag, mode: Mode, fd: Option<RawFd>) -> nix::Result<RawFd> {
+    let opened = open(name, flags, mode)?;
+    match fd {
+        Some(fd) if fd != opened => {
+            let result = dup2(opened, fd);
+            let _ = close(opened);
+            result
+        }
+        _ => Ok(opened),
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let failure = match std::env::var_os("POSIXLY_CORRECT") {
+        Some(_) => POSIX_NOHUP_FAILURE,
+        None => EXIT_CANCELED,
+    };
+    let args: Cli = parse_args(args, failure);
+    if args.command.is_empty() {
+        eprintln!("nohup: missing operand\nTry 'nohup --help' for more information.");
+        return ExitCode::from(failure);
+    }
+
+    let ignoring_input = isatty(STDIN_FD).unwrap_or(false);
+    let redirecting_stdout = isatty(STDOUT_FD).unwrap_or(false);
+    let stdout_is_closed = !redirecting_stdout && fcntl(STDOUT_FD, FcntlArg::F_GETFD).is_err();
+    let redirecting_stderr = isatty(STDERR_FD).unwrap_or(false);
+
+    if ignoring_input {
+        let null = open_onto(b"/dev/null", OFlag::O_WRONLY, Mode::empty(), Some(STDIN_FD));
+        if let Err(e) = null {
+            eprintln!("nohup: failed to render standard input unusable: {}", errno_desc(e));
+            return ExitCode::from(failure);
+        }
+        if !redirecting_stdout && !redirecting_stderr {
+            eprintln!("nohup: ignoring input");
+        }
+    }
+
+    let mut out_fd = STDOUT_FD;
+    if redirecting_stdout || (redirecting_stderr && stdout_is_closed) {
+        let flags = OFlag::O_CREAT | OFlag::O_WRONLY | OFlag::O_APPEND;
+        let mode = Mode::S_IRUSR | Mode::S_IWUSR;
+        let onto = redirecting_stdout.then_some(STDOUT_FD);
+        let mask = umask(!mode);
+        let file = b"nohup.out";
+        let home = std::env::var_os("HOME");
+        let in_home = home.map(|home| file_name_concat(home.as_bytes(), file));
+        let file = match open_onto(file, flags, mode, onto) {
+            Ok(fd) => {
+                out_fd = fd;
+                file.to_vec()
+            }
+            Err(e) => match in_home.as_deref().map(|name| open_onto(name, flags, mode, onto)) {
+                Some(Ok(fd)) => {
+                    out_fd = fd;
+                    in_home.unwrap()
+                }
+                in_home_opened => {
+                    let file = shell_quote(file, true);
+                    eprintln!("nohup: failed to open {file}: {}", errno_desc(e));
+                    if let (Some(name), Some(Err(e))) = (&in_home, in_home_opened) {
+                        let name = shell_quote(name, true);
+                        eprintln!("nohup: failed to open {name}: {}", errno_desc(e));
+                    }
+                    return ExitCode::from(failure);
+                }
+            },
+        };
+        umask(mask);
+        let file = shell_quote(&file, true);
+        match ignoring_input {
+            true => eprintln!("nohup: ignoring input and appending output to {file}"),
+            false => eprintln!("nohup: appending output to {file}"),
+        }
+    }
+
+    // Kept to report a failed exec on the original stderr, not in nohup.out
+    let mut saved_stderr = Some(STDERR_FD);
+    if redirecting_stderr {
+        saved_stderr = fcntl(STDERR_FD, FcntlArg::F_DUPFD_CLOEXEC(STDERR_FD + 1)).ok();
+        if !redirecting_stdout {
+            match ignoring_input {
+                true => eprintln!("nohup: ignoring input and redirecting stderr to stdout"),
+                false => eprintln!("nohup: redirecting stderr to stdout"),
+            }
+        }
+        if let Err(e) = dup2(out_fd, STDERR_FD) {
+            eprintln!("nohup: failed to redirect standard error: {}", errno_desc(e));
+            return ExitCode::from(failure);
+        }
+        if stdout_is_closed {
+            let _ = close(out_fd);
+        }
+    }
+
+    unsafe { signal(Signal::SIGHUP, SigHandler::SigIgn) }.ok();
+    let e = exec_command(&args.command);
+    let restored = saved_stderr.is_some_and(|fd| dup2(fd, STDERR_FD) == Ok(STDERR_FD));
+    if restored {
+        let command = shell_quote(args.command[0].as_bytes(), true);
+        eprintln!("nohup: failed to run command {command}: {}", errno_desc(e));
+    }
+    exec_status(e)
+}
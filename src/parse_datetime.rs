@@ -0,0 +1,1069 @@
+/*
+ * Parse free form dates like `date -d`, after gnulib's parse-datetime
+ *
+ * The input is a sequence of items, each optional and in any order: a time of day
+ * (`14:03`, `2:03:09.5pm`, `14:03:09+05:30`), a calendar date (`2024-03-05`,
+ * `3/5/2024`, `5 March 2024`, `Mar 5`), ISO 8601 date and time (`2024-03-05T14:03Z`),
+ * a time zone (`UTC`, `EST`, `+0100`, military letters), a day of the week
+ * (`friday`, `next friday`, `last tue`), relative items (`3 days ago`, `+1 week`,
+ * `yesterday`, `tomorrow`, `now`) and bare numbers, which are GNU's hhmm, yyyymmdd or
+ * year depending on what came before. `@SECONDS` is a time since the Epoch, and a
+ * leading `TZ="ZONE"` parses the rest in that zone. Words are case insensitive and
+ * parenthesized text is a comment.
+ *
+ * Missing fields are today's in the local zone (TZ), with the time of day at
+ * midnight unless only relative items were given. The date is then normalized by
+ * mktime(3): days of the week move the date forward, relative years, months and days
+ * are added to the calendar date and the other relative items to the result.
+ */
+
+use std::ffi::{CStr, OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+
+const HOUR: i64 = 60 * 60;
+const BILLION: i64 = 1_000_000_000;
+
+extern "C" {
+    fn tzset();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Meridian {
+    Am,
+    Pm,
+    H24,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Unit {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+// A number as written, the digits not counting the sign
+#[derive(Clone, Copy, Debug, Default)]
+struct Number {
+    value: i64,
+    digits: usize,
+    negative: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Token {
+    Unsigned(Number),
+    Signed(Number),
+    // Seconds with a fraction, the nanoseconds always a positive offset, and whether
+    // there was a sign
+    Decimal(i64, i64, bool),
+    Meridian(Meridian),
+    Month(i64),
+    Day(i64),
+    // A name of the local zone, with whether it's the daylight saving one
+    LocalZone(i32),
+    // Offsets east of UTC in seconds, of daylight saving zones without the hour
+    Zone(i64),
+    DayZone(i64),
+    Dst,
+    Unit(Unit, i64),
+    Ordinal(i64),
+    DayShift(i64),
+    Ago(i64),
+    // The military zone, also the separator of ISO 8601 dates and times
+    T,
+    Char(u8),
+}
+
+// Months and days, matched by their first 3 letters too
+const MONTHS_AND_DAYS: [(&[u8], Token); 24] = [
+    (b"JANUARY", Token::Month(1)),
+    (b"FEBRUARY", Token::Month(2)),
+    (b"MARCH", Token::Month(3)),
+    (b"APRIL", Token::Month(4)),
+    (b"MAY", Token::Month(5)),
+    (b"JUNE", Token::Month(6)),
+    (b"JULY", Token::Month(7)),
+    (b"AUGUST", Token::Month(8)),
+    (b"SEPTEMBER", Token::Month(9)),
+    (b"SEPT", Token::Month(9)),
+    (b"OCTOBER", Token::Month(10)),
+    (b"NOVEMBER", Token::Month(11)),
+    (b"DECEMBER", Token::Month(12)),
+    (b"SUNDAY", Token::Day(0)),
+    (b"MONDAY", Token::Day(1)),
+    (b"TUESDAY", Token::Day(2)),
+    (b"TUES", Token::Day(2)),
+    (b"WEDNESDAY", Token::Day(3)),
+    (b"WEDNES", Token::Day(3)),
+    (b"THURSDAY", Token::Day(4)),
+    (b"THUR", Token::Day(4)),
+    (b"THURS", Token::Day(4)),
+    (b"FRIDAY", Token::Day(5)),
+    (b"SATURDAY", Token::Day(6)),
+];
+
+const UNITS: [(&[u8], Token); 10] = [
+    (b"YEAR", Token::Unit(Unit::Year, 1)),
+    (b"MONTH", Token::Unit(Unit::Month, 1)),
+    (b"FORTNIGHT", Token::Unit(Unit::Day, 14)),
+    (b"WEEK", Token::Unit(Unit::Day, 7)),
+    (b"DAY", Token::Unit(Unit::Day, 1)),
+    (b"HOUR", Token::Unit(Unit::Hour, 1)),
+    (b"MINUTE", Token::Unit(Unit::Minute, 1)),
+    (b"MIN", Token::Unit(Unit::Minute, 1)),
+    (b"SECOND", Token::Unit(Unit::Second, 1)),
+    (b"SEC", Token::Unit(Unit::Second, 1)),
+];
+
+// Day shifts, ordinals (but SECOND, which is the unit) and ago
+const RELATIVES: [(&[u8], Token); 20] = [
+    (b"TOMORROW", Token::DayShift(1)),
+    (b"YESTERDAY", Token::DayShift(-1)),
+    (b"TODAY", Token::DayShift(0)),
+    (b"NOW", Token::DayShift(0)),
+    (b"LAST", Token::Ordinal(-1)),
+    (b"THIS", Token::Ordinal(0)),
+    (b"NEXT", Token::Ordinal(1)),
+    (b"FIRST", Token::Ordinal(1)),
+    (b"THIRD", Token::Ordinal(3)),
+    (b"FOURTH", Token::Ordinal(4)),
+    (b"FIFTH", Token::Ordinal(5)),
+    (b"SIXTH", Token::Ordinal(6)),
+    (b"SEVENTH", Token::Ordinal(7)),
+    (b"EIGHTH", Token::Ordinal(8)),
+    (b"NINTH", Token::Ordinal(9)),
+    (b"TENTH", Token::Ordinal(10)),
+    (b"ELEVENTH", Token::Ordinal(11)),
+    (b"TWELFTH", Token::Ordinal(12)),
+    (b"AGO", Token::Ago(-1)),
+    (b"HENCE", Token::Ago(1)),
+];
+
+const UNIVERSAL_ZONES: [&[u8]; 3] = [b"GMT", b"UT", b"UTC"];
+
+const ZONES: [(&[u8], Token); 47] = [
+    (b"WET", Token::Zone(0)),
+    (b"WEST", Token::DayZone(0)),
+    (b"BST", Token::DayZone(0)),
+    (b"ART", Token::Zone(-3 * HOUR)),
+    (b"BRT", Token::Zone(-3 * HOUR)),
+    (b"BRST", Token::DayZone(-3 * HOUR)),
+    (b"NST", Token::Zone(-(3 * HOUR + 30 * 60))),
+    (b"NDT", Token::DayZone(-(3 * HOUR + 30 * 60))),
+    (b"AST", Token::Zone(-4 * HOUR)),
+    (b"ADT", Token::DayZone(-4 * HOUR)),
+    (b"CLT", Token::Zone(-4 * HOUR)),
+    (b"CLST", Token::DayZone(-4 * HOUR)),
+    (b"EST", Token::Zone(-5 * HOUR)),
+    (b"EDT", Token::DayZone(-5 * HOUR)),
+    (b"CST", Token::Zone(-6 * HOUR)),
+    (b"CDT", Token::DayZone(-6 * HOUR)),
+    (b"MST", Token::Zone(-7 * HOUR)),
+    (b"MDT", Token::DayZone(-7 * HOUR)),
+    (b"PST", Token::Zone(-8 * HOUR)),
+    (b"PDT", Token::DayZone(-8 * HOUR)),
+    (b"AKST", Token::Zone(-9 * HOUR)),
+    (b"AKDT", Token::DayZone(-9 * HOUR)),
+    (b"HST", Token::Zone(-10 * HOUR)),
+    (b"HAST", Token::Zone(-10 * HOUR)),
+    (b"HADT", Token::DayZone(-10 * HOUR)),
+    (b"SST", Token::Zone(-12 * HOUR)),
+    (b"WAT", Token::Zone(HOUR)),
+    (b"CET", Token::Zone(HOUR)),
+    (b"CEST", Token::DayZone(HOUR)),
+    (b"MET", Token::Zone(HOUR)),
+    (b"MEZ", Token::Zone(HOUR)),
+    (b"MEST", Token::DayZone(HOUR)),
+    (b"MESZ", Token::DayZone(HOUR)),
+    (b"EET", Token::Zone(2 * HOUR)),
+    (b"EEST", Token::DayZone(2 * HOUR)),
+    (b"CAT", Token::Zone(2 * HOUR)),
+    (b"SAST", Token::Zone(2 * HOUR)),
+    (b"EAT", Token::Zone(3 * HOUR)),
+    (b"MSK", Token::Zone(3 * HOUR)),
+    (b"MSD", Token::DayZone(3 * HOUR)),
+    (b"IST", Token::Zone(5 * HOUR + 30 * 60)),
+    (b"SGT", Token::Zone(8 * HOUR)),
+    (b"KST", Token::Zone(9 * HOUR)),
+    (b"JST", Token::Zone(9 * HOUR)),
+    (b"GST", Token::Zone(10 * HOUR)),
+    (b"NZST", Token::Zone(12 * HOUR)),
+    (b"NZDT", Token::DayZone(12 * HOUR)),
+];
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || (b'\t'..=b'\r').contains(&c)
+}
+
+fn lookup_zone(word: &[u8], local_zones: &[(Vec<u8>, i32)]) -> Option<Token> {
+    if UNIVERSAL_ZONES.contains(&word) {
+        return Some(Token::Zone(0));
+    }
+    if let Some((_, isdst)) = local_zones.iter().find(|(name, _)| name == word) {
+        return Some(Token::LocalZone(*isdst));
+    }
+    ZONES
+        .iter()
+        .find(|(name, _)| *name == word)
+        .map(|&(_, token)| token)
+}
+
+// A military zone letter, J (local time) excepted
+fn military_zone(letter: u8) -> Option<Token> {
+    let hours = match letter {
+        b'A'..=b'I' => (letter - b'A') as i64 + 1,
+        b'K'..=b'M' => (letter - b'K') as i64 + 10,
+        b'T' => return Some(Token::T),
+        b'N'..=b'Y' => -((letter - b'N') as i64 + 1),
+        b'Z' => 0,
+        _ => return None,
+    };
+    Some(Token::Zone(hours * HOUR))
+}
+
+// gnulib's lookup_word, in the same order of tables, `word` being upper cased
+fn lookup_word(word: &mut Vec<u8>, local_zones: &[(Vec<u8>, i32)]) -> Option<Token> {
+    match &word[..] {
+        b"AM" | b"A.M." => return Some(Token::Meridian(Meridian::Am)),
+        b"PM" | b"P.M." => return Some(Token::Meridian(Meridian::Pm)),
+        _ => {}
+    }
+    let abbreviation = word.len() == 3 || (word.len() == 4 && word[3] == b'.');
+    let found = MONTHS_AND_DAYS.iter().find(|(name, _)| match abbreviation {
+        true => name[..3] == word[..3],
+        false => *name == &word[..],
+    });
+    if let Some(&(_, token)) = found {
+        return Some(token);
+    }
+    if let Some(token) = lookup_zone(word, local_zones) {
+        return Some(token);
+    }
+    if word == b"DST" {
+        return Some(Token::Dst);
+    }
+    let unit = |word: &[u8]| {
+        UNITS
+            .iter()
+            .find(|(name, _)| *name == word)
+            .map(|&(_, token)| token)
+    };
+    if let Some(token) = unit(word) {
+        return Some(token);
+    }
+    // Plurals
+    if let Some(token) = word.strip_suffix(b"S").and_then(unit) {
+        return Some(token);
+    }
+    if let Some(&(_, token)) = RELATIVES.iter().find(|(name, _)| *name == &word[..]) {
+        return Some(token);
+    }
+    if word.len() == 1 {
+        return military_zone(word[0]);
+    }
+    // Zones like U.T.C.
+    let length = word.len();
+    word.retain(|&c| c != b'.');
+    match word.len() < length {
+        true => lookup_zone(word, local_zones),
+        false => None,
+    }
+}
+
+// The tokens of gnulib's yylex, None for an unknown word or an overflowing number
+fn tokenize(input: &[u8], local_zones: &[(Vec<u8>, i32)]) -> Option<Vec<Token>> {
+    let digit = |i: usize| {
+        input
+            .get(i)
+            .filter(|c| c.is_ascii_digit())
+            .map(|c| (c - b'0') as i64)
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    loop {
+        while input.get(i).is_some_and(|&c| is_space(c)) {
+            i += 1;
+        }
+        let Some(&c) = input.get(i) else {
+            return Some(tokens);
+        };
+        if c.is_ascii_digit() || c == b'-' || c == b'+' {
+            let signed = !c.is_ascii_digit();
+            if signed {
+                i += 1;
+                while input.get(i).is_some_and(|&c| is_space(c)) {
+                    i += 1;
+                }
+                // A sign without a number is ignored
+                if digit(i).is_none() {
+                    continue;
+                }
+            }
+            let negative = c == b'-';
+            let start = i;
+            let mut value: i64 = 0;
+            while let Some(d) = digit(i) {
+                value = value.checked_mul(10)?.checked_add(d)?;
+                i += 1;
+            }
+            let digits = i - start;
+            let value = match negative {
+                true => -value,
+                false => value,
+            };
+            if !matches!(input.get(i), Some(b'.' | b',')) || digit(i + 1).is_none() {
+                let number = Number {
+                    value,
+                    digits,
+                    negative,
+                };
+                tokens.push(match signed {
+                    true => Token::Signed(number),
+                    false => Token::Unsigned(number),
+                });
+                continue;
+            }
+            // Nanoseconds, further digits truncating towards -Infinity
+            i += 1;
+            let mut nsec = 0;
+            for _ in 0..9 {
+                nsec *= 10;
+                if let Some(d) = digit(i) {
+                    nsec += d;
+                    i += 1;
+                }
+            }
+            let mut inexact = false;
+            while let Some(d) = digit(i) {
+                inexact |= d != 0;
+                i += 1;
+            }
+            let (mut sec, mut nsec) = (value, nsec + (negative && inexact) as i64);
+            if negative && nsec != 0 {
+                sec -= 1;
+                nsec = BILLION - nsec;
+            }
+            tokens.push(Token::Decimal(sec, nsec, signed));
+            continue;
+        }
+        if c.is_ascii_alphabetic() {
+            let start = i;
+            while input
+                .get(i)
+                .is_some_and(|&c| c.is_ascii_alphabetic() || c == b'.')
+            {
+                i += 1;
+            }
+            let mut word = input[start..i].to_ascii_uppercase();
+            word.truncate(19);
+            tokens.push(lookup_word(&mut word, local_zones)?);
+            continue;
+        }
+        i += 1;
+        if c != b'(' {
+            tokens.push(Token::Char(c));
+            continue;
+        }
+        // Comments, which nest, an unterminated one lasting till the end
+        let mut depth = 1;
+        while depth > 0 {
+            match input.get(i) {
+                Some(b'(') => depth += 1,
+                Some(b')') => depth -= 1,
+                Some(_) => {}
+                None => return Some(tokens),
+            }
+            i += 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Relative {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minutes: i64,
+    seconds: i64,
+    nsec: i64,
+}
+
+impl Relative {
+    fn new(count: i64, unit: Unit, multiplier: i64) -> Option<Relative> {
+        let value = count.checked_mul(multiplier)?;
+        let mut relative = Relative::default();
+        match unit {
+            Unit::Year => relative.year = value,
+            Unit::Month => relative.month = value,
+            Unit::Day => relative.day = value,
+            Unit::Hour => relative.hour = value,
+            Unit::Minute => relative.minutes = value,
+            Unit::Second => relative.seconds = value,
+        }
+        Some(relative)
+    }
+}
+
+// The grammar of gnulib's parse-datetime.y, the fields of the date being the current
+// ones until items set them
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    year: Number,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minutes: i64,
+    seconds: i64,
+    nsec: i64,
+    meridian: Meridian,
+    relative: Relative,
+    relative_seen: bool,
+    day_ordinal: i64,
+    day_number: i64,
+    local_isdst: i32,
+    time_zone: i64,
+    times_seen: u32,
+    dates_seen: u32,
+    days_seen: u32,
+    local_zones_seen: u32,
+    dsts_seen: u32,
+    zones_seen: u32,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.next).copied()
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    fn peek_char(&self, c: u8) -> bool {
+        matches!(self.peek(), Some(Token::Char(d)) if d == c)
+    }
+
+    fn unsigned(&mut self) -> Option<Number> {
+        match self.bump()? {
+            Token::Unsigned(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    fn set_hhmmss(&mut self, hour: i64, minutes: i64, seconds: i64, nsec: i64) {
+        (self.hour, self.minutes, self.seconds, self.nsec) = (hour, minutes, seconds, nsec);
+    }
+
+    fn apply(&mut self, relative: Relative, factor: i64) -> Option<()> {
+        let sum = |total: i64, value: i64| total.checked_add(value.checked_mul(factor)?);
+        let mut total = self.relative;
+        total.year = sum(total.year, relative.year)?;
+        total.month = sum(total.month, relative.month)?;
+        total.day = sum(total.day, relative.day)?;
+        total.hour = sum(total.hour, relative.hour)?;
+        total.minutes = sum(total.minutes, relative.minutes)?;
+        total.seconds = sum(total.seconds, relative.seconds)?;
+        total.nsec = sum(total.nsec, relative.nsec)?;
+        self.relative = total;
+        self.relative_seen = true;
+        Some(())
+    }
+
+    // A relative item, followed by `ago` maybe
+    fn relative(&mut self, relative: Relative) -> Option<()> {
+        match self.peek() {
+            Some(Token::Ago(factor)) => {
+                self.next += 1;
+                self.apply(relative, factor)
+            }
+            _ => self.apply(relative, 1),
+        }
+    }
+
+    // `:MM` after a numeric zone
+    fn colon_minutes(&mut self) -> Option<Option<i64>> {
+        if !self.peek_char(b':') {
+            return Some(None);
+        }
+        self.next += 1;
+        Some(Some(self.unsigned()?.value))
+    }
+
+    // +HH, +HHMM or +HH:MM, gnulib's time_zone_hhmm
+    fn time_zone_hhmm(&mut self, hours: Number, minutes: Option<i64>) -> Option<()> {
+        let minutes = match minutes {
+            None if hours.digits <= 2 => hours.value.checked_mul(60)?,
+            None => hours.value / 100 * 60 + hours.value % 100,
+            Some(minutes) => match hours.negative {
+                true => hours.value.checked_mul(60)?.checked_sub(minutes)?,
+                false => hours.value.checked_mul(60)?.checked_add(minutes)?,
+            },
+        };
+        if minutes.abs() > 24 * 60 {
+            return None;
+        }
+        self.time_zone = minutes * 60;
+        Some(())
+    }
+
+    fn zone_offset(&mut self, hours: Number) -> Option<()> {
+        let minutes = self.colon_minutes()?;
+        self.zones_seen += 1;
+        self.time_zone_hhmm(hours, minutes)
+    }
+
+    fn optional_zone_offset(&mut self) -> Option<()> {
+        match self.peek() {
+            Some(Token::Signed(hours)) => {
+                self.next += 1;
+                self.zone_offset(hours)
+            }
+            _ => Some(()),
+        }
+    }
+
+    // The rest of HH:MM[:SS[.NNN]] with a meridian, or else a numeric zone
+    fn clock(&mut self, hour: i64, meridian_allowed: bool) -> Option<()> {
+        let minutes = self.unsigned()?.value;
+        let (seconds, nsec) = match self.peek_char(b':') {
+            true => {
+                self.next += 1;
+                match self.bump()? {
+                    Token::Unsigned(seconds) => (seconds.value, 0),
+                    Token::Decimal(seconds, nsec, false) => (seconds, nsec),
+                    _ => return None,
+                }
+            }
+            false => (0, 0),
+        };
+        self.set_hhmmss(hour, minutes, seconds, nsec);
+        match self.peek() {
+            Some(Token::Meridian(meridian)) if meridian_allowed => {
+                self.next += 1;
+                self.meridian = meridian;
+                Some(())
+            }
+            _ => {
+                self.meridian = Meridian::H24;
+                self.optional_zone_offset()
+            }
+        }
+    }
+
+    // The time after the T of ISO 8601, which can't have a meridian
+    fn iso_8601_time(&mut self) -> Option<()> {
+        let hour = self.unsigned()?.value;
+        match self.bump()? {
+            Token::Signed(hours) => {
+                self.set_hhmmss(hour, 0, 0, 0);
+                self.meridian = Meridian::H24;
+                self.zone_offset(hours)
+            }
+            Token::Char(b':') => self.clock(hour, false),
+            _ => None,
+        }
+    }
+
+    // A number alone: a year after a date, YYYYMMDD, or HHMM
+    fn digits_to_date_time(&mut self, number: Number) {
+        let is_year = self.dates_seen > 0
+            && self.year.digits == 0
+            && !self.relative_seen
+            && (self.times_seen > 0 || number.digits > 2);
+        if is_year {
+            self.year = number;
+        } else if number.digits > 4 {
+            self.dates_seen += 1;
+            self.day = number.value % 100;
+            self.month = number.value / 100 % 100;
+            let digits = number.digits - 4;
+            self.year = Number {
+                value: number.value / 10000,
+                digits,
+                negative: false,
+            };
+        } else {
+            self.times_seen += 1;
+            match number.digits <= 2 {
+                true => self.set_hhmmss(number.value, 0, 0, 0),
+                false => self.set_hhmmss(number.value / 100, number.value % 100, 0, 0),
+            }
+            self.meridian = Meridian::H24;
+        }
+    }
+
+    fn after_number(&mut self, number: Number) -> Option<()> {
+        match self.peek() {
+            Some(Token::Meridian(meridian)) => {
+                self.next += 1;
+                self.times_seen += 1;
+                self.set_hhmmss(number.value, 0, 0, 0);
+                self.meridian = meridian;
+                Some(())
+            }
+            Some(Token::Char(b':')) => {
+                self.next += 1;
+                self.times_seen += 1;
+                self.clock(number.value, true)
+            }
+            // M/D, M/D/Y or Y/M/D
+            Some(Token::Char(b'/')) => {
+                self.next += 1;
+                self.dates_seen += 1;
+                let second = self.unsigned()?;
+                if !self.peek_char(b'/') {
+                    (self.month, self.day) = (number.value, second.value);
+                    return Some(());
+                }
+                self.next += 1;
+                let third = self.unsigned()?;
+                match number.digits >= 4 {
+                    true => (self.year, self.month, self.day) = (number, second.value, third.value),
+                    false => {
+                        (self.month, self.day, self.year) = (number.value, second.value, third)
+                    }
+                }
+                Some(())
+            }
+            Some(Token::Signed(second)) => {
+                self.next += 1;
+                match self.peek() {
+                    // Y-M-D, then maybe THH:MM...
+                    Some(Token::Signed(third)) => {
+                        self.next += 1;
+                        self.dates_seen += 1;
+                        (self.year, self.month, self.day) = (number, -second.value, -third.value);
+                        if let Some(Token::T) = self.peek() {
+                            self.next += 1;
+                            self.times_seen += 1;
+                            return self.iso_8601_time();
+                        }
+                        Some(())
+                    }
+                    Some(Token::Unit(unit, multiplier)) => {
+                        self.next += 1;
+                        self.digits_to_date_time(number);
+                        self.apply(Relative::new(second.value, unit, multiplier)?, 1)
+                    }
+                    // HH followed by a zone
+                    _ => {
+                        self.times_seen += 1;
+                        self.set_hhmmss(number.value, 0, 0, 0);
+                        self.meridian = Meridian::H24;
+                        self.zone_offset(second)
+                    }
+                }
+            }
+            // D MONTH [Y]
+            Some(Token::Month(month)) => {
+                self.next += 1;
+                self.dates_seen += 1;
+                (self.day, self.month) = (number.value, month);
+                match self.peek() {
+                    Some(Token::Signed(year)) => {
+                        self.next += 1;
+                        self.year = Number {
+                            value: -year.value,
+                            ..year
+                        };
+                    }
+                    Some(Token::Unsigned(year)) => {
+                        self.next += 1;
+                        self.year = year;
+                    }
+                    _ => {}
+                }
+                Some(())
+            }
+            Some(Token::Day(day)) => {
+                self.next += 1;
+                self.days_seen += 1;
+                (self.day_ordinal, self.day_number) = (number.value, day);
+                Some(())
+            }
+            Some(Token::Unit(unit, multiplier)) => {
+                self.next += 1;
+                self.relative(Relative::new(number.value, unit, multiplier)?)
+            }
+            _ => {
+                self.digits_to_date_time(number);
+                Some(())
+            }
+        }
+    }
+
+    // ZONE, ZONE +HH[:MM], ZONE DST or ZONE followed by a signed relative item
+    fn zone(&mut self, offset: i64, dst_allowed: bool) -> Option<()> {
+        self.zones_seen += 1;
+        self.time_zone = offset;
+        match self.peek() {
+            Some(Token::Signed(hours)) => {
+                self.next += 1;
+                if let Some(Token::Unit(unit, multiplier)) = self.peek() {
+                    self.next += 1;
+                    return self.apply(Relative::new(hours.value, unit, multiplier)?, 1);
+                }
+                let minutes = self.colon_minutes()?;
+                self.time_zone_hhmm(hours, minutes)?;
+                self.time_zone = self.time_zone.checked_add(offset)?;
+            }
+            Some(Token::Dst) if dst_allowed => {
+                self.next += 1;
+                self.time_zone = offset + HOUR;
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn item(&mut self) -> Option<()> {
+        match self.bump()? {
+            Token::Unsigned(number) => self.after_number(number),
+            Token::Signed(number) => match self.bump()? {
+                Token::Unit(unit, multiplier) => {
+                    self.relative(Relative::new(number.value, unit, multiplier)?)
+                }
+                _ => None,
+            },
+            Token::Decimal(seconds, nsec, _) => match self.bump()? {
+                Token::Unit(Unit::Second, _) => {
+                    let relative = Relative {
+                        seconds,
+                        nsec,
+                        ..Default::default()
+                    };
+                    self.relative(relative)
+                }
+                _ => None,
+            },
+            Token::Ordinal(ordinal) => match self.bump()? {
+                Token::Day(day) => {
+                    self.days_seen += 1;
+                    (self.day_ordinal, self.day_number) = (ordinal, day);
+                    Some(())
+                }
+                Token::Unit(unit, multiplier) => {
+                    self.relative(Relative::new(ordinal, unit, multiplier)?)
+                }
+                _ => None,
+            },
+            Token::Day(day) => {
+                if self.peek_char(b',') {
+                    self.next += 1;
+                }
+                self.days_seen += 1;
+                (self.day_ordinal, self.day_number) = (0, day);
+                Some(())
+            }
+            // MONTH D[, Y] or MONTH-D-Y
+            Token::Month(month) => {
+                self.dates_seen += 1;
+                self.month = month;
+                match self.bump()? {
+                    Token::Unsigned(day) => {
+                        self.day = day.value;
+                        if self.peek_char(b',') {
+                            self.next += 1;
+                            self.year = self.unsigned()?;
+                        }
+                    }
+                    Token::Signed(day) => {
+                        let Token::Signed(year) = self.bump()? else {
+                            return None;
+                        };
+                        self.day = -day.value;
+                        self.year = Number {
+                            value: -year.value,
+                            ..year
+                        };
+                    }
+                    _ => return None,
+                }
+                Some(())
+            }
+            Token::Unit(unit, multiplier) => self.relative(Relative::new(1, unit, multiplier)?),
+            Token::DayShift(days) => self.apply(
+                Relative {
+                    day: days,
+                    ..Default::default()
+                },
+                1,
+            ),
+            Token::Zone(offset) => self.zone(offset, true),
+            Token::T => self.zone(-7 * HOUR, false),
+            Token::DayZone(offset) => {
+                self.zones_seen += 1;
+                self.time_zone = offset + HOUR;
+                Some(())
+            }
+            Token::LocalZone(isdst) => {
+                self.local_zones_seen += 1;
+                self.local_isdst = isdst;
+                if let Some(Token::Dst) = self.peek() {
+                    self.next += 1;
+                    self.dsts_seen += 1;
+                    self.local_isdst = 1;
+                }
+                Some(())
+            }
+            _ => None,
+        }
+    }
+}
+
+// TZ set for the parsing of a `TZ="ZONE"` prefix, then put back
+struct TimeZoneOverride(Option<OsString>);
+
+impl TimeZoneOverride {
+    fn new(zone: &[u8]) -> TimeZoneOverride {
+        let previous = std::env::var_os("TZ");
+        std::env::set_var("TZ", OsStr::from_bytes(zone));
+        unsafe { tzset() };
+        TimeZoneOverride(previous)
+    }
+}
+
+impl Drop for TimeZoneOverride {
+    fn drop(&mut self) {
+        match &self.0 {
+            Some(zone) => std::env::set_var("TZ", zone),
+            None => std::env::remove_var("TZ"),
+        }
+        unsafe { tzset() };
+    }
+}
+
+// The zone of a leading TZ="ZONE", backslashes escaping '"' and '\', and what follows
+fn time_zone_prefix(input: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let rest = input.strip_prefix(b"TZ=\"")?;
+    let mut zone = Vec::new();
+    let mut i = 0;
+    loop {
+        match *rest.get(i)? {
+            b'\\' => match *rest.get(i + 1)? {
+                c @ (b'\\' | b'"') => {
+                    zone.push(c);
+                    i += 2;
+                }
+                _ => return None,
+            },
+            b'"' => return Some((zone, &rest[i + 1..])),
+            c => {
+                zone.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn localtime(sec: i64) -> Option<libc::tm> {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    match unsafe { libc::localtime_r(&sec, &mut tm) }.is_null() {
+        true => None,
+        false => Some(tm),
+    }
+}
+
+fn zone_name(tm: &libc::tm) -> Vec<u8> {
+    match tm.tm_zone.is_null() {
+        true => Vec::new(),
+        false => unsafe { CStr::from_ptr(tm.tm_zone) }.to_bytes().to_vec(),
+    }
+}
+
+// mktime(3), or with a fixed UTC offset. None when it fails
+fn make_time(tm: &mut libc::tm, offset: Option<i64>) -> Option<i64> {
+    tm.tm_wday = -1;
+    let t = match offset {
+        None => unsafe { libc::mktime(tm) },
+        Some(offset) => {
+            let t = unsafe { libc::timegm(tm) };
+            tm.tm_gmtoff = offset;
+            t.checked_sub(offset)?
+        }
+    };
+    (tm.tm_wday != -1).then_some(t)
+}
+
+// Whether mktime kept the fields asked for, rather than normalizing them
+fn kept(wanted: &libc::tm, tm: &libc::tm) -> bool {
+    (wanted.tm_sec, wanted.tm_min, wanted.tm_hour) == (tm.tm_sec, tm.tm_min, tm.tm_hour)
+        && (wanted.tm_mday, wanted.tm_mon, wanted.tm_year) == (tm.tm_mday, tm.tm_mon, tm.tm_year)
+}
+
+fn to_hour(hours: i64, meridian: Meridian) -> Option<i32> {
+    let hour = match (meridian, hours) {
+        (Meridian::H24, 0..=23) => hours,
+        (Meridian::Am, 1..=11) => hours,
+        (Meridian::Pm, 1..=11) => hours + 12,
+        (Meridian::Am, 12) => 0,
+        (Meridian::Pm, 12) => 12,
+        _ => return None,
+    };
+    Some(hour as i32)
+}
+
+// Two digit years are 1969 to 2068
+fn to_year(year: Number) -> i64 {
+    match (year.digits, year.value) {
+        (2, 0..=68) => year.value + 2000,
+        (2, 69..) => year.value + 1900,
+        _ => year.value,
+    }
+}
+
+/// The time described by `input` in seconds and nanoseconds since the Epoch, fields
+/// left out being those of `now`. None when it's not a valid date
+pub fn parse_datetime(input: &[u8], now: (i64, u32)) -> Option<(i64, u32)> {
+    let input = input.split(|&c| c == 0).next().unwrap();
+    let start = input.iter().take_while(|&&c| is_space(c)).count();
+    let mut input = &input[start..];
+    let mut _time_zone = None;
+    if let Some((zone, rest)) = time_zone_prefix(input) {
+        _time_zone = Some(TimeZoneOverride::new(&zone));
+        input = rest;
+    }
+
+    let tm = localtime(now.0)?;
+    // The local zone's names, the daylight saving one from the next 3 quarters
+    let mut local_zones = vec![(zone_name(&tm), tm.tm_isdst)];
+    for quarter in 1..=3 {
+        let Some(probe) = localtime(now.0 + quarter * 90 * 24 * HOUR) else {
+            continue;
+        };
+        if probe.tm_isdst != tm.tm_isdst {
+            local_zones.push((zone_name(&probe), probe.tm_isdst));
+            break;
+        }
+    }
+    if local_zones.len() == 2 && local_zones[0].0 == local_zones[1].0 {
+        local_zones.pop();
+        local_zones[0].1 = -1;
+    }
+
+    let tokens = tokenize(input, &local_zones)?;
+    if let [Token::Char(b'@'), seconds] = tokens[..] {
+        return match seconds {
+            Token::Unsigned(seconds) | Token::Signed(seconds) => Some((seconds.value, 0)),
+            Token::Decimal(seconds, nsec, _) => Some((seconds, nsec as u32)),
+            _ => None,
+        };
+    }
+    let year = Number {
+        value: tm.tm_year as i64 + 1900,
+        digits: 0,
+        negative: false,
+    };
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        year,
+        month: tm.tm_mon as i64 + 1,
+        day: tm.tm_mday as i64,
+        hour: tm.tm_hour as i64,
+        minutes: tm.tm_min as i64,
+        seconds: tm.tm_sec as i64,
+        nsec: now.1 as i64,
+        meridian: Meridian::H24,
+        relative: Relative::default(),
+        relative_seen: false,
+        day_ordinal: 0,
+        day_number: 0,
+        local_isdst: 0,
+        time_zone: 0,
+        times_seen: 0,
+        dates_seen: 0,
+        days_seen: 0,
+        local_zones_seen: 0,
+        dsts_seen: 0,
+        zones_seen: 0,
+    };
+    while parser.next < parser.tokens.len() {
+        parser.item()?;
+    }
+    let p = parser;
+    let seen = [
+        p.times_seen,
+        p.dates_seen,
+        p.days_seen,
+        p.dsts_seen,
+        p.local_zones_seen + p.zones_seen,
+    ];
+    if seen.iter().any(|&seen| seen > 1) {
+        return None;
+    }
+
+    let mut tm = tm;
+    tm.tm_year = i32::try_from(to_year(p.year) - 1900).ok()?;
+    tm.tm_mon = i32::try_from(p.month - 1).ok()?;
+    tm.tm_mday = i32::try_from(p.day).ok()?;
+    let mut nsec = p.nsec;
+    if p.times_seen > 0 || (p.relative_seen && p.dates_seen == 0 && p.days_seen == 0) {
+        tm.tm_hour = to_hour(p.hour, p.meridian)?;
+        tm.tm_min = i32::try_from(p.minutes).ok()?;
+        tm.tm_sec = i32::try_from(p.seconds).ok()?;
+    } else {
+        (tm.tm_hour, tm.tm_min, tm.tm_sec, nsec) = (0, 0, 0, 0);
+    }
+    // mktime works out daylight saving time unless told by the input
+    if p.dates_seen + p.days_seen + p.times_seen > 0 {
+        tm.tm_isdst = -1;
+    }
+    if p.local_zones_seen > 0 {
+        tm.tm_isdst = p.local_isdst;
+    }
+    let wanted = tm;
+    let mut start = make_time(&mut tm, None).filter(|_| kept(&wanted, &tm));
+    // A time that doesn't exist locally may in the zone given
+    if start.is_none() && p.zones_seen > 0 {
+        tm = wanted;
+        start = make_time(&mut tm, Some(p.time_zone)).filter(|_| kept(&wanted, &tm));
+    }
+    let mut start = start?;
+
+    if p.days_seen > 0 && p.dates_seen == 0 {
+        let this_week = p.day_ordinal > 0 && tm.tm_wday as i64 != p.day_number;
+        let weeks = p.day_ordinal - this_week as i64;
+        let days = weeks
+            .checked_mul(7)?
+            .checked_add((p.day_number - tm.tm_wday as i64 + 7) % 7)?;
+        tm.tm_mday = i32::try_from(tm.tm_mday as i64 + days).ok()?;
+        tm.tm_isdst = -1;
+        start = make_time(&mut tm, None)?;
+    }
+    let relative = p.relative;
+    if relative.year != 0 || relative.month != 0 || relative.day != 0 {
+        tm.tm_year = i32::try_from(tm.tm_year as i64 + relative.year).ok()?;
+        tm.tm_mon = i32::try_from(tm.tm_mon as i64 + relative.month).ok()?;
+        tm.tm_mday = i32::try_from(tm.tm_mday as i64 + relative.day).ok()?;
+        (tm.tm_hour, tm.tm_min, tm.tm_sec) = (wanted.tm_hour, wanted.tm_min, wanted.tm_sec);
+        tm.tm_isdst = wanted.tm_isdst;
+        start = make_time(&mut tm, None)?;
+    }
+    if p.zones_seen > 0 {
+        start = start.checked_sub(p.time_zone - tm.tm_gmtoff)?;
+    }
+
+    let nsec = nsec.checked_add(relative.nsec)?;
+    let seconds = [
+        relative.hour.checked_mul(HOUR)?,
+        relative.minutes.checked_mul(60)?,
+    ];
+    let seconds = [
+        seconds[0],
+        seconds[1],
+        relative.seconds,
+        nsec.div_euclid(BILLION),
+    ];
+    let sec = seconds
+        .iter()
+        .try_fold(start, |sec, &seconds| sec.checked_add(seconds))?;
+    Some((sec, nsec.rem_euclid(BILLION) as u32))
+}
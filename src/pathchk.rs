@@ -0,0 +1,185 @@
+/*
+ * pathchk - check whether file names are valid or portable
+ *
+ * GNU's checks in its order. By default a name must be reachable (lstat failing for
+ * any reason but ENOENT is an error, like a prefix that isn't a searchable directory)
+ * and within the limits of the file system it would live on: PATH_MAX from pathconf
+ * of "/" or ".", and NAME_MAX of each component from pathconf of the directory before
+ * it, the longest existing one's limit standing for the directories that don't exist
+ * yet. Names too short to break any limit aren't looked into. -p checks against the
+ * POSIX minimums and portable characters instead, -P for empty names and leading '-'.
+ */
+
+use crate::{errno_desc, parse_args, quote, shell_quote};
+use clap::Parser;
+use nix::errno::Errno;
+use nix::unistd::{pathconf, PathconfVar};
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "pathchk", version, long_about = None)]
+#[command(about = "Diagnose invalid or unportable file names")]
+#[command(override_usage = "pathchk [OPTION]... NAME...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Check for most POSIX systems
+    #[clap(short = 'p', action)]
+    posix: bool,
+    /// Check for empty names and leading "-"
+    #[clap(short = 'P', action)]
+    extra: bool,
+    /// Check for all POSIX systems (equivalent to -p -P)
+    #[clap(long, action)]
+    portability: bool,
+    /// The file names to check
+    #[clap(value_name = "NAME")]
+    names: Vec<OsString>,
+}
+
+// POSIX's minimum limits, which -p holds names to
+const POSIX_NAME_MAX: usize = 14;
+const POSIX_PATH_MAX: usize = 256;
+// X/Open's, that every file system here supports: shorter names aren't looked into
+const NAME_MAX_MINIMUM: usize = 255;
+const PATH_MAX_MINIMUM: usize = 1024;
+const PORTABLE_CHARS: &[u8] = b"/ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789._-";
+
+// The components of `name` with their offsets
+fn components(name: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        start += name[start..].iter().take_while(|&&c| c == b'/').count();
+        let len = name[start..].iter().take_while(|&&c| c != b'/').count();
+        let component = (start, &name[start..start + len]);
+        start += len;
+        (len > 0).then_some(component)
+    })
+}
+
+// pathconf of `dir`: Ok(None) for no limit, Err(ENOENT) when it doesn't exist
+fn limit(dir: &[u8], var: PathconfVar) -> nix::Result<Option<usize>> {
+    pathconf(OsStr::from_bytes(dir), var).map(|limit| limit.map(|limit| limit as usize))
+}
+
+fn validate(name: &[u8], posix: bool, extra: bool) -> bool {
+    if extra {
+        let leading_hyphen = name
+            .iter()
+            .enumerate()
+            .any(|(i, &c)| c == b'-' && (i == 0 || name[i - 1] == b'/'));
+        if leading_hyphen {
+            let name = shell_quote(name, true);
+            eprintln!("pathchk: leading '-' in a component of file name {name}");
+            return false;
+        }
+    }
+    if (posix || extra) && name.is_empty() {
+        eprintln!("pathchk: empty file name");
+        return false;
+    }
+
+    let mut exists = false;
+    if posix {
+        if let Some(at) = name.iter().position(|c| !PORTABLE_CHARS.contains(c)) {
+            eprintln!(
+                "pathchk: nonportable character {} in file name {}",
+                quote(&name[at..at + 1]),
+                shell_quote(name, true)
+            );
+            return false;
+        }
+    } else {
+        // A prefix that isn't a searchable directory, or something worse
+        match nix::sys::stat::lstat(OsStr::from_bytes(name)) {
+            Ok(_) => exists = true,
+            Err(Errno::ENOENT) if !name.is_empty() => {}
+            Err(e) => {
+                eprintln!("pathchk: {}: {}", shell_quote(name, false), errno_desc(e));
+                return false;
+            }
+        }
+    }
+
+    if posix || (!exists && PATH_MAX_MINIMUM <= name.len()) {
+        let max = match posix {
+            true => POSIX_PATH_MAX,
+            false => {
+                let dir: &[u8] = match name.first() {
+                    Some(b'/') => b"/",
+                    _ => b".",
+                };
+                match limit(dir, PathconfVar::PATH_MAX) {
+                    Ok(max) => max.unwrap_or(usize::MAX),
+                    Err(_) => PATH_MAX_MINIMUM,
+                }
+            }
+        };
+        if max <= name.len() {
+            eprintln!(
+                "pathchk: limit {} exceeded by length {} of file name {}",
+                max - 1,
+                name.len(),
+                shell_quote(name, true)
+            );
+            return false;
+        }
+    }
+
+    // Only long components need pathconf, -p having its own limit anyway
+    let check_components =
+        posix || (!exists && components(name).any(|(_, c)| NAME_MAX_MINIMUM < c.len()));
+    if !check_components {
+        return true;
+    }
+    // The limit of the longest existing prefix, for the directories after it
+    let mut name_max = NAME_MAX_MINIMUM;
+    let mut known_max = posix.then_some(POSIX_NAME_MAX);
+    for (start, component) in components(name) {
+        match known_max {
+            Some(max) => name_max = max,
+            None => {
+                let dir = match start {
+                    0 => b".",
+                    start => &name[..start],
+                };
+                match limit(dir, PathconfVar::NAME_MAX) {
+                    Ok(max) => name_max = max.unwrap_or(usize::MAX),
+                    Err(Errno::ENOENT) => known_max = Some(name_max),
+                    Err(e) => {
+                        eprintln!("pathchk: {}: {}", shell_quote(dir, false), errno_desc(e));
+                        return false;
+                    }
+                }
+            }
+        }
+        if name_max < component.len() {
+            eprintln!(
+                "pathchk: limit {name_max} exceeded by length {} of file name component {}",
+                component.len(),
+                quote(component)
+            );
+            return false;
+        }
+    }
+    true
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+    if args.names.is_empty() {
+        eprintln!("pathchk: missing operand\nTry 'pathchk --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let posix = args.posix || args.portability;
+    let extra = args.extra || args.portability;
+    let mut ok = true;
+    for name in &args.names {
+        ok &= validate(name.as_bytes(), posix, extra);
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
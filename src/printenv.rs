@@ -0,0 +1,73 @@
+/*
+ * printenv - print all or part of the environment
+ *
+ * The environment is printed byte for byte as the process got it, whatever its
+ * encoding: all of it as NAME=value lines, or just the values of the variables named,
+ * every entry of a name that appears more than once included. A name with '=' in it
+ * is never found. The status is GNU's: 0 only when every variable was found, 1 when
+ * one wasn't and 2 for usage and write errors. Values holding newlines are why -0
+ * exists.
+ */
+
+use crate::{parse_args, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "printenv", version, long_about = None)]
+#[command(
+    about = "Print the values of the specified environment VARIABLE(s). If no VARIABLE \
+is specified, print name and value pairs for them all"
+)]
+#[command(override_usage = "printenv [OPTION]... [VARIABLE]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// End each output line with NUL, not newline
+    #[clap(long, short = '0', action)]
+    null: bool,
+    /// The variables to print
+    #[clap(value_name = "VARIABLE")]
+    variables: Vec<OsString>,
+}
+
+const EXIT_FAILURE: u8 = 2;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, EXIT_FAILURE);
+    let delimiter = if args.null { b'\0' } else { b'\n' };
+    let mut output = Vec::new();
+    let mut found_all = true;
+    if args.variables.is_empty() {
+        for (name, value) in std::env::vars_os() {
+            output.extend_from_slice(name.as_bytes());
+            output.push(b'=');
+            output.extend_from_slice(value.as_bytes());
+            output.push(delimiter);
+        }
+    }
+    for variable in &args.variables {
+        let mut found = false;
+        if !variable.as_bytes().contains(&b'=') {
+            for (_, value) in std::env::vars_os().filter(|(name, _)| name == variable) {
+                output.extend_from_slice(value.as_bytes());
+                output.push(delimiter);
+                found = true;
+            }
+        }
+        found_all &= found;
+    }
+
+    let written = stdout_file().and_then(|mut out| out.write_all(&output));
+    if let Err(e) = written {
+        eprintln!("printenv: write error: {}", strerror(&e));
+        return ExitCode::from(EXIT_FAILURE);
+    }
+    match found_all {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
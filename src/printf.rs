@@ -0,0 +1,785 @@
+/*
+ * printf - format and print data
+ *
+ * Follows GNU printf in the C locale: the format is reused until the arguments run
+ * out, numeric arguments take C constants or a leading quote for a character's code
+ * and floating point conversions use long double precision (see float80.rs).
+ * Invalid numbers are reported and count as 0 (or what was parsed) with a failing
+ * exit status, while an invalid format or escape stops printf right away.
+ */
+
+use crate::float80::{Class, Float80};
+use crate::{quote, reset_sigpipe, shell_quote, stdout_file, strerror};
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::process::ExitCode;
+use std::slice;
+
+const IO_BUFSIZE: usize = 1 << 17;
+
+const USAGE: &str = "\
+Usage: printf FORMAT [ARGUMENT]...
+  or:  printf OPTION
+Print ARGUMENT(s) according to FORMAT, or execute according to OPTION:
+
+      --help     display this help and exit
+      --version  output version information and exit
+
+FORMAT controls the output as in C printf.  Interpreted sequences are:
+
+  \\\"      double quote
+  \\\\      backslash
+  \\a      alert (BEL)
+  \\b      backspace
+  \\c      produce no further output
+  \\e      escape
+  \\f      form feed
+  \\n      new line
+  \\r      carriage return
+  \\t      horizontal tab
+  \\v      vertical tab
+  \\NNN    byte with octal value NNN (1 to 3 digits)
+  \\xHH    byte with hexadecimal value HH (1 to 2 digits)
+  \\uHHHH  Unicode (ISO/IEC 10646) character with hex value HHHH (4 digits)
+  \\UHHHHHHHH  Unicode character with hex value HHHHHHHH (8 digits)
+  %%      a single %
+  %b      ARGUMENT as a string with '\\' escapes interpreted,
+          except that octal escapes are of the form \\0 or \\0NNN
+  %q      ARGUMENT is printed in a format that can be reused as shell input,
+          escaping non-printable characters with the proposed POSIX $'' syntax.
+
+and all C format specifications ending with one of diouxXfeEgGcs, with
+ARGUMENTs converted to proper type first.  Variable widths are handled.
+";
+
+enum Halt {
+    // `\c`, stop printing and exit successfully
+    Stop,
+    // Fatal error, the message is printed and printf exits with a failure
+    Fatal(Vec<u8>),
+    Io(io::Error),
+}
+
+impl From<io::Error> for Halt {
+    fn from(e: io::Error) -> Halt {
+        Halt::Io(e)
+    }
+}
+
+#[derive(Default)]
+struct Spec {
+    minus: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn sign(&self, negative: bool) -> &'static [u8] {
+        match negative {
+            true => b"-",
+            false if self.plus => b"+",
+            false if self.space => b" ",
+            false => b"",
+        }
+    }
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
+
+fn hex_value(c: u8) -> u8 {
+    (c as char).to_digit(16).unwrap() as u8
+}
+
+/// strtoimax/strtoumax with base 0: returns the sign, the magnitude (saturated),
+/// whether it overflowed and the length parsed, 0 when there are no digits
+fn parse_integer(s: &[u8]) -> (bool, u64, bool, usize) {
+    let mut i = s.iter().take_while(|c| is_space(**c)).count();
+    let negative = s.get(i) == Some(&b'-');
+    if matches!(s.get(i), Some(b'+' | b'-')) {
+        i += 1;
+    }
+    let radix = match (s.get(i), s.get(i + 1), s.get(i + 2)) {
+        (Some(b'0'), Some(b'x' | b'X'), Some(c)) if c.is_ascii_hexdigit() => {
+            i += 2;
+            16
+        }
+        (Some(b'0'), _, _) => 8,
+        _ => 10,
+    };
+    let start = i;
+    let mut magnitude = 0u64;
+    let mut overflow = false;
+    while let Some(d) = s.get(i).and_then(|c| (*c as char).to_digit(radix)) {
+        match magnitude
+            .checked_mul(radix as u64)
+            .and_then(|m| m.checked_add(d as u64))
+        {
+            Some(m) => magnitude = m,
+            None => overflow = true,
+        }
+        i += 1;
+    }
+    if i == start {
+        return (false, 0, false, 0);
+    }
+    if overflow {
+        magnitude = u64::MAX;
+    }
+    (negative, magnitude, overflow, i)
+}
+
+/// Width or precision digits in the format, glibc fails the whole printf(3) call for
+/// values past INT_MAX
+fn parse_literal(digits: &[u8]) -> Result<usize, Halt> {
+    let value = digits.iter().try_fold(0i32, |v, d| {
+        v.checked_mul(10)?.checked_add((d - b'0') as i32)
+    });
+    value
+        .map(|v| v as usize)
+        .ok_or_else(|| Halt::Fatal(b"write error".to_vec()))
+}
+
+struct Printf<W: Write> {
+    out: W,
+    ok: bool,
+    posixly_correct: bool,
+}
+
+impl<W: Write> Printf<W> {
+    /// Report an error like error(3), flushing stdout first so it stays in order
+    fn error(&mut self, message: &[u8]) {
+        let _ = self.out.flush();
+        let _ = io::stderr().write_all(&[b"printf: ", message, b"\n"].concat());
+    }
+
+    fn invalid(&mut self, arg: &[u8], message: &str) {
+        self.error(format!("{}{message}", quote(arg)).as_bytes());
+        self.ok = false;
+    }
+
+    /// The code of the character after a leading quote (`'a` or `"a`), if that's the
+    /// form of the argument
+    fn char_constant(&mut self, arg: &[u8]) -> Option<u8> {
+        if !matches!(arg.first(), Some(b'\'' | b'"')) || arg.len() < 2 {
+            return None;
+        }
+        if arg.len() > 2 && !self.posixly_correct {
+            let message = [
+                b"warning: ",
+                &arg[2..],
+                b": character(s) following character constant have been ignored",
+            ];
+            self.error(&message.concat());
+        }
+        Some(arg[1])
+    }
+
+    fn verify_numeric(&mut self, arg: &[u8], end: usize, range: bool) {
+        if range {
+            self.invalid(arg, ": Numerical result out of range");
+        } else if end == 0 && !arg.is_empty() {
+            self.invalid(arg, ": expected a numeric value");
+        } else if end < arg.len() {
+            self.invalid(arg, ": value not completely converted");
+        }
+    }
+
+    fn int_arg(&mut self, arg: &[u8]) -> i64 {
+        if let Some(c) = self.char_constant(arg) {
+            return c as i64;
+        }
+        let (negative, magnitude, mut range, end) = parse_integer(arg);
+        let value = match negative {
+            true if magnitude > 1 << 63 => {
+                range = true;
+                i64::MIN
+            }
+            true => (magnitude as i64).wrapping_neg(),
+            false if magnitude > i64::MAX as u64 => {
+                range = true;
+                i64::MAX
+            }
+            false => magnitude as i64,
+        };
+        self.verify_numeric(arg, end, range);
+        value
+    }
+
+    fn uint_arg(&mut self, arg: &[u8]) -> u64 {
+        if let Some(c) = self.char_constant(arg) {
+            return c as u64;
+        }
+        let (negative, magnitude, range, end) = parse_integer(arg);
+        self.verify_numeric(arg, end, range);
+        match negative && !range {
+            true => magnitude.wrapping_neg(),
+            false => magnitude,
+        }
+    }
+
+    fn float_arg(&mut self, arg: &[u8]) -> Float80 {
+        if let Some(c) = self.char_constant(arg) {
+            return Float80::from(c);
+        }
+        let (value, end, range) = Float80::parse(arg);
+        self.verify_numeric(arg, end, range);
+        value
+    }
+
+    /// Write `prefix` and `body` padded to the field width, with zeros between them
+    /// when `zero`
+    fn pad(&mut self, spec: &Spec, prefix: &[u8], zero: bool, body: &[u8]) -> io::Result<()> {
+        let fill = spec.width.saturating_sub(prefix.len() + body.len()) as u64;
+        if spec.minus {
+            self.out.write_all(prefix)?;
+            self.out.write_all(body)?;
+            io::copy(&mut io::repeat(b' ').take(fill), &mut self.out)?;
+        } else if zero {
+            self.out.write_all(prefix)?;
+            io::copy(&mut io::repeat(b'0').take(fill), &mut self.out)?;
+            self.out.write_all(body)?;
+        } else {
+            io::copy(&mut io::repeat(b' ').take(fill), &mut self.out)?;
+            self.out.write_all(prefix)?;
+            self.out.write_all(body)?;
+        }
+        Ok(())
+    }
+
+    fn print_integer(
+        &mut self,
+        spec: &Spec,
+        conversion: u8,
+        negative: bool,
+        magnitude: u64,
+    ) -> io::Result<()> {
+        let mut digits = match conversion {
+            b'o' => format!("{magnitude:o}"),
+            b'x' => format!("{magnitude:x}"),
+            b'X' => format!("{magnitude:X}"),
+            _ => magnitude.to_string(),
+        }
+        .into_bytes();
+        if let Some(precision) = spec.precision {
+            if precision == 0 && magnitude == 0 {
+                digits.clear();
+            }
+            if digits.len() < precision {
+                digits.splice(0..0, std::iter::repeat(b'0').take(precision - digits.len()));
+            }
+        }
+        let prefix: &[u8] = match conversion {
+            b'd' | b'i' => spec.sign(negative),
+            b'x' if spec.alt && magnitude != 0 => b"0x",
+            b'X' if spec.alt && magnitude != 0 => b"0X",
+            b'o' if spec.alt && digits.first() != Some(&b'0') => b"0",
+            _ => b"",
+        };
+        self.pad(spec, prefix, spec.zero && spec.precision.is_none(), &digits)
+    }
+
+    fn print_float(&mut self, spec: &Spec, conversion: u8, value: Float80) -> io::Result<()> {
+        let body = value.format(conversion, spec.precision, spec.alt);
+        let finite = matches!(value.class, Class::Finite { .. });
+        // Zero padding goes after %a's 0x
+        let hex = match finite && conversion.eq_ignore_ascii_case(&b'a') {
+            true => 2,
+            false => 0,
+        };
+        let prefix = [spec.sign(value.negative), &body[..hex]].concat();
+        self.pad(spec, &prefix, spec.zero && finite, &body[hex..])
+    }
+
+    /// Print the escape sequence starting at the backslash `s[0]`, returning its
+    /// length. `octal_0` is %b's syntax, where octal escapes may be `\0NNN`.
+    fn print_escape(&mut self, s: &[u8], octal_0: bool) -> Result<usize, Halt> {
+        let at = |i: usize| s.get(i).copied().unwrap_or(0);
+        let missing_hex = || Halt::Fatal(b"missing hexadecimal number in escape".to_vec());
+        let len = match at(1) {
+            b'x' => {
+                let digits = s[2..]
+                    .iter()
+                    .take(2)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .count();
+                if digits == 0 {
+                    return Err(missing_hex());
+                }
+                let value = s[2..2 + digits]
+                    .iter()
+                    .fold(0, |v, c| v * 16 + hex_value(*c));
+                self.out.write_all(&[value])?;
+                2 + digits
+            }
+            b'0'..=b'7' => {
+                let start = 1 + (octal_0 && at(1) == b'0') as usize;
+                let digits = s[start..]
+                    .iter()
+                    .take(3)
+                    .take_while(|c| matches!(c, b'0'..=b'7'))
+                    .count();
+                let value = s[start..start + digits]
+                    .iter()
+                    .fold(0u32, |v, c| v * 8 + (c - b'0') as u32);
+                self.out.write_all(&[value as u8])?;
+                start + digits
+            }
+            b'c' => return Err(Halt::Stop),
+            c @ (b'"' | b'\\' | b'a' | b'b' | b'e' | b'f' | b'n' | b'r' | b't' | b'v') => {
+                let byte = match c {
+                    b'a' => b'\x07',
+                    b'b' => b'\x08',
+                    b'e' => b'\x1b',
+                    b'f' => b'\x0c',
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'v' => b'\x0b',
+                    c => c,
+                };
+                self.out.write_all(&[byte])?;
+                2
+            }
+            c @ (b'u' | b'U') => {
+                let digits = if c == b'u' { 4 } else { 8 };
+                if s.len() < 2 + digits || !s[2..2 + digits].iter().all(u8::is_ascii_hexdigit) {
+                    return Err(missing_hex());
+                }
+                let value = s[2..2 + digits]
+                    .iter()
+                    .fold(0u32, |v, c| v * 16 + hex_value(*c) as u32);
+                if (value <= 0x9f && !matches!(value, 0x24 | 0x40 | 0x60))
+                    || (0xd800..=0xdfff).contains(&value)
+                {
+                    let message = format!(
+                        "invalid universal character name \\{}{value:0digits$x}",
+                        c as char
+                    );
+                    return Err(Halt::Fatal(message.into_bytes()));
+                }
+                // Only ASCII can be encoded in the C locale, the rest is printed
+                // back as an escape like gnulib's unicode_to_mb fallback
+                match value {
+                    0..=0x7f => self.out.write_all(&[value as u8])?,
+                    0x80..=0xffff => write!(self.out, "\\u{value:04X}")?,
+                    _ => write!(self.out, "\\U{value:08X}")?,
+                }
+                2 + digits
+            }
+            0 => {
+                self.out.write_all(b"\\")?;
+                1
+            }
+            c => {
+                self.out.write_all(&[b'\\', c])?;
+                2
+            }
+        };
+        Ok(len)
+    }
+
+    /// Print `s` interpreting backslash escapes
+    fn print_escaped(&mut self, s: &[u8], octal_0: bool) -> Result<(), Halt> {
+        let mut i = 0;
+        while i < s.len() {
+            let end = s[i..]
+                .iter()
+                .position(|c| *c == b'\\')
+                .map_or(s.len(), |n| i + n);
+            self.out.write_all(&s[i..end])?;
+            i = end;
+            if i < s.len() {
+                i += self.print_escape(&s[i..], octal_0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the directive starting at the '%' `format[start]`, returning the index
+    /// past it
+    fn directive(
+        &mut self,
+        format: &[u8],
+        start: usize,
+        args: &mut slice::Iter<Vec<u8>>,
+    ) -> Result<usize, Halt> {
+        let at = |i: usize| format.get(i).copied().unwrap_or(0);
+        let mut f = start + 1;
+        match at(f) {
+            b'%' => {
+                self.out.write_all(b"%")?;
+                return Ok(f + 1);
+            }
+            b'b' => {
+                if let Some(arg) = args.next() {
+                    self.print_escaped(arg, true)?;
+                }
+                return Ok(f + 1);
+            }
+            b'q' => {
+                if let Some(arg) = args.next() {
+                    self.out.write_all(shell_quote(arg, false).as_bytes())?;
+                }
+                return Ok(f + 1);
+            }
+            _ => {}
+        }
+
+        // Conversions still valid with the flags seen so far
+        let mut ok = [false; 256];
+        b"aAcdeEfFgGiosuxX"
+            .iter()
+            .for_each(|c| ok[*c as usize] = true);
+        let disable = |ok: &mut [bool; 256], conversions: &[u8]| {
+            conversions.iter().for_each(|c| ok[*c as usize] = false)
+        };
+
+        let mut spec = Spec::default();
+        loop {
+            match at(f) {
+                b'I' | b'\'' => disable(&mut ok, b"aAceEosxX"),
+                b'-' => spec.minus = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => {
+                    spec.alt = true;
+                    disable(&mut ok, b"cdisu");
+                }
+                b'0' => {
+                    spec.zero = true;
+                    disable(&mut ok, b"cs");
+                }
+                _ => break,
+            }
+            f += 1;
+        }
+
+        if at(f) == b'*' {
+            f += 1;
+            if let Some(arg) = args.next() {
+                let width = self.int_arg(arg);
+                if i32::try_from(width).is_err() {
+                    let message = format!("invalid field width: {}", quote(arg));
+                    return Err(Halt::Fatal(message.into_bytes()));
+                }
+                spec.minus |= width < 0;
+                spec.width = width.unsigned_abs() as usize;
+            }
+        } else {
+            let digits = format[f..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            spec.width = parse_literal(&format[f..f + digits])?;
+            f += digits;
+        }
+
+        if at(f) == b'.' {
+            f += 1;
+            ok[b'c' as usize] = false;
+            if at(f) == b'*' {
+                f += 1;
+                spec.precision = match args.next() {
+                    Some(arg) => match self.int_arg(arg) {
+                        precision if precision < 0 => None,
+                        precision if precision > i32::MAX as i64 => {
+                            let message = format!("invalid precision: {}", quote(arg));
+                            return Err(Halt::Fatal(message.into_bytes()));
+                        }
+                        precision => Some(precision as usize),
+                    },
+                    None => Some(0),
+                };
+            } else {
+                let digits = format[f..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                spec.precision = Some(parse_literal(&format[f..f + digits])?);
+                f += digits;
+            }
+        }
+
+        while matches!(at(f), b'h' | b'j' | b'l' | b'L' | b't' | b'z') {
+            f += 1;
+        }
+        let conversion = at(f);
+        if !ok[conversion as usize] {
+            let directive = &format[start..format.len().min(f + 1)];
+            return Err(Halt::Fatal(
+                [directive, b": invalid conversion specification"].concat(),
+            ));
+        }
+
+        let arg = args.next().map_or(&b""[..], Vec::as_slice);
+        match conversion {
+            b'd' | b'i' => {
+                let value = self.int_arg(arg);
+                self.print_integer(&spec, conversion, value < 0, value.unsigned_abs())?;
+            }
+            b'o' | b'u' | b'x' | b'X' => {
+                let value = self.uint_arg(arg);
+                self.print_integer(&spec, conversion, false, value)?;
+            }
+            b'c' => self.pad(&spec, b"", false, &[arg.first().copied().unwrap_or(0)])?,
+            b's' => {
+                let len = spec
+                    .precision
+                    .map_or(arg.len(), |precision| precision.min(arg.len()));
+                self.pad(&spec, b"", false, &arg[..len])?;
+            }
+            _ => {
+                let value = self.float_arg(arg);
+                self.print_float(&spec, conversion, value)?;
+            }
+        }
+        Ok(f + 1)
+    }
+
+    /// Print the format once, returning how many arguments it used
+    fn print_formatted(&mut self, format: &[u8], args: &[Vec<u8>]) -> Result<usize, Halt> {
+        let mut iter = args.iter();
+        let mut i = 0;
+        while i < format.len() {
+            let end = format[i..]
+                .iter()
+                .position(|c| *c == b'%' || *c == b'\\')
+                .map_or(format.len(), |n| i + n);
+            self.out.write_all(&format[i..end])?;
+            i = end;
+            match format.get(i) {
+                Some(b'%') => i = self.directive(format, i, &mut iter)?,
+                Some(_) => i += self.print_escape(&format[i..], false)?,
+                None => {}
+            }
+        }
+        Ok(args.len() - iter.len())
+    }
+
+    fn run(&mut self, format: &[u8], mut args: &[Vec<u8>]) -> Result<(), Halt> {
+        loop {
+            let used = self.print_formatted(format, args)?;
+            args = &args[used..];
+            if used == 0 || args.is_empty() {
+                break;
+            }
+        }
+        if let Some(arg) = args.first() {
+            let message = format!(
+                "warning: ignoring excess arguments, starting with {}",
+                quote(arg)
+            );
+            self.error(message.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let mut args: Vec<Vec<u8>> = args
+        .into_iter()
+        .skip(1)
+        .map(OsStringExt::into_vec)
+        .collect();
+    // There are no options besides these, anything else is the format
+    if args.len() == 1 {
+        match args[0].as_slice() {
+            b"--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            b"--version" => {
+                println!("printf {}", env!("CARGO_PKG_VERSION"));
+                return ExitCode::SUCCESS;
+            }
+            _ => {}
+        }
+    }
+    if args.first().is_some_and(|arg| arg == b"--") {
+        args.remove(0);
+    }
+    let Some((format, args)) = args.split_first() else {
+        eprintln!("printf: missing operand\nTry 'printf --help' for more information.");
+        return ExitCode::FAILURE;
+    };
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("printf: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut printf = Printf {
+        out: BufWriter::with_capacity(IO_BUFSIZE, stdout),
+        ok: true,
+        posixly_correct: env::var_os("POSIXLY_CORRECT").is_some(),
+    };
+    let ok = match printf.run(format, args) {
+        Ok(()) => printf.ok,
+        Err(Halt::Stop) => true,
+        Err(Halt::Fatal(message)) => {
+            printf.error(&message);
+            false
+        }
+        Err(Halt::Io(e)) => {
+            eprintln!("printf: write error: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = printf.out.flush() {
+        eprintln!("printf: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run printf on `format` and `args`, giving the output and how it ended:
+    // Ok with whether all arguments were valid, or the fatal error message
+    fn printf(format: &str, args: &[&str]) -> (Vec<u8>, Result<bool, String>) {
+        let mut printf = Printf {
+            out: Vec::new(),
+            ok: true,
+            posixly_correct: false,
+        };
+        let args: Vec<Vec<u8>> = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+        let status = match printf.run(format.as_bytes(), &args) {
+            Ok(()) => Ok(printf.ok),
+            Err(Halt::Stop) => Ok(true),
+            Err(Halt::Fatal(message)) => Err(String::from_utf8(message).unwrap()),
+            Err(Halt::Io(e)) => panic!("{e}"),
+        };
+        (printf.out, status)
+    }
+
+    #[test]
+    fn escapes() {
+        let cases: &[(&str, &[&str], &[u8])] = &[
+            ("\\x41\\101\\e\\n", &[], b"AA\x1b\n"),
+            (
+                "\\\"\\\\\\a\\b\\f\\r\\t\\v",
+                &[],
+                b"\"\\\x07\x08\x0c\r\t\x0b",
+            ),
+            // Characters outside of ASCII stay escaped in the C locale
+            ("\\u00e9\\U0001F600", &[], b"\\u00E9\\U0001F600"),
+            ("\\1234", &[], b"S4"),
+            ("a\\cb", &[], b"a"),
+            // %b takes octal as \0NNN, and \c stops everything
+            ("%b|%b", &["\\0101\\101", "\\1"], b"AA|\x01"),
+            ("%b|%b\\n", &["\\x4", "a\\cb", "x"], b"\x04|a"),
+            ("%s\\n", &["\\n"], b"\\n\n"),
+        ];
+        for (format, args, expected) in cases {
+            assert_eq!(
+                printf(format, args),
+                (expected.to_vec(), Ok(true)),
+                "{format:?} {args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        let cases: &[(&str, &[&str], &str)] = &[
+            (
+                "%5.2f|%x|%o|%#x|%X",
+                &["3.14159", "255", "8", "255", "255"],
+                " 3.14|ff|10|0xff|FF",
+            ),
+            (
+                "%c|%-4s|%.3s|%05d|%+d",
+                &["hello", "ab", "abcdef", "-42", "1"],
+                "h|ab  |abc|-0042|+1",
+            ),
+            (
+                "%i|%d|%d|%*d|%-*d|",
+                &["0x10", "010", "'A", "3", "7", "3", "7"],
+                "16|8|65|  7|7  |",
+            ),
+            (
+                "%u|%x",
+                &["-1", "-1"],
+                "18446744073709551615|ffffffffffffffff",
+            ),
+            (
+                "%e|%g|%G|%.0f",
+                &["1e300", "0.0001", "1e-10", "2.5"],
+                "1.000000e+300|0.0001|1E-10|2",
+            ),
+            ("%s %%|%5s|%-5s|", &["a", "b", "c"], "a %|    b|c    |"),
+            // %q quotes for the shell, $'' only for unprintable bytes
+            (
+                "%q %q %q %q",
+                &["a b", "it's", "a\nb", ""],
+                "'a b' \"it's\" 'a'$'\\n''b' ''",
+            ),
+            ("%q", &["plain"], "plain"),
+            // The format is reused for left over arguments, missing ones are empty or 0
+            ("%s-%s\\n", &["a", "b", "c"], "a-b\nc-\n"),
+            ("%d|%s|%f", &[], "0||0.000000"),
+        ];
+        for (format, args, expected) in cases {
+            assert_eq!(
+                printf(format, args),
+                (expected.as_bytes().to_vec(), Ok(true)),
+                "{format:?} {args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        // Reported, printed as far as they were parsed, and printf fails at the end
+        let cases: &[(&str, &str, &str)] = &[
+            ("%d", "9223372036854775808", "9223372036854775807"),
+            ("%d", "-9223372036854775809", "-9223372036854775808"),
+            ("%u", "18446744073709551616", "18446744073709551615"),
+            ("%d", "12abc", "12"),
+            ("%d", "abc", "0"),
+            ("%x", "0x", "0"),
+            ("%f", "1e5000", "inf"),
+            ("%.1f", "1.5x", "1.5"),
+        ];
+        for (format, arg, expected) in cases {
+            assert_eq!(
+                printf(format, &[arg]),
+                (expected.as_bytes().to_vec(), Ok(false)),
+                "{format:?} {arg:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn fatal_errors() {
+        let cases: &[(&str, &str, &str)] = &[
+            ("a%z", "a", "%z: invalid conversion specification"),
+            ("abc|%5%", "abc|", "%5%: invalid conversion specification"),
+            ("\\x", "", "missing hexadecimal number in escape"),
+            ("x%", "x", "%: invalid conversion specification"),
+        ];
+        for (format, output, message) in cases {
+            assert_eq!(
+                printf(format, &[]),
+                (output.as_bytes().to_vec(), Err(message.to_string())),
+                "{format:?}"
+            );
+        }
+    }
+}
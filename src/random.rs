@@ -0,0 +1,154 @@
+/*
+ * Random bytes for shuf/shred/mktemp, either from the kernel (getrandom(2)) or
+ * from a `--random-source` file.
+ *
+ * Integers are drawn like gnulib's randint: bytes are appended big-endian until the
+ * range is covered, a value past the largest multiple of the range is retried and
+ * what's left of a draw is kept for the next one. Permutations are gnulib's randperm,
+ * so a given source file yields the same numbers and orders as GNU's tools.
+ */
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::path::Path;
+
+const POOL_SIZE: usize = 4096;
+
+enum Bytes {
+    Os {
+        pool: Box<[u8; POOL_SIZE]>,
+        pos: usize,
+    },
+    File(BufReader<File>),
+}
+
+pub struct RandomSource {
+    bytes: Bytes,
+    // Randomness left over from the last draw: a number uniformly in `0..=randmax`
+    randnum: u64,
+    randmax: u64,
+}
+
+impl RandomSource {
+    /// Read from `path` when given, the kernel's CSPRNG otherwise
+    pub fn open(path: Option<&Path>) -> io::Result<RandomSource> {
+        let bytes = match path {
+            Some(path) => Bytes::File(BufReader::new(File::open(path)?)),
+            None => Bytes::Os {
+                pool: Box::new([0; POOL_SIZE]),
+                pos: POOL_SIZE,
+            },
+        };
+        Ok(RandomSource {
+            bytes,
+            randnum: 0,
+            randmax: 0,
+        })
+    }
+
+    /// Fill `buf` entirely, a random source file running dry is an `UnexpectedEof` error
+    pub fn fill(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        match &mut self.bytes {
+            Bytes::File(reader) => reader.read_exact(buf).map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => io::Error::new(ErrorKind::UnexpectedEof, "end of file"),
+                _ => e,
+            }),
+            Bytes::Os { pool, pos } => {
+                for byte in buf.iter_mut() {
+                    if *pos == POOL_SIZE {
+                        getrandom(&mut pool[..])?;
+                        *pos = 0;
+                    }
+                    *byte = pool[*pos];
+                    *pos += 1;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Uniformly distributed integer in `0..=genmax`
+    fn genmax(&mut self, genmax: u64) -> io::Result<u64> {
+        let (mut randnum, mut randmax) = (self.randnum, self.randmax);
+        loop {
+            if randmax < genmax {
+                let mut i = 0;
+                let mut rmax = randmax;
+                while rmax < genmax {
+                    rmax = (rmax << 8) + 0xff;
+                    i += 1;
+                }
+                let mut buf = [0u8; 8];
+                self.fill(&mut buf[..i])?;
+                for byte in &buf[..i] {
+                    randnum = (randnum << 8) + *byte as u64;
+                    randmax = (randmax << 8) + 0xff;
+                }
+            }
+            if randmax == genmax {
+                (self.randnum, self.randmax) = (0, 0);
+                return Ok(randnum);
+            }
+            // genmax < randmax, so choices can't overflow to zero
+            let choices = genmax + 1;
+            let excess_choices = randmax - genmax;
+            let unusable_choices = excess_choices % choices;
+            let last_usable_choice = randmax - unusable_choices;
+            if randnum <= last_usable_choice {
+                (self.randnum, self.randmax) = (randnum / choices, excess_choices / choices);
+                return Ok(randnum % choices);
+            }
+            // Retry, keeping what falling past the last usable choice says
+            randnum -= last_usable_choice + 1;
+            randmax = unusable_choices - 1;
+        }
+    }
+
+    /// Uniformly distributed integer in `0..n`, `n` must not be zero
+    pub fn below(&mut self, n: u64) -> io::Result<u64> {
+        self.genmax(n - 1)
+    }
+
+    /// The first `h` of a random permutation of `0..n`, from a Fisher-Yates shuffle
+    /// stopped after `h` steps
+    pub fn permutation(&mut self, h: u64, n: u64) -> io::Result<Vec<u64>> {
+        let mut perm = Vec::with_capacity(h as usize);
+        // Beyond the first `h`, entries are only kept once moved when `n` is far larger
+        if n / 128 > h {
+            let mut moved = HashMap::new();
+            for i in 0..h {
+                let j = i + self.below(n - i)?;
+                let at = |k| moved.get(&k).copied().unwrap_or(k);
+                let (vi, vj) = (at(i), at(j));
+                moved.insert(j, vi);
+                perm.push(vj);
+            }
+        } else {
+            perm.extend(0..n);
+            for i in 0..h {
+                let j = i + self.below(n - i)?;
+                perm.swap(i as usize, j as usize);
+            }
+            perm.truncate(h as usize);
+        }
+        Ok(perm)
+    }
+}
+
+fn getrandom(buf: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let rest = &mut buf[filled..];
+        let n = unsafe { libc::getrandom(rest.as_mut_ptr().cast(), rest.len(), 0) };
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        filled += n as usize;
+    }
+    Ok(())
+}
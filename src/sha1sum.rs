@@ -0,0 +1,13 @@
+/*
+ * sha1sum - compute and check SHA1 message digests
+ *
+ * All of it is the shared *sum front-end in checksum.rs.
+ */
+
+use crate::checksum::{self, Algorithm};
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    checksum::sum_main("sha1sum", Algorithm::Sha1, args)
+}
@@ -0,0 +1,13 @@
+/*
+ * sha384sum - compute and check SHA384 message digests
+ *
+ * All of it is the shared *sum front-end in checksum.rs.
+ */
+
+use crate::checksum::{self, Algorithm};
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    checksum::sum_main("sha384sum", Algorithm::Sha384, args)
+}
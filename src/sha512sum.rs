@@ -0,0 +1,13 @@
+/*
+ * sha512sum - compute and check SHA512 message digests
+ *
+ * All of it is the shared *sum front-end in checksum.rs.
+ */
+
+use crate::checksum::{self, Algorithm};
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    checksum::sum_main("sha512sum", Algorithm::Sha512, args)
+}
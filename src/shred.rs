@@ -0,0 +1,1001 @@
+/*
+ * shred - overwrite a file to hide its contents, and optionally delete it
+ *
+ * The passes are GNU's: fixed patterns taken from its table in the same way and
+ * mixed in with random passes, in an order shuffled by `RandomSource` (so the same
+ * --random-source file always gives the same passes). Regular files are overwritten
+ * up to their size rounded up to the block size unless -x, a file smaller than a block
+ * getting a first round over just its bytes, and every pass is synced with fdatasync.
+ * Writes go through O_DIRECT where that works, a bad sector (EIO) is reported and
+ * skipped. -u truncates the file and renames it to ever shorter names of zeros before
+ * unlinking it, like GNU, -v showing each step.
+ */
+
+use crate::random::RandomSource;
+use crate::{errno_desc, last_component, parse_args, quote, shell_quote, strerror};
+use clap::{ArgAction, Parser};
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::stat::{fstat, SFlag};
+use nix::unistd::{close, fdatasync, fsync, ftruncate, isatty, lseek, unlink, write, Whence};
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::SystemTime;
+
+#[derive(Debug, Parser)]
+#[command(name = "shred", version, long_about = None)]
+#[command(
+    about = "Overwrite the specified FILE(s) repeatedly, in order to make it harder for even \
+very expensive hardware probing to recover the data. If FILE is -, shred standard output. \
+Delete FILE(s) if --remove (-u) is specified. The default is not to remove the files \
+because it is common to operate on device files like /dev/hda, and those files usually \
+should not be removed. The optional HOW parameter indicates how to remove a directory \
+entry: 'unlink' => use a standard unlink call, 'wipe' => also first obfuscate bytes in \
+the name, 'wipesync' => also sync each obfuscated byte to the device. The default mode \
+is 'wipesync', but note it can be expensive"
+)]
+#[command(override_usage = "shred [OPTION]... FILE...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Change permissions to allow writing if necessary
+    #[clap(long, short, action)]
+    force: bool,
+    /// Overwrite N times instead of the default (3)
+    #[clap(long, short = 'n', value_name = "N", allow_hyphen_values = true)]
+    iterations: Option<OsString>,
+    /// Get random bytes from FILE
+    #[clap(long, value_name = "FILE", action = ArgAction::Append)]
+    random_source: Vec<PathBuf>,
+    /// Shred this many bytes (suffixes like K, M, G accepted)
+    #[clap(long, short, value_name = "N", allow_hyphen_values = true)]
+    size: Option<OsString>,
+    /// Deallocate and remove file after overwriting
+    #[clap(short = 'u', action)]
+    unlink: bool,
+    /// Like -u but give control on HOW to delete
+    #[clap(long, value_name = "HOW", num_args = 0..=1, require_equals = true)]
+    remove: Option<Option<String>>,
+    /// Show progress
+    #[clap(long, short, action)]
+    verbose: bool,
+    /// Do not round file sizes up to the next full block; this is the default for
+    /// non-regular files
+    #[clap(long, short = 'x', action)]
+    exact: bool,
+    /// Add a final overwrite with zeros to hide shredding
+    #[clap(long, short, action)]
+    zero: bool,
+    /// The files to shred
+    #[clap(value_name = "FILE")]
+    files: Vec<OsString>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Remove {
+    Unlink,
+    Wipe,
+    WipeSync,
+}
+
+const REMOVE_METHODS: [(&str, Remove); 3] = [
+    ("unlink", Remove::Unlink),
+    ("wipe", Remove::Wipe),
+    ("wipesync", Remove::WipeSync),
+];
+
+#[derive(Debug)]
+struct Options {
+    force: bool,
+    iterations: usize,
+    size: Option<u64>,
+    remove: Option<Remove>,
+    verbose: bool,
+    exact: bool,
+    zero: bool,
+}
+
+// GNU's passes: a count of random passes when negative, else a count of the patterns
+// that follow. 12-bit patterns, 0x1000 flipping the first bit of every sector
+#[rustfmt::skip]
+const PATTERNS: &[i32] = &[
+    -2,
+    2, 0x000, 0xFFF,
+    2, 0x555, 0xAAA,
+    -1,
+    6, 0x249, 0x492, 0x6DB, 0x924, 0xB6D, 0xDB6,
+    12, 0x111, 0x222, 0x333, 0x444, 0x666, 0x777,
+    0x888, 0x999, 0xBBB, 0xCCC, 0xDDD, 0xEEE,
+    -1,
+    8, 0x1000, 0x1249, 0x1492, 0x16DB, 0x1924, 0x1B6D, 0x1DB6, 0x1FFF,
+    14, 0x1111, 0x1222, 0x1333, 0x1444, 0x1555, 0x1666, 0x1777,
+    0x1888, 0x1999, 0x1AAA, 0x1BBB, 0x1CCC, 0x1DDD, 0x1EEE,
+    -1,
+];
+
+const SECTOR_SIZE: usize = 512;
+const SECTOR_MASK: usize = SECTOR_SIZE - 1;
+// Writes of a periodic pattern are a multiple of 3 bytes and sectors
+const PERIODIC_OUTPUT_SIZE: usize = 60 * 1024;
+const NONPERIODIC_OUTPUT_SIZE: usize = 64 * 1024;
+// Seconds between -v progress reports
+const VERBOSE_UPDATE: u64 = 5;
+// Characters of the names -u renames files to, in order
+const NAME_SET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_.";
+
+/// The random source failed, which ends shred like GNU's randread
+struct RandomFailure(io::Error);
+
+/// How a pass went: fine, with write errors that were skipped, or stopped short
+#[derive(Debug, PartialEq, Eq)]
+enum Pass {
+    Done,
+    WriteError,
+    Failed,
+}
+
+// A number like gnulib's xstrtoumax takes it, with C's base prefixes when `base` is 0
+// and the "cbBkKMGTPEZY0" suffixes when `suffixes`. Err(Some(EOVERFLOW)) when over `max`
+fn parse_number(s: &[u8], base: u32, suffixes: bool, max: u64) -> Result<u64, Option<Errno>> {
+    let start = s.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let mut s = &s[start..];
+    if let Some(rest) = s.strip_prefix(b"+") {
+        s = rest;
+    }
+    let radix = match (base, s) {
+        (0, [b'0', b'x' | b'X', c, ..]) if c.is_ascii_hexdigit() => {
+            s = &s[2..];
+            16
+        }
+        (0, [b'0', ..]) => 8,
+        (0, _) => 10,
+        (base, _) => base,
+    };
+    let digits = s
+        .iter()
+        .take_while(|c| (**c as char).is_digit(radix))
+        .count();
+    let (mut value, mut overflow) = (1u64, false);
+    if digits > 0 {
+        value = 0;
+        for &c in &s[..digits] {
+            let digit = (c as char).to_digit(radix).unwrap() as u64;
+            let next = value
+                .checked_mul(radix as u64)
+                .and_then(|v| v.checked_add(digit));
+            overflow |= next.is_none();
+            value = next.unwrap_or(u64::MAX);
+        }
+    }
+    let suffix = &s[digits..];
+    let scaled = match suffix.first() {
+        None if digits == 0 => return Err(None),
+        None => Some(value),
+        Some(_) if !suffixes => return Err(None),
+        Some(&unit) => {
+            let (base, rest): (u64, _) = match &suffix[1..] {
+                [b'i', b'B', rest @ ..] => (1024, rest),
+                [b'B' | b'D', rest @ ..] => (1000, rest),
+                rest => (1024, rest),
+            };
+            if !rest.is_empty() {
+                return Err(None);
+            }
+            let multiplier = match unit {
+                b'c' => Some(1),
+                b'b' => Some(512),
+                b'B' => Some(1024),
+                b'k' | b'K' => base.checked_pow(1),
+                b'M' => base.checked_pow(2),
+                b'G' => base.checked_pow(3),
+                b'T' => base.checked_pow(4),
+                b'P' => base.checked_pow(5),
+                b'E' => base.checked_pow(6),
+                b'Z' => base.checked_pow(7),
+                b'Y' => base.checked_pow(8),
+                _ => return Err(None),
+            };
+            multiplier.and_then(|multiplier| value.checked_mul(multiplier))
+        }
+    };
+    match scaled {
+        Some(value) if !overflow && value <= max => Ok(value),
+        _ => Err(Some(Errno::EOVERFLOW)),
+    }
+}
+
+// Exact names or unambiguous abbreviations of them like argmatch, else its error
+fn match_remove(value: &str) -> Result<Remove, String> {
+    if let Some(&(_, method)) = REMOVE_METHODS.iter().find(|(name, _)| *name == value) {
+        return Ok(method);
+    }
+    let mut matches = REMOVE_METHODS
+        .iter()
+        .filter(|(name, _)| name.starts_with(value));
+    let problem = match (matches.next(), matches.next()) {
+        (Some(&(_, method)), None) => return Ok(method),
+        (Some(_), Some(_)) => "ambiguous",
+        _ => "invalid",
+    };
+    let mut message = format!(
+        "{problem} argument {} for '--remove'\nValid arguments are:",
+        quote(value.as_bytes())
+    );
+    for (name, _) in REMOVE_METHODS {
+        message.push_str(&format!("\n  - '{name}'"));
+    }
+    Err(message)
+}
+
+// Pick `num` passes like GNU's genpattern: whole groups of the table while they fit, a
+// random choice out of the group that doesn't, then random passes spread evenly among
+// the patterns which are shuffled. -1 is a random pass
+fn gen_pattern(num: usize, rng: &mut RandomSource) -> io::Result<Vec<i32>> {
+    let mut dest = vec![0; num];
+    if num == 0 {
+        return Ok(dest);
+    }
+    let (mut p, mut d, mut n, mut random_passes) = (0, 0, num, 0);
+    loop {
+        let k = PATTERNS.get(p).copied().unwrap_or(0);
+        p += 1;
+        if k == 0 {
+            p = 0;
+        } else if k < 0 {
+            let k = -k as usize;
+            if k >= n {
+                random_passes += n;
+                break;
+            }
+            random_passes += k;
+            n -= k;
+        } else if k as usize <= n {
+            let k = k as usize;
+            for (i, &pattern) in PATTERNS[p..p + k].iter().enumerate() {
+                dest[d + i] = pattern;
+            }
+            p += k;
+            d += k;
+            n -= k;
+        } else if n < 2 || 3 * n < k as usize {
+            random_passes += n;
+            break;
+        } else {
+            let mut k = k as usize;
+            while n > 0 {
+                if n == k || (rng.below(k as u64)? as usize) < n {
+                    dest[d] = PATTERNS[p];
+                    d += 1;
+                    n -= 1;
+                }
+                p += 1;
+                k -= 1;
+            }
+            break;
+        }
+    }
+
+    // One random pass first, one last and the others evenly in between, Bresenham's
+    // way. The table starting with random passes, there's always one
+    let mut top = num - random_passes;
+    let slope = random_passes - 1;
+    let mut accum = slope;
+    for n in 0..num {
+        if accum <= slope {
+            accum += num - 1;
+            dest[top] = dest[n];
+            top += 1;
+            dest[n] = -1;
+        } else {
+            let swap = n + rng.below((top - n) as u64)? as usize;
+            dest.swap(n, swap);
+        }
+        accum -= slope;
+    }
+    Ok(dest)
+}
+
+// The 3 bytes of a pattern repeated over the first `size` bytes of `buffer` (which has
+// room for at least 3)
+fn fill_pattern(kind: i32, buffer: &mut [u8], size: usize) {
+    let bits = (kind & 0xfff) as u32;
+    let bits = bits | bits << 12;
+    buffer[..3].copy_from_slice(&[(bits >> 4) as u8, (bits >> 8) as u8, bits as u8]);
+    let mut i = 3;
+    while i <= size / 2 {
+        buffer.copy_within(..i, i);
+        i *= 2;
+    }
+    if i < size {
+        buffer.copy_within(..size - i, i);
+    }
+    if kind & 0x1000 != 0 {
+        for i in (0..size).step_by(SECTOR_SIZE) {
+            buffer[i] ^= 0x80;
+        }
+    }
+}
+
+fn is_periodic(kind: i32) -> bool {
+    if kind <= 0 {
+        return false;
+    }
+    let mut bytes = [0; 3];
+    fill_pattern(kind, &mut bytes, 3);
+    bytes[0] != bytes[1] || bytes[0] != bytes[2]
+}
+
+// gnulib's human_readable of a byte count for -v progress: powers of 1024 like
+// "8.3GiB" with a decimal below 10, rounded down or (`ceiling`) up
+fn human_bytes(n: u64, ceiling: bool) -> String {
+    const BASE: u64 = 1024;
+    const POWERS: &[u8] = b"KMGTPEZY";
+    let (mut amount, mut tenths, mut rounding, mut exponent) = (n, 0, 0, 0);
+    let mut point = None;
+    if BASE <= amount {
+        loop {
+            let r10 = (amount % BASE) * 10 + tenths;
+            let r2 = (r10 % BASE) * 2 + (rounding >> 1);
+            amount /= BASE;
+            tenths = r10 / BASE;
+            rounding = match r2 < BASE {
+                true => (r2 + rounding != 0) as u64,
+                false => 2 + (BASE < r2 + rounding) as u64,
+            };
+            exponent += 1;
+            if amount < BASE || exponent == POWERS.len() {
+                break;
+            }
+        }
+        if amount < 10 {
+            if ceiling && rounding > 0 {
+                tenths += 1;
+                rounding = 0;
+                if tenths == 10 {
+                    amount += 1;
+                    tenths = 0;
+                }
+            }
+            if amount < 10 {
+                point = Some(tenths);
+                (tenths, rounding) = (0, 0);
+            }
+        }
+    }
+    if ceiling && tenths + rounding > 0 {
+        amount += 1;
+        if amount == BASE && exponent < POWERS.len() {
+            exponent += 1;
+            point = Some(0);
+            amount = 1;
+        }
+    }
+    let mut human = amount.to_string();
+    if let Some(tenths) = point {
+        human.push_str(&format!(".{tenths}"));
+    }
+    if exponent > 0 {
+        human.push(POWERS[exponent - 1] as char);
+        human.push('i');
+    }
+    human.push('B');
+    human
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+// Turn O_DIRECT on or off, it's only ever tried
+fn direct_mode(fd: RawFd, enable: bool) {
+    let Ok(flags) = fcntl(fd, FcntlArg::F_GETFL) else {
+        return;
+    };
+    let flags = OFlag::from_bits_truncate(flags);
+    let direct = match enable {
+        true => flags | OFlag::O_DIRECT,
+        false => flags - OFlag::O_DIRECT,
+    };
+    if direct != flags {
+        let _ = fcntl(fd, FcntlArg::F_SETFL(direct));
+    }
+}
+
+// fdatasync, else fsync, else sync(2) when those can't be done on that kind of file
+fn dosync(fd: RawFd, qname: &str) -> Result<(), Errno> {
+    let ignorable = |e: Errno| matches!(e, Errno::EINVAL | Errno::EBADF | Errno::EISDIR);
+    match fdatasync(fd) {
+        Ok(()) => return Ok(()),
+        Err(e) if !ignorable(e) => {
+            eprintln!("shred: {qname}: fdatasync failed: {}", errno_desc(e));
+            return Err(e);
+        }
+        Err(_) => {}
+    }
+    match fsync(fd) {
+        Ok(()) => return Ok(()),
+        Err(e) if !ignorable(e) => {
+            eprintln!("shred: {qname}: fsync failed: {}", errno_desc(e));
+            return Err(e);
+        }
+        Err(_) => {}
+    }
+    nix::unistd::sync();
+    Ok(())
+}
+
+// A buffer of `size` bytes aligned to the page, as O_DIRECT wants
+fn aligned_buffer(storage: &mut Vec<u8>, size: usize) -> &mut [u8] {
+    let page = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()
+        .map_or(4096, |page| page as usize);
+    storage.resize(size + page, 0);
+    let offset = storage.as_ptr().align_offset(page);
+    &mut storage[offset..offset + size]
+}
+
+/// One overwrite of the file with the pattern `kind` (-1 random), pass `k` of `n` for
+/// -v (0 quiet). An unknown size (None) is found out by writing until the end
+#[allow(clippy::too_many_arguments)]
+fn dopass(
+    fd: RawFd,
+    qname: &str,
+    sizep: &mut Option<u64>,
+    kind: i32,
+    rng: &mut RandomSource,
+    k: usize,
+    n: usize,
+) -> Result<Pass, RandomFailure> {
+    let mut size = *sizep;
+    let output_size = match is_periodic(kind) {
+        true => PERIODIC_OUTPUT_SIZE,
+        false => NONPERIODIC_OUTPUT_SIZE,
+    };
+    let fill_size = (output_size + 2) / 3 * 3;
+    let mut storage = Vec::new();
+    let buffer = aligned_buffer(&mut storage, fill_size);
+    let mut write_error = false;
+
+    // Small files aren't worth direct I/O, which may not even take unaligned sizes
+    let mut without_direct = size.is_some_and(|size| 0 < size && size < output_size as u64);
+    if !without_direct {
+        direct_mode(fd, true);
+    }
+    match lseek(fd, 0, Whence::SeekSet) {
+        Ok(0) => {}
+        result => {
+            let e = result.err().unwrap_or(Errno::EINVAL);
+            eprintln!("shred: {qname}: cannot rewind: {}", errno_desc(e));
+            return Ok(Pass::Failed);
+        }
+    }
+
+    // Constant patterns are only set up once
+    let pass_name = match kind {
+        0.. => {
+            let lim = size.map_or(fill_size, |size| (size as usize).min(fill_size));
+            fill_pattern(kind, buffer, lim);
+            format!("{:02x}{:02x}{:02x}", buffer[0], buffer[1], buffer[2])
+        }
+        _ => String::from("random"),
+    };
+    let mut thresh = 0;
+    let mut previous_offset = String::new();
+    if n > 0 {
+        eprintln!("shred: {qname}: pass {k}/{n} ({pass_name})...");
+        thresh = now() + VERBOSE_UPDATE;
+    }
+
+    let mut offset: u64 = 0;
+    let mut current = 0;
+    loop {
+        let mut lim = output_size;
+        if let Some(size) = size.filter(|&size| size - offset.min(size) < output_size as u64) {
+            if size < offset {
+                break;
+            }
+            lim = (size - offset) as usize;
+            if lim == 0 {
+                break;
+            }
+        }
+        if kind < 0 {
+            rng.fill(&mut buffer[..lim]).map_err(RandomFailure)?;
+        }
+        // Partial writes are retried
+        let mut soff = 0;
+        while soff < lim {
+            let error = match write(fd, &buffer[soff..lim]) {
+                Ok(0) => Errno::ENOSPC,
+                Ok(written) => {
+                    soff += written;
+                    continue;
+                }
+                Err(e) => e,
+            };
+            if size.is_none() && error == Errno::ENOSPC {
+                // The end of the file was found
+                size = Some(offset + soff as u64);
+                *sizep = size;
+                break;
+            }
+            // Direct I/O may not be supported at all, or not for that size
+            if !without_direct && error == Errno::EINVAL {
+                direct_mode(fd, false);
+                without_direct = true;
+                continue;
+            }
+            let at = offset + soff as u64;
+            eprintln!(
+                "shred: {qname}: error writing at offset {at}: {}",
+                errno_desc(error)
+            );
+            // shred is often used on bad media before throwing it out, so it doesn't
+            // give up on bad blocks: the rest of the sector is skipped
+            if error == Errno::EIO && size.is_some() && (soff | SECTOR_MASK) < lim {
+                let next = (soff | SECTOR_MASK) + 1;
+                match lseek(fd, (offset + next as u64) as i64, Whence::SeekSet) {
+                    Ok(_) => {
+                        soff = next;
+                        write_error = true;
+                        continue;
+                    }
+                    Err(e) => eprintln!("shred: {qname}: lseek failed: {}", errno_desc(e)),
+                }
+            }
+            return Ok(Pass::Failed);
+        }
+        offset += soff as u64;
+        let done = Some(offset) == size;
+
+        if n > 0 && ((done && !previous_offset.is_empty()) || thresh <= now_updated(&mut current)) {
+            let mut human_offset = human_bytes(offset, false);
+            if done || previous_offset != human_offset {
+                match size {
+                    None => {
+                        eprintln!("shred: {qname}: pass {k}/{n} ({pass_name})...{human_offset}")
+                    }
+                    Some(size) => {
+                        let percent = match size {
+                            0 => 100,
+                            size => (offset as u128 * 100 / size as u128) as u64,
+                        };
+                        let human_size = human_bytes(size, true);
+                        if done {
+                            human_offset = human_size.clone();
+                        }
+                        eprintln!(
+                            "shred: {qname}: pass {k}/{n} ({pass_name})...{human_offset}/\
+                            {human_size} {percent}%"
+                        );
+                    }
+                }
+                previous_offset = human_offset;
+                thresh = current + VERBOSE_UPDATE;
+                // Keep the displayed progress accurate
+                match dosync(fd, qname) {
+                    Ok(()) => {}
+                    Err(Errno::EIO) => write_error = true,
+                    Err(_) => return Ok(Pass::Failed),
+                }
+            }
+        }
+    }
+
+    // Force what was just written to hit the media
+    match dosync(fd, qname) {
+        Ok(()) if write_error => Ok(Pass::WriteError),
+        Ok(()) => Ok(Pass::Done),
+        Err(Errno::EIO) => Ok(Pass::WriteError),
+        Err(_) => Ok(Pass::Failed),
+    }
+}
+
+// The time now, kept in `current` for the next progress report's threshold
+fn now_updated(current: &mut u64) -> u64 {
+    *current = now();
+    *current
+}
+
+/// All the passes over an open file, returns whether they went well
+fn do_wipefd(
+    fd: RawFd,
+    qname: &str,
+    rng: &mut RandomSource,
+    options: &Options,
+) -> Result<bool, RandomFailure> {
+    let n = match options.verbose {
+        true => options.iterations + options.zero as usize,
+        false => 0,
+    };
+    let stat = match fstat(fd) {
+        Ok(stat) => stat,
+        Err(e) => {
+            eprintln!("shred: {qname}: fstat failed: {}", errno_desc(e));
+            return Ok(false);
+        }
+    };
+    let kind = SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT;
+    let is_regular = kind == SFlag::S_IFREG;
+    // Can't be rewound, shredding would go on forever
+    if (kind == SFlag::S_IFCHR && isatty(fd).unwrap_or(false))
+        || kind == SFlag::S_IFIFO
+        || kind == SFlag::S_IFSOCK
+    {
+        eprintln!("shred: {qname}: invalid file type");
+        return Ok(false);
+    }
+    if is_regular && stat.st_size < 0 {
+        eprintln!("shred: {qname}: file has negative size");
+        return Ok(false);
+    }
+    let block_size = match stat.st_blksize {
+        size @ 1..=0x2000_0000_0000_0000 => size as u64,
+        _ => 512,
+    };
+
+    // Files smaller than a block first get a round over just their bytes
+    let mut initial_size = 0;
+    let mut size = match options.size {
+        None if is_regular => {
+            let mut size = stat.st_size as u64;
+            if !options.exact {
+                // Round up to the block size to clear the slack space too
+                if size > 0 && size < block_size {
+                    initial_size = size;
+                }
+                let remainder = size % block_size;
+                if remainder != 0 {
+                    size = size
+                        .saturating_add(block_size - remainder)
+                        .min(i64::MAX as u64);
+                }
+            }
+            Some(size)
+        }
+        // A device's size, if lseek tells
+        None => match lseek(fd, 0, Whence::SeekEnd) {
+            Ok(size) if size > 0 => Some(size as u64),
+            _ => None,
+        },
+        Some(size) => {
+            if is_regular && (stat.st_size as u64) < block_size.min(size) {
+                initial_size = stat.st_size as u64;
+            }
+            Some(size)
+        }
+    };
+
+    let passes = gen_pattern(options.iterations, rng).map_err(RandomFailure)?;
+    let mut ok = true;
+    loop {
+        let (mut pass_size, pn) = if initial_size > 0 {
+            (Some(std::mem::take(&mut initial_size)), 0)
+        } else if size != Some(0) {
+            (size.replace(0), n)
+        } else {
+            break;
+        };
+        for i in 0..options.iterations + options.zero as usize {
+            let kind = passes.get(i).copied().unwrap_or(0);
+            match dopass(fd, qname, &mut pass_size, kind, rng, i + 1, pn)? {
+                Pass::Done => {}
+                Pass::WriteError => ok = false,
+                Pass::Failed => return Ok(false),
+            }
+        }
+    }
+
+    // Let go of the data, which may well allocate new blocks rather than free the
+    // overwritten ones depending on the file system
+    if options.remove.is_some() {
+        if let Err(e) = ftruncate(fd, 0) {
+            if is_regular {
+                eprintln!("shred: {qname}: error truncating: {}", errno_desc(e));
+                return Ok(false);
+            }
+        }
+    }
+    Ok(ok)
+}
+
+// The next name of `len` characters of NAME_SET, false once they're all used
+fn next_name(name: &mut [u8]) -> bool {
+    for c in name.iter_mut().rev() {
+        let at = NAME_SET.iter().position(|n| n == c).unwrap_or(0);
+        if let Some(&next) = NAME_SET.get(at + 1) {
+            *c = next;
+            return true;
+        }
+        *c = NAME_SET[0];
+    }
+    false
+}
+
+// rename(2) that won't replace an existing `new`, EEXIST then
+fn rename_noreplace(old: &[u8], new: &[u8]) -> Result<(), Errno> {
+    let old = CString::new(old).map_err(|_| Errno::EINVAL)?;
+    let new = CString::new(new).map_err(|_| Errno::EINVAL)?;
+    let (cwd, noreplace) = (libc::AT_FDCWD, libc::RENAME_NOREPLACE);
+    let renamed = unsafe { libc::renameat2(cwd, old.as_ptr(), cwd, new.as_ptr(), noreplace) };
+    match Errno::result(renamed) {
+        // File systems without RENAME_NOREPLACE
+        Err(Errno::EINVAL | Errno::ENOSYS | Errno::ENOTSUP) => {
+            if fs::symlink_metadata(OsStr::from_bytes(new.as_bytes())).is_ok() {
+                return Err(Errno::EEXIST);
+            }
+            Errno::result(unsafe { libc::rename(old.as_ptr(), new.as_ptr()) }).map(drop)
+        }
+        result => result.map(drop),
+    }
+}
+
+// gnulib's dir_name: everything before the last component, "." when there's nothing
+fn dir_name(name: &[u8]) -> Vec<u8> {
+    let root = name.starts_with(b"/") as usize;
+    let mut len = name.len() - last_component(name).len();
+    while len > root && name[len - 1] == b'/' {
+        len -= 1;
+    }
+    match len {
+        0 => b".".to_vec(),
+        len => name[..len].to_vec(),
+    }
+}
+
+/// -u after the passes: rename the file to shorter and shorter names (of zeros, or the
+/// next free name of that length) so its name is gone too, then unlink it
+fn wipename(name: &[u8], qname: &str, options: &Options) -> bool {
+    let mut old = name.to_vec();
+    let base = name.len() - last_component(name).len();
+    let dir = dir_name(name);
+    let qdir = shell_quote(&dir, false);
+    let mut ok = true;
+    let dir_fd = match options.remove {
+        Some(Remove::WipeSync) => {
+            let flags = libc::O_DIRECTORY | libc::O_NOCTTY | libc::O_NONBLOCK;
+            let mut open = OpenOptions::new();
+            open.read(true).custom_flags(flags);
+            open.open(OsStr::from_bytes(&dir))
+                .ok()
+                .map(|dir| dir.into_raw_fd())
+        }
+        _ => None,
+    };
+    if options.verbose {
+        eprintln!("shred: {qname}: removing");
+    }
+
+    if options.remove != Some(Remove::Unlink) {
+        let base_len = name[base..]
+            .iter()
+            .rposition(|&c| c != b'/')
+            .map_or(0, |i| i + 1);
+        let mut first = true;
+        for len in (1..=base_len).rev() {
+            let mut new = name[..base].to_vec();
+            new.resize(base + len, NAME_SET[0]);
+            let renamed = loop {
+                match rename_noreplace(&old, &new) {
+                    Err(Errno::EEXIST) if next_name(&mut new[base..]) => {}
+                    result => break result.is_ok(),
+                }
+            };
+            if !renamed {
+                continue;
+            }
+            if let Some(dir_fd) = dir_fd {
+                ok &= dosync(dir_fd, &qdir).is_ok();
+            }
+            if options.verbose {
+                // Only the name given needs quoting, the others were picked here
+                let new_name = String::from_utf8_lossy(&new);
+                match first {
+                    true => eprintln!("shred: {qname}: renamed to {new_name}"),
+                    false => {
+                        let old_name = String::from_utf8_lossy(&old);
+                        eprintln!("shred: {old_name}: renamed to {new_name}");
+                    }
+                }
+                first = false;
+            }
+            old = new;
+        }
+    }
+
+    match unlink(old.as_slice()) {
+        Ok(()) if options.verbose => eprintln!("shred: {qname}: removed"),
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("shred: {qname}: failed to remove: {}", errno_desc(e));
+            ok = false;
+        }
+    }
+    if let Some(dir_fd) = dir_fd {
+        ok &= dosync(dir_fd, &qdir).is_ok();
+        if let Err(e) = close(dir_fd) {
+            eprintln!("shred: {qdir}: failed to close: {}", errno_desc(e));
+            ok = false;
+        }
+    }
+    ok
+}
+
+/// Shred a named file, and remove it with -u
+fn wipefile(
+    name: &[u8],
+    qname: &str,
+    rng: &mut RandomSource,
+    options: &Options,
+) -> Result<bool, RandomFailure> {
+    let path = OsStr::from_bytes(name);
+    let open = || {
+        let mut open = OpenOptions::new();
+        open.write(true).custom_flags(libc::O_NOCTTY);
+        open.open(path)
+    };
+    let mut file = open();
+    if let Err(e) = &file {
+        let writable = fs::Permissions::from_mode(0o200);
+        if e.kind() == ErrorKind::PermissionDenied
+            && options.force
+            && fs::set_permissions(path, writable).is_ok()
+        {
+            file = open();
+        }
+    }
+    let fd = match file {
+        Ok(file) => file.into_raw_fd(),
+        Err(e) => {
+            eprintln!(
+                "shred: {qname}: failed to open for writing: {}",
+                strerror(&e)
+            );
+            return Ok(false);
+        }
+    };
+    let wiped = do_wipefd(fd, qname, rng, options);
+    let closed = close(fd);
+    let mut ok = wiped?;
+    if let Err(e) = closed {
+        eprintln!("shred: {qname}: failed to close: {}", errno_desc(e));
+        ok = false;
+    }
+    if ok && options.remove.is_some() {
+        ok = wipename(name, qname, options);
+    }
+    Ok(ok)
+}
+
+/// `shred -`: standard output, which can't be in append mode
+fn wipe_stdout(
+    qname: &str,
+    rng: &mut RandomSource,
+    options: &Options,
+) -> Result<bool, RandomFailure> {
+    const STDOUT_FD: RawFd = 1;
+    let flags = match fcntl(STDOUT_FD, FcntlArg::F_GETFL) {
+        Ok(flags) => OFlag::from_bits_truncate(flags),
+        Err(e) => {
+            eprintln!("shred: {qname}: fcntl failed: {}", errno_desc(e));
+            return Ok(false);
+        }
+    };
+    if flags.contains(OFlag::O_APPEND) {
+        eprintln!("shred: {qname}: cannot shred append-only file descriptor");
+        return Ok(false);
+    }
+    do_wipefd(STDOUT_FD, qname, rng, options)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+    let try_help = "Try 'shred --help' for more information.";
+    let iterations = match &args.iterations {
+        Some(n) => match parse_number(n.as_bytes(), 10, false, usize::MAX as u64 / 4) {
+            Ok(n) => n as usize,
+            Err(e) => {
+                let reason = e
+                    .map(|e| format!(": {}", errno_desc(e)))
+                    .unwrap_or_default();
+                eprintln!(
+                    "shred: invalid number of passes: {}{reason}",
+                    quote(n.as_bytes())
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        None => 3,
+    };
+    if args.random_source.windows(2).any(|pair| pair[0] != pair[1]) {
+        eprintln!("shred: multiple random sources specified");
+        return ExitCode::FAILURE;
+    }
+    let remove = match &args.remove {
+        Some(Some(how)) => match match_remove(how) {
+            Ok(method) => Some(method),
+            Err(message) => {
+                eprintln!("shred: {message}\n{try_help}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(None) => Some(Remove::WipeSync),
+        None => args.unlink.then_some(Remove::WipeSync),
+    };
+    let size = match &args.size {
+        Some(size) => match parse_number(size.as_bytes(), 0, true, i64::MAX as u64) {
+            Ok(size) => Some(size),
+            Err(e) => {
+                let reason = e
+                    .map(|e| format!(": {}", errno_desc(e)))
+                    .unwrap_or_default();
+                eprintln!(
+                    "shred: invalid file size: {}{reason}",
+                    quote(size.as_bytes())
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    if args.files.is_empty() {
+        eprintln!("shred: missing file operand\n{try_help}");
+        return ExitCode::FAILURE;
+    }
+    let options = Options {
+        force: args.force,
+        iterations,
+        size,
+        remove,
+        verbose: args.verbose,
+        exact: args.exact,
+        zero: args.zero,
+    };
+
+    let source = args.random_source.first();
+    let mut rng = match RandomSource::open(source.map(PathBuf::as_path)) {
+        Ok(rng) => rng,
+        Err(e) => {
+            let name = source.map_or("getrandom".into(), |source| {
+                shell_quote(source.as_os_str().as_bytes(), false)
+            });
+            eprintln!("shred: {name}: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut ok = true;
+    for file in &args.files {
+        let name = file.as_bytes();
+        let qname = shell_quote(name, false);
+        let wiped = match name {
+            b"-" => wipe_stdout(&qname, &mut rng, &options),
+            _ => wipefile(name, &qname, &mut rng, &options),
+        };
+        match wiped {
+            Ok(wiped) => ok &= wiped,
+            // Like GNU's randread, a failing random source ends it all
+            Err(RandomFailure(e)) => {
+                let name = source.map_or("getrandom".into(), |source| {
+                    quote(source.as_os_str().as_bytes())
+                });
+                match e.kind() {
+                    ErrorKind::UnexpectedEof => eprintln!("shred: {name}: end of file"),
+                    _ => eprintln!("shred: {name}: read error: {}", strerror(&e)),
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * shuf - generate random permutations
+ *
+ * Draws random numbers as GNU shuf does, so the same --random-source file gives the
+ * same output: lines are permuted with `RandomSource::permutation`, or with -n and
+ * input that's big or of unknown size, reservoir sampled first so memory stays
+ * bounded by COUNT. -i ranges are permuted without materializing the range when
+ * COUNT is far smaller.
+ */
+
+use crate::random::RandomSource;
+use crate::{quote, reset_sigpipe, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use nix::sys::stat::{fstat, SFlag};
+use nix::unistd::{lseek, Whence};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "shuf", version, long_about = None)]
+#[command(about = "Write a random permutation of the input lines to standard output")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Treat each ARG as an input line
+    #[clap(long, short, action)]
+    echo: bool,
+    /// Treat each number LO through HI as an input line
+    #[clap(long, short, value_name = "LO-HI")]
+    input_range: Option<String>,
+    /// Output at most COUNT lines
+    #[clap(long, short = 'n', value_name = "COUNT")]
+    head_count: Option<String>,
+    /// Write result to FILE instead of standard output
+    #[clap(long, short, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Get random bytes from FILE
+    #[clap(long, value_name = "FILE")]
+    random_source: Option<PathBuf>,
+    /// Output lines can be repeated
+    #[clap(long, short, action)]
+    repeat: bool,
+    /// Line delimiter is NUL, not newline
+    #[clap(long, short, action)]
+    zero_terminated: bool,
+    /// Input lines with -e, otherwise the single input FILE (stdin by default)
+    #[clap(action = ArgAction::Append)]
+    args: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+// Regular files up to this size are read whole even with -n
+const RESERVOIR_MIN_INPUT: u64 = 8192 * 1024;
+
+fn parse_count(count: &str) -> Option<u64> {
+    count.parse().ok()
+}
+
+// Bounds are integers, but accept an exact float notation like `1e9` too
+fn parse_bound(bound: &str) -> Option<u64> {
+    bound.parse().ok().or_else(|| {
+        let value: f64 = bound.parse().ok()?;
+        let exact = value >= 0.0 && value.fract() == 0.0 && value < u64::MAX as f64;
+        exact.then_some(value as u64)
+    })
+}
+
+fn parse_range(range: &str) -> Option<(u64, u64)> {
+    let (lo, hi) = range.split_once('-')?;
+    let (lo, hi) = (parse_bound(lo)?, parse_bound(hi)?);
+    // An empty range (HI = LO - 1) is allowed
+    (hi.checked_add(1)? >= lo).then_some((lo, hi))
+}
+
+// Read the next line into `line` without its delimiter, false at the end of input
+fn read_line(reader: &mut dyn BufRead, delim: u8, line: &mut Vec<u8>) -> io::Result<bool> {
+    line.clear();
+    if reader.read_until(delim, line)? == 0 {
+        return Ok(false);
+    }
+    if line.last() == Some(&delim) {
+        line.pop();
+    }
+    Ok(true)
+}
+
+/// Reservoir sample of at most `count` lines, `count` not being zero. As in GNU,
+/// the fate of each line past the first `count` is drawn before reading it
+fn sample_lines(
+    reader: &mut dyn BufRead,
+    delim: u8,
+    count: u64,
+    rng: &mut RandomSource,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut reservoir = Vec::new();
+    let mut line = Vec::new();
+    while (reservoir.len() as u64) < count {
+        if !read_line(reader, delim, &mut line)? {
+            return Ok(reservoir);
+        }
+        reservoir.push(mem::take(&mut line));
+    }
+    let mut seen = count;
+    loop {
+        let j = rng.below(seen + 1)?;
+        if !read_line(reader, delim, &mut line)? {
+            return Ok(reservoir);
+        }
+        if j < count {
+            mem::swap(&mut reservoir[j as usize], &mut line);
+        }
+        seen += 1;
+    }
+}
+
+fn read_lines(reader: &mut dyn BufRead, delim: u8) -> io::Result<Vec<Vec<u8>>> {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    while read_line(reader, delim, &mut line)? {
+        lines.push(mem::take(&mut line));
+    }
+    Ok(lines)
+}
+
+// What's left to read of `fd` when it's a regular file
+fn input_size(fd: RawFd) -> Option<u64> {
+    let stat = fstat(fd).ok()?;
+    if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFREG {
+        return None;
+    }
+    let offset = lseek(fd, 0, Whence::SeekCur).ok()?;
+    Some(stat.st_size.saturating_sub(offset).max(0) as u64)
+}
+
+fn run(args: Cli) -> Result<(), String> {
+    let count = match &args.head_count {
+        Some(count) => {
+            Some(parse_count(count).ok_or_else(|| format!("invalid line count: '{count}'"))?)
+        }
+        None => None,
+    };
+    let range = match &args.input_range {
+        Some(_) if args.echo => return Err("cannot combine -e and -i options".to_string()),
+        Some(range) => {
+            Some(parse_range(range).ok_or_else(|| format!("invalid input range: '{range}'"))?)
+        }
+        None => None,
+    };
+    if range.is_some() || !args.echo {
+        let extra = if range.is_some() {
+            args.args.first()
+        } else {
+            args.args.get(1)
+        };
+        if let Some(extra) = extra {
+            return Err(format!("extra operand '{}'", extra.to_string_lossy()));
+        }
+    }
+    let delim = if args.zero_terminated { 0 } else { b'\n' };
+    let random_source = args.random_source.as_deref();
+    let mut rng = RandomSource::open(random_source).map_err(|e| {
+        let name = random_source.unwrap_or("getrandom".as_ref());
+        format!("{}: {}", name.display(), strerror(&e))
+    })?;
+    let rng_error = |e: io::Error| match random_source {
+        Some(name) => format!("{}: {}", quote(name.as_os_str().as_bytes()), strerror(&e)),
+        None => strerror(&e),
+    };
+
+    // Numbers from LO, or lines, `n` of them
+    enum Lines {
+        Range(u64),
+        Text(Vec<Vec<u8>>),
+    }
+    let head = count.unwrap_or(u64::MAX);
+    let (lines, n) = match range {
+        // -n 0 never needs any input (nor randomness)
+        _ if head == 0 => (Lines::Text(Vec::new()), 0),
+        Some((lo, hi)) => (Lines::Range(lo), hi + 1 - lo),
+        None if args.echo => {
+            let lines: Vec<_> = args.args.into_iter().map(OsStringExt::into_vec).collect();
+            let n = lines.len() as u64;
+            (Lines::Text(lines), n)
+        }
+        None => {
+            let file = args
+                .args
+                .first()
+                .cloned()
+                .unwrap_or_else(|| OsString::from("-"));
+            let (mut reader, size): (Box<dyn BufRead>, _) = match file.to_str() {
+                Some("-") => {
+                    let stdin = io::stdin().lock();
+                    let size = input_size(stdin.as_raw_fd());
+                    (Box::new(BufReader::with_capacity(IO_BUFSIZE, stdin)), size)
+                }
+                _ => {
+                    let input = File::open(&file)
+                        .map_err(|e| format!("{}: {}", file.to_string_lossy(), strerror(&e)))?;
+                    let size = input_size(input.as_raw_fd());
+                    (Box::new(BufReader::with_capacity(IO_BUFSIZE, input)), size)
+                }
+            };
+            let read_error = |e: io::Error| {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    rng_error(e)
+                } else {
+                    format!("read error: {}", strerror(&e))
+                }
+            };
+            // Like GNU, only sample what's big or of unknown size, so the draws match
+            let sample = !args.repeat
+                && count.is_some()
+                && size.map_or(true, |size| size > RESERVOIR_MIN_INPUT);
+            let lines = match sample {
+                true => sample_lines(reader.as_mut(), delim, head, &mut rng),
+                false => read_lines(reader.as_mut(), delim),
+            };
+            let lines = lines.map_err(read_error)?;
+            let n = lines.len() as u64;
+            (Lines::Text(lines), n)
+        }
+    };
+    // With -r, -n is the count of lines to pick no matter how many there are
+    let head = if args.repeat { head } else { head.min(n) };
+    let permutation = match args.repeat {
+        true => Vec::new(),
+        false => rng.permutation(head, n).map_err(rng_error)?,
+    };
+
+    // Only open (and possibly truncate) the output once the input has been consumed
+    let (name, file) = match &args.output {
+        Some(path) => (
+            path.display().to_string(),
+            File::create(path).map_err(|e| format!("{}: {}", path.display(), strerror(&e)))?,
+        ),
+        None => (
+            String::from("standard output"),
+            stdout_file().map_err(|e| strerror(&e))?,
+        ),
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, file);
+    let write_error = |e: io::Error| format!("write error: {name}: {}", strerror(&e));
+    let mut write_line = |lines: &Lines, i: u64| match lines {
+        Lines::Range(lo) => write!(out, "{}{}", lo + i, delim as char),
+        Lines::Text(lines) => {
+            out.write_all(&lines[i as usize])?;
+            out.write_all(&[delim])
+        }
+    };
+
+    if args.repeat {
+        if head > 0 && n == 0 {
+            return Err("no lines to repeat".to_string());
+        }
+        let mut emitted = 0;
+        while emitted < head {
+            let pick = rng.below(n).map_err(rng_error)?;
+            write_line(&lines, pick).map_err(write_error)?;
+            emitted += 1;
+        }
+    } else {
+        for i in permutation {
+            write_line(&lines, i).map_err(write_error)?;
+        }
+    }
+    out.flush().map_err(write_error)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args = Cli::parse_from(args);
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("shuf: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,1425 @@
+/*
+ * sort - sort lines of text files
+ *
+ * Collation is byte-wise, ie. always LC_ALL=C semantics regardless of the locale:
+ * -f only folds ASCII, -d/-i use the C character classes, -M only knows the english
+ * month abbreviations and -n/-h never accept thousands separators.
+ *
+ * Input lines are collected into a chunk of up to `-S` bytes which gets sorted in
+ * memory (split across `--parallel` threads). When the input doesn't fit, every full
+ * chunk is spilled as a sorted run into a temporary file under `-T` and the runs are
+ * k-way merged at the end, at most `--batch-size` of them at a time. As in GNU sort, a
+ * fatal signal unlinks the runs before killing the process.
+ *
+ * Key handling (`begfield`/`limfield`, option inheritance, last-resort comparison)
+ * follows GNU sort.c so `-k` specs behave identically.
+ */
+
+use crate::{errno_desc, reset_sigpipe, shell_quote, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use std::cmp::{min, Ordering};
+use std::collections::binary_heap::{BinaryHeap, PeekMut};
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::mem::size_of;
+use std::os::fd::FromRawFd;
+use std::os::linux::fs::MetadataExt;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Mutex, Once, PoisonError};
+use std::thread;
+
+#[derive(Debug, Parser)]
+#[command(name = "sort", version, long_about = None)]
+#[command(about = "Write sorted concatenation of all FILE(s) to standard output")]
+#[command(next_line_help = true, disable_help_flag = true, disable_version_flag = true)]
+struct Cli {
+    /// Ignore leading blanks
+    #[clap(long, short = 'b', action)]
+    ignore_leading_blanks: bool,
+    /// Consider only blanks and alphanumeric characters
+    #[clap(long, short = 'd', action)]
+    dictionary_order: bool,
+    /// Fold lower case to upper case characters
+    #[clap(long, short = 'f', action)]
+    ignore_case: bool,
+    /// Compare according to general numerical value
+    #[clap(long, short = 'g', action)]
+    general_numeric_sort: bool,
+    /// Consider only printable characters
+    #[clap(long, short = 'i', action)]
+    ignore_nonprinting: bool,
+    /// Compare (unknown) < 'JAN' < ... < 'DEC'
+    #[clap(long, short = 'M', action)]
+    month_sort: bool,
+    /// Compare human readable numbers (e.g., 2K 1G)
+    #[clap(long, short = 'h', action)]
+    human_numeric_sort: bool,
+    /// Compare according to string numerical value
+    #[clap(long, short = 'n', action)]
+    numeric_sort: bool,
+    /// Reverse the result of comparisons
+    #[clap(long, short = 'r', action)]
+    reverse: bool,
+    /// Natural sort of (version) numbers within text
+    #[clap(long, short = 'V', action)]
+    version_sort: bool,
+    /// Check for sorted input; do not sort
+    #[clap(long, short = 'c', action)]
+    check: bool,
+    /// Like -c, but do not report first bad line
+    #[clap(short = 'C', action)]
+    check_silent: bool,
+    /// Sort via a key; KEYDEF gives location and type
+    #[clap(long, short = 'k', value_name = "KEYDEF", action = ArgAction::Append)]
+    key: Vec<String>,
+    /// Merge already sorted files; do not sort
+    #[clap(long, short = 'm', action)]
+    merge: bool,
+    /// Write result to FILE instead of standard output
+    #[clap(long, short = 'o', value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Stabilize sort by disabling last-resort comparison
+    #[clap(long, short = 's', action)]
+    stable: bool,
+    /// Use SIZE for main memory buffer
+    #[clap(long, short = 'S', value_name = "SIZE")]
+    buffer_size: Option<String>,
+    /// Use SEP instead of non-blank to blank transition
+    #[clap(long, short = 't', value_name = "SEP")]
+    field_separator: Option<OsString>,
+    /// Use DIR for temporaries, not $TMPDIR or /tmp
+    #[clap(long, short = 'T', value_name = "DIR", action = ArgAction::Append)]
+    temporary_directory: Vec<PathBuf>,
+    /// Change the number of sorts run concurrently to N
+    #[clap(long, value_name = "N")]
+    parallel: Option<usize>,
+    /// Merge at most NMERGE inputs at once; for more use temp files
+    #[clap(long, value_name = "NMERGE", default_value_t = 16)]
+    batch_size: usize,
+    /// Output only the first of an equal run
+    #[clap(long, short = 'u', action)]
+    unique: bool,
+    /// Line delimiter is NUL, not newline
+    #[clap(long, short = 'z', action)]
+    zero_terminated: bool,
+    /// Print help
+    #[clap(long, action = ArgAction::Help)]
+    help: Option<bool>,
+    /// Print version
+    #[clap(long, action = ArgAction::Version)]
+    version: Option<bool>,
+    /// Files to sort, stdin by default
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+// Chunks smaller than this aren't worth spawning threads for
+const PARALLEL_MIN_LINES: usize = 1 << 16;
+const MONTHS: [&[u8]; 12] = [
+    b"JAN", b"FEB", b"MAR", b"APR", b"MAY", b"JUN", b"JUL", b"AUG", b"SEP", b"OCT", b"NOV", b"DEC",
+];
+
+fn is_blank(c: u8) -> bool {
+    // GNU's `field_sep`, ie. isblank() plus newline
+    c == b' ' || c == b'\t' || c == b'\n'
+}
+
+fn skip_blanks(s: &[u8]) -> &[u8] {
+    let n = s.iter().take_while(|&&c| is_blank(c)).count();
+    &s[n..]
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Text,
+    Numeric,
+    GeneralNumeric,
+    HumanNumeric,
+    Month,
+    Version,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct KeyOpts {
+    skip_start_blanks: bool,
+    skip_end_blanks: bool,
+    dictionary: bool,
+    fold_case: bool,
+    ignore_nonprinting: bool,
+    mode: Mode,
+    reverse: bool,
+}
+
+impl KeyOpts {
+    /// GNU's `default_key_compare`, reversing alone doesn't count as an option
+    fn is_default(&self) -> bool {
+        KeyOpts {
+            reverse: false,
+            ..*self
+        } == KeyOpts::default()
+    }
+
+    /// Apply a single option letter, `end` tells which side of a KEYDEF `b` is on
+    fn set(&mut self, flag: char, end: bool) -> Result<(), String> {
+        let mode = match flag {
+            'g' => Mode::GeneralNumeric,
+            'h' => Mode::HumanNumeric,
+            'M' => Mode::Month,
+            'n' => Mode::Numeric,
+            'V' => Mode::Version,
+            _ => {
+                match flag {
+                    'b' if end => self.skip_end_blanks = true,
+                    'b' => self.skip_start_blanks = true,
+                    'd' => self.dictionary = true,
+                    'f' => self.fold_case = true,
+                    'i' => self.ignore_nonprinting = true,
+                    'r' => self.reverse = true,
+                    _ => return Err(format!("invalid option '{flag}'")),
+                }
+                return Ok(());
+            }
+        };
+        if self.mode != Mode::Text && self.mode != mode {
+            return Err(format!(
+                "options '-{}{flag}' are incompatible",
+                self.mode_flag()
+            ));
+        }
+        self.mode = mode;
+        Ok(())
+    }
+
+    fn mode_flag(&self) -> char {
+        match self.mode {
+            Mode::Text => ' ',
+            Mode::Numeric => 'n',
+            Mode::GeneralNumeric => 'g',
+            Mode::HumanNumeric => 'h',
+            Mode::Month => 'M',
+            Mode::Version => 'V',
+        }
+    }
+}
+
+/// A parsed KEYDEF, fields and characters are stored zero-based except for `eword`
+/// which counts fields (`None` means end of line) and `echar` where 0 means end of field
+#[derive(Clone, Debug, Default)]
+struct Key {
+    sword: usize,
+    schar: usize,
+    eword: Option<usize>,
+    echar: usize,
+    opts: KeyOpts,
+}
+
+fn split_digits(s: &str) -> (Option<usize>, &str) {
+    let n = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    (s[..n].parse().ok(), &s[n..])
+}
+
+impl Key {
+    fn parse(spec: &str, global: &KeyOpts) -> Result<Key, String> {
+        let bad = |msg: &str| format!("{msg}: invalid field specification '{spec}'");
+
+        let mut key = Key::default();
+        let (start, end) = match spec.split_once(',') {
+            Some((start, end)) => (start, Some(end)),
+            None => (spec, None),
+        };
+
+        let (field, mut rest) = split_digits(start);
+        key.sword = match field {
+            None => return Err(bad("invalid number at field start")),
+            Some(0) => return Err(bad("field number is zero")),
+            Some(n) => n - 1,
+        };
+        if let Some(chars) = rest.strip_prefix('.') {
+            let (offset, tail) = split_digits(chars);
+            key.schar = match offset {
+                None => return Err(bad("invalid number after '.'")),
+                Some(0) => return Err(bad("character offset is zero")),
+                Some(n) => n - 1,
+            };
+            rest = tail;
+        }
+        for flag in rest.chars() {
+            key.opts.set(flag, false).map_err(|_| bad("stray character in field spec"))?;
+        }
+
+        if let Some(end) = end {
+            let (field, mut rest) = split_digits(end);
+            key.eword = match field {
+                None => return Err(bad("invalid number after ','")),
+                Some(0) => return Err(bad("field number is zero")),
+                n => n,
+            };
+            if let Some(chars) = rest.strip_prefix('.') {
+                let (offset, tail) = split_digits(chars);
+                key.echar = offset.ok_or_else(|| bad("invalid number after '.'"))?;
+                rest = tail;
+            }
+            for flag in rest.chars() {
+                key.opts.set(flag, true).map_err(|_| bad("stray character in field spec"))?;
+            }
+        }
+
+        // Keys without any ordering options of their own inherit the global ones
+        if key.opts.is_default() && !key.opts.reverse {
+            key.opts = *global;
+        }
+        Ok(key)
+    }
+
+    fn begfield(&self, line: &[u8], tab: Option<u8>) -> usize {
+        let lim = line.len();
+        let mut ptr = 0;
+        for _ in 0..self.sword {
+            if ptr >= lim {
+                break;
+            }
+            match tab {
+                Some(tab) => {
+                    while ptr < lim && line[ptr] != tab {
+                        ptr += 1;
+                    }
+                    if ptr < lim {
+                        ptr += 1;
+                    }
+                }
+                None => {
+                    while ptr < lim && is_blank(line[ptr]) {
+                        ptr += 1;
+                    }
+                    while ptr < lim && !is_blank(line[ptr]) {
+                        ptr += 1;
+                    }
+                }
+            }
+        }
+        if self.opts.skip_start_blanks {
+            while ptr < lim && is_blank(line[ptr]) {
+                ptr += 1;
+            }
+        }
+        min(lim, ptr + self.schar)
+    }
+
+    fn limfield(&self, line: &[u8], tab: Option<u8>) -> usize {
+        let lim = line.len();
+        let Some(eword) = self.eword else {
+            return lim;
+        };
+        let echar = self.echar;
+        // Without a character offset the whole end field is included
+        let mut remaining = if echar == 0 { eword } else { eword - 1 };
+        let mut ptr = 0;
+        while ptr < lim && remaining > 0 {
+            remaining -= 1;
+            match tab {
+                Some(tab) => {
+                    while ptr < lim && line[ptr] != tab {
+                        ptr += 1;
+                    }
+                    if ptr < lim && (remaining > 0 || echar != 0) {
+                        ptr += 1;
+                    }
+                }
+                None => {
+                    while ptr < lim && is_blank(line[ptr]) {
+                        ptr += 1;
+                    }
+                    while ptr < lim && !is_blank(line[ptr]) {
+                        ptr += 1;
+                    }
+                }
+            }
+        }
+        if echar != 0 {
+            // GNU keeps narrowing `lim` to the end of this field under POSIX_UNSPECIFIED,
+            // so the offset may run into the following fields
+            if self.opts.skip_end_blanks {
+                while ptr < lim && is_blank(line[ptr]) {
+                    ptr += 1;
+                }
+            }
+            ptr = min(lim, ptr + echar);
+        }
+        ptr
+    }
+
+    fn extract<'a>(&self, line: &'a [u8], tab: Option<u8>) -> &'a [u8] {
+        let beg = self.begfield(line, tab);
+        let lim = self.limfield(line, tab);
+        &line[beg..lim.max(beg)]
+    }
+}
+
+/// Leading `-`, integer digits without leading zeros and fraction digits without
+/// trailing zeros; a negative zero is reported as positive
+fn split_number(s: &[u8]) -> (bool, &[u8], &[u8]) {
+    let s = skip_blanks(s);
+    let (negative, s) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    let digits = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    let zeros = s[..digits].iter().take_while(|&&c| c == b'0').count();
+    let int = &s[zeros..digits];
+    let mut frac: &[u8] = &[];
+    if s.get(digits) == Some(&b'.') {
+        let tail = &s[digits + 1..];
+        let n = tail.iter().take_while(|c| c.is_ascii_digit()).count();
+        let trailing = tail[..n].iter().rev().take_while(|&&c| c == b'0').count();
+        frac = &tail[..n - trailing];
+    }
+    let negative = negative && !(int.is_empty() && frac.is_empty());
+    (negative, int, frac)
+}
+
+fn numcompare(a: &[u8], b: &[u8]) -> Ordering {
+    let (aneg, aint, afrac) = split_number(a);
+    let (bneg, bint, bfrac) = split_number(b);
+    match (aneg, bneg) {
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+        (negative, _) => {
+            let ord = aint
+                .len()
+                .cmp(&bint.len())
+                .then_with(|| aint.cmp(bint))
+                .then_with(|| afrac.cmp(bfrac));
+            if negative {
+                ord.reverse()
+            } else {
+                ord
+            }
+        }
+    }
+}
+
+/// Magnitude of the SI suffix following a nonzero number, negated for negative numbers
+fn unit_order(s: &[u8]) -> i32 {
+    let s = skip_blanks(s);
+    let (negative, s) = match s.first() {
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    let mut n = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    if s.get(n) == Some(&b'.') {
+        n += 1 + s[n + 1..].iter().take_while(|c| c.is_ascii_digit()).count();
+    }
+    if !s[..n].iter().any(|c| (b'1'..=b'9').contains(c)) {
+        return 0;
+    }
+    let order = match s.get(n) {
+        Some(b'k') | Some(b'K') => 1,
+        Some(&c) => b"MGTPEZYRQ".iter().position(|&u| u == c).map_or(0, |i| i as i32 + 2),
+        None => 0,
+    };
+    if negative {
+        -order
+    } else {
+        order
+    }
+}
+
+fn human_numcompare(a: &[u8], b: &[u8]) -> Ordering {
+    unit_order(a)
+        .cmp(&unit_order(b))
+        .then_with(|| numcompare(a, b))
+}
+
+/// Longest prefix which strtod(3) would accept, `None` on conversion errors
+fn general_value(s: &[u8]) -> Option<f64> {
+    let n = s.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let s = &s[n..];
+    let mut i = usize::from(matches!(s.first(), Some(b'+') | Some(b'-')));
+    for word in [&b"infinity"[..], b"inf", b"nan"] {
+        if s.len() >= i + word.len() && s[i..i + word.len()].eq_ignore_ascii_case(word) {
+            return std::str::from_utf8(&s[..i + word.len()]).ok()?.parse().ok();
+        }
+    }
+    let int = s[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+    i += int;
+    let mut frac = 0;
+    if s.get(i) == Some(&b'.') {
+        frac = s[i + 1..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if int + frac > 0 {
+            i += 1 + frac;
+        }
+    }
+    if int + frac == 0 {
+        return None;
+    }
+    if matches!(s.get(i), Some(b'e') | Some(b'E')) {
+        let mut j = i + 1;
+        if matches!(s.get(j), Some(b'+') | Some(b'-')) {
+            j += 1;
+        }
+        let exp = s[j.min(s.len())..].iter().take_while(|c| c.is_ascii_digit()).count();
+        if exp > 0 {
+            i = j + exp;
+        }
+    }
+    std::str::from_utf8(&s[..i]).ok()?.parse().ok()
+}
+
+/// Conversion errors sort first, then NaNs, then the numbers themselves
+fn general_numcompare(a: &[u8], b: &[u8]) -> Ordering {
+    let rank = |v: Option<f64>| match v {
+        None => 0,
+        Some(v) if v.is_nan() => 1,
+        Some(_) => 2,
+    };
+    let (a, b) = (general_value(a), general_value(b));
+    match (a, b) {
+        (Some(x), Some(y)) if !x.is_nan() && !y.is_nan() => {
+            x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn month(s: &[u8]) -> usize {
+    let s = skip_blanks(s);
+    MONTHS
+        .iter()
+        .position(|m| s.len() >= 3 && s[..3].eq_ignore_ascii_case(m))
+        .map_or(0, |i| i + 1)
+}
+
+/// gnulib's `order` helper for `verrevcmp`
+fn order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(&c) if c.is_ascii_alphabetic() => c as i32,
+        Some(b'~') => -2,
+        Some(&c) => c as i32 + 256,
+    }
+}
+
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    let digit_at = |s: &[u8], p: usize| s.get(p).is_some_and(u8::is_ascii_digit);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !digit_at(a, i)) || (j < b.len() && !digit_at(b, j)) {
+            let (ac, bc) = (order(a, i), order(b, j));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while digit_at(a, i) && digit_at(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if digit_at(a, i) {
+            return Ordering::Greater;
+        }
+        if digit_at(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Length of `s` without its file suffixes, ie. trailing `(\.[A-Za-z~][A-Za-z0-9~]*)*`
+fn file_prefixlen(s: &[u8]) -> usize {
+    let n = s.len();
+    let mut prefixlen = 0;
+    let mut i = 0;
+    while i < n {
+        i += 1;
+        prefixlen = i;
+        while i + 1 < n && s[i] == b'.' && (s[i + 1].is_ascii_alphabetic() || s[i + 1] == b'~') {
+            i += 2;
+            while i < n && (s[i].is_ascii_alphanumeric() || s[i] == b'~') {
+                i += 1;
+            }
+        }
+    }
+    prefixlen
+}
+
+/// gnulib's `filevercmp`, what `ls -v` and `sort -V` use
+fn filevercmp(a: &[u8], b: &[u8]) -> Ordering {
+    if a.is_empty() || b.is_empty() {
+        return (!a.is_empty()).cmp(&!b.is_empty());
+    }
+    // "." first, then "..", then other hidden files, then everything else
+    if a[0] == b'.' {
+        if b[0] != b'.' {
+            return Ordering::Less;
+        }
+        let (adot, bdot) = (a.len() == 1, b.len() == 1);
+        if adot || bdot {
+            return bdot.cmp(&adot);
+        }
+        let (adotdot, bdotdot) = (a == b"..", b == b"..");
+        if adotdot || bdotdot {
+            return bdotdot.cmp(&adotdot);
+        }
+    } else if b[0] == b'.' {
+        return Ordering::Greater;
+    }
+    let (aprefix, bprefix) = (file_prefixlen(a), file_prefixlen(b));
+    let ord = verrevcmp(&a[..aprefix], &b[..bprefix]);
+    if ord != Ordering::Equal || (aprefix == a.len() && bprefix == b.len()) {
+        return ord;
+    }
+    verrevcmp(a, b)
+}
+
+fn text_compare(a: &[u8], b: &[u8], opts: &KeyOpts) -> Ordering {
+    if !(opts.dictionary || opts.ignore_nonprinting || opts.fold_case) {
+        return a.cmp(b);
+    }
+    let keep = |c: &&u8| {
+        !(opts.dictionary && !(c.is_ascii_alphanumeric() || is_blank(**c)))
+            && (!opts.ignore_nonprinting || (b' '..=b'~').contains(*c))
+    };
+    let translate = |c: &u8| match opts.fold_case {
+        true => c.to_ascii_uppercase(),
+        false => *c,
+    };
+    let a = a.iter().filter(keep).map(translate);
+    let b = b.iter().filter(keep).map(translate);
+    a.cmp(b)
+}
+
+struct Comparator {
+    keys: Vec<Key>,
+    tab: Option<u8>,
+    // Compare whole lines when all keys are equal, disabled by -s and -u
+    last_resort: bool,
+    reverse: bool,
+}
+
+impl Comparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        if !self.keys.is_empty() {
+            let ord = self.key_compare(a, b);
+            if ord != Ordering::Equal || !self.last_resort {
+                return ord;
+            }
+        }
+        let ord = a.cmp(b);
+        if self.reverse {
+            ord.reverse()
+        } else {
+            ord
+        }
+    }
+
+    fn key_compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        for key in &self.keys {
+            let (a, b) = (key.extract(a, self.tab), key.extract(b, self.tab));
+            let ord = match key.opts.mode {
+                Mode::Text => text_compare(a, b, &key.opts),
+                Mode::Numeric => numcompare(a, b),
+                Mode::GeneralNumeric => general_numcompare(a, b),
+                Mode::HumanNumeric => human_numcompare(a, b),
+                Mode::Month => month(a).cmp(&month(b)),
+                Mode::Version => filevercmp(a, b),
+            };
+            if ord != Ordering::Equal {
+                return if key.opts.reverse { ord.reverse() } else { ord };
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// Lines are kept as ranges into one big buffer to keep per-line overhead low
+#[derive(Default)]
+struct Chunk {
+    buf: Vec<u8>,
+    lines: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    fn size(&self) -> usize {
+        self.buf.len() + self.lines.len() * size_of::<(usize, usize)>()
+    }
+
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.lines.clear();
+    }
+}
+
+/// Stable merge of two sorted runs of line ranges
+fn merge_ranges<F>(a: Vec<(usize, usize)>, b: Vec<(usize, usize)>, by: F) -> Vec<(usize, usize)>
+where
+    F: Fn(&(usize, usize), &(usize, usize)) -> Ordering,
+{
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        if by(x, y) == Ordering::Greater {
+            merged.extend(b.next());
+        } else {
+            merged.extend(a.next());
+        }
+    }
+    merged.extend(a);
+    merged.extend(b);
+    merged
+}
+
+fn sort_chunk(chunk: &mut Chunk, cmp: &Comparator, threads: usize) {
+    let Chunk { buf, lines } = chunk;
+    let buf = &buf[..];
+    let by = |a: &(usize, usize), b: &(usize, usize)| cmp.compare(&buf[a.0..a.1], &buf[b.0..b.1]);
+    // With the last-resort comparison, equal lines are identical so stability doesn't matter
+    let sort = |part: &mut [(usize, usize)]| match cmp.last_resort {
+        true => part.sort_unstable_by(by),
+        false => part.sort_by(by),
+    };
+    if threads <= 1 || lines.len() < PARALLEL_MIN_LINES {
+        sort(lines);
+        return;
+    }
+    let per_thread = (lines.len() + threads - 1) / threads;
+    thread::scope(|s| {
+        for part in lines.chunks_mut(per_thread) {
+            s.spawn(move || sort(part));
+        }
+    });
+    let mut parts: Vec<_> = lines.chunks(per_thread).map(<[_]>::to_vec).collect();
+    while parts.len() > 1 {
+        let mut pairs = Vec::new();
+        while parts.len() >= 2 {
+            let b = parts.pop().unwrap();
+            let a = parts.pop().unwrap();
+            pairs.push((a, b));
+        }
+        let odd = parts.pop();
+        let mut merged: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = pairs
+                .into_iter()
+                .map(|(a, b)| s.spawn(move || merge_ranges(a, b, by)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        // Pairs were taken from the back, restore input order for stability
+        merged.reverse();
+        parts = odd.into_iter().chain(merged).collect();
+    }
+    *lines = parts.pop().unwrap_or_default();
+}
+
+/// Sorted run spilled to disk, removed again once dropped
+struct TempRun {
+    path: PathBuf,
+}
+
+impl Drop for TempRun {
+    fn drop(&mut self) {
+        let path = self.path.as_os_str().as_bytes();
+        with_temp_paths(|paths| {
+            let _ = fs::remove_file(&self.path);
+            paths.retain(|temp| temp.as_bytes() != path);
+        });
+    }
+}
+
+// Runs on disk, for `cleanup` to unlink when a signal kills sort before they're dropped
+static TEMP_PATHS: Mutex<Vec<CString>> = Mutex::new(Vec::new());
+
+// GNU sort's list, what terminates a process by default (short of SIGKILL)
+const CLEANUP_SIGNALS: [libc::c_int; 10] = [
+    libc::SIGALRM,
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGPIPE,
+    libc::SIGPROF,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGVTALRM,
+    libc::SIGXCPU,
+    libc::SIGXFSZ,
+];
+
+// Update TEMP_PATHS with the signals blocked, a handler can't then interrupt the thread
+// holding the lock. Handlers are installed the first time
+fn with_temp_paths<T>(update: impl FnOnce(&mut Vec<CString>) -> T) -> T {
+    static HANDLERS: Once = Once::new();
+    HANDLERS.call_once(|| {
+        let cleanup = cleanup as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        for sig in CLEANUP_SIGNALS {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                libc::sigaction(sig, std::ptr::null(), &mut action);
+                // Ignored ones stay ignored, ie. under nohup
+                if action.sa_sigaction == libc::SIG_IGN {
+                    continue;
+                }
+                action.sa_sigaction = cleanup;
+                action.sa_flags = 0;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(sig, &action, std::ptr::null_mut());
+            }
+        }
+    });
+    unsafe {
+        let mut block: libc::sigset_t = std::mem::zeroed();
+        let mut saved: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut block);
+        for sig in CLEANUP_SIGNALS {
+            libc::sigaddset(&mut block, sig);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &block, &mut saved);
+        let result = update(&mut TEMP_PATHS.lock().unwrap_or_else(PoisonError::into_inner));
+        libc::pthread_sigmask(libc::SIG_SETMASK, &saved, std::ptr::null_mut());
+        result
+    }
+}
+
+// Unlink the runs, then die of the signal as if it had never been caught
+extern "C" fn cleanup(sig: libc::c_int) {
+    let paths = TEMP_PATHS.lock().unwrap_or_else(PoisonError::into_inner);
+    for path in paths.iter() {
+        unsafe { libc::unlink(path.as_ptr()) };
+    }
+    unsafe {
+        libc::signal(sig, libc::SIG_DFL);
+        // Delivered once the handler returns, the signal being blocked until then
+        libc::raise(sig);
+    }
+}
+
+enum Source {
+    Input(OsString),
+    Run(TempRun),
+}
+
+impl Source {
+    fn name(&self) -> &OsStr {
+        match self {
+            Source::Input(name) => name,
+            Source::Run(run) => run.path.as_os_str(),
+        }
+    }
+
+    fn open(&self) -> Result<Box<dyn BufRead>, String> {
+        match self {
+            Source::Input(name) if name == "-" => Ok(Box::new(BufReader::with_capacity(
+                IO_BUFSIZE,
+                io::stdin().lock(),
+            ))),
+            _ => {
+                let file = File::open(self.name()).map_err(|e| describe("cannot read", self.name(), &e))?;
+                Ok(Box::new(BufReader::with_capacity(IO_BUFSIZE, file)))
+            }
+        }
+    }
+}
+
+fn describe(what: &str, name: &OsStr, e: &io::Error) -> String {
+    format!("{what}: {}: {}", name.to_string_lossy(), strerror(e))
+}
+
+/// Read one record into `line` without its delimiter, `false` at EOF
+fn read_record(reader: &mut dyn BufRead, line: &mut Vec<u8>, delim: u8) -> io::Result<bool> {
+    if reader.read_until(delim, line)? == 0 {
+        return Ok(false);
+    }
+    if line.last() == Some(&delim) {
+        line.pop();
+    }
+    Ok(true)
+}
+
+/// Merge candidate, ordered so that `BinaryHeap` pops the smallest line (earliest source on ties)
+struct Head<'a> {
+    line: Vec<u8>,
+    idx: usize,
+    by: &'a Comparator,
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.by
+            .compare(&other.line, &self.line)
+            .then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+struct Sorter {
+    cmp: Comparator,
+    delim: u8,
+    unique: bool,
+    buffer_size: usize,
+    threads: usize,
+    batch_size: usize,
+    tmpdirs: Vec<PathBuf>,
+    chunk: Chunk,
+    runs: Vec<TempRun>,
+}
+
+impl Sorter {
+    fn read(&mut self, source: &Source) -> Result<(), String> {
+        let mut reader = source.open()?;
+        loop {
+            let start = self.chunk.buf.len();
+            let n = reader
+                .read_until(self.delim, &mut self.chunk.buf)
+                .map_err(|e| describe("read failed", source.name(), &e))?;
+            if n == 0 {
+                return Ok(());
+            }
+            let mut end = self.chunk.buf.len();
+            if self.chunk.buf[end - 1] == self.delim {
+                end -= 1;
+            }
+            self.chunk.lines.push((start, end));
+            if self.chunk.size() >= self.buffer_size {
+                self.spill()?;
+            }
+        }
+    }
+
+    fn temp_run(&self) -> Result<(TempRun, File), String> {
+        let dir = &self.tmpdirs[self.runs.len() % self.tmpdirs.len()];
+        // Registered before a signal could leave it behind
+        let created = with_temp_paths(|paths| {
+            let (fd, path) = nix::unistd::mkstemp(&dir.join("sortXXXXXX"))?;
+            paths.push(CString::new(path.as_os_str().as_bytes()).unwrap());
+            Ok((fd, path))
+        });
+        let (fd, path) = created.map_err(|e| {
+            let dir = shell_quote(dir.as_os_str().as_bytes(), true);
+            format!("cannot create temporary file in {dir}: {}", errno_desc(e))
+        })?;
+        Ok((TempRun { path }, unsafe { File::from_raw_fd(fd) }))
+    }
+
+    fn spill(&mut self) -> Result<(), String> {
+        sort_chunk(&mut self.chunk, &self.cmp, self.threads);
+        let (run, file) = self.temp_run()?;
+        let mut writer = BufWriter::with_capacity(IO_BUFSIZE, file);
+        self.write_chunk(&mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|e| describe("write failed", run.path.as_os_str(), &e))?;
+        self.runs.push(run);
+        self.chunk.clear();
+        Ok(())
+    }
+
+    fn write_chunk(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut prev: Option<&[u8]> = None;
+        for &(start, end) in &self.chunk.lines {
+            let line = &self.chunk.buf[start..end];
+            if self.unique {
+                if prev.is_some_and(|p| self.cmp.compare(p, line) == Ordering::Equal) {
+                    continue;
+                }
+                prev = Some(line);
+            }
+            out.write_all(line)?;
+            out.write_all(&[self.delim])?;
+        }
+        Ok(())
+    }
+
+    /// Merge everything into `out`, going through intermediate runs while there
+    /// are more sources than `--batch-size`
+    fn merge(
+        &mut self,
+        mut sources: Vec<Source>,
+        out: &mut dyn Write,
+        name: &OsStr,
+    ) -> Result<(), String> {
+        while sources.len() > self.batch_size {
+            let mut next = Vec::new();
+            let mut pending = sources.into_iter();
+            loop {
+                let group: Vec<_> = pending.by_ref().take(self.batch_size).collect();
+                if group.len() <= 1 {
+                    next.extend(group);
+                    break;
+                }
+                let (run, file) = self.temp_run()?;
+                let mut writer = BufWriter::with_capacity(IO_BUFSIZE, file);
+                self.merge_into(&group, &mut writer, run.path.as_os_str())?;
+                writer
+                    .flush()
+                    .map_err(|e| describe("write failed", run.path.as_os_str(), &e))?;
+                next.push(Source::Run(run));
+            }
+            sources = next;
+        }
+        self.merge_into(&sources, out, name)
+    }
+
+    fn merge_into(
+        &self,
+        sources: &[Source],
+        out: &mut dyn Write,
+        name: &OsStr,
+    ) -> Result<(), String> {
+        let mut readers = sources
+            .iter()
+            .map(Source::open)
+            .collect::<Result<Vec<_>, _>>()?;
+        let read = |idx: usize, reader: &mut dyn BufRead, line: &mut Vec<u8>| {
+            read_record(reader, line, self.delim)
+                .map_err(|e| describe("read failed", sources[idx].name(), &e))
+        };
+
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (idx, reader) in readers.iter_mut().enumerate() {
+            let mut line = Vec::new();
+            if read(idx, reader.as_mut(), &mut line)? {
+                heap.push(Head { line, idx, by: &self.cmp });
+            }
+        }
+
+        let mut last: Option<Vec<u8>> = None;
+        while let Some(mut head) = heap.peek_mut() {
+            let duplicate = self.unique
+                && last
+                    .as_ref()
+                    .is_some_and(|l| self.cmp.compare(l, &head.line) == Ordering::Equal);
+            if !duplicate {
+                out.write_all(&head.line)
+                    .and_then(|_| out.write_all(&[self.delim]))
+                    .map_err(|e| describe("write failed", name, &e))?;
+                if self.unique {
+                    last = Some(head.line.clone());
+                }
+            }
+            head.line.clear();
+            let idx = head.idx;
+            if !read(idx, readers[idx].as_mut(), &mut head.line)? {
+                PeekMut::pop(head);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_size(size: &str) -> Option<usize> {
+    let n = size.find(|c: char| !c.is_ascii_digit()).unwrap_or(size.len());
+    let value: usize = size[..n].parse().ok()?;
+    let shift = match &size[n..] {
+        "b" => 0,
+        "" | "k" | "K" => 10,
+        "m" | "M" => 20,
+        "g" | "G" => 30,
+        "t" | "T" => 40,
+        "p" | "P" => 50,
+        "e" | "E" => 60,
+        "%" => return value.checked_mul(physical_memory() / 100),
+        _ => return None,
+    };
+    value.checked_mul(1 << shift)
+}
+
+fn physical_memory() -> usize {
+    let pages = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    let pagesize = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (pages.max(0) as usize).saturating_mul(pagesize.max(0) as usize)
+}
+
+fn check(
+    cmp: &Comparator,
+    source: &Source,
+    delim: u8,
+    unique: bool,
+    quiet: bool,
+) -> Result<bool, String> {
+    let mut reader = source.open()?;
+    let read = |reader: &mut dyn BufRead, line: &mut Vec<u8>| {
+        read_record(reader, line, delim).map_err(|e| describe("read failed", source.name(), &e))
+    };
+    let (mut prev, mut line) = (Vec::new(), Vec::new());
+    if !read(reader.as_mut(), &mut prev)? {
+        return Ok(true);
+    }
+    let mut lineno = 1;
+    while read(reader.as_mut(), &mut line)? {
+        lineno += 1;
+        let ord = cmp.compare(&prev, &line);
+        // With -u equal lines are out of order as well
+        if ord == Ordering::Greater || (unique && ord == Ordering::Equal) {
+            if !quiet {
+                eprintln!(
+                    "sort: {}:{lineno}: disorder: {}",
+                    source.name().to_string_lossy(),
+                    String::from_utf8_lossy(&line)
+                );
+            }
+            return Ok(false);
+        }
+        std::mem::swap(&mut prev, &mut line);
+        line.clear();
+    }
+    Ok(true)
+}
+
+fn run(args: Cli) -> Result<ExitCode, String> {
+    let mut global = KeyOpts::default();
+    let flags = [
+        (args.ignore_leading_blanks, 'b'),
+        (args.dictionary_order, 'd'),
+        (args.ignore_case, 'f'),
+        (args.general_numeric_sort, 'g'),
+        (args.human_numeric_sort, 'h'),
+        (args.ignore_nonprinting, 'i'),
+        (args.month_sort, 'M'),
+        (args.numeric_sort, 'n'),
+        (args.reverse, 'r'),
+        (args.version_sort, 'V'),
+    ];
+    for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+        global.set(*flag, false)?;
+    }
+    // Global -b applies to both ends of every key
+    global.skip_end_blanks = global.skip_start_blanks;
+
+    let mut keys = args
+        .key
+        .iter()
+        .map(|spec| Key::parse(spec, &global))
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() && !global.is_default() {
+        keys.push(Key {
+            opts: global,
+            ..Key::default()
+        });
+    }
+
+    let tab = match args.field_separator.as_deref().map(OsStr::as_bytes) {
+        None => None,
+        Some([]) => return Err("empty tab".to_string()),
+        Some([c]) => Some(*c),
+        Some(b"\\0") => Some(0),
+        Some(sep) => {
+            return Err(format!(
+                "multi-character tab '{}'",
+                String::from_utf8_lossy(sep)
+            ))
+        }
+    };
+
+    let buffer_size = match &args.buffer_size {
+        Some(size) => parse_size(size).ok_or_else(|| format!("invalid -S argument '{size}'"))?,
+        None => physical_memory() / 8,
+    };
+    if args.batch_size < 2 {
+        return Err(format!(
+            "invalid --batch-size argument '{}'\nsort: minimum --batch-size argument is '2'",
+            args.batch_size
+        ));
+    }
+    let threads = match args.parallel {
+        Some(0) => return Err("number in parallel must be nonzero".to_string()),
+        Some(n) => n,
+        None => thread::available_parallelism().map_or(1, |n| min(n.get(), 8)),
+    };
+    let tmpdirs = match args.temporary_directory.is_empty() {
+        false => args.temporary_directory.clone(),
+        true => vec![std::env::var_os("TMPDIR")
+            .filter(|d| !d.is_empty())
+            .map_or_else(|| PathBuf::from("/tmp"), PathBuf::from)],
+    };
+
+    let files = match args.files.is_empty() {
+        true => vec![OsString::from("-")],
+        false => args.files.clone(),
+    };
+    let cmp = Comparator {
+        keys,
+        tab,
+        last_resort: !(args.stable || args.unique),
+        reverse: args.reverse,
+    };
+    let delim = if args.zero_terminated { 0 } else { b'\n' };
+
+    if args.check || args.check_silent {
+        if let Some(extra) = files.get(1) {
+            return Err(format!(
+                "extra operand '{}' not allowed with -c",
+                extra.to_string_lossy()
+            ));
+        }
+        let source = Source::Input(files[0].clone());
+        let sorted = check(&cmp, &source, delim, args.unique, args.check_silent)?;
+        return Ok(if sorted { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+    }
+
+    let mut sorter = Sorter {
+        cmp,
+        delim,
+        unique: args.unique,
+        buffer_size: buffer_size.max(1),
+        threads,
+        batch_size: args.batch_size,
+        tmpdirs,
+        chunk: Chunk::default(),
+        runs: Vec::new(),
+    };
+
+    let mut sources: Vec<Source> = files.into_iter().map(Source::Input).collect();
+    if args.merge {
+        // The output may be one of the inputs, which must be saved before truncating it
+        if let Some(output) = args.output.as_deref().and_then(|o| fs::metadata(o).ok()) {
+            for source in sources.iter_mut() {
+                let same = source.name() != "-"
+                    && fs::metadata(source.name()).is_ok_and(|m| {
+                        m.st_dev() == output.st_dev() && m.st_ino() == output.st_ino()
+                    });
+                if same {
+                    let (run, file) = sorter.temp_run()?;
+                    let mut input = source.open()?;
+                    let mut writer = BufWriter::with_capacity(IO_BUFSIZE, file);
+                    io::copy(&mut input, &mut writer)
+                        .and_then(|_| writer.flush())
+                        .map_err(|e| describe("write failed", run.path.as_os_str(), &e))?;
+                    *source = Source::Run(run);
+                }
+            }
+        }
+    } else {
+        for source in &sources {
+            sorter.read(source)?;
+        }
+        if sorter.runs.is_empty() {
+            sort_chunk(&mut sorter.chunk, &sorter.cmp, sorter.threads);
+            sources.clear();
+        } else {
+            if !sorter.chunk.lines.is_empty() {
+                sorter.spill()?;
+            }
+            sources = sorter.runs.drain(..).map(Source::Run).collect();
+        }
+    }
+
+    // Only now that every input has been consumed is it safe to truncate the output
+    let (name, file) = match &args.output {
+        Some(path) => (
+            path.as_os_str(),
+            File::create(path).map_err(|e| describe("open failed", path.as_os_str(), &e))?,
+        ),
+        None => (OsStr::new("standard output"), stdout_file().map_err(|e| e.to_string())?),
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, file);
+    if sources.is_empty() {
+        sorter
+            .write_chunk(&mut out)
+            .map_err(|e| describe("write failed", name, &e))?;
+    } else {
+        sorter.merge(sources, &mut out, name)?;
+    }
+    out.flush().map_err(|e| describe("write failed", name, &e))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args = Cli::parse_from(args);
+    run(args).unwrap_or_else(|e| {
+        eprintln!("sort: {e}");
+        ExitCode::from(2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(spec: &str) -> Key {
+        Key::parse(spec, &KeyOpts::default()).unwrap()
+    }
+
+    // Sort `lines` like `sort -t TAB -k SPEC...`
+    fn sorted<'a>(lines: &[&'a str], specs: &[&str], tab: Option<u8>) -> Vec<&'a str> {
+        let cmp = Comparator {
+            keys: specs.iter().map(|spec| key(spec)).collect(),
+            tab,
+            last_resort: true,
+            reverse: false,
+        };
+        let mut lines = lines.to_vec();
+        lines.sort_by(|a, b| cmp.compare(a.as_bytes(), b.as_bytes()));
+        lines
+    }
+
+    #[test]
+    fn key_fields_and_offsets() {
+        let k = key("2.3,4.5");
+        assert_eq!((k.sword, k.schar, k.eword, k.echar), (1, 2, Some(4), 5));
+        let k = key("3");
+        assert_eq!((k.sword, k.schar, k.eword, k.echar), (2, 0, None, 0));
+        let k = key("1,1.0");
+        assert_eq!((k.eword, k.echar), (Some(1), 0));
+    }
+
+    #[test]
+    fn key_errors() {
+        let error = |spec| Key::parse(spec, &KeyOpts::default()).unwrap_err();
+        assert_eq!(
+            error("0"),
+            "field number is zero: invalid field specification '0'"
+        );
+        assert_eq!(
+            error("1.0"),
+            "character offset is zero: invalid field specification '1.0'"
+        );
+        assert_eq!(
+            error("x"),
+            "invalid number at field start: invalid field specification 'x'"
+        );
+        assert_eq!(
+            error("1,0"),
+            "field number is zero: invalid field specification '1,0'"
+        );
+        assert_eq!(
+            error("1,x"),
+            "invalid number after ',': invalid field specification '1,x'"
+        );
+        assert_eq!(
+            error("1z"),
+            "stray character in field spec: invalid field specification '1z'"
+        );
+        assert_eq!(
+            error("1n,1g"),
+            "stray character in field spec: invalid field specification '1n,1g'"
+        );
+    }
+
+    #[test]
+    fn key_modifiers() {
+        let k = key("1bnr,2b");
+        assert!(k.opts.skip_start_blanks && k.opts.skip_end_blanks);
+        assert!(k.opts.reverse);
+        assert_eq!(k.opts.mode, Mode::Numeric);
+        let k = key("1df");
+        assert!(k.opts.dictionary && k.opts.fold_case);
+        assert_eq!(k.opts.mode, Mode::Text);
+    }
+
+    #[test]
+    fn key_inherits_global_options() {
+        let global = KeyOpts {
+            mode: Mode::Numeric,
+            reverse: true,
+            ..KeyOpts::default()
+        };
+        assert_eq!(Key::parse("2", &global).unwrap().opts, global);
+        // Any option of its own, even r, and none are inherited
+        let k = Key::parse("2r", &global).unwrap();
+        assert_eq!(k.opts.mode, Mode::Text);
+        let k = Key::parse("2f", &global).unwrap();
+        assert!(!k.opts.reverse && k.opts.fold_case);
+    }
+
+    #[test]
+    fn extract_fields() {
+        let line = b"  one two\tthree";
+        // Blank separated fields keep their leading blanks, unless b
+        assert_eq!(key("2").extract(line, None), b" two\tthree");
+        assert_eq!(key("2,2").extract(line, None), b" two");
+        assert_eq!(key("2b,2").extract(line, None), b"two");
+        assert_eq!(key("1.3,1.4").extract(line, None), b"on");
+        // The end offset counts from the blanks unless it has a b of its own
+        assert_eq!(key("1.2b,1.4").extract(line, None), b"n");
+        assert_eq!(key("1.2b,1.4b").extract(line, None), b"ne ");
+        assert_eq!(key("3").extract(line, None), b"\tthree");
+        assert_eq!(key("4").extract(line, None), b"");
+    }
+
+    #[test]
+    fn extract_tab_fields() {
+        let line = b"a:bc::d";
+        assert_eq!(key("2,2").extract(line, Some(b':')), b"bc");
+        assert_eq!(key("2").extract(line, Some(b':')), b"bc::d");
+        assert_eq!(key("3,3").extract(line, Some(b':')), b"");
+        assert_eq!(key("2.2,4").extract(line, Some(b':')), b"c::d");
+        assert_eq!(key("1,2.1").extract(line, Some(b':')), b"a:b");
+    }
+
+    #[test]
+    fn compare_numeric() {
+        // Equal numbers, ie. -0 and abc, fall back on comparing the lines unreversed
+        let lines = ["10", "9", "-1", "1.5", "abc", "-0", "1e3"];
+        assert_eq!(
+            sorted(&lines, &["1n"], None),
+            ["-1", "-0", "abc", "1e3", "1.5", "9", "10"]
+        );
+        assert_eq!(
+            sorted(&lines, &["1nr"], None),
+            ["10", "9", "1.5", "1e3", "-0", "abc", "-1"]
+        );
+    }
+
+    #[test]
+    fn compare_fold_case_and_blanks() {
+        assert_eq!(sorted(&["b", "a", "B", "A"], &["1f"], None), ["A", "a", "B", "b"]);
+        assert_eq!(sorted(&["b", "a", "B", "A"], &["1"], None), ["A", "B", "a", "b"]);
+        assert_eq!(sorted(&["x  b", "y a"], &["2"], None), ["x  b", "y a"]);
+        assert_eq!(sorted(&["x  b", "y a"], &["2b"], None), ["y a", "x  b"]);
+    }
+
+    #[test]
+    fn compare_tab_keys() {
+        let lines = ["x:10:b", "y:9:a", "z:9:c"];
+        assert_eq!(
+            sorted(&lines, &["2,2n", "3r"], Some(b':')),
+            ["z:9:c", "y:9:a", "x:10:b"]
+        );
+        assert_eq!(sorted(&lines, &["3"], Some(b':')), ["y:9:a", "x:10:b", "z:9:c"]);
+    }
+
+    #[test]
+    fn compare_last_resort() {
+        let cmp = Comparator {
+            keys: vec![key("1,1")],
+            tab: None,
+            last_resort: false,
+            reverse: false,
+        };
+        assert_eq!(cmp.compare(b"a 2", b"a 1"), Ordering::Equal);
+        let cmp = Comparator {
+            last_resort: true,
+            ..cmp
+        };
+        assert_eq!(cmp.compare(b"a 2", b"a 1"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * stdbuf - run a command with modified buffering of its standard streams
+ *
+ * Like GNU the buffering is set inside the command by a preloaded library: the modes
+ * go in _STDBUF_I, _STDBUF_O and _STDBUF_E, libstdbuf.so (the libstdbuf crate of this
+ * workspace) is appended to LD_PRELOAD and the command is exec'd. The library is
+ * $RATISCAT_LIBSTDBUF if set, else it's looked for next to the stdbuf binary, then in
+ * ../lib/ratiscat from there. Statuses are GNU's: 125 for stdbuf's own errors, 126
+ * when the command can't be run and 127 when it isn't found.
+ */
+
+use crate::{errno_desc, exec_command, exec_status, parse_args, quote};
+use clap::Parser;
+use nix::errno::Errno;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "stdbuf", version, long_about = None)]
+#[command(
+    about = "Run COMMAND, with modified buffering operations for its standard streams. \
+If MODE is 'L' the corresponding stream will be line buffered, which is invalid with \
+standard input. If MODE is '0' the corresponding stream will be unbuffered. Otherwise \
+MODE is a number which may be followed by one of the following: KB 1000, K 1024, \
+MB 1000*1000, M 1024*1024, and so on for G, T, P, E, Z, Y. Binary prefixes can be used, \
+too: KiB=K, MiB=M, and so on. In this case the corresponding stream will be fully \
+buffered with the buffer size set to MODE bytes"
+)]
+#[command(override_usage = "stdbuf OPTION... COMMAND")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Adjust standard input stream buffering
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    input: Option<OsString>,
+    /// Adjust standard output stream buffering
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    output: Option<OsString>,
+    /// Adjust standard error stream buffering
+    #[clap(long, short, value_name = "MODE", allow_hyphen_values = true)]
+    error: Option<OsString>,
+    /// The command to run and its arguments
+    #[clap(trailing_var_arg = true)]
+    command: Vec<OsString>,
+}
+
+const EXIT_CANCELED: u8 = 125;
+const LIBRARY: &str = "libstdbuf.so";
+
+// A size like gnulib's xstrtoumax takes it with the "EGkKMPTYZ0" suffixes: K is 1024,
+// KiB too and KB 1000, a suffix alone counts 1 of it. Err(Some(EOVERFLOW)) when too big
+fn parse_size(s: &[u8]) -> Result<usize, Option<Errno>> {
+    let s = match s.first() {
+        Some(b'+') => &s[1..],
+        _ => s,
+    };
+    let digits = s.iter().take_while(|c| c.is_ascii_digit()).count();
+    let (mut value, mut overflow) = (1usize, false);
+    if digits > 0 {
+        value = 0;
+        for &c in &s[..digits] {
+            let next = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((c - b'0') as usize));
+            overflow |= next.is_none();
+            value = next.unwrap_or(usize::MAX);
+        }
+    }
+    let suffix = &s[digits..];
+    let Some(&unit) = suffix.first() else {
+        return match (digits, overflow) {
+            (0, _) => Err(None),
+            (_, true) => Err(Some(Errno::EOVERFLOW)),
+            (_, false) => Ok(value),
+        };
+    };
+    let power = match unit {
+        b'k' | b'K' => 1,
+        b'M' => 2,
+        b'G' => 3,
+        b'T' => 4,
+        b'P' => 5,
+        b'E' => 6,
+        b'Z' => 7,
+        b'Y' => 8,
+        _ => return Err(None),
+    };
+    let base: usize = match &suffix[1..] {
+        b"" | b"iB" => 1024,
+        b"B" | b"D" => 1000,
+        _ => return Err(None),
+    };
+    let scaled = base
+        .checked_pow(power)
+        .and_then(|unit| value.checked_mul(unit));
+    match scaled {
+        Some(size) if !overflow => Ok(size),
+        _ => Err(Some(Errno::EOVERFLOW)),
+    }
+}
+
+// The value of _STDBUF_X for a mode, the size in bytes unless line buffered
+fn parse_mode(option: char, mode: &[u8]) -> Result<String, ExitCode> {
+    let start = mode.iter().take_while(|c| c.is_ascii_whitespace()).count();
+    let mode = &mode[start..];
+    // -oL being the usual, -iL could be taken to do the same
+    if option == 'i' && mode.first() == Some(&b'L') {
+        eprintln!(
+            "stdbuf: line buffering stdin is meaningless\n\
+            Try 'stdbuf --help' for more information."
+        );
+        return Err(ExitCode::from(EXIT_CANCELED));
+    }
+    if mode == b"L" {
+        return Ok(String::from("L"));
+    }
+    parse_size(mode).map(|size| size.to_string()).map_err(|e| {
+        match e {
+            Some(e) => eprintln!("stdbuf: invalid mode {}: {}", quote(mode), errno_desc(e)),
+            None => eprintln!("stdbuf: invalid mode {}", quote(mode)),
+        }
+        ExitCode::from(EXIT_CANCELED)
+    })
+}
+
+// The library to preload, None if there's none where it's looked for
+fn find_library() -> Option<PathBuf> {
+    if let Some(library) = std::env::var_os("RATISCAT_LIBSTDBUF") {
+        return Some(PathBuf::from(library));
+    }
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    [dir.join(LIBRARY), dir.join("../lib/ratiscat").join(LIBRARY)]
+        .into_iter()
+        .find(|library| library.exists())
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, EXIT_CANCELED);
+    let options = [('i', &args.input), ('o', &args.output), ('e', &args.error)];
+    let mut modes = Vec::new();
+    for (option, mode) in options {
+        let Some(mode) = mode else {
+            continue;
+        };
+        match parse_mode(option, mode.as_bytes()) {
+            Ok(mode) => modes.push((option, mode)),
+            Err(status) => return status,
+        }
+    }
+    if args.command.is_empty() {
+        eprintln!("stdbuf: missing operand\nTry 'stdbuf --help' for more information.");
+        return ExitCode::from(EXIT_CANCELED);
+    }
+    if modes.is_empty() {
+        eprintln!(
+            "stdbuf: you must specify a buffering mode option\n\
+            Try 'stdbuf --help' for more information."
+        );
+        return ExitCode::from(EXIT_CANCELED);
+    }
+
+    let Some(library) = find_library() else {
+        eprintln!("stdbuf: failed to find {}", quote(LIBRARY.as_bytes()));
+        return ExitCode::from(EXIT_CANCELED);
+    };
+    for (option, mode) in modes {
+        let variable = format!("_STDBUF_{}", option.to_ascii_uppercase());
+        std::env::set_var(variable, mode);
+    }
+    // After whatever is preloaded already
+    let mut preload = OsString::new();
+    if let Some(previous) = std::env::var_os("LD_PRELOAD") {
+        preload.push(previous);
+        preload.push(":");
+    }
+    preload.push(library);
+    std::env::set_var("LD_PRELOAD", preload);
+
+    let e = exec_command(&args.command);
+    eprintln!(
+        "stdbuf: failed to run command {}: {}",
+        quote(args.command[0].as_bytes()),
+        errno_desc(e)
+    );
+    exec_status(e)
+}
@@ -0,0 +1,410 @@
+/*
+ * Format a time like GNU date does, after gnulib's nstrftime in the C locale
+ *
+ * Besides the POSIX conversions there are GNU's extensions: %N nanoseconds, %:z,
+ * %::z and %:::z numeric zones, %q quarters, %P and %s, and the flags between the
+ * '%' and the conversion: '_' pads with spaces, '-' doesn't pad, '0' and '+' pad with
+ * zeros ('+' also signing years of more than 4 digits), '^' upper cases and '#'
+ * swaps the case of names. A width pads to that many bytes. The E and O modifiers
+ * are accepted where gnulib does and change nothing, and a conversion that isn't
+ * one is copied as is.
+ */
+
+use std::ffi::CStr;
+
+const DAYS: [&[u8]; 7] = [
+    b"Sunday",
+    b"Monday",
+    b"Tuesday",
+    b"Wednesday",
+    b"Thursday",
+    b"Friday",
+    b"Saturday",
+];
+const MONTHS: [&[u8]; 12] = [
+    b"January",
+    b"February",
+    b"March",
+    b"April",
+    b"May",
+    b"June",
+    b"July",
+    b"August",
+    b"September",
+    b"October",
+    b"November",
+    b"December",
+];
+
+/// A time broken down in the local time zone, as TZ says at the time of creation
+pub struct Time {
+    pub sec: i64,
+    pub nsec: u32,
+    tm: libc::tm,
+    zone: Vec<u8>,
+}
+
+impl Time {
+    /// The time `sec` seconds and `nsec` nanoseconds after the Epoch, None when the
+    /// year doesn't fit in a broken down time
+    pub fn local(sec: i64, nsec: u32) -> Option<Time> {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if unsafe { libc::localtime_r(&sec, &mut tm) }.is_null() {
+            return None;
+        }
+        let zone = match tm.tm_zone.is_null() {
+            true => Vec::new(),
+            false => unsafe { CStr::from_ptr(tm.tm_zone) }.to_bytes().to_vec(),
+        };
+        Some(Time {
+            sec,
+            nsec,
+            tm,
+            zone,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Case {
+    Keep,
+    Upper,
+    Lower,
+}
+
+// Add `text` padded to `width` like gnulib's width_add: with zeros for the '0' and
+// '+' flags, not at all for '-', else with spaces
+fn add(out: &mut Vec<u8>, pad: u8, width: isize, text: &[u8], case: Case) {
+    let width = match pad {
+        b'-' => 0,
+        _ => width.max(0) as usize,
+    };
+    if text.len() < width {
+        let fill = match pad {
+            b'0' | b'+' => b'0',
+            _ => b' ',
+        };
+        out.resize(out.len() + width - text.len(), fill);
+    }
+    match case {
+        Case::Keep => out.extend_from_slice(text),
+        Case::Upper => out.extend(text.iter().map(u8::to_ascii_uppercase)),
+        Case::Lower => out.extend(text.iter().map(u8::to_ascii_lowercase)),
+    }
+}
+
+// A number of at least `digits` digits unless another width is given, gnulib's
+// do_number_sign_and_padding. With '_' spaces go before the sign, zeros after it
+fn number(out: &mut Vec<u8>, pad: u8, width: isize, digits: usize, sign: Option<u8>, text: &[u8]) {
+    let pad = match pad {
+        0 => b'0',
+        pad => pad,
+    };
+    let mut width = match width {
+        -1 => digits as isize,
+        width => width,
+    };
+    if let Some(sign) = sign {
+        let shortage = width - 1 - text.len() as isize;
+        if pad == b'_' && shortage > 0 {
+            out.resize(out.len() + shortage as usize, b' ');
+            width -= shortage;
+        }
+        out.push(sign);
+        width -= 1;
+    }
+    add(out, pad, width, text, Case::Keep);
+}
+
+fn decimal(value: i64) -> Vec<u8> {
+    value.unsigned_abs().to_string().into_bytes()
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+// The days between the start of the ISO week based year of a day and that day,
+// negative when it's in the previous year's last week
+fn iso_week_days(yday: i64, wday: i64) -> i64 {
+    const BIG_ENOUGH_MULTIPLE_OF_7: i64 = (366 / 7 + 2) * 7;
+    yday - (yday - wday + 4 + BIG_ENOUGH_MULTIPLE_OF_7) % 7 + 3
+}
+
+// The ISO 8601 year of the week and the day's number in it
+fn iso_week(tm: &libc::tm) -> (i64, i64) {
+    let year = tm.tm_year as i64 + 1900;
+    let (yday, wday) = (tm.tm_yday as i64, tm.tm_wday as i64);
+    let days = iso_week_days(yday, wday);
+    if days < 0 {
+        let previous = 365 + is_leap(year - 1) as i64;
+        return (year - 1, iso_week_days(yday + previous, wday));
+    }
+    let next = iso_week_days(yday - (365 + is_leap(year) as i64), wday);
+    match next >= 0 {
+        true => (year + 1, next),
+        false => (year, days),
+    }
+}
+
+/// Format `time` as strftime(3) would with GNU's extensions
+pub fn strftime(format: &[u8], time: &Time) -> Vec<u8> {
+    let mut out = Vec::new();
+    format_into(&mut out, format, time, false, 0, -1);
+    out
+}
+
+// gnulib's __strftime_internal: `upcase` and the year padding `year_pad` are
+// inherited by composite conversions like %F, `subwidth` being the width of the
+// first conversion of the format
+fn format_into(
+    out: &mut Vec<u8>,
+    format: &[u8],
+    time: &Time,
+    upcase: bool,
+    year_pad: u8,
+    subwidth: isize,
+) {
+    let tm = &time.tm;
+    let mut width = subwidth;
+    let mut i = 0;
+    while i < format.len() {
+        if format[i] != b'%' {
+            add(out, 0, width, &format[i..i + 1], Case::Keep);
+            width = -1;
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        let mut pad = 0;
+        let mut case = match upcase {
+            true => Case::Upper,
+            false => Case::Keep,
+        };
+        let mut change_case = false;
+        while let Some(&flag) = format.get(i) {
+            match flag {
+                b'_' | b'-' | b'+' | b'0' => pad = flag,
+                b'^' => case = Case::Upper,
+                b'#' => change_case = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        if format.get(i).is_some_and(u8::is_ascii_digit) {
+            width = 0;
+            while let Some(&digit) = format.get(i).filter(|c| c.is_ascii_digit()) {
+                width = width
+                    .saturating_mul(10)
+                    .saturating_add((digit - b'0') as isize);
+                i += 1;
+            }
+        }
+        let modifier = match format.get(i) {
+            Some(&modifier @ (b'E' | b'O')) => {
+                i += 1;
+                modifier
+            }
+            _ => 0,
+        };
+        let mut colons = 0;
+        while format.get(i + colons) == Some(&b':') {
+            colons += 1;
+        }
+        if colons > 0 && format.get(i + colons) == Some(&b'z') {
+            i += colons;
+        }
+        let conversion = format.get(i).copied();
+        i = (i + 1).min(format.len());
+
+        let bad = match (modifier, conversion) {
+            (_, None) => true,
+            (b'E', Some(c)) => b"aAbBdDeFgGhHIjklmMNSUVwW".contains(&c),
+            (b'O', Some(c)) => b"aAcDFqxXY".contains(&c),
+            _ => false,
+        };
+        let conversion = match conversion {
+            Some(c) if !bad && (colons == 0 || (c == b'z' && colons <= 3)) => c,
+            // Copied as is, gnulib's bad_format
+            _ => {
+                add(out, pad, width, &format[start..i], case);
+                width = -1;
+                continue;
+            }
+        };
+
+        let hour12 = match tm.tm_hour % 12 {
+            0 => 12,
+            hour => hour,
+        } as i64;
+        let year = tm.tm_year as i64 + 1900;
+        // glibc's %EC and %EY, which aren't padded
+        let era_digits = (modifier == b'E').then_some(1);
+        let upper = case == Case::Upper;
+        // Numbers padded with zeros or spaces by default
+        let zero_padded = |out: &mut Vec<u8>, digits, value: i64| {
+            let sign = (value < 0).then_some(b'-');
+            number(out, pad, width, digits, sign, &decimal(value));
+        };
+        let space_padded = |out: &mut Vec<u8>, digits, value: i64| {
+            let pad = match pad {
+                0 => b'_',
+                pad => pad,
+            };
+            let sign = (value < 0).then_some(b'-');
+            number(out, pad, width, digits, sign, &decimal(value));
+        };
+        // Years, signed with '+' when longer than the usual `digits`
+        let yearish = |out: &mut Vec<u8>, digits, value: i64| {
+            let pad = match pad {
+                0 => year_pad,
+                pad => pad,
+            };
+            let usual = match digits {
+                2 => 99,
+                _ => 9999,
+            };
+            let sign = match value < 0 {
+                true => Some(b'-'),
+                false => {
+                    (pad == b'+' && (value > usual || (digits as isize) < width)).then_some(b'+')
+                }
+            };
+            number(out, pad, width, digits, sign, &decimal(value));
+        };
+        let subformat = |out: &mut Vec<u8>, format: &[u8], year_pad, subwidth| {
+            let mut sub = Vec::new();
+            format_into(&mut sub, format, time, upper, year_pad, subwidth);
+            add(out, pad, width, &sub, Case::Keep);
+        };
+
+        match conversion {
+            b'%' if i - start == 2 => out.push(b'%'),
+            // Copied as is like a bad conversion, but short of a byte in the padding
+            b'%' => add(out, pad, width + 1, &format[start..i], case),
+            b'a' | b'A' | b'b' | b'h' | b'B' => {
+                if change_case {
+                    case = Case::Upper;
+                }
+                let name = match conversion {
+                    b'a' | b'A' => DAYS[tm.tm_wday as usize % 7],
+                    _ => MONTHS[tm.tm_mon as usize % 12],
+                };
+                let name = match conversion {
+                    b'a' | b'b' | b'h' => &name[..3],
+                    _ => name,
+                };
+                add(out, pad, width, name, case);
+            }
+            // glibc's own %c, which doesn't pad
+            b'c' => {
+                let mut sub = Vec::new();
+                format_into(&mut sub, b"%a %b %e %H:%M:%S %-Y", time, false, 0, -1);
+                add(out, 0, -1, &sub, case);
+            }
+            b'C' => {
+                let mut century = year / 100;
+                century -= (year % 100 < 0 && century > 0) as i64;
+                yearish(out, era_digits.unwrap_or(2), century);
+            }
+            b'd' => zero_padded(out, 2, tm.tm_mday as i64),
+            b'D' | b'x' => subformat(out, b"%m/%d/%y", pad, -1),
+            b'e' => space_padded(out, 2, tm.tm_mday as i64),
+            b'F' => {
+                let (year_pad, subwidth) = match (pad, width) {
+                    (0, -1) => (b'+', 4),
+                    _ => (pad, (width - 6).max(0)),
+                };
+                subformat(out, b"%Y-%m-%d", year_pad, subwidth);
+            }
+            b'g' => yearish(out, 2, (iso_week(tm).0 % 100).abs()),
+            b'G' => yearish(out, 4, iso_week(tm).0),
+            b'H' => zero_padded(out, 2, tm.tm_hour as i64),
+            b'I' => zero_padded(out, 2, hour12),
+            b'j' => zero_padded(out, 3, tm.tm_yday as i64 + 1),
+            b'k' => space_padded(out, 2, tm.tm_hour as i64),
+            b'l' => space_padded(out, 2, hour12),
+            b'm' => zero_padded(out, 2, tm.tm_mon as i64 + 1),
+            b'M' => zero_padded(out, 2, tm.tm_min as i64),
+            b'n' => add(out, pad, width, b"\n", Case::Keep),
+            // The width is the number of digits, trailing zeros being padding
+            b'N' => {
+                let width = match width {
+                    -1 | 0 => 9,
+                    width => width,
+                };
+                let (mut digits, mut n) = (9, time.nsec);
+                while width < digits || (digits > 1 && n % 10 == 0) {
+                    digits -= 1;
+                    n /= 10;
+                }
+                let pad = match pad {
+                    0 => b'0',
+                    pad => pad,
+                };
+                out.extend_from_slice(format!("{n:0width$}", width = digits as usize).as_bytes());
+                add(out, pad, width - digits, b"", Case::Keep);
+            }
+            b'p' | b'P' => {
+                if change_case || conversion == b'P' {
+                    case = Case::Lower;
+                }
+                let ampm = match tm.tm_hour >= 12 {
+                    true => b"PM",
+                    false => b"AM",
+                };
+                add(out, pad, width, ampm, case);
+            }
+            b'q' => zero_padded(out, 1, tm.tm_mon as i64 / 3 + 1),
+            b'r' => subformat(out, b"%I:%M:%S %p", pad, -1),
+            b'R' => subformat(out, b"%H:%M", pad, -1),
+            b's' => zero_padded(out, 1, time.sec),
+            b'S' => zero_padded(out, 2, tm.tm_sec as i64),
+            b't' => add(out, pad, width, b"\t", Case::Keep),
+            b'T' | b'X' => subformat(out, b"%H:%M:%S", pad, -1),
+            b'u' => zero_padded(out, 1, (tm.tm_wday as i64 + 6) % 7 + 1),
+            b'U' => zero_padded(out, 2, (tm.tm_yday as i64 - tm.tm_wday as i64 + 7) / 7),
+            b'V' => zero_padded(out, 2, iso_week(tm).1 / 7 + 1),
+            b'w' => zero_padded(out, 1, tm.tm_wday as i64),
+            b'W' => {
+                let monday_based = (tm.tm_wday as i64 + 6) % 7;
+                zero_padded(out, 2, (tm.tm_yday as i64 - monday_based + 7) / 7);
+            }
+            b'y' => yearish(out, 2, (year % 100).abs()),
+            b'Y' => yearish(out, era_digits.unwrap_or(4), year),
+            // +hhmm, +hh:mm, +hh:mm:ss or the shortest of these with :::
+            b'z' => {
+                let offset = tm.tm_gmtoff;
+                let negative = offset < 0 || (offset == 0 && time.zone.first() == Some(&b'-'));
+                let offset = offset.abs();
+                let (hours, minutes, seconds) = (offset / 3600, offset / 60 % 60, offset % 60);
+                let colons = match (colons, minutes, seconds) {
+                    (3, 0, 0) => 3,
+                    (3, _, 0) => 1,
+                    (3, _, _) => 2,
+                    (colons, _, _) => colons,
+                };
+                let (digits, text) = match colons {
+                    0 => (5, format!("{}", hours * 100 + minutes)),
+                    1 => (6, format!("{hours}:{minutes:02}")),
+                    2 => (9, format!("{hours}:{minutes:02}:{seconds:02}")),
+                    _ => (3, format!("{hours}")),
+                };
+                let sign = match negative {
+                    true => b'-',
+                    false => b'+',
+                };
+                number(out, pad, width, digits, Some(sign), text.as_bytes());
+            }
+            b'Z' => {
+                if change_case {
+                    case = Case::Lower;
+                }
+                add(out, pad, width, &time.zone, case);
+            }
+            _ => add(out, pad, width, &format[start..i], case),
+        }
+        width = -1;
+    }
+}
@@ -0,0 +1,66 @@
+/*
+ * sum - checksum and count the blocks in a file
+ *
+ * The BSD (default) and System V checksums are the legacy ones of cksum, see
+ * checksum.rs. Block counts round up, in 1K blocks for BSD and 512 bytes for sysv.
+ */
+
+use crate::checksum::{self, Algorithm, Config};
+use crate::{parse_args, reset_sigpipe, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use std::ffi::OsString;
+use std::io::BufWriter;
+use std::os::unix::ffi::OsStringExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "sum", version, long_about = None)]
+#[command(about = "Print BSD (16-bit) checksums and block counts")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Use the BSD sum algorithm (the default), with 1K blocks
+    #[clap(short = 'r', action, overrides_with = "sysv")]
+    bsd: bool,
+    /// Use the System V sum algorithm, with 512 byte blocks
+    #[clap(long, short, action, overrides_with = "bsd")]
+    sysv: bool,
+    /// Files to checksum, stdin by default
+    #[clap(action = ArgAction::Append)]
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    let algorithm = match args.sysv {
+        true => Algorithm::Sysv,
+        false => Algorithm::Bsd,
+    };
+    let config = Config {
+        prog: "sum",
+        algorithm: Some(algorithm),
+        bits: algorithm.bits(),
+        tag: false,
+        binary: false,
+        zero: false,
+        check: false,
+        ignore_missing: false,
+        quiet: false,
+        status: false,
+        strict: false,
+        warn: false,
+    };
+
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("sum: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files: Vec<Vec<u8>> = args.files.into_iter().map(OsStringExt::into_vec).collect();
+    checksum::run(&config, &files, &mut out)
+}
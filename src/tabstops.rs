@@ -0,0 +1,146 @@
+/*
+ * Tab stop lists shared by expand and unexpand
+ *
+ * `-t` takes a single width, or a comma/blank separated list of ascending stops
+ * where the last one may be written `/N` (then every multiple of N) or `+N`
+ * (then every N columns after the previous stop), like GNU.
+ */
+
+#[derive(Debug, Default)]
+pub struct TabStops {
+    // Set when tabs are simply every `tab_size` columns
+    tab_size: u64,
+    list: Vec<u64>,
+    extend_size: u64,
+    increment_size: u64,
+}
+
+impl TabStops {
+    fn set_extend_size(&mut self, tabval: u64) -> Result<(), String> {
+        if self.extend_size != 0 {
+            return Err(String::from("'/' specifier only allowed with the last value"));
+        }
+        self.extend_size = tabval;
+        Ok(())
+    }
+
+    fn set_increment_size(&mut self, tabval: u64) -> Result<(), String> {
+        if self.increment_size != 0 {
+            return Err(String::from("'+' specifier only allowed with the last value"));
+        }
+        self.increment_size = tabval;
+        Ok(())
+    }
+
+    /// Add the stops of a `-t` argument, can be called once per occurrence
+    pub fn parse(&mut self, stops: &str) -> Result<(), String> {
+        enum Kind {
+            Stop,
+            Extend,
+            Increment,
+        }
+        let mut tabval: Option<u64> = None;
+        let mut kind = Kind::Stop;
+
+        let finish = |this: &mut TabStops, tabval: u64, kind: &Kind| match kind {
+            Kind::Extend => this.set_extend_size(tabval),
+            Kind::Increment => this.set_increment_size(tabval),
+            Kind::Stop => {
+                this.list.push(tabval);
+                Ok(())
+            }
+        };
+
+        let bytes = stops.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            match c {
+                b',' | b' ' | b'\t' => {
+                    if let Some(tabval) = tabval.take() {
+                        finish(self, tabval, &kind)?;
+                    }
+                }
+                b'/' | b'+' => {
+                    if tabval.is_some() {
+                        return Err(format!(
+                            "'{}' specifier not at start of number: '{}'",
+                            c as char,
+                            &stops[i..]
+                        ));
+                    }
+                    kind = if c == b'/' { Kind::Extend } else { Kind::Increment };
+                }
+                b'0'..=b'9' => {
+                    let len = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                    let number = &stops[i..i + len];
+                    match number.parse::<u64>() {
+                        Ok(value) => tabval = Some(value),
+                        Err(_) => return Err(format!("tab stop is too large '{number}'")),
+                    }
+                    i += len;
+                    continue;
+                }
+                _ => {
+                    return Err(format!(
+                        "tab size contains invalid character(s): '{}'",
+                        &stops[i..]
+                    ))
+                }
+            }
+            i += 1;
+        }
+        if let Some(tabval) = tabval {
+            finish(self, tabval, &kind)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the stops once every `-t` was parsed, and default to every 8 columns
+    pub fn finalize(&mut self) -> Result<(), String> {
+        let mut prev = 0;
+        for &tab in &self.list {
+            if tab == 0 {
+                return Err(String::from("tab size cannot be 0"));
+            }
+            if tab <= prev {
+                return Err(String::from("tab sizes must be ascending"));
+            }
+            prev = tab;
+        }
+        if self.increment_size != 0 && self.extend_size != 0 {
+            return Err(String::from("'/' specifier is mutually exclusive with '+'"));
+        }
+
+        self.tab_size = match self.list[..] {
+            [] if self.extend_size != 0 => self.extend_size,
+            [] if self.increment_size != 0 => self.increment_size,
+            [] => 8,
+            [tab] if self.extend_size == 0 && self.increment_size == 0 => tab,
+            _ => 0,
+        };
+        Ok(())
+    }
+
+    /// The next tab stop after `column`, none past the last explicit stop.
+    /// `index` remembers where to resume in the list, start it at 0 on each line.
+    pub fn next_tab_column(&self, column: u64, index: &mut usize) -> Option<u64> {
+        if self.tab_size != 0 {
+            return Some(column + (self.tab_size - column % self.tab_size));
+        }
+        while let Some(&tab) = self.list.get(*index) {
+            if column < tab {
+                return Some(tab);
+            }
+            *index += 1;
+        }
+        if self.extend_size != 0 {
+            return Some(column + (self.extend_size - column % self.extend_size));
+        }
+        if self.increment_size != 0 {
+            let end_tab = self.list.last().copied().unwrap_or(0);
+            return Some(column + (self.increment_size - (column - end_tab) % self.increment_size));
+        }
+        None
+    }
+}
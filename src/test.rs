@@ -0,0 +1,484 @@
+/*
+ * test / [ - check file types and compare values
+ *
+ * Invoked as `[` the last argument must be `]`, that being a link to the test binary
+ * made by hand since cargo refuses `[` as a binary name. Parsing follows GNU: 1 to 4
+ * arguments use POSIX's rules by argument count (so `test -f = -f` compares strings),
+ * more than that go through the -o/-a/!/( ) grammar. Exits 0 for true, 1 for false
+ * and 2 on syntax errors.
+ */
+
+use crate::quote;
+use nix::unistd;
+use std::cmp::Ordering;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: test EXPRESSION
+  or:  test
+  or:  [ EXPRESSION ]
+  or:  [ ]
+  or:  [ OPTION
+Exit with the status determined by EXPRESSION.
+
+      --help     display this help and exit
+      --version  output version information and exit
+
+An omitted EXPRESSION defaults to false.  Otherwise,
+EXPRESSION is true or false and sets exit status.  It is one of:
+
+  ( EXPRESSION )               EXPRESSION is true
+  ! EXPRESSION                 EXPRESSION is false
+  EXPRESSION1 -a EXPRESSION2   both EXPRESSION1 and EXPRESSION2 are true
+  EXPRESSION1 -o EXPRESSION2   either EXPRESSION1 or EXPRESSION2 is true
+
+  -n STRING            the length of STRING is nonzero
+  STRING               equivalent to -n STRING
+  -z STRING            the length of STRING is zero
+  STRING1 = STRING2    the strings are equal
+  STRING1 != STRING2   the strings are not equal
+
+  INTEGER1 -eq INTEGER2   INTEGER1 is equal to INTEGER2
+  INTEGER1 -ge INTEGER2   INTEGER1 is greater than or equal to INTEGER2
+  INTEGER1 -gt INTEGER2   INTEGER1 is greater than INTEGER2
+  INTEGER1 -le INTEGER2   INTEGER1 is less than or equal to INTEGER2
+  INTEGER1 -lt INTEGER2   INTEGER1 is less than INTEGER2
+  INTEGER1 -ne INTEGER2   INTEGER1 is not equal to INTEGER2
+
+  FILE1 -ef FILE2   FILE1 and FILE2 have the same device and inode numbers
+  FILE1 -nt FILE2   FILE1 is newer (modification date) than FILE2
+  FILE1 -ot FILE2   FILE1 is older than FILE2
+
+  -b FILE     FILE exists and is block special
+  -c FILE     FILE exists and is character special
+  -d FILE     FILE exists and is a directory
+  -e FILE     FILE exists
+  -f FILE     FILE exists and is a regular file
+  -g FILE     FILE exists and is set-group-ID
+  -G FILE     FILE exists and is owned by the effective group ID
+  -h FILE     FILE exists and is a symbolic link (same as -L)
+  -k FILE     FILE exists and has its sticky bit set
+  -L FILE     FILE exists and is a symbolic link (same as -h)
+  -N FILE     FILE exists and has been modified since it was last read
+  -O FILE     FILE exists and is owned by the effective user ID
+  -p FILE     FILE exists and is a named pipe
+  -r FILE     FILE exists and the user has read access
+  -s FILE     FILE exists and has a size greater than zero
+  -S FILE     FILE exists and is a socket
+  -t FD       file descriptor FD is opened on a terminal
+  -u FILE     FILE exists and its set-user-ID bit is set
+  -w FILE     FILE exists and the user has write access
+  -x FILE     FILE exists and the user has execute (or search) access
+
+Except for -h and -L, all FILE-related tests dereference symbolic links.
+Beware that parentheses need to be escaped (e.g., by backslashes) for shells.
+INTEGER may also be -l STRING, which evaluates to the length of STRING.
+";
+
+const BINARY_OPERATORS: [&[u8]; 12] = [
+    b"=", b"!=", b"==", b"-nt", b"-ot", b"-ef", b"-eq", b"-ne", b"-lt", b"-le", b"-gt", b"-ge",
+];
+
+fn is_binary_operator(s: &[u8]) -> bool {
+    BINARY_OPERATORS.contains(&s)
+}
+
+fn is_unary_operator(s: &[u8]) -> bool {
+    matches!(s, [b'-', c] if b"bcdefghknprstuwxzGLOSN".contains(c))
+}
+
+fn path(s: &[u8]) -> &Path {
+    Path::new(OsStr::from_bytes(s))
+}
+
+fn stat(s: &[u8]) -> Option<Metadata> {
+    fs::metadata(path(s)).ok()
+}
+
+fn mtime(s: &[u8]) -> Option<(i64, i64)> {
+    stat(s).map(|meta| (meta.mtime(), meta.mtime_nsec()))
+}
+
+fn is_blank(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+/// Validate an integer operand: optional blanks around an optionally signed run of
+/// digits. Returns the sign and digits without leading zeros, so any size compares.
+fn integer(s: &[u8]) -> Result<(bool, &[u8]), String> {
+    let trimmed = &s[s.iter().take_while(|c| is_blank(**c)).count()..];
+    let (negative, number) = match trimmed.first() {
+        Some(b'+') => (false, &trimmed[1..]),
+        Some(b'-') => (true, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    let digits = number.iter().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 || !number[digits..].iter().all(|c| is_blank(*c)) {
+        return Err(format!("invalid integer {}", quote(s)));
+    }
+    let number = &number[..digits];
+    let number = &number[number.iter().take_while(|c| **c == b'0').count()..];
+    Ok((negative && !number.is_empty(), number))
+}
+
+fn compare_integers((l_negative, l): (bool, &[u8]), (r_negative, r): (bool, &[u8])) -> Ordering {
+    let magnitude = l.len().cmp(&r.len()).then_with(|| l.cmp(r));
+    match (l_negative, r_negative) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+struct Test {
+    args: Vec<Vec<u8>>,
+    // Number of arguments, not counting the closing `]`
+    argc: usize,
+    pos: usize,
+}
+
+type Result<T, E = String> = std::result::Result<T, E>;
+
+impl Test {
+    // Like GNU's argv[pos], where the `]` of the `[` form is still there past the end
+    fn arg(&self, i: usize) -> Option<&[u8]> {
+        self.args.get(i).map(Vec::as_slice)
+    }
+
+    fn is(&self, i: usize, s: &[u8]) -> bool {
+        self.arg(i) == Some(s)
+    }
+
+    fn beyond(&self) -> String {
+        format!(
+            "missing argument after {}",
+            quote(&self.args[self.argc - 1])
+        )
+    }
+
+    fn advance(&mut self, need_more: bool) -> Result<()> {
+        self.pos += 1;
+        if need_more && self.pos >= self.argc {
+            return Err(self.beyond());
+        }
+        Ok(())
+    }
+
+    fn integer_operand(&self, i: usize, length_of: bool) -> Result<(bool, Vec<u8>)> {
+        let s = &self.args[i];
+        match length_of {
+            true => Ok((false, s.len().to_string().into_bytes())),
+            false => integer(s).map(|(negative, digits)| (negative, digits.to_vec())),
+        }
+    }
+
+    fn binary_operator(&mut self, l_is_l: bool) -> Result<bool> {
+        if l_is_l {
+            self.advance(false)?;
+        }
+        let op = self.pos + 1;
+        // `INTEGER -op -l STRING`
+        let r_is_l = op + 2 < self.argc && self.is(op + 1, b"-l");
+        if r_is_l {
+            self.advance(false)?;
+        }
+        let operator = self.args[op].clone();
+        match operator.as_slice() {
+            b"-eq" | b"-ne" | b"-lt" | b"-le" | b"-gt" | b"-ge" => {
+                let (l, l_digits) = self.integer_operand(op - 1, l_is_l)?;
+                let (r, r_digits) = match r_is_l {
+                    true => self.integer_operand(op + 2, true)?,
+                    false => self.integer_operand(op + 1, false)?,
+                };
+                let cmp = compare_integers((l, &l_digits), (r, &r_digits));
+                self.pos += 3;
+                Ok(match &operator[1..] {
+                    b"eq" => cmp == Ordering::Equal,
+                    b"ne" => cmp != Ordering::Equal,
+                    b"lt" => cmp == Ordering::Less,
+                    b"le" => cmp != Ordering::Greater,
+                    b"gt" => cmp == Ordering::Greater,
+                    _ => cmp != Ordering::Less,
+                })
+            }
+            b"-nt" | b"-ot" | b"-ef" => {
+                self.pos += 3;
+                if l_is_l || r_is_l {
+                    return Err(format!(
+                        "{} does not accept -l",
+                        String::from_utf8_lossy(&operator)
+                    ));
+                }
+                let (l, r) = (&self.args[op - 1], &self.args[op + 1]);
+                Ok(match operator.as_slice() {
+                    b"-nt" => mtime(l).is_some_and(|lt| mtime(r).map_or(true, |rt| lt > rt)),
+                    b"-ot" => mtime(r).is_some_and(|rt| mtime(l).map_or(true, |lt| lt < rt)),
+                    _ => match (stat(l), stat(r)) {
+                        (Some(l), Some(r)) => l.dev() == r.dev() && l.ino() == r.ino(),
+                        _ => false,
+                    },
+                })
+            }
+            // `=`, `==` and `!=` compare the strings around the operator position, which
+            // the `-l` forms shift like GNU does
+            _ => {
+                let equal = self.args[self.pos] == self.args[self.pos + 2];
+                self.pos += 3;
+                Ok(equal != (operator == b"!="))
+            }
+        }
+    }
+
+    fn unary_operator(&mut self) -> Result<bool> {
+        let operator = self.args[self.pos][1];
+        self.advance(true)?;
+        self.pos += 1;
+        let operand = &self.args[self.pos - 1];
+        // euidaccess(3) like GNU, ie. root may read anything but execute only when some
+        // execute bit is set
+        let access = |mode| {
+            let operand = CString::new(operand.as_slice()).unwrap();
+            unsafe { libc::euidaccess(operand.as_ptr(), mode) == 0 }
+        };
+        let mode = |bit| stat(operand).is_some_and(|meta| meta.mode() & bit != 0);
+        Ok(match operator {
+            b'e' => stat(operand).is_some(),
+            b'r' => access(libc::R_OK),
+            b'w' => access(libc::W_OK),
+            b'x' => access(libc::X_OK),
+            b'N' => stat(operand).is_some_and(|meta| {
+                (meta.mtime(), meta.mtime_nsec()) > (meta.atime(), meta.atime_nsec())
+            }),
+            b'O' => stat(operand).is_some_and(|meta| meta.uid() == unistd::geteuid().as_raw()),
+            b'G' => stat(operand).is_some_and(|meta| meta.gid() == unistd::getegid().as_raw()),
+            b'f' => stat(operand).is_some_and(|meta| meta.is_file()),
+            b'd' => stat(operand).is_some_and(|meta| meta.is_dir()),
+            b's' => stat(operand).is_some_and(|meta| meta.len() > 0),
+            b'S' => stat(operand).is_some_and(|meta| meta.file_type().is_socket()),
+            b'c' => stat(operand).is_some_and(|meta| meta.file_type().is_char_device()),
+            b'b' => stat(operand).is_some_and(|meta| meta.file_type().is_block_device()),
+            b'p' => stat(operand).is_some_and(|meta| meta.file_type().is_fifo()),
+            b'h' | b'L' => {
+                fs::symlink_metadata(path(operand)).is_ok_and(|meta| meta.file_type().is_symlink())
+            }
+            b'u' => mode(libc::S_ISUID),
+            b'g' => mode(libc::S_ISGID),
+            b'k' => mode(libc::S_ISVTX),
+            b't' => {
+                let (negative, digits) = integer(operand)?;
+                let fd = std::str::from_utf8(digits)
+                    .ok()
+                    .and_then(|digits| digits.parse::<i32>().ok());
+                match (negative, fd) {
+                    (false, Some(fd)) => unsafe { libc::isatty(fd) != 0 },
+                    (false, None) if digits.is_empty() => unsafe { libc::isatty(0) != 0 },
+                    _ => false,
+                }
+            }
+            b'n' => !operand.is_empty(),
+            _ => operand.is_empty(),
+        })
+    }
+
+    fn one_argument(&mut self) -> bool {
+        self.pos += 1;
+        !self.args[self.pos - 1].is_empty()
+    }
+
+    fn two_arguments(&mut self) -> Result<bool> {
+        if self.is(self.pos, b"!") {
+            self.advance(false)?;
+            return Ok(!self.one_argument());
+        }
+        let arg = &self.args[self.pos];
+        if arg.len() == 2 && arg[0] == b'-' {
+            if is_unary_operator(arg) {
+                return self.unary_operator();
+            }
+            return Err(format!("{}: unary operator expected", quote(arg)));
+        }
+        Err(self.beyond())
+    }
+
+    fn three_arguments(&mut self) -> Result<bool> {
+        if is_binary_operator(&self.args[self.pos + 1]) {
+            self.binary_operator(false)
+        } else if self.is(self.pos, b"!") {
+            self.advance(true)?;
+            Ok(!self.two_arguments()?)
+        } else if self.is(self.pos, b"(") && self.is(self.pos + 2, b")") {
+            self.advance(false)?;
+            let value = self.one_argument();
+            self.advance(false)?;
+            Ok(value)
+        } else if self.is(self.pos + 1, b"-a") || self.is(self.pos + 1, b"-o") {
+            self.expr()
+        } else {
+            Err(format!(
+                "{}: binary operator expected",
+                quote(&self.args[self.pos + 1])
+            ))
+        }
+    }
+
+    /// POSIX's rules for the number of remaining arguments, the full grammar beyond 4
+    fn posix_test(&mut self, nargs: usize) -> Result<bool> {
+        match nargs {
+            1 => Ok(self.one_argument()),
+            2 => self.two_arguments(),
+            3 => self.three_arguments(),
+            4 if self.is(self.pos, b"!") => {
+                self.advance(true)?;
+                Ok(!self.three_arguments()?)
+            }
+            4 if self.is(self.pos, b"(") && self.is(self.pos + 3, b")") => {
+                self.advance(false)?;
+                let value = self.two_arguments()?;
+                self.advance(false)?;
+                Ok(value)
+            }
+            _ => self.expr(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<bool> {
+        if self.pos >= self.argc {
+            return Err(self.beyond());
+        }
+        self.or()
+    }
+
+    fn or(&mut self) -> Result<bool> {
+        let mut value = false;
+        loop {
+            value |= self.and()?;
+            if !(self.pos < self.argc && self.is(self.pos, b"-o")) {
+                return Ok(value);
+            }
+            self.advance(false)?;
+        }
+    }
+
+    fn and(&mut self) -> Result<bool> {
+        let mut value = true;
+        loop {
+            value &= self.term()?;
+            if !(self.pos < self.argc && self.is(self.pos, b"-a")) {
+                return Ok(value);
+            }
+            self.advance(false)?;
+        }
+    }
+
+    fn term(&mut self) -> Result<bool> {
+        let mut negated = false;
+        while self.pos < self.argc && self.is(self.pos, b"!") {
+            self.advance(true)?;
+            negated = !negated;
+        }
+        if self.pos >= self.argc {
+            return Err(self.beyond());
+        }
+
+        let remaining = self.argc - self.pos;
+        let value = if self.is(self.pos, b"(") {
+            self.advance(true)?;
+            // Up to the closing parenthesis, when it's close enough for POSIX's rules
+            let mut nargs = 1;
+            while self.pos + nargs < self.argc && !self.is(self.pos + nargs, b")") {
+                if nargs == 4 {
+                    nargs = self.argc - self.pos;
+                    break;
+                }
+                nargs += 1;
+            }
+            let value = self.posix_test(nargs)?;
+            match self.arg(self.pos) {
+                None => return Err(format!("{} expected", quote(b")"))),
+                Some(b")") => {}
+                Some(found) => {
+                    return Err(format!("{} expected, found {}", quote(b")"), quote(found)))
+                }
+            }
+            self.advance(false)?;
+            value
+        } else if remaining >= 4
+            && self.is(self.pos, b"-l")
+            && is_binary_operator(&self.args[self.pos + 2])
+        {
+            self.binary_operator(true)?
+        } else if remaining >= 3 && is_binary_operator(&self.args[self.pos + 1]) {
+            self.binary_operator(false)?
+        } else if self.args[self.pos].len() == 2 && self.args[self.pos][0] == b'-' {
+            if !is_unary_operator(&self.args[self.pos]) {
+                return Err(format!(
+                    "{}: unary operator expected",
+                    quote(&self.args[self.pos])
+                ));
+            }
+            self.unary_operator()?
+        } else {
+            self.one_argument()
+        };
+        Ok(negated ^ value)
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Vec<Vec<u8>> = args.into_iter().map(OsStringExt::into_vec).collect();
+    let bracket = args
+        .first()
+        .is_some_and(|arg0| path(arg0).file_name() == Some(OsStr::new("[")));
+    let prog = match bracket {
+        true => "[",
+        false => "test",
+    };
+
+    let mut argc = args.len();
+    if bracket {
+        // POSIX wants `test --help` to be a plain string test, but `[` gets the options
+        if argc == 2 {
+            match args[1].as_slice() {
+                b"--help" => {
+                    print!("{USAGE}");
+                    return ExitCode::SUCCESS;
+                }
+                b"--version" => {
+                    println!("[ {}", env!("CARGO_PKG_VERSION"));
+                    return ExitCode::SUCCESS;
+                }
+                _ => {}
+            }
+        }
+        if argc < 2 || args[argc - 1] != b"]" {
+            eprintln!("{prog}: missing {}", quote(b"]"));
+            return ExitCode::from(2);
+        }
+        argc -= 1;
+    }
+
+    let mut test = Test { args, argc, pos: 1 };
+    if argc <= 1 {
+        return ExitCode::FAILURE;
+    }
+    let value = test
+        .posix_test(argc - 1)
+        .and_then(|value| match test.pos == test.argc {
+            true => Ok(value),
+            false => Err(format!("extra argument {}", quote(&test.args[test.pos]))),
+        });
+    match value {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(message) => {
+            eprintln!("{prog}: {message}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,364 @@
+/*
+ * timeout - run a command with a time limit
+ *
+ * Like GNU the command is forked off with the signals timeout handles blocked, and
+ * they are only let in while waiting in sigsuspend(2), so the handler just records
+ * them and the waiting loop does the signalling: the timer expiring sends the timeout
+ * signal, INT, QUIT, HUP and TERM are passed on, and --kill-after arms a second timer
+ * for KILL. Unless --foreground, timeout moves to a process group of its own, which
+ * the command inherits, and the signals go to the whole group so its children get
+ * them too. Statuses are 124 on timeout, 125 for timeout's own errors, 126/127 when
+ * the command can't be run, else the command's; one killed by a signal is replicated
+ * by timeout killing itself the same way.
+ */
+
+use crate::{errno_desc, exec_command, exec_status, parse_args, quote};
+use clap::Parser;
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use nix::unistd::{fork, setpgid, ForkResult, Pid};
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Parser)]
+#[command(name = "timeout", version, long_about = None)]
+#[command(about = "Start COMMAND, and kill it if still running after DURATION. \
+DURATION is a floating point number with an optional suffix: 's' for seconds (the \
+default), 'm' for minutes, 'h' for hours or 'd' for days. A duration of 0 disables \
+the associated timeout")]
+#[command(override_usage = "timeout [OPTION] DURATION COMMAND [ARG]...")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Exit with the same status as COMMAND, even when the command times out
+    #[clap(long, action)]
+    preserve_status: bool,
+    /// When not running timeout directly from a shell prompt, allow COMMAND to read from
+    /// the TTY and get TTY signals; in this mode, children of COMMAND will not be timed
+    /// out
+    #[clap(long, action)]
+    foreground: bool,
+    /// Also send a KILL signal if COMMAND is still running this long after the initial
+    /// signal was sent
+    #[clap(long, short, value_name = "DURATION")]
+    kill_after: Option<OsString>,
+    /// Specify the signal to be sent on timeout, TERM by default; SIGNAL may be a name
+    /// like 'HUP' or a number
+    #[clap(long, short, value_name = "SIGNAL")]
+    signal: Option<OsString>,
+    /// Diagnose to stderr any signal sent upon timeout
+    #[clap(long, short, action)]
+    verbose: bool,
+    /// The duration, then the command to run and its arguments
+    #[clap(value_name = "DURATION COMMAND", trailing_var_arg = true)]
+    operands: Vec<OsString>,
+}
+
+const EXIT_TIMEDOUT: u8 = 124;
+const EXIT_CANCELED: u8 = 125;
+
+// A duration like GNU's: a non-negative floating point number of seconds, or of
+// minutes, hours or days with an m, h or d suffix
+fn parse_duration(s: &[u8]) -> Option<f64> {
+    let (number, multiplier) = match s.last() {
+        Some(b's') => (&s[..s.len() - 1], 1.0),
+        Some(b'm') => (&s[..s.len() - 1], 60.0),
+        Some(b'h') => (&s[..s.len() - 1], 60.0 * 60.0),
+        Some(b'd') => (&s[..s.len() - 1], 60.0 * 60.0 * 24.0),
+        _ => (s, 1.0),
+    };
+    let number = std::str::from_utf8(number).ok()?;
+    let number = number.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    // NaN isn't >= 0 either
+    match number.parse::<f64>() {
+        Ok(duration) if duration >= 0.0 => Some(duration * multiplier),
+        _ => None,
+    }
+}
+
+// A signal number, or a name with or without the SIG prefix, RTMIN+N and RTMAX-N too
+fn parse_signal(s: &[u8]) -> Option<i32> {
+    let s = std::str::from_utf8(s).ok()?;
+    if !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit()) {
+        return s.parse().ok().filter(|&n| n <= libc::SIGRTMAX());
+    }
+    let name = s.strip_prefix("SIG").unwrap_or(s);
+    let realtime = [
+        ("RTMIN", libc::SIGRTMIN(), '+', 1),
+        ("RTMAX", libc::SIGRTMAX(), '-', -1),
+    ];
+    for (prefix, base, sign, step) in realtime {
+        let Some(rest) = name.strip_prefix(prefix) else {
+            continue;
+        };
+        let offset: i32 = match rest.strip_prefix(sign) {
+            None if rest.is_empty() => 0,
+            Some(n) if !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()) => n.parse().ok()?,
+            _ => return None,
+        };
+        let sig = base.checked_add(step * offset)?;
+        return (libc::SIGRTMIN()..=libc::SIGRTMAX())
+            .contains(&sig)
+            .then_some(sig);
+    }
+    Signal::iterator()
+        .find(|signal| &signal.as_str()[3..] == name)
+        .map(|signal| signal as i32)
+}
+
+// The name in -v messages, ie. TERM
+fn signal_name(sig: i32) -> String {
+    match Signal::try_from(sig) {
+        Ok(signal) => signal.as_str()[3..].to_string(),
+        Err(_) => sig.to_string(),
+    }
+}
+
+// Signals caught while waiting, bit N - 1 for signal N
+static RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn record(sig: libc::c_int) {
+    RECEIVED.fetch_or(1 << (sig - 1), Ordering::SeqCst);
+}
+
+// Arm the real time timer to send SIGALRM after `duration` seconds, 0 disarming it
+fn set_timer(duration: f64) {
+    let mut sec = duration.trunc();
+    let mut usec = ((duration - sec) * 1e6).ceil();
+    if usec >= 1e6 {
+        sec += 1.0;
+        usec = 0.0;
+    }
+    let zero = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let timer = libc::itimerval {
+        it_interval: zero,
+        // Huge durations saturate
+        it_value: libc::timeval {
+            tv_sec: sec as libc::time_t,
+            tv_usec: usec as libc::suseconds_t,
+        },
+    };
+    unsafe { libc::setitimer(libc::ITIMER_REAL, &timer, std::ptr::null_mut()) };
+}
+
+// Signal numbers rather than nix's Signal, which leaves out the real time ones
+fn set_handler(sig: i32, handler: libc::sighandler_t) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut());
+    }
+}
+
+fn signal_set(signals: &[i32]) -> libc::sigset_t {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for &sig in signals {
+            libc::sigaddset(&mut set, sig);
+        }
+        set
+    }
+}
+
+// Send `sig` to `pid`, or to timeout's whole group with 0, which it's itself in: it
+// then ignores the signal from now on so it doesn't keep passing it on
+fn send_signal(pid: i32, sig: i32) {
+    if pid == 0 {
+        set_handler(sig, libc::SIG_IGN);
+    }
+    unsafe { libc::kill(pid, sig) };
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("timeout: {message}\nTry 'timeout --help' for more information.");
+    ExitCode::from(EXIT_CANCELED)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, EXIT_CANCELED);
+    let kill_after = match &args.kill_after {
+        Some(given) => match parse_duration(given.as_bytes()) {
+            Some(kill_after) => kill_after,
+            None => {
+                return usage_error(&format!(
+                    "invalid time interval {}",
+                    quote(given.as_bytes())
+                ))
+            }
+        },
+        None => 0.0,
+    };
+    let mut term_signal = match &args.signal {
+        Some(given) => match parse_signal(given.as_bytes()) {
+            Some(sig) => sig,
+            None => return usage_error(&format!("{}: invalid signal", quote(given.as_bytes()))),
+        },
+        None => libc::SIGTERM,
+    };
+    if args.operands.len() < 2 {
+        eprintln!("Try 'timeout --help' for more information.");
+        return ExitCode::from(EXIT_CANCELED);
+    }
+    let Some(duration) = parse_duration(args.operands[0].as_bytes()) else {
+        let given = quote(args.operands[0].as_bytes());
+        return usage_error(&format!("invalid time interval {given}"));
+    };
+    let command = &args.operands[1..];
+
+    // The command inherits the group, so all its processes can be signalled at once
+    if !args.foreground {
+        let _ = setpgid(Pid::from_raw(0), Pid::from_raw(0));
+    }
+    let record = record as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let handled = [
+        libc::SIGALRM,
+        libc::SIGINT,
+        libc::SIGQUIT,
+        libc::SIGHUP,
+        libc::SIGTERM,
+        term_signal,
+    ];
+    // KILL and STOP can't be caught, they're just sent
+    for sig in handled.into_iter().chain([libc::SIGCHLD]) {
+        set_handler(sig, record);
+    }
+    // A command in the background reading or writing the terminal mustn't stop timeout
+    set_handler(libc::SIGTTIN, libc::SIG_IGN);
+    set_handler(libc::SIGTTOU, libc::SIG_IGN);
+
+    // An inherited mask blocking SIGALRM would be restored for sigsuspend
+    let mut original = signal_set(&[]);
+    unsafe {
+        libc::sigprocmask(
+            libc::SIG_UNBLOCK,
+            &signal_set(&[libc::SIGALRM]),
+            std::ptr::null_mut(),
+        );
+        let blocked = signal_set(&[&handled[..], &[libc::SIGCHLD]].concat());
+        libc::sigprocmask(libc::SIG_BLOCK, &blocked, &mut original);
+    }
+
+    let child = match unsafe { fork() } {
+        Ok(ForkResult::Parent { child }) => child.as_raw(),
+        Ok(ForkResult::Child) => {
+            // A signal arriving before the exec terminates the child as it would the command
+            for sig in handled.into_iter().chain([libc::SIGTTIN, libc::SIGTTOU]) {
+                set_handler(sig, libc::SIG_DFL);
+            }
+            if unsafe { libc::sigprocmask(libc::SIG_SETMASK, &original, std::ptr::null_mut()) } != 0
+            {
+                let e = Errno::last();
+                eprintln!(
+                    "timeout: child failed to reset signal mask: {}",
+                    errno_desc(e)
+                );
+                return ExitCode::from(EXIT_CANCELED);
+            }
+            let e = exec_command(command);
+            eprintln!(
+                "timeout: failed to run command {}: {}",
+                quote(command[0].as_bytes()),
+                errno_desc(e)
+            );
+            return exec_status(e);
+        }
+        Err(e) => {
+            eprintln!("timeout: fork system call failed: {}", errno_desc(e));
+            return ExitCode::from(EXIT_CANCELED);
+        }
+    };
+
+    if duration > 0.0 {
+        set_timer(duration);
+    }
+    let mut kill_after = Some(kill_after).filter(|&kill_after| kill_after > 0.0);
+    let mut timed_out = false;
+    let mut status = 0;
+    let waited = loop {
+        match unsafe { libc::waitpid(child, &mut status, libc::WNOHANG) } {
+            0 => {}
+            waited => break waited,
+        }
+        // Signals are only let in here, the child exiting interrupts it too
+        unsafe { libc::sigsuspend(&original) };
+        let received = RECEIVED.swap(0, Ordering::SeqCst);
+        for sig in (1..=64).filter(|sig| received & (1 << (sig - 1)) != 0) {
+            let sig = match sig {
+                libc::SIGCHLD => continue,
+                libc::SIGALRM => {
+                    timed_out = true;
+                    term_signal
+                }
+                sig => sig,
+            };
+            // Only the first signal sent arms the KILL timer
+            if let Some(kill_after) = kill_after.take() {
+                term_signal = libc::SIGKILL;
+                set_timer(kill_after);
+            }
+            if args.verbose {
+                let name = signal_name(sig);
+                eprintln!(
+                    "timeout: sending signal {name} to command {}",
+                    quote(command[0].as_bytes())
+                );
+            }
+            // Directly too, in case the command became a group leader itself
+            send_signal(child, sig);
+            if !args.foreground {
+                send_signal(0, sig);
+                // Stopped processes wouldn't act on it
+                if sig != libc::SIGKILL && sig != libc::SIGCONT {
+                    send_signal(child, libc::SIGCONT);
+                    send_signal(0, libc::SIGCONT);
+                }
+            }
+        }
+    };
+
+    let mut preserve_status = args.preserve_status;
+    let status = if waited < 0 {
+        eprintln!(
+            "timeout: error waiting for child: {}",
+            errno_desc(Errno::last())
+        );
+        EXIT_CANCELED
+    } else if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status) as u8
+    } else if libc::WIFSIGNALED(status) {
+        let sig = libc::WTERMSIG(status);
+        if libc::WCOREDUMP(status) {
+            eprintln!("timeout: the monitored command dumped core");
+        }
+        // Die of the same signal, without a core dump of timeout itself
+        if !timed_out {
+            match unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } {
+                0 => unsafe {
+                    set_handler(sig, libc::SIG_DFL);
+                    libc::sigprocmask(libc::SIG_UNBLOCK, &signal_set(&[sig]), std::ptr::null_mut());
+                    libc::raise(sig);
+                },
+                _ => eprintln!("timeout: warning: disabling core dumps failed"),
+            }
+        }
+        // Forcibly killed, even with --foreground where timeout itself isn't
+        if timed_out && sig == libc::SIGKILL {
+            preserve_status = true;
+        }
+        128 + sig as u8
+    } else {
+        eprintln!("timeout: unknown status from command ({status})");
+        1
+    };
+    match timed_out && !preserve_status {
+        true => ExitCode::from(EXIT_TIMEDOUT),
+        false => ExitCode::from(status),
+    }
+}
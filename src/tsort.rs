@@ -0,0 +1,228 @@
+/*
+ * tsort - topological sort
+ *
+ * GNU's algorithm (Knuth's, TAOCP 2.2.3) so that the order among nodes that could come
+ * in any order is GNU's too, which Makefiles and the like end up diffing: the nodes
+ * without predecessors are queued in byte order of their names, successors are
+ * visited latest pair first, and a node is printed as soon as its last predecessor is.
+ * When only nodes on or after a loop are left, a loop is searched the same way, its
+ * members are reported and one of its pairs is dropped before carrying on, so every
+ * node still gets printed. A pair of the same node declares it without an edge.
+ */
+
+use crate::{parse_args, quote, reset_sigpipe, shell_quote, stdout_file, strerror};
+use clap::Parser;
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "tsort", version, long_about = None)]
+#[command(
+    about = "Write totally ordered list consistent with the partial ordering in FILE. With \
+no FILE, or when FILE is -, read standard input"
+)]
+#[command(override_usage = "tsort [OPTION] [FILE]")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+#[derive(Debug)]
+struct Node<'a> {
+    name: &'a [u8],
+    // Predecessors not printed yet
+    count: usize,
+    // In the order of the pairs, visited from the last
+    successors: Vec<usize>,
+    // The previous node of the path searched for a loop
+    previous: Option<usize>,
+    printed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Graph<'a> {
+    nodes: Vec<Node<'a>>,
+    // Nodes by name, which is the order they're scanned in
+    names: BTreeMap<&'a [u8], usize>,
+    // The head of the path searched for a loop
+    path: Option<usize>,
+}
+
+impl<'a> Graph<'a> {
+    fn node(&mut self, name: &'a [u8]) -> usize {
+        let nodes = &mut self.nodes;
+        *self.names.entry(name).or_insert_with(|| {
+            nodes.push(Node {
+                name,
+                count: 0,
+                successors: Vec::new(),
+                previous: None,
+                printed: false,
+            });
+            nodes.len() - 1
+        })
+    }
+
+    fn add_pair(&mut self, from: &'a [u8], to: &'a [u8]) {
+        let (from, to) = (self.node(from), self.node(to));
+        if from != to {
+            self.nodes[to].count += 1;
+            self.nodes[from].successors.push(to);
+        }
+    }
+
+    // Print every node with no predecessors left, and those it frees in turn
+    fn print_free(&mut self, out: &mut impl Write) -> io::Result<usize> {
+        let mut queue: VecDeque<usize> = (self.names.values().copied())
+            .filter(|&node| self.nodes[node].count == 0 && !self.nodes[node].printed)
+            .collect();
+        let mut printed = 0;
+        while let Some(node) = queue.pop_front() {
+            out.write_all(self.nodes[node].name)?;
+            out.write_all(b"\n")?;
+            self.nodes[node].printed = true;
+            printed += 1;
+            for i in (0..self.nodes[node].successors.len()).rev() {
+                let successor = self.nodes[node].successors[i];
+                self.nodes[successor].count -= 1;
+                if self.nodes[successor].count == 0 {
+                    queue.push_back(successor);
+                }
+            }
+        }
+        Ok(printed)
+    }
+
+    // One step of the search for a loop, like GNU's detect_loop: the first node with
+    // predecessors starts the path, then nodes leading to its head are added until one
+    // already on it closes a loop. That loop is reported and its last pair dropped
+    fn find_loop(&mut self, node: usize) -> bool {
+        if self.nodes[node].count == 0 {
+            return false;
+        }
+        let Some(head) = self.path else {
+            self.path = Some(node);
+            return false;
+        };
+        let successors = &self.nodes[node].successors;
+        let Some(at) = successors.iter().rposition(|&successor| successor == head) else {
+            return false;
+        };
+        if self.nodes[node].previous.is_none() {
+            self.nodes[node].previous = Some(head);
+            self.path = Some(node);
+            return false;
+        }
+
+        let mut on_path = self.path.take();
+        while let Some(member) = on_path {
+            let mut stderr = io::stderr().lock();
+            let name = self.nodes[member].name;
+            let _ = [b"tsort: ", name, b"\n"]
+                .iter()
+                .try_for_each(|s| stderr.write_all(s));
+            if member == node {
+                let successor = self.nodes[node].successors.remove(at);
+                self.nodes[successor].count -= 1;
+                break;
+            }
+            on_path = self.nodes[member].previous.take();
+        }
+        // The rest of the path is undone for the next search
+        while let Some(member) = on_path {
+            on_path = self.nodes[member].previous.take();
+        }
+        true
+    }
+}
+
+type Pair<'a> = (&'a [u8], &'a [u8]);
+
+// Whitespace separated tokens in pairs, None when one is left over
+fn pairs(input: &[u8]) -> Option<Vec<Pair<'_>>> {
+    let mut tokens = input
+        .split(|c| matches!(c, b' ' | b'\t' | b'\n'))
+        .filter(|token| !token.is_empty());
+    let mut pairs = Vec::new();
+    while let Some(from) = tokens.next() {
+        let to = tokens.next()?;
+        pairs.push((from, to));
+    }
+    Some(pairs)
+}
+
+fn tsort(input: &[u8], name: &str) -> io::Result<bool> {
+    let Some(pairs) = pairs(input) else {
+        eprintln!("tsort: {name}: input contains an odd number of tokens");
+        return Ok(false);
+    };
+    let mut graph = Graph::default();
+    for (from, to) in pairs {
+        graph.add_pair(from, to);
+    }
+
+    let mut out = BufWriter::new(stdout_file()?);
+    let mut left = graph.nodes.len();
+    let mut ok = true;
+    while left > 0 {
+        left -= graph.print_free(&mut out)?;
+        if left > 0 {
+            // What's sorted so far comes before the report, like GNU's
+            out.flush()?;
+            eprintln!("tsort: {name}: input contains a loop:");
+            ok = false;
+            // Every search extends the path by a node until the loop is found
+            let order: Vec<usize> = graph.names.values().copied().collect();
+            while !order.iter().any(|&node| graph.find_loop(node)) {}
+        }
+    }
+    out.flush()?;
+    Ok(ok)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    let file = match &args.operands[..] {
+        [] => None,
+        [file] if file == "-" => None,
+        [file] => Some(file),
+        [_, extra, ..] => {
+            let extra = quote(extra.as_bytes());
+            eprintln!("tsort: extra operand {extra}\nTry 'tsort --help' for more information.");
+            return ExitCode::FAILURE;
+        }
+    };
+    let name = file.map_or(String::from("-"), |file| {
+        shell_quote(file.as_bytes(), false)
+    });
+
+    let mut input: Box<dyn Read> = match file {
+        Some(file) => match File::open(file) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("tsort: {name}: {}", strerror(&e));
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut buffer = Vec::new();
+    if let Err(e) = input.read_to_end(&mut buffer) {
+        eprintln!("tsort: {name}: read error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    match tsort(&buffer, &name) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("tsort: write error: {}", strerror(&e));
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ * tty - print the file name of the terminal connected to standard input
+ *
+ * The name is ttyname(3) of fd 0 itself, not of a reopened /dev/stdin. Exit status
+ * is GNU's: 0 on a terminal, 1 when stdin isn't one, 2 on usage errors and 3 when
+ * the name can't be written.
+ */
+
+use crate::{parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::unistd::{isatty, ttyname};
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "tty", version, long_about = None)]
+#[command(about = "Print the file name of the terminal connected to standard input")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Print nothing, only return an exit status
+    #[clap(long, short, visible_alias = "quiet", action)]
+    silent: bool,
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+const STDIN_FD: i32 = 0;
+const TTY_STDIN_NOTTY: u8 = 1;
+const TTY_FAILURE: u8 = 2;
+const TTY_WRITE_ERROR: u8 = 3;
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, TTY_FAILURE);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("tty: extra operand {extra}\nTry 'tty --help' for more information.");
+        return ExitCode::from(TTY_FAILURE);
+    }
+    if args.silent {
+        return match isatty(STDIN_FD) {
+            Ok(true) => ExitCode::SUCCESS,
+            _ => ExitCode::from(TTY_STDIN_NOTTY),
+        };
+    }
+
+    let (mut name, status) = match ttyname(STDIN_FD) {
+        Ok(name) => (name.into_os_string().into_vec(), ExitCode::SUCCESS),
+        Err(_) => (b"not a tty".to_vec(), ExitCode::from(TTY_STDIN_NOTTY)),
+    };
+    name.push(b'\n');
+    let written = stdout_file().and_then(|mut out| out.write_all(&name));
+    if let Err(e) = written {
+        eprintln!("tty: write error: {}", strerror(&e));
+        return ExitCode::from(TTY_WRITE_ERROR);
+    }
+    status
+}
@@ -0,0 +1,114 @@
+/*
+ * uname - print system information
+ *
+ * Fields are printed in uname(2)'s order whatever the order of the options, -s
+ * alone being the default. Linux has no way to tell the processor type or the
+ * hardware platform, so like GNU -p and -i print "unknown" and -a leaves them out.
+ */
+
+use crate::{errno_desc, parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::sys::utsname::{uname, UtsName};
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "uname", version, long_about = None)]
+#[command(about = "Print certain system information, the kernel name by default")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Print all information, in the following order, except omit -p and -i if unknown
+    #[clap(long, short, action)]
+    all: bool,
+    /// Print the kernel name
+    #[clap(long, short = 's', action)]
+    kernel_name: bool,
+    /// Print the network node hostname
+    #[clap(long, short, action)]
+    nodename: bool,
+    /// Print the kernel release
+    #[clap(long, short = 'r', action)]
+    kernel_release: bool,
+    /// Print the kernel version
+    #[clap(long, short = 'v', action)]
+    kernel_version: bool,
+    /// Print the machine hardware name
+    #[clap(long, short, action)]
+    machine: bool,
+    /// Print the processor type (non-portable)
+    #[clap(long, short, action)]
+    processor: bool,
+    /// Print the hardware platform (non-portable)
+    #[clap(long, short = 'i', action)]
+    hardware_platform: bool,
+    /// Print the operating system
+    #[clap(long, short, action)]
+    operating_system: bool,
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+const UNKNOWN: &[u8] = b"unknown";
+const OPERATING_SYSTEM: &[u8] = b"GNU/Linux";
+
+/// uname(2), for uname and arch, failures being reported for `prog`
+pub(crate) fn system_name(prog: &str) -> Option<UtsName> {
+    match uname() {
+        Ok(uts) => Some(uts),
+        Err(e) => {
+            eprintln!("{prog}: cannot get system name: {}", errno_desc(e));
+            None
+        }
+    }
+}
+
+/// Write the fields as a line to stdout
+pub(crate) fn write_line(prog: &str, line: &[u8]) -> ExitCode {
+    let written = stdout_file().and_then(|mut out| out.write_all(&[line, b"\n"].concat()));
+    if let Err(e) = written {
+        eprintln!("{prog}: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("uname: extra operand {extra}\nTry 'uname --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let Some(uts) = system_name("uname") else {
+        return ExitCode::FAILURE;
+    };
+
+    let any = args.kernel_name
+        || args.nodename
+        || args.kernel_release
+        || args.kernel_version
+        || args.machine
+        || args.processor
+        || args.hardware_platform
+        || args.operating_system;
+    let fields = [
+        (args.all || args.kernel_name || !any, uts.sysname().as_bytes()),
+        (args.all || args.nodename, uts.nodename().as_bytes()),
+        (args.all || args.kernel_release, uts.release().as_bytes()),
+        (args.all || args.kernel_version, uts.version().as_bytes()),
+        (args.all || args.machine, uts.machine().as_bytes()),
+        // -a leaves these out when unknown, which they always are here, even if also
+        // asked for explicitly
+        (args.processor && !args.all, UNKNOWN),
+        (args.hardware_platform && !args.all, UNKNOWN),
+        (args.all || args.operating_system, OPERATING_SYSTEM),
+    ];
+    let line: Vec<&[u8]> = fields
+        .into_iter()
+        .filter_map(|(shown, field)| shown.then_some(field))
+        .collect();
+    write_line("uname", &line.join(&b' '))
+}
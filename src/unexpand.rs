@@ -0,0 +1,326 @@
+/*
+ * unexpand - convert spaces to tabs
+ *
+ * Blanks are held back until it's known whether they reach a tab stop: then they
+ * become a tab, otherwise they're written out as they were. A lone space right
+ * before a stop is kept as is unless more blanks follow it, like GNU does.
+ * Columns are counted in bytes and lines continue across input files.
+ */
+
+use crate::tabstops::TabStops;
+use crate::{parse_args, reset_sigpipe, stdout_file, strerror};
+use clap::{ArgAction, Parser};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "unexpand", version, long_about = None)]
+#[command(about = "Convert blanks in each FILE to tabs, writing to standard output")]
+#[command(next_line_help = true)]
+struct Cli {
+    /// Convert all blanks, instead of just initial blanks
+    #[clap(long, short, action)]
+    all: bool,
+    /// Convert only leading sequences of blanks (overrides -a)
+    #[clap(long, action)]
+    first_only: bool,
+    /// Use comma separated list of tab positions (enables -a). The last one may be
+    /// prefixed with '/' (tabs after it every multiple of N) or '+' (tabs after it
+    /// every N columns)
+    #[clap(long, short, value_name = "LIST", action = ArgAction::Append)]
+    tabs: Vec<String>,
+    /// Files to unexpand, stdin by default
+    files: Vec<OsString>,
+}
+
+const IO_BUFSIZE: usize = 1 << 17;
+
+/// Blank compression state, fed one byte at a time
+pub struct Unexpander<'a> {
+    stops: &'a TabStops,
+    all: bool,
+    // Still converting, ie. not past the leading blanks without `all`
+    convert: bool,
+    column: u64,
+    tab_index: usize,
+    // Blanks not written yet, the first of which came just before a tab stop
+    pending: Vec<u8>,
+    one_blank_before_tab_stop: bool,
+    // Leading blanks count as if the line was preceded by a blank
+    prev_blank: bool,
+}
+
+impl<'a> Unexpander<'a> {
+    pub fn new(stops: &'a TabStops, all: bool) -> Unexpander<'a> {
+        Unexpander {
+            stops,
+            all,
+            convert: true,
+            column: 0,
+            tab_index: 0,
+            pending: Vec::new(),
+            one_blank_before_tab_stop: false,
+            prev_blank: true,
+        }
+    }
+
+    pub fn push(&mut self, mut c: u8, out: &mut dyn Write) -> io::Result<()> {
+        if self.convert {
+            let blank = c == b' ' || c == b'\t';
+            if blank {
+                match self.stops.next_tab_column(self.column, &mut self.tab_index) {
+                    // Past the last tab stop, nothing left to convert on this line
+                    None => self.convert = false,
+                    Some(next_tab_column) => {
+                        if c == b'\t' {
+                            self.column = next_tab_column;
+                            if let Some(first) = self.pending.first_mut() {
+                                *first = b'\t';
+                            }
+                        } else {
+                            self.column += 1;
+                            if !(self.prev_blank && self.column == next_tab_column) {
+                                // Not yet known whether these will be replaced by a tab
+                                if self.column == next_tab_column {
+                                    self.one_blank_before_tab_stop = true;
+                                }
+                                self.pending.push(c);
+                                self.prev_blank = true;
+                                return Ok(());
+                            }
+                            // Replace the pending blanks by a tab or two
+                            c = b'\t';
+                            if let Some(first) = self.pending.first_mut() {
+                                *first = b'\t';
+                            }
+                        }
+                        // Drop the pending blanks, unless it was a single one just
+                        // before the previous tab stop
+                        self.pending.truncate(self.one_blank_before_tab_stop as usize);
+                    }
+                }
+            } else if c == b'\x08' {
+                // Go back one column, and reconsider the previous tab stop
+                self.column = self.column.saturating_sub(1);
+                self.tab_index = self.tab_index.saturating_sub(1);
+            } else {
+                self.column += 1;
+            }
+            self.flush_pending(out)?;
+            self.prev_blank = blank;
+            self.convert &= self.all || blank;
+        }
+        out.write_all(&[c])?;
+        if c == b'\n' {
+            *self = Unexpander::new(self.stops, self.all);
+        }
+        Ok(())
+    }
+
+    fn flush_pending(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            if self.pending.len() > 1 && self.one_blank_before_tab_stop {
+                self.pending[0] = b'\t';
+            }
+            out.write_all(&self.pending)?;
+            self.pending.clear();
+            self.one_blank_before_tab_stop = false;
+        }
+        Ok(())
+    }
+
+    /// Write out any blanks still held back at the end of input
+    pub fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.flush_pending(out)
+    }
+}
+
+// Rewrite the obsolete `-N[,N...]` form into `-t N[,N...]`, counting how many there were
+// since only an explicit -t implies -a. Like GNU the digits run on from one argument
+// to the next, a stop is only added at a comma or once all the options are read.
+fn obsolete_tabs(args: impl IntoIterator<Item = OsString>) -> (Vec<OsString>, usize) {
+    let mut rewritten = Vec::new();
+    let mut obsolete = 0;
+    let mut digits = String::new();
+    let mut tabs_value = false;
+    for arg in args {
+        let bytes = arg.as_bytes();
+        let is_obsolete = !tabs_value
+            && bytes.len() > 1
+            && bytes[0] == b'-'
+            && bytes[1..].iter().all(|&c| c.is_ascii_digit() || c == b',');
+        tabs_value = arg == "-t" || arg == "--tabs";
+        if !is_obsolete {
+            rewritten.push(arg);
+            continue;
+        }
+        digits.push_str(&arg.to_string_lossy()[1..]);
+        if let Some(comma) = digits.rfind(',') {
+            if comma > 0 {
+                rewritten.push(OsString::from(format!("-t{}", &digits[..comma])));
+                obsolete += 1;
+            }
+            digits.drain(..=comma);
+        }
+    }
+    if !digits.is_empty() {
+        let at = rewritten.iter().position(|arg| arg == "--").unwrap_or(rewritten.len());
+        rewritten.insert(at, OsString::from(format!("-t{digits}")));
+        obsolete += 1;
+    }
+    (rewritten, obsolete)
+}
+
+fn unexpand_file(
+    unexpander: &mut Unexpander,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        for &c in buf {
+            unexpander.push(c, out)?;
+        }
+        let n = buf.len();
+        input.consume(n);
+    }
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let (args, obsolete) = obsolete_tabs(args);
+    let args: Cli = parse_args(args, 1);
+    let mut stops = TabStops::default();
+    let parsed = args
+        .tabs
+        .iter()
+        .try_for_each(|list| stops.parse(list))
+        .and_then(|_| stops.finalize());
+    if let Err(e) = parsed {
+        eprintln!("unexpand: {e}");
+        return ExitCode::FAILURE;
+    }
+    let all = (args.all || args.tabs.len() > obsolete) && !args.first_only;
+    let stdout = match stdout_file() {
+        Ok(stdout) => stdout,
+        Err(e) => {
+            eprintln!("unexpand: {}", strerror(&e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut out = BufWriter::with_capacity(IO_BUFSIZE, stdout);
+    let files = match args.files.is_empty() {
+        true => vec![OsString::from("-")],
+        false => args.files,
+    };
+
+    let mut ok = true;
+    // Lines (and their columns) carry on from one file to the next
+    let mut unexpander = Unexpander::new(&stops, all);
+    for file in files {
+        let mut input: Box<dyn BufRead> = if file == "-" {
+            Box::new(BufReader::with_capacity(IO_BUFSIZE, io::stdin().lock()))
+        } else {
+            match File::open(&file) {
+                Ok(f) => Box::new(BufReader::with_capacity(IO_BUFSIZE, f)),
+                Err(e) => {
+                    eprintln!("unexpand: {}: {}", file.to_string_lossy(), strerror(&e));
+                    ok = false;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = unexpand_file(&mut unexpander, input.as_mut(), &mut out) {
+            eprintln!("unexpand: {}: {}", file.to_string_lossy(), strerror(&e));
+            ok = false;
+        }
+    }
+    if let Err(e) = unexpander.finish(&mut out).and_then(|_| out.flush()) {
+        eprintln!("unexpand: write error: {}", strerror(&e));
+        ok = false;
+    }
+    match ok {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expand::Expander;
+
+    fn stops(lists: &[&str]) -> TabStops {
+        let mut stops = TabStops::default();
+        for list in lists {
+            stops.parse(list).unwrap();
+        }
+        stops.finalize().unwrap();
+        stops
+    }
+
+    fn unexpand(input: &[u8], stops: &TabStops, all: bool) -> Vec<u8> {
+        let mut unexpander = Unexpander::new(stops, all);
+        let mut out = Vec::new();
+        for &c in input {
+            unexpander.push(c, &mut out).unwrap();
+        }
+        unexpander.finish(&mut out).unwrap();
+        out
+    }
+
+    fn expand(input: &[u8], stops: &TabStops) -> Vec<u8> {
+        let mut expander = Expander::new(stops, false);
+        let mut out = Vec::new();
+        for &c in input {
+            expander.push(c, &mut out).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn blanks() {
+        let every8 = stops(&[]);
+        // Matching GNU unexpand
+        assert_eq!(
+            unexpand(b"a       b       c\n        x  y   z\n", &every8, true),
+            b"a\tb\tc\n\tx  y   z\n"
+        );
+        assert_eq!(unexpand(b"        x       y\n", &every8, false), b"\tx       y\n");
+        // A single space before a stop stays one
+        assert_eq!(unexpand(b"abcdefg b\n", &every8, true), b"abcdefg b\n");
+        assert_eq!(unexpand(b"ab      c\n", &stops(&["2,4"]), true), b"ab\t    c\n");
+    }
+
+    #[test]
+    fn expand_round_trip() {
+        // Tabs where expand put their blanks come back as they were
+        for (input, lists) in [
+            (&b"a\tb\tc\n"[..], &[][..]),
+            (b"\tx\n\t\ty\n", &[]),
+            (b"ab\t\tc\td\n", &[]),
+            (b"ab\tc\td\n", &["2,5,9"]),
+            (b"a\tb\tc\td\n", &["4"]),
+            (b"\t\ta\tb\n", &["3,/5"]),
+        ] {
+            let stops = stops(lists);
+            let expanded = expand(input, &stops);
+            assert_eq!(unexpand(&expanded, &stops, true), input, "{lists:?}");
+        }
+        // Any text expands back to the same once its blanks are compressed
+        let text = b"  a    b\t c  \t  d e     f\n\t \t x y  z   \n  \x08 q\n";
+        for lists in [&[][..], &["3"], &["2,4,9"], &["4,+6"], &["1,/8"]] {
+            let stops = stops(lists);
+            for all in [false, true] {
+                let compressed = unexpand(&expand(text, &stops), &stops, all);
+                assert_eq!(expand(&compressed, &stops), expand(text, &stops), "{lists:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/*
+ * unlink - call the unlink function to remove the specified file
+ *
+ * Just unlink(2) on the one operand: no -f, no recursion and no check beforehand, a
+ * directory is handed to the syscall too and fails the way the kernel says (EISDIR on
+ * Linux). A symlink is removed itself, not what it points to.
+ */
+
+use crate::{errno_desc, parse_args, quote, shell_quote};
+use clap::Parser;
+use nix::unistd::unlink;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "unlink", version, long_about = None)]
+#[command(about = "Call the unlink function to remove the specified FILE")]
+#[command(override_usage = "unlink FILE\n       unlink OPTION")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    let args: Cli = parse_args(args, 1);
+    let try_help = "Try 'unlink --help' for more information.";
+    let file = match &args.operands[..] {
+        [] => {
+            eprintln!("unlink: missing operand\n{try_help}");
+            return ExitCode::FAILURE;
+        }
+        [file] => file.as_bytes(),
+        [_, extra, ..] => {
+            let extra = quote(extra.as_bytes());
+            eprintln!("unlink: extra operand {extra}\n{try_help}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = unlink(file) {
+        eprintln!(
+            "unlink: cannot unlink {}: {}",
+            shell_quote(file, true),
+            errno_desc(e)
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
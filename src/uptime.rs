@@ -0,0 +1,248 @@
+/*
+ * uptime - tell how long the system has been running
+ *
+ * The traditional line: the time, how long since boot, the users logged in and the 1,
+ * 5 and 15 minute load averages, laid out like procps' uptime ("up 3 days,  2:05" but
+ * "up 45 min"). Uptime and load are /proc/uptime and /proc/loadavg, or sysinfo(2) when
+ * those can't be read. Users are the USER_PROCESS entries of FILE (/var/run/utmp by
+ * default), `?` when it can't be read. Both come from a `Source` so that the line can
+ * be tested with made up ones.
+ */
+
+use crate::strftime::{strftime, Time};
+use crate::{quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::sys::sysinfo::sysinfo;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+use std::time::SystemTime;
+
+#[derive(Debug, Parser)]
+#[command(name = "uptime", version, long_about = None)]
+#[command(
+    about = "Print the current time, the length of time the system has been up, the number \
+of users on the system, and the average number of jobs in the run queue over the last 1, \
+5 and 15 minutes. If FILE is not specified, use /var/run/utmp"
+)]
+#[command(next_line_help = true)]
+struct Cli {
+    /// The utmp file to count users in
+    #[clap(value_name = "FILE")]
+    file: Option<OsString>,
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+const UTMP_FILE: &str = "/var/run/utmp";
+
+/// Where the uptime, load and users come from
+trait Source {
+    /// Seconds since boot and the 1, 5 and 15 minute load averages
+    fn uptime(&self) -> Option<(u64, [f64; 3])>;
+    /// Users logged in according to the utmp file `file`, None when it can't be read
+    fn users(&self, file: &[u8]) -> Option<usize>;
+}
+
+/// /proc or sysinfo(2), and utmp files
+struct System;
+
+impl Source for System {
+    fn uptime(&self) -> Option<(u64, [f64; 3])> {
+        proc_uptime().or_else(sysinfo_uptime)
+    }
+
+    fn users(&self, file: &[u8]) -> Option<usize> {
+        count_users(file)
+    }
+}
+
+// Seconds since boot and the load averages, from /proc
+fn proc_uptime() -> Option<(u64, [f64; 3])> {
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let uptime: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let mut loads = loadavg
+        .split_whitespace()
+        .map(|load| load.parse::<f64>().ok());
+    let loads = [loads.next()??, loads.next()??, loads.next()??];
+    (uptime >= 0.0).then_some((uptime as u64, loads))
+}
+
+// The same from sysinfo(2), in case /proc isn't mounted
+fn sysinfo_uptime() -> Option<(u64, [f64; 3])> {
+    let info = sysinfo().ok()?;
+    let (one, five, fifteen) = info.load_average();
+    Some((info.uptime().as_secs(), [one, five, fifteen]))
+}
+
+// Users logged in according to a utmp file, None when it can't be read
+fn count_users(file: &[u8]) -> Option<usize> {
+    let records = fs::read(std::ffi::OsStr::from_bytes(file)).ok()?;
+    let size = std::mem::size_of::<libc::utmpx>();
+    let users = records
+        .chunks_exact(size)
+        .map(|record| unsafe { std::ptr::read_unaligned(record.as_ptr().cast::<libc::utmpx>()) })
+        .filter(|entry| entry.ut_type == libc::USER_PROCESS && entry.ut_user[0] != 0)
+        .count();
+    Some(users)
+}
+
+// "up 3 days,  2:05, " or "up 45 min, "
+fn format_uptime(uptime: u64) -> String {
+    let days = uptime / 86400;
+    let (hours, minutes) = (uptime / 3600 % 24, uptime / 60 % 60);
+    let mut line = String::from("up ");
+    match days {
+        0 => {}
+        1 => line.push_str("1 day, "),
+        days => line.push_str(&format!("{days} days, ")),
+    }
+    match hours {
+        0 => line.push_str(&format!("{minutes} min, ")),
+        hours => line.push_str(&format!("{hours:2}:{minutes:02}, ")),
+    }
+    line
+}
+
+// The line after `clock`, the time of day, with what `source` says about `file`. None
+// without a boot time
+fn uptime_line(clock: &[u8], source: &dyn Source, file: &[u8]) -> Option<Vec<u8>> {
+    let (uptime, loads) = source.uptime()?;
+    let mut line = clock.to_vec();
+    line.extend_from_slice(format_uptime(uptime).as_bytes());
+    let users = match source.users(file) {
+        Some(users) => format!("{users:2} {}", if users != 1 { "users" } else { "user" }),
+        None => String::from(" ? user"),
+    };
+    let [one, five, fifteen] = loads;
+    let loads = format!(",  load average: {one:.2}, {five:.2}, {fifteen:.2}\n");
+    line.extend_from_slice(users.as_bytes());
+    line.extend_from_slice(loads.as_bytes());
+    Some(line)
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args = Cli::parse_from(args);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("uptime: extra operand {extra}\nTry 'uptime --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let file = args
+        .file
+        .as_ref()
+        .map_or(UTMP_FILE.as_bytes(), |file| file.as_bytes());
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs() as i64);
+    let clock = match Time::local(now, 0) {
+        Some(time) => strftime(b" %H:%M:%S ", &time),
+        None => b" ??:??:?? ".to_vec(),
+    };
+    let Some(line) = uptime_line(&clock, &System, file) else {
+        eprintln!("uptime: couldn't get boot time");
+        return ExitCode::FAILURE;
+    };
+    let written = stdout_file().and_then(|mut out| out.write_all(&line));
+    if let Err(e) = written {
+        eprintln!("uptime: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fixed uptime and load, and a number of users for any file
+    struct Fixed(Option<(u64, [f64; 3])>, Option<usize>);
+
+    impl Source for Fixed {
+        fn uptime(&self) -> Option<(u64, [f64; 3])> {
+            self.0
+        }
+
+        fn users(&self, _: &[u8]) -> Option<usize> {
+            self.1
+        }
+    }
+
+    fn line(uptime: u64, users: Option<usize>) -> String {
+        let source = Fixed(Some((uptime, [0.0, 0.125, 12.5])), users);
+        let line = uptime_line(b" 10:20:30 ", &source, b"utmp").unwrap();
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn uptimes() {
+        assert_eq!(format_uptime(0), "up 0 min, ");
+        assert_eq!(format_uptime(59 * 60 + 59), "up 59 min, ");
+        assert_eq!(format_uptime(3600), "up  1:00, ");
+        assert_eq!(format_uptime(23 * 3600 + 5 * 60), "up 23:05, ");
+        assert_eq!(format_uptime(86400 + 120), "up 1 day, 2 min, ");
+        assert_eq!(
+            format_uptime(3 * 86400 + 2 * 3600 + 5 * 60),
+            "up 3 days,  2:05, "
+        );
+    }
+
+    #[test]
+    fn users() {
+        let load = ",  load average: 0.00, 0.12, 12.50\n";
+        assert_eq!(
+            line(60, Some(0)),
+            format!(" 10:20:30 up 1 min,  0 users{load}")
+        );
+        assert_eq!(
+            line(60, Some(1)),
+            format!(" 10:20:30 up 1 min,  1 user{load}")
+        );
+        assert_eq!(
+            line(60, Some(2)),
+            format!(" 10:20:30 up 1 min,  2 users{load}")
+        );
+        assert_eq!(
+            line(60, Some(123)),
+            format!(" 10:20:30 up 1 min, 123 users{load}")
+        );
+        assert_eq!(line(60, None), format!(" 10:20:30 up 1 min,  ? user{load}"));
+    }
+
+    #[test]
+    fn no_boot_time() {
+        assert_eq!(uptime_line(b"", &Fixed(None, Some(1)), b"utmp"), None);
+    }
+
+    #[test]
+    fn utmp() {
+        let record = |kind, user: &[u8]| {
+            let mut entry: libc::utmpx = unsafe { std::mem::zeroed() };
+            entry.ut_type = kind;
+            for (to, &from) in entry.ut_user.iter_mut().zip(user) {
+                *to = from as libc::c_char;
+            }
+            let size = std::mem::size_of::<libc::utmpx>();
+            let bytes = std::ptr::addr_of!(entry).cast::<u8>();
+            unsafe { std::slice::from_raw_parts(bytes, size) }.to_vec()
+        };
+        let records = [
+            record(libc::BOOT_TIME, b"reboot"),
+            record(libc::USER_PROCESS, b"alice"),
+            record(libc::DEAD_PROCESS, b"bob"),
+            record(libc::USER_PROCESS, b""),
+            record(libc::USER_PROCESS, b"carol"),
+        ];
+        let file = std::env::temp_dir().join(format!("ratiscat-{}-utmp", std::process::id()));
+        fs::write(&file, records.concat()).unwrap();
+        let users = count_users(file.as_os_str().as_bytes());
+        let _ = fs::remove_file(&file);
+        assert_eq!(users, Some(2));
+        assert_eq!(count_users(b"/nonexistent/utmp"), None);
+    }
+}
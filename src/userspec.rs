@@ -0,0 +1,408 @@
+/*
+ * User and group specifications like chown's `OWNER[:GROUP]`, after gnulib's userspec
+ *
+ * Names are looked up first and decimal ids are the fallback, a leading '+' skips
+ * the lookup. `USER:` means the user's login group and for compatibility a '.'
+ * still separates the group when the whole spec isn't valid on its own. Lookups go
+ * through `Accounts`, the system's databases or made up ones in tests.
+ */
+
+use nix::unistd::{getgrouplist, getgroups, Gid, Group, Uid, User};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+
+// uid_t and gid_t are 32 bits, with the all ones value meaning "unchanged"
+const MAX_ID: u64 = u32::MAX as u64 - 1;
+
+/// The ids of a spec, with the names given when they were looked up by name
+#[derive(Clone, Debug, Default)]
+pub struct UserSpec {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+}
+
+/// A decimal id like xstrtoul takes it: leading white space and '+' but no sign
+pub fn parse_id(s: &[u8]) -> Option<u32> {
+    let start = s
+        .iter()
+        .take_while(|c| b" \t\n\x0b\x0c\r".contains(c))
+        .count();
+    let s = &s[start..];
+    let digits = s.strip_prefix(b"+").unwrap_or(s);
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let mut value = 0u64;
+    for &c in digits {
+        value = value * 10 + (c - b'0') as u64;
+        if value > MAX_ID {
+            return None;
+        }
+    }
+    Some(value as u32)
+}
+
+/// The user named `name`, None if there's none or it isn't a valid name
+pub fn lookup_user(name: &[u8]) -> Option<User> {
+    let name = std::str::from_utf8(name).ok()?;
+    User::from_name(name).ok().flatten()
+}
+
+/// The group named `name`, None if there's none or it isn't a valid name
+pub fn lookup_group(name: &[u8]) -> Option<Group> {
+    let name = std::str::from_utf8(name).ok()?;
+    Group::from_name(name).ok().flatten()
+}
+
+thread_local! {
+    // Names already looked up, a recursive chown asks for the same few ids over and over
+    static USER_NAMES: RefCell<HashMap<u32, Option<String>>> = RefCell::new(HashMap::new());
+    static GROUP_NAMES: RefCell<HashMap<u32, Option<String>>> = RefCell::new(HashMap::new());
+}
+
+/// The passwd name of `uid`, None when the database has no entry for it (ie. containers
+/// running as an arbitrary uid)
+pub fn user_name(uid: u32) -> Option<String> {
+    USER_NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let name = names.entry(uid).or_insert_with(|| {
+            let user = User::from_uid(Uid::from_raw(uid)).ok().flatten();
+            user.map(|user| user.name)
+        });
+        name.clone()
+    })
+}
+
+/// The name of `uid`, or the number when it has none
+pub fn uid_to_name(uid: u32) -> String {
+    user_name(uid).unwrap_or_else(|| uid.to_string())
+}
+
+/// The group database name of `gid`, None when there's no entry for it
+pub fn group_name(gid: u32) -> Option<String> {
+    GROUP_NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let name = names.entry(gid).or_insert_with(|| {
+            let group = Group::from_gid(Gid::from_raw(gid)).ok().flatten();
+            group.map(|group| group.name)
+        });
+        name.clone()
+    })
+}
+
+/// The name of `gid`, or the number when it has none
+pub fn gid_to_name(gid: u32) -> String {
+    group_name(gid).unwrap_or_else(|| gid.to_string())
+}
+
+/// The groups of `user` from the group database with getgrouplist, or those of the
+/// process without one, like gnulib's mgetgroups: `gid` comes first (unless it's None)
+/// and repeats of it or of the previous group are dropped
+pub fn group_list(user: Option<&str>, gid: Option<u32>) -> nix::Result<Vec<u32>> {
+    let groups = match user {
+        Some(user) => {
+            let user = CString::new(user).map_err(|_| nix::Error::EINVAL)?;
+            // getgrouplist always includes the group it's given
+            let gid = Gid::from_raw(gid.unwrap_or(u32::MAX));
+            getgrouplist(&user, gid)?
+        }
+        None => gid
+            .map(Gid::from_raw)
+            .into_iter()
+            .chain(getgroups()?)
+            .collect(),
+    };
+    let mut groups: Vec<u32> = groups.into_iter().map(Gid::as_raw).collect();
+    if let Some(&first) = groups.first() {
+        let mut previous = None;
+        groups.retain(|&gid| {
+            let keep = previous.is_none() || (gid != first && Some(gid) != previous);
+            if keep {
+                previous = Some(gid);
+            }
+            keep
+        });
+    }
+    Ok(groups)
+}
+
+/// The user and group databases specs are resolved against
+pub trait Accounts {
+    /// The uid and login group of the user named `name`
+    fn user(&self, name: &[u8]) -> Option<(u32, u32)>;
+    /// The gid of the group named `name`
+    fn group(&self, name: &[u8]) -> Option<u32>;
+    fn user_name(&self, uid: u32) -> Option<String>;
+    fn group_name(&self, gid: u32) -> Option<String>;
+    /// The name and login group of `uid`, not cached as the databases may change
+    /// (with chroot)
+    fn user_by_id(&self, uid: u32) -> Option<(String, u32)>;
+    /// The groups of the user `name`, as `group_list` has them
+    fn group_list(&self, name: &str, gid: Option<u32>) -> nix::Result<Vec<u32>>;
+
+    /// The name of `uid`, or the number when it has none
+    fn uid_to_name(&self, uid: u32) -> String {
+        self.user_name(uid).unwrap_or_else(|| uid.to_string())
+    }
+
+    /// The name of `gid`, or the number when it has none
+    fn gid_to_name(&self, gid: u32) -> String {
+        self.group_name(gid).unwrap_or_else(|| gid.to_string())
+    }
+}
+
+/// The system's passwd and group databases, through NSS
+pub struct System;
+
+impl Accounts for System {
+    fn user(&self, name: &[u8]) -> Option<(u32, u32)> {
+        lookup_user(name).map(|user| (user.uid.as_raw(), user.gid.as_raw()))
+    }
+
+    fn group(&self, name: &[u8]) -> Option<u32> {
+        lookup_group(name).map(|group| group.gid.as_raw())
+    }
+
+    fn user_name(&self, uid: u32) -> Option<String> {
+        user_name(uid)
+    }
+
+    fn group_name(&self, gid: u32) -> Option<String> {
+        group_name(gid)
+    }
+
+    fn user_by_id(&self, uid: u32) -> Option<(String, u32)> {
+        let user = User::from_uid(Uid::from_raw(uid)).ok().flatten()?;
+        Some((user.name, user.gid.as_raw()))
+    }
+
+    fn group_list(&self, name: &str, gid: Option<u32>) -> nix::Result<Vec<u32>> {
+        group_list(Some(name), gid)
+    }
+}
+
+fn parse_with_separator(
+    spec: &[u8],
+    separator: Option<usize>,
+    accounts: &dyn Accounts,
+) -> Result<UserSpec, &'static str> {
+    let (user, group) = match separator {
+        Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+        None => (spec, None),
+    };
+    let group = group.filter(|group| !group.is_empty());
+    let mut parsed = UserSpec::default();
+
+    if !user.is_empty() {
+        let found = match user.first() {
+            Some(b'+') => None,
+            _ => accounts.user(user),
+        };
+        match found {
+            Some((uid, gid)) => {
+                parsed.uid = Some(uid);
+                parsed.user_name = Some(String::from_utf8_lossy(user).into_owned());
+                if separator.is_some() && group.is_none() {
+                    parsed.gid = Some(gid);
+                    parsed.group_name = Some(accounts.gid_to_name(gid));
+                }
+            }
+            // A number can't have a login group
+            None if separator.is_some() && group.is_none() => return Err("invalid spec"),
+            None => parsed.uid = Some(parse_id(user).ok_or("invalid user")?),
+        }
+    }
+
+    if let Some(group) = group {
+        let found = match group.first() {
+            Some(b'+') => None,
+            _ => accounts.group(group),
+        };
+        match found {
+            Some(gid) => {
+                parsed.gid = Some(gid);
+                parsed.group_name = Some(String::from_utf8_lossy(group).into_owned());
+            }
+            None => parsed.gid = Some(parse_id(group).ok_or("invalid group")?),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parse `USER[:GROUP]`, `USER:` or `:GROUP`. The error is the message GNU prefixes
+/// the spec with, the flag is set when the obsolete `USER.GROUP` form was used
+pub fn parse_user_spec(spec: &[u8]) -> Result<(UserSpec, bool), &'static str> {
+    parse_user_spec_with(spec, &System)
+}
+
+/// `parse_user_spec` looking names up in `accounts`
+pub fn parse_user_spec_with(
+    spec: &[u8],
+    accounts: &dyn Accounts,
+) -> Result<(UserSpec, bool), &'static str> {
+    let colon = spec.iter().position(|&c| c == b':');
+    let error = match parse_with_separator(spec, colon, accounts) {
+        Ok(parsed) => return Ok((parsed, false)),
+        Err(error) => error,
+    };
+    if colon.is_none() {
+        if let Some(dot) = spec.iter().position(|&c| c == b'.') {
+            if let Ok(parsed) = parse_with_separator(spec, Some(dot), accounts) {
+                return Ok((parsed, true));
+            }
+        }
+    }
+    Err(error)
+}
+
+/// Made up user and group databases
+#[cfg(test)]
+pub(crate) mod mock {
+    pub struct Accounts;
+
+    // name, uid, login group
+    const USERS: &[(&str, u32, u32)] = &[("root", 0, 0), ("alice", 1000, 100), ("42", 7, 100)];
+    const GROUPS: &[(&str, u32)] = &[("root", 0), ("users", 100), ("staff", 50), ("42", 8)];
+    // user, supplementary groups
+    const MEMBERS: &[(&str, &[u32])] = &[("alice", &[50])];
+
+    impl super::Accounts for Accounts {
+        fn user(&self, name: &[u8]) -> Option<(u32, u32)> {
+            let found = USERS.iter().find(|user| user.0.as_bytes() == name);
+            found.map(|&(_, uid, gid)| (uid, gid))
+        }
+
+        fn group(&self, name: &[u8]) -> Option<u32> {
+            let found = GROUPS.iter().find(|group| group.0.as_bytes() == name);
+            found.map(|&(_, gid)| gid)
+        }
+
+        fn user_name(&self, uid: u32) -> Option<String> {
+            let found = USERS.iter().find(|user| user.1 == uid);
+            found.map(|user| user.0.to_string())
+        }
+
+        fn group_name(&self, gid: u32) -> Option<String> {
+            let found = GROUPS.iter().find(|group| group.1 == gid);
+            found.map(|group| group.0.to_string())
+        }
+
+        fn user_by_id(&self, uid: u32) -> Option<(String, u32)> {
+            let found = USERS.iter().find(|user| user.1 == uid);
+            found.map(|&(name, _, gid)| (name.to_string(), gid))
+        }
+
+        fn group_list(&self, name: &str, gid: Option<u32>) -> nix::Result<Vec<u32>> {
+            let found = MEMBERS.iter().find(|member| member.0 == name);
+            let groups = found.map_or(&[][..], |member| member.1);
+            let groups = groups.iter().copied().filter(|&group| Some(group) != gid);
+            Ok(gid.into_iter().chain(groups).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The ids, the names as "USER:GROUP" and whether '.' was used
+    type Parsed = (Option<u32>, Option<u32>, String, bool);
+
+    fn parse(spec: &str) -> Result<Parsed, &'static str> {
+        let (spec, dot) = parse_user_spec_with(spec.as_bytes(), &mock::Accounts)?;
+        let names = format!(
+            "{}:{}",
+            spec.user_name.unwrap_or_default(),
+            spec.group_name.unwrap_or_default()
+        );
+        Ok((spec.uid, spec.gid, names, dot))
+    }
+
+    #[test]
+    fn user_and_group() {
+        assert_eq!(
+            parse("alice"),
+            Ok((Some(1000), None, "alice:".into(), false))
+        );
+        assert_eq!(
+            parse("alice:staff"),
+            Ok((Some(1000), Some(50), "alice:staff".into(), false))
+        );
+        assert_eq!(
+            parse(":staff"),
+            Ok((None, Some(50), ":staff".into(), false))
+        );
+        // The login group, named as the group database has it
+        assert_eq!(
+            parse("alice:"),
+            Ok((Some(1000), Some(100), "alice:users".into(), false))
+        );
+        assert_eq!(parse(":"), Ok((None, None, ":".into(), false)));
+        assert_eq!(parse(""), Ok((None, None, ":".into(), false)));
+    }
+
+    #[test]
+    fn numeric_ids() {
+        // Numbers have no names, -v shows them as numbers
+        assert_eq!(parse("1234"), Ok((Some(1234), None, ":".into(), false)));
+        assert_eq!(
+            parse("1234:55"),
+            Ok((Some(1234), Some(55), ":".into(), false))
+        );
+        assert_eq!(parse(":55"), Ok((None, Some(55), ":".into(), false)));
+        assert_eq!(
+            parse("alice:55"),
+            Ok((Some(1000), Some(55), "alice:".into(), false))
+        );
+        // A name that looks like a number is looked up first, '+' skips the lookup
+        assert_eq!(
+            parse("42:42"),
+            Ok((Some(7), Some(8), "42:42".into(), false))
+        );
+        assert_eq!(
+            parse("+42:+42"),
+            Ok((Some(42), Some(42), ":".into(), false))
+        );
+        assert_eq!(
+            parse("4294967294"),
+            Ok((Some(u32::MAX - 1), None, ":".into(), false))
+        );
+    }
+
+    #[test]
+    fn invalid_specs() {
+        assert_eq!(parse("nobody"), Err("invalid user"));
+        assert_eq!(parse("nobody:staff"), Err("invalid user"));
+        assert_eq!(parse("alice:nogroup"), Err("invalid group"));
+        assert_eq!(parse(":nogroup"), Err("invalid group"));
+        // A number has no login group
+        assert_eq!(parse("1234:"), Err("invalid spec"));
+        assert_eq!(parse("4294967295"), Err("invalid user"));
+        assert_eq!(parse("-1"), Err("invalid user"));
+    }
+
+    #[test]
+    fn obsolete_dot_separator() {
+        assert_eq!(
+            parse("alice.staff"),
+            Ok((Some(1000), Some(50), "alice:staff".into(), true))
+        );
+        assert_eq!(
+            parse("1234.55"),
+            Ok((Some(1234), Some(55), ":".into(), true))
+        );
+        // Only when the whole spec isn't a user
+        assert_eq!(parse("alice.x:staff"), Err("invalid user"));
+    }
+
+    #[test]
+    fn ids() {
+        assert_eq!(parse_id(b" +12"), Some(12));
+        assert_eq!(parse_id(b"12 "), None);
+        assert_eq!(parse_id(b""), None);
+        assert_eq!(parse_id(b"4294967295"), None);
+    }
+}
@@ -0,0 +1,45 @@
+/*
+ * whoami - print the user name of the effective user id
+ *
+ * Same as `id -un`. The name comes from the passwd database, a uid without an entry
+ * is an error.
+ */
+
+use crate::userspec::user_name;
+use crate::{parse_args, quote, reset_sigpipe, stdout_file, strerror};
+use clap::Parser;
+use nix::unistd::geteuid;
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "whoami", version, long_about = None)]
+#[command(about = "Print the user name associated with the current effective user ID")]
+#[command(next_line_help = true)]
+struct Cli {
+    #[clap(hide = true)]
+    operands: Vec<OsString>,
+}
+
+pub fn main(args: impl IntoIterator<Item = OsString>) -> ExitCode {
+    reset_sigpipe();
+    let args: Cli = parse_args(args, 1);
+    if let Some(extra) = args.operands.first() {
+        let extra = quote(extra.as_bytes());
+        eprintln!("whoami: extra operand {extra}\nTry 'whoami --help' for more information.");
+        return ExitCode::FAILURE;
+    }
+    let uid = geteuid().as_raw();
+    let Some(name) = user_name(uid) else {
+        eprintln!("whoami: cannot find name for user ID {uid}");
+        return ExitCode::FAILURE;
+    };
+    let written = stdout_file().and_then(|mut out| writeln!(out, "{name}"));
+    if let Err(e) = written {
+        eprintln!("whoami: write error: {}", strerror(&e));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
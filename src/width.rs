@@ -0,0 +1,68 @@
+/*
+ * Display widths, the terminal columns text takes rather than its bytes
+ *
+ * With the `unicode-width` feature East Asian wide characters (and emoji) take 2
+ * columns and combining marks none, without it every character takes one. Column
+ * aware transforms measure through here so they all agree.
+ */
+
+#[cfg(feature = "unicode-width")]
+use unicode_width::UnicodeWidthStr;
+
+/// Columns `s` takes on a terminal
+pub fn display_width(s: &str) -> usize {
+    #[cfg(feature = "unicode-width")]
+    return s.width();
+    #[cfg(not(feature = "unicode-width"))]
+    s.chars().count()
+}
+
+/// Columns a line takes, invalid UTF-8 bytes counting one each (as U+FFFD) and tabs
+/// advancing to the next multiple of 8 like `wc -L`
+pub fn line_width(line: &[u8]) -> usize {
+    let mut columns = 0;
+    for (i, segment) in line.split(|&c| c == b'\t').enumerate() {
+        if i > 0 {
+            columns = (columns / 8 + 1) * 8;
+        }
+        columns += display_width(&String::from_utf8_lossy(segment));
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_and_tabs() {
+        assert_eq!(line_width(b""), 0);
+        assert_eq!(line_width(b"abc"), 3);
+        assert_eq!(line_width(b"\t"), 8);
+        assert_eq!(line_width(b"abc\tde"), 10);
+        assert_eq!(line_width(b"abcdefgh\t"), 16);
+        // Invalid bytes are a U+FFFD each
+        assert_eq!(line_width(b"a\xff\xfeb"), 4);
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn wide_and_zero_width() {
+        // CJK and emoji take 2 columns, the tab stops after them
+        assert_eq!(line_width("日本語".as_bytes()), 6);
+        assert_eq!(line_width("日本\tx".as_bytes()), 9);
+        assert_eq!(line_width("😀".as_bytes()), 2);
+        assert_eq!(line_width("a😀b".as_bytes()), 4);
+        // Combining marks add nothing to their base
+        assert_eq!(line_width("e\u{301}".as_bytes()), 1);
+        assert_eq!(line_width("cafe\u{301}\t".as_bytes()), 8);
+    }
+
+    #[cfg(not(feature = "unicode-width"))]
+    #[test]
+    fn characters_without_unicode_width() {
+        assert_eq!(line_width("日本語".as_bytes()), 3);
+        assert_eq!(line_width("😀".as_bytes()), 1);
+        assert_eq!(line_width("e\u{301}".as_bytes()), 2);
+    }
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::{run, TempDir};
+
+fn arch(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_arch"), args, dir.path())
+}
+
+#[test]
+fn machine() {
+    let dir = TempDir::new("arch-machine");
+    let machine = nix::sys::utsname::uname().unwrap().machine().to_owned();
+    let expected = format!("{}\n", machine.to_str().unwrap());
+    assert_eq!(arch(&dir, &[]), (0, expected.clone(), "".into()));
+    // The same as uname -m, ours and the system's
+    let uname = run(env!("CARGO_BIN_EXE_uname"), &["-m"], dir.path());
+    assert_eq!(uname, (0, expected.clone(), "".into()));
+    let (status, system, _) = run("uname", &["-m"], dir.path());
+    if status == 0 {
+        assert_eq!(system, expected);
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("arch-usage");
+    for (args, message) in [
+        (&["x"][..], "extra operand 'x'"),
+        (&["-m"], "invalid option -- 'm'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("arch: {message}\nTry 'arch --help' for more information.\n");
+        assert_eq!(arch(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+    // Like GNU's when there's one
+    let (_, version, _) = run("arch", &["--version"], dir.path());
+    if version.contains("GNU coreutils") {
+        for args in [&[][..], &["x"], &["-m"], &["--foo"]] {
+            assert_eq!(arch(&dir, args), run("arch", args, dir.path()), "{args:?}");
+        }
+    }
+}
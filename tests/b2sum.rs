@@ -0,0 +1,190 @@
+mod common;
+
+use common::{run, TempDir};
+
+// The BLAKE2b digests of "a\n" by length, the default of 512 bits last
+const DIGESTS: [(&str, &str); 4] = [
+    ("8", "13"),
+    ("128", "b93e0fc7bb21633c08bba07c5e71dc00"),
+    (
+        "256",
+        "be29a54b934581ab434fde713c16db07c3e0124a371daca7c33588be7526630e",
+    ),
+    (
+        "512",
+        "bedfbb90d858c2d67b7ee8f7523be3d3b54004ef9e4f02f2ad79a1d05bfdfe49\
+         b81e3c92ebf99b504102b6bf003fa342587f5b3124c205f55204e8c4b4ce7d7c",
+    ),
+];
+
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("good", "a\n");
+    dir.write("bad", "b\n");
+    dir
+}
+
+fn b2sum(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_b2sum"), args, dir.path())
+}
+
+// Whether `prog` on PATH is usable to compare with, GNU's when `gnu`
+fn available(prog: &str, args: &[&str], gnu: bool, dir: &TempDir) -> bool {
+    match std::process::Command::new(prog)
+        .args(args)
+        .current_dir(dir.path())
+        .output()
+    {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            output.status.success() && (!gnu || stdout.contains("GNU coreutils"))
+        }
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn lengths() {
+    let dir = setup("b2sum-lengths");
+    for (bits, digest) in DIGESTS {
+        assert_eq!(
+            b2sum(&dir, &["-l", bits, "good"]),
+            (0, format!("{digest}  good\n"), "".into()),
+            "{bits}"
+        );
+        let tag = match bits {
+            "512" => "BLAKE2b".to_string(),
+            _ => format!("BLAKE2b-{bits}"),
+        };
+        assert_eq!(
+            b2sum(&dir, &["--length", bits, "--tag", "good"]),
+            (0, format!("{tag} (good) = {digest}\n"), "".into()),
+            "{bits}"
+        );
+    }
+    // 0 is the default length
+    assert_eq!(b2sum(&dir, &["-l", "0", "good"]), b2sum(&dir, &["good"]));
+    for (length, message) in [
+        ("7", "length is not a multiple of 8"),
+        ("520", "maximum digest length for 'BLAKE2b' is 512 bits"),
+    ] {
+        assert_eq!(
+            b2sum(&dir, &["-l", length, "good"]),
+            (
+                1,
+                "".into(),
+                format!("b2sum: invalid length: '{length}'\nb2sum: {message}\n")
+            ),
+            "{length}"
+        );
+    }
+    assert_eq!(
+        b2sum(&dir, &["-l", "x", "good"]),
+        (1, "".into(), "b2sum: invalid length: 'x'\n".into())
+    );
+}
+
+#[test]
+fn check_lengths() {
+    let dir = setup("b2sum-check");
+    // Untagged lines of any length, told by the number of hex digits
+    let mut list = String::new();
+    for (bits, digest) in DIGESTS {
+        list += &format!("{digest}  good\n");
+        list += &format!("BLAKE2b-{bits} (bad) = {digest}\n");
+    }
+    dir.write("list", list);
+    let warning = "b2sum: WARNING: 4 computed checksums did NOT match\n";
+    assert_eq!(
+        b2sum(&dir, &["-c", "list"]),
+        (1, "good: OK\nbad: FAILED\n".repeat(4), warning.into())
+    );
+    // Still by their digits whatever -l says, like GNU
+    dir.write(
+        "list",
+        format!("{}  good\n{}  good\n", DIGESTS[0].1, DIGESTS[2].1),
+    );
+    assert_eq!(
+        b2sum(&dir, &["-c", "-l", "256", "list"]),
+        (0, "good: OK\ngood: OK\n".into(), "".into())
+    );
+    // Not a whole number of bytes, or too long
+    dir.write("list", format!("123  good\n{}00  good\n", DIGESTS[3].1));
+    assert_eq!(
+        b2sum(&dir, &["-c", "list"]),
+        (
+            1,
+            "".into(),
+            "b2sum: list: no properly formatted checksum lines found\n".into()
+        )
+    );
+}
+
+#[test]
+fn gnu_and_python() {
+    let dir = setup("b2sum-interop");
+    let lengths = ["8", "128", "256", "384", "512"];
+    if available("b2sum", &["--version"], true, &dir) {
+        for bits in lengths {
+            for tag in [&[][..], &["--tag"]] {
+                let args = [&["-l", bits][..], tag, &["good", "bad"]].concat();
+                let (status, list, stderr) = run("b2sum", &args, dir.path());
+                assert_eq!(b2sum(&dir, &args), (status, list.clone(), stderr));
+                dir.write("list", list);
+                assert_eq!(
+                    b2sum(&dir, &["-c", "list"]),
+                    run("b2sum", &["-c", "list"], dir.path()),
+                    "{args:?}"
+                );
+            }
+        }
+    }
+    if available("python3", &["-c", "import hashlib"], false, &dir) {
+        for bits in lengths {
+            let script = format!(
+                "import hashlib\n\
+                 data = open('good', 'rb').read()\n\
+                 print(hashlib.blake2b(data, digest_size={bits} // 8).hexdigest() + '  good')"
+            );
+            let python = run("python3", &["-c", &script], dir.path());
+            assert_eq!(b2sum(&dir, &["-l", bits, "good"]), python, "{bits}");
+        }
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = setup("b2sum-usage");
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-l"], "option requires an argument -- 'l'"),
+        (&["--length"], "option '--length' requires an argument"),
+    ] {
+        let stderr = format!("b2sum: {message}\nTry 'b2sum --help' for more information.\n");
+        assert_eq!(b2sum(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
+
+// cargo test --release --test b2sum -- --ignored --nocapture throughput_benchmark
+#[test]
+#[ignore = "benchmark"]
+fn throughput_benchmark() {
+    use std::time::Instant;
+    let dir = TempDir::new("b2sum-benchmark");
+    let size = 256 << 20;
+    dir.write("data", vec![0x5a; size]);
+    for (prog, bin) in [
+        ("b2sum", env!("CARGO_BIN_EXE_b2sum")),
+        ("sha256sum", env!("CARGO_BIN_EXE_sha256sum")),
+        ("md5sum", env!("CARGO_BIN_EXE_md5sum")),
+        ("GNU b2sum", "b2sum"),
+    ] {
+        let start = Instant::now();
+        let (status, _, _) = run(bin, &["data"], dir.path());
+        let elapsed = start.elapsed();
+        assert_eq!(status, 0, "{prog}");
+        let rate = size as f64 / elapsed.as_secs_f64() / (1 << 20) as f64;
+        println!("{prog}: {size} bytes in {elapsed:?}, {rate:.0} MiB/s");
+    }
+}
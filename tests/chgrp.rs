@@ -0,0 +1,89 @@
+mod common;
+
+use common::{run, TempDir};
+use std::os::unix::fs::MetadataExt;
+
+fn chgrp(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_chgrp"), args, dir.path())
+}
+
+#[test]
+fn operand_errors() {
+    let dir = TempDir::new("chgrp-operands");
+    dir.write("f", "");
+    let try_help = "Try 'chgrp --help' for more information.\n";
+    assert_eq!(
+        chgrp(&dir, &[]),
+        (1, "".into(), format!("chgrp: missing operand\n{try_help}"))
+    );
+    assert_eq!(
+        chgrp(&dir, &["0"]),
+        (
+            1,
+            "".into(),
+            format!("chgrp: missing operand after '0'\n{try_help}")
+        )
+    );
+    assert_eq!(
+        chgrp(&dir, &["no-such-group", "f"]),
+        (
+            1,
+            "".into(),
+            "chgrp: invalid group: 'no-such-group'\n".into()
+        )
+    );
+}
+
+// Changing to any group takes root
+#[test]
+fn numeric_group() {
+    if !nix::unistd::geteuid().is_root() {
+        return;
+    }
+    let dir = TempDir::new("chgrp-numeric");
+    dir.write("f", "");
+    let gid = || dir.path().join("f").metadata().unwrap().gid();
+    assert_eq!(
+        chgrp(&dir, &["-c", "4321", "f"]),
+        (
+            0,
+            "changed group of 'f' from root to 4321\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!(gid(), 4321);
+    assert_eq!(
+        chgrp(&dir, &["-v", "+4321", "f"]),
+        (0, "group of 'f' retained as 4321\n".into(), "".into())
+    );
+    assert_eq!(
+        chgrp(&dir, &["-v", "root", "f"]),
+        (
+            0,
+            "changed group of 'f' from 4321 to root\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!(gid(), 0);
+}
+
+#[test]
+fn recursive() {
+    if !nix::unistd::geteuid().is_root() {
+        return;
+    }
+    let dir = TempDir::new("chgrp-recursive");
+    std::fs::create_dir_all(dir.path().join("d/sub")).unwrap();
+    std::fs::create_dir(dir.path().join("e")).unwrap();
+    dir.write("d/sub/f", "");
+    dir.write("e/g", "");
+    std::os::unix::fs::symlink("../e", dir.path().join("d/link")).unwrap();
+    let gid = |path: &str| dir.path().join(path).symlink_metadata().unwrap().gid();
+
+    // -P changes the link met, not what it points to
+    assert_eq!(chgrp(&dir, &["-R", "4321", "d"]).0, 0);
+    assert_eq!((gid("d/sub/f"), gid("d/link"), gid("e")), (4321, 4321, 0));
+    // -L goes through it
+    assert_eq!(chgrp(&dir, &["-R", "-L", "4322", "d"]).0, 0);
+    assert_eq!((gid("d/sub/f"), gid("e"), gid("e/g")), (4322, 4322, 4322));
+}
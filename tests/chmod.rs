@@ -0,0 +1,311 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+
+const STARTS: [u32; 6] = [0o644, 0o755, 0o600, 0o4755, 0o2750, 0o000];
+
+// Every clause form, with the resulting mode of files then directories for each of
+// STARTS, as GNU chmod leaves them under umask 022
+#[rustfmt::skip]
+const CLAUSES: [(&str, [u32; 6], [u32; 6]); 25] = [
+    ("u+x", [0o744, 0o755, 0o700, 0o4755, 0o2750, 0o100], [0o744, 0o755, 0o700, 0o4755, 0o2750, 0o100]),
+    ("g-r", [0o604, 0o715, 0o600, 0o4715, 0o2710, 0o000], [0o604, 0o715, 0o600, 0o4715, 0o2710, 0o000]),
+    ("o=rw", [0o646, 0o756, 0o606, 0o4756, 0o2756, 0o006], [0o646, 0o756, 0o606, 0o4756, 0o2756, 0o006]),
+    ("a+X", [0o644, 0o755, 0o600, 0o4755, 0o2751, 0o000], [0o755, 0o755, 0o711, 0o4755, 0o2751, 0o111]),
+    ("+X", [0o644, 0o755, 0o600, 0o4755, 0o2751, 0o000], [0o755, 0o755, 0o711, 0o4755, 0o2751, 0o111]),
+    ("=r", [0o444, 0o444, 0o444, 0o444, 0o444, 0o444], [0o444, 0o444, 0o444, 0o4444, 0o2444, 0o444]),
+    ("u=g", [0o444, 0o555, 0o000, 0o555, 0o2550, 0o000], [0o444, 0o555, 0o000, 0o4555, 0o2550, 0o000]),
+    ("g=u", [0o664, 0o775, 0o660, 0o4775, 0o770, 0o000], [0o664, 0o775, 0o660, 0o4775, 0o2770, 0o000]),
+    ("go=u", [0o666, 0o777, 0o666, 0o4777, 0o777, 0o000], [0o666, 0o777, 0o666, 0o4777, 0o2777, 0o000]),
+    ("g+u", [0o664, 0o775, 0o660, 0o4775, 0o2770, 0o000], [0o664, 0o775, 0o660, 0o4775, 0o2770, 0o000]),
+    ("o-g", [0o640, 0o750, 0o600, 0o4750, 0o2750, 0o000], [0o640, 0o750, 0o600, 0o4750, 0o2750, 0o000]),
+    ("u+s,g+s", [0o6644, 0o6755, 0o6600, 0o6755, 0o6750, 0o6000], [0o6644, 0o6755, 0o6600, 0o6755, 0o6750, 0o6000]),
+    ("+t", [0o1644, 0o1755, 0o1600, 0o5755, 0o3750, 0o1000], [0o1644, 0o1755, 0o1600, 0o5755, 0o3750, 0o1000]),
+    ("a-rwx", [0o000, 0o000, 0o000, 0o4000, 0o2000, 0o000], [0o000, 0o000, 0o000, 0o4000, 0o2000, 0o000]),
+    ("u=rwx,g=rx,o=", [0o750, 0o750, 0o750, 0o750, 0o750, 0o750], [0o750, 0o750, 0o750, 0o4750, 0o2750, 0o750]),
+    ("ug+w,o-r", [0o660, 0o771, 0o620, 0o4771, 0o2770, 0o220], [0o660, 0o771, 0o620, 0o4771, 0o2770, 0o220]),
+    ("+w", [0o644, 0o755, 0o600, 0o4755, 0o2750, 0o200], [0o644, 0o755, 0o600, 0o4755, 0o2750, 0o200]),
+    ("u=", [0o044, 0o055, 0o000, 0o055, 0o2050, 0o000], [0o044, 0o055, 0o000, 0o4055, 0o2050, 0o000]),
+    ("a=X", [0o000, 0o111, 0o000, 0o111, 0o111, 0o000], [0o111, 0o111, 0o111, 0o4111, 0o2111, 0o111]),
+    ("1755", [0o1755; 6], [0o1755, 0o1755, 0o1755, 0o5755, 0o3755, 0o1755]),
+    ("0", [0o000; 6], [0o000, 0o000, 0o000, 0o4000, 0o2000, 0o000]),
+    ("u+rws", [0o4644, 0o4755, 0o4600, 0o4755, 0o6750, 0o4600], [0o4644, 0o4755, 0o4600, 0o4755, 0o6750, 0o4600]),
+    ("o+t,g-s", [0o1644, 0o1755, 0o1600, 0o5755, 0o1750, 0o1000], [0o1644, 0o1755, 0o1600, 0o5755, 0o1750, 0o1000]),
+    ("00755", [0o755; 6], [0o755; 6]),
+    ("u=r-w", [0o444, 0o455, 0o400, 0o455, 0o2450, 0o400], [0o444, 0o455, 0o400, 0o4455, 0o2450, 0o400]),
+];
+
+// Run `prog` under umask 022, the modes +w and the like depend on it
+fn with_umask(prog: &str, dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    let script = "umask 022 && exec \"$@\"";
+    run(
+        "sh",
+        &[&["-c", script, "sh", prog][..], args].concat(),
+        dir.path(),
+    )
+}
+
+fn chmod(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    with_umask(env!("CARGO_BIN_EXE_chmod"), dir, args)
+}
+
+fn mode(dir: &TempDir, name: &str) -> u32 {
+    fs::symlink_metadata(dir.path().join(name))
+        .unwrap()
+        .permissions()
+        .mode()
+        & 0o7777
+}
+
+fn set_mode(dir: &TempDir, name: &str, mode: u32) {
+    fs::set_permissions(dir.path().join(name), fs::Permissions::from_mode(mode)).unwrap();
+}
+
+// Whether chmod on PATH is GNU's, to compare with
+fn gnu_chmod(dir: &TempDir) -> bool {
+    let (_, version, _) = run("chmod", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// `x` created afresh as a file or directory with mode `start`
+fn fresh(dir: &TempDir, is_dir: bool, start: u32) {
+    let path = dir.path().join("x");
+    let _ = fs::remove_dir(&path);
+    let _ = fs::remove_file(&path);
+    match is_dir {
+        true => fs::create_dir(&path).unwrap(),
+        false => dir.write("x", ""),
+    }
+    set_mode(dir, "x", start);
+}
+
+#[test]
+fn modes() {
+    let dir = TempDir::new("chmod-modes");
+    for (clause, files, dirs) in CLAUSES {
+        for (is_dir, expected) in [(false, files), (true, dirs)] {
+            for (start, expected) in STARTS.into_iter().zip(expected) {
+                fresh(&dir, is_dir, start);
+                let (status, _, stderr) = chmod(&dir, &["--", clause, "x"]);
+                assert_eq!((status, stderr.as_str()), (0, ""), "{clause}");
+                assert_eq!(
+                    mode(&dir, "x"),
+                    expected,
+                    "{clause} on {start:o}, directory {is_dir}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("chmod-gnu");
+    if !gnu_chmod(&dir) {
+        return;
+    }
+    // Clauses beyond the table, compared under another umask
+    let script = "umask 077 && exec \"$@\"";
+    for clause in ["+r", "-w", "=rwX", "g=o,o=g", "u-s,+s", "a=", "-t", "=7"] {
+        for is_dir in [false, true] {
+            for start in STARTS {
+                let mut modes = Vec::new();
+                for prog in [env!("CARGO_BIN_EXE_chmod"), "chmod"] {
+                    fresh(&dir, is_dir, start);
+                    let args = ["-c", script, "sh", prog, "--", clause, "x"];
+                    let (status, _, stderr) = run("sh", &args, dir.path());
+                    modes.push((status, stderr, mode(&dir, "x")));
+                }
+                assert_eq!(modes[0], modes[1], "{clause} on {start:o}");
+            }
+        }
+    }
+}
+
+#[test]
+fn option_modes() {
+    let dir = TempDir::new("chmod-option-modes");
+    dir.write("t", "");
+    set_mode(&dir, "t", 0o644);
+    // Taken for a mode wherever it is, and joined with commas
+    assert_eq!(
+        chmod(&dir, &["644", "-w", "t"]),
+        (
+            1,
+            "".into(),
+            "chmod: cannot access '644': No such file or directory\n".into()
+        )
+    );
+    assert_eq!(mode(&dir, "t"), 0o444);
+    assert_eq!(chmod(&dir, &["-w,g+s", "t"]), (0, "".into(), "".into()));
+    assert_eq!(mode(&dir, "t"), 0o2444);
+    // Without a who the umask applies, which is told when options give the mode
+    let script = "umask 077 && exec \"$@\"";
+    let args = ["-c", script, "sh", env!("CARGO_BIN_EXE_chmod"), "+w", "t"];
+    assert_eq!(run("sh", &args, dir.path()).0, 0);
+    let args = ["-c", script, "sh", env!("CARGO_BIN_EXE_chmod"), "-r", "t"];
+    assert_eq!(
+        run("sh", &args, dir.path()),
+        (
+            1,
+            "".into(),
+            "chmod: t: new permissions are -w-r-Sr--, not -w---S---\n".into()
+        )
+    );
+}
+
+#[test]
+fn verbose_recursive() {
+    let dir = TempDir::new("chmod-verbose");
+    fs::create_dir_all(dir.path().join("d/sub")).unwrap();
+    dir.write("d/f", "");
+    dir.write("d/sub/g", "");
+    dir.write("t", "");
+    symlink("../t", dir.path().join("d/link")).unwrap();
+    for name in ["d/f", "d/sub/g", "t"] {
+        set_mode(&dir, name, 0o644);
+    }
+    for name in ["d", "d/sub"] {
+        set_mode(&dir, name, 0o755);
+    }
+    assert_eq!(
+        chmod(&dir, &["-v", "-R", "go-r", "d"]),
+        (
+            0,
+            "mode of 'd' changed from 0755 (rwxr-xr-x) to 0711 (rwx--x--x)\n\
+             mode of 'd/f' changed from 0644 (rw-r--r--) to 0600 (rw-------)\n\
+             neither symbolic link 'd/link' nor referent has been changed\n\
+             mode of 'd/sub' changed from 0755 (rwxr-xr-x) to 0711 (rwx--x--x)\n\
+             mode of 'd/sub/g' changed from 0644 (rw-r--r--) to 0600 (rw-------)\n"
+                .into(),
+            "".into()
+        )
+    );
+    assert_eq!(mode(&dir, "t"), 0o644);
+    // Only what changed with -c
+    assert_eq!(
+        chmod(&dir, &["-c", "644", "d/f", "t"]),
+        (
+            0,
+            "mode of 'd/f' changed from 0600 (rw-------) to 0644 (rw-r--r--)\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!(
+        chmod(&dir, &["-v", "644", "t"]),
+        (
+            0,
+            "mode of 't' retained as 0644 (rw-r--r--)\n".into(),
+            "".into()
+        )
+    );
+}
+
+#[test]
+fn symlink_policies() {
+    let dir = TempDir::new("chmod-symlinks");
+    fs::create_dir(dir.path().join("d")).unwrap();
+    dir.write("d/f", "");
+    dir.write("t", "");
+    symlink("../t", dir.path().join("d/link")).unwrap();
+    symlink("d", dir.path().join("dl")).unwrap();
+    set_mode(&dir, "d", 0o755);
+    set_mode(&dir, "d/f", 0o644);
+    set_mode(&dir, "t", 0o644);
+    // -P changes no symlink's target
+    assert_eq!(
+        chmod(&dir, &["-R", "-P", "-v", "o+w", "dl"]),
+        (
+            0,
+            "neither symbolic link 'dl' nor referent has been changed\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!((mode(&dir, "d"), mode(&dir, "t")), (0o755, 0o644));
+    // By default the symlinks given are followed like GNU does, as with -H
+    assert_eq!(chmod(&dir, &["-R", "o+w", "dl"]).0, 0);
+    assert_eq!(
+        (mode(&dir, "d"), mode(&dir, "d/f"), mode(&dir, "t")),
+        (0o757, 0o646, 0o644)
+    );
+    assert_eq!(chmod(&dir, &["-R", "-H", "o-w", "dl"]).0, 0);
+    assert_eq!(
+        (mode(&dir, "d"), mode(&dir, "d/f"), mode(&dir, "t")),
+        (0o755, 0o644, 0o644)
+    );
+    // -L follows them all
+    assert_eq!(chmod(&dir, &["-R", "-L", "o+w", "dl"]).0, 0);
+    assert_eq!(mode(&dir, "t"), 0o646);
+    // The last of them wins
+    assert_eq!(chmod(&dir, &["-R", "-L", "-P", "o-w", "dl"]).0, 0);
+    assert_eq!((mode(&dir, "d"), mode(&dir, "t")), (0o757, 0o646));
+}
+
+#[test]
+fn reference() {
+    let dir = TempDir::new("chmod-reference");
+    dir.write("r", "");
+    dir.write("t", "");
+    set_mode(&dir, "r", 0o640);
+    assert_eq!(
+        chmod(&dir, &["--reference=r", "t"]),
+        (0, "".into(), "".into())
+    );
+    assert_eq!(mode(&dir, "t"), 0o640);
+    // The mode operand is a file then
+    assert_eq!(
+        chmod(&dir, &["--reference", "r", "644", "t"]),
+        (
+            1,
+            "".into(),
+            "chmod: cannot access '644': No such file or directory\n".into()
+        )
+    );
+    assert_eq!(
+        chmod(&dir, &["--reference=nofile", "t"]),
+        (
+            1,
+            "".into(),
+            "chmod: failed to get attributes of 'nofile': No such file or directory\n".into()
+        )
+    );
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("chmod-errors");
+    dir.write("t", "");
+    let try_help = "Try 'chmod --help' for more information.\n";
+    for (args, message) in [
+        (&[][..], "missing operand"),
+        (&["g-x"], "missing operand after 'g-x'"),
+        (&["999", "t"], "invalid mode: '999'"),
+        (&["-x,u+q", "t"], "invalid mode: '-x,u+q'"),
+        (&["-Z", "t"], "invalid option -- 'Z'"),
+        (&["--foo", "t"], "unrecognized option '--foo'"),
+        (
+            &["--reference"],
+            "option '--reference' requires an argument",
+        ),
+    ] {
+        assert_eq!(
+            chmod(&dir, args),
+            (1, "".into(), format!("chmod: {message}\n{try_help}")),
+            "{args:?}"
+        );
+    }
+    assert_eq!(
+        chmod(&dir, &["644", "nofile"]),
+        (
+            1,
+            "".into(),
+            "chmod: cannot access 'nofile': No such file or directory\n".into()
+        )
+    );
+    assert_eq!(
+        chmod(&dir, &["-f", "644", "nofile"]),
+        (1, "".into(), "".into())
+    );
+}
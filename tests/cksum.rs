@@ -0,0 +1,132 @@
+mod common;
+
+use common::{run, TempDir};
+
+const GOOD: &str = "87428fc522803d31065e7bce3cf03fe475096631e5e07bbd7a0fde60c4cf25c7";
+
+// `good` matching its sha256, `bad` not, the lists of tagged lines checking them
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("good", "a\n");
+    dir.write("bad", "b\n");
+    dir.write(
+        "mixed",
+        format!("SHA256 (good) = {GOOD}\nSHA256 (bad) = {GOOD}\n"),
+    );
+    dir.write("improper", format!("SHA256 (good) = {GOOD}\nnot a line\n"));
+    dir.write(
+        "missing",
+        format!("SHA256 (good) = {GOOD}\nSHA256 (nofile) = {GOOD}\n"),
+    );
+    dir.write("only_missing", format!("SHA256 (nofile) = {GOOD}\n"));
+    dir.write("no_lines", "junk\n");
+    dir
+}
+
+fn cksum(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_cksum"), args, dir.path())
+}
+
+#[test]
+fn check_improperly_formatted() {
+    let dir = setup("cksum-improper");
+    let warning = "cksum: WARNING: 1 line is improperly formatted\n";
+    assert_eq!(
+        cksum(&dir, &["-c", "improper"]),
+        (0, "good: OK\n".into(), warning.into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--strict", "improper"]),
+        (1, "good: OK\n".into(), warning.into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--warn", "improper"]),
+        (
+            0,
+            "good: OK\n".into(),
+            format!("cksum: improper: 2: improperly formatted SHA256 checksum line\n{warning}")
+        )
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--quiet", "improper"]),
+        (0, "".into(), warning.into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--status", "improper"]),
+        (0, "".into(), "".into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "no_lines"]),
+        (
+            1,
+            "".into(),
+            "cksum: no_lines: no properly formatted checksum lines found\n".into()
+        )
+    );
+}
+
+#[test]
+fn check_mismatch() {
+    let dir = setup("cksum-mismatch");
+    let warning = "cksum: WARNING: 1 computed checksum did NOT match\n";
+    assert_eq!(
+        cksum(&dir, &["-c", "mixed"]),
+        (1, "good: OK\nbad: FAILED\n".into(), warning.into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--quiet", "mixed"]),
+        (1, "bad: FAILED\n".into(), warning.into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--status", "mixed"]),
+        (1, "".into(), "".into())
+    );
+}
+
+#[test]
+fn check_missing() {
+    let dir = setup("cksum-missing");
+    assert_eq!(
+        cksum(&dir, &["-c", "missing"]),
+        (
+            1,
+            "good: OK\nnofile: FAILED open or read\n".into(),
+            "cksum: nofile: No such file or directory\n\
+             cksum: WARNING: 1 listed file could not be read\n"
+                .into()
+        )
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--ignore-missing", "missing"]),
+        (0, "good: OK\n".into(), "".into())
+    );
+    assert_eq!(
+        cksum(&dir, &["-c", "--ignore-missing", "only_missing"]),
+        (
+            1,
+            "".into(),
+            "cksum: only_missing: no file was verified\n".into()
+        )
+    );
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("cksum-usage");
+    let try_help = "Try 'cksum --help' for more information.\n";
+    for (args, error) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-a"], "option requires an argument -- 'a'"),
+        (
+            &["--algorithm"],
+            "option '--algorithm' requires an argument",
+        ),
+    ] {
+        assert_eq!(
+            cksum(&dir, args),
+            (1, "".into(), format!("cksum: {error}\n{try_help}")),
+            "{args:?}"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+// Each test crate includes this and uses only part of it
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::fd::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// A scratch directory removed again once dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("ratiscat-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) {
+        fs::write(self.0.join(name), contents).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run `program` with `args` in `dir`, its exit status, stdout and stderr
+pub fn run(program: &str, args: &[&str], dir: &Path) -> (i32, String, String) {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    strings(output)
+}
+
+/// Like `run`, `input` on its stdin
+pub fn run_with_input(
+    program: &str,
+    args: &[&str],
+    dir: &Path,
+    input: &[u8],
+) -> (i32, String, String) {
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The program may exit before reading it all
+    let _ = child.stdin.take().unwrap().write_all(input);
+    strings(child.wait_with_output().unwrap())
+}
+
+fn strings(output: Output) -> (i32, String, String) {
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// A pseudo-terminal `rows` high and 80 wide: its master end, and the terminal end to
+/// give a child as stdin, stdout or stderr
+pub fn pty(rows: u16) -> (File, File) {
+    let size = nix::pty::Winsize {
+        ws_row: rows,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let pty = nix::pty::openpty(&size, None).unwrap();
+    unsafe { (File::from_raw_fd(pty.master), File::from_raw_fd(pty.slave)) }
+}
+
+/// Everything written to the terminal of a `pty` once no one has it open anymore, the
+/// terminal's CRLF line endings read as LF
+pub fn read_pty(mut master: File) -> String {
+    let mut output = Vec::new();
+    // Reading past the end fails with EIO rather than returning 0
+    let _ = master.read_to_end(&mut output);
+    String::from_utf8(output).unwrap().replace("\r\n", "\n")
+}
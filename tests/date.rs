@@ -0,0 +1,172 @@
+mod common;
+
+use common::{run, TempDir};
+use std::process::Command;
+
+#[test]
+fn last_date_wins() {
+    let dir = TempDir::new("date-last");
+    let date = |args: &[&str]| run(env!("CARGO_BIN_EXE_date"), args, dir.path());
+    assert_eq!(
+        date(&["-u", "-d", "2001-01-01", "-d", "2002-02-02", "+%F"]),
+        (0, "2002-02-02\n".into(), "".into())
+    );
+    assert_eq!(
+        date(&["-u", "--date=2001-01-01", "-d2003-03-03", "+%F"]),
+        (0, "2003-03-03\n".into(), "".into())
+    );
+}
+
+#[test]
+fn dates() {
+    let dir = TempDir::new("date-dates");
+    let date = |args: &[&str]| run(env!("CARGO_BIN_EXE_date"), args, dir.path());
+    // Seconds since the Epoch, ISO dates with zones and fractions, relative items
+    let cases = [
+        ("@0", "+%F %T %z", "1970-01-01 00:00:00 +0000\n"),
+        (
+            "@1234567890.123456789",
+            "+%F %T.%N",
+            "2009-02-13 23:31:30.123456789\n",
+        ),
+        ("@-1", "+%F %T", "1969-12-31 23:59:59\n"),
+        (
+            "2020-02-29T12:34:56Z",
+            "+%F %T %Z",
+            "2020-02-29 12:34:56 UTC\n",
+        ),
+        (
+            "2020-02-29T12:34:56+05:30",
+            "+%F %T",
+            "2020-02-29 07:04:56\n",
+        ),
+        (
+            "2020-02-29 12:34:56.5-0800",
+            "+%F %T.%N",
+            "2020-02-29 20:34:56.500000000\n",
+        ),
+        ("2020-02-29T12:34:56,25Z", "+%T.%3N", "12:34:56.250\n"),
+        ("2020-02-29 12:34:56 UTC", "+%s", "1582979696\n"),
+        ("2020-02-29 +1 year", "+%F", "2021-03-01\n"),
+        ("2020-01-31 +1 month", "+%F", "2020-03-02\n"),
+        ("2020-03-01 -1 day", "+%F", "2020-02-29\n"),
+        (
+            "2020-03-01 12:00 2 hours ago",
+            "+%F %T",
+            "2020-03-01 10:00:00\n",
+        ),
+        ("2020-03-01 +90 minutes", "+%F %T", "2020-03-01 01:30:00\n"),
+        ("2020-03-01 next week", "+%F", "2020-03-08\n"),
+        ("2020-03-01 last year", "+%F", "2019-03-01\n"),
+        ("2020-03-01 tomorrow", "+%F", "2020-03-02\n"),
+        ("2020-03-01 yesterday", "+%F", "2020-02-29\n"),
+        ("2020-03-01 fortnight ago", "+%F", "2020-02-16\n"),
+        (
+            "2020-03-01 12:00 +1 day -3 hours",
+            "+%F %T",
+            "2020-03-02 08:00:00\n",
+        ),
+    ];
+    for (input, format, expected) in cases {
+        let output = date(&["-u", "-d", input, format]);
+        assert_eq!(output, (0, expected.into(), "".into()), "{input}");
+    }
+    assert_eq!(
+        date(&["-u", "-d", "1 Mar 2020 noon"]),
+        (
+            1,
+            "".into(),
+            "date: invalid date '1 Mar 2020 noon'\n".into()
+        )
+    );
+}
+
+#[test]
+fn formats() {
+    let dir = TempDir::new("date-formats");
+    let date = |args: &[&str]| run(env!("CARGO_BIN_EXE_date"), args, dir.path());
+    // %N and its widths, numeric zones, padding and case flags
+    let cases = [
+        ("@0", "+%N", "000000000\n"),
+        ("@1.5", "+%N %3N %-N %_3N", "500000000 500 500000000 5  \n"),
+        ("@0", "+%:z %::z %:::z", "+00:00 +00:00:00 +00\n"),
+        (
+            "@0",
+            "+%-d %_d %02e %-m %_H %^a %#b %10A %-5S",
+            "1  1 01 1  0 THU JAN   Thursday 0\n",
+        ),
+        (
+            "@0",
+            "+%+5Y %_10Y %010d %^B %#Z %-j %_j",
+            "+1970       1970 0000000001 JANUARY utc 1   1\n",
+        ),
+        (
+            "2020-02-29",
+            "+%G-W%V-%u %U %W %q %P",
+            "2020-W09-6 08 08 1 am\n",
+        ),
+    ];
+    for (input, format, expected) in cases {
+        let output = date(&["-u", "-d", input, format]);
+        assert_eq!(output, (0, expected.into(), "".into()), "{format}");
+    }
+}
+
+#[test]
+fn zones() {
+    let dir = TempDir::new("date-zones");
+    let date = |tz: &str, input: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_date"))
+            .args(["-d", input, "+%F %T %z %:z %::z %:::z %Z"])
+            .env("TZ", tz)
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let cases = [
+        (
+            "IST-5:30",
+            "@0",
+            "1970-01-01 05:30:00 +0530 +05:30 +05:30:00 +05:30 IST\n",
+        ),
+        (
+            "IST-5:30",
+            "2020-07-01 12:00",
+            "2020-07-01 12:00:00 +0530 +05:30 +05:30:00 +05:30 IST\n",
+        ),
+        (
+            "NST3:30",
+            "@0",
+            "1969-12-31 20:30:00 -0330 -03:30 -03:30:00 -03:30 NST\n",
+        ),
+        (
+            "NST3:30",
+            "2020-07-01 12:00",
+            "2020-07-01 12:00:00 -0330 -03:30 -03:30:00 -03:30 NST\n",
+        ),
+        (
+            "XYZ-5:45:30",
+            "@0",
+            "1970-01-01 05:45:30 +0545 +05:45 +05:45:30 +05:45:30 XYZ\n",
+        ),
+        (
+            "XYZ-5:45:30",
+            "2020-07-01 12:00",
+            "2020-07-01 12:00:00 +0545 +05:45 +05:45:30 +05:45:30 XYZ\n",
+        ),
+        (
+            "UTC0",
+            "@0",
+            "1970-01-01 00:00:00 +0000 +00:00 +00:00:00 +00 UTC\n",
+        ),
+        (
+            "UTC0",
+            "2020-07-01 12:00",
+            "2020-07-01 12:00:00 +0000 +00:00 +00:00:00 +00 UTC\n",
+        ),
+    ];
+    for (tz, input, expected) in cases {
+        assert_eq!(date(tz, input), expected, "{tz} {input}");
+    }
+}
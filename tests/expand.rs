@@ -0,0 +1,128 @@
+mod common;
+
+use common::{run, run_with_input, TempDir};
+
+// Tabs, a backspace over a tab stop, multibyte text and a line past the stops
+const MIXED: &str = "a\tb\tc\n\tx\ty\n ab\x08\x08\tz\n\u{e9}\tw\n12345678\tq\n";
+
+fn expand(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run_with_input(env!("CARGO_BIN_EXE_expand"), args, dir.path(), b"")
+}
+
+// Whether expand on PATH is GNU's, to compare with
+fn gnu_expand(dir: &TempDir) -> bool {
+    let (_, version, _) = run("expand", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+#[test]
+fn tab_stops() {
+    let dir = TempDir::new("expand-tab-stops");
+    dir.write("tabs", "a\tb\tc\n");
+    for (args, expected) in [
+        (&["-t4"][..], "a   b   c\n"),
+        (&["-3"], "a  b  c\n"),
+        (&["-t2,5"], "a b  c\n"),
+        (&["-t", "2 5"], "a b  c\n"),
+        // Repeating every multiple of 4, or every 4 after the last stop
+        (&["-t2,/4"], "a b c\n"),
+        (&["-t2,+4"], "a b   c\n"),
+    ] {
+        let args = [args, &["tabs"]].concat();
+        assert_eq!(
+            expand(&dir, &args),
+            (0, expected.into(), "".into()),
+            "{args:?}"
+        );
+    }
+    // A tab past the last explicit stop is a single space
+    dir.write("past", "12345678\tq\n");
+    assert_eq!(
+        expand(&dir, &["-t2,5", "past"]),
+        (0, "12345678 q\n".into(), "".into())
+    );
+}
+
+#[test]
+fn columns() {
+    let dir = TempDir::new("expand-columns");
+    // Backspaces move back a column, newlines start over
+    dir.write("backspace", " ab\x08\x08\tz\n\tz\n");
+    assert_eq!(
+        expand(&dir, &["backspace"]),
+        (0, " ab\x08\x08       z\n        z\n".into(), "".into())
+    );
+    // Only leading blanks with -i
+    dir.write("initial", " \ta\tb\n");
+    assert_eq!(
+        expand(&dir, &["-i", "initial"]),
+        (0, "        a\tb\n".into(), "".into())
+    );
+    // A line carries on into the next file
+    dir.write("first", "ab");
+    dir.write("second", "\tc\n");
+    assert_eq!(
+        expand(&dir, &["first", "second"]),
+        (0, "ab      c\n".into(), "".into())
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("expand-gnu");
+    dir.write("mixed", MIXED);
+    let expected = "a       b       c\n        x       y\n ab\x08\x08       z\n\
+                    \u{e9}      w\n12345678        q\n";
+    assert_eq!(expand(&dir, &["mixed"]), (0, expected.into(), "".into()));
+    if !gnu_expand(&dir) {
+        return;
+    }
+    for args in [
+        &[][..],
+        &["-t4"],
+        &["-t2,5"],
+        &["-t2,/4"],
+        &["-t2,+4"],
+        &["-i"],
+        &["-t3,6,9", "-i"],
+    ] {
+        let args = [args, &["mixed"]].concat();
+        assert_eq!(
+            expand(&dir, &args),
+            run("expand", &args, dir.path()),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("expand-errors");
+    for (args, message) in [
+        (&["-t0"][..], "tab size cannot be 0"),
+        (&["-t4,2"], "tab sizes must be ascending"),
+        (&["-ta"], "tab size contains invalid character(s): 'a'"),
+        (
+            &["-t2,/4,6"],
+            "'/' specifier only allowed with the last value",
+        ),
+    ] {
+        let stderr = format!("expand: {message}\n");
+        assert_eq!(expand(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--tabs"], "option '--tabs' requires an argument"),
+    ] {
+        let stderr = format!("expand: {message}\nTry 'expand --help' for more information.\n");
+        assert_eq!(expand(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+    assert_eq!(
+        expand(&dir, &["nofile"]),
+        (
+            1,
+            "".into(),
+            "expand: nofile: No such file or directory\n".into()
+        )
+    );
+}
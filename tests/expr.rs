@@ -0,0 +1,25 @@
+mod common;
+
+use common::{run, TempDir};
+
+#[test]
+fn exit_status() {
+    let dir = TempDir::new("expr-status");
+    let expr = |args: &[&str]| run(env!("CARGO_BIN_EXE_expr"), args, dir.path());
+    assert_eq!(expr(&["1", "+", "1"]), (0, "2\n".into(), "".into()));
+    assert_eq!(expr(&["abc", ":", "x"]), (1, "0\n".into(), "".into()));
+    assert_eq!(expr(&[""]), (1, "\n".into(), "".into()));
+    assert_eq!(expr(&["--", "-1"]), (0, "-1\n".into(), "".into()));
+    assert_eq!(
+        expr(&["1", "/", "0"]),
+        (2, "".into(), "expr: division by zero\n".into())
+    );
+    assert_eq!(
+        expr(&[]),
+        (
+            2,
+            "".into(),
+            "expr: missing operand\nTry 'expr --help' for more information.\n".into()
+        )
+    );
+}
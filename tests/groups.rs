@@ -0,0 +1,134 @@
+mod common;
+
+use common::{run, TempDir};
+use nix::unistd::{getegid, geteuid, getgid, getgroups, Group, User};
+use std::fs;
+
+fn groups(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_groups"), args, dir.path())
+}
+
+// Whether groups on PATH is GNU's, to compare with
+fn gnu_groups(dir: &TempDir) -> bool {
+    let (_, version, _) = run("groups", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+fn group_name(gid: u32) -> String {
+    match Group::from_gid(gid.into()).unwrap() {
+        Some(group) => group.name,
+        None => gid.to_string(),
+    }
+}
+
+#[test]
+fn current_process() {
+    let dir = TempDir::new("groups-current");
+    let (gid, egid) = (getgid().as_raw(), getegid().as_raw());
+    // The real group, then the effective one, then the rest
+    let mut gids = vec![gid];
+    if egid != gid {
+        gids.push(egid);
+    }
+    for group in getgroups().unwrap() {
+        if !gids.contains(&group.as_raw()) {
+            gids.push(group.as_raw());
+        }
+    }
+    let names: Vec<String> = gids.into_iter().map(group_name).collect();
+    assert_eq!(groups(&dir, &[]), (0, names.join(" ") + "\n", "".into()));
+}
+
+#[test]
+fn users() {
+    let dir = TempDir::new("groups-users");
+    let root = User::from_name("root").unwrap().unwrap();
+    let root_groups = format!("root : {}\n", group_name(root.gid.as_raw()));
+    assert_eq!(groups(&dir, &["root"]), (0, root_groups.clone(), "".into()));
+    // One line for each, the unknown reported in between
+    assert_eq!(
+        groups(&dir, &["root", "nosuchuser", "root"]),
+        (
+            1,
+            root_groups.repeat(2),
+            "groups: 'nosuchuser': no such user\n".into()
+        )
+    );
+    // Names only, not user IDs
+    assert_eq!(
+        groups(&dir, &["0"]),
+        (1, "".into(), "groups: '0': no such user\n".into())
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("groups-gnu");
+    if !gnu_groups(&dir) {
+        return;
+    }
+    // Every user in the passwd file, at once and one by one
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let users: Vec<&str> = passwd
+        .lines()
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| !name.is_empty() && !name.starts_with('#'))
+        .collect();
+    for user in &users {
+        assert_eq!(groups(&dir, &[user]), run("groups", &[user], dir.path()));
+    }
+    assert_eq!(groups(&dir, &users), run("groups", &users, dir.path()));
+    assert_eq!(groups(&dir, &[]), run("groups", &[], dir.path()));
+}
+
+#[test]
+fn unnamed_groups() {
+    let dir = TempDir::new("groups-unnamed");
+    let (status, _, _) = run("setpriv", &["--version"], dir.path());
+    if status != 0 || !geteuid().is_root() {
+        return;
+    }
+    let gid = |name| {
+        Group::from_name(name)
+            .unwrap()
+            .map(|group| group.gid.as_raw())
+    };
+    let (Some(root), Some(tty)) = (gid("root"), gid("tty")) else {
+        return;
+    };
+    let unnamed: Vec<u32> = (54321..)
+        .filter(|&gid| Group::from_gid(gid.into()).unwrap().is_none())
+        .take(2)
+        .collect();
+    let egid = format!("--egid={}", unnamed[0]);
+    let rgid = format!("--rgid={root}");
+    let list = format!("--groups={tty},{}", unnamed[1]);
+    let creds = [&egid[..], &rgid, &list];
+    // Numbers in their place, each reported
+    let expected = (
+        1,
+        format!("root {} tty {}\n", unnamed[0], unnamed[1]),
+        format!(
+            "groups: cannot find name for group ID {}\n\
+             groups: cannot find name for group ID {}\n",
+            unnamed[0], unnamed[1]
+        ),
+    );
+    let setpriv = |groups: &str| run("setpriv", &[&creds[..], &[groups]].concat(), dir.path());
+    assert_eq!(setpriv(env!("CARGO_BIN_EXE_groups")), expected);
+    if gnu_groups(&dir) {
+        assert_eq!(setpriv("groups"), expected);
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("groups-usage");
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("groups: {message}\nTry 'groups --help' for more information.\n");
+        assert_eq!(groups(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use common::{run, TempDir};
+use nix::unistd::{getegid, geteuid, getgid, getgroups, getuid, Group, User};
+use std::fs;
+
+fn id(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_id"), args, dir.path())
+}
+
+// Whether id on PATH is GNU's, to compare with
+fn gnu_id(dir: &TempDir) -> bool {
+    let (_, version, _) = run("id", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// `id(name)`, or just the id without a name
+fn with_name(id: u32, name: Option<String>) -> String {
+    match name {
+        Some(name) => format!("{id}({name})"),
+        None => id.to_string(),
+    }
+}
+
+#[test]
+fn current_process() {
+    let dir = TempDir::new("id-current");
+    let (uid, gid) = (getuid().as_raw(), getgid().as_raw());
+    assert_eq!((geteuid().as_raw(), getegid().as_raw()), (uid, gid));
+    let user = User::from_uid(uid.into()).unwrap().map(|user| user.name);
+    let group_name = |gid: u32| Group::from_gid(gid.into()).unwrap().map(|group| group.name);
+    let mut groups: Vec<u32> = getgroups()
+        .unwrap()
+        .iter()
+        .map(|gid| gid.as_raw())
+        .collect();
+    // The effective group first, like GNU
+    groups.retain(|&group| group != gid);
+    groups.insert(0, gid);
+    let listed: Vec<String> = groups
+        .iter()
+        .map(|&gid| with_name(gid, group_name(gid)))
+        .collect();
+    assert_eq!(
+        id(&dir, &[]),
+        (
+            0,
+            format!(
+                "uid={} gid={} groups={}\n",
+                with_name(uid, user.clone()),
+                with_name(gid, group_name(gid)),
+                listed.join(",")
+            ),
+            "".into()
+        )
+    );
+    assert_eq!(id(&dir, &["-u"]), (0, format!("{uid}\n"), "".into()));
+    assert_eq!(id(&dir, &["-gr"]), (0, format!("{gid}\n"), "".into()));
+    let numbers: Vec<String> = groups.iter().map(u32::to_string).collect();
+    assert_eq!(id(&dir, &["-G"]), (0, numbers.join(" ") + "\n", "".into()));
+    assert_eq!(
+        id(&dir, &["-Gz"]),
+        (0, numbers.join("\0") + "\0", "".into())
+    );
+    if let Some(user) = user {
+        assert_eq!(id(&dir, &["-un"]), (0, format!("{user}\n"), "".into()));
+    }
+}
+
+#[test]
+fn users() {
+    let dir = TempDir::new("id-users");
+    // root is in every passwd database, by name or number
+    let root = "uid=0(root) gid=0(root) groups=0(root)\n";
+    if User::from_name("root")
+        .unwrap()
+        .is_some_and(|user| user.uid.is_root())
+    {
+        assert_eq!(id(&dir, &["root"]), (0, root.into(), "".into()));
+        assert_eq!(id(&dir, &["0"]), (0, root.into(), "".into()));
+        assert_eq!(id(&dir, &["-un", "0"]), (0, "root\n".into(), "".into()));
+        assert_eq!(id(&dir, &["-uz", "root"]), (0, "0\0".into(), "".into()));
+    }
+    // Each is reported, the others still printed
+    assert_eq!(
+        id(&dir, &["-u", "nosuchuser", "root"]),
+        (1, "0\n".into(), "id: 'nosuchuser': no such user\n".into())
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("id-gnu");
+    if !gnu_id(&dir) {
+        return;
+    }
+    for args in [
+        &[][..],
+        &["-u"],
+        &["-gn"],
+        &["-Gr"],
+        &["-Gnz"],
+        &["root"],
+        &["-G", "daemon"],
+        &["-Gn", "nobody"],
+        &["0"],
+        &["root", "daemon"],
+        &["nosuchuser"],
+        &["-u", "-g"],
+        &["-n"],
+        &["-z"],
+        &["-Z"],
+    ] {
+        assert_eq!(id(&dir, args), run("id", args, dir.path()), "{args:?}");
+    }
+}
+
+#[test]
+fn real_and_effective() {
+    let dir = TempDir::new("id-credentials");
+    let (status, _, _) = run("setpriv", &["--version"], dir.path());
+    if status != 0 || !geteuid().is_root() {
+        return;
+    }
+    // Copied out of the target directory, which may not be theirs to search
+    let ours = dir.path().join("id");
+    fs::copy(env!("CARGO_BIN_EXE_id"), &ours).unwrap();
+    let ours = ours.to_str().unwrap();
+    let setpriv = |creds: &[&str], args: &[&str]| {
+        run("setpriv", &[creds, &[ours], args].concat(), dir.path())
+    };
+    let differ = [
+        "--ruid=1",
+        "--euid=2",
+        "--rgid=3",
+        "--egid=4",
+        "--clear-groups",
+    ];
+    let name = |uid: u32| User::from_uid(uid.into()).unwrap().map(|user| user.name);
+    let group = |gid: u32| Group::from_gid(gid.into()).unwrap().map(|group| group.name);
+    assert_eq!(
+        setpriv(&differ, &[]),
+        (
+            0,
+            format!(
+                "uid={} gid={} euid={} egid={} groups={}\n",
+                with_name(1, name(1)),
+                with_name(3, group(3)),
+                with_name(2, name(2)),
+                with_name(4, group(4)),
+                with_name(4, group(4)),
+            ),
+            "".into()
+        )
+    );
+    assert_eq!(setpriv(&differ, &["-u"]), (0, "2\n".into(), "".into()));
+    assert_eq!(setpriv(&differ, &["-ur"]), (0, "1\n".into(), "".into()));
+    assert_eq!(setpriv(&differ, &["-gr"]), (0, "3\n".into(), "".into()));
+
+    // Ids without names print as numbers, -n reporting them; the real group
+    // is listed too, being different
+    let unnamed = (54321..)
+        .find(|&id| name(id).is_none() && group(id).is_none())
+        .unwrap();
+    let uid = format!("--euid={unnamed}");
+    let gid = format!("--egid={unnamed}");
+    let creds = [&uid, &gid, "--ruid=0", "--rgid=0", "--clear-groups"];
+    assert_eq!(
+        setpriv(&creds, &[]),
+        (
+            0,
+            format!("uid=0(root) gid=0(root) euid={unnamed} egid={unnamed} groups={unnamed}\n"),
+            "".into()
+        )
+    );
+    assert_eq!(
+        setpriv(&creds, &["-un"]),
+        (
+            1,
+            format!("{unnamed}\n"),
+            format!("id: cannot find name for user ID {unnamed}\n")
+        )
+    );
+    assert_eq!(
+        setpriv(&creds, &["-Gn"]),
+        (
+            1,
+            format!("root {unnamed}\n"),
+            format!("id: cannot find name for group ID {unnamed}\n")
+        )
+    );
+    if gnu_id(&dir) {
+        for args in [&[][..], &["-Gn"], &["-unr"], &["-g"]] {
+            let gnu = run("setpriv", &[&creds[..], &["id"], args].concat(), dir.path());
+            assert_eq!(setpriv(&creds, args), gnu, "{args:?}");
+        }
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("id-usage");
+    for (args, message) in [
+        (
+            &["-u", "-g"][..],
+            "cannot print \"only\" of more than one choice",
+        ),
+        (
+            &["-n"],
+            "cannot print only names or real IDs in default format",
+        ),
+        (
+            &["-r"],
+            "cannot print only names or real IDs in default format",
+        ),
+        (&["-z"], "option --zero not permitted in default format"),
+        (
+            &["-Z"],
+            "--context (-Z) works only on an SELinux-enabled kernel",
+        ),
+    ] {
+        assert_eq!(
+            id(&dir, args),
+            (1, "".into(), format!("id: {message}\n")),
+            "{args:?}"
+        );
+    }
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("id: {message}\nTry 'id --help' for more information.\n");
+        assert_eq!(id(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
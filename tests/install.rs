@@ -0,0 +1,306 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+// What `make install` of an automake package runs, the directories made first
+const MAKE_INSTALL: [&[&str]; 12] = [
+    &["-v", "-d", "dest/usr/bin", "dest/usr/share/man/man1"],
+    &["-v", "-c", "prog", "dest/usr/bin"],
+    &["-v", "-c", "-m", "644", "prog.1", "dest/usr/share/man/man1"],
+    &[
+        "-v",
+        "-D",
+        "-m",
+        "644",
+        "-p",
+        "lib.h",
+        "dest/usr/include/sub/lib.h",
+    ],
+    &["-v", "-t", "dest/usr/include", "prog.1", "lib.h"],
+    &["-v", "-C", "-m", "644", "lib.h", "dest/usr/include/lib.h"],
+    &["-v", "-C", "-m", "644", "lib.h", "dest/usr/include/lib.h"],
+    &["-v", "-b", "prog", "dest/usr/bin/prog"],
+    &["-v", "-S", ".old", "-b", "prog", "dest/usr/bin/prog"],
+    &["-v", "-T", "prog", "dest/usr/bin/p2"],
+    &[
+        "-v",
+        "-s",
+        "--strip-program=true",
+        "prog",
+        "dest/usr/bin/p3",
+    ],
+    &["-v", "-m", "g+s,o=", "-d", "dest/usr/shared"],
+];
+
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("prog", "#!/bin/sh\necho hi\n");
+    dir.write("prog.1", "man\n");
+    dir.write("lib.h", "int x;\n");
+    let prog_1 = dir.path().join("prog.1");
+    fs::set_permissions(prog_1, fs::Permissions::from_mode(0o600)).unwrap();
+    dir
+}
+
+fn install(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_install"), args, dir.path())
+}
+
+// Whether install on PATH is GNU's, to compare with
+fn gnu_install(dir: &TempDir) -> bool {
+    let (_, version, _) = run("install", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// Every file below `root` with its mode and contents, sorted
+fn tree(root: &Path) -> Vec<(String, u32, Option<String>)> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let meta = fs::symlink_metadata(&path).unwrap();
+            let name = path.strip_prefix(root).unwrap().display().to_string();
+            let mode = meta.mode() & 0o7777;
+            match meta.is_dir() {
+                true => {
+                    dirs.push(path);
+                    files.push((name, mode, None));
+                }
+                false => files.push((name, mode, Some(fs::read_to_string(&path).unwrap()))),
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn make_install() {
+    let dir = setup("install-make");
+    let outputs: Vec<_> = MAKE_INSTALL
+        .iter()
+        .map(|args| install(&dir, args))
+        .collect();
+    let created = |names: &[&str]| -> String {
+        names
+            .iter()
+            .map(|name| format!("install: creating directory '{name}'\n"))
+            .collect()
+    };
+    let copied = |from: &str, to: &str| format!("'{from}' -> '{to}'\n");
+    assert_eq!(
+        outputs[..6],
+        [
+            (
+                0,
+                created(&[
+                    "dest",
+                    "dest/usr",
+                    "dest/usr/bin",
+                    "dest/usr/share",
+                    "dest/usr/share/man",
+                    "dest/usr/share/man/man1",
+                ]),
+                "".into()
+            ),
+            (0, copied("prog", "dest/usr/bin/prog"), "".into()),
+            (
+                0,
+                copied("prog.1", "dest/usr/share/man/man1/prog.1"),
+                "".into()
+            ),
+            (
+                0,
+                created(&["dest/usr/include", "dest/usr/include/sub"])
+                    + &copied("lib.h", "dest/usr/include/sub/lib.h"),
+                "".into()
+            ),
+            (
+                0,
+                copied("prog.1", "dest/usr/include/prog.1")
+                    + &copied("lib.h", "dest/usr/include/lib.h"),
+                "".into()
+            ),
+            // Copied again for the mode, then left alone
+            (
+                0,
+                "removed 'dest/usr/include/lib.h'\n".to_string()
+                    + &copied("lib.h", "dest/usr/include/lib.h"),
+                "".into()
+            ),
+        ]
+    );
+    assert_eq!(outputs[6], (0, "".into(), "".into()));
+    assert_eq!(
+        outputs[7..9],
+        [
+            (
+                0,
+                "'prog' -> 'dest/usr/bin/prog' (backup: 'dest/usr/bin/prog~')\n".into(),
+                "".into()
+            ),
+            (
+                0,
+                "'prog' -> 'dest/usr/bin/prog' (backup: 'dest/usr/bin/prog.old')\n".into(),
+                "".into()
+            ),
+        ]
+    );
+
+    let file = |name: &str, mode, contents: &str| (name.to_string(), mode, Some(contents.into()));
+    let directory = |name: &str, mode| (name.to_string(), mode, None);
+    let (prog, man, lib) = ("#!/bin/sh\necho hi\n", "man\n", "int x;\n");
+    assert_eq!(
+        tree(&dir.path().join("dest")),
+        [
+            directory("usr", 0o755),
+            directory("usr/bin", 0o755),
+            file("usr/bin/p2", 0o755, prog),
+            file("usr/bin/p3", 0o755, prog),
+            file("usr/bin/prog", 0o755, prog),
+            file("usr/bin/prog.old", 0o755, prog),
+            file("usr/bin/prog~", 0o755, prog),
+            directory("usr/include", 0o755),
+            file("usr/include/lib.h", 0o644, lib),
+            file("usr/include/prog.1", 0o755, man),
+            directory("usr/include/sub", 0o755),
+            file("usr/include/sub/lib.h", 0o644, lib),
+            directory("usr/share", 0o755),
+            directory("usr/share/man", 0o755),
+            directory("usr/share/man/man1", 0o755),
+            file("usr/share/man/man1/prog.1", 0o644, man),
+            // Only the bits the mode mentions
+            directory("usr/shared", 0o2000),
+        ]
+    );
+
+    let gnu = setup("install-make-gnu");
+    if gnu_install(&gnu) {
+        for (args, ours) in MAKE_INSTALL.iter().zip(&outputs) {
+            assert_eq!(&run("install", args, gnu.path()), ours, "{args:?}");
+        }
+        assert_eq!(
+            tree(&gnu.path().join("dest")),
+            tree(&dir.path().join("dest"))
+        );
+    }
+}
+
+#[test]
+fn compare() {
+    let dir = setup("install-compare");
+    assert_eq!(install(&dir, &["-m", "644", "lib.h", "h"]).0, 0);
+    // Whether installing with `args` replaced h, backdated beforehand
+    let replaces = |args: &[&str]| {
+        let (status, _, _) = run("touch", &["-d", "2001-01-01", "h"], dir.path());
+        assert_eq!(status, 0);
+        let mtime = fs::metadata(dir.path().join("h")).unwrap().mtime();
+        assert_eq!(install(&dir, args), (0, "".into(), "".into()), "{args:?}");
+        fs::metadata(dir.path().join("h")).unwrap().mtime() != mtime
+    };
+    // The same contents, mode and owner leave the destination alone
+    assert!(!replaces(&["-C", "-m", "644", "lib.h", "h"]));
+    // Not when any of them differ
+    assert!(replaces(&["-C", "lib.h", "h"]));
+    assert!(replaces(&["-C", "prog", "h"]));
+    assert!(!replaces(&["-C", "prog", "h"]));
+    // Without -C it's always copied
+    assert!(replaces(&["prog", "h"]));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("h")).unwrap(),
+        "#!/bin/sh\necho hi\n"
+    );
+}
+
+#[test]
+fn preserve_timestamps() {
+    let dir = setup("install-timestamps");
+    let (status, _, _) = run("touch", &["-d", "2001-01-01 00:00:00", "lib.h"], dir.path());
+    assert_eq!(status, 0);
+    let source = fs::metadata(dir.path().join("lib.h")).unwrap();
+    assert_eq!(install(&dir, &["-p", "lib.h", "kept"]).0, 0);
+    assert_eq!(install(&dir, &["lib.h", "new"]).0, 0);
+    let kept = fs::metadata(dir.path().join("kept")).unwrap();
+    let new = fs::metadata(dir.path().join("new")).unwrap();
+    assert_eq!(kept.mtime(), source.mtime());
+    assert_ne!(new.mtime(), source.mtime());
+}
+
+#[test]
+fn strip() {
+    let dir = setup("install-strip");
+    // A strip program telling what it was run on
+    dir.write("strip.sh", "#!/bin/sh\necho \"$@\" > stripped\n");
+    let strip_sh = dir.path().join("strip.sh");
+    fs::set_permissions(strip_sh, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(
+        install(&dir, &["-s", "--strip-program=./strip.sh", "prog", "p"]),
+        (0, "".into(), "".into())
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("stripped")).unwrap(),
+        "p\n"
+    );
+    assert_eq!(
+        install(&dir, &["-s", "--strip-program=false", "prog", "p"]),
+        (
+            1,
+            "".into(),
+            "install: strip process terminated abnormally\n".into()
+        )
+    );
+}
+
+#[test]
+fn errors() {
+    let dir = setup("install-errors");
+    let try_help = "Try 'install --help' for more information.\n";
+    for (args, message) in [
+        (&[][..], "missing file operand"),
+        (&["prog"], "missing destination file operand after 'prog'"),
+        (&["-T", "prog", "lib.h", "dest"], "extra operand 'dest'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-m"], "option requires an argument -- 'm'"),
+    ] {
+        assert_eq!(
+            install(&dir, args),
+            (1, "".into(), format!("install: {message}\n{try_help}")),
+            "{args:?}"
+        );
+    }
+    for (args, message) in [
+        (
+            &["-o", "nosuchuser", "prog", "x"][..],
+            "invalid user 'nosuchuser'",
+        ),
+        (
+            &["-g", "nosuchgroup", "prog", "x"],
+            "invalid group 'nosuchgroup'",
+        ),
+        (&["-m", "999", "prog", "x"], "invalid mode '999'"),
+        (
+            &["-t", "nodir", "prog"],
+            "failed to access 'nodir': No such file or directory",
+        ),
+        (
+            &["-d", "-t", "dest", "prog"],
+            "target directory not allowed when installing a directory",
+        ),
+        (
+            &["nofile", "x"],
+            "cannot stat 'nofile': No such file or directory",
+        ),
+        (&["prog", "prog"], "'prog' and 'prog' are the same file"),
+    ] {
+        assert_eq!(
+            install(&dir, args),
+            (1, "".into(), format!("install: {message}\n")),
+            "{args:?}"
+        );
+    }
+}
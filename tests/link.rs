@@ -0,0 +1,68 @@
+mod common;
+
+use common::{run, TempDir};
+use std::os::unix::fs::MetadataExt;
+
+fn link(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_link"), args, dir.path())
+}
+
+#[test]
+fn operand_counts() {
+    let dir = TempDir::new("link-operands");
+    let try_help = "Try 'link --help' for more information.\n";
+    let cases: &[(&[&str], &str)] = &[
+        (&[], "link: missing operand\n"),
+        (&["a"], "link: missing operand after 'a'\n"),
+        (&["a", "b", "c"], "link: extra operand 'c'\n"),
+        (&["a", "b", "c d", "e"], "link: extra operand 'c d'\n"),
+    ];
+    for (args, error) in cases {
+        let expected = (1, "".into(), format!("{error}{try_help}"));
+        assert_eq!(link(&dir, args), expected, "{args:?}");
+    }
+}
+
+#[test]
+fn links() {
+    let dir = TempDir::new("link-links");
+    dir.write("a", "x");
+    assert_eq!(link(&dir, &["a", "b"]), (0, "".into(), "".into()));
+    let ino = |name: &str| dir.path().join(name).metadata().unwrap().ino();
+    assert_eq!(ino("a"), ino("b"));
+
+    assert_eq!(
+        link(&dir, &["a", "b"]),
+        (
+            1,
+            "".into(),
+            "link: cannot create link 'b' to 'a': File exists\n".into()
+        )
+    );
+    assert_eq!(
+        link(&dir, &["missing", "c"]),
+        (
+            1,
+            "".into(),
+            "link: cannot create link 'c' to 'missing': No such file or directory\n".into()
+        )
+    );
+}
+
+#[test]
+fn cross_device() {
+    let dir = TempDir::new("link-cross-device");
+    dir.write("a", "x");
+    // Only where another file system is at hand
+    let shm = std::path::Path::new("/dev/shm");
+    let other = shm.metadata().map(|shm| shm.dev());
+    if other.map_or(true, |dev| dev == dir.path().metadata().unwrap().dev()) {
+        return;
+    }
+    let name = shm.join(format!("ratiscat-{}-link", std::process::id()));
+    let name = name.to_str().unwrap();
+    let (status, out, err) = link(&dir, &["a", name]);
+    let _ = std::fs::remove_file(name);
+    let error = format!("link: cannot create link '{name}' to 'a': Invalid cross-device link\n");
+    assert_eq!((status, out, err), (1, "".into(), error));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{run, TempDir};
+use std::process::{Command, Stdio};
+
+const NO_LOGIN_NAME: &str = "logname: no login name\n";
+
+// logname run in a session logged in as `uid`, None when the login uid can't be set
+fn logged_in(dir: &TempDir, uid: u32) -> Option<(i32, String, String)> {
+    let script = format!("echo {uid} > /proc/self/loginuid 2>/dev/null || exit 99; exec \"$0\"");
+    let output = run(
+        "sh",
+        &["-c", &script, env!("CARGO_BIN_EXE_logname")],
+        dir.path(),
+    );
+    (output.0 != 99).then_some(output)
+}
+
+#[test]
+fn no_login_name() {
+    let dir = TempDir::new("logname-none");
+    // In a new session without a controlling terminal or stdin, and with the
+    // login uid unset as far as it can be
+    let Ok(output) = Command::new("setsid")
+        .args(["-w", env!("CARGO_BIN_EXE_logname")])
+        .current_dir(dir.path())
+        .stdin(Stdio::null())
+        .output()
+    else {
+        return;
+    };
+    let loginuid = std::fs::read_to_string("/proc/self/loginuid").unwrap_or_default();
+    if matches!(loginuid.trim(), "" | "4294967295") {
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), NO_LOGIN_NAME);
+    }
+    // A login uid without a passwd entry has no name either, not an empty one
+    if let Some(output) = logged_in(&dir, 54321) {
+        assert_eq!(output, (1, "".into(), NO_LOGIN_NAME.into()));
+    }
+}
+
+#[test]
+fn session() {
+    let dir = TempDir::new("logname-session");
+    let Some(root) = nix::unistd::User::from_uid(0.into()).unwrap() else {
+        return;
+    };
+    if let Some(output) = logged_in(&dir, 0) {
+        assert_eq!(output, (0, format!("{}\n", root.name), "".into()));
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("logname-usage");
+    for (args, message) in [
+        (&["a"][..], "extra operand 'a'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("logname: {message}\nTry 'logname --help' for more information.\n");
+        assert_eq!(
+            run(env!("CARGO_BIN_EXE_logname"), args, dir.path()),
+            (1, "".into(), stderr),
+            "{args:?}"
+        );
+    }
+}
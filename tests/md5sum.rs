@@ -0,0 +1,147 @@
+mod common;
+
+use common::{run, TempDir};
+
+const GOOD: &str = "60b725f10c9c85c70d97880dfe8191b3";
+
+// `good` matching its md5, `bad` not, the lists checking them
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("good", "a\n");
+    dir.write("bad", "b\n");
+    dir.write("back\\slash", "x");
+    dir.write("new\nline", "y");
+    dir.write(" lead", "");
+    dir.write("mixed", format!("{GOOD}  good\n{GOOD} *bad\n"));
+    dir.write("improper", format!("{GOOD}  good\nbogus\n{GOOD}ff  good\n"));
+    dir.write("reversed", format!("{GOOD} good\n"));
+    dir.write("mixed_formats", format!("{GOOD} good\n{GOOD}  good\n"));
+    dir.write("missing", format!("{GOOD}  nofile\n{GOOD}  good\n"));
+    dir
+}
+
+fn md5sum(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_md5sum"), args, dir.path())
+}
+
+#[test]
+fn escaped_names_round_trip() {
+    let dir = setup("md5sum-escaped");
+    let names = ["good", "back\\slash", "new\nline", " lead"];
+    let (status, list, _) = md5sum(&dir, &names);
+    assert_eq!(status, 0);
+    assert_eq!(
+        list,
+        "60b725f10c9c85c70d97880dfe8191b3  good\n\
+         \\9dd4e461268c8034f5c8564e155c67a6  back\\\\slash\n\
+         \\415290769594460e2e485922904f345d  new\\nline\n\
+         d41d8cd98f00b204e9800998ecf8427e   lead\n"
+    );
+    dir.write("all.md5", list);
+    assert_eq!(
+        md5sum(&dir, &["-c", "all.md5"]),
+        (
+            0,
+            "good: OK\nback\\slash: OK\n\\new\\nline: OK\n lead: OK\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--quiet", "all.md5"]),
+        (0, "".into(), "".into())
+    );
+}
+
+#[test]
+fn check_mismatch() {
+    let dir = setup("md5sum-mismatch");
+    let warning = "md5sum: WARNING: 1 computed checksum did NOT match\n";
+    assert_eq!(
+        md5sum(&dir, &["-c", "mixed"]),
+        (1, "good: OK\nbad: FAILED\n".into(), warning.into())
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--quiet", "mixed"]),
+        (1, "bad: FAILED\n".into(), warning.into())
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--status", "mixed"]),
+        (1, "".into(), "".into())
+    );
+}
+
+#[test]
+fn check_improperly_formatted() {
+    let dir = setup("md5sum-improper");
+    let warning = "md5sum: WARNING: 2 lines are improperly formatted\n";
+    assert_eq!(
+        md5sum(&dir, &["-c", "improper"]),
+        (0, "good: OK\n".into(), warning.into())
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--strict", "improper"]),
+        (1, "good: OK\n".into(), warning.into())
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "-w", "improper"]),
+        (
+            0,
+            "good: OK\n".into(),
+            format!(
+                "md5sum: improper: 2: improperly formatted MD5 checksum line\n\
+                 md5sum: improper: 3: improperly formatted MD5 checksum line\n{warning}"
+            )
+        )
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--status", "--strict", "improper"]),
+        (1, "".into(), "".into())
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "good"]),
+        (
+            1,
+            "".into(),
+            "md5sum: good: no properly formatted checksum lines found\n".into()
+        )
+    );
+}
+
+#[test]
+fn check_formats() {
+    let dir = setup("md5sum-formats");
+    assert_eq!(
+        md5sum(&dir, &["-c", "reversed"]),
+        (0, "good: OK\n".into(), "".into())
+    );
+    // Once reversed, the second blank is part of the name
+    assert_eq!(
+        md5sum(&dir, &["-c", "mixed_formats"]),
+        (
+            1,
+            "good: OK\n good: FAILED open or read\n".into(),
+            "md5sum: ' good': No such file or directory\n\
+             md5sum: WARNING: 1 listed file could not be read\n"
+                .into()
+        )
+    );
+}
+
+#[test]
+fn check_missing() {
+    let dir = setup("md5sum-missing");
+    assert_eq!(
+        md5sum(&dir, &["-c", "missing"]),
+        (
+            1,
+            "nofile: FAILED open or read\ngood: OK\n".into(),
+            "md5sum: nofile: No such file or directory\n\
+             md5sum: WARNING: 1 listed file could not be read\n"
+                .into()
+        )
+    );
+    assert_eq!(
+        md5sum(&dir, &["-c", "--ignore-missing", "missing"]),
+        (0, "good: OK\n".into(), "".into())
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+// Run mkfifo under `umask`
+fn mkfifo(dir: &TempDir, umask: &str, args: &[&str]) -> (i32, String, String) {
+    let script = format!("umask {umask} && exec \"$@\"");
+    let prog = env!("CARGO_BIN_EXE_mkfifo");
+    run(
+        "sh",
+        &[&["-c", &script, "sh", prog][..], args].concat(),
+        dir.path(),
+    )
+}
+
+fn fifo_mode(dir: &TempDir, name: &str) -> u32 {
+    let meta = fs::symlink_metadata(dir.path().join(name)).unwrap();
+    assert!(meta.file_type().is_fifo(), "{name}");
+    meta.permissions().mode() & 0o7777
+}
+
+#[test]
+fn modes() {
+    let dir = TempDir::new("mkfifo-modes");
+    // a=rw less the umask by default
+    assert_eq!(mkfifo(&dir, "027", &["a"]), (0, "".into(), "".into()));
+    assert_eq!(fifo_mode(&dir, "a"), 0o640);
+    // -m isn't filtered by the umask, only its clauses without a who are
+    for (name, mode, expected) in [
+        ("b", "666", 0o666),
+        ("c", "a=rw,o-w", 0o664),
+        ("d", "u+x", 0o766),
+        ("e", "+x", 0o766),
+        ("f", "0", 0o000),
+    ] {
+        assert_eq!(
+            mkfifo(&dir, "077", &["-m", mode, name]),
+            (0, "".into(), "".into()),
+            "{mode}"
+        );
+        assert_eq!(fifo_mode(&dir, name), expected, "{mode}");
+    }
+    // Only the permission bits
+    assert_eq!(
+        mkfifo(&dir, "022", &["-m", "1777", "g"]),
+        (
+            1,
+            "".into(),
+            "mkfifo: mode must specify only file permission bits\n".into()
+        )
+    );
+    assert_eq!(
+        mkfifo(&dir, "022", &["-m", "999", "g"]),
+        (1, "".into(), "mkfifo: invalid mode\n".into())
+    );
+    assert!(!dir.path().join("g").exists());
+}
+
+#[test]
+fn operands() {
+    let dir = TempDir::new("mkfifo-operands");
+    dir.write("a", "");
+    // The others are still created
+    assert_eq!(
+        mkfifo(&dir, "022", &["a", "nodir/x", "b", "c"]),
+        (
+            1,
+            "".into(),
+            "mkfifo: cannot create fifo 'a': File exists\n\
+             mkfifo: cannot create fifo 'nodir/x': No such file or directory\n"
+                .into()
+        )
+    );
+    assert_eq!(fifo_mode(&dir, "b"), 0o644);
+    assert_eq!(fifo_mode(&dir, "c"), 0o644);
+    assert!(fs::metadata(dir.path().join("a")).unwrap().is_file());
+}
+
+#[test]
+fn selinux() {
+    let dir = TempDir::new("mkfifo-selinux");
+    assert_eq!(mkfifo(&dir, "022", &["-Z", "a"]), (0, "".into(), "".into()));
+    assert_eq!(
+        mkfifo(&dir, "022", &["--context=ctx", "b"]),
+        (
+            0,
+            "".into(),
+            "mkfifo: warning: ignoring --context; it requires an SELinux/SMACK-enabled kernel\n"
+                .into()
+        )
+    );
+    assert_eq!(fifo_mode(&dir, "a"), 0o644);
+    assert_eq!(fifo_mode(&dir, "b"), 0o644);
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("mkfifo-usage");
+    for (args, message) in [
+        (&[][..], "missing operand"),
+        (&["-x", "a"], "invalid option -- 'x'"),
+        (&["--foo", "a"], "unrecognized option '--foo'"),
+        (&["-m"], "option requires an argument -- 'm'"),
+    ] {
+        let stderr = format!("mkfifo: {message}\nTry 'mkfifo --help' for more information.\n");
+        assert_eq!(
+            mkfifo(&dir, "022", args),
+            (1, "".into(), stderr),
+            "{args:?}"
+        );
+    }
+    assert!(!dir.path().join("a").exists());
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+fn mktemp(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_mktemp"), args, dir.path())
+}
+
+// mktemp run with TMPDIR set to `tmpdir`
+fn mktemp_in(dir: &TempDir, tmpdir: &str, args: &[&str]) -> String {
+    let tmpdir = format!("TMPDIR={tmpdir}");
+    let args = [&[tmpdir.as_str(), env!("CARGO_BIN_EXE_mktemp")][..], args].concat();
+    let (status, stdout, stderr) = run("env", &args, dir.path());
+    assert_eq!((status, stderr.as_str()), (0, ""), "{args:?}");
+    stdout
+}
+
+// Whether `name` is `template` with its X's replaced
+fn matches(name: &str, template: &str) -> bool {
+    name.len() == template.len()
+        && name.chars().zip(template.chars()).all(|(n, t)| match t {
+            'X' => LETTERS.contains(n),
+            _ => n == t,
+        })
+}
+
+fn mode(dir: &TempDir, name: &str) -> u32 {
+    let meta = fs::metadata(dir.path().join(name)).unwrap();
+    meta.permissions().mode() & 0o7777
+}
+
+#[test]
+fn create() {
+    let dir = TempDir::new("mktemp-create");
+    let (status, file, stderr) = mktemp(&dir, &["fooXXXXXX"]);
+    assert_eq!((status, stderr.as_str()), (0, ""));
+    let file = file.strip_suffix('\n').unwrap();
+    assert!(matches(file, "fooXXXXXX"), "{file}");
+    assert!(fs::metadata(dir.path().join(file)).unwrap().is_file());
+    assert_eq!(mode(&dir, file), 0o600);
+
+    let (status, directory, _) = mktemp(&dir, &["-d", "dXXX"]);
+    assert_eq!(status, 0);
+    let directory = directory.strip_suffix('\n').unwrap();
+    assert!(matches(directory, "dXXX"), "{directory}");
+    assert!(fs::metadata(dir.path().join(directory)).unwrap().is_dir());
+    assert_eq!(mode(&dir, directory), 0o700);
+
+    // Only the last run of X's, the suffix after it kept
+    let (_, name, _) = mktemp(&dir, &["--suffix=.c", "XXaXXX"]);
+    assert!(matches(name.trim_end(), "XXaXXX.c"), "{name}");
+    // Nothing created with -u
+    let (status, name, _) = mktemp(&dir, &["-u", "uXXX"]);
+    assert_eq!(status, 0);
+    assert!(matches(name.trim_end(), "uXXX"), "{name}");
+    assert!(!dir.path().join(name.trim_end()).exists());
+}
+
+#[test]
+fn directories() {
+    let dir = TempDir::new("mktemp-directories");
+    for name in ["env", "p"] {
+        fs::create_dir(dir.path().join(name)).unwrap();
+    }
+    // -p and --tmpdir over TMPDIR, TMPDIR over -p with -t
+    for (tmpdir, args, template) in [
+        ("env", &["--tmpdir", "aXXX"][..], "env/aXXX"),
+        ("env", &["-p", "p", "aXXX"], "p/aXXX"),
+        ("env", &["--tmpdir=p", "aXXX"], "p/aXXX"),
+        ("", &["-p", "p", "bXXX"], "p/bXXX"),
+        ("env", &["-t", "cXXX"], "env/cXXX"),
+        ("env", &["-t", "-p", "p", "dXXX"], "env/dXXX"),
+        ("", &["-t", "-p", "p", "dXXX"], "p/dXXX"),
+        ("env", &["-p", "p"], "p/tmp.XXXXXXXXXX"),
+        ("env", &["-d"], "env/tmp.XXXXXXXXXX"),
+    ] {
+        let name = mktemp_in(&dir, tmpdir, args);
+        assert!(matches(name.trim_end(), template), "{args:?}: {name}");
+        assert!(dir.path().join(name.trim_end()).exists(), "{args:?}");
+    }
+    // /tmp without TMPDIR, a template is relative to the current directory
+    // without -p, --tmpdir or -t
+    let name = mktemp_in(&dir, "", &["-u", "--tmpdir"]);
+    assert!(matches(name.trim_end(), "/tmp/tmp.XXXXXXXXXX"), "{name}");
+    let name = mktemp_in(&dir, "env", &["-u", "aXXX"]);
+    assert!(matches(name.trim_end(), "aXXX"), "{name}");
+}
+
+// cargo test --test mktemp -- --ignored collisions
+#[test]
+#[ignore = "creates 62^3 files"]
+fn collisions() {
+    let dir = TempDir::new("mktemp-collisions");
+    fs::create_dir(dir.path().join("full")).unwrap();
+    // Every name but those starting zz, so fresh names are tried thousands of times
+    let mut free = Vec::new();
+    for a in LETTERS.chars() {
+        for b in LETTERS.chars() {
+            for c in LETTERS.chars() {
+                let name = format!("{a}{b}{c}");
+                match (a, b) {
+                    ('z', 'z') => free.push(name),
+                    _ => dir.write(&format!("full/{name}"), ""),
+                }
+            }
+        }
+    }
+    let (status, name, stderr) = mktemp(&dir, &["-p", "full", "XXX"]);
+    assert_eq!((status, stderr.as_str()), (0, ""));
+    let name = name.trim_end().strip_prefix("full/").unwrap();
+    assert!(free.contains(&name.to_string()), "{name}");
+    assert_eq!(fs::read(dir.path().join("full").join(name)).unwrap(), b"");
+    // Then none left
+    for name in free {
+        dir.write(&format!("full/{name}"), "");
+    }
+    assert_eq!(
+        mktemp(&dir, &["-p", "full", "XXX"]),
+        (
+            1,
+            "".into(),
+            "mktemp: failed to create file via template 'full/XXX': File exists\n".into()
+        )
+    );
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("mktemp-errors");
+    for (args, message) in [
+        (&["fooXX"][..], "too few X's in template 'fooXX'"),
+        (&["foo"], "too few X's in template 'foo'"),
+        (
+            &["--suffix=/x", "fooXXX"],
+            "invalid suffix '/x', contains directory separator",
+        ),
+        (
+            &["--suffix=.c", "fooXXXa"],
+            "with --suffix, template 'fooXXXa' must end in X",
+        ),
+        (
+            &["-t", "/abs.XXX"],
+            "invalid template, '/abs.XXX', contains directory separator",
+        ),
+        (
+            &["--tmpdir", "/abs.XXX"],
+            "invalid template, '/abs.XXX'; with --tmpdir, it may not be absolute",
+        ),
+        (
+            &["-p", "nodir", "XXX"],
+            "failed to create file via template 'nodir/XXX': No such file or directory",
+        ),
+    ] {
+        assert_eq!(
+            mktemp(&dir, args),
+            (1, "".into(), format!("mktemp: {message}\n")),
+            "{args:?}"
+        );
+    }
+    // -q only quiets failures to create
+    assert_eq!(
+        mktemp(&dir, &["-q", "-p", "nodir", "XXX"]),
+        (1, "".into(), "".into())
+    );
+    assert_eq!(
+        mktemp(&dir, &["-q", "foo"]),
+        (
+            1,
+            "".into(),
+            "mktemp: too few X's in template 'foo'\n".into()
+        )
+    );
+    let try_help = "Try 'mktemp --help' for more information.\n";
+    for (args, message) in [
+        (&["aXXX", "bXXX"][..], "too many templates"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["-p"], "option requires an argument -- 'p'"),
+    ] {
+        assert_eq!(
+            mktemp(&dir, args),
+            (1, "".into(), format!("mktemp: {message}\n{try_help}")),
+            "{args:?}"
+        );
+    }
+}
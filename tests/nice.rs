@@ -0,0 +1,170 @@
+mod common;
+
+use common::{run, TempDir};
+use nix::unistd::geteuid;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+const NICE: &str = env!("CARGO_BIN_EXE_nice");
+
+fn nice(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(NICE, args, dir.path())
+}
+
+// Whether nice on PATH is GNU's, to compare with
+fn gnu_nice(dir: &TempDir) -> bool {
+    let (_, version, _) = run("nice", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// The niceness the tests run at, and what adding `adjustment` to it sets
+fn niceness() -> i32 {
+    unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }
+}
+
+fn adjusted(adjustment: i32) -> String {
+    format!("{}\n", (niceness() + adjustment).clamp(-20, 19))
+}
+
+#[test]
+fn adjustments() {
+    let dir = TempDir::new("nice-adjustments");
+    assert_eq!(nice(&dir, &[]), (0, adjusted(0), "".into()));
+    // The command is nice again, printing what it runs at
+    for (args, adjustment) in [
+        (&[][..], 10),
+        (&["-n", "5"], 5),
+        (&["--adjustment=5"], 5),
+        (&["-5"], 5),
+        (&["-n", "+3"], 3),
+        (&["-n", " 3"], 3),
+        (&["-n", "3", "-n", "4"], 4),
+        (&["-2", "-n", "4"], 4),
+        (&["-n", "100"], 39),
+        (&["-n", "99999999999999999999"], 39),
+    ] {
+        let args = [args, &[NICE]].concat();
+        assert_eq!(
+            nice(&dir, &args),
+            (0, adjusted(adjustment), "".into()),
+            "{args:?}"
+        );
+    }
+    // Options end at the command, its own left to it
+    assert_eq!(
+        nice(&dir, &["-n", "1", NICE, "-n", "2", NICE]),
+        (0, adjusted(3), "".into())
+    );
+    // Raising it back up is only for the privileged
+    if geteuid().is_root() {
+        for (args, adjustment) in [
+            (&["--5"][..], -5),
+            (&["-n", "-3"], -3),
+            (&["-n", "-100"], -39),
+        ] {
+            let args = [args, &[NICE]].concat();
+            assert_eq!(
+                nice(&dir, &args),
+                (0, adjusted(adjustment), "".into()),
+                "{args:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn exit_statuses() {
+    let dir = TempDir::new("nice-statuses");
+    dir.write("data", "");
+    // The command's own status passed through
+    assert_eq!(
+        nice(&dir, &["sh", "-c", "exit 7"]),
+        (7, "".into(), "".into())
+    );
+    assert_eq!(nice(&dir, &["true", "-x"]), (0, "".into(), "".into()));
+    for (args, status, message) in [
+        (&["nosuch"][..], 127, "'nosuch': No such file or directory"),
+        (&["./data"], 126, "'./data': Permission denied"),
+        (&["."], 126, "'.': Permission denied"),
+        (&["-n", "x", "true"], 125, "invalid adjustment 'x'"),
+        (&["-n", "1x", "true"], 125, "invalid adjustment '1x'"),
+    ] {
+        assert_eq!(
+            nice(&dir, args),
+            (status, "".into(), format!("nice: {message}\n")),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn unprivileged() {
+    if !geteuid().is_root() {
+        return;
+    }
+    // Copied out of the target directory, which may not be theirs to search
+    let dir = TempDir::new("nice-unprivileged");
+    let nice = dir.path().join("nice");
+    fs::copy(NICE, &nice).unwrap();
+    // A warning, then the command run at the niceness it already had
+    let output = Command::new(&nice)
+        .args(["-n", "-5", nice.to_str().unwrap()])
+        .uid(65534)
+        .gid(65534)
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), adjusted(0));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "nice: cannot set niceness: Permission denied\n"
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("nice-gnu");
+    if !gnu_nice(&dir) {
+        return;
+    }
+    dir.write("data", "");
+    for args in [
+        &[][..],
+        &["nice"],
+        &["-n", "5", "nice"],
+        &["-5", "nice"],
+        &["--5", "nice"],
+        &["-n", "-3", "nice"],
+        &["-n", "100", "nice"],
+        &["nice", "-n", "3", "nice"],
+        &["-n", "x", "true"],
+        &["-n", "5"],
+        &["nosuch"],
+        &["./data"],
+        &["sh", "-c", "exit 7"],
+        &["-x"],
+        &["-n"],
+        &["--foo"],
+    ] {
+        assert_eq!(nice(&dir, args), run("nice", args, dir.path()), "{args:?}");
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("nice-usage");
+    for (args, message) in [
+        (
+            &["-n", "5"][..],
+            "a command must be given with an adjustment",
+        ),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-n"], "option requires an argument -- 'n'"),
+    ] {
+        let stderr = format!("nice: {message}\nTry 'nice --help' for more information.\n");
+        assert_eq!(nice(&dir, args), (125, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,280 @@
+mod common;
+
+use common::{pty, read_pty, run, TempDir};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// What a run under a pty gave: the status, what reached the terminal, and what
+// went to stdout and stderr when they weren't the terminal
+#[derive(Debug, PartialEq)]
+struct Run {
+    status: i32,
+    terminal: String,
+    stdout: String,
+    stderr: String,
+}
+
+// `nohup` run in `dir` with HOME at `home`, those of stdin, stdout and stderr in
+// `on_terminal` connected to a pty and the others to /dev/null or pipes
+fn nohup_on(nohup: &str, dir: &Path, home: &Path, args: &[&str], on_terminal: [bool; 3]) -> Run {
+    let (master, terminal) = pty(24);
+    let output = {
+        let stdio = |on: bool, otherwise: fn() -> Stdio| match on {
+            true => Stdio::from(terminal.try_clone().unwrap()),
+            false => otherwise(),
+        };
+        Command::new(nohup)
+            .args(args)
+            .current_dir(dir)
+            .env("HOME", home)
+            .env_remove("POSIXLY_CORRECT")
+            .stdin(stdio(on_terminal[0], Stdio::null))
+            .stdout(stdio(on_terminal[1], Stdio::piped))
+            .stderr(stdio(on_terminal[2], Stdio::piped))
+            .output()
+            .unwrap()
+    };
+    drop(terminal);
+    Run {
+        status: output.status.code().unwrap(),
+        terminal: read_pty(master),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+fn run_on(status: i32, terminal: &str, stdout: &str, stderr: &str) -> Run {
+    Run {
+        status,
+        terminal: terminal.into(),
+        stdout: stdout.into(),
+        stderr: stderr.into(),
+    }
+}
+
+// Whether nohup on PATH is GNU's, to compare with
+fn gnu_nohup(dir: &TempDir) -> bool {
+    let (_, version, _) = run("nohup", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// A command writing to stdout and stderr, then telling whether stdin can be read
+const SCRIPT: &str = "echo out; echo err >&2; cat 2>/dev/null; echo read $?";
+
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    fs::create_dir(dir.path().join("work")).unwrap();
+    fs::create_dir(dir.path().join("home")).unwrap();
+    dir
+}
+
+// The nohup.out of `dir`, its mode and contents
+fn nohup_out(dir: &Path) -> Option<(u32, String)> {
+    let file = dir.join("nohup.out");
+    let mode = fs::metadata(&file).ok()?.permissions().mode() & 0o7777;
+    Some((mode, fs::read_to_string(file).unwrap()))
+}
+
+#[test]
+fn redirections() {
+    let nohup = env!("CARGO_BIN_EXE_nohup");
+    let dir = setup("nohup-redirections");
+    let (work, home) = (dir.path().join("work"), dir.path().join("home"));
+    let args = ["sh", "-c", SCRIPT];
+    let appending = "nohup: ignoring input and appending output to 'nohup.out'\n";
+    assert_eq!(
+        nohup_on(nohup, &work, &home, &args, [true; 3]),
+        run_on(0, appending, "", "")
+    );
+    // Appended to, stdin unreadable
+    assert_eq!(
+        nohup_on(nohup, &work, &home, &args, [true; 3]),
+        run_on(0, appending, "", "")
+    );
+    let twice = "out\nerr\nread 1\n".repeat(2);
+    assert_eq!(nohup_out(&work), Some((0o600, twice)));
+    fs::remove_file(work.join("nohup.out")).unwrap();
+
+    // Only what's a terminal is redirected
+    for (on_terminal, expected) in [
+        (
+            [false, true, true],
+            run_on(0, "nohup: appending output to 'nohup.out'\n", "", ""),
+        ),
+        (
+            [true, false, true],
+            run_on(
+                0,
+                "nohup: ignoring input and redirecting stderr to stdout\n",
+                "out\nerr\nread 1\n",
+                "",
+            ),
+        ),
+        (
+            [false, false, true],
+            run_on(
+                0,
+                "nohup: redirecting stderr to stdout\n",
+                "out\nerr\nread 0\n",
+                "",
+            ),
+        ),
+        (
+            [true, false, false],
+            run_on(0, "", "out\nread 1\n", "nohup: ignoring input\nerr\n"),
+        ),
+        (
+            [false, false, false],
+            run_on(0, "", "out\nread 0\n", "err\n"),
+        ),
+    ] {
+        let ran = nohup_on(nohup, &work, &home, &args, on_terminal);
+        assert_eq!(ran, expected, "{on_terminal:?}");
+        let written = nohup_out(&work).map(|(_, contents)| contents);
+        match on_terminal[1] {
+            true => assert_eq!(written.as_deref(), Some("out\nerr\nread 0\n")),
+            false => assert_eq!(written, None, "{on_terminal:?}"),
+        }
+        let _ = fs::remove_file(work.join("nohup.out"));
+    }
+}
+
+#[test]
+fn home_fallback() {
+    let nohup = env!("CARGO_BIN_EXE_nohup");
+    let dir = setup("nohup-home");
+    let (work, home) = (dir.path().join("work"), dir.path().join("home"));
+    // Not something that can be appended to, even by root
+    fs::create_dir(work.join("nohup.out")).unwrap();
+    let args = ["sh", "-c", SCRIPT];
+    let in_home = format!("{}/nohup.out", home.display());
+    assert_eq!(
+        nohup_on(nohup, &work, &home, &args, [true; 3]),
+        run_on(
+            0,
+            &format!("nohup: ignoring input and appending output to '{in_home}'\n"),
+            "",
+            ""
+        )
+    );
+    assert_eq!(nohup_out(&home), Some((0o600, "out\nerr\nread 1\n".into())));
+
+    // Neither, the command not run
+    fs::remove_file(home.join("nohup.out")).unwrap();
+    fs::create_dir(home.join("nohup.out")).unwrap();
+    assert_eq!(
+        nohup_on(nohup, &work, &home, &args, [true; 3]),
+        run_on(
+            125,
+            &format!(
+                "nohup: failed to open 'nohup.out': Is a directory\n\
+                 nohup: failed to open '{in_home}': Is a directory\n"
+            ),
+            "",
+            ""
+        )
+    );
+}
+
+#[test]
+fn hangups_ignored() {
+    let dir = setup("nohup-hangups");
+    let (work, home) = (dir.path().join("work"), dir.path().join("home"));
+    let args = ["sh", "-c", "kill -HUP $$; echo alive"];
+    assert_eq!(
+        nohup_on(env!("CARGO_BIN_EXE_nohup"), &work, &home, &args, [false; 3]),
+        run_on(0, "", "alive\n", "")
+    );
+}
+
+#[test]
+fn exit_statuses() {
+    let nohup = env!("CARGO_BIN_EXE_nohup");
+    let dir = setup("nohup-statuses");
+    let (work, home) = (dir.path().join("work"), dir.path().join("home"));
+    let on_pipes = |args: &[&str]| nohup_on(nohup, &work, &home, args, [false; 3]);
+    assert_eq!(on_pipes(&["sh", "-c", "exit 7"]), run_on(7, "", "", ""));
+    for (args, status, message) in [
+        (
+            &["nosuch"][..],
+            127,
+            "failed to run command 'nosuch': No such file or directory",
+        ),
+        (&["."], 126, "failed to run command '.': Permission denied"),
+    ] {
+        let stderr = format!("nohup: {message}\n");
+        assert_eq!(on_pipes(args), run_on(status, "", "", &stderr), "{args:?}");
+    }
+    // Reported on the terminal, not in nohup.out
+    assert_eq!(
+        nohup_on(nohup, &work, &home, &["nosuch"], [true; 3]),
+        run_on(
+            127,
+            "nohup: ignoring input and appending output to 'nohup.out'\n\
+             nohup: failed to run command 'nosuch': No such file or directory\n",
+            "",
+            ""
+        )
+    );
+    assert_eq!(nohup_out(&work), Some((0o600, "".into())));
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = setup("nohup-gnu");
+    if !gnu_nohup(&dir) {
+        return;
+    }
+    let home = dir.path().join("home");
+    for on_terminal in [
+        [true; 3],
+        [false, true, false],
+        [true, false, true],
+        [false; 3],
+    ] {
+        for args in [&["sh", "-c", SCRIPT][..], &["nosuch"]] {
+            let [ours, gnu] = ["ours", "gnu"].map(|name| {
+                let work = dir.path().join(name);
+                fs::create_dir(&work).unwrap();
+                work
+            });
+            let ran = nohup_on(env!("CARGO_BIN_EXE_nohup"), &ours, &home, args, on_terminal);
+            assert_eq!(ran, nohup_on("nohup", &gnu, &home, args, on_terminal));
+            assert_eq!(
+                nohup_out(&ours),
+                nohup_out(&gnu),
+                "{on_terminal:?} {args:?}"
+            );
+            fs::remove_dir_all(ours).unwrap();
+            fs::remove_dir_all(gnu).unwrap();
+        }
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = setup("nohup-usage");
+    let nohup = env!("CARGO_BIN_EXE_nohup");
+    let try_help = "Try 'nohup --help' for more information.\n";
+    for (args, message) in [
+        (&[][..], "missing operand"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("nohup: {message}\n{try_help}");
+        assert_eq!(
+            run(nohup, args, dir.path()),
+            (125, "".into(), stderr.clone()),
+            "{args:?}"
+        );
+        // 127 for POSIX
+        let posix = [&["POSIXLY_CORRECT=1", nohup][..], args].concat();
+        assert_eq!(
+            run("env", &posix, dir.path()),
+            (127, "".into(), stderr),
+            "{args:?}"
+        );
+    }
+}
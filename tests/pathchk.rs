@@ -0,0 +1,47 @@
+mod common;
+
+use common::{run, TempDir};
+
+fn pathchk(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_pathchk"), args, dir.path())
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("pathchk-usage");
+    let try_help = "Try 'pathchk --help' for more information.\n";
+    for (args, message) in [
+        (&["-x", "a"][..], "invalid option -- 'x'"),
+        (&["--foo", "a"], "unrecognized option '--foo'"),
+        (&["-p"], "missing operand"),
+    ] {
+        let stderr = format!("pathchk: {message}\n{try_help}");
+        assert_eq!(pathchk(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
+
+#[test]
+fn names() {
+    let dir = TempDir::new("pathchk-names");
+    let long = "x".repeat(20);
+    for (args, stderr) in [
+        (&["a", "a//b", "/nonexistent/f"][..], "".to_string()),
+        (&["-p", "a//b"], "".into()),
+        (
+            &["-P", "--", "-a"],
+            "pathchk: leading '-' in a component of file name '-a'\n".into(),
+        ),
+        (&["-P", ""], "pathchk: empty file name\n".into()),
+        (
+            &["-p", "é"],
+            "pathchk: nonportable character '\\303' in file name ''$'\\303\\251'\n".into(),
+        ),
+        (
+            &["-p", &long],
+            format!("pathchk: limit 14 exceeded by length 20 of file name component '{long}'\n"),
+        ),
+    ] {
+        let code = if stderr.is_empty() { 0 } else { 1 };
+        assert_eq!(pathchk(&dir, args), (code, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use std::ffi::OsStr;
+use std::fs::OpenOptions;
+use std::os::unix::ffi::OsStrExt;
+use std::process::{Command, Stdio};
+
+// printenv, or what `prog` is, run with only `env` in its environment; the output
+// bytes as they are
+fn printenv_with(prog: &str, env: &[(&[u8], &[u8])], args: &[&str]) -> (i32, Vec<u8>, String) {
+    let mut command = Command::new(prog);
+    command.args(args).env_clear().stdin(Stdio::null());
+    for (name, value) in env {
+        command.env(OsStr::from_bytes(name), OsStr::from_bytes(value));
+    }
+    let output = command.output().unwrap();
+    (
+        output.status.code().unwrap(),
+        output.stdout,
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn printenv(env: &[(&[u8], &[u8])], args: &[&str]) -> (i32, Vec<u8>, String) {
+    printenv_with(env!("CARGO_BIN_EXE_printenv"), env, args)
+}
+
+fn ok(stdout: &[u8]) -> (i32, Vec<u8>, String) {
+    (0, stdout.to_vec(), "".into())
+}
+
+// Values with a newline, nothing, and bytes that aren't UTF-8
+const ENV: [(&[u8], &[u8]); 4] = [
+    (b"A", b"1"),
+    (b"LINES", b"x\ny"),
+    (b"EMPTY", b""),
+    (b"RAW", b"\xff\xfe"),
+];
+
+#[test]
+fn everything() {
+    let expected = b"A=1\nLINES=x\ny\nEMPTY=\nRAW=\xff\xfe\n";
+    let (status, stdout, stderr) = printenv(&ENV, &[]);
+    assert_eq!((status, stderr.as_str()), (0, ""));
+    // In whatever order the environment has them
+    let mut lines: Vec<&[u8]> = stdout.split(|&c| c == b'\n').collect();
+    let mut expected_lines: Vec<&[u8]> = expected.split(|&c| c == b'\n').collect();
+    lines.sort();
+    expected_lines.sort();
+    assert_eq!(lines, expected_lines);
+    let (_, stdout, _) = printenv(&ENV, &["-0"]);
+    let mut entries: Vec<&[u8]> = stdout.split(|&c| c == b'\0').collect();
+    entries.sort();
+    assert_eq!(
+        entries,
+        [&b""[..], b"A=1", b"EMPTY=", b"LINES=x\ny", b"RAW=\xff\xfe"]
+    );
+    assert_eq!(printenv(&[], &[]), ok(b""));
+}
+
+#[test]
+fn variables() {
+    assert_eq!(printenv(&ENV, &["A"]), ok(b"1\n"));
+    assert_eq!(
+        printenv(&ENV, &["RAW", "LINES", "EMPTY", "A"]),
+        ok(b"\xff\xfe\nx\ny\n\n1\n")
+    );
+    assert_eq!(
+        printenv(&ENV, &["--null", "LINES", "RAW"]),
+        ok(b"x\ny\0\xff\xfe\0")
+    );
+    assert_eq!(printenv(&ENV, &["-0", "A", "A"]), ok(b"1\x001\0"));
+}
+
+#[test]
+fn missing() {
+    // Those found printed, 1 when any isn't
+    for (args, stdout) in [
+        (&["NOPE"][..], &b""[..]),
+        (&["A", "NOPE", "EMPTY"], b"1\n\n"),
+        (&["NOPE", "A"], b"1\n"),
+        // Names only, not assignments
+        (&["A=1"], b""),
+        (&[""], b""),
+        (&["a"], b""),
+    ] {
+        assert_eq!(
+            printenv(&ENV, args),
+            (1, stdout.to_vec(), "".into()),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn gnu_compatible() {
+    let (_, version, _) = printenv_with("env", &[], &["printenv", "--version"]);
+    if !String::from_utf8_lossy(&version).contains("GNU coreutils") {
+        return;
+    }
+    for args in [
+        &[][..],
+        &["-0"],
+        &["A", "LINES", "RAW"],
+        &["-0", "LINES", "NOPE", "EMPTY"],
+        &["A=1"],
+        &["-x"],
+    ] {
+        // Through env, which finds it without a PATH in the environment
+        let gnu = printenv_with("env", &ENV, &[&["printenv"][..], args].concat());
+        assert_eq!(printenv(&ENV, args), gnu, "{args:?}");
+    }
+}
+
+#[test]
+fn errors() {
+    let full = OpenOptions::new().write(true).open("/dev/full").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_printenv"))
+        .arg("PATH")
+        .stdout(full)
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "printenv: write error: No space left on device\n"
+    );
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("printenv: {message}\nTry 'printenv --help' for more information.\n");
+        assert_eq!(printenv(&ENV, args), (2, vec![], stderr), "{args:?}");
+    }
+}
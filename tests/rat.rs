@@ -0,0 +1,1209 @@
+mod common;
+
+use common::{pty, read_pty, run, run_with_input, TempDir};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Run rat with `args`, `input` on its stdin
+fn rat(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Run rat in `dir`
+fn rat_in(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_rat"), args, dir.path())
+}
+
+// Like `rat_in`, `input` on its stdin
+fn rat_piped(dir: &TempDir, args: &[&str], input: &str) -> (i32, String, String) {
+    run_with_input(
+        env!("CARGO_BIN_EXE_rat"),
+        args,
+        dir.path(),
+        input.as_bytes(),
+    )
+}
+
+fn ok(output: &str) -> (i32, String, String) {
+    (0, output.into(), "".into())
+}
+
+#[test]
+fn normalized_newlines_after_line_transforms() {
+    let output = rat(&["-nE", "--normalize-newlines-to=cr"], b"a\nb\n");
+    assert_eq!(output.stdout, b"     1\ta$\r     2\tb$\r");
+}
+
+#[test]
+fn show_ends_with_normalized_newlines() {
+    // Bare CR and CRLF endings read as lines, $ before the ending written
+    let output = rat(&["-E", "--normalize-newlines-to=crlf"], b"a\rb\r\n\nc");
+    assert_eq!(output.stdout, b"a$\r\nb$\r\n$\r\nc");
+
+    let output = rat(&["-E", "--normalize-newlines-to=lf"], b"a\r\nb\r");
+    assert_eq!(output.stdout, b"a$\nb$\n");
+}
+
+#[test]
+fn count_matches_names_stdin() {
+    let file = std::env::temp_dir().join(format!("ratiscat-{}-count", std::process::id()));
+    std::fs::write(&file, "ab\nb\n").unwrap();
+    let output = rat(
+        &["--grep=a", "--count-matches", "-", file.to_str().unwrap()],
+        b"ab\nx\n",
+    );
+    std::fs::remove_file(&file).unwrap();
+    let expected = format!("(standard input):1\n{}:1\ntotal:2\n", file.display());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn numbered_names_first() {
+    let dir = TempDir::new("rat-numbered-names");
+    dir.write("file", "ab\nb\na\n");
+    // Like `grep -n -H`, the file name before the number
+    assert_eq!(
+        rat_piped(&dir, &["-n", "-H", "--grep=a", "-", "file"], "x\na\n"),
+        ok("(standard input):     1\ta\nfile:     2\tab\nfile:     3\ta\n")
+    );
+    // -b still tells the blank lines apart with the names added
+    assert_eq!(
+        rat_piped(&dir, &["-b", "-H", "--grep=^a*$"], "a\n\na\n"),
+        ok("(standard input):     1\ta\n(standard input):\n(standard input):     2\ta\n")
+    );
+}
+
+#[test]
+fn max_consecutive_blank() {
+    let dir = TempDir::new("rat-max-blank");
+    let input = "a\n\nb\n\n\nc\n\n\n\n\nd\n";
+    let blank = |n: &str| rat_piped(&dir, &["--max-consecutive-blank", n], input);
+    assert_eq!(blank("0"), ok("a\nb\nc\nd\n"));
+    assert_eq!(blank("1"), ok("a\n\nb\n\nc\n\nd\n"));
+    // Shorter runs are left alone, longer ones cut down to N
+    assert_eq!(blank("2"), ok("a\n\nb\n\n\nc\n\n\nd\n"));
+    assert_eq!(blank("9"), ok(input));
+}
+
+#[test]
+fn max_consecutive_blank_across_files() {
+    let dir = TempDir::new("rat-max-blank-files");
+    dir.write("unterminated", "last");
+    dir.write("blanks", "\n\n\nx\n");
+    dir.write("only_blanks", "\n\n");
+    // The first newline ends the previous file's last line, it isn't blank
+    assert_eq!(
+        rat_in(
+            &dir,
+            &["--max-consecutive-blank=1", "unterminated", "blanks"]
+        ),
+        ok("last\n\nx\n")
+    );
+    assert_eq!(
+        rat_in(
+            &dir,
+            &["--max-consecutive-blank=0", "-n", "unterminated", "blanks"]
+        ),
+        ok("     1\tlast\n     2\tx\n")
+    );
+    // Runs carry on into the next file
+    assert_eq!(
+        rat_in(
+            &dir,
+            &["--max-consecutive-blank=2", "only_blanks", "blanks"]
+        ),
+        ok("\n\nx\n")
+    );
+}
+
+// What GNU cat prints for `args` in `dir`, None when cat isn't GNU's
+fn gnu_cat(dir: &TempDir, args: &[&str]) -> Option<(i32, String, String)> {
+    let (_, version, _) = run("cat", &["--version"], dir.path());
+    version
+        .contains("GNU coreutils")
+        .then(|| run("cat", args, dir.path()))
+}
+
+#[test]
+fn squeeze_blank() {
+    let dir = TempDir::new("rat-squeeze-blank");
+    dir.write("runs", "a\n\n\n\nb\n\n\nc\n\n\n\n\n");
+    dir.write("leading", "\n\n\nx\n");
+    dir.write("unterminated", "last");
+    dir.write("crs", "\r\n\r\n\n\n");
+    let cases: &[(&[&str], &str)] = &[
+        // Runs of 3 and more newlines and a trailing run all come down to one
+        (&["runs"], "a\n\nb\n\nc\n\n"),
+        (&["leading"], "\nx\n"),
+        (&["runs", "leading"], "a\n\nb\n\nc\n\nx\n"),
+        (&["unterminated", "leading"], "last\n\nx\n"),
+        (&["crs"], "\r\n\r\n\n"),
+    ];
+    for (files, expected) in cases {
+        let mut args = vec!["--squeeze-blank"];
+        args.extend_from_slice(files);
+        let output = rat_in(&dir, &args);
+        assert_eq!(output, ok(expected), "{files:?}");
+        let mut cat_args = vec!["-s"];
+        cat_args.extend_from_slice(files);
+        if let Some(cat) = gnu_cat(&dir, &cat_args) {
+            assert_eq!(output, cat, "{files:?}");
+        }
+    }
+}
+
+#[test]
+fn number_like_cat() {
+    let dir = TempDir::new("rat-number");
+    // Lines longer than any buffer, and one with no newline ending a file
+    dir.write(
+        "long",
+        format!("{}\n{}", "x".repeat(200_000), "y".repeat(70_000)),
+    );
+    dir.write("short", "a\nb");
+    dir.write("blanks", "\n\n");
+    dir.write("empty", "");
+    // Past 999999 the numbers take more than their 6 columns
+    dir.write("many", "x\n".repeat(1_000_002));
+
+    let long = format!(
+        "     1\t{}\n     2\t{}",
+        "x".repeat(200_000),
+        "y".repeat(70_000)
+    );
+    // Numbering carries on across files, an unterminated line joining the next file's
+    let cases: &[(&[&str], String)] = &[
+        (&["long"], long.clone()),
+        (
+            &["short", "short"],
+            "     1\ta\n     2\tba\n     3\tb".into(),
+        ),
+        (&["long", "short"], format!("{long}a\n     3\tb")),
+        (
+            &["blanks", "empty", "short"],
+            "     1\t\n     2\t\n     3\ta\n     4\tb".into(),
+        ),
+        (
+            &["short", "blanks"],
+            "     1\ta\n     2\tb\n     3\t\n".into(),
+        ),
+    ];
+    for (files, expected) in cases {
+        let args = [&["-n"], *files].concat();
+        let output = rat_in(&dir, &args);
+        assert_eq!(output, ok(expected), "{files:?}");
+        if let Some(cat) = gnu_cat(&dir, &args) {
+            assert_eq!(output, cat, "{files:?}");
+        }
+    }
+
+    let (status, many, _) = rat_in(&dir, &["-n", "many"]);
+    assert_eq!(status, 0);
+    assert!(many.ends_with("\n999999\tx\n1000000\tx\n1000001\tx\n1000002\tx\n"));
+    if let Some((_, cat, _)) = gnu_cat(&dir, &["-n", "many"]) {
+        assert!(many == cat);
+    }
+}
+
+#[test]
+fn number_nonblank() {
+    let dir = TempDir::new("rat-number-nonblank");
+    // Only a lone newline is blank, not a space or a CR
+    dir.write("mixed", "a\n\n\nb\n \n\r\nc");
+    dir.write("leading", "\n\nd\n");
+    let mixed = "     1\ta\n\n\n     2\tb\n     3\t \n     4\t\r\n     5\tc";
+    let cases: &[(&[&str], &[&str], String)] = &[
+        (&["-b", "mixed"], &["-b", "mixed"], mixed.into()),
+        // -b wins over -n whichever comes first
+        (&["-bn", "mixed"], &["-bn", "mixed"], mixed.into()),
+        (&["-n", "-b", "mixed"], &["-n", "-b", "mixed"], mixed.into()),
+        // The unterminated "c" goes on with the next file's first newline
+        (
+            &["-b", "mixed", "leading"],
+            &["-b", "mixed", "leading"],
+            format!("{mixed}\n\n     6\td\n"),
+        ),
+        (
+            &["-b", "--squeeze-blank", "mixed", "leading"],
+            &["-bs", "mixed", "leading"],
+            "     1\ta\n\n     2\tb\n     3\t \n     4\t\r\n     5\tc\n\n     6\td\n".into(),
+        ),
+        (
+            &["-b", "-E", "leading"],
+            &["-bE", "leading"],
+            "$\n$\n     1\td$\n".into(),
+        ),
+    ];
+    for (args, cat_args, expected) in cases {
+        let output = rat_in(&dir, args);
+        assert_eq!(output, ok(expected), "{args:?}");
+        if let Some(cat) = gnu_cat(&dir, cat_args) {
+            assert_eq!(output, cat, "{args:?}");
+        }
+    }
+}
+
+#[test]
+fn path_telemetry() {
+    let dir = TempDir::new("rat-paths");
+    dir.write("file", "hello\n");
+    let telemetry = |auto: bool| {
+        let out = std::fs::File::create(dir.path().join("out")).unwrap();
+        let mut args = vec!["--path-telemetry", "file", "-", "/dev/null"];
+        if auto {
+            args.insert(0, "--auto-pipe-detect");
+        }
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+            .args(args)
+            .current_dir(dir.path())
+            .stdin(Stdio::piped())
+            .stdout(out)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"pipe\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let stdout = std::fs::read_to_string(dir.path().join("out")).unwrap();
+        assert_eq!(stdout, "hello\npipe\n");
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    // Into a regular file: a file is copied in the kernel, the pipe spliced, and
+    // /dev/null (a character device) has no syscall but read/write
+    assert_eq!(
+        telemetry(false),
+        "rat: file: copy_file_range (regular files on both ends), 6 bytes\n\
+         rat: -: splice (a pipe on either end), 5 bytes\n\
+         rat: /dev/null: read/write (no end a file or pipe), 0 bytes\n\
+         rat: copy_file_range: 6 bytes from 1 file\n\
+         rat: splice: 5 bytes from 1 file\n\
+         rat: read/write: 0 bytes from 1 file\n"
+    );
+    assert_eq!(
+        telemetry(true),
+        "rat: file: copy_file_range (regular files on both ends), 6 bytes\n\
+         rat: -: splice (a pipe on either end), 5 bytes\n\
+         rat: /dev/null: chunks (--auto-pipe-detect, no end a file or pipe), 0 bytes\n\
+         rat: copy_file_range: 6 bytes from 1 file\n\
+         rat: splice: 5 bytes from 1 file\n\
+         rat: chunks: 0 bytes from 1 file\n"
+    );
+
+    // Formatting options always go through rat's lines
+    let (code, _, stderr) = rat_piped(&dir, &["--path-telemetry", "-n", "file", "-"], "pipe\n");
+    assert_eq!(code, 0);
+    assert_eq!(
+        stderr,
+        "rat: file: lines (a formatting option), 6 bytes\n\
+         rat: -: lines (a formatting option), 5 bytes\n\
+         rat: lines: 11 bytes from 2 files\n"
+    );
+}
+
+#[test]
+fn line_numbers_from() {
+    let dir = TempDir::new("rat-numbers-from");
+    let lines: String = (1..=20).map(|n| format!("{n}\n")).collect();
+    dir.write("lines", &lines);
+    // The lines keep their numbers in the file
+    let (code, stdout, stderr) = rat_in(
+        &dir,
+        &["--seek-to-line=11", "--line-numbers-from=11", "lines"],
+    );
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    assert!(stdout.starts_with("    11\t11\n    12\t12\n"), "{stdout}");
+    assert!(stdout.ends_with("    20\t20\n"), "{stdout}");
+
+    assert_eq!(
+        rat_piped(&dir, &["-b", "--line-numbers-from=5"], "a\n\nb\n"),
+        ok("     5\ta\n\n     6\tb\n")
+    );
+    let (code, _, stderr) = rat_in(&dir, &["--line-numbers-from=0", "lines"]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("lines are numbered from 1"), "{stderr}");
+}
+
+#[test]
+fn gutter() {
+    // Each line's offset, advancing by the previous line's length with its newline
+    let output = rat(&["--gutter=byte"], b"a\nbb\n\nccc\n");
+    assert_eq!(
+        output.stdout,
+        b"     0\ta\n     2\tbb\n     5\t\n     6\tccc\n"
+    );
+    let output = rat(&["-b", "--gutter=byte"], b"a\n\nb\n");
+    assert_eq!(output.stdout, b"     0\ta\n\n     3\tb\n");
+    let output = rat(&["--gutter=char"], "é\n日本\nx\n".as_bytes());
+    assert_eq!(
+        output.stdout,
+        "     0\té\n     2\t日本\n     5\tx\n".as_bytes()
+    );
+    let output = rat(&["--prefix-line-count=line"], b"a\nb\n");
+    assert_eq!(output.stdout, b"     1\ta\n     2\tb\n");
+
+    // Counting on across files
+    let dir = TempDir::new("rat-gutter");
+    dir.write("one", "abc\n");
+    dir.write("two", "de\nf\n");
+    assert_eq!(
+        rat_in(&dir, &["--gutter=byte", "one", "two"]),
+        ok("     0\tabc\n     4\tde\n     7\tf\n")
+    );
+}
+
+#[test]
+fn open_errors() {
+    let dir = TempDir::new("rat-open");
+    dir.write("file", "hello\n");
+    std::os::unix::fs::symlink("loop", dir.path().join("loop")).unwrap();
+    // Any error opening a file is reported, the others still written
+    let (code, stdout, stderr) = rat_in(&dir, &["loop", "file", "file/x", "missing"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "hello\n");
+    assert_eq!(
+        stderr,
+        "rat: loop: Too many levels of symbolic links\n\
+         rat: file/x: Not a directory\n\
+         rat: missing: No such file or directory\n"
+    );
+}
+
+#[test]
+fn read_errors() {
+    let dir = TempDir::new("rat-read");
+    dir.write("one", "a\n");
+    dir.write("two", "b\n");
+    std::fs::create_dir(dir.path().join("dir")).unwrap();
+    // Opened fine but not read, as cat words it, the files around it still written
+    for args in [&["one", "dir", "two"][..], &["-n", "one", "dir", "two"]] {
+        let (code, stdout, stderr) = rat_in(&dir, args);
+        assert_eq!((code, stderr.as_str()), (1, "rat: dir: Is a directory\n"));
+        assert_eq!(stdout.lines().count(), 2, "{stdout}");
+        if let Some(cat) = gnu_cat(&dir, args) {
+            assert_eq!(stderr.replace("rat:", "cat:"), cat.2);
+        }
+    }
+}
+
+#[test]
+fn fail_if_empty() {
+    let status = |args: &[&str], input: &[u8]| rat(args, input).status.code().unwrap();
+    assert_eq!(status(&["--fail-if-empty"], b""), 1);
+    assert_eq!(status(&["--fail-if-empty"], b"x\n"), 0);
+    assert_eq!(status(&["--fail-if-empty", "--grep=a"], b"b\n"), 1);
+    assert_eq!(status(&["--fail-if-empty", "--grep=a"], b"a\n"), 0);
+    // What rat writes itself counts too, even a count of nothing
+    let output = rat(&["--fail-if-empty", "--grep=a", "--count-matches"], b"b\n");
+    assert_eq!(
+        (output.status.code(), &output.stdout[..]),
+        (Some(0), &b"0\n"[..])
+    );
+    // A file without a bare LF line writes nothing, one with writes where
+    assert_eq!(status(&["--fail-if-empty", "--check-lf"], b"x\r\n"), 1);
+    let output = rat(&["--fail-if-empty", "--check-lf"], b"x\n");
+    assert_eq!(output.stdout, b"-:1: LF line ending\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn dump_config() {
+    let dir = TempDir::new("rat-dump");
+    let (code, stdout, stderr) = rat_in(
+        &dir,
+        &[
+            "--dump-config",
+            "--progress-to-title",
+            "--gutter=byte",
+            "--squeeze-repeats=a",
+            "--squeeze-repeats=b",
+            "--to-clipboard",
+            "missing",
+        ],
+    );
+    // Nothing read, and the clipboard not started
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    let lines: Vec<&str> = stdout.lines().collect();
+    for line in [
+        // Implied by --progress-to-title and --gutter
+        "no-iocopy=true",
+        "number=true",
+        "number-nonblank=false",
+        "gutter=byte",
+        "squeeze-repeats=a",
+        "squeeze-repeats=b",
+        // Defaults, and an option not given
+        "on-fifo-block=wait",
+        "grep=",
+        "to-clipboard=true",
+        "paths=missing",
+    ] {
+        assert!(lines.contains(&line), "{line} in {stdout}");
+    }
+    assert!(lines.iter().all(|line| line.contains('=')), "{stdout}");
+    // The same options give the same dump
+    let first = rat_in(&dir, &["--dump-config", "--gutter=byte"]);
+    assert_eq!(first, rat_in(&dir, &["--gutter=byte", "--dump-config"]));
+}
+
+// Each rat option against its request, the output path first and the reports after
+
+#[test]
+fn field_aggregates() {
+    let input = "a 1\nb 2.5\nc x\nd -3\n";
+    let output = rat(
+        &[
+            "--field-sum=2",
+            "--field-avg=2",
+            "--field-min=2",
+            "--field-max=2",
+        ],
+        input.as_bytes(),
+    );
+    // The lines go through, the non-numbers are left out of the figures
+    assert_eq!(output.stdout, input.as_bytes());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: field 2 sum: 0.5\n\
+         rat: field 2 avg: 0.16666666666666666\n\
+         rat: field 2 min: -3\n\
+         rat: field 2 max: 2.5\n"
+    );
+    let output = rat(&["-d", ",", "--field-sum=2"], b"a,1\nb,4\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: field 2 sum: 5\n"
+    );
+}
+
+#[test]
+fn long_lines_whole() {
+    // Lines longer than any read buffer are numbered and matched once
+    let dir = TempDir::new("rat-long");
+    let long = "x".repeat(300_000);
+    dir.write("long", format!("{long}\ny{long}\n"));
+    let (code, stdout, _) = rat_in(&dir, &["-n", "long"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, format!("     1\t{long}\n     2\ty{long}\n"));
+    assert_eq!(
+        rat_in(&dir, &["--grep=^y", "long"]),
+        ok(&format!("y{long}\n"))
+    );
+}
+
+#[test]
+fn chunk_size_report() {
+    let dir = TempDir::new("rat-chunks");
+    dir.write("file", "abcdefgh");
+    let (code, stdout, stderr) = rat_in(&dir, &["--chunk-size-report", "file"]);
+    assert_eq!((code, stdout.as_str()), (0, "abcdefgh"));
+    let report = "rat: file: 1 chunks, 8 bytes (smallest 8, largest 8, limit ";
+    assert!(stderr.starts_with(report), "{stderr}");
+}
+
+#[test]
+fn utf8_lossy() {
+    let output = rat(&["--utf8-lossy"], b"a\xffb\xc3\n\xc3\xa9\n");
+    assert_eq!(output.stdout, "a\u{fffd}b\u{fffd}\né\n".as_bytes());
+    // Cut short at the end of the input too
+    let output = rat(&["--utf8-lossy"], b"a\xe6\x97");
+    assert_eq!(output.stdout, "a\u{fffd}".as_bytes());
+}
+
+#[test]
+fn max_matches() {
+    let output = rat(&["--grep=a", "--max-matches=2"], b"a\nb\na\na\n");
+    assert_eq!(output.stdout, b"a\na\n");
+    let output = rat(
+        &["--grep=a", "--max-matches=2", "--count-matches"],
+        b"a\na\na\n",
+    );
+    assert_eq!(output.stdout, b"2\n");
+}
+
+#[test]
+fn invert_exit_code() {
+    let status = |args: &[&str]| rat(args, b"a\n").status.code().unwrap();
+    assert_eq!(status(&["--grep=a", "--invert-exit-code"]), 0);
+    assert_eq!(status(&["--grep=z", "--invert-exit-code"]), 1);
+    assert_eq!(
+        status(&["--grep=a", "--invert-exit-code", "-", "missing"]),
+        2
+    );
+    // Without it, no match is no error
+    assert_eq!(status(&["--grep=z"]), 0);
+}
+
+#[test]
+fn show_control_names() {
+    let output = rat(&["--show-control-names"], b"a\x00\t\x1b\x7fb\n");
+    assert_eq!(output.stdout, b"a<NUL><TAB><ESC><DEL>b\n");
+}
+
+#[test]
+fn output_delimiter_between_files() {
+    let dir = TempDir::new("rat-between");
+    dir.write("one", "a\n");
+    dir.write("empty", "");
+    dir.write("two", "b\n");
+    // Not after the last file, nor around the empty one
+    assert_eq!(
+        rat_in(
+            &dir,
+            &[
+                "--output-delimiter-between-files=--\\n",
+                "one",
+                "empty",
+                "two"
+            ]
+        ),
+        ok("a\n--\nb\n")
+    );
+}
+
+#[test]
+fn stdin_fallback() {
+    let dir = TempDir::new("rat-fallback");
+    dir.write("fallback", "from the file\n");
+    let (master, terminal) = pty(24);
+    let output = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .arg("--stdin-fallback=fallback")
+        .current_dir(dir.path())
+        .stdin(terminal)
+        .output()
+        .unwrap();
+    drop(master);
+    assert_eq!(output.stdout, b"from the file\n");
+    // Piped input is read as usual
+    assert_eq!(
+        rat_piped(&dir, &["--stdin-fallback=fallback"], "piped\n"),
+        ok("piped\n")
+    );
+}
+
+#[test]
+fn write_combine() {
+    // The two writes come out in a single one, the input having been idle less than MS
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .arg("--write-combine=2000")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"a").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    stdin.write_all(b"b").unwrap();
+    drop(stdin);
+    let mut first = [0; 16];
+    let read = std::io::Read::read(child.stdout.as_mut().unwrap(), &mut first).unwrap();
+    assert_eq!(&first[..read], b"ab");
+    assert!(child.wait().unwrap().success());
+}
+
+// cargo test --release --test rat -- --ignored --nocapture write_combine_benchmark
+#[test]
+#[ignore = "benchmark"]
+fn write_combine_benchmark() {
+    use std::io::Read;
+    use std::time::Instant;
+    // Many small writes, as from a program logging line by line
+    let lines = 100_000;
+    for args in [&["--no-iocopy"][..], &["--write-combine"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || {
+            for n in 0..lines {
+                stdin.write_all(format!("line {n}\n").as_bytes()).unwrap();
+            }
+        });
+        let start = Instant::now();
+        let mut stdout = child.stdout.take().unwrap();
+        let (mut reads, mut bytes, mut buffer) = (0, 0, vec![0; 1 << 16]);
+        loop {
+            match stdout.read(&mut buffer).unwrap() {
+                0 => break,
+                read => (reads, bytes) = (reads + 1, bytes + read),
+            }
+        }
+        writer.join().unwrap();
+        assert!(child.wait().unwrap().success());
+        let elapsed = start.elapsed();
+        println!("{args:?}: {bytes} bytes in {reads} reads, {elapsed:?}");
+    }
+}
+
+#[test]
+fn force_formatted() {
+    let dir = TempDir::new("rat-formatted");
+    dir.write("file", "a\nb");
+    let (code, stdout, stderr) = rat_in(&dir, &["--force-formatted", "--path-telemetry", "file"]);
+    assert_eq!((code, stdout.as_str()), (0, "a\nb"));
+    assert!(
+        stderr.starts_with("rat: file: lines (a formatting option), 3 bytes\n"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn checksum_verify() {
+    let dir = TempDir::new("rat-verify");
+    dir.write("one", "a\n");
+    dir.write("two", "b\n");
+    // sha256sum of "a\n" and "b\n"
+    dir.write(
+        "sums",
+        "87428fc522803d31065e7bce3cf03fe475096631e5e07bbd7a0fde60c4cf25c7  one\n\
+         0263829989b6fd954f72baaf2fc64bc2e2f01d692d4de72986ea808f6e99813f  two\n",
+    );
+    // Without paths the listed files are read
+    let (code, stdout, stderr) = rat_in(&dir, &["--checksum-verify=sums"]);
+    assert_eq!((code, stdout.as_str()), (0, "a\nb\n"));
+    assert_eq!(stderr, "rat: one: OK\nrat: two: OK\n");
+    dir.write("two", "c\n");
+    let (code, stdout, stderr) = rat_in(&dir, &["--checksum-verify=sums", "two"]);
+    assert_eq!((code, stdout.as_str()), (1, "c\n"));
+    assert_eq!(stderr, "rat: two: FAILED\n");
+}
+
+#[test]
+fn dedupe_global() {
+    let input = b"a\nB\nb\na\nA\n";
+    assert_eq!(rat(&["--dedupe-global"], input).stdout, b"a\nB\nb\nA\n");
+    let output = rat(&["--dedupe-global", "--ignore-case-dedupe"], input);
+    assert_eq!(output.stdout, b"a\nB\n");
+    // Across files too
+    let dir = TempDir::new("rat-dedupe");
+    dir.write("one", "x\ny\n");
+    dir.write("two", "y\nz\n");
+    assert_eq!(
+        rat_in(&dir, &["--dedupe-global", "one", "two"]),
+        ok("x\ny\nz\n")
+    );
+}
+
+#[test]
+fn on_fifo_block() {
+    let dir = TempDir::new("rat-fifo");
+    dir.write("file", "a\n");
+    nix::unistd::mkfifo(&dir.path().join("fifo"), nix::sys::stat::Mode::S_IRWXU).unwrap();
+    assert_eq!(
+        rat_in(&dir, &["--on-fifo-block=error", "fifo", "file"]),
+        (1, "a\n".into(), "rat: fifo: FIFO has no writer\n".into())
+    );
+    assert_eq!(
+        rat_in(&dir, &["--on-fifo-block=skip", "fifo", "file"]),
+        ok("a\n")
+    );
+}
+
+#[test]
+fn squeeze_repeats() {
+    let args = [
+        "--squeeze-repeats=a",
+        "--squeeze-repeats= ",
+        "--squeeze-repeats=\\t",
+    ];
+    let output = rat(&args, b"aaa  bb\t\t\n\naa");
+    assert_eq!(output.stdout, b"a bb\t\n\na");
+}
+
+#[test]
+fn check_line_endings() {
+    let dir = TempDir::new("rat-endings");
+    dir.write("crlf", "a\r\nb\nc\r\n");
+    dir.write("lf", "a\nb");
+    assert_eq!(
+        rat_in(&dir, &["--check-crlf", "crlf", "lf"]),
+        (
+            1,
+            "crlf:1: CRLF line ending\ncrlf:3: CRLF line ending\n".into(),
+            "".into()
+        )
+    );
+    assert_eq!(
+        rat_in(&dir, &["--check-lf", "crlf"]),
+        (1, "crlf:2: LF line ending\n".into(), "".into())
+    );
+    assert_eq!(rat_in(&dir, &["--check-crlf", "lf"]), ok(""));
+}
+
+#[test]
+fn buffers_across_files() {
+    // Files larger and smaller than the buffers, one after the other through the same ones
+    let dir = TempDir::new("rat-pool");
+    let sizes = [0, 1, 70_000, 3, 200_000, 0, 5];
+    let mut names = Vec::new();
+    let mut all = String::new();
+    for (n, size) in sizes.iter().enumerate() {
+        let contents: String = (0..*size)
+            .map(|i| if i % 61 == 60 { '\n' } else { 'x' })
+            .collect();
+        dir.write(&n.to_string(), &contents);
+        names.push(n.to_string());
+        all.push_str(&contents);
+    }
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    assert_eq!(rat_in(&dir, &names), ok(&all));
+    let no_iocopy = [&["--no-iocopy"], &names[..]].concat();
+    assert_eq!(rat_in(&dir, &no_iocopy), ok(&all));
+    let numbered = [&["-n"], &names[..]].concat();
+    if let Some(cat) = gnu_cat(&dir, &numbered) {
+        assert_eq!(rat_in(&dir, &numbered), cat);
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn stream_json_array() {
+    let output = rat(
+        &["--stream-json-array"],
+        b"{\"a\":1}\n\n{\"b\": [1, 2]}\nbad\n",
+    );
+    assert_eq!(output.stdout, b"[{\"a\":1},{\"b\":[1,2]}]\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: -: line 4: invalid JSON (expected value at line 1 column 1), skipped\n"
+    );
+    assert_eq!(rat(&["--stream-json-array"], b"").stdout, b"[]\n");
+    let output = rat(&["--stream-json-array", "--strict"], b"{}\nbad\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn fsync_interval() {
+    let dir = TempDir::new("rat-fsync");
+    let lines: String = (0..10_000).map(|n| format!("{n}\n")).collect();
+    dir.write("lines", &lines);
+    let out = std::fs::File::create(dir.path().join("out")).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(["--fsync-interval=4096", "lines"])
+        .current_dir(dir.path())
+        .stdout(out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("out")).unwrap(),
+        lines
+    );
+}
+
+#[test]
+fn detect_truncated_utf8() {
+    let output = rat(&["--detect-truncated-utf8"], b"ab\xe6\x97");
+    // Written as is all the same
+    assert_eq!(
+        (output.status.code(), &output.stdout[..]),
+        (Some(0), &b"ab\xe6\x97"[..])
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: -: warning: truncated UTF-8 sequence at byte 2\n"
+    );
+    let output = rat(&["--detect-truncated-utf8", "--strict"], b"ab\xe6\x97");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: -: truncated UTF-8 sequence at byte 2\n"
+    );
+    let output = rat(
+        &["--detect-truncated-utf8", "--strict"],
+        "ab日\n".as_bytes(),
+    );
+    assert_eq!(
+        (output.status.code(), &output.stderr[..]),
+        (Some(0), &b""[..])
+    );
+}
+
+// Run rat in `dir` with its stdout a terminal `rows` high, what the terminal got
+fn rat_on_terminal(dir: &TempDir, args: &[&str], rows: u16) -> String {
+    let (master, terminal) = pty(rows);
+    let status = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(args)
+        .current_dir(dir.path())
+        .stdout(terminal)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    read_pty(master)
+}
+
+#[test]
+fn pager() {
+    let dir = TempDir::new("rat-pager");
+    dir.write("short", "1\n2\n3\n");
+    let tall: String = (1..=10).map(|n| format!("{n}\n")).collect();
+    dir.write("tall", &tall);
+    let pager = "--pager=cat > paged";
+    // What fits the terminal goes to it, the rest through the pager only
+    assert_eq!(rat_on_terminal(&dir, &[pager, "short"], 5), "1\n2\n3\n");
+    assert!(!dir.path().join("paged").exists());
+    assert_eq!(rat_on_terminal(&dir, &[pager, "tall"], 5), "");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("paged")).unwrap(),
+        tall
+    );
+    // Not a terminal, no pager
+    assert_eq!(rat_in(&dir, &["--pager=false", "tall"]), ok(&tall));
+}
+
+#[test]
+fn abort_after_bytes() {
+    let output = rat(&["--abort-after-bytes=5"], b"1\n2\n3\n4\n");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(output.stdout, b"1\n2\n3");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: -: output exceeded 5 bytes, aborting\n"
+    );
+    assert_eq!(
+        rat(&["--abort-after-bytes=8"], b"1\n2\n3\n4\n")
+            .status
+            .code(),
+        Some(0)
+    );
+}
+
+// Run rat in `dir` with a stand-in xclip first in PATH, the clipboard kept in the file
+// `clipboard`. It says what it was run with on stderr
+fn rat_with_xclip(dir: &TempDir, args: &[&str], input: &str) -> (i32, String, String) {
+    use std::os::unix::fs::PermissionsExt;
+    let bin = dir.path().join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let xclip = bin.join("xclip");
+    std::fs::write(
+        &xclip,
+        "#!/bin/sh\n\
+         echo \"xclip $*\" >&2\n\
+         case \"$*\" in\n\
+         *-in) cat > clipboard ;;\n\
+         *-out) cat clipboard ;;\n\
+         esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&xclip, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(args)
+        .current_dir(dir.path())
+        .env("PATH", path)
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn clipboard() {
+    let dir = TempDir::new("rat-clipboard");
+    let (code, stdout, stderr) = rat_with_xclip(&dir, &["--to-clipboard", "-n"], "a\n");
+    assert_eq!((code, stdout.as_str()), (0, ""));
+    assert_eq!(stderr, "xclip -selection clipboard -in\n");
+    let clipboard = std::fs::read_to_string(dir.path().join("clipboard")).unwrap();
+    assert_eq!(clipboard, "     1\ta\n");
+    // Read back in place of stdin
+    let (code, stdout, _) = rat_with_xclip(&dir, &["--from-clipboard", "-E"], "ignored\n");
+    assert_eq!((code, stdout.as_str()), (0, "     1\ta$\n"));
+}
+
+#[test]
+fn capture_stderr_of_children() {
+    let dir = TempDir::new("rat-capture");
+    dir.write("clipboard", "x\n");
+    let args = ["--from-clipboard", "--capture-stderr-of-children"];
+    assert_eq!(
+        rat_with_xclip(&dir, &args, ""),
+        (
+            0,
+            "x\n".into(),
+            "rat: xclip: xclip -selection clipboard -out\n".into()
+        )
+    );
+}
+
+#[test]
+fn seek_to_line() {
+    let dir = TempDir::new("rat-seek");
+    let lines: String = (1..=10_000).map(|n| format!("{n}\n")).collect();
+    dir.write("lines", &lines);
+    assert_eq!(
+        rat_in(&dir, &["--seek-to-line=9999", "lines"]),
+        ok("9999\n10000\n")
+    );
+    assert_eq!(rat_in(&dir, &["--seek-to-line=20000", "lines"]), ok(""));
+    // Through an index, the same lines
+    assert_eq!(rat_in(&dir, &["--build-line-index=index", "lines"]), ok(""));
+    let indexed = ["--seek-to-line=5000", "--use-line-index=index", "lines"];
+    let (code, stdout, stderr) = rat_in(&dir, &indexed);
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    assert!(stdout.starts_with("5000\n5001\n"), "{stdout}");
+    // Unless the file changed since
+    dir.write("lines", "changed\n".repeat(5000) + &lines);
+    let (code, stdout, stderr) = rat_in(&dir, &indexed);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stderr,
+        "rat: lines: warning: line index is out of date, reading through\n"
+    );
+    assert!(stdout.starts_with("changed\n1\n2\n"), "{stdout}");
+    let strict = [&indexed[..], &["--strict"]].concat();
+    assert_eq!(
+        rat_in(&dir, &strict),
+        (
+            1,
+            "".into(),
+            "rat: lines: line index is out of date\n".into()
+        )
+    );
+}
+
+#[test]
+fn output_fd_fallback() {
+    let dir = TempDir::new("rat-fallback-output");
+    dir.write("one", "a\n");
+    dir.write("two", "b\n");
+    let full = || {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/full")
+            .unwrap()
+    };
+    let output = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(["--output-fd-fallback=saved", "one", "two"])
+        .current_dir(dir.path())
+        .stdout(full())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: standard output: No space left on device, writing to saved instead\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("saved")).unwrap(),
+        "a\nb\n"
+    );
+    // Lost otherwise
+    let output = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .arg("one")
+        .current_dir(dir.path())
+        .stdout(full())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn lock_input() {
+    use nix::fcntl::{flock, FlockArg};
+    use std::os::fd::AsRawFd;
+    let dir = TempDir::new("rat-lock");
+    dir.write("file", "a\n");
+    let held = std::fs::File::open(dir.path().join("file")).unwrap();
+    flock(held.as_raw_fd(), FlockArg::LockSharedNonblock).unwrap();
+    // Shared locks go together, an exclusive one waits or fails
+    let shared = ["--lock-input=shared", "--lock-nonblocking", "file"];
+    assert_eq!(rat_in(&dir, &shared), ok("a\n"));
+    let exclusive = ["--lock-input=exclusive", "--lock-nonblocking", "file"];
+    assert_eq!(
+        rat_in(&dir, &exclusive),
+        (
+            1,
+            "".into(),
+            "rat: file: locked by another process\n".into()
+        )
+    );
+    drop(held);
+    assert_eq!(rat_in(&dir, &exclusive), ok("a\n"));
+}
+
+#[test]
+fn progress_to_title() {
+    let dir = TempDir::new("rat-title");
+    dir.write("file", "hello\n");
+    let (master, terminal) = pty(24);
+    let output = Command::new(env!("CARGO_BIN_EXE_rat"))
+        .args(["--progress-to-title", "file"])
+        .current_dir(dir.path())
+        .stderr(terminal)
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, b"hello\n");
+    // The title pushed, set and popped back
+    let title = read_pty(master);
+    assert!(title.starts_with("\x1b[22;0t\x1b]0;rat: "), "{title:?}");
+    assert!(title.ends_with("\x07\x1b[23;0t"), "{title:?}");
+    // Not when stderr isn't a terminal
+    assert_eq!(
+        rat_in(&dir, &["--progress-to-title", "file"]),
+        ok("hello\n")
+    );
+}
+
+#[test]
+fn show_invisible() {
+    let output = rat(&["--show-invisible"], b"a \t\nb c\t \n");
+    assert_eq!(output.stdout, "a·→\nb c→·\n".as_bytes());
+    let output = rat(
+        &["--show-invisible=all", "--invisible-marks=_>"],
+        b"a b\t\n",
+    );
+    assert_eq!(output.stdout, b"a_b>\n");
+}
+
+#[test]
+fn expand_env() {
+    let expand = |args: &[&str], input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rat"))
+            .args(args)
+            .env("FOO", "bar")
+            .env_remove("NOPE")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (
+            output.status.code().unwrap(),
+            stdout,
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    assert_eq!(
+        expand(&["--expand-env"], "x $FOO ${FOO}y $$ $NOPE.\n"),
+        ok("x bar bary $ .\n")
+    );
+    assert_eq!(
+        expand(&["--expand-env", "--env-default=keep"], "$NOPE ${NOPE}\n"),
+        ok("$NOPE ${NOPE}\n")
+    );
+    let (code, _, stderr) = expand(&["--expand-env", "--env-default=error"], "a $NOPE\n");
+    assert_eq!(
+        (code, stderr.as_str()),
+        (1, "rat: -: unset variable: NOPE\n")
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_field() {
+    let dir = TempDir::new("rat-json-field");
+    dir.write(
+        "log",
+        "{\"msg\":\"hi\",\"h\":{\"s\":200},\"i\":[{\"id\":7}]}\n{\"x\":1}\n",
+    );
+    assert_eq!(rat_in(&dir, &["--json-field=.msg", "log"]), ok("hi\n"));
+    assert_eq!(
+        rat_in(&dir, &["--json-field=.h", "log"]),
+        ok("{\"s\":200}\n")
+    );
+    assert_eq!(
+        rat_in(
+            &dir,
+            &["--json-field=.i[0].id", "--json-missing=empty", "log"]
+        ),
+        ok("7\n\n")
+    );
+    assert_eq!(
+        rat_in(&dir, &["--json-field=.h.s", "--json-missing=error", "log"]),
+        (
+            1,
+            "200\n".into(),
+            "rat: log: line 2: no value at the --json-field path\n".into()
+        )
+    );
+}
+
+#[test]
+fn show_tabs_and_nonprinting() {
+    let dir = TempDir::new("rat-nonprinting");
+    let all: Vec<u8> = (0..=255).chain([b'\n']).collect();
+    dir.write("all", &all);
+    // Every byte as cat -v shows it, all of it ASCII then
+    for args in [&["-v", "all"][..], &["-vT", "all"], &["-vET", "all"]] {
+        let Some(cat) = gnu_cat(&dir, args) else {
+            continue;
+        };
+        assert_eq!(rat_in(&dir, args), cat, "{args:?}");
+    }
+    assert_eq!(rat(&["-T"], b"a\tb\n").stdout, b"a^Ib\n");
+    assert_eq!(
+        rat(&["-v"], b"a\x01\x7f\x80\xff\tb\n").stdout,
+        b"a^A^?M-^@M-^?\tb\n"
+    );
+}
+
+#[test]
+fn line_prefix_from_file() {
+    let dir = TempDir::new("rat-prefixes");
+    dir.write("prefixes", "P1 \nP2 \n");
+    assert_eq!(
+        rat_piped(&dir, &["--line-prefix-from-file=prefixes"], "1\n2\n3\n"),
+        ok("P1 1\nP2 2\nP1 3\n")
+    );
+    assert_eq!(
+        rat_piped(
+            &dir,
+            &["--line-prefix-from-file=prefixes", "--prefix-one-to-one"],
+            "1\n2\n3\n"
+        ),
+        ok("P1 1\nP2 2\n3\n")
+    );
+}
+
+#[test]
+fn strip_shebang() {
+    let dir = TempDir::new("rat-shebang");
+    dir.write("one", "#!/bin/sh\necho a\n");
+    dir.write("two", "#!/bin/sh\necho b\n");
+    assert_eq!(
+        rat_in(&dir, &["--strip-shebang", "one", "two"]),
+        ok("#!/bin/sh\necho a\necho b\n")
+    );
+    assert_eq!(
+        rat_in(&dir, &["--strip-shebang=all", "one", "two"]),
+        ok("echo a\necho b\n")
+    );
+}
+
+#[test]
+fn max_line_width() {
+    let output = rat(&["--max-line-width"], "a\tb\n日本\n".as_bytes());
+    assert_eq!(output.stdout, "a\tb\n日本\n".as_bytes());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rat: -: max line width 9\n"
+    );
+}
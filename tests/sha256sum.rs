@@ -0,0 +1,157 @@
+mod common;
+
+use common::{run, TempDir};
+
+// Every sha* utility with its binary and the digest of "a\n"
+const SHAS: [(&str, &str, &str); 5] = [
+    (
+        "sha1sum",
+        env!("CARGO_BIN_EXE_sha1sum"),
+        "3f786850e387550fdab836ed7e6dc881de23001b",
+    ),
+    (
+        "sha224sum",
+        env!("CARGO_BIN_EXE_sha224sum"),
+        "7c297c1793fdad2ac52a68bdd6b8fde3eb59b99c3f8c44710fde5fd7",
+    ),
+    (
+        "sha256sum",
+        env!("CARGO_BIN_EXE_sha256sum"),
+        "87428fc522803d31065e7bce3cf03fe475096631e5e07bbd7a0fde60c4cf25c7",
+    ),
+    (
+        "sha384sum",
+        env!("CARGO_BIN_EXE_sha384sum"),
+        "927b2f0395e4ce326f4ab759c75aa5fc399f7d6c3037b2deb4095279ca7ced3c\
+         a70f68f3574148680fc52d6c15bc8914",
+    ),
+    (
+        "sha512sum",
+        env!("CARGO_BIN_EXE_sha512sum"),
+        "162b0b32f02482d5aca0a7c93dd03ceac3acd7e410a5f18f3fb990fc958ae0df\
+         6f32233b91831eaf99ca581a8c4ddf9c8ba315ac482db6d4ea01cc7884a635be",
+    ),
+];
+
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("good", "a\n");
+    dir.write("bad", "b\n");
+    dir.write("back\\slash", "x");
+    dir.write("new\nline", "y");
+    dir
+}
+
+// Whether `prog` on PATH is GNU's, to compare with
+fn gnu(prog: &str, dir: &TempDir) -> bool {
+    let (_, version, _) = run(prog, &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+#[test]
+fn digests() {
+    let dir = setup("sha256sum-digests");
+    for (prog, bin, digest) in SHAS {
+        assert_eq!(
+            run(bin, &["good"], dir.path()),
+            (0, format!("{digest}  good\n"), "".into()),
+            "{prog}"
+        );
+        let tag = prog.trim_end_matches("sum").to_uppercase();
+        assert_eq!(
+            run(bin, &["--tag", "good"], dir.path()),
+            (0, format!("{tag} (good) = {digest}\n"), "".into()),
+            "{prog}"
+        );
+    }
+}
+
+#[test]
+fn check() {
+    let dir = setup("sha256sum-check");
+    for (prog, bin, _) in SHAS {
+        let names = ["good", "bad", "back\\slash", "new\nline"];
+        for tag in [&[][..], &["--tag"]] {
+            let (status, list, _) = run(bin, &[tag, &names].concat(), dir.path());
+            assert_eq!(status, 0, "{prog}");
+            dir.write("list", list);
+            assert_eq!(
+                run(bin, &["-c", "list"], dir.path()),
+                (
+                    0,
+                    "good: OK\nbad: OK\nback\\slash: OK\n\\new\\nline: OK\n".into(),
+                    "".into()
+                ),
+                "{prog} {tag:?}"
+            );
+        }
+        // Another algorithm's tagged lines are not for this one
+        let other = if prog == "sha1sum" { SHAS[1] } else { SHAS[0] };
+        let (_, list, _) = run(other.1, &["--tag", "good"], dir.path());
+        dir.write("other", list);
+        assert_eq!(
+            run(bin, &["-c", "other"], dir.path()),
+            (
+                1,
+                "".into(),
+                format!("{prog}: other: no properly formatted checksum lines found\n")
+            ),
+            "{prog}"
+        );
+    }
+}
+
+#[test]
+fn check_gnu_manifests() {
+    let dir = setup("sha256sum-gnu");
+    for (prog, bin, _) in SHAS {
+        if !gnu(prog, &dir) {
+            continue;
+        }
+        for tag in [&[][..], &["--tag"]] {
+            dir.write("bad", "b\n");
+            let args = [tag, &["good", "bad", "back\\slash", "new\nline"]].concat();
+            let (status, list, _) = run(prog, &args, dir.path());
+            assert_eq!(status, 0, "{prog}");
+            // Ours writes GNU's manifests, and checks them like GNU
+            assert_eq!(
+                run(bin, &args, dir.path()),
+                (0, list.clone(), "".into()),
+                "{prog} {tag:?}"
+            );
+            dir.write("list", list);
+            dir.write("bad", "changed\n");
+            for check in [
+                &["-c", "list"][..],
+                &["-c", "--quiet", "list"],
+                &["-c", "--status", "list"],
+            ] {
+                assert_eq!(
+                    run(bin, check, dir.path()),
+                    run(prog, check, dir.path()),
+                    "{prog} {tag:?} {check:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = setup("sha256sum-usage");
+    for (prog, bin, _) in SHAS {
+        for (args, message) in [
+            (&["-x"][..], "invalid option -- 'x'"),
+            (&["--foo"], "unrecognized option '--foo'"),
+            // Only b2sum has a length
+            (&["-l", "8"], "invalid option -- 'l'"),
+        ] {
+            let stderr = format!("{prog}: {message}\nTry '{prog} --help' for more information.\n");
+            assert_eq!(
+                run(bin, args, dir.path()),
+                (1, "".into(), stderr),
+                "{prog} {args:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+
+fn shred(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_shred"), args, dir.path())
+}
+
+// Whether shred on PATH is GNU's, to compare with
+fn gnu_shred(dir: &TempDir) -> bool {
+    let (_, version, _) = run("shred", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// A 12 byte file, and a fixed random source so the passes are always the same
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("abc", "hello world\n");
+    let random: Vec<u8> = (0..65536).map(|i| (i * 7 + 3) as u8).collect();
+    dir.write("random", random);
+    dir
+}
+
+fn passes(name: &str, passes: &[&str]) -> String {
+    let n = passes.len();
+    let mut lines = String::new();
+    for (k, pass) in passes.iter().enumerate() {
+        lines += &format!("shred: {name}: pass {}/{n} ({pass})...\n", k + 1);
+    }
+    lines
+}
+
+#[test]
+fn pass_sequence() {
+    let dir = setup("shred-passes");
+    let shred_v = |args: &[&str]| {
+        let args = [&["-v", "--random-source=random"][..], args, &["abc"]].concat();
+        shred(&dir, &args)
+    };
+    assert_eq!(shred_v(&[]), (0, "".into(), passes("abc", &["random"; 3])));
+    // Patterns from GNU's table among the random passes, then the zeros of -z
+    let expected = ["random", "000000", "random", "ffffff", "random", "000000"];
+    assert_eq!(
+        shred_v(&["-n", "5", "-z"]),
+        (0, "".into(), passes("abc", &expected))
+    );
+    assert_eq!(
+        shred_v(&["-n", "0", "-z"]),
+        (0, "".into(), passes("abc", &["000000"]))
+    );
+    assert_eq!(shred_v(&["-n", "0"]), (0, "".into(), "".into()));
+    // The same source, the same data
+    let once = |name: &str| {
+        dir.write(name, "hello world\n");
+        assert_eq!(
+            shred(&dir, &["-n", "1", "--random-source=random", name]).0,
+            0
+        );
+        fs::read(dir.path().join(name)).unwrap()
+    };
+    let shredded = once("a");
+    assert_eq!(shredded.len(), 4096);
+    assert_ne!(&shredded[..12], b"hello world\n");
+    assert_eq!(once("b"), shredded);
+}
+
+#[test]
+fn sizes() {
+    let dir = setup("shred-sizes");
+    // Rounded up to a whole block unless -x, or as much as -s says
+    for (args, size) in [
+        (&[][..], 4096),
+        (&["-x"], 12),
+        (&["--exact"], 12),
+        (&["-s", "100"], 100),
+        (&["-s", "5000"], 5000),
+        (&["-s", "1K", "-x"], 1024),
+    ] {
+        dir.write("abc", "hello world\n");
+        let args = [&["-n", "1", "-z"][..], args, &["abc"]].concat();
+        assert_eq!(shred(&dir, &args), (0, "".into(), "".into()), "{args:?}");
+        // Zeros all through after -z
+        assert_eq!(
+            fs::read(dir.path().join("abc")).unwrap(),
+            vec![0; size],
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn removing() {
+    let dir = setup("shred-removing");
+    let renames = "shred: abc: removing\n\
+                   shred: abc: renamed to 000\n\
+                   shred: 000: renamed to 00\n\
+                   shred: 00: renamed to 0\n\
+                   shred: abc: removed\n";
+    for remove in ["-u", "--remove", "--remove=wipesync", "--remove=wipe"] {
+        dir.write("abc", "hello world\n");
+        assert_eq!(
+            shred(&dir, &["-v", "-n", "0", "-z", remove, "abc"]),
+            (0, "".into(), passes("abc", &["000000"]) + renames),
+            "{remove}"
+        );
+        assert!(!dir.path().join("abc").exists());
+    }
+    // No renaming with unlink
+    dir.write("abc", "hello world\n");
+    assert_eq!(
+        shred(&dir, &["-v", "-n", "0", "--remove=unlink", "abc"]),
+        (
+            0,
+            "".into(),
+            "shred: abc: removing\nshred: abc: removed\n".into()
+        )
+    );
+    assert!(!dir.path().join("abc").exists());
+
+    // Names already there are skipped over, the next one tried
+    dir.write("abcd", "x");
+    dir.write("000", "");
+    dir.write("0000", "");
+    assert_eq!(
+        shred(&dir, &["-v", "-n", "0", "-u", "abcd"]),
+        (
+            0,
+            "".into(),
+            "shred: abcd: removing\n\
+             shred: abcd: renamed to 0001\n\
+             shred: 0001: renamed to 001\n\
+             shred: 001: renamed to 00\n\
+             shred: 00: renamed to 0\n\
+             shred: abcd: removed\n"
+                .into()
+        )
+    );
+    let mut left: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    left.sort();
+    assert_eq!(left, ["000", "0000", "random"]);
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = setup("shred-gnu");
+    if !gnu_shred(&dir) {
+        return;
+    }
+    for args in [
+        &["-v"][..],
+        &["-v", "-n", "5", "-z"],
+        &["-v", "-n", "30"],
+        &["-n", "1", "-x"],
+        &["-n", "1", "-s", "5000"],
+        &["-v", "-n", "1", "-u"],
+        &["-v", "-n", "1", "--remove=wipe"],
+        &["-n", "x"],
+    ] {
+        let args = [&["--random-source=../random"][..], args, &["abc"]].concat();
+        // Each in a directory of its own, the names being renamed the same way
+        let mut results = Vec::new();
+        for (prog, name) in [(env!("CARGO_BIN_EXE_shred"), "ours"), ("shred", "gnu")] {
+            let work = dir.path().join(name);
+            fs::create_dir_all(&work).unwrap();
+            fs::write(work.join("abc"), "hello world\n").unwrap();
+            let (status, stdout, stderr) = run(prog, &args, &work);
+            results.push((status, stdout, stderr, fs::read(work.join("abc")).ok()));
+        }
+        assert_eq!(results[0], results[1], "{args:?}");
+    }
+}
+
+#[test]
+fn errors() {
+    let dir = setup("shred-errors");
+    fs::write(dir.path().join("short"), [0; 3000]).unwrap();
+    for (args, message) in [
+        (
+            &["-n", "1", "nofile"][..],
+            "nofile: failed to open for writing: No such file or directory",
+        ),
+        (
+            &["-n", "1", "-u", "."],
+            ".: failed to open for writing: Is a directory",
+        ),
+        (&["-n", "x", "abc"], "invalid number of passes: 'x'"),
+        (&["-n", "-1", "abc"], "invalid number of passes: '-1'"),
+        (
+            &["-n", "99999999999999999999", "abc"],
+            "invalid number of passes: '99999999999999999999': \
+             Value too large for defined data type",
+        ),
+        (&["-s", "x", "abc"], "invalid file size: 'x'"),
+        (
+            &["--random-source=nofile", "abc"],
+            "nofile: No such file or directory",
+        ),
+        // Not enough random bytes for the passes
+        (&["--random-source=short", "abc"], "'short': end of file"),
+    ] {
+        assert_eq!(
+            shred(&dir, args),
+            (1, "".into(), format!("shred: {message}\n")),
+            "{args:?}"
+        );
+    }
+    // Where a write failed
+    assert_eq!(
+        shred(&dir, &["-v", "-n", "1", "-s", "10K", "/dev/full"]),
+        (
+            1,
+            "".into(),
+            "shred: /dev/full: pass 1/1 (random)...\n\
+             shred: /dev/full: error writing at offset 0: No space left on device\n"
+                .into()
+        )
+    );
+    // Standard output, not when it's appended to
+    let (status, _, stderr) = run(
+        "sh",
+        &[
+            "-c",
+            "exec \"$0\" -n 1 - >> abc",
+            env!("CARGO_BIN_EXE_shred"),
+        ],
+        dir.path(),
+    );
+    assert_eq!(
+        (status, stderr.as_str()),
+        (1, "shred: -: cannot shred append-only file descriptor\n")
+    );
+    let (status, _, stderr) = run(
+        "sh",
+        &[
+            "-c",
+            "exec \"$0\" -n 1 -s 10 -x - 1<> abc",
+            env!("CARGO_BIN_EXE_shred"),
+        ],
+        dir.path(),
+    );
+    assert_eq!((status, stderr.as_str()), (0, ""));
+    assert_ne!(
+        &fs::read(dir.path().join("abc")).unwrap()[..10],
+        b"hello worl"
+    );
+}
+
+#[test]
+fn usage_errors() {
+    let dir = setup("shred-usage");
+    let try_help = "Try 'shred --help' for more information.\n";
+    for (args, message) in [
+        (&[][..], "missing file operand".to_string()),
+        (
+            &["--remove=foo", "abc"],
+            "invalid argument 'foo' for '--remove'\n\
+             Valid arguments are:\n  - 'unlink'\n  - 'wipe'\n  - 'wipesync'"
+                .to_string(),
+        ),
+        (&["-y"], "invalid option -- 'y'".to_string()),
+        (&["--foo"], "unrecognized option '--foo'".to_string()),
+        (&["-n"], "option requires an argument -- 'n'".to_string()),
+    ] {
+        assert_eq!(
+            shred(&dir, args),
+            (1, "".into(), format!("shred: {message}\n{try_help}")),
+            "{args:?}"
+        );
+    }
+    assert_eq!(
+        fs::read_to_string(dir.path().join("abc")).unwrap(),
+        "hello world\n"
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{run, TempDir};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// A fixed random source, `short` having too few bytes for most draws
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    let bytes: Vec<u8> = (0..4096u32).map(|i| (i * 167 + 13) as u8).collect();
+    dir.write("source", &bytes);
+    dir.write("short", &bytes[..3]);
+    let lines: String = (1..=1000).map(|i| format!("{i}\n")).collect();
+    dir.write("lines", lines);
+    dir
+}
+
+fn shuf(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_shuf"), args, dir.path())
+}
+
+// Run shuf in `dir` with `input` piped to it
+fn shuf_piped(dir: &TempDir, args: &[&str], input: &[u8]) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_shuf"))
+        .args(args)
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // shuf may fail before reading it all
+    let _ = child.stdin.take().unwrap().write_all(input);
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn ok(lines: &[&str]) -> (i32, String, String) {
+    (
+        0,
+        lines.iter().map(|line| format!("{line}\n")).collect(),
+        "".into(),
+    )
+}
+
+// Outputs are those of GNU shuf given the same source
+#[test]
+fn fixed_source_is_deterministic() {
+    let dir = setup("shuf-deterministic");
+    assert_eq!(
+        shuf(&dir, &["--random-source=source", "-i", "1-10"]),
+        ok(&["4", "3", "7", "5", "8", "2", "9", "6", "10", "1"])
+    );
+    assert_eq!(
+        shuf(
+            &dir,
+            &["--random-source=source", "-e", "a", "b", "c", "d", "e"]
+        ),
+        ok(&["d", "a", "c", "b", "e"])
+    );
+    assert_eq!(
+        shuf(&dir, &["--random-source=source", "-n", "5", "lines"]),
+        ok(&["509", "861", "5", "173", "812"])
+    );
+    assert_eq!(
+        shuf(
+            &dir,
+            &[
+                "--random-source=source",
+                "-r",
+                "-n",
+                "6",
+                "-e",
+                "x",
+                "y",
+                "z"
+            ]
+        ),
+        ok(&["y", "y", "y", "x", "x", "x"])
+    );
+}
+
+#[test]
+fn reservoir_sampling() {
+    let dir = setup("shuf-reservoir");
+    let lines: String = (1..=1000).map(|i| format!("{i}\n")).collect();
+    assert_eq!(
+        shuf_piped(
+            &dir,
+            &["--random-source=source", "-n", "5"],
+            lines.as_bytes()
+        ),
+        ok(&["959", "905", "6", "317", "522"])
+    );
+    assert_eq!(
+        shuf_piped(
+            &dir,
+            &["--random-source=short", "-n", "5"],
+            lines.as_bytes()
+        ),
+        (1, "".into(), "shuf: 'short': end of file\n".into())
+    );
+}
+
+#[test]
+fn sparse_range() {
+    let dir = setup("shuf-sparse");
+    let sample = [
+        "229923587",
+        "840655778",
+        "763066703",
+        "165775411",
+        "598525660",
+        "799192243",
+        "376309106",
+        "555538371",
+        "369844436",
+        "316836154",
+    ];
+    assert_eq!(
+        shuf(&dir, &["--random-source=source", "-i", "1-1e9", "-n", "10"]),
+        ok(&sample)
+    );
+    assert_eq!(
+        shuf(
+            &dir,
+            &["--random-source=source", "-i", "1-1000000000", "-n", "10"]
+        ),
+        ok(&sample)
+    );
+}
+
+#[test]
+fn repeat_none() {
+    let dir = setup("shuf-repeat-none");
+    // Nothing is read, so nothing to repeat is fine
+    assert_eq!(shuf_piped(&dir, &["-r", "-n", "0"], b""), ok(&[]));
+    assert_eq!(shuf(&dir, &["-r", "-n", "0", "-e"]), ok(&[]));
+    assert_eq!(
+        shuf(
+            &dir,
+            &["--random-source=short", "-r", "-n", "0", "-i", "1-1e9"]
+        ),
+        ok(&[])
+    );
+    assert_eq!(
+        shuf(&dir, &["-r", "-n", "1", "-e"]),
+        (1, "".into(), "shuf: no lines to repeat\n".into())
+    );
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{run, TempDir};
+use std::process::Command;
+
+// Lines of a made up table, "word,number,word" with repeats, about 300 KiB
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    let mut state = 12345u32;
+    let mut next = || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        state >> 8
+    };
+    let mut input = String::new();
+    for _ in 0..12000 {
+        let (a, b, c) = (next(), next(), next());
+        let word = |n: u32| format!("{:x}", n % 4096);
+        input.push_str(&format!("{},{},{}\n", word(a), b % 2000, word(c)));
+    }
+    dir.write("input", input);
+    std::fs::create_dir(dir.path().join("tmp")).unwrap();
+    dir
+}
+
+fn sort(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_sort"), args, dir.path())
+}
+
+// GNU sort's output in the C locale, None when it isn't around
+fn gnu_sort(dir: &TempDir, args: &[&str]) -> Option<String> {
+    let version = Command::new("sort").arg("--version").output().ok()?;
+    if !String::from_utf8_lossy(&version.stdout).contains("GNU coreutils") {
+        return None;
+    }
+    let output = Command::new("sort")
+        .args(args)
+        .current_dir(dir.path())
+        .env("LC_ALL", "C")
+        .output()
+        .unwrap();
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn external_sort() {
+    let dir = setup("sort-external");
+    let keys: &[&[&str]] = &[
+        &[],
+        &["-r"],
+        &["-u"],
+        &["-t", ",", "-k2,2n", "-k1,1"],
+        &["-t", ",", "-k3,3", "-s"],
+        &["-t", ",", "-k2,2nr", "-u"],
+    ];
+    for keys in keys {
+        let args = [*keys, &["input"]].concat();
+        let (status, in_memory, _) = sort(&dir, &args);
+        assert_eq!(status, 0);
+        // A buffer a fraction of the input spills dozens of runs, merged 4 at a time
+        let spilling = [&["-S", "8K", "-T", "tmp", "--batch-size=4"], &args[..]].concat();
+        let spilled = sort(&dir, &spilling);
+        assert_eq!(spilled, (0, in_memory.clone(), "".into()), "{keys:?}");
+        if let Some(expected) = gnu_sort(&dir, &args) {
+            assert_eq!(in_memory, expected, "{keys:?}");
+        }
+        // Every run is removed once merged
+        let left = std::fs::read_dir(dir.path().join("tmp")).unwrap().count();
+        assert_eq!(left, 0);
+    }
+}
+
+#[test]
+fn temporary_directory_errors() {
+    let dir = setup("sort-temporary");
+    assert_eq!(
+        sort(&dir, &["-S", "8K", "-T", "missing", "input"]),
+        (
+            2,
+            "".into(),
+            "sort: cannot create temporary file in 'missing': No such file or directory\n".into()
+        )
+    );
+}
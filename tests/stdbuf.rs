@@ -0,0 +1,263 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const STDBUF: &str = env!("CARGO_BIN_EXE_stdbuf");
+
+fn stdbuf(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(STDBUF, args, dir.path())
+}
+
+// Whether stdbuf on PATH is GNU's, to compare with
+fn gnu_stdbuf(dir: &TempDir) -> bool {
+    let (_, version, _) = run("stdbuf", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// The libstdbuf.so built next to stdbuf, None when only the package was built
+fn library() -> Option<PathBuf> {
+    let library = Path::new(STDBUF).with_file_name("libstdbuf.so");
+    library.exists().then_some(library)
+}
+
+// Whether the first line `grep` run through `command` matches comes out while its
+// input is still open, ie. isn't held back in a buffer
+fn line_comes_through(command: &[&str]) -> bool {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .arg("a")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"a1\nb\n").unwrap();
+    stdin.flush().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let reader = std::thread::spawn(move || {
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line).unwrap();
+        let _ = sender.send(line);
+    });
+    let line = receiver.recv_timeout(Duration::from_secs(1));
+    drop(stdin);
+    child.wait().unwrap();
+    reader.join().unwrap();
+    match line {
+        Ok(line) => {
+            assert_eq!(line, "a1\n");
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn pipeline() {
+    if library().is_none() {
+        return;
+    }
+    // grep fully buffers a pipe by itself
+    assert!(!line_comes_through(&["grep"]));
+    for mode in ["-oL", "-o0", "--output=L"] {
+        assert!(line_comes_through(&[STDBUF, mode, "grep"]), "{mode}");
+    }
+    // A buffer bigger than the line holds it back again
+    assert!(!line_comes_through(&[STDBUF, "-o4K", "grep"]));
+}
+
+#[test]
+fn environment() {
+    let dir = TempDir::new("stdbuf-environment");
+    let Some(library) = library() else {
+        return;
+    };
+    let library = library.to_str().unwrap();
+    // What the command gets to tell its library how to buffer
+    let variables = |args: &[&str]| {
+        let (status, env, stderr) = stdbuf(&dir, &[args, &["env"]].concat());
+        assert_eq!((status, stderr.as_str()), (0, ""), "{args:?}");
+        let mut set: Vec<String> = env
+            .lines()
+            .filter(|line| line.starts_with("_STDBUF_") || line.starts_with("LD_PRELOAD="))
+            .map(String::from)
+            .collect();
+        set.sort();
+        set
+    };
+    let preload = format!("LD_PRELOAD={library}");
+    for (args, mode) in [
+        (&["-oL"][..], "_STDBUF_O=L"),
+        (&["-o", " L"], "_STDBUF_O=L"),
+        (&["-o0"], "_STDBUF_O=0"),
+        (&["-e", "1K"], "_STDBUF_E=1024"),
+        (&["-e", "1KiB"], "_STDBUF_E=1024"),
+        (&["--error=2KB"], "_STDBUF_E=2000"),
+        (&["-i", "1M"], "_STDBUF_I=1048576"),
+        (&["-i", "+4096"], "_STDBUF_I=4096"),
+        (&["-i", "K"], "_STDBUF_I=1024"),
+    ] {
+        assert_eq!(variables(args), [preload.clone(), mode.into()], "{args:?}");
+    }
+    assert_eq!(
+        variables(&["-i0", "-oL", "-e", "0"]),
+        [
+            preload,
+            "_STDBUF_E=0".into(),
+            "_STDBUF_I=0".into(),
+            "_STDBUF_O=L".into()
+        ]
+    );
+    // After what's already preloaded
+    let (_, env, _) = run(
+        "env",
+        &["LD_PRELOAD=", STDBUF, "-oL", "sh", "-c", "echo $LD_PRELOAD"],
+        dir.path(),
+    );
+    assert_eq!(env, format!(":{library}\n"));
+}
+
+#[test]
+fn finding_the_library() {
+    let dir = TempDir::new("stdbuf-library");
+    let Some(library) = library() else {
+        return;
+    };
+    let print_preload = ["-oL", "sh", "-c", "echo $LD_PRELOAD"];
+    // Overridden by RATISCAT_LIBSTDBUF
+    let (status, preload, _) = run(
+        "env",
+        &[
+            &["RATISCAT_LIBSTDBUF=/elsewhere/lib.so", STDBUF][..],
+            &print_preload,
+        ]
+        .concat(),
+        dir.path(),
+    );
+    assert_eq!((status, preload.as_str()), (0, "/elsewhere/lib.so\n"));
+    // Installed as bin/stdbuf and lib/ratiscat/libstdbuf.so
+    for name in ["bin", "lib/ratiscat", "other/bin"] {
+        fs::create_dir_all(dir.path().join(name)).unwrap();
+    }
+    fs::copy(STDBUF, dir.path().join("bin/stdbuf")).unwrap();
+    fs::copy(&library, dir.path().join("lib/ratiscat/libstdbuf.so")).unwrap();
+    let installed = dir.path().join("bin/stdbuf");
+    let (status, preload, stderr) = run(installed.to_str().unwrap(), &print_preload, dir.path());
+    let expected = dir.path().join("bin/../lib/ratiscat/libstdbuf.so");
+    assert_eq!(
+        (status, preload, stderr),
+        (0, format!("{}\n", expected.display()), "".into())
+    );
+    // Nowhere to be found
+    fs::copy(STDBUF, dir.path().join("other/bin/stdbuf")).unwrap();
+    let alone = dir.path().join("other/bin/stdbuf");
+    assert_eq!(
+        run(alone.to_str().unwrap(), &print_preload, dir.path()),
+        (
+            125,
+            "".into(),
+            "stdbuf: failed to find 'libstdbuf.so'\n".into()
+        )
+    );
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("stdbuf-errors");
+    assert_eq!(
+        stdbuf(&dir, &["-oL", "sh", "-c", "exit 3"]).0,
+        match library() {
+            Some(_) => 3,
+            None => 125,
+        }
+    );
+    for (args, message) in [
+        (&["-o", "x", "true"][..], "invalid mode 'x'"),
+        (&["-o", "1Q", "true"], "invalid mode '1Q'"),
+        (&["-e", "-1", "true"], "invalid mode '-1'"),
+        (
+            &["-o", "99999999999999999999999", "true"],
+            "invalid mode '99999999999999999999999': Value too large for defined data type",
+        ),
+        (
+            &["-o", "1Y", "true"],
+            "invalid mode '1Y': Value too large for defined data type",
+        ),
+    ] {
+        assert_eq!(
+            stdbuf(&dir, args),
+            (125, "".into(), format!("stdbuf: {message}\n")),
+            "{args:?}"
+        );
+    }
+    if library().is_some() {
+        for (args, status, message) in [
+            (
+                &["-oL", "nosuch"][..],
+                127,
+                "'nosuch': No such file or directory",
+            ),
+            (&["-oL", "."], 126, "'.': Permission denied"),
+        ] {
+            assert_eq!(
+                stdbuf(&dir, args),
+                (
+                    status,
+                    "".into(),
+                    format!("stdbuf: failed to run command {message}\n")
+                ),
+                "{args:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("stdbuf-gnu");
+    if !gnu_stdbuf(&dir) || library().is_none() {
+        return;
+    }
+    for args in [
+        &["-oL", "sh", "-c", "echo out; echo err >&2; exit 3"][..],
+        &["-iL", "true"],
+        &["-o", "x", "true"],
+        &["-o", "99999999999999999999999", "true"],
+        &["-oL"],
+        &["true"],
+        &["-oL", "nosuch"],
+        &["-x"],
+        &["-o"],
+    ] {
+        assert_eq!(
+            stdbuf(&dir, args),
+            run("stdbuf", args, dir.path()),
+            "{args:?}"
+        );
+    }
+    assert!(line_comes_through(&["stdbuf", "-oL", "grep"]));
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("stdbuf-usage");
+    for (args, message) in [
+        (&["-iL", "true"][..], "line buffering stdin is meaningless"),
+        (&["-oL"], "missing operand"),
+        (&["true"], "you must specify a buffering mode option"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-o"], "option requires an argument -- 'o'"),
+        (&["--output"], "option '--output' requires an argument"),
+    ] {
+        let stderr = format!("stdbuf: {message}\nTry 'stdbuf --help' for more information.\n");
+        assert_eq!(stdbuf(&dir, args), (125, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{run, run_with_input, TempDir};
+
+// Binary files, empty, of exactly one 1K block, one 512 byte block and just past 3
+fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(name);
+    dir.write("empty", "");
+    dir.write("k1", (0..1024).map(|i| i as u8).collect::<Vec<u8>>());
+    dir.write("b512", (0..512).map(|i| (i * 7) as u8).collect::<Vec<u8>>());
+    dir.write(
+        "odd",
+        (0..1537).map(|i| (i * 13) as u8).collect::<Vec<u8>>(),
+    );
+    dir
+}
+
+fn sum(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run_with_input(env!("CARGO_BIN_EXE_sum"), args, dir.path(), b"")
+}
+
+// Whether sum on PATH is GNU's, to compare with
+fn gnu_sum(dir: &TempDir) -> bool {
+    let (_, version, _) = run("sum", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+#[test]
+fn bsd() {
+    let dir = setup("sum-bsd");
+    let expected = "00000     0 empty\n02048     1 k1\n42322     1 b512\n22478     2 odd\n";
+    for args in [&[][..], &["-r"], &["--sysv", "-r"]] {
+        let args = [args, &["empty", "k1", "b512", "odd"]].concat();
+        assert_eq!(
+            sum(&dir, &args),
+            (0, expected.into(), "".into()),
+            "{args:?}"
+        );
+    }
+    // No name for stdin
+    let odd = std::fs::read(dir.path().join("odd")).unwrap();
+    assert_eq!(
+        run_with_input(env!("CARGO_BIN_EXE_sum"), &[], dir.path(), &odd),
+        (0, "22478     2\n".into(), "".into())
+    );
+}
+
+#[test]
+fn sysv() {
+    let dir = setup("sum-sysv");
+    let expected = "0 0 empty\n65025 2 k1\n65280 1 b512\n64770 4 odd\n";
+    for args in [&["-s"][..], &["--sysv"], &["-r", "-s"]] {
+        let args = [args, &["empty", "k1", "b512", "odd"]].concat();
+        assert_eq!(
+            sum(&dir, &args),
+            (0, expected.into(), "".into()),
+            "{args:?}"
+        );
+    }
+    let odd = std::fs::read(dir.path().join("odd")).unwrap();
+    assert_eq!(
+        run_with_input(env!("CARGO_BIN_EXE_sum"), &["-s"], dir.path(), &odd),
+        (0, "64770 4\n".into(), "".into())
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = setup("sum-gnu");
+    if !gnu_sum(&dir) {
+        return;
+    }
+    for args in [&[][..], &["-s"]] {
+        for file in ["empty", "k1", "b512", "odd", "nofile"] {
+            let args = [args, &[file]].concat();
+            assert_eq!(sum(&dir, &args), run("sum", &args, dir.path()), "{args:?}");
+        }
+    }
+}
+
+#[test]
+fn errors() {
+    let dir = setup("sum-errors");
+    assert_eq!(
+        sum(&dir, &["k1", "nofile"]),
+        (
+            1,
+            "02048     1 k1\n".into(),
+            "sum: nofile: No such file or directory\n".into()
+        )
+    );
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("sum: {message}\nTry 'sum --help' for more information.\n");
+        assert_eq!(sum(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::{run, TempDir};
+use std::os::unix::fs::symlink;
+
+// `[` is a link to test, installed by hand
+#[test]
+fn bracket_link() {
+    let dir = TempDir::new("test-bracket");
+    let bracket = dir.path().join("[");
+    symlink(env!("CARGO_BIN_EXE_test"), &bracket).unwrap();
+    let bracket = |args: &[&str]| run(bracket.to_str().unwrap(), args, dir.path());
+    assert_eq!(bracket(&["1", "=", "1", "]"]), (0, "".into(), "".into()));
+    assert_eq!(bracket(&["-n", "", "]"]), (1, "".into(), "".into()));
+    assert_eq!(
+        bracket(&["1", "=", "1"]),
+        (2, "".into(), "[: missing ']'\n".into())
+    );
+    assert_eq!(
+        run(
+            env!("CARGO_BIN_EXE_test"),
+            &["1", "=", "1", "]"],
+            dir.path()
+        ),
+        (2, "".into(), "test: extra argument ']'\n".into())
+    );
+}
+
+#[test]
+fn expressions() {
+    let dir = TempDir::new("test-expressions");
+    let cases: &[(&[&str], i32, &str)] = &[
+        // By the number of arguments as POSIX has it, an operator first
+        (&[], 1, ""),
+        (&["!"], 0, ""),
+        (&["-n"], 0, ""),
+        (&["x"], 0, ""),
+        (&[""], 1, ""),
+        (&["!", "x"], 1, ""),
+        (&["!", ""], 0, ""),
+        (&["!", "!"], 1, ""),
+        (&["-n", "x"], 0, ""),
+        (&["-z", "x"], 1, ""),
+        (&["!", "-z", "x"], 0, ""),
+        (&["-n", "-n"], 0, ""),
+        (&["x", "=", "x"], 0, ""),
+        (&["x", "!=", "x"], 1, ""),
+        (&["!", "!", "x"], 0, ""),
+        (&["!", "!", ""], 1, ""),
+        (&["!", "=", "!"], 0, ""),
+        (&["(", "=", "("], 0, ""),
+        (&["-n", "=", "-n"], 0, ""),
+        (&["!", "("], 1, ""),
+        // '!' binds tighter than -a, which binds tighter than -o, parentheses group
+        (&["!", "x", "=", "y"], 0, ""),
+        (&["!", "x", "=", "x"], 1, ""),
+        (&["(", "x", ")"], 0, ""),
+        (&["(", "", ")"], 1, ""),
+        (&["(", "!", "x", ")"], 1, ""),
+        (&["x", "-a", ""], 1, ""),
+        (&["", "-o", "x"], 0, ""),
+        (&["!", "x", "-a", ""], 0, ""),
+        (&["!", "", "-o", ""], 0, ""),
+        (&["x", "-o", "", "-a", ""], 0, ""),
+        (&["", "-a", "x", "-o", "x"], 0, ""),
+        (&["(", "x", "-o", "", ")", "-a", ""], 1, ""),
+        (&["!", "(", "x", "-a", "", ")"], 0, ""),
+        (&["-a", "=", "-a"], 0, ""),
+        (&["!", "-n", "=", "x"], 0, ""),
+        // Integers may have blanks around them, anything else is an error
+        (&["1", "-eq", "1"], 0, ""),
+        (&["1", "-lt", "2"], 0, ""),
+        (&["-1", "-gt", "-2"], 0, ""),
+        (&[" 1", "-eq", "1"], 0, ""),
+        (&["1", "-eq", " 1 "], 0, ""),
+        (&["1.5", "-eq", "1"], 2, "test: invalid integer '1.5'\n"),
+        (&["a", "-eq", "1"], 2, "test: invalid integer 'a'\n"),
+        (&["1", "-eq", ""], 2, "test: invalid integer ''\n"),
+        (&["99999999999999999999", "-gt", "1"], 0, ""),
+        (&["x", "-lt", "1"], 2, "test: invalid integer 'x'\n"),
+        // Malformed expressions
+        (&["(", "x"], 2, "test: missing argument after 'x'\n"),
+        (&["x", ")"], 2, "test: missing argument after ')'\n"),
+        (&["x", "-a"], 2, "test: missing argument after '-a'\n"),
+        (
+            &["(", "x", "-a"],
+            2,
+            "test: 'x': binary operator expected\n",
+        ),
+        (
+            &["1", "-foo", "2"],
+            2,
+            "test: '-foo': binary operator expected\n",
+        ),
+        (&["x", "y", "z"], 2, "test: 'y': binary operator expected\n"),
+        (&["x", "y", "z", "w"], 2, "test: extra argument 'y'\n"),
+    ];
+    for &(args, status, error) in cases {
+        let output = run(env!("CARGO_BIN_EXE_test"), args, dir.path());
+        assert_eq!(output, (status, "".into(), error.into()), "{args:?}");
+    }
+}
+
+#[test]
+fn bracket_without_close() {
+    let dir = TempDir::new("test-bracket-close");
+    let bracket = dir.path().join("[");
+    symlink(env!("CARGO_BIN_EXE_test"), &bracket).unwrap();
+    let bracket = |args: &[&str]| run(bracket.to_str().unwrap(), args, dir.path());
+    let cases: &[(&[&str], i32, &str)] = &[
+        (&[], 2, "[: missing ']'\n"),
+        (&["x"], 2, "[: missing ']'\n"),
+        (&["]"], 1, ""),
+        (&["!", "]"], 0, ""),
+        (&["(", "x", ")", "]"], 0, ""),
+        (&["(", "x", "]"], 2, "[: missing argument after 'x'\n"),
+        (&["x", "]", "]"], 2, "[: missing argument after ']'\n"),
+    ];
+    for &(args, status, error) in cases {
+        assert_eq!(bracket(args), (status, "".into(), error.into()), "{args:?}");
+    }
+}
@@ -0,0 +1,264 @@
+mod common;
+
+use common::{run, TempDir};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: &str = env!("CARGO_BIN_EXE_timeout");
+
+// How `timeout` ended: its status or the signal it died of, output and errors
+#[derive(Debug, PartialEq)]
+enum Ended {
+    Exited(i32),
+    Killed(i32),
+}
+
+fn ended(status: ExitStatus) -> Ended {
+    match status.code() {
+        Some(code) => Ended::Exited(code),
+        None => Ended::Killed(status.signal().unwrap()),
+    }
+}
+
+fn timeout_with(timeout: &str, args: &[&str]) -> (Ended, String, String) {
+    let output = Command::new(timeout)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    (
+        ended(output.status),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn timeout(args: &[&str]) -> (Ended, String, String) {
+    timeout_with(TIMEOUT, args)
+}
+
+// Like `timeout`, asserting it took less than `limit` seconds
+fn timeout_within(limit: f64, args: &[&str]) -> (Ended, String, String) {
+    let start = Instant::now();
+    let result = timeout(args);
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed < Duration::from_secs_f64(limit),
+        "{args:?}: {elapsed:?}"
+    );
+    result
+}
+
+fn exited(code: i32, stderr: &str) -> (Ended, String, String) {
+    (Ended::Exited(code), "".into(), stderr.into())
+}
+
+// Whether timeout on PATH is GNU's, to compare with
+fn gnu_timeout(dir: &TempDir) -> bool {
+    let (_, version, _) = run("timeout", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+// A shell command the timeout signal can't stop
+const IGNORES_TERM: &str = "trap '' TERM; exec sleep 5";
+
+#[test]
+fn finishing_in_time() {
+    assert_eq!(timeout(&["5", "true"]), exited(0, ""));
+    assert_eq!(timeout(&["5", "sh", "-c", "exit 3"]), exited(3, ""));
+    assert_eq!(
+        timeout(&["5", "sh", "-c", "echo out; echo err >&2"]),
+        (Ended::Exited(0), "out\n".into(), "err\n".into())
+    );
+    // 0 disables the limit
+    assert_eq!(timeout(&["0", "sleep", "0.2"]), exited(0, ""));
+    for duration in ["1d", "0.1m", "1h", "1e3s"] {
+        assert_eq!(timeout(&[duration, "true"]), exited(0, ""), "{duration}");
+    }
+}
+
+#[test]
+fn timing_out() {
+    for duration in ["0.2", "0.2s", "2e-1", ".2"] {
+        assert_eq!(
+            timeout_within(3.0, &[duration, "sleep", "5"]),
+            exited(124, ""),
+            "{duration}"
+        );
+    }
+    assert_eq!(
+        timeout_within(3.0, &["-v", "0.2", "sleep", "5"]),
+        exited(124, "timeout: sending signal TERM to command 'sleep'\n")
+    );
+    for signal in ["INT", "SIGINT", "2"] {
+        assert_eq!(
+            timeout_within(3.0, &["-v", "-s", signal, "0.2", "sleep", "5"]),
+            exited(124, "timeout: sending signal INT to command 'sleep'\n"),
+            "{signal}"
+        );
+    }
+    // The whole process group is signalled, the command's children too
+    let script = "(sleep 2; echo survived) & wait";
+    assert_eq!(
+        timeout_within(1.5, &["0.2", "sh", "-c", script]),
+        exited(124, "")
+    );
+    // But only the command with --foreground
+    assert_eq!(
+        timeout(&["--foreground", "0.2", "sh", "-c", script]),
+        (Ended::Exited(124), "survived\n".into(), "".into())
+    );
+}
+
+#[test]
+fn kill_after() {
+    // Still running after TERM, then killed along with timeout itself
+    for args in [
+        &["-k", "0.3", "0.2"][..],
+        &["--kill-after=0.3", "0.2"],
+        &["--preserve-status", "-k", "0.3", "0.2"],
+    ] {
+        let args = [args, &["sh", "-c", IGNORES_TERM]].concat();
+        assert_eq!(
+            timeout_within(3.0, &args),
+            (Ended::Killed(libc::SIGKILL), "".into(), "".into()),
+            "{args:?}"
+        );
+    }
+    assert_eq!(
+        timeout_within(3.0, &["-v", "-k", "0.3", "0.2", "sh", "-c", IGNORES_TERM]),
+        (
+            Ended::Killed(libc::SIGKILL),
+            "".into(),
+            "timeout: sending signal TERM to command 'sh'\n\
+             timeout: sending signal KILL to command 'sh'\n"
+                .into()
+        )
+    );
+    // With --foreground only the command is killed, which is then the status
+    assert_eq!(
+        timeout_within(
+            3.0,
+            &["--foreground", "-k", "0.3", "0.2", "sh", "-c", IGNORES_TERM]
+        ),
+        exited(137, "")
+    );
+    // Not needed when TERM does it
+    assert_eq!(
+        timeout_within(3.0, &["-k", "0.3", "0.2", "sleep", "5"]),
+        exited(124, "")
+    );
+}
+
+#[test]
+fn preserve_status() {
+    // 128 + the signal the command died of, not 124
+    assert_eq!(
+        timeout_within(3.0, &["--preserve-status", "0.2", "sleep", "5"]),
+        exited(128 + libc::SIGTERM, "")
+    );
+    assert_eq!(
+        timeout_within(
+            3.0,
+            &["--preserve-status", "-s", "INT", "0.2", "sleep", "5"]
+        ),
+        exited(128 + libc::SIGINT, "")
+    );
+    // Or whatever it exited with having caught it
+    let script = "trap 'exit 9' TERM; sleep 5 & wait";
+    assert_eq!(
+        timeout_within(3.0, &["--preserve-status", "0.2", "sh", "-c", script]),
+        exited(9, "")
+    );
+    assert_eq!(
+        timeout(&["--preserve-status", "5", "sh", "-c", "exit 3"]),
+        exited(3, "")
+    );
+}
+
+#[test]
+fn signals() {
+    // A command killed by a signal before the time limit kills timeout the same way
+    assert_eq!(
+        timeout(&["5", "sh", "-c", "kill -USR1 $$"]),
+        (Ended::Killed(libc::SIGUSR1), "".into(), "".into())
+    );
+    // Signals sent to timeout are passed on to the command
+    for sig in [libc::SIGTERM, libc::SIGINT, libc::SIGHUP] {
+        let start = Instant::now();
+        let child = Command::new(TIMEOUT)
+            .args(["10", "sleep", "10"])
+            .spawn()
+            .unwrap();
+        sleep(Duration::from_millis(200));
+        unsafe { libc::kill(child.id() as i32, sig) };
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(ended(output.status), Ended::Killed(sig));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("timeout-gnu");
+    if !gnu_timeout(&dir) {
+        return;
+    }
+    for args in [
+        &["5", "sh", "-c", "exit 3"][..],
+        &["0.2", "sleep", "5"],
+        &["-v", "-s", "HUP", "0.2", "sleep", "5"],
+        &["--preserve-status", "0.2", "sleep", "5"],
+        &["-v", "-k", "0.3", "0.2", "sh", "-c", IGNORES_TERM],
+        &["--foreground", "-k", "0.3", "0.2", "sh", "-c", IGNORES_TERM],
+        &["5", "sh", "-c", "kill -USR1 $$"],
+        &["1", "nosuch"],
+        &["x", "true"],
+        &["-s", "RTMIN+1", "1", "true"],
+        &["-s", "FOO", "1", "true"],
+        &["1"],
+        &["-x"],
+    ] {
+        assert_eq!(timeout(args), timeout_with("timeout", args), "{args:?}");
+    }
+}
+
+#[test]
+fn errors() {
+    let try_help = "Try 'timeout --help' for more information.\n";
+    for (args, status, message) in [
+        (
+            &["1", "nosuch"][..],
+            127,
+            "failed to run command 'nosuch': No such file or directory",
+        ),
+        (
+            &["1", "."],
+            126,
+            "failed to run command '.': Permission denied",
+        ),
+    ] {
+        let stderr = format!("timeout: {message}\n");
+        assert_eq!(timeout(args), exited(status, &stderr), "{args:?}");
+    }
+    for (args, message) in [
+        (&["x", "true"][..], "invalid time interval 'x'"),
+        (&["-1", "true"], "invalid option -- '1'"),
+        (&["--", "-1", "true"], "invalid time interval '-1'"),
+        (&["-k", "x", "1", "true"], "invalid time interval 'x'"),
+        (&["-s", "FOO", "1", "true"], "'FOO': invalid signal"),
+        (&["-s", "RTMAX+1", "1", "true"], "'RTMAX+1': invalid signal"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+        (&["-s"], "option requires an argument -- 's'"),
+    ] {
+        let stderr = format!("timeout: {message}\n{try_help}");
+        assert_eq!(timeout(args), exited(125, &stderr), "{args:?}");
+    }
+    // Just where to look for help without a command
+    for args in [&[][..], &["1"]] {
+        assert_eq!(timeout(args), exited(125, try_help), "{args:?}");
+    }
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::{run, run_with_input, TempDir};
+
+fn tsort(dir: &TempDir, args: &[&str], input: &str) -> (i32, String, String) {
+    run_with_input(
+        env!("CARGO_BIN_EXE_tsort"),
+        args,
+        dir.path(),
+        input.as_bytes(),
+    )
+}
+
+// Whether tsort on PATH is GNU's, to compare with
+fn gnu_tsort(dir: &TempDir) -> bool {
+    let (_, version, _) = run("tsort", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+fn ok(stdout: &str) -> (i32, String, String) {
+    (0, stdout.into(), "".into())
+}
+
+// The loop report of GNU for `members`
+fn looped(file: &str, members: &[&str]) -> String {
+    let mut report = format!("tsort: {file}: input contains a loop:\n");
+    for member in members {
+        report += &format!("tsort: {member}\n");
+    }
+    report
+}
+
+#[test]
+fn dags() {
+    let dir = TempDir::new("tsort-dags");
+    // Of the orders possible, GNU's
+    assert_eq!(
+        tsort(&dir, &[], "a b b c a c d c e e"),
+        ok("a\nd\ne\nb\nc\n")
+    );
+    assert_eq!(
+        tsort(
+            &dir,
+            &[],
+            "main parse main eval parse lex eval lex lex util"
+        ),
+        ok("main\neval\nparse\nlex\nutil\n")
+    );
+    // Any whitespace between tokens, pairs across lines
+    assert_eq!(
+        tsort(&dir, &[], "\tb\n\n c  a\nd\n\nb\n a"),
+        ok("b\na\nc\nd\n")
+    );
+    // A pair of the same node declares it
+    assert_eq!(tsort(&dir, &[], "x x"), ok("x\n"));
+    assert_eq!(tsort(&dir, &[], "y y x x"), ok("x\ny\n"));
+    assert_eq!(tsort(&dir, &[], ""), ok(""));
+    // From a file, - being stdin
+    dir.write("pairs", "1 2\n2 3\n");
+    assert_eq!(tsort(&dir, &["pairs"], ""), ok("1\n2\n3\n"));
+    assert_eq!(tsort(&dir, &["-"], "3 2"), ok("3\n2\n"));
+    assert_eq!(tsort(&dir, &["--", "-"], "3 2"), ok("3\n2\n"));
+}
+
+#[test]
+fn loops() {
+    let dir = TempDir::new("tsort-loops");
+    // Each loop reported, all the nodes still printed
+    assert_eq!(
+        tsort(&dir, &[], "a b b a c d d c e f"),
+        (
+            1,
+            "e\nf\na\nb\nc\nd\n".into(),
+            looped("-", &["a", "b"]) + &looped("-", &["c", "d"])
+        )
+    );
+    // Two loops through the same node
+    assert_eq!(
+        tsort(&dir, &[], "a b b c c a c d d e e c f g"),
+        (
+            1,
+            "f\ng\nd\ne\na\nb\nc\n".into(),
+            looped("-", &["d", "e", "c"]) + &looped("-", &["a", "b", "c"])
+        )
+    );
+    dir.write("cycle", "x y\ny z\nz x\n");
+    assert_eq!(
+        tsort(&dir, &["cycle"], ""),
+        (1, "x\ny\nz\n".into(), looped("cycle", &["x", "y", "z"]))
+    );
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("tsort-gnu");
+    if !gnu_tsort(&dir) {
+        return;
+    }
+    // Random graphs from a fixed seed, some with loops
+    let mut seed: u64 = 1;
+    let mut random = |n: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % n
+    };
+    for nodes in [3, 5, 10, 30, 100] {
+        for _ in 0..20 {
+            let pairs = random(nodes * 2) + 1;
+            let mut input = String::new();
+            for _ in 0..pairs {
+                let (from, to) = (random(nodes), random(nodes));
+                // Mostly forward, so not everything is one big loop
+                let (from, to) = match random(8) {
+                    0 => (from.max(to), from.min(to)),
+                    _ => (from.min(to), from.max(to)),
+                };
+                input += &format!("n{from} n{to}\n");
+            }
+            let gnu = run_with_input("tsort", &[], dir.path(), input.as_bytes());
+            assert_eq!(tsort(&dir, &[], &input), gnu, "{input}");
+        }
+    }
+    for args in [&["nofile"][..], &["a", "b"], &["-x"]] {
+        assert_eq!(
+            tsort(&dir, args, ""),
+            run("tsort", args, dir.path()),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn errors() {
+    let dir = TempDir::new("tsort-errors");
+    assert_eq!(
+        tsort(&dir, &[], "a b c"),
+        (
+            1,
+            "".into(),
+            "tsort: -: input contains an odd number of tokens\n".into()
+        )
+    );
+    dir.write("odd", "a b\nc\n");
+    assert_eq!(
+        tsort(&dir, &["odd"], ""),
+        (
+            1,
+            "".into(),
+            "tsort: odd: input contains an odd number of tokens\n".into()
+        )
+    );
+    assert_eq!(
+        tsort(&dir, &["nofile"], ""),
+        (
+            1,
+            "".into(),
+            "tsort: nofile: No such file or directory\n".into()
+        )
+    );
+    assert_eq!(
+        tsort(&dir, &["."], ""),
+        (
+            1,
+            "".into(),
+            "tsort: .: read error: Is a directory\n".into()
+        )
+    );
+    for (args, message) in [
+        (&["a", "b"][..], "extra operand 'b'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("tsort: {message}\nTry 'tsort --help' for more information.\n");
+        assert_eq!(tsort(&dir, args, ""), (1, "".into(), stderr), "{args:?}");
+    }
+}
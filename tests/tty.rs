@@ -0,0 +1,98 @@
+mod common;
+
+use common::{pty, read_pty, run_with_input, TempDir};
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::process::{Command, Stdio};
+
+// tty run with `stdin` and `stdout`, its status, output and errors
+fn tty(args: &[&str], stdin: impl Into<Stdio>, stdout: impl Into<Stdio>) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_tty"))
+        .args(args)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn terminal() {
+    let (master, terminal) = pty(24);
+    let name = nix::unistd::ttyname(terminal.as_raw_fd()).unwrap();
+    let stdin = terminal.try_clone().unwrap();
+    assert_eq!(
+        tty(&[], stdin, Stdio::piped()),
+        (0, format!("{}\n", name.display()), "".into())
+    );
+    assert_eq!(
+        tty(&["-s"], terminal, Stdio::piped()),
+        (0, "".into(), "".into())
+    );
+    assert_eq!(read_pty(master), "");
+}
+
+#[test]
+fn not_a_tty() {
+    let dir = TempDir::new("tty-pipe");
+    let tty_piped =
+        |args: &[&str]| run_with_input(env!("CARGO_BIN_EXE_tty"), args, dir.path(), b"");
+    assert_eq!(tty_piped(&[]), (1, "not a tty\n".into(), "".into()));
+    for args in [&["-s"][..], &["--silent"], &["--quiet"]] {
+        assert_eq!(tty_piped(args), (1, "".into(), "".into()), "{args:?}");
+    }
+    let null = File::open("/dev/null").unwrap();
+    assert_eq!(
+        tty(&[], null, Stdio::piped()),
+        (1, "not a tty\n".into(), "".into())
+    );
+}
+
+#[test]
+fn write_error() {
+    let full = || OpenOptions::new().write(true).open("/dev/full").unwrap();
+    let (_master, terminal) = pty(24);
+    assert_eq!(
+        tty(&[], terminal, full()),
+        (
+            3,
+            "".into(),
+            "tty: write error: No space left on device\n".into()
+        )
+    );
+    // Not a tty is still 3 when it can't be said
+    assert_eq!(
+        tty(&[], Stdio::null(), full()),
+        (
+            3,
+            "".into(),
+            "tty: write error: No space left on device\n".into()
+        )
+    );
+    // Nothing written with -s
+    assert_eq!(
+        tty(&["-s"], Stdio::null(), full()),
+        (1, "".into(), "".into())
+    );
+}
+
+#[test]
+fn usage_errors() {
+    for (args, message) in [
+        (&["a"][..], "extra operand 'a'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("tty: {message}\nTry 'tty --help' for more information.\n");
+        assert_eq!(
+            tty(args, Stdio::null(), Stdio::piped()),
+            (2, "".into(), stderr),
+            "{args:?}"
+        );
+    }
+}
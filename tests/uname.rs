@@ -0,0 +1,92 @@
+mod common;
+
+use common::{run, TempDir};
+use nix::sys::utsname::uname as utsname;
+
+fn uname(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_uname"), args, dir.path())
+}
+
+// Whether uname on PATH is GNU's, to compare with
+fn gnu_uname(dir: &TempDir) -> bool {
+    let (_, version, _) = run("uname", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+#[test]
+fn fields() {
+    let dir = TempDir::new("uname-fields");
+    let uts = utsname().unwrap();
+    let field = |s: &std::ffi::OsStr| s.to_str().unwrap().to_string();
+    let (s, n, r, v, m) = (
+        field(uts.sysname()),
+        field(uts.nodename()),
+        field(uts.release()),
+        field(uts.version()),
+        field(uts.machine()),
+    );
+    let line = |fields: &[&str]| (0, fields.join(" ") + "\n", "".into());
+    for (args, expected) in [
+        (&[][..], line(&[&s])),
+        (&["-s"], line(&[&s])),
+        (&["-n"], line(&[&n])),
+        (&["--kernel-release"], line(&[&r])),
+        (&["-v"], line(&[&v])),
+        (&["-m"], line(&[&m])),
+        (&["-p"], line(&["unknown"])),
+        (&["-i"], line(&["unknown"])),
+        (&["-o"], line(&["GNU/Linux"])),
+        // In the canonical order whatever the order of the options
+        (&["-m", "-s"], line(&[&s, &m])),
+        (&["-om", "-n"], line(&[&n, &m, "GNU/Linux"])),
+        (&["-i", "-p", "-r"], line(&[&r, "unknown", "unknown"])),
+        // -a leaving out the unknown -p and -i, even when they're asked for
+        (&["-a"], line(&[&s, &n, &r, &v, &m, "GNU/Linux"])),
+        (
+            &["-a", "-p", "-i"],
+            line(&[&s, &n, &r, &v, &m, "GNU/Linux"]),
+        ),
+        (&["-snrvmo"], line(&[&s, &n, &r, &v, &m, "GNU/Linux"])),
+    ] {
+        assert_eq!(uname(&dir, args), expected, "{args:?}");
+    }
+}
+
+#[test]
+fn gnu_compatible() {
+    let dir = TempDir::new("uname-gnu");
+    if !gnu_uname(&dir) {
+        return;
+    }
+    for args in [
+        &[][..],
+        &["-a"],
+        &["-p"],
+        &["-i"],
+        &["-o", "-s"],
+        &["-a", "-p"],
+        &["-pi"],
+        &["-x"],
+        &["extra"],
+    ] {
+        assert_eq!(
+            uname(&dir, args),
+            run("uname", args, dir.path()),
+            "{args:?}"
+        );
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("uname-usage");
+    for (args, message) in [
+        (&["extra"][..], "extra operand 'extra'"),
+        (&["-a", "b", "c"], "extra operand 'b'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("uname: {message}\nTry 'uname --help' for more information.\n");
+        assert_eq!(uname(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{run, run_with_input, TempDir};
+
+fn unexpand(dir: &TempDir, args: &[&str], input: &str) -> (i32, String, String) {
+    run_with_input(
+        env!("CARGO_BIN_EXE_unexpand"),
+        args,
+        dir.path(),
+        input.as_bytes(),
+    )
+}
+
+// Whether unexpand on PATH is GNU's, to compare with
+fn gnu_unexpand(dir: &TempDir) -> bool {
+    let (_, version, _) = run("unexpand", &["--version"], dir.path());
+    version.contains("GNU coreutils")
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("unexpand-usage");
+    for (args, message) in [
+        (&["-x"][..], "invalid option -- 'x'"),
+        (&["--foo=1"], "unrecognized option '--foo=1'"),
+        (&["-t"], "option requires an argument -- 't'"),
+        (&["--tabs"], "option '--tabs' requires an argument"),
+    ] {
+        let stderr = format!("unexpand: {message}\nTry 'unexpand --help' for more information.\n");
+        assert_eq!(unexpand(&dir, args, ""), (1, "".into(), stderr), "{args:?}");
+    }
+}
+
+#[test]
+fn expand_round_trip() {
+    let dir = TempDir::new("unexpand-round-trip");
+    let text = "a\tb\tc\n\tx\n\t\ty  z\n";
+    dir.write("text", text);
+    // Only the leading blanks with --first-only, a lone space before a stop kept
+    let first_only = "a b  c\n\tx\n\t\ty  z\n";
+    for (tabs, all, expected) in [
+        (None, "-a", text),
+        (Some("-t4"), "-a", text),
+        (Some("-t2,5,9"), "--first-only", first_only),
+    ] {
+        let args: Vec<&str> = tabs.into_iter().collect();
+        let expand = run(
+            env!("CARGO_BIN_EXE_expand"),
+            &[&args[..], &["text"]].concat(),
+            dir.path(),
+        );
+        let args = [&args[..], &[all]].concat();
+        let unexpanded = unexpand(&dir, &args, &expand.1);
+        assert_eq!(unexpanded, (0, expected.into(), "".into()), "{args:?}");
+        if gnu_unexpand(&dir) {
+            let gnu = run_with_input("unexpand", &args, dir.path(), expand.1.as_bytes());
+            assert_eq!(unexpanded, gnu, "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{run, TempDir};
+use std::fs;
+use std::os::unix::fs::symlink;
+
+fn unlink(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_unlink"), args, dir.path())
+}
+
+#[test]
+fn removes() {
+    let dir = TempDir::new("unlink-removes");
+    dir.write("file", "contents\n");
+    assert_eq!(unlink(&dir, &["file"]), (0, "".into(), "".into()));
+    assert!(!dir.path().join("file").exists());
+
+    // The link itself, not its target
+    dir.write("target", "contents\n");
+    symlink("target", dir.path().join("link")).unwrap();
+    symlink("nowhere", dir.path().join("dangling")).unwrap();
+    for link in ["link", "dangling"] {
+        assert_eq!(unlink(&dir, &[link]), (0, "".into(), "".into()));
+        assert!(fs::symlink_metadata(dir.path().join(link)).is_err());
+    }
+    assert!(dir.path().join("target").exists());
+    // Whatever the name is, an option-like one after --
+    dir.write("-f", "");
+    assert_eq!(unlink(&dir, &["--", "-f"]), (0, "".into(), "".into()));
+    assert!(!dir.path().join("-f").exists());
+}
+
+#[test]
+fn failures() {
+    let dir = TempDir::new("unlink-failures");
+    fs::create_dir(dir.path().join("dir")).unwrap();
+    assert_eq!(
+        unlink(&dir, &["nofile"]),
+        (
+            1,
+            "".into(),
+            "unlink: cannot unlink 'nofile': No such file or directory\n".into()
+        )
+    );
+    // A directory goes to the syscall too, Linux saying EISDIR where others say EPERM
+    let (status, stdout, stderr) = unlink(&dir, &["dir"]);
+    assert_eq!((status, stdout.as_str()), (1, ""));
+    assert!(
+        stderr == "unlink: cannot unlink 'dir': Is a directory\n"
+            || stderr == "unlink: cannot unlink 'dir': Operation not permitted\n",
+        "{stderr}"
+    );
+    assert!(dir.path().join("dir").is_dir());
+    assert_eq!(
+        unlink(&dir, &["nodir/file"]),
+        (
+            1,
+            "".into(),
+            "unlink: cannot unlink 'nodir/file': No such file or directory\n".into()
+        )
+    );
+    // Quoted for the shell
+    assert_eq!(
+        unlink(&dir, &["it's"]),
+        (
+            1,
+            "".into(),
+            "unlink: cannot unlink \"it's\": No such file or directory\n".into()
+        )
+    );
+
+    let (_, version, _) = run("unlink", &["--version"], dir.path());
+    if version.contains("GNU coreutils") {
+        for args in [
+            &["nofile"][..],
+            &["dir"],
+            &["dir/"],
+            &["it's"],
+            &[],
+            &["a", "b"],
+            &["-x"],
+        ] {
+            assert_eq!(
+                unlink(&dir, args),
+                run("unlink", args, dir.path()),
+                "{args:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("unlink-usage");
+    dir.write("a", "");
+    for (args, message) in [
+        (&[][..], "missing operand"),
+        (&["a", "b"], "extra operand 'b'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("unlink: {message}\nTry 'unlink --help' for more information.\n");
+        assert_eq!(unlink(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+    // Nothing removed when there's an extra operand
+    assert!(dir.path().join("a").exists());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{run, TempDir};
+use nix::unistd::{geteuid, User};
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+fn whoami(dir: &TempDir, args: &[&str]) -> (i32, String, String) {
+    run(env!("CARGO_BIN_EXE_whoami"), args, dir.path())
+}
+
+#[test]
+fn name() {
+    let dir = TempDir::new("whoami-name");
+    let user = User::from_uid(geteuid()).unwrap().unwrap();
+    assert_eq!(
+        whoami(&dir, &[]),
+        (0, format!("{}\n", user.name), "".into())
+    );
+}
+
+#[test]
+fn unknown_uid() {
+    if !geteuid().is_root() {
+        return;
+    }
+    // A uid without a passwd entry, as containers often run as
+    let uid = (54321..)
+        .find(|&uid| User::from_uid(uid.into()).unwrap().is_none())
+        .unwrap();
+    // Copied out of the target directory, which may not be theirs to search
+    let dir = TempDir::new("whoami-unknown");
+    let whoami = dir.path().join("whoami");
+    fs::copy(env!("CARGO_BIN_EXE_whoami"), &whoami).unwrap();
+    let output = Command::new(whoami)
+        .uid(uid)
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("whoami: cannot find name for user ID {uid}\n")
+    );
+}
+
+#[test]
+fn usage_errors() {
+    let dir = TempDir::new("whoami-usage");
+    for (args, message) in [
+        (&["extra"][..], "extra operand 'extra'"),
+        (&["-x"], "invalid option -- 'x'"),
+        (&["--foo"], "unrecognized option '--foo'"),
+    ] {
+        let stderr = format!("whoami: {message}\nTry 'whoami --help' for more information.\n");
+        assert_eq!(whoami(&dir, args), (1, "".into(), stderr), "{args:?}");
+    }
+}